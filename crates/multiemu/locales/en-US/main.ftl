@@ -0,0 +1,108 @@
+# Every string the built in UI shows, in Fluent syntax (https://projectfluent.org)
+#
+# Translations are a copy of this file under locales/<language>/main.ftl, or dropped into the locales folder of the
+# storage directory as <language>.ftl to try them out without rebuilding. Anything a translation leaves out falls
+# back to the English here
+
+## Shared dialog buttons
+
+dialog-ok = Ok
+dialog-continue = Continue
+no-machine-running = No machine is running
+
+## Launcher sections
+
+menu-main = Main
+menu-file-browser = File Browser
+menu-options = Options
+menu-input = Input
+menu-database = Database
+menu-debug = Debug
+menu-resume = Resume
+
+file-browser-sorting = Sorting
+file-browser-sort-name = Name
+file-browser-sort-date = Date
+
+## Settings
+
+options-save-config = Save Config
+options-language = Language
+options-language-system = System Default
+options-graphics-setting = Graphics Setting
+options-vsync = VSync
+options-variable-refresh-rate = Variable Refresh Rate
+options-stats-overlay = Stats Overlay
+options-audio-host = Audio Host
+options-audio-buffer-size = Audio Buffer Size (samples)
+options-audio-latency = Audio Latency (ms)
+options-av-sync = A/V Sync
+options-av-offset = A/V Offset (ms)
+options-watchdog-budget = Watchdog Budget (ms, 0 turns it off)
+options-display-scaling = Display Scaling
+options-scaler-filter = Scaler Filter
+options-color-blind-filter = Color Blind Filter
+options-high-contrast = High Contrast Menu
+options-fullscreen-mode = Fullscreen Mode
+options-start-fullscreen = Start Fullscreen
+options-window-sizing = Window Sizing
+options-fast-boot = Skip Firmware (applies to the next game started)
+
+input-no-controller-ports = This machine has no controller ports
+input-port = Port { $port }
+input-port-kind = Port { $port } ({ $kind })
+input-unplugged = Unplugged
+
+database-watch-folders = Watch Folders
+database-watch = Watch { $folder }
+database-remove = Remove
+database-recently-added = Recently Added
+database-unknown-rom = Unknown
+
+debug-log-level = Log Level
+debug-audio-stats = Audio underruns: { $underruns }, overruns: { $overruns }
+debug-reset = Reset
+debug-save-bug-report = Save Bug Report
+debug-bug-report-without-state = Leave Out Save State
+debug-text-output-save = Save to File
+debug-text-output-clear = Clear
+
+## Error dialogs
+
+load-error-title = Could Not Load ROM
+rom-problems-title = ROM Problems
+rom-problems-missing-optional = Some ROMs could not be found, the machine may not behave correctly
+rom-problems-missing-required = Some ROMs the machine requires could not be found
+rom-problems-mismatch = Some ROMs do not match the database
+watchdog-title = Machine Stopped Responding
+watchdog-explanation = A part of the machine ran far longer than it should, so the machine was stopped. This is a bug in the emulator, please report it with the details below
+watchdog-keep-waiting = Keep Waiting
+crash-title = Machine Crashed
+crash-explanation = The emulator hit a bug while running the machine, so the machine was closed. A crash report was saved, please attach it when reporting this
+
+## Pause menu
+
+pause-title = Paused
+pause-resume = Resume
+pause-reset = Reset
+pause-save-state = Save State
+pause-load-state = Load State
+pause-export-archive = Export Session Archive
+pause-settings = Settings
+pause-tape-play = Play Tape
+pause-tape-stop = Stop Tape
+pause-tape-rewind = Rewind Tape
+pause-tape-fast-load = Fast Load From Tape
+pause-quit-to-launcher = Quit to Launcher
+
+## On screen display
+
+progress-count = { $label } ({ $done }/{ $total })
+progress-cancel = Cancel
+progress-cancelling = Cancelling
+
+overlay-speed = Speed: { $speed }%
+overlay-fps = FPS: { $fps }
+overlay-device = Device: { $device }
+overlay-audio-buffer = Audio buffer { $fill }%
+overlay-av-skew = A/V skew: { $skew } ms
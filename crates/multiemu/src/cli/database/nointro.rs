@@ -1,13 +1,14 @@
 use crate::{
     config::GLOBAL_CONFIG,
-    rom::{id::RomId, info::RomInfo, manager::RomManager, system::GameSystem},
+    rom::{id::RomId, info::RomInfo, manager::RomManager, region::RomRegion, system::GameSystem},
+    runtime::progress::PROGRESS,
 };
 use clap::Subcommand;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::Deserialize;
 use serde_with::serde_as;
 use serde_with::DisplayFromStr;
-use std::{error::Error, fs::File, io::BufReader, path::PathBuf};
+use std::{collections::HashMap, error::Error, fs::File, io::BufReader, path::PathBuf};
 
 #[derive(Clone, Debug, Subcommand)]
 pub enum NoIntroAction {
@@ -38,6 +39,8 @@ struct Header {
 struct Machine {
     #[serde(rename = "@name")]
     name: String,
+    #[serde(rename = "@cloneof")]
+    clone_of: Option<String>,
     description: String,
     rom: Rom,
 }
@@ -61,10 +64,15 @@ struct Rom {
 pub fn database_nointro_import(files: Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
     let global_config_guard = GLOBAL_CONFIG.try_read()?;
     let rom_manager = RomManager::new(Some(&global_config_guard.database_file))?;
+    let progress = PROGRESS.begin("Importing No-Intro DATs", Some(files.len() as u64));
 
     files
         .into_par_iter()
         .try_for_each(|path| {
+            if progress.is_cancelled() {
+                return Ok(());
+            }
+
             let file = BufReader::new(File::open(&path)?);
 
             // Parse XML based data file
@@ -76,6 +84,7 @@ pub fn database_nointro_import(files: Vec<PathBuf>) -> Result<(), Box<dyn std::e
                         path.display(),
                         err
                     );
+                    progress.advance(1);
                     return Ok(());
                 }
             };
@@ -87,16 +96,30 @@ pub fn database_nointro_import(files: Vec<PathBuf>) -> Result<(), Box<dyn std::e
                 data_file.header.name
             );
 
+            // Clones refer to their parent by name, not hash
+            let ids_by_name: HashMap<_, _> = data_file
+                .machine
+                .iter()
+                .map(|entry| (entry.name.clone(), entry.rom.id))
+                .collect();
+
             let database_transaction = rom_manager.rom_information.rw_transaction()?;
             for entry in data_file.machine {
+                let parent = entry
+                    .clone_of
+                    .as_ref()
+                    .and_then(|parent| ids_by_name.get(parent).copied());
+
                 database_transaction.upsert(RomInfo {
+                    regions: RomRegion::from_nointro_name(&entry.name),
                     name: Some(entry.name),
                     id: entry.rom.id,
                     system: data_file.header.name,
-                    region: None,
+                    parent,
                 })?;
             }
             database_transaction.commit()?;
+            progress.advance(1);
 
             Ok(())
         })
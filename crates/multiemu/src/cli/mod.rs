@@ -1,14 +1,20 @@
+use crate::{
+    logging::{self, LogLevel},
+    machine::capture::FrameCapture,
+};
 use clap::{Parser, Subcommand, ValueEnum};
 use database::{
     native::{database_native_import, NativeAction},
     nointro::{database_nointro_import, NoIntroAction},
     DatabaseAction,
 };
-use rom::{import::rom_import, run::rom_run, RomAction};
+use rom::{capture::rom_capture, import::rom_import, run::rom_run, RomAction};
+use save::{export::save_export, import::save_import, sync::save_sync, SaveAction};
 use std::error::Error;
 
 pub mod database;
 pub mod rom;
+pub mod save;
 
 // pub mod run_rom;
 
@@ -22,6 +28,16 @@ pub enum DatabaseType {
 pub struct Cli {
     #[clap(subcommand)]
     pub action: Option<CliAction>,
+    /// Log level for this run, either "level" or "module=level", can be given several times
+    ///
+    /// These go over the configured levels without being saved
+    #[clap(long = "log", global = true, value_parser = parse_log_directive)]
+    pub log_directives: Vec<(Option<String>, LogLevel)>,
+}
+
+fn parse_log_directive(directive: &str) -> Result<(Option<String>, LogLevel), String> {
+    logging::parse_directive(directive)
+        .ok_or_else(|| format!("{} is not a valid log level", directive))
 }
 
 #[derive(Clone, Debug, Subcommand)]
@@ -36,6 +52,11 @@ pub enum CliAction {
         #[clap(subcommand)]
         action: RomAction,
     },
+    #[command(about = Some("Commands relating to save files"))]
+    Save {
+        #[clap(subcommand)]
+        action: SaveAction,
+    },
 }
 
 pub fn handle_cli(cli_action: CliAction) -> Result<(), Box<dyn Error>> {
@@ -63,6 +84,38 @@ pub fn handle_cli(cli_action: CliAction) -> Result<(), Box<dyn Error>> {
             } => {
                 rom_run(roms, forced_system)?;
             }
+            RomAction::Capture {
+                roms,
+                forced_system,
+                frame,
+                screenshot,
+                state,
+            } => {
+                rom_capture(
+                    roms,
+                    forced_system,
+                    FrameCapture {
+                        frame,
+                        screenshot,
+                        state,
+                    },
+                )?;
+            }
+            #[cfg(feature = "test-rom-download")]
+            RomAction::DownloadTests { suites } => {
+                rom::download_tests::rom_download_tests(suites)?;
+            }
+        },
+        CliAction::Save { action } => match action {
+            SaveAction::Import { paths, rom } => {
+                save_import(paths, rom)?;
+            }
+            SaveAction::Export { rom, destination } => {
+                save_export(rom, destination)?;
+            }
+            SaveAction::Sync { prefer } => {
+                save_sync(prefer)?;
+            }
         },
     }
 
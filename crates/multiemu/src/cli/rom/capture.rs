@@ -0,0 +1,37 @@
+use super::{run::resolve_roms, RomSpecification};
+use crate::{
+    machine::{capture::FrameCapture, Machine},
+    rom::{info::RomInfo, system::GameSystem},
+};
+use std::{error::Error, sync::Arc};
+
+pub fn rom_capture(
+    roms: Vec<RomSpecification>,
+    forced_system: Option<GameSystem>,
+    capture: FrameCapture,
+) -> Result<(), Box<dyn Error>> {
+    let (rom_manager, user_specified_roms) = resolve_roms(roms, forced_system)?;
+
+    let system = match forced_system {
+        Some(system) => system,
+        None => rom_manager
+            .rom_information
+            .r_transaction()?
+            .get()
+            .primary::<RomInfo>(user_specified_roms[0])?
+            .map(|info| info.system)
+            .ok_or("Could not figure out which system the ROM is for")?,
+    };
+
+    let mut machine = Machine::from_system(user_specified_roms, Arc::new(rom_manager), system);
+    if !machine.bootable() {
+        return Err("Machine is missing ROMs it needs to start".into());
+    }
+    // Not the user playing
+    machine.play_session = None;
+    machine.render_in_software();
+
+    machine.capture_at(&capture)?;
+
+    Ok(())
+}
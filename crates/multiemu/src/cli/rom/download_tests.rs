@@ -0,0 +1,35 @@
+use crate::{
+    config::GLOBAL_CONFIG,
+    rom::{
+        manager::RomManager,
+        test_roms::{find_suite, TEST_ROM_SUITES},
+    },
+};
+use std::error::Error;
+
+pub fn rom_download_tests(suites: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+    let rom_manager = RomManager::new(Some(&global_config_guard.database_file))?;
+
+    let suites = if suites.is_empty() {
+        TEST_ROM_SUITES.iter().collect()
+    } else {
+        suites
+            .iter()
+            .map(|name| find_suite(name))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    for suite in suites {
+        tracing::info!(
+            "Fetching test ROM suite {} (licensed {})",
+            suite.name,
+            suite.license
+        );
+
+        let roms = rom_manager.download_test_roms(suite, &global_config_guard.roms_directory)?;
+        tracing::info!("{} ROMs from {} are in the library", roms.len(), suite.name);
+    }
+
+    Ok(())
+}
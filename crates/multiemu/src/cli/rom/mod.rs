@@ -2,6 +2,9 @@ use crate::rom::{id::RomId, system::GameSystem};
 use clap::{Subcommand, ValueEnum};
 use std::{error::Error, path::PathBuf, str::FromStr};
 
+pub mod capture;
+#[cfg(feature = "test-rom-download")]
+pub mod download_tests;
 pub mod import;
 pub mod run;
 
@@ -9,6 +12,8 @@ pub mod run;
 pub enum RomSpecification {
     Id(RomId),
     Path(PathBuf),
+    /// A game title to look up in the database, like "Tetris"
+    Title(String),
 }
 
 impl FromStr for RomSpecification {
@@ -20,10 +25,10 @@ impl FromStr for RomSpecification {
             return Ok(RomSpecification::Path(path));
         }
 
-        // If it's not a valid path, try to parse as a RomId
+        // If it's not a valid path, try to parse as a RomId, and failing that treat it as a title
         match RomId::from_str(s) {
             Ok(id) => Ok(RomSpecification::Id(id)),
-            Err(err) => Err(err.into()),
+            Err(_) => Ok(RomSpecification::Title(s.to_string())),
         }
     }
 }
@@ -41,4 +46,23 @@ pub enum RomAction {
         #[clap(short, long)]
         forced_system: Option<GameSystem>,
     },
+    /// Runs without a window to an exact frame, then saves a screenshot and/or savestate of it
+    Capture {
+        #[clap(required = true)]
+        roms: Vec<RomSpecification>,
+        #[clap(short, long)]
+        forced_system: Option<GameSystem>,
+        /// Frames to run before capturing
+        #[clap(long)]
+        frame: u64,
+        /// Where to write a PNG of the main display
+        #[clap(long, required_unless_present = "state")]
+        screenshot: Option<PathBuf>,
+        /// Where to write a savestate, in the same layout as a snapshot slot
+        #[clap(long)]
+        state: Option<PathBuf>,
+    },
+    /// Downloads freely licensed test ROM suites into the library, all of them if none are named
+    #[cfg(feature = "test-rom-download")]
+    DownloadTests { suites: Vec<String> },
 }
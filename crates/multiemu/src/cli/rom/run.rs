@@ -1,6 +1,7 @@
 use super::RomSpecification;
 use crate::{
     config::{GraphicsSettings, GLOBAL_CONFIG},
+    definitions::chip8::assembler::{assemble_into_store, is_octo_source},
     rom::{id::RomId, info::RomInfo, manager::RomManager, system::GameSystem},
     runtime::{
         launch::Runtime,
@@ -13,10 +14,11 @@ use std::{
     sync::Arc,
 };
 
-pub fn rom_run(
+/// Turns whatever the user passed on the command line into ROM ids, adding files that aren't in the library yet
+pub fn resolve_roms(
     roms: Vec<RomSpecification>,
     forced_system: Option<GameSystem>,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(RomManager, Vec<RomId>), Box<dyn Error>> {
     let global_config_guard = GLOBAL_CONFIG.read().unwrap();
     let rom_manager = RomManager::new(Some(&global_config_guard.database_file))?;
 
@@ -29,19 +31,38 @@ pub fn rom_run(
     for rom in roms {
         match rom {
             RomSpecification::Id(rom_id) => user_specified_roms.push(rom_id),
+            RomSpecification::Title(title) => {
+                let Some(rom_id) = rom_manager.find_by_title(
+                    &title,
+                    forced_system,
+                    &global_config_guard.region_preference,
+                )?
+                else {
+                    return Err(format!("No ROM in the database is titled {}", title).into());
+                };
+
+                tracing::info!("Picked ROM {} for title {}", rom_id, title);
+                user_specified_roms.push(rom_id);
+            }
             RomSpecification::Path(rom_path) => {
                 let Some(system) = GameSystem::guess(&rom_path) else {
                     return Err(format!("{} is not a valid rom", rom_path.display()).into());
                 };
 
-                let mut rom_file = File::open(&rom_path)?;
-                let rom_id = RomId::from_read(&mut rom_file);
+                // Octo source is assembled first, and what runs is the assembled program
+                let (rom_id, program_path) = if is_octo_source(&rom_path) {
+                    assemble_into_store(&rom_path, &global_config_guard.roms_directory)?
+                } else {
+                    let mut rom_file = File::open(&rom_path)?;
+                    (RomId::from_read(&mut rom_file), rom_path.clone())
+                };
 
                 let rom_info = RomInfo {
                     name: Some(rom_path.to_string_lossy().to_string()),
                     id: rom_id,
                     system,
-                    region: None,
+                    regions: Vec::new(),
+                    parent: None,
                 };
 
                 user_specified_roms.push(rom_id);
@@ -55,14 +76,26 @@ pub fn rom_run(
                     }
                 }
 
-                rom_manager.rom_paths.insert(rom_id, rom_path);
+                rom_manager.rom_paths.insert(rom_id, program_path);
             }
         }
     }
 
     transaction.commit()?;
 
+    Ok((rom_manager, user_specified_roms))
+}
+
+pub fn rom_run(
+    roms: Vec<RomSpecification>,
+    forced_system: Option<GameSystem>,
+) -> Result<(), Box<dyn Error>> {
+    let (rom_manager, user_specified_roms) = resolve_roms(roms, forced_system)?;
+
+    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
     let graphics_setting = global_config_guard.graphics_setting;
+    #[cfg(graphics_vulkan)]
+    let vulkan_device = global_config_guard.vulkan_device.clone();
     drop(global_config_guard);
     let rom_manager = Arc::new(rom_manager);
 
@@ -78,11 +111,27 @@ pub fn rom_run(
         GraphicsSettings::Vulkan => {
             use crate::runtime::platform::desktop::renderer::vulkan::VulkanRenderingRuntime;
 
-            PlatformRuntime::<VulkanRenderingRuntime>::launch_game(
-                user_specified_roms,
-                forced_system,
-                rom_manager,
-            );
+            match VulkanRenderingRuntime::probe(&vulkan_device) {
+                Ok(device_name) => {
+                    tracing::info!("Vulkan device {} is usable", device_name);
+                    PlatformRuntime::<VulkanRenderingRuntime>::launch_game(
+                        user_specified_roms,
+                        forced_system,
+                        rom_manager,
+                    );
+                }
+                Err(error) => {
+                    tracing::error!(
+                        "Could not initialize vulkan ({}), falling back to software rendering",
+                        error
+                    );
+                    PlatformRuntime::<SoftwareRenderingRuntime>::launch_game(
+                        user_specified_roms,
+                        forced_system,
+                        rom_manager,
+                    );
+                }
+            }
         }
     }
 
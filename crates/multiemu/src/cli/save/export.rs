@@ -0,0 +1,40 @@
+use crate::{
+    cli::rom::RomSpecification,
+    config::GLOBAL_CONFIG,
+    rom::{id::RomId, manager::RomManager},
+    save::manager::SaveManager,
+};
+use std::{error::Error, fs::File, path::PathBuf, sync::Arc};
+
+pub fn save_export(rom: RomSpecification, destination: PathBuf) -> Result<(), Box<dyn Error>> {
+    let global_config_guard = GLOBAL_CONFIG.try_read()?;
+    let rom_manager = Arc::new(RomManager::new(Some(&global_config_guard.database_file))?);
+
+    let rom_id = match rom {
+        RomSpecification::Id(rom_id) => rom_id,
+        RomSpecification::Path(rom_path) => {
+            // Register the path so saves stored next to the ROM can be found
+            let rom_id = RomId::from_read(&mut File::open(&rom_path)?);
+            rom_manager.rom_paths.insert(rom_id, rom_path);
+            rom_id
+        }
+        RomSpecification::Title(title) => rom_manager
+            .find_by_title(&title, None, &global_config_guard.region_preference)?
+            .ok_or_else(|| format!("No ROM in the database is titled {}", title))?,
+    };
+
+    let save_manager = SaveManager::new(
+        global_config_guard.save_directory.clone(),
+        global_config_guard.save_location,
+        rom_manager,
+    );
+    save_manager.export(save_manager.system_of(rom_id), rom_id, &destination)?;
+
+    tracing::info!(
+        "Exported battery save for ROM {} to {}",
+        rom_id,
+        destination.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use crate::{
+    cli::rom::RomSpecification,
+    config::GLOBAL_CONFIG,
+    machine::legacy::{LegacyImportError, LegacySave},
+    rom::{id::RomId, manager::RomManager},
+    save::manager::SaveManager,
+};
+use std::{
+    error::Error,
+    fs::{read_dir, File},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+pub fn save_import(
+    paths: Vec<PathBuf>,
+    rom: Option<RomSpecification>,
+) -> Result<(), Box<dyn Error>> {
+    let global_config_guard = GLOBAL_CONFIG.try_read()?;
+    let rom_manager = Arc::new(RomManager::new(Some(&global_config_guard.database_file))?);
+    let save_manager = SaveManager::new(
+        global_config_guard.save_directory.clone(),
+        global_config_guard.save_location,
+        rom_manager.clone(),
+    );
+
+    let specified_rom = match rom {
+        Some(RomSpecification::Id(rom_id)) => Some(rom_id),
+        Some(RomSpecification::Path(rom_path)) => {
+            Some(RomId::from_read(&mut File::open(rom_path)?))
+        }
+        Some(RomSpecification::Title(title)) => Some(
+            rom_manager
+                .find_by_title(&title, None, &global_config_guard.region_preference)?
+                .ok_or_else(|| format!("No ROM in the database is titled {}", title))?,
+        ),
+        None => None,
+    };
+
+    for path in paths {
+        match LegacySave::load(&path) {
+            Ok(LegacySave::Battery(contents)) => {
+                let Some(rom_id) = specified_rom.or_else(|| guess_rom(&path)) else {
+                    tracing::error!(
+                        "Could not figure out which ROM {} belongs to, specify it with --rom",
+                        path.display()
+                    );
+                    continue;
+                };
+
+                save_manager.store(save_manager.system_of(rom_id), rom_id, &contents)?;
+
+                tracing::info!(
+                    "Imported battery save {} for ROM {}",
+                    path.display(),
+                    rom_id
+                );
+            }
+            Ok(LegacySave::Spectrum(_)) => {
+                tracing::error!(
+                    "Could not import {}: {}",
+                    path.display(),
+                    LegacyImportError::NoMachine("ZX Spectrum snapshot")
+                );
+            }
+            Err(error) => {
+                tracing::error!("Could not import {}: {}", path.display(), error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Most emulators name saves after the ROM, so look for a ROM with the same name next to it
+fn guess_rom(save_path: &Path) -> Option<RomId> {
+    let file_stem = save_path.file_stem()?;
+    let directory = save_path.parent()?;
+
+    read_dir(directory)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path != save_path && path.is_file())
+        .find(|path| path.file_stem() == Some(file_stem))
+        .and_then(|path| File::open(path).ok())
+        .map(|mut file| RomId::from_read(&mut file))
+}
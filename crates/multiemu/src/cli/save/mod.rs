@@ -0,0 +1,39 @@
+use super::rom::RomSpecification;
+use clap::{Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+pub mod export;
+pub mod import;
+pub mod sync;
+
+/// Which copy wins when a save changed both here and remotely
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ConflictPreference {
+    Local,
+    Remote,
+    /// Whichever was written last
+    Newer,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum SaveAction {
+    #[command(about = Some("Import battery saves and savestates from other emulators"))]
+    Import {
+        #[clap(required=true, num_args=1..)]
+        paths: Vec<PathBuf>,
+        /// ROM the saves belong to, guessed from ROMs next to the save if not given
+        #[clap(short, long)]
+        rom: Option<RomSpecification>,
+    },
+    #[command(about = Some("Copy the battery save of a ROM out as a plain file"))]
+    Export {
+        rom: RomSpecification,
+        destination: PathBuf,
+    },
+    #[command(about = Some("Sync battery saves with the backend in the config"))]
+    Sync {
+        /// Resolve conflicts instead of only reporting them
+        #[clap(short, long)]
+        prefer: Option<ConflictPreference>,
+    },
+}
@@ -0,0 +1,61 @@
+use super::ConflictPreference;
+use crate::{
+    config::GLOBAL_CONFIG,
+    rom::manager::RomManager,
+    save::{
+        manager::SaveManager,
+        sync::{SyncOutcome, SyncSide},
+    },
+};
+use std::{error::Error, sync::Arc};
+
+pub fn save_sync(prefer: Option<ConflictPreference>) -> Result<(), Box<dyn Error>> {
+    let global_config_guard = GLOBAL_CONFIG.try_read()?;
+    let Some(backend_config) = &global_config_guard.save_sync else {
+        return Err("No sync backend is set up in the config".into());
+    };
+    let backend = backend_config.build()?;
+
+    let rom_manager = Arc::new(RomManager::new(Some(&global_config_guard.database_file))?);
+    let save_manager = SaveManager::new(
+        global_config_guard.save_directory.clone(),
+        global_config_guard.save_location,
+        rom_manager,
+    );
+
+    let mut conflicts = 0;
+
+    for (system, rom_id) in save_manager.saves() {
+        let outcome = match save_manager.sync(system, rom_id, backend.as_ref()) {
+            Ok(SyncOutcome::Conflict(conflict)) => {
+                let keep = match prefer {
+                    Some(ConflictPreference::Local) => SyncSide::Local,
+                    Some(ConflictPreference::Remote) => SyncSide::Remote,
+                    Some(ConflictPreference::Newer) => conflict.newer(),
+                    None => {
+                        tracing::warn!(
+                            "Save for ROM {} changed both here and remotely, pass --prefer to pick one",
+                            rom_id
+                        );
+                        conflicts += 1;
+                        continue;
+                    }
+                };
+
+                save_manager.resolve_conflict(system, rom_id, backend.as_ref(), keep)
+            }
+            outcome => outcome,
+        };
+
+        match outcome {
+            Ok(outcome) => tracing::info!("Save for ROM {}: {:?}", rom_id, outcome),
+            Err(error) => tracing::error!("Could not sync save for ROM {}: {}", rom_id, error),
+        }
+    }
+
+    if conflicts != 0 {
+        return Err(format!("{} saves are in conflict", conflicts).into());
+    }
+
+    Ok(())
+}
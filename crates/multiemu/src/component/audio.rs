@@ -0,0 +1,12 @@
+use super::Component;
+use num::rational::Ratio;
+
+/// A component making sound, which the runtime collects samples from after every frame
+///
+/// How many channels the samples are interleaved by comes from [Component::audio_channels]
+pub trait AudioComponent: Component {
+    /// Rate of each channel of [AudioComponent::drain_samples]
+    fn sample_rate(&self) -> Ratio<u64>;
+    /// Moves out every sample made so far, interleaved by channel and between 0 and 1
+    fn drain_samples(&self, output: &mut Vec<f32>);
+}
@@ -1,9 +1,25 @@
 use super::Component;
 use crate::runtime::rendering_backend::{
-    DisplayComponentFramebuffer, DisplayComponentInitializationData,
+    DisplayComponentFramebuffer, DisplayComponentInitializationData, RawFrame,
 };
+use nalgebra::Vector2;
+use num::rational::Ratio;
 
 pub trait DisplayComponent: Component {
     fn set_display_data(&self, display_data: DisplayComponentInitializationData);
     fn get_framebuffer(&self) -> DisplayComponentFramebuffer;
+    /// Hands the current frame to the reader as RGBA bytes, returning false if it can't be read without a readback
+    ///
+    /// For tooling that wants the pixels without holding on to the framebuffer
+    fn read_raw_frame(&self, reader: &mut dyn FnMut(RawFrame<'_>)) -> bool {
+        self.get_framebuffer().read_raw(reader).is_some()
+    }
+    /// Where the visible area sits on a larger scrolling background, for hardware that has scroll registers
+    fn scroll_position(&self) -> Option<Vector2<i32>> {
+        None
+    }
+    /// Frames per second the emulated display shows, None if it doesn't have a fixed rate
+    fn refresh_rate(&self) -> Option<Ratio<u64>> {
+        None
+    }
 }
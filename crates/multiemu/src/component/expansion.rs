@@ -0,0 +1,67 @@
+use crate::memory::{AddressSpaceId, MemoryTranslationTable};
+use std::fmt::Debug;
+
+/// Whatever normally sits behind an expansion port, like the cartridge under a Game Genie
+pub struct Passthrough<'a> {
+    pub(crate) memory_translation_table: Option<&'a MemoryTranslationTable>,
+    pub(crate) address_space: Option<AddressSpaceId>,
+}
+
+impl Passthrough<'_> {
+    /// Returns false if nothing is behind the port or it refused
+    pub fn read(&self, address: usize, buffer: &mut [u8]) -> bool {
+        let (Some(memory_translation_table), Some(address_space)) =
+            (self.memory_translation_table, self.address_space)
+        else {
+            return false;
+        };
+
+        memory_translation_table
+            .read(address, buffer, address_space)
+            .is_ok()
+    }
+
+    pub fn write(&self, address: usize, buffer: &[u8]) -> bool {
+        let (Some(memory_translation_table), Some(address_space)) =
+            (self.memory_translation_table, self.address_space)
+        else {
+            return false;
+        };
+
+        memory_translation_table
+            .write(address, buffer, address_space)
+            .is_ok()
+    }
+
+    pub fn preview(&self, address: usize, buffer: &mut [u8]) -> bool {
+        let (Some(memory_translation_table), Some(address_space)) =
+            (self.memory_translation_table, self.address_space)
+        else {
+            return false;
+        };
+
+        memory_translation_table
+            .preview(address, buffer, address_space)
+            .is_ok()
+    }
+}
+
+/// A peripheral plugged into an expansion port, which sees every access to the port first
+///
+/// The defaults pass everything through, so devices only have to handle what they change
+pub trait ExpansionDevice: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns false if the access should be refused
+    fn read(&self, address: usize, buffer: &mut [u8], passthrough: &Passthrough) -> bool {
+        passthrough.read(address, buffer)
+    }
+
+    fn write(&self, address: usize, buffer: &[u8], passthrough: &Passthrough) -> bool {
+        passthrough.write(address, buffer)
+    }
+
+    fn preview(&self, address: usize, buffer: &mut [u8], passthrough: &Passthrough) -> bool {
+        passthrough.preview(address, buffer)
+    }
+}
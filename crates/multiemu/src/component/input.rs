@@ -43,4 +43,9 @@ pub trait InputComponent: Component {
         _gamepad_ids: &[EmulatedGamepadId],
     ) {
     }
+
+    /// Gamepad and inputs to hold together to type a character, for pasting text into machines with keyboards
+    fn character_keys(&self, _character: char) -> Option<(EmulatedGamepadId, Vec<Input>)> {
+        None
+    }
 }
@@ -0,0 +1,11 @@
+use std::fmt::Debug;
+
+/// Whatever is on the other end of a serial link cable, another console or a peripheral like a printer
+///
+/// Transfers go a byte at a time with the console driving the clock, shifting a byte out while one shifts in
+pub trait LinkEndpoint: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The console clocked out a byte, returns the byte the endpoint shifted back at the same time
+    fn exchange(&self, byte: u8) -> u8;
+}
@@ -1,23 +1,60 @@
 use crate::machine::ComponentBuilder;
 use crate::memory::MemoryTranslationTable;
 use downcast_rs::DowncastSync;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::any::Any;
 use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
+pub mod audio;
 pub mod display;
+pub mod expansion;
 pub mod input;
+pub mod link;
 pub mod memory;
+pub mod processor;
 pub mod schedulable;
+pub mod tape;
+pub mod text_output;
 
 // Basic supertrait for all components
 pub trait Component: Any + Debug + Send + Sync + DowncastSync {
     fn reset(&self) {}
+    /// Put the component in the state the system firmware would have left it in, instead of running the firmware
+    ///
+    /// Only called when the user opted into fast boot. This must not depend on anything outside the machine, so
+    /// movies recorded with fast boot replay the same. Firmware nothing can skip this way is run out of sight
+    /// instead, see [crate::machine::MachineBuilder::boot_duration]
+    fn skip_boot(&self) {}
     fn save_snapshot(&self) -> rmpv::Value {
         rmpv::Value::Nil
     }
     fn load_snapshot(&self, _snapshot: rmpv::Value) {}
+    /// Called once every component in the machine has loaded its snapshot, to rebuild whatever was left out of it
+    fn after_load_snapshot(&self) {}
+    /// True if nothing about this component belongs in a savestate, like a host file handle or a staging buffer
+    ///
+    /// Volatile components are left out of machine snapshots and keep whatever they had when one is loaded
+    fn volatile(&self) -> bool {
+        false
+    }
+    /// False if [Component::save_snapshot] can't capture everything this component does
+    fn supports_snapshots(&self) -> bool {
+        true
+    }
+    /// False if loading an older snapshot would confuse something outside the machine, like a linked peer
+    fn rewind_safe(&self) -> bool {
+        true
+    }
+    /// Sound channels this component mixes into the output
+    fn audio_channels(&self) -> usize {
+        0
+    }
+    /// If this component can be connected to another machine
+    fn has_link_port(&self) -> bool {
+        false
+    }
     fn set_memory_translation_table(&self, _memory_translation_table: Arc<MemoryTranslationTable>) {
     }
 }
@@ -30,5 +67,68 @@ pub trait FromConfig: Component + Sized {
     fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config);
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ComponentId(pub u16);
+
+/// Part of a snapshot that is never saved, it is written as nil and comes back as its default
+///
+/// For fields that only mirror something outside the machine or can be worked out from the rest of the state, which
+/// the component fills back in from [Component::after_load_snapshot]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Volatile<T>(pub T);
+
+impl<T> Deref for Volatile<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Volatile<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> Serialize for Volatile<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+impl<'de, T: Default> Deserialize<'de> for Volatile<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde::de::IgnoredAny::deserialize(deserializer)?;
+
+        Ok(Self::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Snapshot {
+        counter: u8,
+        staging: Volatile<Vec<u8>>,
+    }
+
+    #[test]
+    fn volatile_fields_are_skipped() {
+        let snapshot = Snapshot {
+            counter: 3,
+            staging: Volatile(vec![1, 2, 3]),
+        };
+        let value = rmpv::ext::to_value(&snapshot).unwrap();
+        assert_eq!(
+            value,
+            rmpv::Value::Array(vec![rmpv::Value::from(3), rmpv::Value::Nil])
+        );
+
+        let snapshot: Snapshot = rmpv::ext::from_value(value).unwrap();
+        assert_eq!(snapshot.counter, 3);
+        assert!(snapshot.staging.is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use super::Component;
+use crate::memory::AddressSpaceId;
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// A register as a debugger sees it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcessorRegister {
+    pub name: &'static str,
+    /// Size in bytes
+    pub size: usize,
+    /// What debuggers should treat it as, like "pc", "sp" or "flags"
+    pub generic: Option<&'static str>,
+}
+
+/// A processor an external debugger can stop, step and look inside of
+pub trait DebuggableProcessor: Component {
+    /// Every register, in the order the debugger numbers them
+    fn registers(&self) -> &'static [ProcessorRegister];
+
+    fn read_register(&self, index: usize) -> u64;
+
+    fn write_register(&self, index: usize, value: u64);
+
+    /// Where the processor fetches instructions and data from
+    fn address_space(&self) -> AddressSpaceId;
+
+    /// Runs exactly one instruction, even while halted
+    fn step(&self);
+
+    fn debug_state(&self) -> &ProcessorDebugState;
+}
+
+/// Breakpoints and whether a debugger has the processor stopped, checked by the processor before every instruction
+#[derive(Debug, Default)]
+pub struct ProcessorDebugState {
+    breakpoints: Mutex<HashSet<usize>>,
+    /// Something to look at before each instruction, so processors nobody is debugging don't take the lock
+    active: AtomicBool,
+    halted: AtomicBool,
+    /// Lets the instruction under a breakpoint run once the debugger resumes from it
+    resuming: AtomicBool,
+}
+
+impl ProcessorDebugState {
+    pub fn set_breakpoint(&self, address: usize, present: bool) {
+        let mut breakpoints = self.breakpoints.lock().unwrap();
+
+        if present {
+            breakpoints.insert(address);
+        } else {
+            breakpoints.remove(&address);
+        }
+
+        self.active
+            .store(!breakpoints.is_empty() || self.halted(), Ordering::Relaxed);
+    }
+
+    pub fn halt(&self) {
+        self.halted.store(true, Ordering::Relaxed);
+        self.active.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.halted.store(false, Ordering::Relaxed);
+        self.resuming.store(true, Ordering::Relaxed);
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted.load(Ordering::Relaxed)
+    }
+
+    /// Called by the processor before the instruction at `address`, true if it should stay put instead
+    pub fn should_stop(&self, address: usize) -> bool {
+        if !self.active.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        if self.halted() {
+            return true;
+        }
+
+        if self.resuming.swap(false, Ordering::Relaxed) {
+            return false;
+        }
+
+        if self.breakpoints.lock().unwrap().contains(&address) {
+            self.halt();
+            return true;
+        }
+
+        false
+    }
+}
@@ -0,0 +1,14 @@
+use super::Component;
+use crate::tape::TapeDeck;
+use std::sync::Mutex;
+
+/// A tape deck built into or plugged into a machine, which the frontend can press the buttons of
+pub trait TapeComponent: Component {
+    fn deck(&self) -> &Mutex<TapeDeck>;
+
+    /// Puts the next file on the tape straight into memory the way the machine's loader would, returns false if
+    /// the machine has no way to or the tape has no files it can tell apart
+    fn fast_load(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,12 @@
+use super::Component;
+
+/// Somewhere a machine prints text, like a printer or a serial port, which the debug menu shows
+pub trait TextOutputComponent: Component {
+    /// What the debug menu calls it
+    fn name(&self) -> &str;
+
+    /// Everything printed since the machine started or it was last cleared
+    fn text(&self) -> String;
+
+    fn clear(&self);
+}
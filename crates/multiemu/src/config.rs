@@ -1,10 +1,15 @@
 use crate::{
     component::input::EmulatedGamepadTypeId,
+    definitions::misc::{expansion::PeripheralConfig, link::LinkPeripheralConfig},
     input::{
         hotkey::{Hotkey, DEFAULT_HOTKEYS},
-        Input,
+        profile::ControllerProfile,
+        Input, InputSampling,
     },
-    rom::system::GameSystem,
+    logging::LogLevel,
+    rom::{region::RomRegion, system::GameSystem},
+    runtime::{audio::AudioHost, livesplit::LiveSplitConfig, rewind::RewindConfig},
+    save::sync::SyncBackendConfig,
 };
 use indexmap::IndexMap;
 use ron::ser::PrettyConfig;
@@ -48,6 +53,112 @@ impl Default for GraphicsSettings {
     }
 }
 
+/// How the emulated display is fit into the window
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum DisplayScaling {
+    /// Fill the entire window, ignoring aspect ratio
+    #[default]
+    Stretch,
+    /// Largest size that keeps the aspect ratio
+    Fit,
+    /// Largest whole number multiple of the display size
+    Integer,
+}
+
+/// Upscaling done on the CPU before the framebuffer is stretched to the window, for renderers without shaders
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum ScalerFilter {
+    #[default]
+    Nearest,
+    Scale2x,
+    Scale3x,
+    Hq2x,
+}
+
+/// What pixels integer scaling is measured in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum WindowSizing {
+    /// Raw monitor pixels, integer scaling is exact but looks smaller on HiDPI monitors
+    #[default]
+    Physical,
+    /// Pixels adjusted by the monitor scale factor
+    Logical,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum FullscreenMode {
+    /// Window covering the monitor at its current resolution
+    #[default]
+    Borderless,
+    /// Takes over the monitor and switches its resolution
+    Exclusive,
+}
+
+/// Where the window was last time it wasn't fullscreen
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowGeometry {
+    pub position: Option<(i32, i32)>,
+    pub size: Option<(u32, u32)>,
+}
+
+/// Per system adjustment applied to colors right before they are shown
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum ColorCorrection {
+    #[default]
+    None,
+    /// Mimics the washed out colors of the Game Boy Color LCD
+    GameBoyColor,
+    /// Mimics the dark, low contrast Game Boy Advance LCD
+    GameBoyAdvance,
+}
+
+/// Simulation of the analog video signal, for systems whose games were drawn with a tv in mind
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum NtscFilter {
+    #[default]
+    None,
+    /// Luma and chroma share one signal, so fine patterns turn into artifact colors and colors bleed
+    Composite,
+    /// Luma and chroma are kept apart, colors still bleed but there are no artifact colors
+    SVideo,
+}
+
+/// Filter for color vision deficiencies, applied to the emulated display after everything else
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum ColorBlindFilter {
+    #[default]
+    None,
+    /// Shows what someone without red cones sees, for checking if a game is playable
+    SimulateProtanopia,
+    /// Same but without green cones
+    SimulateDeuteranopia,
+    /// Shifts colors that would be confused into ones that can be told apart
+    CorrectProtanopia,
+    CorrectDeuteranopia,
+}
+
+/// Where battery saves are written
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum SaveLocation {
+    /// Organized by system and ROM inside the save directory
+    #[default]
+    Directory,
+    /// Beside the ROM file with a .sav extension, like most other emulators do
+    NextToRom,
+}
+
+/// Which vulkan device the vulkan renderer should try to use
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub enum VulkanDevicePreference {
+    /// Discrete gpus first, then integrated, then whatever else is around
+    #[default]
+    Automatic,
+    PreferDiscrete,
+    PreferIntegrated,
+    /// Exact device name as reported by the driver, falls back to automatic if missing
+    Named(String),
+}
+
 #[serde_as]
 #[serde_inline_default]
 #[derive(Serialize, Deserialize, Debug)]
@@ -55,39 +166,221 @@ pub struct GlobalConfig {
     #[serde(default)]
     pub gamepad_configs:
         IndexMap<GameSystem, IndexMap<EmulatedGamepadTypeId, IndexMap<Input, Input>>>,
+    /// Per device bindings, which take priority over the ones above when that device is connected
+    #[serde(default)]
+    pub controller_profiles: IndexMap<String, ControllerProfile>,
+    /// SDL_GameControllerDB file used to map unknown controllers onto the standard layout
+    #[serde_inline_default(STORAGE_DIRECTORY.join("gamecontrollerdb.txt"))]
+    pub controller_database: PathBuf,
     #[serde_inline_default(DEFAULT_HOTKEYS.clone())]
     pub hotkeys: IndexMap<BTreeSet<Input>, Hotkey>,
     #[serde(default)]
     pub graphics_setting: GraphicsSettings,
+    #[serde(default)]
+    pub vulkan_device: VulkanDevicePreference,
+    #[serde(default)]
+    pub display_scaling: DisplayScaling,
+    #[serde(default)]
+    pub window_sizing: WindowSizing,
+    #[serde(default)]
+    pub scaler_filter: ScalerFilter,
+    #[serde(default)]
+    pub fullscreen_mode: FullscreenMode,
+    /// Monitor name to go fullscreen on, the current monitor is used if unset
+    #[serde(default)]
+    pub fullscreen_monitor: Option<String>,
+    /// Resolution for exclusive fullscreen, the largest mode available is used if unset
+    #[serde(default)]
+    pub fullscreen_resolution: Option<(u32, u32)>,
+    #[serde(default)]
+    pub start_fullscreen: bool,
+    /// Speed, framerate and audio numbers drawn over the running machine
+    #[serde(default)]
+    pub stats_overlay: bool,
+    #[serde(default)]
+    pub window_geometry: WindowGeometry,
+    /// Headerless RGB palette files, used by systems that output indexed colors
+    #[serde(default)]
+    pub palettes: IndexMap<GameSystem, PathBuf>,
+    #[serde(default)]
+    pub color_correction: IndexMap<GameSystem, ColorCorrection>,
+    /// Applied after the other color effects, before scaling
+    #[serde(default)]
+    pub ntsc_filter: IndexMap<GameSystem, NtscFilter>,
+    /// Systems whose frames get averaged with the previous one to hide flicker
+    #[serde(default)]
+    pub frame_blending: IndexMap<GameSystem, bool>,
+    /// Peripherals plugged into each systems expansion ports, by port name
+    #[serde(default)]
+    pub peripherals: IndexMap<GameSystem, IndexMap<String, PeripheralConfig>>,
+    /// What is plugged into each systems link port
+    #[serde(default)]
+    pub link_peripherals: IndexMap<GameSystem, LinkPeripheralConfig>,
+    /// Systems with a multitap plugged in, for more than the usual number of players
+    #[serde(default)]
+    pub multitap: IndexMap<GameSystem, bool>,
+    /// Systems with a light gun plugged in, aimed with the mouse
+    #[serde(default)]
+    pub light_gun: IndexMap<GameSystem, bool>,
+    /// Systems that skip their firmware intro where the machine supports it
+    #[serde(default)]
+    pub fast_boot: IndexMap<GameSystem, bool>,
+    /// Systems that take host input once a frame instead of whenever the game looks at it
+    #[serde(default)]
+    pub input_sampling: IndexMap<GameSystem, InputSampling>,
+    #[serde(default)]
+    pub color_blind_filter: ColorBlindFilter,
+    #[serde(default)]
+    pub high_contrast_ui: bool,
+    /// Speaks menu text through the given command, like "espeak", as the user moves around
+    #[serde(default)]
+    pub screen_reader: Option<String>,
+    /// Language for the built in UI, like "de-DE", None to follow the system
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Order regions are picked in when a game is chosen by title and has several releases
+    #[serde_inline_default(DEFAULT_REGION_PREFERENCE.to_vec())]
+    pub region_preference: Vec<RomRegion>,
+    /// Sends splits to a running LiveSplit when triggers fire
+    #[serde(default)]
+    pub livesplit: Option<LiveSplitConfig>,
+    /// Port to listen on for gdb or lldb to attach to the running machine's processor
+    #[serde(default)]
+    pub gdb_port: Option<u16>,
+    /// Keeps snapshots to step back through with the rewind hotkey, for machines where that is safe
+    #[serde(default)]
+    pub rewind: Option<RewindConfig>,
+    /// Stitch scrolling games into a picture of the whole level, saved when the game closes
+    #[serde(default)]
+    pub map_capture: bool,
+    /// Frames each key is held, and then let go, when pasting text, slow machines drop keys if this is too low
+    #[serde_inline_default(3)]
+    pub paste_hold_frames: u32,
     #[serde_inline_default(true)]
     pub vsync: bool,
+    /// Present at the machines own rate instead of the host refresh rate, for variable refresh displays
+    #[serde(default)]
+    pub variable_refresh_rate: bool,
+    /// Samples the audio device is handed at a time, smaller means less latency but more underruns
+    #[serde_inline_default(512)]
+    pub audio_buffer_size: u32,
+    /// Low latency hosts fall back to the shared one when they can't be used
+    #[serde(default)]
+    pub audio_host: AudioHost,
+    /// Milliseconds of audio kept queued ahead of the device
+    #[serde_inline_default(64)]
+    pub audio_latency: u32,
+    /// Retarget the audio latency to match how long frames take to show up, see [crate::runtime::av_sync]
+    #[serde_inline_default(true)]
+    pub av_sync: bool,
+    /// Milliseconds added on top of A/V sync, for speakers or TVs with their own delay
+    #[serde(default)]
+    pub av_offset: i32,
+    /// Milliseconds a component may run without returning before the machine is stopped, 0 turns the watchdog off
+    #[serde_inline_default(2000)]
+    pub watchdog_budget: u32,
     #[serde_inline_default(STORAGE_DIRECTORY.clone())]
     pub file_browser_home: PathBuf,
     #[serde_inline_default(STORAGE_DIRECTORY.join("log"))]
     pub log_location: PathBuf,
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// Overrides for specific modules, like "multiemu::memory"
+    #[serde(default)]
+    pub module_log_levels: IndexMap<String, LogLevel>,
     #[serde_inline_default(STORAGE_DIRECTORY.join("database"))]
     pub database_file: PathBuf,
     #[serde_inline_default(STORAGE_DIRECTORY.join("saves"))]
     pub save_directory: PathBuf,
+    #[serde(default)]
+    pub save_location: SaveLocation,
+    /// Remote copy of the save directory, for keeping saves the same across computers
+    #[serde(default)]
+    pub save_sync: Option<SyncBackendConfig>,
     #[serde_inline_default(STORAGE_DIRECTORY.join("snapshot"))]
     pub snapshot_directory: PathBuf,
     #[serde_inline_default(STORAGE_DIRECTORY.join("roms"))]
     pub roms_directory: PathBuf,
+    /// Folders checked in the background for new ROMs, which get added to the library as they show up
+    #[serde(default)]
+    pub watch_folders: Vec<PathBuf>,
+    /// Per ROM memory triggers, named after the ROM id
+    #[serde_inline_default(STORAGE_DIRECTORY.join("triggers"))]
+    pub trigger_directory: PathBuf,
+    #[serde_inline_default(STORAGE_DIRECTORY.join("maps"))]
+    pub map_directory: PathBuf,
+    /// Images produced by the emulated hardware itself, like printouts
+    #[serde_inline_default(STORAGE_DIRECTORY.join("captures"))]
+    pub capture_directory: PathBuf,
 }
 
+pub const DEFAULT_REGION_PREFERENCE: [RomRegion; 4] = [
+    RomRegion::World,
+    RomRegion::NorthAmerica,
+    RomRegion::Europe,
+    RomRegion::Japan,
+];
+
 impl Default for GlobalConfig {
     fn default() -> Self {
         Self {
             gamepad_configs: Default::default(),
+            controller_profiles: IndexMap::default(),
+            controller_database: STORAGE_DIRECTORY.join("gamecontrollerdb.txt"),
             hotkeys: DEFAULT_HOTKEYS.clone(),
             graphics_setting: GraphicsSettings::default(),
+            vulkan_device: VulkanDevicePreference::default(),
+            display_scaling: DisplayScaling::default(),
+            window_sizing: WindowSizing::default(),
+            scaler_filter: ScalerFilter::default(),
+            fullscreen_mode: FullscreenMode::default(),
+            fullscreen_monitor: None,
+            fullscreen_resolution: None,
+            start_fullscreen: false,
+            stats_overlay: false,
+            window_geometry: WindowGeometry::default(),
+            palettes: IndexMap::default(),
+            color_correction: IndexMap::default(),
+            ntsc_filter: IndexMap::default(),
+            frame_blending: IndexMap::default(),
+            fast_boot: IndexMap::default(),
+            input_sampling: IndexMap::default(),
+            multitap: IndexMap::default(),
+            light_gun: IndexMap::default(),
+            peripherals: IndexMap::default(),
+            link_peripherals: IndexMap::default(),
+            color_blind_filter: ColorBlindFilter::default(),
+            high_contrast_ui: false,
+            screen_reader: None,
+            language: None,
+            region_preference: DEFAULT_REGION_PREFERENCE.to_vec(),
+            livesplit: None,
+            gdb_port: None,
+            rewind: None,
+            map_capture: false,
+            paste_hold_frames: 3,
             vsync: true,
+            variable_refresh_rate: false,
+            audio_buffer_size: 512,
+            audio_host: AudioHost::default(),
+            audio_latency: 64,
+            av_sync: true,
+            av_offset: 0,
+            watchdog_budget: 2000,
             file_browser_home: STORAGE_DIRECTORY.clone(),
             log_location: STORAGE_DIRECTORY.join("log"),
+            log_level: LogLevel::default(),
+            module_log_levels: IndexMap::default(),
             database_file: STORAGE_DIRECTORY.join("database"),
             save_directory: STORAGE_DIRECTORY.join("saves"),
+            save_location: SaveLocation::default(),
+            save_sync: None,
             snapshot_directory: STORAGE_DIRECTORY.join("snapshot"),
             roms_directory: STORAGE_DIRECTORY.join("roms"),
+            watch_folders: Vec::default(),
+            trigger_directory: STORAGE_DIRECTORY.join("triggers"),
+            map_directory: STORAGE_DIRECTORY.join("maps"),
+            capture_directory: STORAGE_DIRECTORY.join("captures"),
         }
     }
 }
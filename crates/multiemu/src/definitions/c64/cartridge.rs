@@ -0,0 +1,239 @@
+use super::C64_CARTRIDGE_ADDRESS_SPACE_ID;
+use crate::{
+    component::{memory::MemoryComponent, Component, FromConfig},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+    rom::{handle::RomHandle, id::RomId, manager::RomRequirement},
+};
+use rangemap::RangeMap;
+use std::ops::Range;
+
+const CRT_MAGIC: &[u8] = b"C64 CARTRIDGE   ";
+const CHIP_MAGIC: &[u8] = b"CHIP";
+/// Size of the header in front of each chip's contents
+const CHIP_HEADER_SIZE: usize = 0x10;
+
+const ROML_START: usize = 0x8000;
+const ROML_SIZE: usize = 0x2000;
+
+#[derive(Debug)]
+pub(super) struct C64CartridgeConfig {
+    pub rom: RomId,
+}
+
+/// What the cartridge does to the memory map, from the EXROM and GAME lines it pulls low
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct CartridgeLines {
+    pub exrom: bool,
+    pub game: bool,
+}
+
+/// Where each chip of the cartridge sits, and the lines it pulls
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CartridgeLayout {
+    lines: CartridgeLines,
+    /// Address range of each chip and where its contents start in the ROM
+    chips: Vec<(Range<usize>, usize)>,
+}
+
+/// Reads a .crt file, or places a headerless dump the way an 8KB or 16KB cartridge would sit
+///
+/// Only the first bank of each chip is used, since bank switching hardware isn't emulated
+fn layout(rom: &[u8]) -> CartridgeLayout {
+    if rom.is_empty() {
+        return CartridgeLayout::default();
+    }
+
+    if !rom.starts_with(CRT_MAGIC) {
+        let size = rom.len().min(2 * ROML_SIZE);
+
+        return CartridgeLayout {
+            lines: CartridgeLines {
+                exrom: true,
+                game: size > ROML_SIZE,
+            },
+            chips: vec![(ROML_START..ROML_START + size, 0)],
+        };
+    }
+
+    let read_u16 = |offset: usize| {
+        rom.get(offset..offset + 2)
+            .map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]) as usize)
+    };
+    let read_u32 = |offset: usize| {
+        rom.get(offset..offset + 4).map_or(0, |bytes| {
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+        })
+    };
+
+    let hardware_type = read_u16(0x16);
+    if hardware_type != 0 {
+        tracing::warn!(
+            "Cartridge hardware type {} is not supported, it will probably not work",
+            hardware_type
+        );
+    }
+
+    // The header stores the levels of the lines, which pull the memory map when low
+    let mut layout = CartridgeLayout {
+        lines: CartridgeLines {
+            exrom: rom.get(0x18) == Some(&0),
+            game: rom.get(0x19) == Some(&0),
+        },
+        chips: Vec::new(),
+    };
+
+    let mut offset = read_u32(0x10);
+    while rom.get(offset..offset + CHIP_MAGIC.len()) == Some(CHIP_MAGIC) {
+        let packet_length = read_u32(offset + 0x4);
+        let bank = read_u16(offset + 0xa);
+        let load_address = read_u16(offset + 0xc);
+        let size = read_u16(offset + 0xe);
+
+        if bank == 0 && size != 0 {
+            layout
+                .chips
+                .push((load_address..load_address + size, offset + CHIP_HEADER_SIZE));
+        }
+
+        if packet_length == 0 {
+            break;
+        }
+        offset += packet_length;
+    }
+
+    layout
+}
+
+/// A cartridge in the expansion port, appearing on its own bus for the PLA to page in
+///
+/// TODO: Bank switching cartridges, and the I/O areas at 0xde00 and 0xdf00
+#[derive(Debug)]
+pub(super) struct C64Cartridge {
+    /// Missing if the ROM could not be found, in which case it reads as open bus
+    rom: Option<RomHandle>,
+    layout: CartridgeLayout,
+}
+
+impl C64Cartridge {
+    pub fn lines(&self) -> CartridgeLines {
+        self.layout.lines
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        self.layout
+            .chips
+            .iter()
+            .find(|(range, _)| range.contains(&address))
+            .and_then(|(range, offset)| {
+                self.rom
+                    .as_ref()
+                    .and_then(|rom| rom.get(offset + address - range.start).copied())
+            })
+            .unwrap_or(0xff)
+    }
+}
+
+impl Component for C64Cartridge {
+    fn reset(&self) {
+        // Nothing to page, so nothing to reset
+    }
+}
+
+impl FromConfig for C64Cartridge {
+    type Config = C64CartridgeConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let rom = component_builder.open_rom(config.rom, RomRequirement::Required);
+        let layout = rom.as_deref().map(layout).unwrap_or_default();
+        let ranges: RangeMap<_, _> = layout
+            .chips
+            .iter()
+            .map(|(range, _)| (range.clone(), ()))
+            .collect();
+
+        component_builder
+            .set_component(Self { rom, layout })
+            .set_memory(
+                ranges
+                    .iter()
+                    .map(|(range, _)| (C64_CARTRIDGE_ADDRESS_SPACE_ID, range.clone())),
+            );
+    }
+}
+
+impl MemoryComponent for C64Cartridge {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = self.read(address);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        errors.insert(address..address + buffer.len(), WriteMemoryRecord::Denied);
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = self.read(address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crt_and_headerless_layouts() {
+        // A 16KB cartridge as one chip at 0x8000
+        let mut crt = CRT_MAGIC.to_vec();
+        crt.extend_from_slice(&[0x00, 0x00, 0x00, 0x40, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        crt.resize(0x40, 0);
+        crt.extend_from_slice(CHIP_MAGIC);
+        crt.extend_from_slice(&[0x00, 0x00, 0x40, 0x10, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00]);
+        crt.extend_from_slice(&[0x40, 0x00]);
+        crt.resize(0x50 + 0x4000, 0);
+
+        assert_eq!(
+            layout(&crt),
+            CartridgeLayout {
+                lines: CartridgeLines {
+                    exrom: true,
+                    game: true
+                },
+                chips: vec![(0x8000..0xc000, 0x50)],
+            }
+        );
+
+        // Headerless 8KB dumps only pull EXROM
+        assert_eq!(
+            layout(&[0; 0x2000]),
+            CartridgeLayout {
+                lines: CartridgeLines {
+                    exrom: true,
+                    game: false
+                },
+                chips: vec![(0x8000..0xa000, 0)],
+            }
+        );
+    }
+}
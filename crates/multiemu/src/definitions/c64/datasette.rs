@@ -0,0 +1,161 @@
+use super::C64_RAM_ADDRESS_SPACE_ID;
+use crate::{
+    component::{
+        schedulable::SchedulableComponent, tape::TapeComponent, Component, ComponentId, FromConfig,
+    },
+    definitions::misc::io::cia::M6526,
+    machine::ComponentBuilder,
+    memory::MemoryTranslationTable,
+    tape::{Tape, TapeDeck, TapeDeckState},
+};
+use num::rational::Ratio;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Where the KERNAL leaves the end address of whatever it loaded
+const LOAD_END: usize = 0xae;
+/// BASIC's pointers to the end of the program, and the variables and arrays that come after it
+const BASIC_END_POINTERS: [usize; 3] = [0x2d, 0x2f, 0x31];
+const BASIC_START: u16 = 0x0801;
+
+#[derive(Debug)]
+pub(super) struct C64DatasetteConfig {
+    pub frequency: Ratio<u64>,
+    pub tape: Option<Tape>,
+    /// The CIA with the read line on its FLAG pin
+    pub cia: ComponentId,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct C64DatasetteState {
+    /// Part of a nanosecond left over from the last run, so the tape doesn't drift against the clock
+    remainder: u64,
+    /// File the next fast load takes off the tape
+    next_file: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct C64DatasetteSnapshot {
+    deck: TapeDeckState,
+    state: C64DatasetteState,
+}
+
+/// The C2N datasette, feeding the tape to the first CIA a falling edge at a time
+///
+/// The motor and the sense line go through the processor port, which the PLA looks after. Saving to tape isn't
+/// supported
+#[derive(Debug)]
+pub(super) struct C64Datasette {
+    frequency: Ratio<u64>,
+    cia: Arc<M6526>,
+    deck: Mutex<TapeDeck>,
+    state: Mutex<C64DatasetteState>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+}
+
+impl Component for C64Datasette {
+    fn reset(&self) {
+        // The tape stays where it is, only the machine forgets about it
+        self.deck.lock().unwrap().set_motor(false);
+        *self.state.lock().unwrap() = C64DatasetteState::default();
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(C64DatasetteSnapshot {
+            deck: self.deck.lock().unwrap().state.clone(),
+            state: self.state.lock().unwrap().clone(),
+        })
+        .unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let snapshot: C64DatasetteSnapshot = rmpv::ext::from_value(state).unwrap();
+
+        self.deck.lock().unwrap().state = snapshot.deck;
+        *self.state.lock().unwrap() = snapshot.state;
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for C64Datasette {
+    type Config = C64DatasetteConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let cia = component_builder
+            .machine()
+            .get_component::<M6526>(config.cia)
+            .expect("CIA the datasette is wired to is missing");
+
+        component_builder
+            .set_component(Self {
+                frequency: config.frequency,
+                cia,
+                deck: Mutex::new(TapeDeck::new(config.tape)),
+                state: Mutex::default(),
+                memory_translation_table: OnceLock::new(),
+            })
+            .set_schedulable(config.frequency, [], [])
+            .set_tape();
+    }
+}
+
+impl SchedulableComponent for C64Datasette {
+    fn run(&self, period: u64) {
+        let mut deck = self.deck.lock().unwrap();
+        if !deck.running() {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let elapsed = period * 1_000_000_000 * self.frequency.denom() + state.remainder;
+        state.remainder = elapsed % self.frequency.numer();
+
+        for _ in 0..deck.advance(elapsed / self.frequency.numer()) {
+            self.cia.pulse_flag();
+        }
+    }
+}
+
+impl TapeComponent for C64Datasette {
+    fn deck(&self) -> &Mutex<TapeDeck> {
+        &self.deck
+    }
+
+    fn fast_load(&self) -> bool {
+        let deck = self.deck.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        let Some(file) = deck.tape().and_then(|tape| tape.files.get(state.next_file)) else {
+            return false;
+        };
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+
+        tracing::info!(
+            "Fast loading \"{}\" to {:#06x}",
+            file.name,
+            file.start_address
+        );
+
+        for (address, byte) in (file.start_address as usize..0x10000).zip(file.data.iter()) {
+            let _ = memory_translation_table.write(address, &[*byte], C64_RAM_ADDRESS_SPACE_ID);
+        }
+
+        let end = (file.start_address as usize + file.data.len()).min(0x10000) as u16;
+        let mut pointers = vec![LOAD_END];
+        // A BASIC program also needs BASIC to know where it ends, which LOAD would have done from direct mode
+        if file.start_address == BASIC_START {
+            pointers.extend(BASIC_END_POINTERS);
+        }
+        for (address, byte) in pointers
+            .into_iter()
+            .flat_map(|pointer| [pointer, pointer + 1].into_iter().zip(end.to_le_bytes()))
+        {
+            let _ = memory_translation_table.write(address, &[byte], C64_RAM_ADDRESS_SPACE_ID);
+        }
+
+        state.next_file += 1;
+        true
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        Component, FromConfig,
+    },
+    definitions::misc::io::{PortConnection, PortWiring},
+    input::{
+        gamepad::GamepadInput, keyboard::KeyboardInput, manager::InputManager, EmulatedGamepadId,
+        Input,
+    },
+    machine::ComponentBuilder,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+pub const C64_KEYBOARD_GAMEPAD_TYPE: EmulatedGamepadTypeId =
+    EmulatedGamepadTypeId::new("C64 Keyboard");
+pub const C64_JOYSTICK_GAMEPAD_TYPE: EmulatedGamepadTypeId =
+    EmulatedGamepadTypeId::new("C64 Joystick");
+
+/// Column on port A and row on port B of every key, with the host keys standing in for them by position
+///
+/// RESTORE isn't in the matrix, it pulls the NMI, and isn't here either
+const MATRIX: &[(u8, u8, KeyboardInput)] = &[
+    // INST/DEL
+    (0, 0, KeyboardInput::Backspace),
+    (0, 1, KeyboardInput::Enter),
+    (0, 2, KeyboardInput::ArrowRight),
+    (0, 3, KeyboardInput::F7),
+    (0, 4, KeyboardInput::F1),
+    (0, 5, KeyboardInput::F3),
+    (0, 6, KeyboardInput::F5),
+    (0, 7, KeyboardInput::ArrowDown),
+    (1, 0, KeyboardInput::Digit3),
+    (1, 1, KeyboardInput::KeyW),
+    (1, 2, KeyboardInput::KeyA),
+    (1, 3, KeyboardInput::Digit4),
+    (1, 4, KeyboardInput::KeyZ),
+    (1, 5, KeyboardInput::KeyS),
+    (1, 6, KeyboardInput::KeyE),
+    (1, 7, KeyboardInput::ShiftLeft),
+    (2, 0, KeyboardInput::Digit5),
+    (2, 1, KeyboardInput::KeyR),
+    (2, 2, KeyboardInput::KeyD),
+    (2, 3, KeyboardInput::Digit6),
+    (2, 4, KeyboardInput::KeyC),
+    (2, 5, KeyboardInput::KeyF),
+    (2, 6, KeyboardInput::KeyT),
+    (2, 7, KeyboardInput::KeyX),
+    (3, 0, KeyboardInput::Digit7),
+    (3, 1, KeyboardInput::KeyY),
+    (3, 2, KeyboardInput::KeyG),
+    (3, 3, KeyboardInput::Digit8),
+    (3, 4, KeyboardInput::KeyB),
+    (3, 5, KeyboardInput::KeyH),
+    (3, 6, KeyboardInput::KeyU),
+    (3, 7, KeyboardInput::KeyV),
+    (4, 0, KeyboardInput::Digit9),
+    (4, 1, KeyboardInput::KeyI),
+    (4, 2, KeyboardInput::KeyJ),
+    (4, 3, KeyboardInput::Digit0),
+    (4, 4, KeyboardInput::KeyM),
+    (4, 5, KeyboardInput::KeyK),
+    (4, 6, KeyboardInput::KeyO),
+    (4, 7, KeyboardInput::KeyN),
+    // +
+    (5, 0, KeyboardInput::Minus),
+    (5, 1, KeyboardInput::KeyP),
+    (5, 2, KeyboardInput::KeyL),
+    // -
+    (5, 3, KeyboardInput::Equal),
+    (5, 4, KeyboardInput::Period),
+    // :
+    (5, 5, KeyboardInput::Semicolon),
+    // @
+    (5, 6, KeyboardInput::BracketLeft),
+    (5, 7, KeyboardInput::Comma),
+    // £
+    (6, 0, KeyboardInput::Insert),
+    // *
+    (6, 1, KeyboardInput::BracketRight),
+    // ;
+    (6, 2, KeyboardInput::Quote),
+    // CLR/HOME
+    (6, 3, KeyboardInput::Home),
+    (6, 4, KeyboardInput::ShiftRight),
+    // =
+    (6, 5, KeyboardInput::Backslash),
+    // Up arrow
+    (6, 6, KeyboardInput::Delete),
+    (6, 7, KeyboardInput::Slash),
+    (7, 0, KeyboardInput::Digit1),
+    // Left arrow
+    (7, 1, KeyboardInput::Backquote),
+    // CTRL
+    (7, 2, KeyboardInput::Tab),
+    (7, 3, KeyboardInput::Digit2),
+    (7, 4, KeyboardInput::Space),
+    // Commodore
+    (7, 5, KeyboardInput::ControlLeft),
+    (7, 6, KeyboardInput::KeyQ),
+    // RUN/STOP
+    (7, 7, KeyboardInput::Escape),
+];
+
+/// Joystick pins in the order the ports read them, low while held
+/// Characters that need SHIFT held with a key, following the symbols printed on the keys
+const SHIFTED_CHARACTERS: &[(char, KeyboardInput)] = &[
+    ('!', KeyboardInput::Digit1),
+    ('"', KeyboardInput::Digit2),
+    ('#', KeyboardInput::Digit3),
+    ('$', KeyboardInput::Digit4),
+    ('%', KeyboardInput::Digit5),
+    ('&', KeyboardInput::Digit6),
+    ('\'', KeyboardInput::Digit7),
+    ('(', KeyboardInput::Digit8),
+    (')', KeyboardInput::Digit9),
+    ('[', KeyboardInput::Semicolon),
+    (']', KeyboardInput::Quote),
+    ('<', KeyboardInput::Comma),
+    ('>', KeyboardInput::Period),
+    ('?', KeyboardInput::Slash),
+];
+
+/// Key typing a character without SHIFT, lowercase letters included since the machine starts in uppercase
+fn unshifted_key(character: char) -> Option<KeyboardInput> {
+    Some(match character.to_ascii_uppercase() {
+        'A' => KeyboardInput::KeyA,
+        'B' => KeyboardInput::KeyB,
+        'C' => KeyboardInput::KeyC,
+        'D' => KeyboardInput::KeyD,
+        'E' => KeyboardInput::KeyE,
+        'F' => KeyboardInput::KeyF,
+        'G' => KeyboardInput::KeyG,
+        'H' => KeyboardInput::KeyH,
+        'I' => KeyboardInput::KeyI,
+        'J' => KeyboardInput::KeyJ,
+        'K' => KeyboardInput::KeyK,
+        'L' => KeyboardInput::KeyL,
+        'M' => KeyboardInput::KeyM,
+        'N' => KeyboardInput::KeyN,
+        'O' => KeyboardInput::KeyO,
+        'P' => KeyboardInput::KeyP,
+        'Q' => KeyboardInput::KeyQ,
+        'R' => KeyboardInput::KeyR,
+        'S' => KeyboardInput::KeyS,
+        'T' => KeyboardInput::KeyT,
+        'U' => KeyboardInput::KeyU,
+        'V' => KeyboardInput::KeyV,
+        'W' => KeyboardInput::KeyW,
+        'X' => KeyboardInput::KeyX,
+        'Y' => KeyboardInput::KeyY,
+        'Z' => KeyboardInput::KeyZ,
+        '0' => KeyboardInput::Digit0,
+        '1' => KeyboardInput::Digit1,
+        '2' => KeyboardInput::Digit2,
+        '3' => KeyboardInput::Digit3,
+        '4' => KeyboardInput::Digit4,
+        '5' => KeyboardInput::Digit5,
+        '6' => KeyboardInput::Digit6,
+        '7' => KeyboardInput::Digit7,
+        '8' => KeyboardInput::Digit8,
+        '9' => KeyboardInput::Digit9,
+        ' ' => KeyboardInput::Space,
+        '\n' => KeyboardInput::Enter,
+        '+' => KeyboardInput::Minus,
+        '-' => KeyboardInput::Equal,
+        ':' => KeyboardInput::Semicolon,
+        '@' => KeyboardInput::BracketLeft,
+        ',' => KeyboardInput::Comma,
+        '.' => KeyboardInput::Period,
+        '/' => KeyboardInput::Slash,
+        '*' => KeyboardInput::BracketRight,
+        ';' => KeyboardInput::Quote,
+        '=' => KeyboardInput::Backslash,
+        '£' => KeyboardInput::Insert,
+        '^' => KeyboardInput::Delete,
+        _ => return None,
+    })
+}
+
+const JOYSTICK_BUTTONS: [GamepadInput; 5] = [
+    GamepadInput::DPadUp,
+    GamepadInput::DPadDown,
+    GamepadInput::DPadLeft,
+    GamepadInput::DPadRight,
+    GamepadInput::FPadDown,
+];
+
+/// Gamepads in the order the component asks for them
+const KEYBOARD: usize = 0;
+/// Most games read the joystick in control port 2, so it comes first
+const CONTROL_PORT_2: usize = 1;
+const CONTROL_PORT_1: usize = 2;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct C64KeyboardState {
+    /// Pins of port A the first CIA is pulling low, selecting keyboard columns
+    columns_driven: u8,
+    /// Pins of port B it is pulling low, which scans the matrix backwards
+    rows_driven: u8,
+}
+
+/// The keyboard matrix and both joysticks, which share the ports of the first CIA
+///
+/// Port A drives the columns and reads control port 2, port B reads the rows and control port 1. Both directions
+/// work, since some games scan the matrix from port B
+#[derive(Debug)]
+pub(super) struct C64Keyboard {
+    state: Mutex<C64KeyboardState>,
+    input_manager: OnceLock<(Arc<InputManager>, Vec<EmulatedGamepadId>)>,
+}
+
+impl C64Keyboard {
+    /// What to wire ports A and B of the first CIA to
+    pub fn ports(self: &Arc<Self>) -> (PortWiring, PortWiring) {
+        (
+            PortWiring::Connection(Arc::new(KeyboardPort {
+                keyboard: self.clone(),
+                columns: true,
+            })),
+            PortWiring::Connection(Arc::new(KeyboardPort {
+                keyboard: self.clone(),
+                columns: false,
+            })),
+        )
+    }
+
+    fn held(&self, gamepad_index: usize, input: Input) -> bool {
+        let Some((input_manager, gamepad_ids)) = self.input_manager.get() else {
+            return false;
+        };
+
+        gamepad_ids
+            .get(gamepad_index)
+            .is_some_and(|gamepad_id| input_manager.get_input(*gamepad_id, input).as_digital())
+    }
+
+    fn joystick(&self, gamepad_index: usize) -> u8 {
+        JOYSTICK_BUTTONS
+            .into_iter()
+            .enumerate()
+            .filter(|(_, button)| self.held(gamepad_index, Input::Gamepad(*button)))
+            .fold(0xff, |pins, (bit, _)| pins & !(1 << bit))
+    }
+
+    /// Level of the column pins if `columns`, otherwise the row pins, with held keys connecting them to whatever
+    /// the other port is pulling low
+    fn matrix(&self, columns: bool) -> u8 {
+        let state = self.state.lock().unwrap();
+
+        MATRIX
+            .iter()
+            .filter(|(_, _, key)| self.held(KEYBOARD, Input::Keyboard(*key)))
+            .fold(0xff, |pins, (column, row, _)| {
+                let (from, to, driven) = if columns {
+                    (row, column, state.rows_driven)
+                } else {
+                    (column, row, state.columns_driven)
+                };
+
+                if driven & (1 << from) != 0 {
+                    pins & !(1 << to)
+                } else {
+                    pins
+                }
+            })
+    }
+}
+
+impl Component for C64Keyboard {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = C64KeyboardState::default();
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+    }
+}
+
+impl FromConfig for C64Keyboard {
+    type Config = ();
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, _config: Self::Config) {
+        let keyboard_inputs: HashSet<_> = MATRIX
+            .iter()
+            .map(|(_, _, key)| Input::Keyboard(*key))
+            .collect();
+        let joystick_inputs: HashSet<_> =
+            JOYSTICK_BUTTONS.into_iter().map(Input::Gamepad).collect();
+
+        component_builder
+            .set_component(Self {
+                state: Mutex::default(),
+                input_manager: OnceLock::default(),
+            })
+            .set_input(
+                [
+                    (
+                        C64_KEYBOARD_GAMEPAD_TYPE,
+                        EmulatedGamepadMetadata {
+                            default_bindings: identity_bindings(&keyboard_inputs),
+                            present_inputs: keyboard_inputs,
+                        },
+                    ),
+                    (
+                        C64_JOYSTICK_GAMEPAD_TYPE,
+                        EmulatedGamepadMetadata {
+                            default_bindings: identity_bindings(&joystick_inputs),
+                            present_inputs: joystick_inputs,
+                        },
+                    ),
+                ],
+                [
+                    C64_KEYBOARD_GAMEPAD_TYPE,
+                    C64_JOYSTICK_GAMEPAD_TYPE,
+                    C64_JOYSTICK_GAMEPAD_TYPE,
+                ],
+            );
+    }
+}
+
+impl InputComponent for C64Keyboard {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.input_manager
+            .set((input_manager, gamepad_ids.to_vec()))
+            .expect("Input manager set multiple times");
+    }
+
+    fn character_keys(&self, character: char) -> Option<(EmulatedGamepadId, Vec<Input>)> {
+        let (_, gamepad_ids) = self.input_manager.get()?;
+        let gamepad_id = *gamepad_ids.get(KEYBOARD)?;
+
+        let keys = if let Some(key) = unshifted_key(character) {
+            vec![Input::Keyboard(key)]
+        } else {
+            let (_, key) = SHIFTED_CHARACTERS
+                .iter()
+                .find(|(shifted, _)| *shifted == character)?;
+            vec![
+                Input::Keyboard(KeyboardInput::ShiftLeft),
+                Input::Keyboard(*key),
+            ]
+        };
+
+        Some((gamepad_id, keys))
+    }
+}
+
+/// One of the two ports of the first CIA
+#[derive(Debug)]
+struct KeyboardPort {
+    keyboard: Arc<C64Keyboard>,
+    /// Port A with the columns and control port 2, rather than port B with the rows and control port 1
+    columns: bool,
+}
+
+impl PortConnection for KeyboardPort {
+    fn read_pins(&self) -> u8 {
+        let joystick = if self.columns {
+            CONTROL_PORT_2
+        } else {
+            CONTROL_PORT_1
+        };
+
+        self.keyboard.matrix(self.columns) & self.keyboard.joystick(joystick)
+    }
+
+    fn write_pins(&self, value: u8, direction: u8) {
+        let mut state = self.keyboard.state.lock().unwrap();
+        let driven = !value & direction;
+
+        if self.columns {
+            state.columns_driven = driven;
+        } else {
+            state.rows_driven = driven;
+        }
+    }
+}
+
+fn identity_bindings(inputs: &HashSet<Input>) -> HashMap<Input, Input> {
+    inputs.iter().map(|input| (*input, *input)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matrix_is_complete() {
+        let keys: HashSet<_> = MATRIX.iter().map(|(_, _, key)| key).collect();
+        let positions: HashSet<_> = MATRIX
+            .iter()
+            .map(|(column, row, _)| (column, row))
+            .collect();
+
+        assert_eq!(keys.len(), 64);
+        assert_eq!(positions.len(), 64);
+    }
+}
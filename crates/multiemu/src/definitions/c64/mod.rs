@@ -0,0 +1,234 @@
+use super::misc::{
+    audio::sid::{Sid, SidConfig, SidKind},
+    io::{
+        cia::{M6526Config, M6526},
+        PortWiring,
+    },
+    memory::{
+        rom::{RomMemory, RomMemoryConfig},
+        standard::{StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents},
+    },
+    processor::{
+        m6502::{M6502Config, M6502Interrupt, M6502Kind, UndocumentedOpcodes, M6502},
+        WaitStates,
+    },
+    video::vic2::{Vic2, Vic2Config, Vic2Region, VIC2_DEFAULT_PALETTE},
+};
+use crate::{
+    machine::{validation::MachineConfigurationError, Machine},
+    memory::AddressSpaceId,
+    rom::{
+        id::RomId,
+        manager::{RomManager, RomRequirement},
+        system::{GameSystem, OtherSystem},
+    },
+    runtime::color::Palette,
+    tape::Tape,
+};
+use cartridge::{C64Cartridge, C64CartridgeConfig};
+use datasette::{C64Datasette, C64DatasetteConfig};
+use keyboard::C64Keyboard;
+use num::rational::Ratio;
+use pla::{C64Pla, C64PlaConfig};
+use std::{sync::Arc, time::Duration};
+use vic_bank::C64VicBank;
+
+mod cartridge;
+mod datasette;
+mod keyboard;
+mod pla;
+mod vic_bank;
+
+/// What the processor sees, through the PLA
+pub const C64_CPU_ADDRESS_SPACE_ID: AddressSpaceId = 0;
+pub const C64_RAM_ADDRESS_SPACE_ID: AddressSpaceId = 1;
+/// BASIC, the character generator and the KERNAL, each at the address the PLA puts it at
+pub const C64_ROM_ADDRESS_SPACE_ID: AddressSpaceId = 2;
+/// The chips and color RAM from 0xd000 to 0xdfff
+pub const C64_IO_ADDRESS_SPACE_ID: AddressSpaceId = 3;
+pub const C64_CARTRIDGE_ADDRESS_SPACE_ID: AddressSpaceId = 4;
+/// The 16KB the VIC-II sees, out of whichever bank the second CIA picks
+pub const C64_VIC_ADDRESS_SPACE_ID: AddressSpaceId = 5;
+
+/// The PAL color burst times four, divided by 18
+pub const C64_PAL_FREQUENCY: Ratio<u64> = Ratio::new_raw(17_734_475, 18);
+/// 312 lines of 63 cycles
+pub const C64_PAL_FRAME_RATE: Ratio<u64> = Ratio::new_raw(17_734_475, 18 * 312 * 63);
+
+/// The KERNAL testing RAM and BASIC printing its banner, with a little to spare
+pub const C64_BOOT_DURATION: Duration = Duration::from_secs(3);
+
+/// 901226-01
+pub const C64_BASIC: RomId = RomId::new([
+    0x79, 0x01, 0x53, 0x23, 0x12, 0x86, 0x50, 0xc7, 0x42, 0xa3, 0x69, 0x4c, 0x94, 0x29, 0xaa, 0x91,
+    0xf3, 0x55, 0x90, 0x5e,
+]);
+/// 901227-03
+pub const C64_KERNAL: RomId = RomId::new([
+    0x1d, 0x50, 0x3e, 0x56, 0xdf, 0x85, 0xa6, 0x2f, 0xee, 0x69, 0x6e, 0x76, 0x18, 0xdc, 0x5b, 0x4e,
+    0x78, 0x1d, 0xf1, 0xbb,
+]);
+/// 901225-01
+pub const C64_CHARACTERS: RomId = RomId::new([
+    0xad, 0xc7, 0xc3, 0x1e, 0x18, 0xc7, 0xc7, 0x41, 0x3d, 0x54, 0x80, 0x2e, 0xf2, 0xf4, 0x19, 0x3d,
+    0xa1, 0x47, 0x11, 0xaa,
+]);
+
+/// A PAL C64 with the cartridge in the expansion port, or the tape in the datasette
+///
+/// TODO: NTSC machines, and the character ROM the VIC-II sees with an Ultimax cartridge
+pub fn c64_machine(
+    user_specified_roms: Vec<RomId>,
+    rom_manager: Arc<RomManager>,
+) -> Result<Machine, MachineConfigurationError> {
+    let machine = Machine::build(GameSystem::Other(OtherSystem::Commodore64), rom_manager);
+    let machine = [
+        C64_CPU_ADDRESS_SPACE_ID,
+        C64_RAM_ADDRESS_SPACE_ID,
+        C64_ROM_ADDRESS_SPACE_ID,
+        C64_IO_ADDRESS_SPACE_ID,
+        C64_CARTRIDGE_ADDRESS_SPACE_ID,
+    ]
+    .into_iter()
+    .fold(machine, |machine, address_space| {
+        machine.insert_bus(address_space, 16)
+    })
+    .insert_bus(C64_VIC_ADDRESS_SPACE_ID, 14);
+    let machine = machine.display_clock(C64_PAL_FRAME_RATE);
+    let palette = Palette::load_for_system(machine.system, &VIC2_DEFAULT_PALETTE);
+    // Badlines and sprites take the bus away from the processor
+    let wait_states = Arc::new(WaitStates::default());
+
+    let (machine, processor) = machine.build_component::<M6502>(M6502Config {
+        // The 6510 is a 6502 with an IO port bolted on
+        kind: M6502Kind::M6502 {
+            quirk_broken_ror: false,
+        },
+        frequency: C64_PAL_FREQUENCY,
+        assigned_address_space: C64_CPU_ADDRESS_SPACE_ID,
+        undocumented_opcodes: UndocumentedOpcodes::Full,
+        // What most C64s settle on
+        magic_constant: 0xef,
+        cycle_accurate: true,
+        wait_states: Some(wait_states.clone()),
+    });
+    let processor = machine.get_component::<M6502>(processor).unwrap();
+
+    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
+        readable: true,
+        writable: true,
+        max_word_size: 2,
+        assigned_range: 0x0000..0x10000,
+        assigned_address_space: C64_RAM_ADDRESS_SPACE_ID,
+        initial_contents: StandardMemoryInitialContents::Random,
+    });
+    let machine = [
+        (C64_BASIC, 0xa000..0xc000),
+        (C64_CHARACTERS, 0xd000..0xe000),
+        (C64_KERNAL, 0xe000..0x10000),
+    ]
+    .into_iter()
+    .fold(machine, |machine, (rom, assigned_range)| {
+        machine
+            .build_component::<RomMemory>(RomMemoryConfig {
+                rom,
+                max_word_size: 2,
+                assigned_range,
+                assigned_address_space: C64_ROM_ADDRESS_SPACE_ID,
+            })
+            .0
+    });
+
+    // Tapes go in the datasette, anything else is taken to be a cartridge
+    let tape = machine
+        .rom_manager
+        .handle(user_specified_roms[0], RomRequirement::Required)
+        .and_then(|rom| Tape::from_bytes(&rom, None).ok());
+    let (machine, cartridge) = if tape.is_none() {
+        let (machine, cartridge) = machine.build_component::<C64Cartridge>(C64CartridgeConfig {
+            rom: user_specified_roms[0],
+        });
+        (machine, Some(cartridge))
+    } else {
+        (machine, None)
+    };
+
+    let (machine, vic_bank) = machine.build_component::<C64VicBank>(());
+    let vic_bank_port = machine
+        .get_component::<C64VicBank>(vic_bank)
+        .unwrap()
+        .port();
+    let (machine, _) = machine.build_component::<Vic2>(Vic2Config {
+        region: Vic2Region::Pal,
+        frequency: C64_PAL_FREQUENCY,
+        palette,
+        assigned_address_space: C64_IO_ADDRESS_SPACE_ID,
+        assigned_range: 0xd000..0xd400,
+        memory_address_space: C64_VIC_ADDRESS_SPACE_ID,
+        color_ram_address_space: C64_IO_ADDRESS_SPACE_ID,
+        color_ram_address: 0xd800,
+        irq: Some(processor.interrupt_connection(M6502Interrupt::Irq)),
+        wait_states: Some(wait_states),
+    });
+
+    let (machine, _) = machine.build_component::<Sid>(SidConfig {
+        kind: SidKind::Mos6581,
+        frequency: C64_PAL_FREQUENCY,
+        assigned_address_space: C64_IO_ADDRESS_SPACE_ID,
+        assigned_range: 0xd400..0xd800,
+    });
+
+    // Only the low nibble of each byte is there, the rest reads back whatever was left on the bus
+    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
+        readable: true,
+        writable: true,
+        max_word_size: 2,
+        assigned_range: 0xd800..0xdc00,
+        assigned_address_space: C64_IO_ADDRESS_SPACE_ID,
+        initial_contents: StandardMemoryInitialContents::Random,
+    });
+
+    let (machine, keyboard) = machine.build_component::<C64Keyboard>(());
+    let (port_a, port_b) = machine
+        .get_component::<C64Keyboard>(keyboard)
+        .unwrap()
+        .ports();
+    let (machine, cia) = machine.build_component::<M6526>(M6526Config {
+        frequency: C64_PAL_FREQUENCY,
+        assigned_address_space: C64_IO_ADDRESS_SPACE_ID,
+        assigned_range: 0xdc00..0xdd00,
+        port_a,
+        port_b,
+        gamepads: Vec::new(),
+        irq: Some(processor.interrupt_connection(M6502Interrupt::Irq)),
+    });
+    // The VIC-II bank, then the serial bus and the user port, which have nothing to talk to yet
+    let (machine, _) = machine.build_component::<M6526>(M6526Config {
+        frequency: C64_PAL_FREQUENCY,
+        assigned_address_space: C64_IO_ADDRESS_SPACE_ID,
+        assigned_range: 0xdd00..0xde00,
+        port_a: vic_bank_port,
+        port_b: PortWiring::Unconnected,
+        gamepads: Vec::new(),
+        // CIA2 drives NMI instead, which it shares with the restore key on the real machine
+        irq: Some(processor.interrupt_connection(M6502Interrupt::Nmi)),
+    });
+
+    // Cartridges start before BASIC does, so only tapes wait on the boot
+    let machine = if tape.is_some() {
+        machine.boot_duration(C64_BOOT_DURATION)
+    } else {
+        machine
+    };
+    let (machine, datasette) = machine.build_component::<C64Datasette>(C64DatasetteConfig {
+        frequency: C64_PAL_FREQUENCY,
+        tape,
+        cia,
+    });
+    let (machine, _) = machine.build_component::<C64Pla>(C64PlaConfig {
+        cartridge,
+        datasette: Some(datasette),
+    });
+
+    machine.try_build()
+}
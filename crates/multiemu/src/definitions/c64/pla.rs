@@ -0,0 +1,351 @@
+use super::{
+    cartridge::{C64Cartridge, CartridgeLines},
+    datasette::C64Datasette,
+    C64_CARTRIDGE_ADDRESS_SPACE_ID, C64_CPU_ADDRESS_SPACE_ID, C64_IO_ADDRESS_SPACE_ID,
+    C64_RAM_ADDRESS_SPACE_ID, C64_ROM_ADDRESS_SPACE_ID,
+};
+use crate::{
+    component::{memory::MemoryComponent, tape::TapeComponent, Component, ComponentId, FromConfig},
+    definitions::misc::io::PortRegisters,
+    machine::ComponentBuilder,
+    memory::{
+        AddressSpaceId, MemoryTranslationTable, PreviewMemoryRecord, ReadMemoryRecord,
+        WriteMemoryRecord,
+    },
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, OnceLock};
+
+const PORT_DIRECTION: usize = 0x0000;
+const PORT_DATA: usize = 0x0001;
+
+const LORAM: u8 = 0b0000_0001;
+const HIRAM: u8 = 0b0000_0010;
+const CHAREN: u8 = 0b0000_0100;
+/// Pulled low by any of the datasette's buttons
+const CASSETTE_SENSE: u8 = 0b0001_0000;
+/// Runs the datasette motor when driven low
+const CASSETTE_MOTOR: u8 = 0b0010_0000;
+/// The banking lines and the cassette sense are pulled up, the rest of the pins read low when not driven
+const PORT_PULL_UPS: u8 = 0b0001_0111;
+
+#[derive(Debug)]
+pub(super) struct C64PlaConfig {
+    /// Whatever is in the expansion port
+    pub cartridge: Option<ComponentId>,
+    /// The datasette on the cassette port
+    pub datasette: Option<ComponentId>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct C64PlaState {
+    /// The 6510's own I/O port at 0x0000 and 0x0001
+    port: PortRegisters,
+}
+
+impl C64PlaState {
+    /// Level of the port pins, inputs reading what is pulling them
+    fn port_pins(&self) -> u8 {
+        (self.port.output & self.port.direction) | (PORT_PULL_UPS & !self.port.direction)
+    }
+}
+
+/// Which bus the processor reaches at an address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bank {
+    Ram,
+    Rom,
+    Io,
+    Cartridge,
+    /// Nothing answers, which only happens with an Ultimax cartridge
+    Open,
+}
+
+/// The PLA, and the processor port that feeds it, deciding whether RAM, ROM, I/O or the cartridge answers the
+/// processor at each address
+///
+/// The port really is part of the 6510, it lives here since it only matters for banking. Everything the PLA picks
+/// from sits on a bus of its own, and accesses are passed through to whichever one is selected. Writes to ROM land
+/// in the RAM underneath
+#[derive(Debug)]
+pub(super) struct C64Pla {
+    cartridge_lines: CartridgeLines,
+    datasette: Option<Arc<C64Datasette>>,
+    state: Mutex<C64PlaState>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+}
+
+impl C64Pla {
+    /// Ultimax mode takes the cartridge as the whole top of memory and leaves most of the RAM unmapped
+    fn ultimax(&self) -> bool {
+        self.cartridge_lines.game && !self.cartridge_lines.exrom
+    }
+
+    fn bank(&self, state: &C64PlaState, address: usize) -> Bank {
+        let pins = state.port_pins();
+        let loram = pins & LORAM != 0;
+        let hiram = pins & HIRAM != 0;
+        let charen = pins & CHAREN != 0;
+        let CartridgeLines { exrom, game } = self.cartridge_lines;
+
+        if self.ultimax() {
+            return match address {
+                0x0000..0x1000 => Bank::Ram,
+                0x8000..0xa000 | 0xe000..0x10000 => Bank::Cartridge,
+                0xd000..0xe000 => Bank::Io,
+                _ => Bank::Open,
+            };
+        }
+
+        match address {
+            0x8000..0xa000 if exrom && loram && hiram => Bank::Cartridge,
+            0xa000..0xc000 if exrom && game && hiram => Bank::Cartridge,
+            0xa000..0xc000 if loram && hiram => Bank::Rom,
+            0xd000..0xe000 if loram || hiram => {
+                if charen {
+                    Bank::Io
+                } else {
+                    Bank::Rom
+                }
+            }
+            0xe000..0x10000 if hiram => Bank::Rom,
+            _ => Bank::Ram,
+        }
+    }
+
+    fn read_address_space(&self, state: &C64PlaState, address: usize) -> Option<AddressSpaceId> {
+        match self.bank(state, address) {
+            Bank::Ram => Some(C64_RAM_ADDRESS_SPACE_ID),
+            Bank::Rom => Some(C64_ROM_ADDRESS_SPACE_ID),
+            Bank::Io => Some(C64_IO_ADDRESS_SPACE_ID),
+            Bank::Cartridge => Some(C64_CARTRIDGE_ADDRESS_SPACE_ID),
+            Bank::Open => None,
+        }
+    }
+
+    fn write_address_space(&self, state: &C64PlaState, address: usize) -> Option<AddressSpaceId> {
+        match self.bank(state, address) {
+            Bank::Ram | Bank::Rom => Some(C64_RAM_ADDRESS_SPACE_ID),
+            Bank::Io => Some(C64_IO_ADDRESS_SPACE_ID),
+            // Only Ultimax cartridges see writes, which can have RAM of their own
+            Bank::Cartridge if self.ultimax() => Some(C64_CARTRIDGE_ADDRESS_SPACE_ID),
+            Bank::Cartridge => Some(C64_RAM_ADDRESS_SPACE_ID),
+            Bank::Open => None,
+        }
+    }
+
+    fn read_port(&self, state: &C64PlaState, address: usize) -> u8 {
+        match address {
+            PORT_DIRECTION => state.port.direction,
+            _ => {
+                let play_pressed = self
+                    .datasette
+                    .as_ref()
+                    .is_some_and(|datasette| datasette.deck().lock().unwrap().state.playing);
+
+                if play_pressed && state.port.direction & CASSETTE_SENSE == 0 {
+                    state.port_pins() & !CASSETTE_SENSE
+                } else {
+                    state.port_pins()
+                }
+            }
+        }
+    }
+
+    fn update_motor(&self, state: &C64PlaState) {
+        if let Some(datasette) = &self.datasette {
+            datasette.deck().lock().unwrap().set_motor(
+                state.port.direction & CASSETTE_MOTOR != 0
+                    && state.port.output & CASSETTE_MOTOR == 0,
+            );
+        }
+    }
+
+    fn mappings_changed(&self) {
+        if let Some(memory_translation_table) = self.memory_translation_table.get() {
+            memory_translation_table.mappings_changed(C64_CPU_ADDRESS_SPACE_ID);
+        }
+    }
+
+    /// Reads through to the selected bus, with `preview` for the debugger looking
+    fn read(&self, address: usize, buffer: &mut [u8], preview: bool) {
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        let state = self.state.lock().unwrap();
+
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            if address <= PORT_DATA {
+                *byte = self.read_port(&state, address);
+                continue;
+            }
+
+            let mut value = [0xff];
+            if let Some(address_space) = self.read_address_space(&state, address) {
+                // Nothing answering leaves the bus floating high
+                let _ = if preview {
+                    memory_translation_table.preview(address, &mut value, address_space)
+                } else {
+                    memory_translation_table.read(address, &mut value, address_space)
+                };
+            }
+            *byte = value[0];
+        }
+    }
+}
+
+impl Component for C64Pla {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = C64PlaState::default();
+        self.update_motor(&state);
+        self.mappings_changed();
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, snapshot: rmpv::Value) {
+        let mut state = self.state.lock().unwrap();
+        *state = rmpv::ext::from_value(snapshot).unwrap();
+        self.update_motor(&state);
+        self.mappings_changed();
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for C64Pla {
+    type Config = C64PlaConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let cartridge_lines = config
+            .cartridge
+            .map(|cartridge| {
+                component_builder
+                    .machine()
+                    .get_component::<C64Cartridge>(cartridge)
+                    .expect("C64 cartridge is missing")
+                    .lines()
+            })
+            .unwrap_or_default();
+        let datasette = config.datasette.map(|datasette| {
+            component_builder
+                .machine()
+                .get_component::<C64Datasette>(datasette)
+                .expect("C64 datasette is missing")
+        });
+
+        component_builder
+            .set_component(Self {
+                cartridge_lines,
+                datasette,
+                state: Mutex::default(),
+                memory_translation_table: OnceLock::new(),
+            })
+            .set_memory([(C64_CPU_ADDRESS_SPACE_ID, 0x0000..0x10000)]);
+    }
+}
+
+impl MemoryComponent for C64Pla {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        self.read(address, buffer, false);
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        let mut state = self.state.lock().unwrap();
+
+        for (address, value) in (address..).zip(buffer.iter().copied()) {
+            if address <= PORT_DATA {
+                let pins = state.port_pins();
+                match address {
+                    PORT_DIRECTION => state.port.direction = value,
+                    _ => state.port.output = value,
+                }
+
+                if state.port_pins() & (LORAM | HIRAM | CHAREN) != pins & (LORAM | HIRAM | CHAREN) {
+                    self.mappings_changed();
+                }
+                self.update_motor(&state);
+            }
+
+            // The port doesn't stop the write reaching the RAM underneath it
+            if let Some(address_space) = self.write_address_space(&state, address) {
+                // Writes to ROM just go nowhere
+                let _ = memory_translation_table.write(address, &[value], address_space);
+            }
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        self.read(address, buffer, true);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn banking_through_the_processor_port() {
+        // RAM, ROM and I/O each filled with their own number
+        let (builder, _) = TestMachineBuilder::new()
+            .bus(C64_CPU_ADDRESS_SPACE_ID, 16)
+            .bus(C64_RAM_ADDRESS_SPACE_ID, 16)
+            .bus(C64_ROM_ADDRESS_SPACE_ID, 16)
+            .bus(C64_IO_ADDRESS_SPACE_ID, 16)
+            .bus(C64_CARTRIDGE_ADDRESS_SPACE_ID, 16)
+            .scratch_ram(C64_RAM_ADDRESS_SPACE_ID, 0x0000..0x10000, 1)
+            .scratch_ram(C64_ROM_ADDRESS_SPACE_ID, 0xa000..0xc000, 2)
+            .scratch_ram(C64_ROM_ADDRESS_SPACE_ID, 0xd000..0x10000, 2)
+            .scratch_ram(C64_IO_ADDRESS_SPACE_ID, 0xd000..0xe000, 3)
+            .component::<C64Pla>(C64PlaConfig {
+                cartridge: None,
+                datasette: None,
+            });
+        let machine = builder.build();
+        let peek = |address| machine.peek(C64_CPU_ADDRESS_SPACE_ID, address, 1)[0];
+
+        // The banking lines float high, putting BASIC, I/O and the KERNAL in
+        assert_eq!(peek(0x0001), PORT_PULL_UPS);
+        assert_eq!(
+            [peek(0x8000), peek(0xa000), peek(0xd000), peek(0xe000)],
+            [1, 2, 3, 2]
+        );
+
+        // Writes to ROM land in the RAM underneath
+        machine.load(C64_CPU_ADDRESS_SPACE_ID, 0xa000, &[0x42]);
+        assert_eq!(peek(0xa000), 2);
+
+        // Character ROM in place of I/O, and BASIC out
+        machine.load(C64_CPU_ADDRESS_SPACE_ID, PORT_DIRECTION, &[0b0000_0111]);
+        machine.load(C64_CPU_ADDRESS_SPACE_ID, PORT_DATA, &[HIRAM]);
+        assert_eq!([peek(0xa000), peek(0xd000), peek(0xe000)], [0x42, 2, 2]);
+
+        // All RAM
+        machine.load(C64_CPU_ADDRESS_SPACE_ID, PORT_DATA, &[0]);
+        assert_eq!([peek(0xd000), peek(0xe000)], [1, 1]);
+    }
+}
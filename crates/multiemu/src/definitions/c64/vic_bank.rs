@@ -0,0 +1,171 @@
+use super::{C64_RAM_ADDRESS_SPACE_ID, C64_ROM_ADDRESS_SPACE_ID, C64_VIC_ADDRESS_SPACE_ID};
+use crate::{
+    component::{memory::MemoryComponent, Component, FromConfig},
+    definitions::misc::io::{PortConnection, PortWiring},
+    machine::ComponentBuilder,
+    memory::{
+        AddressSpaceId, MemoryTranslationTable, PreviewMemoryRecord, ReadMemoryRecord,
+        WriteMemoryRecord,
+    },
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+const BANK_SIZE: usize = 0x4000;
+/// Where the character ROM shows up in the banks that have it
+const CHARACTER_ROM_WINDOW: Range<usize> = 0x1000..0x2000;
+const CHARACTER_ROM_START: usize = 0xd000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct C64VicBankState {
+    /// Which 16KB of RAM the VIC-II sees, 0 being the bottom
+    bank: u8,
+}
+
+/// Which 16KB of memory the VIC-II sees, picked by the low two pins of port A of the second CIA
+///
+/// The pins are inverted, so with them floating high the chip sees the bottom of RAM. Banks 0 and 2 have the
+/// character ROM in place of RAM from 0x1000 to 0x1fff
+#[derive(Debug)]
+pub(super) struct C64VicBank {
+    state: Mutex<C64VicBankState>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+}
+
+impl C64VicBank {
+    /// What to wire port A of the second CIA to
+    pub fn port(self: &Arc<Self>) -> PortWiring {
+        PortWiring::Connection(Arc::new(VicBankPort {
+            vic_bank: self.clone(),
+        }))
+    }
+}
+
+impl Component for C64VicBank {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = C64VicBankState::default();
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for C64VicBank {
+    type Config = ();
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, _config: Self::Config) {
+        component_builder
+            .set_component(Self {
+                state: Mutex::default(),
+                memory_translation_table: OnceLock::new(),
+            })
+            .set_memory([(C64_VIC_ADDRESS_SPACE_ID, 0x0000..BANK_SIZE)]);
+    }
+}
+
+impl MemoryComponent for C64VicBank {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        self.preview_memory(address, buffer, address_space, &mut RangeMap::default());
+    }
+
+    fn write_memory(
+        &self,
+        _address: usize,
+        _buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        // The VIC-II never writes
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        let bank = self.state.lock().unwrap().bank as usize;
+
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            let (address_space, address) =
+                if bank % 2 == 0 && CHARACTER_ROM_WINDOW.contains(&address) {
+                    (
+                        C64_ROM_ADDRESS_SPACE_ID,
+                        CHARACTER_ROM_START + address - CHARACTER_ROM_WINDOW.start,
+                    )
+                } else {
+                    (C64_RAM_ADDRESS_SPACE_ID, bank * BANK_SIZE + address)
+                };
+
+            let mut value = [0xff];
+            let _ = memory_translation_table.preview(address, &mut value, address_space);
+            *byte = value[0];
+        }
+    }
+}
+
+/// Port A of the second CIA, of which only the bank pins matter here
+#[derive(Debug)]
+struct VicBankPort {
+    vic_bank: Arc<C64VicBank>,
+}
+
+impl PortConnection for VicBankPort {
+    fn write_pins(&self, value: u8, direction: u8) {
+        // Pins the CIA isn't driving are pulled up
+        let pins = (value & direction) | !direction;
+
+        self.vic_bank.state.lock().unwrap().bank = !pins & 0b11;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn banks_follow_the_port() {
+        let (builder, vic_bank) = TestMachineBuilder::new()
+            .bus(C64_RAM_ADDRESS_SPACE_ID, 16)
+            .bus(C64_ROM_ADDRESS_SPACE_ID, 16)
+            .bus(C64_VIC_ADDRESS_SPACE_ID, 14)
+            .scratch_ram(C64_RAM_ADDRESS_SPACE_ID, 0x0000..0x10000, 1)
+            .scratch_ram(C64_ROM_ADDRESS_SPACE_ID, 0xd000..0xe000, 2)
+            .component::<C64VicBank>(());
+        let machine = builder.build();
+        machine.load(C64_RAM_ADDRESS_SPACE_ID, 0xc000, &[3]);
+        let peek = |address| machine.peek(C64_VIC_ADDRESS_SPACE_ID, address, 1)[0];
+        let port = machine.component::<C64VicBank>(vic_bank).port();
+        let PortWiring::Connection(port) = port else {
+            unreachable!()
+        };
+
+        assert_eq!([peek(0x0000), peek(0x1000), peek(0x2000)], [1, 2, 1]);
+
+        // Driving both pins low is the top bank, which has no character ROM
+        port.write_pins(0b0000_0000, 0b0000_0011);
+        assert_eq!([peek(0x0000), peek(0x1000)], [3, 1]);
+    }
+}
@@ -0,0 +1,681 @@
+use crate::rom::id::RomId;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Where programs are loaded, and so where the first assembled byte ends up
+const PROGRAM_START: u16 = 0x200;
+/// Programs can't go past the end of the 4k address space
+const MAX_PROGRAM_SIZE: usize = 0x1000 - PROGRAM_START as usize;
+
+/// File extensions Octo source goes by
+pub const OCTO_EXTENSIONS: [&str; 2] = ["8o", "o8"];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AssemblyErrorKind {
+    #[error("Source ended in the middle of a statement")]
+    UnexpectedEnd,
+    #[error("Did not expect {0}")]
+    UnexpectedToken(String),
+    #[error("Expected a register, found {0}")]
+    ExpectedRegister(String),
+    #[error("Expected a number, found {0}")]
+    ExpectedNumber(String),
+    #[error("{0} does not fit here")]
+    OutOfRange(i64),
+    #[error("Label {0} is never defined")]
+    UndefinedLabel(String),
+    #[error("Label {0} is defined twice")]
+    DuplicateLabel(String),
+    #[error("{0} without a matching block")]
+    Unbalanced(&'static str),
+    #[error("Block opened here is never closed")]
+    Unclosed,
+    #[error("{0} is not supported by this assembler")]
+    Unsupported(String),
+    #[error("Program is larger than the {MAX_PROGRAM_SIZE} bytes of memory available")]
+    TooLarge,
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("Line {line}: {kind}")]
+pub struct AssemblyError {
+    pub line: usize,
+    pub kind: AssemblyErrorKind,
+}
+
+#[derive(Error, Debug)]
+pub enum OctoLoadError {
+    #[error("Could not access {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Could not assemble {path}: {error}")]
+    Assembly { path: PathBuf, error: AssemblyError },
+}
+
+pub fn is_octo_source(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            OCTO_EXTENSIONS
+                .iter()
+                .any(|octo_extension| extension.eq_ignore_ascii_case(octo_extension))
+        })
+}
+
+/// Assembles an Octo source file and stores the result in the ROM store, named after its hash like imported ROMs
+///
+/// Returns the id and path of the assembled ROM
+pub fn assemble_into_store(
+    source_path: &Path,
+    store: &Path,
+) -> Result<(RomId, PathBuf), OctoLoadError> {
+    let source = fs::read_to_string(source_path).map_err(|error| OctoLoadError::Io {
+        path: source_path.to_path_buf(),
+        error,
+    })?;
+    let program = assemble(&source).map_err(|error| OctoLoadError::Assembly {
+        path: source_path.to_path_buf(),
+        error,
+    })?;
+
+    let rom_id = RomId::from_read(&mut program.as_slice());
+    let rom_path = store.join(rom_id.to_string());
+
+    fs::create_dir_all(store)
+        .and_then(|_| fs::write(&rom_path, &program))
+        .map_err(|error| OctoLoadError::Io {
+            path: rom_path.clone(),
+            error,
+        })?;
+
+    tracing::info!(
+        "Assembled {} into {} bytes as {}",
+        source_path.display(),
+        program.len(),
+        rom_id
+    );
+
+    Ok((rom_id, rom_path))
+}
+
+/// Assembles Octo source into a program to be loaded at 0x200
+///
+/// Covers the instructions, labels, constants, aliases and structured control flow. Macros, `:calc` and the
+/// XO-CHIP extensions are not supported
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblyError> {
+    let tokens = source
+        .lines()
+        .enumerate()
+        .flat_map(|(line, text)| {
+            let code = text.split('#').next().unwrap_or_default();
+            code.split_whitespace().map(move |token| (line + 1, token))
+        })
+        .collect();
+
+    let mut assembler = Assembler {
+        tokens,
+        position: 0,
+        line: 1,
+        output: Vec::new(),
+        labels: HashMap::default(),
+        constants: HashMap::default(),
+        aliases: HashMap::default(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+    };
+
+    while let Some(token) = assembler.next_token() {
+        assembler.statement(token)?;
+    }
+
+    assembler.finish()
+}
+
+/// A jump or call whose target was not known yet when it was emitted
+struct Fixup {
+    offset: usize,
+    label: String,
+    line: usize,
+}
+
+enum Block {
+    Loop {
+        start: u16,
+        breaks: Vec<usize>,
+        line: usize,
+    },
+    /// Offset of the jump taken when the condition is false
+    If { jump: usize, line: usize },
+    /// Offset of the jump over the else branch
+    Else { jump: usize, line: usize },
+}
+
+/// The two ways of skipping the next instruction on a condition
+struct Condition {
+    skip_if_false: u16,
+    skip_if_true: u16,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<(usize, &'a str)>,
+    position: usize,
+    line: usize,
+    output: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u8>,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Assembler<'a> {
+    fn error(&self, kind: AssemblyErrorKind) -> AssemblyError {
+        AssemblyError {
+            line: self.line,
+            kind,
+        }
+    }
+
+    fn next_token(&mut self) -> Option<&'a str> {
+        let (line, token) = *self.tokens.get(self.position)?;
+        self.position += 1;
+        self.line = line;
+
+        Some(token)
+    }
+
+    fn expect_token(&mut self) -> Result<&'a str, AssemblyError> {
+        self.next_token()
+            .ok_or_else(|| self.error(AssemblyErrorKind::UnexpectedEnd))
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), AssemblyError> {
+        let token = self.expect_token()?;
+
+        if token != expected {
+            return Err(self.error(AssemblyErrorKind::UnexpectedToken(token.to_string())));
+        }
+
+        Ok(())
+    }
+
+    fn address(&self) -> u16 {
+        PROGRAM_START + self.output.len() as u16
+    }
+
+    fn emit(&mut self, instruction: u16) {
+        self.output.extend_from_slice(&instruction.to_be_bytes());
+    }
+
+    fn patch(&mut self, offset: usize, address: u16) {
+        let instruction = u16::from_be_bytes([self.output[offset], self.output[offset + 1]]);
+        let instruction = (instruction & 0xf000) | (address & 0x0fff);
+
+        self.output[offset..offset + 2].copy_from_slice(&instruction.to_be_bytes());
+    }
+
+    fn register(&self, token: &str) -> Option<u8> {
+        if let Some(register) = self.aliases.get(token) {
+            return Some(*register);
+        }
+
+        let index = token.strip_prefix(['v', 'V'])?;
+
+        if index.len() != 1 {
+            return None;
+        }
+
+        u8::from_str_radix(index, 16).ok()
+    }
+
+    fn expect_register(&mut self) -> Result<u8, AssemblyError> {
+        let token = self.expect_token()?;
+
+        self.register(token)
+            .ok_or_else(|| self.error(AssemblyErrorKind::ExpectedRegister(token.to_string())))
+    }
+
+    fn number(&self, token: &str) -> Option<i64> {
+        if let Some(value) = self.constants.get(token) {
+            return Some(*value);
+        }
+
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i64::from_str_radix(hex, 16).ok()?
+        } else if let Some(binary) = digits.strip_prefix("0b") {
+            i64::from_str_radix(binary, 2).ok()?
+        } else {
+            digits.parse().ok()?
+        };
+
+        Some(if negative { -value } else { value })
+    }
+
+    fn expect_number(
+        &mut self,
+        range: std::ops::RangeInclusive<i64>,
+    ) -> Result<i64, AssemblyError> {
+        let token = self.expect_token()?;
+        let value = self
+            .number(token)
+            .ok_or_else(|| self.error(AssemblyErrorKind::ExpectedNumber(token.to_string())))?;
+
+        if !range.contains(&value) {
+            return Err(self.error(AssemblyErrorKind::OutOfRange(value)));
+        }
+
+        Ok(value)
+    }
+
+    /// Bytes can be written signed or unsigned
+    fn expect_byte(&mut self) -> Result<u8, AssemblyError> {
+        Ok(self.expect_number(-128..=255)? as u8)
+    }
+
+    /// Emits an instruction with a 12 bit address, filled in later if the label isn't defined yet
+    fn emit_address(&mut self, opcode: u16) -> Result<(), AssemblyError> {
+        let token = self.expect_token()?;
+
+        if let Some(address) = self.number(token) {
+            if !(0..=0xfff).contains(&address) {
+                return Err(self.error(AssemblyErrorKind::OutOfRange(address)));
+            }
+
+            self.emit(opcode | address as u16);
+        } else if let Some(address) = self.labels.get(token) {
+            self.emit(opcode | address);
+        } else {
+            self.fixups.push(Fixup {
+                offset: self.output.len(),
+                label: token.to_string(),
+                line: self.line,
+            });
+            self.emit(opcode);
+        }
+
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<Condition, AssemblyError> {
+        let x = self.expect_register()? as u16;
+        let operator = self.expect_token()?;
+
+        let (skip_if_false, skip_if_true) = match operator {
+            "key" => (0xe0a1 | x << 8, 0xe09e | x << 8),
+            "-key" => (0xe09e | x << 8, 0xe0a1 | x << 8),
+            "==" | "!=" => {
+                let token = self.expect_token()?;
+
+                let (not_equal, equal) = if let Some(y) = self.register(token) {
+                    (
+                        0x9000 | x << 8 | (y as u16) << 4,
+                        0x5000 | x << 8 | (y as u16) << 4,
+                    )
+                } else {
+                    self.position -= 1;
+                    let immediate = self.expect_byte()? as u16;
+
+                    (0x4000 | x << 8 | immediate, 0x3000 | x << 8 | immediate)
+                };
+
+                // Skipping when false means skipping when the opposite holds
+                if operator == "==" {
+                    (not_equal, equal)
+                } else {
+                    (equal, not_equal)
+                }
+            }
+            // These need scratch registers and code generation the rest of the assembler doesn't do
+            "<" | ">" | "<=" | ">=" => {
+                return Err(self.error(AssemblyErrorKind::Unsupported(operator.to_string())))
+            }
+            _ => return Err(self.error(AssemblyErrorKind::UnexpectedToken(operator.to_string()))),
+        };
+
+        Ok(Condition {
+            skip_if_false,
+            skip_if_true,
+        })
+    }
+
+    fn statement(&mut self, token: &'a str) -> Result<(), AssemblyError> {
+        match token {
+            ":" => {
+                let name = self.expect_token()?;
+
+                if self.labels.insert(name, self.address()).is_some() {
+                    return Err(self.error(AssemblyErrorKind::DuplicateLabel(name.to_string())));
+                }
+            }
+            ":const" => {
+                let name = self.expect_token()?;
+                let value = self.expect_number(i64::MIN..=i64::MAX)?;
+
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.expect_token()?;
+                let register = self.expect_register()?;
+
+                self.aliases.insert(name, register);
+            }
+            ":org" => {
+                let address = self.expect_number(0..=0xfff)? as u16;
+
+                if address < self.address() {
+                    return Err(self.error(AssemblyErrorKind::OutOfRange(address as i64)));
+                }
+
+                self.output.resize((address - PROGRAM_START) as usize, 0);
+            }
+            ":byte" => {
+                let byte = self.expect_byte()?;
+                self.output.push(byte);
+            }
+            ":call" => self.emit_address(0x2000)?,
+            ":breakpoint" | ":monitor" => {
+                // Debugger hints, which have nothing to say about the program itself
+                self.expect_token()?;
+                if token == ":monitor" {
+                    self.expect_token()?;
+                }
+            }
+            "clear" => self.emit(0x00e0),
+            "return" | ";" => self.emit(0x00ee),
+            "scroll-down" => {
+                let amount = self.expect_number(0..=15)? as u16;
+                self.emit(0x00c0 | amount);
+            }
+            "scroll-right" => self.emit(0x00fb),
+            "scroll-left" => self.emit(0x00fc),
+            "exit" => self.emit(0x00fd),
+            "lores" => self.emit(0x00fe),
+            "hires" => self.emit(0x00ff),
+            "jump" => self.emit_address(0x1000)?,
+            "jump0" => self.emit_address(0xb000)?,
+            "sprite" => {
+                let x = self.expect_register()? as u16;
+                let y = self.expect_register()? as u16;
+                let height = self.expect_number(0..=15)? as u16;
+
+                self.emit(0xd000 | x << 8 | y << 4 | height);
+            }
+            "bcd" => {
+                let x = self.expect_register()? as u16;
+                self.emit(0xf033 | x << 8);
+            }
+            "save" => {
+                let x = self.expect_register()? as u16;
+                self.emit(0xf055 | x << 8);
+            }
+            "load" => {
+                let x = self.expect_register()? as u16;
+                self.emit(0xf065 | x << 8);
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.expect_register()? as u16;
+
+                self.emit(if token == "delay" { 0xf015 } else { 0xf018 } | x << 8);
+            }
+            "i" => self.index_statement()?,
+            "if" => {
+                let condition = self.condition()?;
+
+                match self.expect_token()? {
+                    "then" => self.emit(condition.skip_if_false),
+                    "begin" => {
+                        self.emit(condition.skip_if_true);
+                        self.blocks.push(Block::If {
+                            jump: self.output.len(),
+                            line: self.line,
+                        });
+                        self.emit(0x1000);
+                    }
+                    token => {
+                        return Err(
+                            self.error(AssemblyErrorKind::UnexpectedToken(token.to_string()))
+                        )
+                    }
+                }
+            }
+            "else" => {
+                let Some(Block::If { jump, line }) = self.blocks.pop() else {
+                    return Err(self.error(AssemblyErrorKind::Unbalanced("else")));
+                };
+
+                let else_jump = self.output.len();
+                self.emit(0x1000);
+                self.patch(jump, self.address());
+                self.blocks.push(Block::Else {
+                    jump: else_jump,
+                    line,
+                });
+            }
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. } | Block::Else { jump, .. }) => {
+                    self.patch(jump, self.address())
+                }
+                _ => return Err(self.error(AssemblyErrorKind::Unbalanced("end"))),
+            },
+            "loop" => self.blocks.push(Block::Loop {
+                start: self.address(),
+                breaks: Vec::new(),
+                line: self.line,
+            }),
+            "while" => {
+                let condition = self.condition()?;
+                let offset = self.output.len() + 2;
+
+                let Some(Block::Loop { breaks, .. }) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                else {
+                    return Err(self.error(AssemblyErrorKind::Unbalanced("while")));
+                };
+                breaks.push(offset);
+
+                self.emit(condition.skip_if_true);
+                self.emit(0x1000);
+            }
+            "again" => {
+                let Some(Block::Loop { start, breaks, .. }) = self.blocks.pop() else {
+                    return Err(self.error(AssemblyErrorKind::Unbalanced("again")));
+                };
+
+                self.emit(0x1000 | start);
+
+                for offset in breaks {
+                    self.patch(offset, self.address());
+                }
+            }
+            token if token.starts_with(':') => {
+                return Err(self.error(AssemblyErrorKind::Unsupported(token.to_string())))
+            }
+            token => {
+                if let Some(x) = self.register(token) {
+                    self.register_statement(x as u16)?;
+                } else if let Some(value) = self.number(token) {
+                    if !(-128..=255).contains(&value) {
+                        return Err(self.error(AssemblyErrorKind::OutOfRange(value)));
+                    }
+
+                    self.output.push(value as u8);
+                } else {
+                    // A bare name calls it
+                    self.position -= 1;
+                    self.emit_address(0x2000)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn index_statement(&mut self) -> Result<(), AssemblyError> {
+        match self.expect_token()? {
+            ":=" => match self.expect_token()? {
+                "hex" => {
+                    let x = self.expect_register()? as u16;
+                    self.emit(0xf029 | x << 8);
+                }
+                "bighex" => {
+                    let x = self.expect_register()? as u16;
+                    self.emit(0xf030 | x << 8);
+                }
+                "long" => {
+                    return Err(self.error(AssemblyErrorKind::Unsupported("long".to_string())))
+                }
+                _ => {
+                    self.position -= 1;
+                    self.emit_address(0xa000)?;
+                }
+            },
+            "+=" => {
+                let x = self.expect_register()? as u16;
+                self.emit(0xf01e | x << 8);
+            }
+            token => return Err(self.error(AssemblyErrorKind::UnexpectedToken(token.to_string()))),
+        }
+
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: u16) -> Result<(), AssemblyError> {
+        let operator = self.expect_token()?;
+        let operand = self.expect_token()?;
+
+        if let Some(y) = self.register(operand) {
+            let y = y as u16;
+
+            let function = match operator {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xe,
+                _ => {
+                    return Err(self.error(AssemblyErrorKind::UnexpectedToken(operator.to_string())))
+                }
+            };
+
+            self.emit(0x8000 | x << 8 | y << 4 | function);
+            return Ok(());
+        }
+
+        match (operator, operand) {
+            (":=", "delay") => self.emit(0xf007 | x << 8),
+            (":=", "key") => self.emit(0xf00a | x << 8),
+            (":=", "random") => {
+                let mask = self.expect_byte()? as u16;
+                self.emit(0xc000 | x << 8 | mask);
+            }
+            (":=" | "+=" | "-=", _) => {
+                self.position -= 1;
+                let immediate = self.expect_byte()?;
+
+                match operator {
+                    ":=" => self.emit(0x6000 | x << 8 | immediate as u16),
+                    "+=" => self.emit(0x7000 | x << 8 | immediate as u16),
+                    // No subtract immediate instruction, but adding the negative wraps around the same way
+                    _ => self.emit(0x7000 | x << 8 | immediate.wrapping_neg() as u16),
+                }
+            }
+            _ => return Err(self.error(AssemblyErrorKind::UnexpectedToken(operator.to_string()))),
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, AssemblyError> {
+        if let Some(block) = self.blocks.last() {
+            let (Block::If { line, .. } | Block::Else { line, .. } | Block::Loop { line, .. }) =
+                block;
+
+            return Err(AssemblyError {
+                line: *line,
+                kind: AssemblyErrorKind::Unclosed,
+            });
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(address) = self.labels.get(fixup.label.as_str()).copied() else {
+                return Err(AssemblyError {
+                    line: fixup.line,
+                    kind: AssemblyErrorKind::UndefinedLabel(fixup.label),
+                });
+            };
+
+            self.patch(fixup.offset, address);
+        }
+
+        if self.output.len() > MAX_PROGRAM_SIZE {
+            return Err(self.error(AssemblyErrorKind::TooLarge));
+        }
+
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn assembles_program() {
+        let program = assemble(
+            "
+            : main
+                clear
+                v0 := 0x10
+                i := sprite
+                loop
+                    sprite v0 v1 3   # draw it
+                    v0 += -1
+                    if v0 == 0 then return
+                again
+            : sprite
+                0b11100000 0x40 0xe0
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            program,
+            [
+                0x00, 0xe0, 0x60, 0x10, 0xa2, 0x10, 0xd0, 0x13, 0x70, 0xff, 0x40, 0x00, 0x00, 0xee,
+                0x12, 0x06, 0xe0, 0x40, 0xe0
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_line() {
+        let error = assemble("clear\n\n jump nowhere").unwrap_err();
+
+        assert_eq!(
+            error,
+            AssemblyError {
+                line: 3,
+                kind: AssemblyErrorKind::UndefinedLabel("nowhere".to_string())
+            }
+        );
+    }
+}
@@ -1,40 +1,227 @@
-use std::sync::Mutex;
+use std::{collections::VecDeque, sync::Mutex};
 
 use crate::{
-    component::{schedulable::SchedulableComponent, Component, FromConfig},
+    component::{audio::AudioComponent, schedulable::SchedulableComponent, Component, FromConfig},
     machine::ComponentBuilder,
 };
+use bitvec::{order::Msb0, view::BitView};
 use num::rational::Ratio;
+use serde::{Deserialize, Serialize};
 
+/// Pitch register value that plays the pattern at 4000 bits a second
+const DEFAULT_PITCH: u8 = 64;
+/// The sound timer counts down at 60 Hz like the delay timer
+const TIMER_FREQUENCY: u64 = 60;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Chip8AudioConfig {
+    /// Pitch of the buzz in Hz, the original hardware left this up to whatever was wired to it
+    pub tone: f32,
+    /// Level of the buzz from 0 to 1
+    pub volume: f32,
+    pub sample_rate: u32,
+}
+
+impl Default for Chip8AudioConfig {
+    fn default() -> Self {
+        Self {
+            tone: 440.0,
+            volume: 0.25,
+            sample_rate: 44100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Chip8AudioState {
+    sound_timer: u8,
+    /// XO-CHIP 1 bit sample pattern, played instead of the buzz once a program loads one
+    pattern: [u8; 16],
+    pattern_loaded: bool,
+    pitch: u8,
+    /// Sixtieths of a sample since the sound timer last counted down
+    timer_phase: u64,
+    /// Position in the wave, in cycles of the buzz or bits of the pattern
+    phase: f32,
+}
+
+impl Default for Chip8AudioState {
+    fn default() -> Self {
+        Self {
+            sound_timer: 0,
+            pattern: [0; 16],
+            pattern_loaded: false,
+            pitch: DEFAULT_PITCH,
+            timer_phase: 0,
+            phase: 0.0,
+        }
+    }
+}
+
+impl Chip8AudioState {
+    /// Bits of the pattern played a second
+    fn pattern_rate(&self) -> f32 {
+        4000.0 * 2.0f32.powf((self.pitch as f32 - DEFAULT_PITCH as f32) / 48.0)
+    }
+}
+
+/// Sound timer and the buzzer it drives, sounding for as long as the timer is above 0
 #[derive(Debug)]
 pub struct Chip8Audio {
+    config: Chip8AudioConfig,
     // The CPU will set this according to what the program wants
-    sound_timer: Mutex<u8>,
+    state: Mutex<Chip8AudioState>,
+    /// Samples not taken yet, never saved
+    samples: Mutex<VecDeque<f32>>,
 }
 
 impl Chip8Audio {
     pub fn set(&self, value: u8) {
-        *self.sound_timer.lock().unwrap() = value;
+        self.state.lock().unwrap().sound_timer = value;
+    }
+
+    pub fn set_pattern(&self, pattern: [u8; 16]) {
+        let mut state = self.state.lock().unwrap();
+
+        // Playback starts over from the first bit
+        state.pattern = pattern;
+        state.pattern_loaded = true;
+        state.phase = 0.0;
+    }
+
+    pub fn set_pitch(&self, pitch: u8) {
+        self.state.lock().unwrap().pitch = pitch;
+    }
+
+    /// Bits of the pattern played a second
+    pub fn pattern_rate(&self) -> f32 {
+        self.state.lock().unwrap().pattern_rate()
     }
 }
 
-impl Component for Chip8Audio {}
+impl Component for Chip8Audio {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = Chip8AudioState::default();
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+        self.samples.lock().unwrap().clear();
+    }
+
+    fn audio_channels(&self) -> usize {
+        1
+    }
+}
+
+impl AudioComponent for Chip8Audio {
+    fn sample_rate(&self) -> Ratio<u64> {
+        Ratio::from_integer(self.config.sample_rate as u64)
+    }
+
+    fn drain_samples(&self, output: &mut Vec<f32>) {
+        output.extend(self.samples.lock().unwrap().drain(..));
+    }
+}
 
 impl FromConfig for Chip8Audio {
-    type Config = ();
+    type Config = Chip8AudioConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let sample_rate = Ratio::from_integer(config.sample_rate as u64);
 
-    fn from_config(component_builder: &mut ComponentBuilder<Self>, _config: Self::Config) {
         component_builder
             .set_component(Self {
-                sound_timer: Mutex::new(0),
+                config,
+                state: Mutex::default(),
+                samples: Mutex::default(),
             })
-            .set_schedulable(Ratio::from_integer(60), [], []);
+            .set_schedulable(sample_rate, [], [])
+            .set_audio();
     }
 }
 
 impl SchedulableComponent for Chip8Audio {
     fn run(&self, period: u64) {
-        let mut sound_timer_guard = self.sound_timer.lock().unwrap();
-        *sound_timer_guard = sound_timer_guard.saturating_sub(period.try_into().unwrap_or(u8::MAX));
+        let mut state = self.state.lock().unwrap();
+        let mut samples = self.samples.lock().unwrap();
+        let sample_rate = self.config.sample_rate as f32;
+
+        for _ in 0..period {
+            let high = if state.sound_timer == 0 {
+                false
+            } else if state.pattern_loaded {
+                let bit = state.phase as usize % 128;
+                state.phase = (state.phase + state.pattern_rate() / sample_rate) % 128.0;
+
+                state.pattern.view_bits::<Msb0>()[bit]
+            } else {
+                let high = state.phase < 0.5;
+                state.phase = (state.phase + self.config.tone / sample_rate).fract();
+
+                high
+            };
+            samples.push_back(if high { self.config.volume } else { 0.0 });
+
+            state.timer_phase += TIMER_FREQUENCY;
+            if state.timer_phase >= self.config.sample_rate as u64 {
+                state.timer_phase -= self.config.sample_rate as u64;
+                state.sound_timer = state.sound_timer.saturating_sub(1);
+            }
+        }
+
+        // Nobody is listening, keep a second at most
+        let limit = self.config.sample_rate as usize;
+        if samples.len() > limit {
+            let excess = samples.len() - limit;
+            samples.drain(..excess);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn buzz_follows_sound_timer() {
+        let (builder, audio) =
+            TestMachineBuilder::new().component::<Chip8Audio>(Chip8AudioConfig {
+                tone: 500.0,
+                volume: 0.5,
+                sample_rate: 32000,
+            });
+        let machine = builder.build();
+        let component = machine.component::<Chip8Audio>(audio);
+        let mut samples = Vec::new();
+
+        // Silent until the timer is set
+        machine.run_component::<Chip8Audio>(audio, 64);
+        component.drain_samples(&mut samples);
+        assert!(samples.iter().all(|sample| *sample == 0.0));
+
+        // A 64 sample square wave, running out within 2 ticks of the timer
+        component.set(2);
+        samples.clear();
+        machine.run_component::<Chip8Audio>(audio, 1200);
+        component.drain_samples(&mut samples);
+        assert_eq!(samples[..32], [0.5; 32]);
+        assert_eq!(samples[32..64], [0.0; 32]);
+        assert!(samples[1067..].iter().all(|sample| *sample == 0.0));
+
+        // A pattern replaces the buzz, at 4000 bits a second every bit lasts 8 samples
+        component.set_pattern([0b1010_0000; 16]);
+        component.set(1);
+        samples.clear();
+        machine.run_component::<Chip8Audio>(audio, 24);
+        component.drain_samples(&mut samples);
+        assert_eq!(samples[..8], [0.5; 8]);
+        assert_eq!(samples[8..16], [0.0; 8]);
+        assert_eq!(samples[16..24], [0.5; 8]);
     }
 }
@@ -3,100 +3,190 @@ use crate::{
     component::{
         display::DisplayComponent, schedulable::SchedulableComponent, Component, FromConfig,
     },
+    definitions::misc::video::output::FrameOutput,
     machine::ComponentBuilder,
-    runtime::rendering_backend::{DisplayComponentFramebuffer, DisplayComponentInitializationData},
+    runtime::{
+        color::Palette,
+        rendering_backend::{DisplayComponentFramebuffer, DisplayComponentInitializationData},
+    },
 };
 use bitvec::{order::Msb0, view::BitView};
 use nalgebra::{DMatrix, DMatrixViewMut, Point2, Vector2};
 use num::rational::Ratio;
-use palette::Srgba;
 use serde::{Deserialize, Serialize};
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex, OnceLock,
+    atomic::{AtomicBool, AtomicU8, Ordering},
+    Mutex,
 };
 
-#[cfg(platform_desktop)]
-mod desktop;
-#[cfg(graphics_vulkan)]
-use desktop::vulkan::VulkanState;
+/// Off and on, in that order
+pub const CHIP8_DEFAULT_PALETTE: [u8; 6] = [0x00, 0x00, 0x00, 0xff, 0xff, 0xff];
 
-mod software;
-use software::SoftwareState;
+/// One color for every combination of the 4 XO-CHIP planes, indexed by the planes set
+#[rustfmt::skip]
+pub const XO_CHIP_DEFAULT_PALETTE: [u8; 48] = [
+    0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xaa, 0xaa, 0xaa, 0x55, 0x55, 0x55,
+    0xff, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0x00,
+    0x88, 0x00, 0x00, 0x00, 0x88, 0x00, 0x00, 0x00, 0x88, 0x88, 0x88, 0x00,
+    0xff, 0x00, 0xff, 0x00, 0xff, 0xff, 0x88, 0x00, 0x88, 0x00, 0x88, 0x88,
+];
 
-#[derive(Debug)]
-#[non_exhaustive]
-enum InternalState {
-    #[cfg(graphics_vulkan)]
-    Vulkan(VulkanState),
-    Software(SoftwareState),
-}
+const LORES_RESOLUTION: Vector2<usize> = Vector2::new(64, 32);
+/// SuperChip8 extended mode
+const HIRES_RESOLUTION: Vector2<usize> = Vector2::new(128, 64);
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Chip8DisplaySnapshot {
-    screen_buffer: DMatrix<Srgba<u8>>,
+    screen: DMatrix<u8>,
+    selected_planes: u8,
 }
 
 #[derive(Debug)]
 pub struct Chip8Display {
     config: Chip8DisplayConfig,
-    state: OnceLock<InternalState>,
+    refresh_rate: Ratio<u64>,
+    /// Bitmask of the planes set at each pixel, only XO-CHIP uses more than the first
+    screen: Mutex<DMatrix<u8>>,
+    /// Planes drawing, clearing and scrolling touch
+    selected_planes: AtomicU8,
+    output: FrameOutput,
     modified: AtomicBool,
 }
 
 impl Chip8Display {
+    /// Draws a sprite 8 pixels wide, one byte per row, with the rows for each selected plane one after another
     pub fn draw_sprite(&self, position: Point2<u8>, sprite: &[u8]) -> bool {
+        self.draw(position, sprite, 8)
+    }
+
+    /// Draws the 16x16 SuperChip8 sprite, two bytes per row, with 32 bytes for each selected plane
+    pub fn draw_large_sprite(&self, position: Point2<u8>, sprite: &[u8]) -> bool {
+        self.draw(position, sprite, 16)
+    }
+
+    fn draw(&self, position: Point2<u8>, sprite: &[u8], sprite_width: usize) -> bool {
+        let selected_planes = self.selected_planes();
+        let plane_size = sprite.len() / (selected_planes.count_ones() as usize).max(1);
+
         tracing::trace!(
-            "Drawing sprite at position {} of dimensions 8x{}",
+            "Drawing sprite at position {} of dimensions {}x{}",
             position,
-            sprite.len()
+            sprite_width,
+            plane_size * 8 / sprite_width
         );
 
+        let mut screen = self.screen.lock().unwrap();
+        let resolution = Vector2::new(screen.nrows(), screen.ncols());
+
         let position = match self.config.kind {
             Chip8Kind::Chip8 | Chip8Kind::Chip48 => Point2::new(position.x % 63, position.y % 31),
-            Chip8Kind::SuperChip8 => todo!(),
+            // The starting position wraps, the sprite itself is clipped or wraps depending on the kind
+            Chip8Kind::SuperChip8 | Chip8Kind::XoChip => Point2::new(
+                position.x % resolution.x as u8,
+                position.y % resolution.y as u8,
+            ),
             _ => todo!(),
         };
+        let wrap = self.config.kind == Chip8Kind::XoChip;
 
         self.modified.store(true, Ordering::Relaxed);
 
-        match self.state.get() {
-            #[cfg(graphics_vulkan)]
-            Some(InternalState::Vulkan(vulkan_state)) => vulkan_state.draw_sprite(position, sprite),
-            Some(InternalState::Software(software_state)) => {
-                software_state.draw_sprite(position, sprite)
-            }
-            _ => panic!("Internal state not initialized"),
+        let mut collided = false;
+        for (plane, plane_sprite) in (0..4)
+            .map(|plane| 1 << plane)
+            .filter(|plane| selected_planes & plane != 0)
+            .zip(sprite.chunks(plane_size.max(1)))
+        {
+            collided |= draw_sprite_common(
+                position,
+                plane_sprite,
+                sprite_width,
+                plane,
+                wrap,
+                screen.as_view_mut(),
+            );
         }
+
+        collided
     }
 
+    /// Clears the selected planes
     pub fn clear_display(&self) {
         tracing::trace!("Clearing display");
 
-        match self.state.get() {
-            #[cfg(graphics_vulkan)]
-            Some(InternalState::Vulkan(vulkan_state)) => vulkan_state.clear_display(),
-            Some(InternalState::Software(software_state)) => software_state.clear_display(),
-            _ => panic!("Internal state not initialized"),
+        let selected_planes = self.selected_planes();
+        self.modified.store(true, Ordering::Relaxed);
+
+        for pixel in self.screen.lock().unwrap().iter_mut() {
+            *pixel &= !selected_planes;
         }
     }
+
+    /// Moves the contents of the selected planes, pixels scrolled in are off
+    pub fn scroll(&self, offset: Vector2<isize>) {
+        tracing::trace!("Scrolling display by {}", offset);
+
+        self.modified.store(true, Ordering::Relaxed);
+
+        scroll_common(
+            offset,
+            self.selected_planes(),
+            self.screen.lock().unwrap().as_view_mut(),
+        );
+    }
+
+    pub fn hires(&self) -> bool {
+        self.screen.lock().unwrap().nrows() == HIRES_RESOLUTION.x
+    }
+
+    /// Switches between 64x32 and 128x64, the screen is resized and every plane cleared
+    pub fn set_hires(&self, hires: bool) {
+        tracing::debug!(
+            "Switching display to {}",
+            if hires { "hires" } else { "lores" }
+        );
+
+        let resolution = if hires {
+            HIRES_RESOLUTION
+        } else {
+            LORES_RESOLUTION
+        };
+
+        self.modified.store(true, Ordering::Relaxed);
+        *self.screen.lock().unwrap() = DMatrix::zeros(resolution.x, resolution.y);
+    }
+
+    pub fn selected_planes(&self) -> u8 {
+        self.selected_planes.load(Ordering::Relaxed)
+    }
+
+    /// XO-CHIP plane select, the low 4 bits pick the planes
+    pub fn set_selected_planes(&self, planes: u8) {
+        self.selected_planes.store(planes & 0x0f, Ordering::Relaxed);
+    }
+
+    /// Colors the planes and hands them to the output
+    fn commit_display(&self) {
+        let frame = self
+            .screen
+            .lock()
+            .unwrap()
+            .map(|planes| self.config.palette.get(planes as usize));
+
+        self.output.present(&frame);
+    }
 }
 
 impl Component for Chip8Display {
     fn reset(&self) {
-        self.clear_display();
+        self.selected_planes.store(1, Ordering::Relaxed);
+        self.set_hires(false);
     }
 
     fn save_snapshot(&self) -> rmpv::Value {
-        let display_buffer = match self.state.get() {
-            #[cfg(graphics_vulkan)]
-            Some(InternalState::Vulkan(vulkan_state)) => vulkan_state.save_screen_contents(),
-            Some(InternalState::Software(software_state)) => software_state.save_screen_contents(),
-            _ => panic!("Internal state not initialized"),
-        };
-
         rmpv::ext::to_value(Chip8DisplaySnapshot {
-            screen_buffer: display_buffer,
+            screen: self.screen.lock().unwrap().clone(),
+            selected_planes: self.selected_planes(),
         })
         .unwrap()
     }
@@ -104,167 +194,197 @@ impl Component for Chip8Display {
     fn load_snapshot(&self, state: rmpv::Value) {
         let snapshot: Chip8DisplaySnapshot = rmpv::ext::from_value(state).unwrap();
 
-        match self.state.get() {
-            #[cfg(graphics_vulkan)]
-            Some(InternalState::Vulkan(vulkan_state)) => {
-                vulkan_state.load_screen_contents(snapshot.screen_buffer);
-            }
-            Some(InternalState::Software(software_state)) => {
-                software_state.load_screen_contents(snapshot.screen_buffer);
-            }
-            _ => panic!("Internal state not initialized"),
-        }
+        *self.screen.lock().unwrap() = snapshot.screen;
+        self.set_selected_planes(snapshot.selected_planes);
+        self.modified.store(true, Ordering::Relaxed);
     }
 }
 
 #[derive(Debug)]
 pub struct Chip8DisplayConfig {
     pub kind: Chip8Kind,
+    /// Indexed by the planes set at a pixel, so 2 colors or 16 for XO-CHIP
+    pub palette: Palette,
 }
 
 impl FromConfig for Chip8Display {
     type Config = Chip8DisplayConfig;
 
     fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        // Commits once a frame, whatever rate the machine presents at
+        let refresh_rate = component_builder.machine().display_clock_rate();
+
         component_builder
             .set_component(Chip8Display {
                 config,
-                state: OnceLock::default(),
+                refresh_rate,
+                screen: Mutex::new(DMatrix::zeros(LORES_RESOLUTION.x, LORES_RESOLUTION.y)),
+                selected_planes: AtomicU8::new(1),
+                output: FrameOutput::default(),
                 modified: AtomicBool::new(false),
             })
-            .set_schedulable(Ratio::from_integer(60), [], [])
+            .set_schedulable(refresh_rate, [], [])
             .set_display();
     }
 }
 
-trait Chip8DisplayImplementation {
-    fn draw_sprite(&self, position: Point2<u8>, sprite: &[u8]) -> bool;
-    fn clear_display(&self);
-    fn save_screen_contents(&self) -> DMatrix<Srgba<u8>>;
-    fn load_screen_contents(&self, buffer: DMatrix<Srgba<u8>>);
-    fn get_framebuffer(&self) -> DisplayComponentFramebuffer;
-    fn commit_display(&self);
-}
-
 impl SchedulableComponent for Chip8Display {
     fn run(&self, _period: u64) {
         // Only update it once and if the thing is actually updated
         if self.modified.swap(false, Ordering::Relaxed) {
-            match self.state.get() {
-                Some(InternalState::Software(software_state)) => {
-                    software_state.commit_display();
-                }
-                #[cfg(graphics_vulkan)]
-                Some(InternalState::Vulkan(vulkan_state)) => {
-                    vulkan_state.commit_display();
-                }
-                _ => panic!("Internal state not initialized"),
-            }
+            self.commit_display();
         }
     }
 }
 
 impl DisplayComponent for Chip8Display {
     fn set_display_data(&self, initialization_data: DisplayComponentInitializationData) {
-        let _ = self.state.set(match initialization_data {
-            DisplayComponentInitializationData::Software => {
-                let framebuffer = DMatrix::from_element(64, 32, Srgba::new(0, 0, 0, 255));
-                InternalState::Software(SoftwareState {
-                    framebuffer: Arc::new(Mutex::new(framebuffer)),
-                })
-            }
-            #[cfg(graphics_vulkan)]
-            DisplayComponentInitializationData::Vulkan(initialization_data) => {
-                use vulkano::buffer::Buffer;
-                use vulkano::buffer::BufferCreateInfo;
-                use vulkano::buffer::BufferUsage;
-                use vulkano::format::Format;
-                use vulkano::image::Image;
-                use vulkano::image::ImageCreateInfo;
-                use vulkano::image::ImageType;
-                use vulkano::image::ImageUsage;
-                use vulkano::memory::allocator::AllocationCreateInfo;
-                use vulkano::memory::allocator::MemoryTypeFilter;
-
-                let staging_buffer = Buffer::from_iter(
-                    initialization_data.memory_allocator.clone(),
-                    BufferCreateInfo {
-                        usage: BufferUsage::TRANSFER_SRC,
-                        ..Default::default()
-                    },
-                    AllocationCreateInfo {
-                        memory_type_filter: MemoryTypeFilter::HOST_RANDOM_ACCESS,
-                        ..Default::default()
-                    },
-                    vec![Srgba::new(0, 0, 0, 0xff); 64 * 32],
-                )
-                .unwrap();
-
-                let render_image = Image::new(
-                    initialization_data.memory_allocator.clone(),
-                    ImageCreateInfo {
-                        image_type: ImageType::Dim2d,
-                        format: Format::R8G8B8A8_SRGB,
-                        extent: [64, 32, 1],
-                        usage: ImageUsage::TRANSFER_SRC
-                            | ImageUsage::TRANSFER_DST
-                            | ImageUsage::SAMPLED,
-                        ..Default::default()
-                    },
-                    AllocationCreateInfo::default(),
-                )
-                .unwrap();
-
-                InternalState::Vulkan(VulkanState {
-                    queue: initialization_data.queue,
-                    command_buffer_allocator: initialization_data.command_buffer_allocator,
-                    staging_buffer,
-                    render_image,
-                })
-            }
-        });
+        let screen = self.screen.lock().unwrap();
+
+        self.output.set_display_data(
+            initialization_data,
+            Vector2::new(screen.nrows(), screen.ncols()),
+            self.config.palette.get(0),
+        );
     }
 
     fn get_framebuffer(&self) -> DisplayComponentFramebuffer {
-        match self.state.get() {
-            Some(InternalState::Software(software_state)) => software_state.get_framebuffer(),
-            #[cfg(graphics_vulkan)]
-            Some(InternalState::Vulkan(vulkan_state)) => vulkan_state.get_framebuffer(),
-            _ => panic!("Internal state not initialized"),
-        }
+        self.output.get_framebuffer()
+    }
+
+    fn refresh_rate(&self) -> Option<Ratio<u64>> {
+        Some(self.refresh_rate)
     }
 }
 
+/// XORs a sprite into one plane, returning if any pixel of it was turned off
 fn draw_sprite_common(
     position: Point2<u8>,
     sprite: &[u8],
-    mut framebuffer: DMatrixViewMut<'_, Srgba<u8>>,
+    sprite_width: usize,
+    plane: u8,
+    wrap: bool,
+    mut screen: DMatrixViewMut<'_, u8>,
 ) -> bool {
     let mut collided = false;
     let position = position.cast();
+    let (width, height) = screen.shape();
 
-    for (y, sprite_row) in sprite.view_bits::<Msb0>().chunks(8).enumerate() {
+    for (y, sprite_row) in sprite.view_bits::<Msb0>().chunks(sprite_width).enumerate() {
         for (x, sprite_pixel) in sprite_row.iter().enumerate() {
-            let coord = position + Vector2::new(x, y);
+            let mut coord = position + Vector2::new(x, y);
 
-            if coord.x >= 64 || coord.y >= 32 {
+            if wrap {
+                coord = Point2::new(coord.x % width, coord.y % height);
+            } else if coord.x >= width || coord.y >= height {
                 continue;
             }
 
-            let old_sprite_pixel =
-                framebuffer[(coord.x, coord.y)] == Srgba::new(255, 255, 255, 255);
+            if !*sprite_pixel {
+                continue;
+            }
 
-            if *sprite_pixel && old_sprite_pixel {
+            let pixel = &mut screen[(coord.x, coord.y)];
+            if *pixel & plane != 0 {
                 collided = true;
             }
+            *pixel ^= plane;
+        }
+    }
+
+    collided
+}
+
+fn scroll_common(offset: Vector2<isize>, planes: u8, mut screen: DMatrixViewMut<'_, u8>) {
+    let original = screen.clone_owned();
+    let (width, height) = screen.shape();
+
+    for y in 0..height {
+        for x in 0..width {
+            let source = Point2::new(x as isize, y as isize) - offset;
 
-            framebuffer[(coord.x, coord.y)] = if *sprite_pixel ^ old_sprite_pixel {
-                Srgba::new(255, 255, 255, 255)
+            let scrolled = if (0..width as isize).contains(&source.x)
+                && (0..height as isize).contains(&source.y)
+            {
+                original[(source.x as usize, source.y as usize)]
             } else {
-                Srgba::new(0, 0, 0, 255)
+                0
             };
+
+            screen[(x, y)] = (original[(x, y)] & !planes) | (scrolled & planes);
         }
     }
+}
 
-    collided
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn large_sprites_and_scrolling() {
+        let mut screen = DMatrix::zeros(HIRES_RESOLUTION.x, HIRES_RESOLUTION.y);
+
+        // Left and right columns set, the right one ends up past the edge and is clipped
+        let sprite = [0b10000000, 0b00000001].repeat(16);
+        assert!(!draw_sprite_common(
+            Point2::new(120, 56),
+            &sprite,
+            16,
+            1,
+            false,
+            screen.as_view_mut(),
+        ));
+        assert_eq!(screen[(120, 56)], 1);
+        assert_eq!(screen[(120, 63)], 1);
+        assert_eq!(screen[(121, 56)], 0);
+        assert!(draw_sprite_common(
+            Point2::new(120, 56),
+            &sprite,
+            16,
+            1,
+            false,
+            screen.as_view_mut(),
+        ));
+        assert!(screen.iter().all(|pixel| *pixel == 0));
+
+        screen[(0, 0)] = 1;
+        scroll_common(Vector2::new(0, 4), 1, screen.as_view_mut());
+        assert_eq!(screen[(0, 4)], 1);
+        assert_eq!(screen[(0, 0)], 0);
+        scroll_common(Vector2::new(4, 0), 1, screen.as_view_mut());
+        assert_eq!(screen[(4, 4)], 1);
+        scroll_common(Vector2::new(-8, 0), 1, screen.as_view_mut());
+        assert!(screen.iter().all(|pixel| *pixel == 0));
+    }
+
+    #[test]
+    fn planes() {
+        let mut screen = DMatrix::zeros(LORES_RESOLUTION.x, LORES_RESOLUTION.y);
+
+        // Wraps around to the left edge on XO-CHIP
+        draw_sprite_common(
+            Point2::new(60, 0),
+            &[0xff],
+            8,
+            0b01,
+            true,
+            screen.as_view_mut(),
+        );
+        draw_sprite_common(
+            Point2::new(0, 0),
+            &[0xf0],
+            8,
+            0b10,
+            true,
+            screen.as_view_mut(),
+        );
+        assert_eq!(screen[(63, 0)], 0b01);
+        assert_eq!(screen[(0, 0)], 0b11);
+        assert_eq!(screen[(4, 0)], 0);
+
+        // Only the second plane moves
+        scroll_common(Vector2::new(0, 1), 0b10, screen.as_view_mut());
+        assert_eq!(screen[(0, 0)], 0b01);
+        assert_eq!(screen[(0, 1)], 0b10);
+    }
 }
@@ -2,21 +2,23 @@ use super::misc::memory::standard::{
     StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents,
 };
 use crate::{
-    machine::Machine,
+    machine::{validation::MachineConfigurationError, Machine},
     memory::AddressSpaceId,
     rom::{
         id::RomId,
         manager::RomManager,
         system::{GameSystem, OtherSystem},
     },
+    runtime::color::Palette,
 };
-use audio::Chip8Audio;
-use display::{Chip8Display, Chip8DisplayConfig};
+use audio::{Chip8Audio, Chip8AudioConfig};
+use display::{Chip8Display, Chip8DisplayConfig, CHIP8_DEFAULT_PALETTE, XO_CHIP_DEFAULT_PALETTE};
 use num::rational::Ratio;
 use processor::{Chip8Processor, Chip8ProcessorConfig};
 use std::{borrow::Cow, sync::Arc};
 use timer::Chip8Timer;
 
+pub mod assembler;
 pub mod audio;
 pub mod display;
 pub mod processor;
@@ -149,20 +151,57 @@ const CHIP8_FONT: [[u8; 5]; 16] = [
     ],
 ];
 
-pub fn chip8_machine(user_specified_roms: Vec<RomId>, rom_manager: Arc<RomManager>) -> Machine {
-    let machine = Machine::build(GameSystem::Other(OtherSystem::Chip8), rom_manager);
-    let machine = machine.insert_bus(CHIP8_ADDRESS_SPACE_ID, 12);
+pub fn chip8_machine(
+    user_specified_roms: Vec<RomId>,
+    rom_manager: Arc<RomManager>,
+) -> Result<Machine, MachineConfigurationError> {
+    build_machine(Chip8Kind::Chip8, user_specified_roms, rom_manager)
+}
+
+/// XO-CHIP, with 64KB of memory, 4 display planes and the audio pattern buffer
+pub fn xochip_machine(
+    user_specified_roms: Vec<RomId>,
+    rom_manager: Arc<RomManager>,
+) -> Result<Machine, MachineConfigurationError> {
+    build_machine(Chip8Kind::XoChip, user_specified_roms, rom_manager)
+}
+
+fn build_machine(
+    kind: Chip8Kind,
+    user_specified_roms: Vec<RomId>,
+    rom_manager: Arc<RomManager>,
+) -> Result<Machine, MachineConfigurationError> {
+    let (system, address_space_width, frequency, default_palette) = match kind {
+        Chip8Kind::XoChip => (
+            OtherSystem::XoChip,
+            16,
+            // Octo's default of 1000 instructions a frame
+            60_000,
+            XO_CHIP_DEFAULT_PALETTE.as_slice(),
+        ),
+        _ => (
+            OtherSystem::Chip8,
+            12,
+            700,
+            CHIP8_DEFAULT_PALETTE.as_slice(),
+        ),
+    };
+
+    let machine = Machine::build(GameSystem::Other(system), rom_manager);
+    let machine = machine
+        .insert_bus(CHIP8_ADDRESS_SPACE_ID, address_space_width)
+        .display_clock(Ratio::from_integer(60));
 
-    let (machine, audio_component_id) = machine.default_component::<Chip8Audio>();
+    let (machine, audio_component_id) =
+        machine.build_component::<Chip8Audio>(Chip8AudioConfig::default());
     let (machine, timer_component_id) = machine.default_component::<Chip8Timer>();
+    let palette = Palette::load_for_system(machine.system, default_palette);
     let (machine, display_component_id) =
-        machine.build_component::<Chip8Display>(Chip8DisplayConfig {
-            kind: Chip8Kind::Chip8,
-        });
+        machine.build_component::<Chip8Display>(Chip8DisplayConfig { kind, palette });
 
     let (machine, _) = machine.build_component::<Chip8Processor>(Chip8ProcessorConfig {
-        frequency: Ratio::from_integer(700),
-        kind: Chip8Kind::Chip8,
+        frequency: Ratio::from_integer(frequency),
+        kind,
         display: display_component_id,
         audio: audio_component_id,
         timer: timer_component_id,
@@ -184,7 +223,7 @@ pub fn chip8_machine(user_specified_roms: Vec<RomId>, rom_manager: Arc<RomManage
         readable: true,
         writable: true,
         max_word_size: 2,
-        assigned_range: 0x200..0x1000,
+        assigned_range: 0x200..1 << address_space_width,
         assigned_address_space: CHIP8_ADDRESS_SPACE_ID,
         initial_contents: StandardMemoryInitialContents::Rom {
             rom_id: user_specified_roms[0],
@@ -192,5 +231,5 @@ pub fn chip8_machine(user_specified_roms: Vec<RomId>, rom_manager: Arc<RomManage
         },
     });
 
-    machine.build()
+    machine.try_build()
 }
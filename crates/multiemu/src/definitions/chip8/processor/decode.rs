@@ -1,4 +1,7 @@
-use super::instruction::{Chip8InstructionSet, InstructionSetChip8, Register};
+use super::instruction::{
+    Chip8InstructionSet, DecodingError, InstructionSetChip8, InstructionSetSuperChip8,
+    InstructionSetXoChip, Register,
+};
 use bitvec::{field::BitField, prelude::Msb0, view::BitView};
 use nalgebra::Point2;
 
@@ -11,9 +14,35 @@ pub(super) fn decode_instruction(
         0x0 => {
             let syscall = instruction_view[4..16].load_be::<u16>();
 
-            Ok(Chip8InstructionSet::Chip8(InstructionSetChip8::Sys {
-                syscall,
-            }))
+            // SuperChip8 took over some of the syscall space
+            match syscall {
+                0x0c0..=0x0cf => Ok(Chip8InstructionSet::SuperChip8(
+                    InstructionSetSuperChip8::Scrd {
+                        amount: instruction_view[12..16].load::<u8>(),
+                    },
+                )),
+                0x0d0..=0x0df => Ok(Chip8InstructionSet::XoChip(InstructionSetXoChip::Scru {
+                    amount: instruction_view[12..16].load::<u8>(),
+                })),
+                0x0fb => Ok(Chip8InstructionSet::SuperChip8(
+                    InstructionSetSuperChip8::Scrr,
+                )),
+                0x0fc => Ok(Chip8InstructionSet::SuperChip8(
+                    InstructionSetSuperChip8::Scrl,
+                )),
+                0x0fd => Ok(Chip8InstructionSet::SuperChip8(
+                    InstructionSetSuperChip8::Exit,
+                )),
+                0x0fe => Ok(Chip8InstructionSet::SuperChip8(
+                    InstructionSetSuperChip8::Lores,
+                )),
+                0x0ff => Ok(Chip8InstructionSet::SuperChip8(
+                    InstructionSetSuperChip8::Hires,
+                )),
+                _ => Ok(Chip8InstructionSet::Chip8(InstructionSetChip8::Sys {
+                    syscall,
+                })),
+            }
         }
         0x1 => {
             let address = instruction_view[4..16].load_be::<u16>();
@@ -48,13 +77,23 @@ pub(super) fn decode_instruction(
             }))
         }
         0x5 => {
-            let param_register_1 = instruction_view[4..8].load::<u8>();
-            let param_register_2 = instruction_view[8..12].load::<u8>();
+            let param_register_1 = Register::try_from(instruction_view[4..8].load::<u8>()).unwrap();
+            let param_register_2 =
+                Register::try_from(instruction_view[8..12].load::<u8>()).unwrap();
 
-            Ok(Chip8InstructionSet::Chip8(InstructionSetChip8::Skre {
-                param_register_1: Register::try_from(param_register_1).unwrap(),
-                param_register_2: Register::try_from(param_register_2).unwrap(),
-            }))
+            match instruction_view[12..16].load::<u8>() {
+                0x0 => Ok(Chip8InstructionSet::Chip8(InstructionSetChip8::Skre {
+                    param_register_1,
+                    param_register_2,
+                })),
+                0x2 => Ok(Chip8InstructionSet::XoChip(InstructionSetXoChip::Ssub {
+                    bounds: param_register_1..param_register_2,
+                })),
+                0x3 => Ok(Chip8InstructionSet::XoChip(InstructionSetXoChip::Rsub {
+                    bounds: param_register_1..param_register_2,
+                })),
+                _ => Err(DecodingError::InvalidInstruction(instruction).into()),
+            }
         }
         0x6 => {
             let register = instruction_view[4..8].load::<u8>();
@@ -191,6 +230,15 @@ pub(super) fn decode_instruction(
             let register = instruction_view[4..8].load::<u8>();
 
             match instruction_view[8..16].load::<u8>() {
+                0x00 if register == 0 => {
+                    Ok(Chip8InstructionSet::XoChip(InstructionSetXoChip::Loadl))
+                }
+                0x01 => Ok(Chip8InstructionSet::XoChip(InstructionSetXoChip::Plane {
+                    planes: register,
+                })),
+                0x02 if register == 0 => {
+                    Ok(Chip8InstructionSet::XoChip(InstructionSetXoChip::Audio))
+                }
                 0x07 => Ok(Chip8InstructionSet::Chip8(InstructionSetChip8::Moved {
                     register: Register::try_from(register).unwrap(),
                 })),
@@ -209,6 +257,9 @@ pub(super) fn decode_instruction(
                 0x29 => Ok(Chip8InstructionSet::Chip8(InstructionSetChip8::Font {
                     register: Register::try_from(register).unwrap(),
                 })),
+                0x3a => Ok(Chip8InstructionSet::XoChip(InstructionSetXoChip::Pitch {
+                    register: Register::try_from(register).unwrap(),
+                })),
                 0x33 => Ok(Chip8InstructionSet::Chip8(InstructionSetChip8::Bcd {
                     register: Register::try_from(register).unwrap(),
                 })),
@@ -240,4 +291,38 @@ mod tests {
             Chip8InstructionSet::Chip8(InstructionSetChip8::Sys { syscall: 0 })
         )
     }
+
+    #[test]
+    pub fn superchip8() {
+        assert_eq!(
+            decode_instruction([0x00, 0xc3]).unwrap(),
+            Chip8InstructionSet::SuperChip8(InstructionSetSuperChip8::Scrd { amount: 3 })
+        );
+        assert_eq!(
+            decode_instruction([0x00, 0xff]).unwrap(),
+            Chip8InstructionSet::SuperChip8(InstructionSetSuperChip8::Hires)
+        );
+        assert_eq!(
+            decode_instruction([0x00, 0xe0]).unwrap(),
+            Chip8InstructionSet::Chip8(InstructionSetChip8::Sys { syscall: 0x0e0 })
+        );
+    }
+
+    #[test]
+    pub fn xochip() {
+        assert_eq!(
+            decode_instruction([0x51, 0x32]).unwrap(),
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Ssub {
+                bounds: Register::V1..Register::V3
+            })
+        );
+        assert_eq!(
+            decode_instruction([0xf0, 0x00]).unwrap(),
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Loadl)
+        );
+        assert_eq!(
+            decode_instruction([0xf3, 0x01]).unwrap(),
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Plane { planes: 3 })
+        );
+    }
 }
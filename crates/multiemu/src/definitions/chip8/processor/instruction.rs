@@ -189,14 +189,35 @@ pub enum InstructionSetSuperChip8 {
     Scrd { amount: u8 },
     Scrr,
     Scrl,
+    Exit,
+    Lores,
+    Hires,
     Srpl { amount: u8 },
     Rrpl { amount: u8 },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum InstructionSetXoChip {
-    Ssub { bounds: Range<Register> },
-    Rsub { bounds: Range<Register> },
+    /// Both ends are included, and the registers go in reverse when the start is past the end
+    Ssub {
+        bounds: Range<Register>,
+    },
+    Rsub {
+        bounds: Range<Register>,
+    },
+    Scru {
+        amount: u8,
+    },
+    /// Loads the index register with the 16 bit word following the instruction
+    Loadl,
+    Plane {
+        planes: u8,
+    },
+    /// Loads the audio pattern buffer from the 16 bytes at the index register
+    Audio,
+    Pitch {
+        register: Register,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -1,6 +1,8 @@
 use super::{
     input::Chip8KeyCode,
-    instruction::{Chip8InstructionSet, InstructionSetChip8},
+    instruction::{
+        Chip8InstructionSet, InstructionSetChip8, InstructionSetSuperChip8, InstructionSetXoChip,
+    },
     Chip8Processor, ExecutionState, ProcessorState,
 };
 use crate::definitions::chip8::{Chip8Kind, CHIP8_ADDRESS_SPACE_ID, CHIP8_FONT};
@@ -10,7 +12,7 @@ use bitvec::{
     prelude::{Lsb0, Msb0},
     view::BitView,
 };
-use nalgebra::Point2;
+use nalgebra::{Point2, Vector2};
 use rand::Rng;
 
 impl Chip8Processor {
@@ -51,7 +53,7 @@ impl Chip8Processor {
                 let register_value = state.registers.work_registers[register as usize];
 
                 if register_value == immediate {
-                    state.registers.program = state.registers.program.wrapping_add(2);
+                    self.skip_instruction(state);
                 }
             }
             Chip8InstructionSet::Chip8(InstructionSetChip8::Skne {
@@ -61,7 +63,7 @@ impl Chip8Processor {
                 let register_value = state.registers.work_registers[register as usize];
 
                 if register_value != immediate {
-                    state.registers.program = state.registers.program.wrapping_add(2);
+                    self.skip_instruction(state);
                 }
             }
             Chip8InstructionSet::Chip8(InstructionSetChip8::Skre {
@@ -74,7 +76,7 @@ impl Chip8Processor {
                     state.registers.work_registers[param_register_2 as usize];
 
                 if param_register_1_value == param_register_2_value {
-                    state.registers.program = state.registers.program.wrapping_add(2);
+                    self.skip_instruction(state);
                 }
             }
             Chip8InstructionSet::Chip8(InstructionSetChip8::Load {
@@ -159,7 +161,7 @@ impl Chip8Processor {
             Chip8InstructionSet::Chip8(InstructionSetChip8::Shr { register, value }) => {
                 let mut destination_value = state.registers.work_registers[register as usize];
 
-                if self.config.kind == Chip8Kind::Chip8 {
+                if matches!(self.config.kind, Chip8Kind::Chip8 | Chip8Kind::XoChip) {
                     destination_value = state.registers.work_registers[value as usize];
                 }
 
@@ -183,7 +185,7 @@ impl Chip8Processor {
             Chip8InstructionSet::Chip8(InstructionSetChip8::Shl { register, value }) => {
                 let mut destination_value = state.registers.work_registers[register as usize];
 
-                if self.config.kind == Chip8Kind::Chip8 {
+                if matches!(self.config.kind, Chip8Kind::Chip8 | Chip8Kind::XoChip) {
                     destination_value = state.registers.work_registers[value as usize];
                 }
 
@@ -202,14 +204,14 @@ impl Chip8Processor {
                     state.registers.work_registers[param_register_2 as usize];
 
                 if param_register_1_value != param_register_2_value {
-                    state.registers.program = state.registers.program.wrapping_add(2);
+                    self.skip_instruction(state);
                 }
             }
             Chip8InstructionSet::Chip8(InstructionSetChip8::Loadi { value }) => {
                 state.registers.index = value;
             }
             Chip8InstructionSet::Chip8(InstructionSetChip8::Jumpi { address }) => {
-                let address = if self.config.kind == Chip8Kind::Chip8 {
+                let address = if matches!(self.config.kind, Chip8Kind::Chip8 | Chip8Kind::XoChip) {
                     address.wrapping_add(state.registers.work_registers[0x0] as u16)
                 } else {
                     let register = address.view_bits::<Msb0>()[4..8].load::<u8>();
@@ -226,26 +228,25 @@ impl Chip8Processor {
                 state.registers.work_registers[register as usize] =
                     rand::rng().random::<u8>() & immediate;
             }
+            Chip8InstructionSet::Chip8(InstructionSetChip8::Draw {
+                coordinate_registers,
+                height: 0,
+            }) if matches!(self.config.kind, Chip8Kind::SuperChip8 | Chip8Kind::XoChip) => {
+                let buffer = self.read_sprite(state, 32);
+
+                let actual_coords = Point2::new(
+                    state.registers.work_registers[coordinate_registers.x as usize],
+                    state.registers.work_registers[coordinate_registers.y as usize],
+                );
+
+                state.registers.work_registers[0xf] =
+                    self.display.draw_large_sprite(actual_coords, &buffer) as u8;
+            }
             Chip8InstructionSet::Chip8(InstructionSetChip8::Draw {
                 coordinate_registers,
                 height,
             }) => {
-                let mut buffer =
-                    ArrayVec::<_, 16>::from_iter(std::iter::repeat(0).take(height as usize));
-
-                let mut cursor = 0;
-                for buffer_section in buffer.chunks_mut(2) {
-                    self.memory_translation_table
-                        .get()
-                        .unwrap()
-                        .read(
-                            state.registers.index as usize + cursor,
-                            buffer_section,
-                            CHIP8_ADDRESS_SPACE_ID,
-                        )
-                        .unwrap();
-                    cursor += buffer_section.len();
-                }
+                let buffer = self.read_sprite(state, height as usize);
 
                 let actual_coords = Point2::new(
                     state.registers.work_registers[coordinate_registers.x as usize],
@@ -263,7 +264,7 @@ impl Chip8Processor {
                 let key_value = input_manager.get_input(*gamepad_port, key.try_into().unwrap());
 
                 if key_value.as_digital() {
-                    state.registers.program = state.registers.program.wrapping_add(2);
+                    self.skip_instruction(state);
                 }
             }
             Chip8InstructionSet::Chip8(InstructionSetChip8::Skup { key }) => {
@@ -274,7 +275,7 @@ impl Chip8Processor {
                 let key_value = input_manager.get_input(*gamepad_port, key.try_into().unwrap());
 
                 if !key_value.as_digital() {
-                    state.registers.program = state.registers.program.wrapping_add(2);
+                    self.skip_instruction(state);
                 }
             }
             Chip8InstructionSet::Chip8(InstructionSetChip8::Moved { register }) => {
@@ -347,8 +348,8 @@ impl Chip8Processor {
                         .unwrap();
                 }
 
-                // Only the original chip8 modifies the index register for this operation
-                if self.config.kind == Chip8Kind::Chip8 {
+                // Only the original chip8 and XO-CHIP modify the index register for this operation
+                if matches!(self.config.kind, Chip8Kind::Chip8 | Chip8Kind::XoChip) {
                     state.registers.index = state.registers.index.wrapping_add(count as u16 + 1);
                 }
             }
@@ -365,15 +366,174 @@ impl Chip8Processor {
                         .unwrap();
                 }
 
-                // Only the original chip8 modifies the index register for this operation
-                if self.config.kind == Chip8Kind::Chip8 {
+                // Only the original chip8 and XO-CHIP modify the index register for this operation
+                if matches!(self.config.kind, Chip8Kind::Chip8 | Chip8Kind::XoChip) {
                     state.registers.index = state.registers.index.wrapping_add(count as u16 + 1);
                 }
             }
+            Chip8InstructionSet::SuperChip8(instruction)
+                if !matches!(self.config.kind, Chip8Kind::SuperChip8 | Chip8Kind::XoChip) =>
+            {
+                tracing::warn!(
+                    "SuperChip8 instruction {:?} on a machine without it",
+                    instruction
+                );
+            }
+            Chip8InstructionSet::SuperChip8(InstructionSetSuperChip8::Scrd { amount }) => {
+                self.display.scroll(Vector2::new(0, amount as isize));
+            }
+            Chip8InstructionSet::SuperChip8(InstructionSetSuperChip8::Scrr) => {
+                self.display.scroll(Vector2::new(4, 0));
+            }
+            Chip8InstructionSet::SuperChip8(InstructionSetSuperChip8::Scrl) => {
+                self.display.scroll(Vector2::new(-4, 0));
+            }
+            Chip8InstructionSet::SuperChip8(InstructionSetSuperChip8::Exit) => {
+                // Nothing to return to, so spin on this instruction
+                state.registers.program = state.registers.program.wrapping_sub(2);
+            }
+            Chip8InstructionSet::SuperChip8(InstructionSetSuperChip8::Lores) => {
+                self.display.set_hires(false);
+            }
+            Chip8InstructionSet::SuperChip8(InstructionSetSuperChip8::Hires) => {
+                self.display.set_hires(true);
+            }
             Chip8InstructionSet::SuperChip8(_) => todo!(),
-            Chip8InstructionSet::XoChip(_) => todo!(),
+            Chip8InstructionSet::XoChip(instruction) if self.config.kind != Chip8Kind::XoChip => {
+                tracing::warn!(
+                    "XO-CHIP instruction {:?} on a machine without it",
+                    instruction
+                );
+            }
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Ssub { bounds }) => {
+                let memory_translation_table = self.memory_translation_table.get().unwrap();
+
+                for (offset, register) in register_span(bounds.start as usize, bounds.end as usize)
+                {
+                    memory_translation_table
+                        .write(
+                            state.registers.index as usize + offset,
+                            &state.registers.work_registers[register..=register],
+                            CHIP8_ADDRESS_SPACE_ID,
+                        )
+                        .unwrap();
+                }
+            }
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Rsub { bounds }) => {
+                let memory_translation_table = self.memory_translation_table.get().unwrap();
+
+                for (offset, register) in register_span(bounds.start as usize, bounds.end as usize)
+                {
+                    memory_translation_table
+                        .read(
+                            state.registers.index as usize + offset,
+                            &mut state.registers.work_registers[register..=register],
+                            CHIP8_ADDRESS_SPACE_ID,
+                        )
+                        .unwrap();
+                }
+            }
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Scru { amount }) => {
+                self.display.scroll(Vector2::new(0, -(amount as isize)));
+            }
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Loadl) => {
+                let mut value = [0; 2];
+
+                self.memory_translation_table
+                    .get()
+                    .unwrap()
+                    .read(
+                        state.registers.program as usize,
+                        &mut value,
+                        CHIP8_ADDRESS_SPACE_ID,
+                    )
+                    .unwrap();
+
+                state.registers.index = u16::from_be_bytes(value);
+                state.registers.program = state.registers.program.wrapping_add(2);
+            }
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Plane { planes }) => {
+                self.display.set_selected_planes(planes);
+            }
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Audio) => {
+                let mut pattern = [0; 16];
+
+                for (cursor, buffer_section) in pattern.chunks_mut(2).enumerate() {
+                    self.memory_translation_table
+                        .get()
+                        .unwrap()
+                        .read(
+                            state.registers.index as usize + cursor * 2,
+                            buffer_section,
+                            CHIP8_ADDRESS_SPACE_ID,
+                        )
+                        .unwrap();
+                }
+
+                self.audio.set_pattern(pattern);
+            }
+            Chip8InstructionSet::XoChip(InstructionSetXoChip::Pitch { register }) => {
+                self.audio
+                    .set_pitch(state.registers.work_registers[register as usize]);
+            }
         }
     }
+
+    /// Steps over the next instruction, which on XO-CHIP may be the 4 byte long index load
+    fn skip_instruction(&self, state: &mut ProcessorState) {
+        let mut next = [0; 2];
+
+        if self.config.kind == Chip8Kind::XoChip {
+            self.memory_translation_table
+                .get()
+                .unwrap()
+                .read(
+                    state.registers.program as usize,
+                    &mut next,
+                    CHIP8_ADDRESS_SPACE_ID,
+                )
+                .unwrap();
+        }
+
+        let length = if next == [0xf0, 0x00] { 4 } else { 2 };
+        state.registers.program = state.registers.program.wrapping_add(length);
+    }
+
+    /// Reads `height` bytes of sprite for each selected plane from the index register
+    fn read_sprite(&self, state: &ProcessorState, height: usize) -> ArrayVec<u8, 128> {
+        let planes = self.display.selected_planes().count_ones() as usize;
+        let mut buffer = ArrayVec::<_, 128>::from_iter(std::iter::repeat(0).take(height * planes));
+
+        let mut cursor = 0;
+        for buffer_section in buffer.chunks_mut(2) {
+            self.memory_translation_table
+                .get()
+                .unwrap()
+                .read(
+                    state.registers.index as usize + cursor,
+                    buffer_section,
+                    CHIP8_ADDRESS_SPACE_ID,
+                )
+                .unwrap();
+            cursor += buffer_section.len();
+        }
+
+        buffer
+    }
+}
+
+/// Offsets from the index register and the registers that go there, for XO-CHIP's ranged save and load
+fn register_span(start: usize, end: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..=start.abs_diff(end)).map(move |offset| {
+        (
+            offset,
+            if start <= end {
+                start + offset
+            } else {
+                start - offset
+            },
+        )
+    })
 }
 
 #[inline]
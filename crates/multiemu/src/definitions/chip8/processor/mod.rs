@@ -2,13 +2,14 @@ use super::{audio::Chip8Audio, display::Chip8Display, timer::Chip8Timer, Chip8Ki
 use crate::{
     component::{
         input::{EmulatedGamepadMetadata, InputComponent},
+        processor::{DebuggableProcessor, ProcessorDebugState, ProcessorRegister},
         schedulable::SchedulableComponent,
         Component, ComponentId, FromConfig,
     },
     definitions::chip8::CHIP8_ADDRESS_SPACE_ID,
     input::{manager::InputManager, EmulatedGamepadId},
     machine::ComponentBuilder,
-    memory::MemoryTranslationTable,
+    memory::{AddressSpaceId, MemoryTranslationTable},
 };
 use arrayvec::ArrayVec;
 use decode::decode_instruction;
@@ -38,6 +39,100 @@ enum ExecutionState {
 
 // This is extremely complex because the chip8 cpu has a lot of non cpu machinery
 
+/// V0 to VF, then I and the program counter
+const DEBUG_REGISTERS: &[ProcessorRegister] = &[
+    ProcessorRegister {
+        name: "v0",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v1",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v2",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v3",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v4",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v5",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v6",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v7",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v8",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "v9",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "va",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "vb",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "vc",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "vd",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "ve",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "vf",
+        size: 1,
+        generic: Some("flags"),
+    },
+    ProcessorRegister {
+        name: "i",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "pc",
+        size: 2,
+        generic: Some("pc"),
+    },
+];
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Chip8ProcessorRegisters {
     work_registers: [u8; 16],
@@ -88,6 +183,8 @@ pub struct Chip8Processor {
     memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
     /// input manager + port for our keypad
     input_manager: OnceLock<(Arc<InputManager>, EmulatedGamepadId)>,
+    /// breakpoints and whether a debugger stopped us
+    debug: ProcessorDebugState,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -164,8 +261,10 @@ impl FromConfig for Chip8Processor {
                 config,
                 memory_translation_table: OnceLock::default(),
                 input_manager: OnceLock::default(),
+                debug: ProcessorDebugState::default(),
             })
             .set_schedulable(frequency, [], [])
+            .set_debuggable()
             .set_input(
                 [(
                     CHIP8_KEYPAD_GAMEPAD_TYPE,
@@ -197,75 +296,126 @@ impl InputComponent for Chip8Processor {
     }
 }
 
-impl SchedulableComponent for Chip8Processor {
-    fn run(&self, period: u64) {
-        let mut state = self.state.lock().unwrap();
+impl Chip8Processor {
+    /// Runs one instruction, or checks the keypad once if waiting on it
+    fn cycle(&self, state: &mut ProcessorState) {
+        match &state.execution_state {
+            ExecutionState::Normal => {
+                let mut instruction = [0; 2];
+                self.memory_translation_table
+                    .get()
+                    .unwrap()
+                    .read(
+                        state.registers.program as usize,
+                        &mut instruction,
+                        CHIP8_ADDRESS_SPACE_ID,
+                    )
+                    .unwrap();
 
-        for _ in 0..period {
-            match &state.execution_state {
-                ExecutionState::Normal => {
-                    let mut instruction = [0; 2];
-                    self.memory_translation_table
-                        .get()
-                        .unwrap()
-                        .read(
-                            state.registers.program as usize,
-                            &mut instruction,
-                            CHIP8_ADDRESS_SPACE_ID,
-                        )
-                        .unwrap();
-
-                    let decompiled_instruction = decode_instruction(instruction).unwrap();
-                    state.registers.program = state.registers.program.wrapping_add(2);
-
-                    tracing::trace!(
-                        "Decoded instruction {:?} from {:#04x}",
-                        instruction,
-                        state.registers.program
-                    );
-
-                    self.interpret_instruction(&mut state, decompiled_instruction);
-                }
-                ExecutionState::AwaitingKeyPress { register } => {
-                    // FIXME: A allocation every cycle isn't a good idea
-                    let mut pressed = Vec::new();
-                    let (input_manager, gamepad_id) = self.input_manager.get().unwrap();
-
-                    // Go through every chip8 key
-                    for key in 0x0..0xf {
-                        let keycode = Chip8KeyCode(key);
-
-                        if input_manager
-                            .get_input(*gamepad_id, keycode.try_into().unwrap())
-                            .as_digital()
-                        {
-                            pressed.push(keycode);
-                        }
+                let decompiled_instruction = decode_instruction(instruction).unwrap();
+                state.registers.program = state.registers.program.wrapping_add(2);
+
+                tracing::trace!(
+                    "Decoded instruction {:?} from {:#04x}",
+                    instruction,
+                    state.registers.program
+                );
+
+                self.interpret_instruction(state, decompiled_instruction);
+            }
+            ExecutionState::AwaitingKeyPress { register } => {
+                // FIXME: A allocation every cycle isn't a good idea
+                let mut pressed = Vec::new();
+                let (input_manager, gamepad_id) = self.input_manager.get().unwrap();
+
+                // Go through every chip8 key
+                for key in 0x0..0xf {
+                    let keycode = Chip8KeyCode(key);
+
+                    if input_manager
+                        .get_input(*gamepad_id, keycode.try_into().unwrap())
+                        .as_digital()
+                    {
+                        pressed.push(keycode);
                     }
+                }
 
-                    if !pressed.is_empty() {
-                        state.execution_state = ExecutionState::AwaitingKeyRelease {
-                            register: *register,
-                            keys: pressed,
-                        }
+                if !pressed.is_empty() {
+                    state.execution_state = ExecutionState::AwaitingKeyRelease {
+                        register: *register,
+                        keys: pressed,
                     }
                 }
-                ExecutionState::AwaitingKeyRelease { register, keys } => {
-                    let (input_manager, gamepad_id) = self.input_manager.get().unwrap();
-
-                    for key_code in keys {
-                        if !input_manager
-                            .get_input(*gamepad_id, (*key_code).try_into().unwrap())
-                            .as_digital()
-                        {
-                            let register = *register;
-                            state.registers.work_registers[register as usize] = key_code.0;
-                            state.execution_state = ExecutionState::Normal;
-                            break;
-                        }
+            }
+            ExecutionState::AwaitingKeyRelease { register, keys } => {
+                let (input_manager, gamepad_id) = self.input_manager.get().unwrap();
+
+                for key_code in keys {
+                    if !input_manager
+                        .get_input(*gamepad_id, (*key_code).try_into().unwrap())
+                        .as_digital()
+                    {
+                        let register = *register;
+                        state.registers.work_registers[register as usize] = key_code.0;
+                        state.execution_state = ExecutionState::Normal;
+                        break;
                     }
                 }
             }
         }
     }
 }
+
+impl SchedulableComponent for Chip8Processor {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..period {
+            if self.debug.should_stop(state.registers.program as usize) {
+                return;
+            }
+
+            self.cycle(&mut state);
+        }
+    }
+}
+
+impl DebuggableProcessor for Chip8Processor {
+    fn registers(&self) -> &'static [ProcessorRegister] {
+        DEBUG_REGISTERS
+    }
+
+    fn read_register(&self, index: usize) -> u64 {
+        let registers = &self.state.lock().unwrap().registers;
+
+        match index {
+            0..16 => registers.work_registers[index] as u64,
+            16 => registers.index as u64,
+            17 => registers.program as u64,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&self, index: usize, value: u64) {
+        let registers = &mut self.state.lock().unwrap().registers;
+
+        match index {
+            0..16 => registers.work_registers[index] = value as u8,
+            16 => registers.index = value as u16,
+            17 => registers.program = value as u16,
+            _ => {}
+        }
+    }
+
+    fn address_space(&self) -> AddressSpaceId {
+        CHIP8_ADDRESS_SPACE_ID
+    }
+
+    fn step(&self) {
+        self.cycle(&mut self.state.lock().unwrap());
+    }
+
+    fn debug_state(&self) -> &ProcessorDebugState {
+        &self.debug
+    }
+}
@@ -0,0 +1,515 @@
+use crate::{
+    component::{
+        audio::AudioComponent,
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        memory::MemoryComponent,
+        schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::{
+        io::{PortPins, PortRegisters, PortWiring},
+        noise::Lfsr,
+    },
+    input::{manager::InputManager, EmulatedGamepadId},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use num::rational::Ratio;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+const CHANNELS: usize = 3;
+
+const PORT_A: usize = 0;
+const PORT_B: usize = 1;
+
+/// Bits of each register that exist, the rest read back as 0
+const REGISTER_MASKS: [u8; 16] = [
+    0xff, 0x0f, 0xff, 0x0f, 0xff, 0x0f, 0x1f, 0xff, 0x1f, 0x1f, 0x1f, 0xff, 0xff, 0x0f, 0xff, 0xff,
+];
+
+/// The chip counts at a 1/8 of its input clock
+const CLOCK_DIVIDER: u64 = 8;
+/// Internal ticks averaged into each output sample
+const SAMPLE_DIVIDER: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ay38910Kind {
+    /// General Instrument original, with 16 step envelopes
+    #[default]
+    Ay38910,
+    /// Yamaha clone, with 32 step envelopes
+    Ym2149,
+}
+
+#[derive(Debug)]
+pub struct Ay38910Config {
+    pub kind: Ay38910Kind,
+    /// Input clock, like 1.7734 MHz on the ZX Spectrum 128
+    pub frequency: Ratio<u64>,
+    pub assigned_address_space: AddressSpaceId,
+    /// Writes here pick the register
+    pub select_address: usize,
+    /// Writes here go to the picked register
+    pub write_address: usize,
+    /// Reads here return the picked register, may be the same as either of the others
+    pub read_address: usize,
+    pub port_a: PortWiring,
+    pub port_b: PortWiring,
+    /// Gamepads the ports read from with [PortWiring::Gamepads]
+    pub gamepads: Vec<(EmulatedGamepadTypeId, EmulatedGamepadMetadata)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Envelope {
+    /// 0 to 31, counting towards the end of the ramp
+    step: u8,
+    /// Rising rather than falling
+    attack: bool,
+    holding: bool,
+    counter: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Ay38910State {
+    registers: [u8; 16],
+    selected: u8,
+    tone_counters: [u16; CHANNELS],
+    tone_outputs: [bool; CHANNELS],
+    noise_counter: u8,
+    noise: Lfsr,
+    envelope: Envelope,
+    /// Ticks summed into the sample being built
+    sample_ticks: u64,
+    sample_sums: [f32; CHANNELS],
+}
+
+impl Default for Ay38910State {
+    fn default() -> Self {
+        Self {
+            registers: [0; 16],
+            selected: 0,
+            tone_counters: [0; CHANNELS],
+            tone_outputs: [false; CHANNELS],
+            noise_counter: 0,
+            // 17 bit register feeding back bits 0 and 3
+            noise: Lfsr::new(17, 0b1001, 1),
+            envelope: Envelope {
+                step: 0,
+                attack: false,
+                holding: true,
+                counter: 0,
+            },
+            sample_ticks: 0,
+            sample_sums: [0.0; CHANNELS],
+        }
+    }
+}
+
+impl Ay38910State {
+    fn tone_period(&self, channel: usize) -> u16 {
+        u16::from_le_bytes([self.registers[channel * 2], self.registers[channel * 2 + 1]]).max(1)
+    }
+
+    fn envelope_level(&self) -> u8 {
+        if self.envelope.attack {
+            self.envelope.step
+        } else {
+            31 - self.envelope.step
+        }
+    }
+
+    fn restart_envelope(&mut self) {
+        self.envelope = Envelope {
+            step: 0,
+            attack: self.registers[13] & 0b0100 != 0,
+            holding: false,
+            counter: 0,
+        };
+    }
+
+    fn step_envelope(&mut self) {
+        let shape = self.registers[13];
+        let envelope = &mut self.envelope;
+
+        if envelope.step < 31 {
+            envelope.step += 1;
+            return;
+        }
+
+        let continuing = shape & 0b1000 != 0;
+        let alternate = shape & 0b0010 != 0;
+        let hold = shape & 0b0001 != 0;
+
+        if !continuing {
+            // Any shape without continue drops to silence after one ramp
+            envelope.attack = false;
+            envelope.holding = true;
+        } else if hold {
+            envelope.attack ^= alternate;
+            envelope.holding = true;
+        } else {
+            envelope.attack ^= alternate;
+            envelope.step = 0;
+        }
+    }
+
+    /// Advances every generator by one tick of the divided clock
+    fn tick(&mut self) {
+        for channel in 0..CHANNELS {
+            self.tone_counters[channel] += 1;
+
+            if self.tone_counters[channel] >= self.tone_period(channel) {
+                self.tone_counters[channel] = 0;
+                self.tone_outputs[channel] = !self.tone_outputs[channel];
+            }
+        }
+
+        // The noise generator runs at half the speed of the tones
+        self.noise_counter += 1;
+        if self.noise_counter >= (self.registers[6] & 0x1f).max(1) * 2 {
+            self.noise_counter = 0;
+            self.noise.clock();
+        }
+
+        if !self.envelope.holding {
+            self.envelope.counter += 1;
+
+            if self.envelope.counter
+                >= u16::from_le_bytes([self.registers[11], self.registers[12]]).max(1)
+            {
+                self.envelope.counter = 0;
+                self.step_envelope();
+            }
+        }
+    }
+
+    /// Level out of each channel, out of 31
+    fn levels(&self, kind: Ay38910Kind) -> [u8; CHANNELS] {
+        let mixer = self.registers[7];
+        let noise = self.noise.output();
+
+        std::array::from_fn(|channel| {
+            let tone_disabled = mixer & (1 << channel) != 0;
+            let noise_disabled = mixer & (1 << (channel + 3)) != 0;
+
+            if !((self.tone_outputs[channel] || tone_disabled) && (noise || noise_disabled)) {
+                return 0;
+            }
+
+            let amplitude = self.registers[8 + channel];
+            match (amplitude & 0x10 != 0, kind) {
+                (true, Ay38910Kind::Ym2149) => self.envelope_level(),
+                (true, Ay38910Kind::Ay38910) => coarse_level(self.envelope_level() >> 1),
+                (false, _) => coarse_level(amplitude & 0x0f),
+            }
+        })
+    }
+}
+
+/// 16 step levels line up with every other of the 32
+fn coarse_level(level: u8) -> u8 {
+    if level == 0 {
+        0
+    } else {
+        (level << 1) | 1
+    }
+}
+
+/// Roughly 1.5 dB per step, silent at 0
+fn volume(level: u8) -> f32 {
+    if level == 0 {
+        0.0
+    } else {
+        10f32.powf(-0.075 * (31 - level) as f32)
+    }
+}
+
+/// General Instrument AY-3-8910 programmable sound generator and its Yamaha YM2149 clone
+///
+/// Three square wave channels sharing a noise generator and an envelope, plus two 8 bit I/O ports. Samples come
+/// out at 1/32 of the input clock, one per channel
+#[derive(Debug)]
+pub struct Ay38910 {
+    config: Ay38910Config,
+    state: Mutex<Ay38910State>,
+    pins: PortPins<2>,
+    /// Interleaved samples not taken yet, never saved
+    samples: Mutex<VecDeque<f32>>,
+}
+
+impl Ay38910 {
+    fn port_registers(&self, state: &Ay38910State, port: usize) -> PortRegisters {
+        PortRegisters {
+            output: state.registers[14 + port],
+            direction: if state.registers[7] & (0x40 << port) != 0 {
+                0xff
+            } else {
+                0x00
+            },
+        }
+    }
+
+    fn read_register(&self, state: &Ay38910State) -> u8 {
+        let register = state.selected as usize;
+
+        match register {
+            14 | 15 => self
+                .pins
+                .read(register - 14, self.port_registers(state, register - 14)),
+            _ => state.registers[register] & REGISTER_MASKS[register],
+        }
+    }
+
+    fn write_register(&self, state: &mut Ay38910State, value: u8) {
+        let register = state.selected as usize;
+        state.registers[register] = value & REGISTER_MASKS[register];
+
+        if matches!(register, 7 | 14) {
+            self.pins.write(PORT_A, self.port_registers(state, PORT_A));
+        }
+        if matches!(register, 7 | 15) {
+            self.pins.write(PORT_B, self.port_registers(state, PORT_B));
+        }
+        if register == 13 {
+            state.restart_envelope();
+        }
+    }
+}
+
+impl Component for Ay38910 {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = Ay38910State::default();
+
+        for port in [PORT_A, PORT_B] {
+            self.pins.write(port, self.port_registers(&state, port));
+        }
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+        self.samples.lock().unwrap().clear();
+    }
+
+    fn audio_channels(&self) -> usize {
+        CHANNELS
+    }
+}
+
+impl AudioComponent for Ay38910 {
+    fn sample_rate(&self) -> Ratio<u64> {
+        self.config.frequency / (CLOCK_DIVIDER * SAMPLE_DIVIDER)
+    }
+
+    fn drain_samples(&self, output: &mut Vec<f32>) {
+        output.extend(self.samples.lock().unwrap().drain(..));
+    }
+}
+
+impl FromConfig for Ay38910 {
+    type Config = Ay38910Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let frequency = config.frequency / CLOCK_DIVIDER;
+        let assigned_address_space = config.assigned_address_space;
+        let mut addresses = vec![
+            config.select_address,
+            config.write_address,
+            config.read_address,
+        ];
+        addresses.sort_unstable();
+        addresses.dedup();
+
+        let pins = PortPins::new(
+            [
+                std::mem::take(&mut config.port_a),
+                std::mem::take(&mut config.port_b),
+            ],
+            std::mem::take(&mut config.gamepads),
+        );
+        let gamepads = pins.gamepads().to_vec();
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                pins,
+                samples: Mutex::default(),
+            })
+            .set_schedulable(frequency, [], [])
+            .set_audio()
+            .set_memory(
+                addresses
+                    .into_iter()
+                    .map(|address| (assigned_address_space, address..address + 1)),
+            );
+
+        if !gamepads.is_empty() {
+            let gamepad_types: Vec<_> = gamepads.iter().map(|(id, _)| id.clone()).collect();
+            component_builder.set_input(gamepads, gamepad_types);
+        }
+    }
+}
+
+impl SchedulableComponent for Ay38910 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+        let mut samples = self.samples.lock().unwrap();
+
+        for _ in 0..period {
+            let levels = state.levels(self.config.kind);
+            for (sum, level) in state.sample_sums.iter_mut().zip(levels) {
+                *sum += volume(level);
+            }
+            state.tick();
+            state.sample_ticks += 1;
+
+            if state.sample_ticks == SAMPLE_DIVIDER {
+                let sums = std::mem::take(&mut state.sample_sums);
+                samples.extend(sums.map(|sum| sum / SAMPLE_DIVIDER as f32));
+                state.sample_ticks = 0;
+            }
+        }
+
+        // Nobody is listening, keep a second at most
+        let limit = *(self.sample_rate() * CHANNELS as u64).ceil().numer() as usize;
+        if samples.len() > limit {
+            let excess = samples.len() - limit;
+            samples.drain(..excess);
+        }
+    }
+}
+
+impl InputComponent for Ay38910 {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.pins.set_input_manager(input_manager, gamepad_ids);
+    }
+}
+
+impl MemoryComponent for Ay38910 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        if address != self.config.read_address || buffer.len() != 1 {
+            errors.insert(address..address + buffer.len(), ReadMemoryRecord::Denied);
+            return;
+        }
+
+        buffer[0] = self.read_register(&self.state.lock().unwrap());
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        if buffer.len() != 1 {
+            errors.insert(address..address + buffer.len(), WriteMemoryRecord::Denied);
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if address == self.config.select_address {
+            // Addresses past 15 deselect the chip on real hardware, the low bits are close enough
+            state.selected = buffer[0] & 0x0f;
+        } else if address == self.config.write_address {
+            self.write_register(&mut state, buffer[0]);
+        } else {
+            errors.insert(address..address + 1, WriteMemoryRecord::Denied);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        // Reading has no side effects on this chip
+        let mut read_errors = RangeMap::default();
+        self.read_memory(address, buffer, address_space, &mut read_errors);
+
+        for (range, _) in read_errors {
+            errors.insert(range, PreviewMemoryRecord::Denied);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn tone_and_envelope() {
+        let (builder, psg) =
+            TestMachineBuilder::new()
+                .bus(0, 16)
+                .component::<Ay38910>(Ay38910Config {
+                    kind: Ay38910Kind::Ay38910,
+                    frequency: Ratio::from_integer(1_773_400),
+                    assigned_address_space: 0,
+                    select_address: 0xfffd,
+                    write_address: 0xbffd,
+                    read_address: 0xfffd,
+                    port_a: PortWiring::Unconnected,
+                    port_b: PortWiring::Unconnected,
+                    gamepads: Vec::new(),
+                });
+        let machine = builder.build();
+        let write = |register: u8, value: u8| {
+            machine.load(0, 0xfffd, &[register]);
+            machine.load(0, 0xbffd, &[value]);
+        };
+
+        // Channel A only, period 4, full volume
+        write(0, 4);
+        write(7, 0b0011_1110);
+        write(8, 0x0f);
+        machine.load(0, 0xfffd, &[1]);
+        assert_eq!(machine.peek(0, 0xfffd, 1), [0]);
+
+        machine.run_component::<Ay38910>(psg, 16);
+        let ay = machine.component::<Ay38910>(psg);
+        let mut samples = Vec::new();
+        ay.drain_samples(&mut samples);
+        // 4 ticks high, then 4 low
+        assert_eq!(
+            samples
+                .chunks(3)
+                .map(|sample| sample[0])
+                .collect::<Vec<_>>(),
+            [0.0, 1.0, 0.0, 1.0]
+        );
+        assert!(samples.chunks(3).all(|sample| sample[1] == 0.0));
+
+        // Single decay, which ends in silence and stays there
+        write(8, 0x10);
+        write(11, 1);
+        write(13, 0b0000);
+        machine.run_component::<Ay38910>(psg, 40);
+        let state = ay.state.lock().unwrap();
+        assert!(state.envelope.holding);
+        assert_eq!(state.envelope_level(), 0);
+    }
+}
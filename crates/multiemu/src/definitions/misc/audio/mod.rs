@@ -0,0 +1,3 @@
+pub mod ay3_8910;
+pub mod sid;
+pub mod sn76489;
@@ -0,0 +1,607 @@
+use crate::{
+    component::{
+        audio::AudioComponent, memory::MemoryComponent, schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::noise::Lfsr,
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use num::rational::Ratio;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, f32::consts::PI, ops::Range, sync::Mutex};
+
+const VOICES: usize = 3;
+/// Registers each voice takes up, one after another from 0x00
+const VOICE_REGISTERS: usize = 7;
+/// The chip decodes 5 address lines, so it repeats every 32 bytes
+const REGISTER_COUNT: usize = 0x20;
+
+const FILTER_CUTOFF_LOW: usize = 0x15;
+const FILTER_CUTOFF_HIGH: usize = 0x16;
+const FILTER_ROUTING: usize = 0x17;
+const MODE_VOLUME: usize = 0x18;
+const POT_X: usize = 0x19;
+const POT_Y: usize = 0x1a;
+const OSCILLATOR_3: usize = 0x1b;
+const ENVELOPE_3: usize = 0x1c;
+
+const GATE: u8 = 0b0000_0001;
+const SYNC: u8 = 0b0000_0010;
+const RING: u8 = 0b0000_0100;
+const TEST: u8 = 0b0000_1000;
+const TRIANGLE: u8 = 0b0001_0000;
+const SAWTOOTH: u8 = 0b0010_0000;
+const PULSE: u8 = 0b0100_0000;
+const NOISE: u8 = 0b1000_0000;
+
+const LOW_PASS: u8 = 0b0001_0000;
+const BAND_PASS: u8 = 0b0010_0000;
+const HIGH_PASS: u8 = 0b0100_0000;
+/// Takes voice 3 out of the mix, as long as it isn't going through the filter
+const VOICE_3_OFF: u8 = 0b1000_0000;
+
+/// The datasheet's register shifts left with bits 22 and 17 feeding back, [Lfsr] shifts right so its bits are mirrored
+const NOISE_WIDTH: u8 = 23;
+const NOISE_TAPS: u32 = 0b10_0001;
+/// 0x7ffff8 in the datasheet's bit order
+const NOISE_SEED: u32 = 0x0fffff;
+
+/// Cycles between envelope steps for each of the 16 rates
+///
+/// Decay and release take three times as long as attack at the same rate because of the exponential counter
+const RATE_PERIODS: [u16; 16] = [
+    9, 32, 63, 95, 149, 220, 267, 313, 392, 977, 1954, 3126, 3907, 11720, 19532, 31251,
+];
+
+/// Cycles averaged into each output sample
+const SAMPLE_DIVIDER: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SidKind {
+    /// The original, with its darker filter and the DC offset that lets volume writes play samples
+    #[default]
+    Mos6581,
+    /// The later revision in the C64C, with a linear filter and almost no DC offset
+    Mos8580,
+}
+
+impl SidKind {
+    /// Rough fit of where the filter cutoff ends up for an 11 bit cutoff register
+    fn cutoff_frequency(self, cutoff: u16) -> f32 {
+        let position = cutoff as f32 / 2047.0;
+
+        match self {
+            Self::Mos6581 => 220.0 + 17800.0 * position * position,
+            Self::Mos8580 => 30.0 + 12000.0 * position,
+        }
+    }
+
+    /// What the output sits at with every voice silent, in the same units as one voice at full level
+    fn dc_offset(self) -> f32 {
+        match self {
+            Self::Mos6581 => 0.5,
+            Self::Mos8580 => 0.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SidConfig {
+    pub kind: SidKind,
+    /// Input clock, the processor clock on the C64
+    pub frequency: Ratio<u64>,
+    pub assigned_address_space: AddressSpaceId,
+    /// The registers repeat every 32 bytes across this
+    pub assigned_range: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum EnvelopePhase {
+    Attack,
+    DecaySustain,
+    #[default]
+    Release,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Envelope {
+    phase: EnvelopePhase,
+    level: u8,
+    rate_counter: u16,
+    /// Slows decay and release down as the level drops, which makes them sound exponential
+    exponential_counter: u8,
+}
+
+impl Envelope {
+    /// Steps of the rate counter per level step at the current level
+    fn exponential_period(&self) -> u8 {
+        match self.level {
+            94.. => 1,
+            55..=93 => 2,
+            27..=54 => 4,
+            15..=26 => 8,
+            7..=14 => 16,
+            1..=6 => 30,
+            0 => 1,
+        }
+    }
+
+    fn tick(&mut self, attack_decay: u8, sustain_release: u8) {
+        let rate = match self.phase {
+            EnvelopePhase::Attack => attack_decay >> 4,
+            EnvelopePhase::DecaySustain => attack_decay & 0x0f,
+            EnvelopePhase::Release => sustain_release & 0x0f,
+        };
+
+        self.rate_counter += 1;
+        if self.rate_counter < RATE_PERIODS[rate as usize] {
+            return;
+        }
+        self.rate_counter = 0;
+
+        if self.phase == EnvelopePhase::Attack {
+            self.level = self.level.saturating_add(1);
+
+            if self.level == 0xff {
+                self.phase = EnvelopePhase::DecaySustain;
+                self.exponential_counter = 0;
+            }
+            return;
+        }
+
+        self.exponential_counter += 1;
+        if self.exponential_counter < self.exponential_period() {
+            return;
+        }
+        self.exponential_counter = 0;
+
+        match self.phase {
+            // The sustain nibble is repeated into both halves of the level it holds at
+            EnvelopePhase::DecaySustain if self.level > (sustain_release >> 4) * 0x11 => {
+                self.level -= 1;
+            }
+            EnvelopePhase::Release => self.level = self.level.saturating_sub(1),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Voice {
+    /// 24 bit phase, advanced by the frequency every cycle
+    accumulator: u32,
+    /// 23 bit noise register, clocked whenever bit 19 of the accumulator rises
+    noise: Lfsr,
+    envelope: Envelope,
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        Self {
+            accumulator: 0,
+            noise: Lfsr::new(NOISE_WIDTH, NOISE_TAPS, NOISE_SEED),
+            envelope: Envelope::default(),
+        }
+    }
+}
+
+impl Voice {
+    fn noise_output(&self) -> u16 {
+        // Back to the datasheet's bit order, which the taps below are in
+        let noise = self.noise.state().reverse_bits() >> (32 - NOISE_WIDTH as u32);
+
+        (((noise & 0x100000) >> 9)
+            | ((noise & 0x040000) >> 8)
+            | ((noise & 0x004000) >> 5)
+            | ((noise & 0x000800) >> 3)
+            | ((noise & 0x000200) >> 2)
+            | ((noise & 0x000020) << 1)
+            | ((noise & 0x000004) << 3)
+            | ((noise & 0x000001) << 4)) as u16
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SidState {
+    registers: [u8; REGISTER_COUNT],
+    /// Last byte written to the chip, which the write only registers read back
+    ///
+    /// It fades away after a while on a real chip, here it stays
+    bus_value: u8,
+    voices: [Voice; VOICES],
+    filter_low_pass: f32,
+    filter_band_pass: f32,
+    sample_ticks: u64,
+    sample_sum: f32,
+}
+
+impl SidState {
+    fn voice_register(&self, voice: usize, offset: usize) -> u8 {
+        self.registers[voice * VOICE_REGISTERS + offset]
+    }
+
+    fn frequency(&self, voice: usize) -> u32 {
+        u16::from_le_bytes([self.voice_register(voice, 0), self.voice_register(voice, 1)]) as u32
+    }
+
+    fn pulse_width(&self, voice: usize) -> u16 {
+        u16::from_le_bytes([
+            self.voice_register(voice, 2),
+            self.voice_register(voice, 3) & 0x0f,
+        ])
+    }
+
+    fn control(&self, voice: usize) -> u8 {
+        self.voice_register(voice, 4)
+    }
+
+    /// Voice that syncs and ring modulates this one
+    fn source(voice: usize) -> usize {
+        (voice + VOICES - 1) % VOICES
+    }
+
+    /// 12 bit output of a voice's waveform generator, selected waveforms are ANDed together
+    fn waveform(&self, voice: usize) -> u16 {
+        let control = self.control(voice);
+        let accumulator = self.voices[voice].accumulator;
+        let mut output = 0xfff;
+
+        if control & (TRIANGLE | SAWTOOTH | PULSE | NOISE) == 0 {
+            return 0;
+        }
+
+        if control & TRIANGLE != 0 {
+            let source = self.voices[Self::source(voice)].accumulator;
+            let msb = if control & RING != 0 {
+                (accumulator ^ source) & 0x800000
+            } else {
+                accumulator & 0x800000
+            };
+            let folded = if msb != 0 { !accumulator } else { accumulator };
+
+            output &= ((folded >> 11) & 0xfff) as u16;
+        }
+
+        if control & SAWTOOTH != 0 {
+            output &= (accumulator >> 12) as u16;
+        }
+
+        // The test bit holds the pulse high
+        if control & PULSE != 0
+            && control & TEST == 0
+            && ((accumulator >> 12) as u16) < self.pulse_width(voice)
+        {
+            output = 0;
+        }
+
+        if control & NOISE != 0 {
+            output &= self.voices[voice].noise_output();
+        }
+
+        output
+    }
+
+    fn write(&mut self, register: usize, value: u8) {
+        self.bus_value = value;
+
+        if register >= POT_X {
+            return;
+        }
+
+        let previous = self.registers[register];
+        self.registers[register] = value;
+
+        if register % VOICE_REGISTERS == 4 && register < VOICES * VOICE_REGISTERS {
+            let voice = &mut self.voices[register / VOICE_REGISTERS];
+
+            match (previous & GATE != 0, value & GATE != 0) {
+                (false, true) => voice.envelope.phase = EnvelopePhase::Attack,
+                (true, false) => voice.envelope.phase = EnvelopePhase::Release,
+                _ => {}
+            }
+
+            if value & TEST != 0 {
+                voice.accumulator = 0;
+                voice.noise.set_state(NOISE_SEED);
+            }
+        }
+    }
+
+    fn read(&self, register: usize) -> u8 {
+        match register {
+            // Nothing plugged in, which charges up straight away
+            POT_X | POT_Y => 0xff,
+            OSCILLATOR_3 => (self.waveform(2) >> 4) as u8,
+            ENVELOPE_3 => self.voices[2].envelope.level,
+            _ => self.bus_value,
+        }
+    }
+
+    fn tick(&mut self) {
+        let mut msb_rose = [false; VOICES];
+
+        for (voice, msb_rose) in msb_rose.iter_mut().enumerate() {
+            let control = self.control(voice);
+            let frequency = self.frequency(voice);
+            let attack_decay = self.voice_register(voice, 5);
+            let sustain_release = self.voice_register(voice, 6);
+            let state = &mut self.voices[voice];
+
+            state.envelope.tick(attack_decay, sustain_release);
+
+            if control & TEST != 0 {
+                continue;
+            }
+
+            let previous = state.accumulator;
+            state.accumulator = (previous + frequency) & 0xffffff;
+            *msb_rose = previous & 0x800000 == 0 && state.accumulator & 0x800000 != 0;
+
+            if previous & 0x080000 == 0 && state.accumulator & 0x080000 != 0 {
+                state.noise.clock();
+            }
+        }
+
+        // Synced after everything moved, so a voice syncing to the one after it sees this cycle's edge too
+        for voice in 0..VOICES {
+            if self.control(voice) & SYNC != 0 && msb_rose[Self::source(voice)] {
+                self.voices[voice].accumulator = 0;
+            }
+        }
+    }
+
+    /// Output level for this cycle, through the filter and the master volume
+    fn output(&mut self, kind: SidKind, frequency: f32) -> f32 {
+        let routing = self.registers[FILTER_ROUTING];
+        let mode_volume = self.registers[MODE_VOLUME];
+        let mut filter_input = 0.0;
+        let mut direct = 0.0;
+
+        for voice in 0..VOICES {
+            let filtered = routing & (1 << voice) != 0;
+            if voice == 2 && mode_volume & VOICE_3_OFF != 0 && !filtered {
+                continue;
+            }
+
+            let level = (self.waveform(voice) as f32 - 2048.0) / 2048.0
+                * self.voices[voice].envelope.level as f32
+                / 255.0;
+            if filtered {
+                filter_input += level;
+            } else {
+                direct += level;
+            }
+        }
+
+        // State variable filter, the cutoff is clamped where this stops being stable
+        let cutoff = ((self.registers[FILTER_CUTOFF_HIGH] as u16) << 3)
+            | (self.registers[FILTER_CUTOFF_LOW] & 0b111) as u16;
+        let w0 = (2.0 * PI * kind.cutoff_frequency(cutoff) / frequency).min(1.0);
+        let damping = 1.0 / (0.707 + (routing >> 4) as f32 / 8.0);
+        let high_pass = filter_input - self.filter_low_pass - damping * self.filter_band_pass;
+        self.filter_band_pass += w0 * high_pass;
+        self.filter_low_pass += w0 * self.filter_band_pass;
+
+        let mut filtered = 0.0;
+        if mode_volume & LOW_PASS != 0 {
+            filtered += self.filter_low_pass;
+        }
+        if mode_volume & BAND_PASS != 0 {
+            filtered += self.filter_band_pass;
+        }
+        if mode_volume & HIGH_PASS != 0 {
+            filtered += high_pass;
+        }
+
+        (direct + filtered + kind.dc_offset()) * (mode_volume & 0x0f) as f32 / 15.0 / VOICES as f32
+    }
+}
+
+/// MOS 6581 and 8580 Sound Interface Device
+///
+/// Three voices, each with a triangle, sawtooth, pulse and noise generator and an ADSR envelope, that can sync and
+/// ring modulate each other, mixed through a multimode filter. Samples come out mono at 1/20 of the input clock.
+/// Combined waveforms are approximated by ANDing them, and the ADSR delay bug isn't emulated
+#[derive(Debug)]
+pub struct Sid {
+    config: SidConfig,
+    state: Mutex<SidState>,
+    /// Samples not taken yet, never saved
+    samples: Mutex<VecDeque<f32>>,
+}
+
+impl Sid {
+    /// A register write, for playing back register dumps without a processor
+    pub fn write(&self, register: u8, value: u8) {
+        self.state
+            .lock()
+            .unwrap()
+            .write(register as usize % REGISTER_COUNT, value);
+    }
+
+    pub fn read(&self, register: u8) -> u8 {
+        self.state
+            .lock()
+            .unwrap()
+            .read(register as usize % REGISTER_COUNT)
+    }
+}
+
+impl Component for Sid {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = SidState::default();
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+        self.samples.lock().unwrap().clear();
+    }
+
+    fn audio_channels(&self) -> usize {
+        1
+    }
+}
+
+impl AudioComponent for Sid {
+    fn sample_rate(&self) -> Ratio<u64> {
+        self.config.frequency / SAMPLE_DIVIDER
+    }
+
+    fn drain_samples(&self, output: &mut Vec<f32>) {
+        output.extend(self.samples.lock().unwrap().drain(..));
+    }
+}
+
+impl FromConfig for Sid {
+    type Config = SidConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let frequency = config.frequency;
+        let assigned_address_space = config.assigned_address_space;
+        let assigned_range = config.assigned_range.clone();
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                samples: Mutex::default(),
+            })
+            .set_schedulable(frequency, [], [])
+            .set_audio()
+            .set_memory([(assigned_address_space, assigned_range)]);
+    }
+}
+
+impl SchedulableComponent for Sid {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+        let mut samples = self.samples.lock().unwrap();
+        let frequency =
+            *self.config.frequency.numer() as f32 / *self.config.frequency.denom() as f32;
+
+        for _ in 0..period {
+            state.tick();
+            state.sample_sum += state.output(self.config.kind, frequency);
+            state.sample_ticks += 1;
+
+            if state.sample_ticks == SAMPLE_DIVIDER {
+                samples.push_back(state.sample_sum / SAMPLE_DIVIDER as f32);
+                state.sample_sum = 0.0;
+                state.sample_ticks = 0;
+            }
+        }
+
+        // Nobody is listening, keep a second at most
+        let limit = *self.sample_rate().ceil().numer() as usize;
+        if samples.len() > limit {
+            let excess = samples.len() - limit;
+            samples.drain(..excess);
+        }
+    }
+}
+
+impl MemoryComponent for Sid {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        let state = self.state.lock().unwrap();
+        let offset = address - self.config.assigned_range.start;
+
+        for (register, byte) in (offset..).zip(buffer.iter_mut()) {
+            *byte = state.read(register % REGISTER_COUNT);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let offset = address - self.config.assigned_range.start;
+
+        for (register, value) in (offset..).zip(buffer.iter().copied()) {
+            state.write(register % REGISTER_COUNT, value);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        // Reading has no side effects on this chip
+        self.read_memory(address, buffer, address_space, &mut RangeMap::default());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn oscillator_and_envelope_readback() {
+        let (builder, sid) = TestMachineBuilder::new()
+            .bus(0, 16)
+            .component::<Sid>(SidConfig {
+                kind: SidKind::Mos6581,
+                frequency: Ratio::from_integer(985_248),
+                assigned_address_space: 0,
+                assigned_range: 0xd400..0xd800,
+            });
+        let machine = builder.build();
+
+        // Voice 3 sawtooth, fastest attack, full sustain, written through a mirror
+        machine.load(0, 0xd420 + 0x0e, &[0x00, 0x10]);
+        machine.load(0, 0xd420 + 0x13, &[0x00, 0xf0]);
+        machine.load(0, 0xd420 + 0x12, &[SAWTOOTH | GATE]);
+        // Write only registers read back what was last written
+        assert_eq!(machine.peek(0, 0xd400, 1), [SAWTOOTH | GATE]);
+
+        machine.run_component::<Sid>(sid, 0x100);
+        // The accumulator moved 0x100 * 0x1000, whose top 8 bits are OSC3, and the attack stepped every 9 cycles
+        assert_eq!(machine.peek(0, 0xd41b, 2), [0x10, 28]);
+
+        // Attack tops out and holds at the sustain level
+        machine.run_component::<Sid>(sid, 9 * 0xff);
+        assert_eq!(machine.peek(0, 0xd41c, 1), [0xff]);
+
+        // Release falls, slower as it goes
+        machine.load(0, 0xd412, &[SAWTOOTH]);
+        machine.run_component::<Sid>(sid, 9 * 100);
+        let level = machine.peek(0, 0xd41c, 1)[0];
+        assert!(level < 0xff && level > 0xff - 100);
+    }
+
+    #[test]
+    fn noise_matches_datasheet_register() {
+        let mut voice = Voice::default();
+        let mut register: u32 = 0x7ffff8;
+
+        for _ in 0..1000 {
+            voice.noise.clock();
+            let feedback = ((register >> 22) ^ (register >> 17)) & 1;
+            register = ((register << 1) & 0x7fffff) | feedback;
+
+            assert_eq!(
+                voice.noise.state().reverse_bits() >> (32 - NOISE_WIDTH as u32),
+                register
+            );
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use crate::{
+    component::{
+        audio::AudioComponent, memory::MemoryComponent, schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::noise::Lfsr,
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use num::rational::Ratio;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, ops::Range, sync::Mutex};
+
+const TONE_CHANNELS: usize = 3;
+const NOISE_CHANNEL: usize = 3;
+
+/// The chip counts at a 1/16 of its input clock
+const CLOCK_DIVIDER: u64 = 16;
+/// Internal ticks averaged into each output sample
+const SAMPLE_DIVIDER: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sn76489Kind {
+    /// Texas Instruments original, like in the ColecoVision and BBC Micro
+    #[default]
+    Sn76489,
+    /// The copy built into the Master System VDP, with a longer noise register and tone period 0 holding high
+    Sega,
+}
+
+impl Sn76489Kind {
+    /// Width of the noise register and the taps of white noise
+    fn noise(self) -> (u8, u32) {
+        match self {
+            Sn76489Kind::Sn76489 => (15, 0b0011),
+            Sn76489Kind::Sega => (16, 0b1001),
+        }
+    }
+
+    fn noise_seed(self) -> u32 {
+        1 << (self.noise().0 - 1)
+    }
+}
+
+#[derive(Debug)]
+pub struct Sn76489Config {
+    pub kind: Sn76489Kind,
+    /// Input clock, 3.579545 MHz on NTSC machines
+    pub frequency: Ratio<u64>,
+    /// Writes anywhere in here go to the chip, which can't be read. None for machines that decode the port
+    /// themselves and go through [Sn76489::write]
+    pub assigned_ports: Option<(AddressSpaceId, Range<usize>)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sn76489State {
+    /// 10 bit periods of the tone channels
+    tone_periods: [u16; TONE_CHANNELS],
+    /// Mode and rate of the noise channel
+    noise_control: u8,
+    /// 4 bit attenuation of every channel, 15 being off
+    attenuations: [u8; 4],
+    /// Register the last latch byte picked, data bytes go to it
+    latched: u8,
+    counters: [u16; 4],
+    outputs: [bool; 4],
+    noise: Lfsr,
+    /// Ticks summed into the sample being built
+    sample_ticks: u64,
+    sample_sum: f32,
+}
+
+impl Sn76489State {
+    fn new(kind: Sn76489Kind) -> Self {
+        let (width, taps) = kind.noise();
+
+        Self {
+            tone_periods: [0; TONE_CHANNELS],
+            noise_control: 0,
+            attenuations: [0x0f; 4],
+            latched: 0,
+            counters: [0; 4],
+            outputs: [false; 4],
+            noise: Lfsr::new(width, taps, kind.noise_seed()),
+            sample_ticks: 0,
+            sample_sum: 0.0,
+        }
+    }
+
+    fn noise_period(&self) -> u16 {
+        match self.noise_control & 0b11 {
+            0b11 => self.tone_periods[2],
+            rate => 0x10 << rate,
+        }
+    }
+
+    /// Advances every generator by one tick of the divided clock
+    fn tick(&mut self, kind: Sn76489Kind) {
+        for channel in 0..4 {
+            let period = if channel == NOISE_CHANNEL {
+                self.noise_period()
+            } else {
+                self.tone_periods[channel]
+            };
+
+            // Sega's copy holds the output high for the lowest periods, which games use to play samples
+            if kind == Sn76489Kind::Sega && period <= 1 && channel != NOISE_CHANNEL {
+                self.outputs[channel] = true;
+                continue;
+            }
+
+            self.counters[channel] = self.counters[channel].saturating_sub(1);
+            if self.counters[channel] == 0 {
+                self.counters[channel] = if period == 0 { 0x400 } else { period };
+                self.outputs[channel] = !self.outputs[channel];
+
+                // The noise register shifts on the rising edge of its own square wave
+                if channel == NOISE_CHANNEL && self.outputs[channel] {
+                    self.noise.clock();
+                }
+            }
+        }
+    }
+
+    /// Mixed level of every channel, between 0 and 1
+    fn level(&self) -> f32 {
+        let noise = self.noise.output();
+
+        (0..4)
+            .filter(|channel| {
+                if *channel == NOISE_CHANNEL {
+                    noise
+                } else {
+                    self.outputs[*channel]
+                }
+            })
+            .map(|channel| volume(self.attenuations[channel]))
+            .sum::<f32>()
+            / 4.0
+    }
+}
+
+/// 2 dB a step, silent at 15
+fn volume(attenuation: u8) -> f32 {
+    if attenuation >= 0x0f {
+        0.0
+    } else {
+        10f32.powf(-0.1 * attenuation as f32)
+    }
+}
+
+/// Texas Instruments SN76489 programmable sound generator, and the copy of it in the Master System and Game Gear
+///
+/// Three square wave channels and a noise channel, each with its own attenuation, behind a single write only port.
+/// Samples come out mono at 1/64 of the input clock
+#[derive(Debug)]
+pub struct Sn76489 {
+    config: Sn76489Config,
+    state: Mutex<Sn76489State>,
+    /// Samples not taken yet, never saved
+    samples: Mutex<VecDeque<f32>>,
+}
+
+impl Sn76489 {
+    /// A byte written to the chip, either a latch byte picking a register or more data for the latched one
+    pub fn write(&self, value: u8) {
+        let mut state = self.state.lock().unwrap();
+
+        if value & 0x80 != 0 {
+            state.latched = (value >> 4) & 0b111;
+        }
+
+        let channel = (state.latched >> 1) as usize;
+        let volume = state.latched & 1 != 0;
+
+        match (channel, volume, value & 0x80 != 0) {
+            (_, true, _) => state.attenuations[channel] = value & 0x0f,
+            (NOISE_CHANNEL, false, _) => {
+                state.noise_control = value & 0b111;
+
+                // Writing the noise register restarts it, picking between white noise and a repeating pattern
+                let (_, taps) = self.config.kind.noise();
+                state
+                    .noise
+                    .set_taps(if value & 0b100 != 0 { taps } else { 1 });
+                state.noise.set_state(self.config.kind.noise_seed());
+            }
+            (_, false, true) => {
+                state.tone_periods[channel] =
+                    (state.tone_periods[channel] & 0x3f0) | (value & 0x0f) as u16;
+            }
+            (_, false, false) => {
+                state.tone_periods[channel] =
+                    (state.tone_periods[channel] & 0x00f) | (((value & 0x3f) as u16) << 4);
+            }
+        }
+    }
+}
+
+impl Component for Sn76489 {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = Sn76489State::new(self.config.kind);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+        self.samples.lock().unwrap().clear();
+    }
+
+    fn audio_channels(&self) -> usize {
+        1
+    }
+}
+
+impl AudioComponent for Sn76489 {
+    fn sample_rate(&self) -> Ratio<u64> {
+        self.config.frequency / (CLOCK_DIVIDER * SAMPLE_DIVIDER)
+    }
+
+    fn drain_samples(&self, output: &mut Vec<f32>) {
+        output.extend(self.samples.lock().unwrap().drain(..));
+    }
+}
+
+impl FromConfig for Sn76489 {
+    type Config = Sn76489Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let frequency = config.frequency / CLOCK_DIVIDER;
+        let assigned_ports = config.assigned_ports.clone();
+        let state = Sn76489State::new(config.kind);
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::new(state),
+                samples: Mutex::default(),
+            })
+            .set_schedulable(frequency, [], [])
+            .set_audio()
+            .set_memory(assigned_ports);
+    }
+}
+
+impl SchedulableComponent for Sn76489 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+        let mut samples = self.samples.lock().unwrap();
+
+        for _ in 0..period {
+            state.tick(self.config.kind);
+            state.sample_sum += state.level();
+            state.sample_ticks += 1;
+
+            if state.sample_ticks == SAMPLE_DIVIDER {
+                samples.push_back(state.sample_sum / SAMPLE_DIVIDER as f32);
+                state.sample_sum = 0.0;
+                state.sample_ticks = 0;
+            }
+        }
+
+        // Nobody is listening, keep a second at most
+        let limit = *self.sample_rate().ceil().numer() as usize;
+        if samples.len() > limit {
+            let excess = samples.len() - limit;
+            samples.drain(..excess);
+        }
+    }
+}
+
+impl MemoryComponent for Sn76489 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        errors.insert(address..address + buffer.len(), ReadMemoryRecord::Denied);
+    }
+
+    fn write_memory(
+        &self,
+        _address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        for byte in buffer {
+            self.write(*byte);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        errors.insert(address..address + buffer.len(), PreviewMemoryRecord::Denied);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn tone_and_noise() {
+        let (builder, psg) =
+            TestMachineBuilder::new()
+                .bus(0, 8)
+                .component::<Sn76489>(Sn76489Config {
+                    kind: Sn76489Kind::Sega,
+                    frequency: Ratio::from_integer(3_579_545),
+                    assigned_ports: Some((0, 0x7e..0x80)),
+                });
+        let machine = builder.build();
+
+        // Channel 0 at period 4 and full volume, through a latch byte and a data byte
+        machine.load(0, 0x7f, &[0x84]);
+        machine.load(0, 0x7f, &[0x00]);
+        machine.load(0, 0x7f, &[0x90]);
+
+        machine.run_component::<Sn76489>(psg, 16);
+        let sn76489 = machine.component::<Sn76489>(psg);
+        let mut samples = Vec::new();
+        sn76489.drain_samples(&mut samples);
+        // 4 ticks high, then 4 low, with the other channels off
+        assert_eq!(samples, [0.25, 0.0, 0.25, 0.0]);
+
+        // Channel 1 gets a 10 bit period, with the second byte holding the upper bits
+        machine.load(0, 0x7f, &[0xa5]);
+        machine.load(0, 0x7f, &[0x3f]);
+        assert_eq!(sn76489.state.lock().unwrap().tone_periods[1], 0x3f5);
+
+        // Periodic noise restarts with only the top bit set
+        machine.run_component::<Sn76489>(psg, 64);
+        machine.load(0, 0x7f, &[0xe0]);
+        let state = sn76489.state.lock().unwrap();
+        assert_eq!(state.noise.state(), 0x8000);
+        assert_eq!(state.noise_period(), 0x10);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::component::expansion::{ExpansionDevice, Passthrough};
+
+/// Letters in the order of the nibbles they encode
+const GAME_GENIE_ALPHABET: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameGenieCode {
+    pub address: u16,
+    pub value: u8,
+    /// Only substitute if the cartridge has this there, which is how 8 letter codes avoid hitting the wrong bank
+    pub compare: Option<u8>,
+}
+
+impl GameGenieCode {
+    pub fn parse(code: &str) -> Option<Self> {
+        let nibbles = code
+            .bytes()
+            .map(|letter| {
+                GAME_GENIE_ALPHABET
+                    .iter()
+                    .position(|candidate| *candidate == letter.to_ascii_uppercase())
+                    .map(|position| position as u16)
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        if nibbles.len() != 6 && nibbles.len() != 8 {
+            return None;
+        }
+
+        let n = |index: usize| nibbles[index];
+
+        let address = 0x8000
+            | ((n(3) & 7) << 12)
+            | ((n(5) & 7) << 8)
+            | ((n(4) & 8) << 8)
+            | ((n(2) & 7) << 4)
+            | ((n(1) & 8) << 4)
+            | (n(4) & 7)
+            | (n(3) & 8);
+
+        // The last letter holds the high bit of the value for 6 letter codes, and of the compare for 8 letter ones
+        let value_high_bit = if nibbles.len() == 6 { n(5) } else { n(7) };
+        let value = ((n(1) & 7) << 4) | ((n(0) & 8) << 4) | (n(0) & 7) | (value_high_bit & 8);
+
+        let compare = (nibbles.len() == 8)
+            .then(|| ((n(7) & 7) << 4) | ((n(6) & 8) << 4) | (n(6) & 7) | (n(5) & 8));
+
+        Some(Self {
+            address,
+            value: value as u8,
+            compare: compare.map(|compare| compare as u8),
+        })
+    }
+}
+
+/// Sits between the console and the cartridge, swapping out bytes the cartridge returns
+#[derive(Debug)]
+pub struct GameGenie {
+    codes: Vec<GameGenieCode>,
+}
+
+impl GameGenie {
+    pub fn new(codes: &[String]) -> Result<Self, String> {
+        Ok(Self {
+            codes: codes
+                .iter()
+                .map(|code| {
+                    GameGenieCode::parse(code)
+                        .ok_or_else(|| format!("{} is not a valid Game Genie code", code))
+                })
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn patch(&self, address: usize, buffer: &mut [u8]) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            for code in &self.codes {
+                if address + offset == code.address as usize
+                    && code.compare.is_none_or(|compare| compare == *byte)
+                {
+                    *byte = code.value;
+                }
+            }
+        }
+    }
+}
+
+impl ExpansionDevice for GameGenie {
+    fn name(&self) -> &'static str {
+        "Game Genie"
+    }
+
+    fn read(&self, address: usize, buffer: &mut [u8], passthrough: &Passthrough) -> bool {
+        let success = passthrough.read(address, buffer);
+        self.patch(address, buffer);
+        success
+    }
+
+    fn preview(&self, address: usize, buffer: &mut [u8], passthrough: &Passthrough) -> bool {
+        let success = passthrough.preview(address, buffer);
+        self.patch(address, buffer);
+        success
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_codes() {
+        assert_eq!(
+            GameGenieCode::parse("SXIOPO"),
+            Some(GameGenieCode {
+                address: 0x91d9,
+                value: 0xad,
+                compare: None
+            })
+        );
+        assert_eq!(GameGenieCode::parse("SXIOP"), None);
+    }
+}
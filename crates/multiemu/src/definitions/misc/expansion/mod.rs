@@ -0,0 +1,198 @@
+use crate::{
+    component::{
+        expansion::{ExpansionDevice, Passthrough},
+        memory::MemoryComponent,
+        Component, FromConfig,
+    },
+    config::GLOBAL_CONFIG,
+    machine::ComponentBuilder,
+    memory::{
+        AddressSpaceId, MemoryTranslationTable, PreviewMemoryRecord, ReadMemoryRecord,
+        WriteMemoryRecord,
+    },
+};
+use game_genie::GameGenie;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    ops::Range,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+pub mod game_genie;
+
+/// Peripherals that can be plugged into expansion ports from the config
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PeripheralConfig {
+    /// NES Game Genie with 6 or 8 letter codes
+    GameGenie { codes: Vec<String> },
+}
+
+impl PeripheralConfig {
+    pub fn create(&self) -> Result<Arc<dyn ExpansionDevice>, String> {
+        Ok(match self {
+            PeripheralConfig::GameGenie { codes } => Arc::new(GameGenie::new(codes)?),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ExpansionPortConfig {
+    /// What the user refers to the port as in the config, like "cartridge" or "controller 1"
+    pub name: Cow<'static, str>,
+    pub assigned_ranges: Vec<Range<usize>>,
+    pub assigned_address_space: AddressSpaceId,
+    /// Bus where whatever normally lives behind the port is mapped, at the same addresses
+    pub passthrough_address_space: Option<AddressSpaceId>,
+}
+
+/// A slot peripherals can be attached to while building the machine or while it runs
+#[derive(Debug)]
+pub struct ExpansionPort {
+    config: ExpansionPortConfig,
+    device: RwLock<Option<Arc<dyn ExpansionDevice>>>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+}
+
+impl ExpansionPort {
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    /// Replaces whatever is plugged in, None empties the port
+    pub fn attach(&self, device: Option<Arc<dyn ExpansionDevice>>) {
+        match &device {
+            Some(device) => tracing::info!("Attached {} to port {}", device.name(), self.name()),
+            None => tracing::info!("Emptied port {}", self.name()),
+        }
+
+        *self.device.write().unwrap() = device;
+
+        if let Some(memory_translation_table) = self.memory_translation_table.get() {
+            memory_translation_table.mappings_changed(self.config.assigned_address_space);
+        }
+    }
+
+    pub fn device_name(&self) -> Option<&'static str> {
+        self.device
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|device| device.name())
+    }
+
+    fn passthrough(&self) -> Passthrough {
+        Passthrough {
+            memory_translation_table: self.memory_translation_table.get().map(Arc::as_ref),
+            address_space: self.config.passthrough_address_space,
+        }
+    }
+}
+
+impl Component for ExpansionPort {
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for ExpansionPort {
+    type Config = ExpansionPortConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let device = GLOBAL_CONFIG
+            .read()
+            .unwrap()
+            .peripherals
+            .get(&component_builder.machine().system)
+            .and_then(|ports| ports.get(config.name.as_ref()))
+            .and_then(|peripheral| match peripheral.create() {
+                Ok(device) => Some(device),
+                Err(error) => {
+                    tracing::error!(
+                        "Could not attach {:?} to port {}: {}",
+                        peripheral,
+                        config.name,
+                        error
+                    );
+                    None
+                }
+            });
+
+        let assigned_address_space = config.assigned_address_space;
+        let assigned_ranges = config.assigned_ranges.clone();
+        let name = config.name.to_string();
+
+        component_builder
+            .set_component(Self {
+                config,
+                device: RwLock::new(device),
+                memory_translation_table: OnceLock::new(),
+            })
+            .set_memory(
+                assigned_ranges
+                    .into_iter()
+                    .map(|range| (assigned_address_space, range)),
+            )
+            .set_expansion_port(name);
+    }
+}
+
+impl MemoryComponent for ExpansionPort {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        let passthrough = self.passthrough();
+
+        let success = match self.device.read().unwrap().as_ref() {
+            Some(device) => device.read(address, buffer, &passthrough),
+            None => passthrough.read(address, buffer),
+        };
+
+        if !success {
+            errors.insert(address..address + buffer.len(), ReadMemoryRecord::Denied);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let passthrough = self.passthrough();
+
+        let success = match self.device.read().unwrap().as_ref() {
+            Some(device) => device.write(address, buffer, &passthrough),
+            None => passthrough.write(address, buffer),
+        };
+
+        if !success {
+            errors.insert(address..address + buffer.len(), WriteMemoryRecord::Denied);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        let passthrough = self.passthrough();
+
+        let success = match self.device.read().unwrap().as_ref() {
+            Some(device) => device.preview(address, buffer, &passthrough),
+            None => passthrough.preview(address, buffer),
+        };
+
+        if !success {
+            errors.insert(address..address + buffer.len(), PreviewMemoryRecord::Denied);
+        }
+    }
+}
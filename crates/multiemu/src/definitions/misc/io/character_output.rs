@@ -0,0 +1,157 @@
+use crate::{
+    component::{memory::MemoryComponent, text_output::TextOutputComponent, Component, FromConfig},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use rangemap::RangeMap;
+use std::{
+    fs::{File, OpenOptions},
+    io::{LineWriter, Write},
+    ops::Range,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+#[derive(Debug)]
+pub struct CharacterOutputConfig {
+    /// What the debug menu calls it, like "Printer" or "Serial"
+    pub name: String,
+    /// A memory bus or an I/O port bus, it works the same either way
+    pub assigned_address_space: AddressSpaceId,
+    /// Every address is the data register
+    pub assigned_range: Range<usize>,
+    /// Where to also append everything printed, so test ROMs can be checked without a window
+    pub log_file: Option<PathBuf>,
+}
+
+/// A device that prints every byte written to it as a character, for test ROMs that report over a "serial port" and
+/// printers that only ever get text
+///
+/// Reads come back 0, which programs polling for a busy flag take as ready. What was printed belongs to the host,
+/// so it is left out of snapshots and loading one doesn't unprint anything
+#[derive(Debug)]
+pub struct CharacterOutput {
+    name: String,
+    text: Mutex<String>,
+    log_file: Option<Mutex<LineWriter<File>>>,
+}
+
+impl Component for CharacterOutput {
+    fn volatile(&self) -> bool {
+        true
+    }
+}
+
+impl FromConfig for CharacterOutput {
+    type Config = CharacterOutputConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let log_file = config.log_file.and_then(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .inspect_err(|error| {
+                    tracing::error!("Could not open {} for printing: {}", path.display(), error)
+                })
+                .ok()
+                .map(|file| Mutex::new(LineWriter::new(file)))
+        });
+
+        component_builder
+            .set_component(Self {
+                name: config.name,
+                text: Mutex::default(),
+                log_file,
+            })
+            .set_memory([(config.assigned_address_space, config.assigned_range)])
+            .set_text_output();
+    }
+}
+
+impl MemoryComponent for CharacterOutput {
+    fn read_memory(
+        &self,
+        _address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        buffer.fill(0);
+    }
+
+    fn write_memory(
+        &self,
+        _address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut text = self.text.lock().unwrap();
+
+        for byte in buffer.iter().copied() {
+            // Line endings are left to the host
+            if byte != b'\r' {
+                text.push(byte as char);
+            }
+        }
+
+        if let Some(log_file) = &self.log_file {
+            if let Err(error) = log_file.lock().unwrap().write_all(buffer) {
+                tracing::error!("Could not write to the log of {}: {}", self.name, error);
+            }
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        _address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        buffer.fill(0);
+    }
+}
+
+impl TextOutputComponent for CharacterOutput {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn text(&self) -> String {
+        self.text.lock().unwrap().clone()
+    }
+
+    fn clear(&self) {
+        self.text.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn prints_what_is_written() {
+        let (builder, output) = TestMachineBuilder::new()
+            .bus(0, 8)
+            .component::<CharacterOutput>(CharacterOutputConfig {
+                name: "Serial".to_string(),
+                assigned_address_space: 0,
+                assigned_range: 0x10..0x11,
+                log_file: None,
+            });
+        let machine = builder.build();
+
+        for byte in b"Passed\r\n" {
+            machine.load(0, 0x10, &[*byte]);
+        }
+        assert_eq!(
+            machine.component::<CharacterOutput>(output).text(),
+            "Passed\n"
+        );
+        assert_eq!(machine.peek(0, 0x10, 1), [0]);
+    }
+}
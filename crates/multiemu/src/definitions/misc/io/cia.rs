@@ -0,0 +1,562 @@
+use super::{InterruptConnection, InterruptOutput, PortPins, PortRegisters, PortWiring};
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        memory::MemoryComponent,
+        schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    input::{manager::InputManager, EmulatedGamepadId},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use num::rational::Ratio;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+const PORT_A: usize = 0;
+const PORT_B: usize = 1;
+
+const TIMER_A: usize = 0;
+const TIMER_B: usize = 1;
+
+const TIMER_A_FLAG: u8 = 0b0000_0001;
+const TIMER_B_FLAG: u8 = 0b0000_0010;
+const ALARM_FLAG: u8 = 0b0000_0100;
+const FLAG_PIN_FLAG: u8 = 0b0001_0000;
+
+/// Control register bits shared by both timers
+const CONTROL_START: u8 = 0b0000_0001;
+const CONTROL_ONE_SHOT: u8 = 0b0000_1000;
+/// Strobe that copies the latch into the counter, never stored
+const CONTROL_FORCE_LOAD: u8 = 0b0001_0000;
+/// Timer A counting CNT pulses instead of cycles
+const CONTROL_A_COUNT_CNT: u8 = 0b0010_0000;
+/// Timer B input select, cycles, CNT pulses, timer A underflows or timer A underflows while CNT is high
+const CONTROL_B_INPUT: u8 = 0b0110_0000;
+/// Writes to the time of day registers set the alarm instead of the clock
+const CONTROL_B_ALARM: u8 = 0b1000_0000;
+
+#[derive(Debug)]
+pub struct M6526Config {
+    pub frequency: Ratio<u64>,
+    pub assigned_address_space: AddressSpaceId,
+    /// 16 registers, mirrored across the whole range
+    pub assigned_range: Range<usize>,
+    pub port_a: PortWiring,
+    pub port_b: PortWiring,
+    /// Gamepads the ports read from with [PortWiring::Gamepads]
+    pub gamepads: Vec<(EmulatedGamepadTypeId, EmulatedGamepadMetadata)>,
+    pub irq: Option<Arc<dyn InterruptConnection>>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Timer {
+    counter: u16,
+    latch: u16,
+    control: u8,
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self {
+            counter: 0xffff,
+            latch: 0xffff,
+            control: 0,
+        }
+    }
+}
+
+impl Timer {
+    fn running(&self) -> bool {
+        self.control & CONTROL_START != 0
+    }
+
+    /// Counts one pulse, returning whether that underflowed
+    fn count(&mut self) -> bool {
+        if self.counter != 0 {
+            self.counter -= 1;
+            return false;
+        }
+
+        self.counter = self.latch;
+        if self.control & CONTROL_ONE_SHOT != 0 {
+            self.control &= !CONTROL_START;
+        }
+
+        true
+    }
+}
+
+/// Tenths, seconds, minutes and hours, all in BCD, with the PM flag in the top bit of the hours
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct TimeOfDay([u8; 4]);
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self([0x00, 0x00, 0x00, 0x01])
+    }
+}
+
+impl TimeOfDay {
+    fn tick(&mut self) {
+        let [tenths, seconds, minutes, hours] = &mut self.0;
+
+        *tenths = (*tenths + 1) % 10;
+        if *tenths != 0 {
+            return;
+        }
+
+        *seconds = bcd_increment(*seconds, 0x60);
+        if *seconds != 0 {
+            return;
+        }
+
+        *minutes = bcd_increment(*minutes, 0x60);
+        if *minutes != 0 {
+            return;
+        }
+
+        // 12 hour clock, flipping AM and PM on the way from 11 to 12
+        let pm = *hours & 0x80;
+        *hours = match *hours & 0x1f {
+            0x11 => 0x12 | (pm ^ 0x80),
+            0x12 => 0x01 | pm,
+            hour => bcd_increment(hour, 0x13) | pm,
+        };
+    }
+}
+
+/// Adds one to a BCD number, wrapping to zero at `limit`
+fn bcd_increment(value: u8, limit: u8) -> u8 {
+    let value = if value & 0x0f >= 9 {
+        (value & 0xf0) + 0x10
+    } else {
+        value + 1
+    };
+
+    if value >= limit {
+        0
+    } else {
+        value
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct M6526State {
+    ports: [PortRegisters; 2],
+    timers: [Timer; 2],
+    time_of_day: TimeOfDay,
+    alarm: TimeOfDay,
+    /// Copy of the clock frozen by reading the hours, until the tenths are read
+    time_of_day_latch: Option<TimeOfDay>,
+    /// Writing the hours stops the clock, writing the tenths starts it again
+    time_of_day_stopped: bool,
+    /// Cycles since the clock last ticked
+    time_of_day_cycles: u64,
+    serial: u8,
+    interrupt_flags: u8,
+    interrupt_mask: u8,
+    irq: bool,
+}
+
+/// MOS 6526 complex interface adapter, two 8 bit ports, two 16 bit timers and a time of day clock
+///
+/// The time of day clock counts tenths off the chip clock rather than the power line, nothing is wired to CNT and the
+/// serial register only holds its value. Timers don't show up on port B
+#[derive(Debug)]
+pub struct M6526 {
+    config: M6526Config,
+    /// Chip cycles per tenth of a second
+    time_of_day_period: u64,
+    state: Mutex<M6526State>,
+    pins: PortPins<2>,
+    irq: InterruptOutput,
+}
+
+impl M6526 {
+    /// Whether the IRQ output is pulled
+    pub fn irq(&self) -> bool {
+        self.state.lock().unwrap().irq
+    }
+
+    /// A falling edge on the FLAG input, which the C64 has wired to the tape read line and the serial bus
+    pub fn pulse_flag(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.interrupt_flags |= FLAG_PIN_FLAG;
+        self.update_irq(&mut state);
+    }
+
+    fn update_irq(&self, state: &mut M6526State) {
+        let raised = state.interrupt_flags & state.interrupt_mask != 0;
+        self.irq.update(&mut state.irq, raised);
+    }
+
+    fn check_alarm(&self, state: &mut M6526State) {
+        if state.time_of_day == state.alarm {
+            state.interrupt_flags |= ALARM_FLAG;
+        }
+    }
+
+    fn register(&self, address: usize) -> usize {
+        (address - self.config.assigned_range.start) & 0xf
+    }
+
+    /// Reads a register, `side_effects` being false for the debugger looking at it
+    fn read_register(&self, state: &mut M6526State, register: usize, side_effects: bool) -> u8 {
+        match register {
+            0x0 => self.pins.read(PORT_A, state.ports[PORT_A]),
+            0x1 => self.pins.read(PORT_B, state.ports[PORT_B]),
+            0x2 => state.ports[PORT_A].direction,
+            0x3 => state.ports[PORT_B].direction,
+            0x4 => state.timers[TIMER_A].counter as u8,
+            0x5 => (state.timers[TIMER_A].counter >> 8) as u8,
+            0x6 => state.timers[TIMER_B].counter as u8,
+            0x7 => (state.timers[TIMER_B].counter >> 8) as u8,
+            0x8..=0xb => {
+                let index = register - 0x8;
+                let time_of_day = state.time_of_day_latch.unwrap_or(state.time_of_day);
+
+                if side_effects {
+                    // Reading the hours freezes what the rest read back so the time can't roll over in between
+                    match index {
+                        0 => state.time_of_day_latch = None,
+                        3 => state.time_of_day_latch = Some(time_of_day),
+                        _ => {}
+                    }
+                }
+
+                time_of_day.0[index]
+            }
+            0xc => state.serial,
+            0xd => {
+                let pending = state.interrupt_flags & state.interrupt_mask != 0;
+                let value = state.interrupt_flags | if pending { 0x80 } else { 0x00 };
+
+                if side_effects {
+                    state.interrupt_flags = 0;
+                    self.update_irq(state);
+                }
+
+                value
+            }
+            0xe => state.timers[TIMER_A].control,
+            0xf => state.timers[TIMER_B].control,
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_register(&self, state: &mut M6526State, register: usize, value: u8) {
+        match register {
+            0x0 => {
+                state.ports[PORT_A].output = value;
+                self.pins.write(PORT_A, state.ports[PORT_A]);
+            }
+            0x1 => {
+                state.ports[PORT_B].output = value;
+                self.pins.write(PORT_B, state.ports[PORT_B]);
+            }
+            0x2 => {
+                state.ports[PORT_A].direction = value;
+                self.pins.write(PORT_A, state.ports[PORT_A]);
+            }
+            0x3 => {
+                state.ports[PORT_B].direction = value;
+                self.pins.write(PORT_B, state.ports[PORT_B]);
+            }
+            0x4 | 0x6 => {
+                let timer = &mut state.timers[(register - 0x4) / 2];
+                timer.latch = (timer.latch & 0xff00) | value as u16;
+            }
+            0x5 | 0x7 => {
+                let timer = &mut state.timers[(register - 0x5) / 2];
+                timer.latch = (timer.latch & 0x00ff) | ((value as u16) << 8);
+
+                // A stopped timer picks up the new value straight away
+                if !timer.running() {
+                    timer.counter = timer.latch;
+                }
+            }
+            0x8..=0xb => {
+                let index = register - 0x8;
+                // Bits the counters don't have read back as zero
+                let value = value & [0x0f, 0x7f, 0x7f, 0x9f][index];
+
+                if state.timers[TIMER_B].control & CONTROL_B_ALARM != 0 {
+                    state.alarm.0[index] = value;
+                } else {
+                    state.time_of_day.0[index] = value;
+                    match index {
+                        0 => {
+                            state.time_of_day_stopped = false;
+                            state.time_of_day_cycles = 0;
+                        }
+                        3 => state.time_of_day_stopped = true,
+                        _ => {}
+                    }
+                }
+
+                self.check_alarm(state);
+            }
+            0xc => state.serial = value,
+            0xd => {
+                if value & 0x80 != 0 {
+                    state.interrupt_mask |= value & 0x1f;
+                } else {
+                    state.interrupt_mask &= !value;
+                }
+            }
+            0xe | 0xf => {
+                let timer = &mut state.timers[register - 0xe];
+
+                if value & CONTROL_FORCE_LOAD != 0 {
+                    timer.counter = timer.latch;
+                }
+                timer.control = value & !CONTROL_FORCE_LOAD;
+            }
+            _ => unreachable!(),
+        }
+
+        self.update_irq(state);
+    }
+}
+
+impl Component for M6526 {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let irq = state.irq;
+
+        *state = M6526State {
+            irq,
+            ..Default::default()
+        };
+        self.update_irq(&mut state);
+
+        for port in [PORT_A, PORT_B] {
+            self.pins.write(port, state.ports[port]);
+        }
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let mut state_guard = self.state.lock().unwrap();
+        let irq = state_guard.irq;
+
+        *state_guard = rmpv::ext::from_value(state).unwrap();
+        // Tell the connection about the loaded level
+        let raised = state_guard.irq;
+        state_guard.irq = irq;
+        self.irq.update(&mut state_guard.irq, raised);
+    }
+}
+
+impl FromConfig for M6526 {
+    type Config = M6526Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let frequency = config.frequency;
+        let time_of_day_period = (frequency / 10).round().to_integer().max(1);
+        let assigned_range = config.assigned_range.clone();
+        let assigned_address_space = config.assigned_address_space;
+        let pins = PortPins::new(
+            [
+                std::mem::take(&mut config.port_a),
+                std::mem::take(&mut config.port_b),
+            ],
+            std::mem::take(&mut config.gamepads),
+        );
+        let irq = InterruptOutput::new(config.irq.take());
+        let gamepads = pins.gamepads().to_vec();
+
+        component_builder
+            .set_component(Self {
+                config,
+                time_of_day_period,
+                state: Mutex::default(),
+                pins,
+                irq,
+            })
+            .set_schedulable(frequency, [], [])
+            .set_memory([(assigned_address_space, assigned_range)]);
+
+        if !gamepads.is_empty() {
+            let gamepad_types: Vec<_> = gamepads.iter().map(|(id, _)| id.clone()).collect();
+            component_builder.set_input(gamepads, gamepad_types);
+        }
+    }
+}
+
+impl SchedulableComponent for M6526 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..period {
+            let timer_a = &mut state.timers[TIMER_A];
+            let timer_a_underflow =
+                timer_a.running() && timer_a.control & CONTROL_A_COUNT_CNT == 0 && timer_a.count();
+
+            // Nothing pulses CNT, which floats high, so timer B either counts cycles or timer A underflows
+            let timer_b = &mut state.timers[TIMER_B];
+            let timer_b_pulse = match (timer_b.control & CONTROL_B_INPUT) >> 5 {
+                0b00 => true,
+                0b01 => false,
+                _ => timer_a_underflow,
+            };
+            let timer_b_underflow = timer_b.running() && timer_b_pulse && timer_b.count();
+
+            if timer_a_underflow {
+                state.interrupt_flags |= TIMER_A_FLAG;
+            }
+            if timer_b_underflow {
+                state.interrupt_flags |= TIMER_B_FLAG;
+            }
+
+            if !state.time_of_day_stopped {
+                state.time_of_day_cycles += 1;
+
+                if state.time_of_day_cycles >= self.time_of_day_period {
+                    state.time_of_day_cycles = 0;
+                    state.time_of_day.tick();
+                    self.check_alarm(&mut state);
+                }
+            }
+        }
+
+        self.update_irq(&mut state);
+    }
+}
+
+impl InputComponent for M6526 {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.pins.set_input_manager(input_manager, gamepad_ids);
+    }
+}
+
+impl MemoryComponent for M6526 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_register(&mut state, self.register(address + offset), true);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter().enumerate() {
+            self.write_register(&mut state, self.register(address + offset), *byte);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_register(&mut state, self.register(address + offset), false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::{InterruptEvent, TestMachineBuilder};
+
+    #[test]
+    fn timers_and_time_of_day() {
+        let builder = TestMachineBuilder::new().bus(0, 16);
+        let irq = builder.interrupt_line("irq");
+        let (builder, cia) = builder.component::<M6526>(M6526Config {
+            frequency: Ratio::from_integer(1_000),
+            assigned_address_space: 0,
+            assigned_range: 0xdc00..0xdd00,
+            port_a: PortWiring::Unconnected,
+            port_b: PortWiring::Unconnected,
+            gamepads: Vec::new(),
+            irq: Some(Arc::new(irq)),
+        });
+        let machine = builder.build();
+
+        // Timer A continuous at 10 cycles, timer B counting its underflows down from 2
+        machine.load(0, 0xdc0d, &[0x80 | TIMER_B_FLAG]);
+        machine.load(0, 0xdc04, &[9, 0]);
+        machine.load(0, 0xdc06, &[2, 0]);
+        machine.load(0, 0xdc0f, &[0b0100_0000 | CONTROL_START]);
+        machine.load(0, 0xdc0e, &[CONTROL_START]);
+
+        // Timer A underflows on its own without interrupting, timer B on the third one does
+        machine.run_component::<M6526>(cia, 20);
+        assert!(machine.take_interrupts().is_empty());
+        machine.run_component::<M6526>(cia, 10);
+        assert_eq!(
+            machine.peek(0, 0xdc0d, 1),
+            [0x80 | TIMER_A_FLAG | TIMER_B_FLAG]
+        );
+
+        // Reading the flags clears them
+        let mut buffer = [0];
+        machine
+            .machine
+            .memory_translation_table
+            .read(0xdc0d, &mut buffer, 0)
+            .unwrap();
+        assert_eq!(
+            machine.take_interrupts(),
+            [
+                InterruptEvent {
+                    line: "irq",
+                    raised: true,
+                    source: None
+                },
+                InterruptEvent {
+                    line: "irq",
+                    raised: false,
+                    source: None
+                }
+            ]
+        );
+
+        // 11:59:59.9 PM rolls over to 12 AM
+        for (address, value) in [
+            (0xdc0b, 0x91),
+            (0xdc0a, 0x59),
+            (0xdc09, 0x59),
+            (0xdc08, 0x09),
+        ] {
+            machine.load(0, address, &[value]);
+        }
+        machine.run_component::<M6526>(cia, 100);
+        assert_eq!(machine.peek(0, 0xdc08, 4), [0x00, 0x00, 0x00, 0x12]);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{
+    component::input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId},
+    input::{manager::InputManager, EmulatedGamepadId, Input},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    sync::{Arc, OnceLock},
+};
+
+pub mod character_output;
+pub mod cia;
+pub mod ppi;
+pub mod riot;
+pub mod via;
+
+/// Whatever sits on the other end of an 8 bit I/O port, like a keyboard matrix or another chip
+pub trait PortConnection: Debug + Send + Sync {
+    /// Level of the pins, only the ones the chip has set as inputs are looked at
+    fn read_pins(&self) -> u8 {
+        0xff
+    }
+
+    /// Called whenever the chip changes what it drives, `direction` has the pins it drives set
+    fn write_pins(&self, _value: u8, _direction: u8) {}
+}
+
+/// Whatever an interrupt output is wired to, usually the IRQ input of a processor
+pub trait InterruptConnection: Debug + Send + Sync {
+    fn set_interrupt(&self, raised: bool);
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum PortWiring {
+    /// Nothing plugged in, every pin floats high
+    #[default]
+    Unconnected,
+    /// Each pin reads one input of one of the chip's gamepads, low while held, like joysticks wired straight to the
+    /// port
+    Gamepads([Option<(usize, Input)>; 8]),
+    Connection(Arc<dyn PortConnection>),
+}
+
+/// The output and data direction registers of one port
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct PortRegisters {
+    pub output: u8,
+    /// Set bits are outputs
+    pub direction: u8,
+}
+
+/// The ports of a chip and what they are wired to
+#[derive(Debug)]
+pub struct PortPins<const N: usize> {
+    wiring: [PortWiring; N],
+    gamepads: Vec<(EmulatedGamepadTypeId, EmulatedGamepadMetadata)>,
+    input_manager: OnceLock<(Arc<InputManager>, Vec<EmulatedGamepadId>)>,
+}
+
+impl<const N: usize> PortPins<N> {
+    /// `gamepads` are the ones [PortWiring::Gamepads] refers to by index
+    pub fn new(
+        wiring: [PortWiring; N],
+        gamepads: Vec<(EmulatedGamepadTypeId, EmulatedGamepadMetadata)>,
+    ) -> Self {
+        Self {
+            wiring,
+            gamepads,
+            input_manager: OnceLock::new(),
+        }
+    }
+
+    pub fn gamepads(&self) -> &[(EmulatedGamepadTypeId, EmulatedGamepadMetadata)] {
+        &self.gamepads
+    }
+
+    pub fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.input_manager
+            .set((input_manager, gamepad_ids.to_vec()))
+            .expect("Input manager set multiple times");
+    }
+
+    /// What a read of the port returns, outputs read back what the chip drives
+    pub fn read(&self, port: usize, registers: PortRegisters) -> u8 {
+        (registers.output & registers.direction) | (self.pins(port) & !registers.direction)
+    }
+
+    /// Level of the pins from the outside
+    pub fn pins(&self, port: usize) -> u8 {
+        match &self.wiring[port] {
+            PortWiring::Unconnected => 0xff,
+            PortWiring::Gamepads(pins) => {
+                let Some((input_manager, gamepad_ids)) = self.input_manager.get() else {
+                    return 0xff;
+                };
+
+                pins.iter()
+                    .enumerate()
+                    .filter(|(_, pin)| {
+                        pin.is_some_and(|(gamepad, input)| {
+                            gamepad_ids.get(gamepad).is_some_and(|gamepad_id| {
+                                input_manager.get_input(*gamepad_id, input).as_digital()
+                            })
+                        })
+                    })
+                    .fold(0xff, |pins, (bit, _)| pins & !(1 << bit))
+            }
+            PortWiring::Connection(connection) => connection.read_pins(),
+        }
+    }
+
+    pub fn write(&self, port: usize, registers: PortRegisters) {
+        if let PortWiring::Connection(connection) = &self.wiring[port] {
+            connection.write_pins(registers.output, registers.direction);
+        }
+    }
+}
+
+/// Latest level of an interrupt output, telling the connection only about edges
+#[derive(Debug, Default)]
+pub struct InterruptOutput {
+    connection: Option<Arc<dyn InterruptConnection>>,
+}
+
+impl InterruptOutput {
+    pub fn new(connection: Option<Arc<dyn InterruptConnection>>) -> Self {
+        Self { connection }
+    }
+
+    /// Call with the new level and the one stored in the chip state, which is updated
+    pub fn update(&self, level: &mut bool, raised: bool) {
+        if *level != raised {
+            *level = raised;
+
+            if let Some(connection) = &self.connection {
+                connection.set_interrupt(raised);
+            }
+        }
+    }
+}
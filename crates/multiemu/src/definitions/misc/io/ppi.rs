@@ -0,0 +1,549 @@
+use super::{InterruptConnection, InterruptOutput, PortPins, PortRegisters, PortWiring};
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        memory::MemoryComponent,
+        Component, FromConfig,
+    },
+    input::{manager::InputManager, EmulatedGamepadId},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+const PORT_A: usize = 0;
+const PORT_B: usize = 1;
+const PORT_C: usize = 2;
+
+/// What the chip powers on with, every port an input in mode 0
+const DEFAULT_CONTROL: u8 = 0b1001_1011;
+
+/// Port C latches that double as the interrupt enables in the handshake modes
+const INTE_A_INPUT: u8 = 1 << 4;
+const INTE_A_OUTPUT: u8 = 1 << 6;
+const INTE_B: u8 = 1 << 2;
+
+/// The two ports that can handshake, group A being port A with the top of port C, and group B port B with the
+/// bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeGroup {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PortMode {
+    Basic { input: bool },
+    Strobed { input: bool },
+    Bidirectional,
+}
+
+#[derive(Debug)]
+pub struct I8255Config {
+    pub assigned_address_space: AddressSpaceId,
+    /// 4 registers, mirrored across the whole range
+    pub assigned_range: Range<usize>,
+    pub port_a: PortWiring,
+    pub port_b: PortWiring,
+    pub port_c: PortWiring,
+    /// Gamepads the ports read from with [PortWiring::Gamepads]
+    pub gamepads: Vec<(EmulatedGamepadTypeId, EmulatedGamepadMetadata)>,
+    /// INTRA, on PC3
+    pub interrupt_a: Option<Arc<dyn InterruptConnection>>,
+    /// INTRB, on PC0
+    pub interrupt_b: Option<Arc<dyn InterruptConnection>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct I8255State {
+    control: u8,
+    ports: [PortRegisters; 3],
+    /// What the peripheral strobed in, for groups A and B
+    input_latches: [u8; 2],
+    input_buffer_full: [bool; 2],
+    output_buffer_full: [bool; 2],
+    interrupts: [bool; 2],
+}
+
+impl Default for I8255State {
+    fn default() -> Self {
+        let mut state = Self {
+            control: 0,
+            ports: Default::default(),
+            input_latches: [0; 2],
+            input_buffer_full: [false; 2],
+            output_buffer_full: [false; 2],
+            interrupts: [false; 2],
+        };
+        state.set_mode(DEFAULT_CONTROL);
+
+        state
+    }
+}
+
+impl I8255State {
+    fn mode(&self, group: HandshakeGroup) -> PortMode {
+        match group {
+            HandshakeGroup::A => {
+                let input = self.control & 0b0001_0000 != 0;
+
+                match (self.control >> 5) & 0b11 {
+                    0b00 => PortMode::Basic { input },
+                    0b01 => PortMode::Strobed { input },
+                    _ => PortMode::Bidirectional,
+                }
+            }
+            HandshakeGroup::B => {
+                let input = self.control & 0b0000_0010 != 0;
+
+                if self.control & 0b0000_0100 == 0 {
+                    PortMode::Basic { input }
+                } else {
+                    PortMode::Strobed { input }
+                }
+            }
+        }
+    }
+
+    /// A mode set clears every output and flag, and makes the pins follow the new directions
+    fn set_mode(&mut self, control: u8) {
+        self.control = control;
+        self.ports = Default::default();
+        self.input_buffer_full = [false; 2];
+        self.output_buffer_full = [false; 2];
+
+        for (port, group) in [(PORT_A, HandshakeGroup::A), (PORT_B, HandshakeGroup::B)] {
+            self.ports[port].direction = match self.mode(group) {
+                PortMode::Basic { input } | PortMode::Strobed { input } if input => 0x00,
+                PortMode::Bidirectional => 0x00,
+                _ => 0xff,
+            };
+        }
+
+        let upper = if control & 0b0000_1000 != 0 {
+            0x00
+        } else {
+            0xf0
+        };
+        let lower = if control & 0b0000_0001 != 0 {
+            0x00
+        } else {
+            0x0f
+        };
+        // Handshake lines aren't driven through the latch
+        self.ports[PORT_C].direction = (upper | lower) & !self.handshake_mask();
+    }
+
+    /// Port C bits taken over by the handshake modes
+    fn handshake_mask(&self) -> u8 {
+        let group_a = match self.mode(HandshakeGroup::A) {
+            PortMode::Basic { .. } => 0x00,
+            PortMode::Strobed { input: true } => 0b0011_1000,
+            PortMode::Strobed { input: false } => 0b1100_1000,
+            PortMode::Bidirectional => 0b1111_1000,
+        };
+        let group_b = match self.mode(HandshakeGroup::B) {
+            PortMode::Basic { .. } => 0x00,
+            _ => 0b0000_0111,
+        };
+
+        group_a | group_b
+    }
+
+    /// Levels of INTRA and INTRB
+    fn interrupt_levels(&self) -> [bool; 2] {
+        let inte = self.ports[PORT_C].output;
+        let input_ready =
+            |index: usize, enable: u8| self.input_buffer_full[index] && inte & enable != 0;
+        let output_ready =
+            |index: usize, enable: u8| !self.output_buffer_full[index] && inte & enable != 0;
+
+        let a = match self.mode(HandshakeGroup::A) {
+            PortMode::Basic { .. } => false,
+            PortMode::Strobed { input: true } => input_ready(0, INTE_A_INPUT),
+            PortMode::Strobed { input: false } => output_ready(0, INTE_A_OUTPUT),
+            PortMode::Bidirectional => {
+                input_ready(0, INTE_A_INPUT) || output_ready(0, INTE_A_OUTPUT)
+            }
+        };
+        let b = match self.mode(HandshakeGroup::B) {
+            PortMode::Basic { .. } => false,
+            PortMode::Strobed { input: true } => input_ready(1, INTE_B),
+            PortMode::Strobed { input: false } => output_ready(1, INTE_B),
+            PortMode::Bidirectional => unreachable!(),
+        };
+
+        [a, b]
+    }
+
+    /// What the handshake modes put on port C, reading back the interrupt enables in place of the strobe and
+    /// acknowledge inputs like the status word of the real chip
+    fn handshake_status(&self) -> u8 {
+        let [interrupt_a, interrupt_b] = self.interrupt_levels();
+        let inte = self.ports[PORT_C].output;
+        let mut status = 0;
+
+        match self.mode(HandshakeGroup::A) {
+            PortMode::Basic { .. } => {}
+            mode => {
+                status |= (interrupt_a as u8) << 3;
+
+                if matches!(
+                    mode,
+                    PortMode::Strobed { input: true } | PortMode::Bidirectional
+                ) {
+                    status |= inte & INTE_A_INPUT;
+                    status |= (self.input_buffer_full[0] as u8) << 5;
+                }
+                if matches!(
+                    mode,
+                    PortMode::Strobed { input: false } | PortMode::Bidirectional
+                ) {
+                    status |= inte & INTE_A_OUTPUT;
+                    // OBF is active low
+                    status |= (!self.output_buffer_full[0] as u8) << 7;
+                }
+            }
+        }
+
+        if let PortMode::Strobed { input } = self.mode(HandshakeGroup::B) {
+            status |= interrupt_b as u8;
+            status |= inte & INTE_B;
+            status |= if input {
+                (self.input_buffer_full[1] as u8) << 1
+            } else {
+                (!self.output_buffer_full[1] as u8) << 1
+            };
+        }
+
+        status
+    }
+}
+
+/// Intel 8255 programmable peripheral interface, three 8 bit ports with optional strobed handshaking
+#[derive(Debug)]
+pub struct I8255 {
+    config: I8255Config,
+    state: Mutex<I8255State>,
+    pins: PortPins<3>,
+    interrupts: [InterruptOutput; 2],
+}
+
+impl I8255 {
+    /// The peripheral latching a byte into a port in strobed input mode, pulsing STB
+    pub fn strobe(&self, group: HandshakeGroup, value: u8) {
+        let mut state = self.state.lock().unwrap();
+        let index = group as usize;
+
+        if matches!(
+            state.mode(group),
+            PortMode::Strobed { input: true } | PortMode::Bidirectional
+        ) {
+            state.input_latches[index] = value;
+            state.input_buffer_full[index] = true;
+        }
+        self.update_interrupts(&mut state);
+    }
+
+    /// The peripheral taking the byte written to a port in strobed output mode, pulsing ACK
+    pub fn acknowledge(&self, group: HandshakeGroup) {
+        let mut state = self.state.lock().unwrap();
+
+        if matches!(
+            state.mode(group),
+            PortMode::Strobed { input: false } | PortMode::Bidirectional
+        ) {
+            state.output_buffer_full[group as usize] = false;
+        }
+        self.update_interrupts(&mut state);
+    }
+
+    fn update_interrupts(&self, state: &mut I8255State) {
+        let levels = state.interrupt_levels();
+
+        for (index, level) in levels.into_iter().enumerate() {
+            self.interrupts[index].update(&mut state.interrupts[index], level);
+        }
+    }
+
+    fn write_all_ports(&self, state: &I8255State) {
+        for port in [PORT_A, PORT_B, PORT_C] {
+            self.pins.write(port, state.ports[port]);
+        }
+    }
+
+    fn read_data(
+        &self,
+        state: &mut I8255State,
+        port: usize,
+        group: HandshakeGroup,
+        side_effects: bool,
+    ) -> u8 {
+        match state.mode(group) {
+            PortMode::Basic { .. } => self.pins.read(port, state.ports[port]),
+            PortMode::Strobed { input: false } => state.ports[port].output,
+            PortMode::Strobed { input: true } | PortMode::Bidirectional => {
+                if side_effects {
+                    state.input_buffer_full[group as usize] = false;
+                    self.update_interrupts(state);
+                }
+
+                state.input_latches[group as usize]
+            }
+        }
+    }
+
+    fn read_register(&self, state: &mut I8255State, register: usize, side_effects: bool) -> u8 {
+        match register {
+            0b00 => self.read_data(state, PORT_A, HandshakeGroup::A, side_effects),
+            0b01 => self.read_data(state, PORT_B, HandshakeGroup::B, side_effects),
+            0b10 => {
+                let mask = state.handshake_mask();
+                (self.pins.read(PORT_C, state.ports[PORT_C]) & !mask)
+                    | (state.handshake_status() & mask)
+            }
+            // The control register can't be read back
+            0b11 => 0xff,
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_data(&self, state: &mut I8255State, port: usize, group: HandshakeGroup, value: u8) {
+        state.ports[port].output = value;
+
+        if matches!(
+            state.mode(group),
+            PortMode::Strobed { input: false } | PortMode::Bidirectional
+        ) {
+            state.output_buffer_full[group as usize] = true;
+        }
+        self.pins.write(port, state.ports[port]);
+    }
+
+    fn write_register(&self, state: &mut I8255State, register: usize, value: u8) {
+        match register {
+            0b00 => self.write_data(state, PORT_A, HandshakeGroup::A, value),
+            0b01 => self.write_data(state, PORT_B, HandshakeGroup::B, value),
+            0b10 => {
+                state.ports[PORT_C].output = value;
+                self.pins.write(PORT_C, state.ports[PORT_C]);
+            }
+            0b11 => {
+                if value & 0x80 != 0 {
+                    state.set_mode(value);
+                    self.write_all_ports(state);
+                } else {
+                    // Bit set/reset, which is also how the interrupt enables get toggled
+                    let bit = 1 << ((value >> 1) & 0b111);
+
+                    if value & 0b1 != 0 {
+                        state.ports[PORT_C].output |= bit;
+                    } else {
+                        state.ports[PORT_C].output &= !bit;
+                    }
+                    self.pins.write(PORT_C, state.ports[PORT_C]);
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        self.update_interrupts(state);
+    }
+
+    fn register(&self, address: usize) -> usize {
+        (address - self.config.assigned_range.start) & 0b11
+    }
+}
+
+impl Component for I8255 {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let interrupts = state.interrupts;
+
+        *state = I8255State {
+            interrupts,
+            ..Default::default()
+        };
+        self.update_interrupts(&mut state);
+        self.write_all_ports(&state);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let mut state_guard = self.state.lock().unwrap();
+        let interrupts = state_guard.interrupts;
+
+        *state_guard = rmpv::ext::from_value(state).unwrap();
+        // Tell the connections about the loaded levels
+        let levels = state_guard.interrupts;
+        state_guard.interrupts = interrupts;
+        for (index, level) in levels.into_iter().enumerate() {
+            self.interrupts[index].update(&mut state_guard.interrupts[index], level);
+        }
+    }
+}
+
+impl FromConfig for I8255 {
+    type Config = I8255Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let assigned_range = config.assigned_range.clone();
+        let assigned_address_space = config.assigned_address_space;
+        let pins = PortPins::new(
+            [
+                std::mem::take(&mut config.port_a),
+                std::mem::take(&mut config.port_b),
+                std::mem::take(&mut config.port_c),
+            ],
+            std::mem::take(&mut config.gamepads),
+        );
+        let interrupts = [
+            InterruptOutput::new(config.interrupt_a.take()),
+            InterruptOutput::new(config.interrupt_b.take()),
+        ];
+        let gamepads = pins.gamepads().to_vec();
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                pins,
+                interrupts,
+            })
+            .set_memory([(assigned_address_space, assigned_range)]);
+
+        if !gamepads.is_empty() {
+            let gamepad_types: Vec<_> = gamepads.iter().map(|(id, _)| id.clone()).collect();
+            component_builder.set_input(gamepads, gamepad_types);
+        }
+    }
+}
+
+impl InputComponent for I8255 {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.pins.set_input_manager(input_manager, gamepad_ids);
+    }
+}
+
+impl MemoryComponent for I8255 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_register(&mut state, self.register(address + offset), true);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter().enumerate() {
+            self.write_register(&mut state, self.register(address + offset), *byte);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_register(&mut state, self.register(address + offset), false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{definitions::misc::io::PortConnection, machine::test_machine::TestMachineBuilder};
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    #[derive(Debug, Default)]
+    struct Latch(AtomicU8);
+
+    impl PortConnection for Latch {
+        fn read_pins(&self) -> u8 {
+            0x5a
+        }
+
+        fn write_pins(&self, value: u8, direction: u8) {
+            self.0.store(value & direction, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn modes() {
+        let builder = TestMachineBuilder::new().bus(0, 16);
+        let interrupt_a = builder.interrupt_line("intra");
+        let port_b = Arc::new(Latch::default());
+        let (builder, ppi) = builder.component::<I8255>(I8255Config {
+            assigned_address_space: 0,
+            assigned_range: 0x10..0x14,
+            port_a: PortWiring::Connection(Arc::new(Latch::default())),
+            port_b: PortWiring::Connection(port_b.clone()),
+            port_c: PortWiring::Unconnected,
+            gamepads: Vec::new(),
+            interrupt_a: Some(Arc::new(interrupt_a.clone())),
+            interrupt_b: None,
+        });
+        let machine = builder.build();
+
+        // Mode 0, A in, B out, C all out
+        machine.load(0, 0x13, &[0b1001_0000]);
+        assert_eq!(machine.peek(0, 0x10, 1), [0x5a]);
+        machine.load(0, 0x11, &[0x33]);
+        assert_eq!(port_b.0.load(Ordering::Relaxed), 0x33);
+        // Set PC5
+        machine.load(0, 0x13, &[0b0000_1011]);
+        assert_eq!(machine.peek(0, 0x12, 1), [0b0010_0000]);
+
+        // Mode 1 strobed input on A, with its interrupt enabled
+        machine.load(0, 0x13, &[0b1011_0000]);
+        machine.load(0, 0x13, &[0b0000_1001]);
+        assert!(!interrupt_a.is_raised());
+
+        let ppi = machine.component::<I8255>(ppi);
+        ppi.strobe(HandshakeGroup::A, 0x42);
+        assert!(interrupt_a.is_raised());
+        // INTRA, INTE A and IBFA
+        assert_eq!(machine.peek(0, 0x12, 1)[0] & 0b0011_1000, 0b0011_1000);
+
+        let mut buffer = [0];
+        machine
+            .machine
+            .memory_translation_table
+            .read(0x10, &mut buffer, 0)
+            .unwrap();
+        assert_eq!(buffer, [0x42]);
+        assert!(!interrupt_a.is_raised());
+    }
+}
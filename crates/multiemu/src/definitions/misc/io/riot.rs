@@ -0,0 +1,405 @@
+use super::{InterruptConnection, InterruptOutput, PortPins, PortRegisters, PortWiring};
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        memory::MemoryComponent,
+        schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    input::{manager::InputManager, EmulatedGamepadId},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use num::rational::Ratio;
+use rand::RngCore;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+const PORT_A: usize = 0;
+const PORT_B: usize = 1;
+
+const RAM_SIZE: usize = 128;
+
+const TIMER_FLAG: u8 = 0b1000_0000;
+const PA7_FLAG: u8 = 0b0100_0000;
+
+/// Cycles per timer decrement for each of the four timer write addresses
+const PRESCALERS: [u16; 4] = [1, 8, 64, 1024];
+
+#[derive(Debug)]
+pub struct M6532Config {
+    pub frequency: Ratio<u64>,
+    pub assigned_address_space: AddressSpaceId,
+    /// 128 bytes of RAM, mirrored across the whole range
+    pub ram_range: Range<usize>,
+    /// 32 register addresses, mirrored across the whole range
+    pub register_range: Range<usize>,
+    pub port_a: PortWiring,
+    pub port_b: PortWiring,
+    /// Gamepads the ports read from with [PortWiring::Gamepads]
+    pub gamepads: Vec<(EmulatedGamepadTypeId, EmulatedGamepadMetadata)>,
+    pub irq: Option<Arc<dyn InterruptConnection>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct M6532State {
+    ram: Vec<u8>,
+    ports: [PortRegisters; 2],
+    timer: u8,
+    prescaler: u16,
+    /// Cycles since the timer last decremented
+    prescaler_counter: u16,
+    timer_interrupt_enable: bool,
+    /// After running out the timer counts down every cycle until written again
+    timer_expired: bool,
+    pa7_interrupt_enable: bool,
+    pa7_positive_edge: bool,
+    pa7: bool,
+    interrupt_flags: u8,
+    irq: bool,
+}
+
+impl Default for M6532State {
+    fn default() -> Self {
+        let mut ram = vec![0; RAM_SIZE];
+        // Undefined at power on, and games that forget to clear it should notice like they would on hardware
+        rand::rng().fill_bytes(&mut ram);
+
+        Self {
+            ram,
+            ports: Default::default(),
+            timer: 0,
+            prescaler: PRESCALERS[3],
+            prescaler_counter: 0,
+            timer_interrupt_enable: false,
+            timer_expired: false,
+            pa7_interrupt_enable: false,
+            pa7_positive_edge: false,
+            pa7: true,
+            interrupt_flags: 0,
+            irq: false,
+        }
+    }
+}
+
+/// MOS 6532 RAM-I/O-timer, 128 bytes of RAM, two 8 bit ports and an interval timer, best known as the RIOT in the
+/// Atari 2600
+#[derive(Debug)]
+pub struct M6532 {
+    config: M6532Config,
+    state: Mutex<M6532State>,
+    pins: PortPins<2>,
+    irq: InterruptOutput,
+}
+
+impl M6532 {
+    /// Whether the IRQ output is pulled
+    pub fn irq(&self) -> bool {
+        self.state.lock().unwrap().irq
+    }
+
+    fn update_irq(&self, state: &mut M6532State) {
+        let raised = (state.interrupt_flags & TIMER_FLAG != 0 && state.timer_interrupt_enable)
+            || (state.interrupt_flags & PA7_FLAG != 0 && state.pa7_interrupt_enable);
+        self.irq.update(&mut state.irq, raised);
+    }
+
+    /// Flags an edge on PA7 in whichever direction the chip was told to watch for
+    fn sample_pa7(&self, state: &mut M6532State) {
+        let pa7 = self.pins.read(PORT_A, state.ports[PORT_A]) & 0x80 != 0;
+
+        if state.pa7 != pa7 && pa7 == state.pa7_positive_edge {
+            state.interrupt_flags |= PA7_FLAG;
+        }
+        state.pa7 = pa7;
+    }
+
+    fn read_register(&self, state: &mut M6532State, register: usize, side_effects: bool) -> u8 {
+        if register & 0b100 == 0 {
+            return match register & 0b11 {
+                0b00 => self.pins.read(PORT_A, state.ports[PORT_A]),
+                0b01 => state.ports[PORT_A].direction,
+                0b10 => self.pins.read(PORT_B, state.ports[PORT_B]),
+                0b11 => state.ports[PORT_B].direction,
+                _ => unreachable!(),
+            };
+        }
+
+        if register & 0b1 == 0 {
+            let value = state.timer;
+
+            if side_effects {
+                state.timer_interrupt_enable = register & 0b1000 != 0;
+                state.interrupt_flags &= !TIMER_FLAG;
+                self.update_irq(state);
+            }
+
+            value
+        } else {
+            let value = state.interrupt_flags;
+
+            if side_effects {
+                state.interrupt_flags &= !PA7_FLAG;
+                self.update_irq(state);
+            }
+
+            value
+        }
+    }
+
+    fn write_register(&self, state: &mut M6532State, register: usize, value: u8) {
+        if register & 0b100 == 0 {
+            let port = if register & 0b10 == 0 { PORT_A } else { PORT_B };
+
+            if register & 0b1 == 0 {
+                state.ports[port].output = value;
+            } else {
+                state.ports[port].direction = value;
+            }
+            self.pins.write(port, state.ports[port]);
+
+            if port == PORT_A {
+                self.sample_pa7(state);
+            }
+        } else if register & 0b1_0000 != 0 {
+            state.timer = value;
+            state.prescaler = PRESCALERS[register & 0b11];
+            state.prescaler_counter = 0;
+            state.timer_expired = false;
+            state.timer_interrupt_enable = register & 0b1000 != 0;
+            state.interrupt_flags &= !TIMER_FLAG;
+        } else {
+            state.pa7_positive_edge = register & 0b01 != 0;
+            state.pa7_interrupt_enable = register & 0b10 != 0;
+        }
+
+        self.update_irq(state);
+    }
+
+    fn read(&self, address: usize, side_effects: bool) -> u8 {
+        let mut state = self.state.lock().unwrap();
+
+        if self.config.ram_range.contains(&address) {
+            state.ram[(address - self.config.ram_range.start) % RAM_SIZE]
+        } else {
+            let register = (address - self.config.register_range.start) & 0x1f;
+            self.read_register(&mut state, register, side_effects)
+        }
+    }
+}
+
+impl Component for M6532 {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let ram = std::mem::take(&mut state.ram);
+        let irq = state.irq;
+
+        // Reset leaves RAM alone
+        *state = M6532State {
+            ram,
+            irq,
+            ..Default::default()
+        };
+        self.update_irq(&mut state);
+
+        for port in [PORT_A, PORT_B] {
+            self.pins.write(port, state.ports[port]);
+        }
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let mut state_guard = self.state.lock().unwrap();
+        let irq = state_guard.irq;
+
+        *state_guard = rmpv::ext::from_value(state).unwrap();
+        // Tell the connection about the loaded level
+        let raised = state_guard.irq;
+        state_guard.irq = irq;
+        self.irq.update(&mut state_guard.irq, raised);
+    }
+}
+
+impl FromConfig for M6532 {
+    type Config = M6532Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let frequency = config.frequency;
+        let assigned_address_space = config.assigned_address_space;
+        let ranges = [config.ram_range.clone(), config.register_range.clone()];
+        let pins = PortPins::new(
+            [
+                std::mem::take(&mut config.port_a),
+                std::mem::take(&mut config.port_b),
+            ],
+            std::mem::take(&mut config.gamepads),
+        );
+        let irq = InterruptOutput::new(config.irq.take());
+        let gamepads = pins.gamepads().to_vec();
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                pins,
+                irq,
+            })
+            .set_schedulable(frequency, [], [])
+            .set_memory(
+                ranges
+                    .into_iter()
+                    .map(|range| (assigned_address_space, range)),
+            );
+
+        if !gamepads.is_empty() {
+            let gamepad_types: Vec<_> = gamepads.iter().map(|(id, _)| id.clone()).collect();
+            component_builder.set_input(gamepads, gamepad_types);
+        }
+    }
+}
+
+impl SchedulableComponent for M6532 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..period {
+            if !state.timer_expired {
+                state.prescaler_counter += 1;
+
+                if state.prescaler_counter < state.prescaler {
+                    continue;
+                }
+                state.prescaler_counter = 0;
+            }
+
+            state.timer = state.timer.wrapping_sub(1);
+
+            if state.timer == 0xff && !state.timer_expired {
+                state.timer_expired = true;
+                state.interrupt_flags |= TIMER_FLAG;
+            }
+        }
+
+        // Joysticks don't change mid slice
+        self.sample_pa7(&mut state);
+        self.update_irq(&mut state);
+    }
+}
+
+impl InputComponent for M6532 {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.pins.set_input_manager(input_manager, gamepad_ids);
+    }
+}
+
+impl MemoryComponent for M6532 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address + offset, true);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter().enumerate() {
+            let address = address + offset;
+
+            if self.config.ram_range.contains(&address) {
+                state.ram[(address - self.config.ram_range.start) % RAM_SIZE] = *byte;
+            } else {
+                let register = (address - self.config.register_range.start) & 0x1f;
+                self.write_register(&mut state, register, *byte);
+            }
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address + offset, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{definitions::misc::io::PortConnection, machine::test_machine::TestMachineBuilder};
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    #[derive(Debug, Default)]
+    struct Switches(AtomicU8);
+
+    impl PortConnection for Switches {
+        fn read_pins(&self) -> u8 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn timer_and_ports() {
+        let builder = TestMachineBuilder::new().bus(0, 16);
+        let irq = builder.interrupt_line("irq");
+        let switches = Arc::new(Switches(AtomicU8::new(0xff)));
+        let (builder, riot) = builder.component::<M6532>(M6532Config {
+            frequency: Ratio::from_integer(1_193_182),
+            assigned_address_space: 0,
+            ram_range: 0x80..0x100,
+            register_range: 0x280..0x2a0,
+            port_a: PortWiring::Connection(switches.clone()),
+            port_b: PortWiring::Unconnected,
+            gamepads: Vec::new(),
+            irq: Some(Arc::new(irq)),
+        });
+        let machine = builder.build();
+
+        machine.load(0, 0x80, &[0x12]);
+        assert_eq!(machine.peek(0, 0x80, 1), [0x12]);
+
+        switches.0.store(0x7e, Ordering::Relaxed);
+        assert_eq!(machine.peek(0, 0x280, 1), [0x7e]);
+
+        // TIM8T with interrupts, 3 decrements of 8 cycles to reach 0 and one more to run out
+        machine.load(0, 0x29d, &[3]);
+        machine.run_component::<M6532>(riot, 24);
+        assert_eq!(machine.peek(0, 0x284, 1), [0]);
+        assert!(!machine.component::<M6532>(riot).irq());
+        machine.run_component::<M6532>(riot, 8);
+        assert!(machine.component::<M6532>(riot).irq());
+        assert_eq!(machine.peek(0, 0x285, 1)[0] & TIMER_FLAG, TIMER_FLAG);
+
+        // Then once a cycle
+        machine.run_component::<M6532>(riot, 2);
+        assert_eq!(machine.peek(0, 0x284, 1), [0xfd]);
+        assert_eq!(machine.take_interrupts().len(), 1);
+    }
+}
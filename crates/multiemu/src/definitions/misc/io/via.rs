@@ -0,0 +1,458 @@
+use super::{InterruptConnection, InterruptOutput, PortPins, PortRegisters, PortWiring};
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        memory::MemoryComponent,
+        schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    input::{manager::InputManager, EmulatedGamepadId},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use num::rational::Ratio;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+const PORT_A: usize = 0;
+const PORT_B: usize = 1;
+
+const CA2_FLAG: u8 = 0b0000_0001;
+const CA1_FLAG: u8 = 0b0000_0010;
+const SHIFT_FLAG: u8 = 0b0000_0100;
+const CB2_FLAG: u8 = 0b0000_1000;
+const CB1_FLAG: u8 = 0b0001_0000;
+const TIMER2_FLAG: u8 = 0b0010_0000;
+const TIMER1_FLAG: u8 = 0b0100_0000;
+
+/// Auxiliary control bit that makes timer 1 reload itself from the latch
+const TIMER1_FREE_RUNNING: u8 = 0b0100_0000;
+/// Auxiliary control bit that makes timer 2 count PB6 pulses instead of cycles
+const TIMER2_PULSE_COUNTING: u8 = 0b0010_0000;
+
+#[derive(Debug)]
+pub struct M6522Config {
+    pub frequency: Ratio<u64>,
+    pub assigned_address_space: AddressSpaceId,
+    /// 16 registers, mirrored across the whole range
+    pub assigned_range: Range<usize>,
+    pub port_a: PortWiring,
+    pub port_b: PortWiring,
+    /// Gamepads the ports read from with [PortWiring::Gamepads]
+    pub gamepads: Vec<(EmulatedGamepadTypeId, EmulatedGamepadMetadata)>,
+    pub irq: Option<Arc<dyn InterruptConnection>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct M6522State {
+    ports: [PortRegisters; 2],
+    timer1_counter: u16,
+    timer1_latch: u16,
+    /// One shot mode only interrupts once per load
+    timer1_armed: bool,
+    timer2_counter: u16,
+    timer2_latch_low: u8,
+    timer2_armed: bool,
+    shift: u8,
+    auxiliary_control: u8,
+    peripheral_control: u8,
+    interrupt_flags: u8,
+    interrupt_enable: u8,
+    ca1: bool,
+    cb1: bool,
+    irq: bool,
+}
+
+/// MOS 6522 versatile interface adapter, two 8 bit ports and two 16 bit timers
+///
+/// The shift register and the CA2/CB2 handshake lines only hold their registers
+#[derive(Debug)]
+pub struct M6522 {
+    config: M6522Config,
+    state: Mutex<M6522State>,
+    pins: PortPins<2>,
+    irq: InterruptOutput,
+}
+
+impl M6522 {
+    /// Whether the IRQ output is pulled
+    pub fn irq(&self) -> bool {
+        self.state.lock().unwrap().irq
+    }
+
+    /// Drives the CA1 input, flagging an interrupt on the edge the peripheral control register picks
+    pub fn set_ca1(&self, level: bool) {
+        let mut state = self.state.lock().unwrap();
+        let positive_edge = state.peripheral_control & 0b0000_0001 != 0;
+
+        if state.ca1 != level && level == positive_edge {
+            state.interrupt_flags |= CA1_FLAG;
+        }
+        state.ca1 = level;
+        self.update_irq(&mut state);
+    }
+
+    /// Drives the CB1 input, flagging an interrupt on the edge the peripheral control register picks
+    pub fn set_cb1(&self, level: bool) {
+        let mut state = self.state.lock().unwrap();
+        let positive_edge = state.peripheral_control & 0b0001_0000 != 0;
+
+        if state.cb1 != level && level == positive_edge {
+            state.interrupt_flags |= CB1_FLAG;
+        }
+        state.cb1 = level;
+        self.update_irq(&mut state);
+    }
+
+    /// A falling edge on PB6, which timer 2 counts in pulse counting mode
+    pub fn pulse_pb6(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.auxiliary_control & TIMER2_PULSE_COUNTING != 0 {
+            self.tick_timer2(&mut state);
+            self.update_irq(&mut state);
+        }
+    }
+
+    fn update_irq(&self, state: &mut M6522State) {
+        let raised = state.interrupt_flags & state.interrupt_enable & 0x7f != 0;
+        self.irq.update(&mut state.irq, raised);
+    }
+
+    fn tick_timer2(&self, state: &mut M6522State) {
+        state.timer2_counter = state.timer2_counter.wrapping_sub(1);
+
+        if state.timer2_counter == 0xffff && state.timer2_armed {
+            state.timer2_armed = false;
+            state.interrupt_flags |= TIMER2_FLAG;
+        }
+    }
+
+    fn register(&self, address: usize) -> usize {
+        (address - self.config.assigned_range.start) & 0xf
+    }
+
+    /// Reads a register, `side_effects` being false for the debugger looking at it
+    fn read_register(&self, state: &mut M6522State, register: usize, side_effects: bool) -> u8 {
+        let mut clear_flags = 0;
+
+        let value = match register {
+            0x0 => {
+                clear_flags = CB1_FLAG | CB2_FLAG;
+                self.pins.read(PORT_B, state.ports[PORT_B])
+            }
+            0x1 => {
+                clear_flags = CA1_FLAG | CA2_FLAG;
+                self.pins.read(PORT_A, state.ports[PORT_A])
+            }
+            0x2 => state.ports[PORT_B].direction,
+            0x3 => state.ports[PORT_A].direction,
+            0x4 => {
+                clear_flags = TIMER1_FLAG;
+                state.timer1_counter as u8
+            }
+            0x5 => (state.timer1_counter >> 8) as u8,
+            0x6 => state.timer1_latch as u8,
+            0x7 => (state.timer1_latch >> 8) as u8,
+            0x8 => {
+                clear_flags = TIMER2_FLAG;
+                state.timer2_counter as u8
+            }
+            0x9 => (state.timer2_counter >> 8) as u8,
+            0xa => {
+                clear_flags = SHIFT_FLAG;
+                state.shift
+            }
+            0xb => state.auxiliary_control,
+            0xc => state.peripheral_control,
+            0xd => {
+                let pending = state.interrupt_flags & state.interrupt_enable & 0x7f != 0;
+                state.interrupt_flags | if pending { 0x80 } else { 0x00 }
+            }
+            0xe => state.interrupt_enable | 0x80,
+            0xf => self.pins.read(PORT_A, state.ports[PORT_A]),
+            _ => unreachable!(),
+        };
+
+        if side_effects {
+            state.interrupt_flags &= !clear_flags;
+            self.update_irq(state);
+        }
+
+        value
+    }
+
+    fn write_register(&self, state: &mut M6522State, register: usize, value: u8) {
+        match register {
+            0x0 => {
+                state.interrupt_flags &= !(CB1_FLAG | CB2_FLAG);
+                state.ports[PORT_B].output = value;
+                self.pins.write(PORT_B, state.ports[PORT_B]);
+            }
+            0x1 | 0xf => {
+                if register == 0x1 {
+                    state.interrupt_flags &= !(CA1_FLAG | CA2_FLAG);
+                }
+                state.ports[PORT_A].output = value;
+                self.pins.write(PORT_A, state.ports[PORT_A]);
+            }
+            0x2 => {
+                state.ports[PORT_B].direction = value;
+                self.pins.write(PORT_B, state.ports[PORT_B]);
+            }
+            0x3 => {
+                state.ports[PORT_A].direction = value;
+                self.pins.write(PORT_A, state.ports[PORT_A]);
+            }
+            0x4 | 0x6 => {
+                state.timer1_latch = (state.timer1_latch & 0xff00) | value as u16;
+            }
+            0x5 => {
+                state.timer1_latch = (state.timer1_latch & 0x00ff) | ((value as u16) << 8);
+                state.timer1_counter = state.timer1_latch;
+                state.timer1_armed = true;
+                state.interrupt_flags &= !TIMER1_FLAG;
+            }
+            0x7 => {
+                state.timer1_latch = (state.timer1_latch & 0x00ff) | ((value as u16) << 8);
+                state.interrupt_flags &= !TIMER1_FLAG;
+            }
+            0x8 => {
+                state.timer2_latch_low = value;
+            }
+            0x9 => {
+                state.timer2_counter = ((value as u16) << 8) | state.timer2_latch_low as u16;
+                state.timer2_armed = true;
+                state.interrupt_flags &= !TIMER2_FLAG;
+            }
+            0xa => {
+                state.shift = value;
+                state.interrupt_flags &= !SHIFT_FLAG;
+            }
+            0xb => state.auxiliary_control = value,
+            0xc => state.peripheral_control = value,
+            0xd => state.interrupt_flags &= !value,
+            0xe => {
+                if value & 0x80 != 0 {
+                    state.interrupt_enable |= value & 0x7f;
+                } else {
+                    state.interrupt_enable &= !value;
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        self.update_irq(state);
+    }
+}
+
+impl Component for M6522 {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let irq = state.irq;
+
+        *state = M6522State {
+            irq,
+            ..Default::default()
+        };
+        self.update_irq(&mut state);
+
+        for port in [PORT_A, PORT_B] {
+            self.pins.write(port, state.ports[port]);
+        }
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let mut state_guard = self.state.lock().unwrap();
+        let irq = state_guard.irq;
+
+        *state_guard = rmpv::ext::from_value(state).unwrap();
+        // Tell the connection about the loaded level
+        let raised = state_guard.irq;
+        state_guard.irq = irq;
+        self.irq.update(&mut state_guard.irq, raised);
+    }
+}
+
+impl FromConfig for M6522 {
+    type Config = M6522Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let frequency = config.frequency;
+        let assigned_range = config.assigned_range.clone();
+        let assigned_address_space = config.assigned_address_space;
+        let pins = PortPins::new(
+            [
+                std::mem::take(&mut config.port_a),
+                std::mem::take(&mut config.port_b),
+            ],
+            std::mem::take(&mut config.gamepads),
+        );
+        let irq = InterruptOutput::new(config.irq.take());
+        let gamepads = pins.gamepads().to_vec();
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                pins,
+                irq,
+            })
+            .set_schedulable(frequency, [], [])
+            .set_memory([(assigned_address_space, assigned_range)]);
+
+        if !gamepads.is_empty() {
+            let gamepad_types: Vec<_> = gamepads.iter().map(|(id, _)| id.clone()).collect();
+            component_builder.set_input(gamepads, gamepad_types);
+        }
+    }
+}
+
+impl SchedulableComponent for M6522 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..period {
+            state.timer1_counter = state.timer1_counter.wrapping_sub(1);
+
+            if state.timer1_counter == 0xffff {
+                if state.timer1_armed {
+                    state.interrupt_flags |= TIMER1_FLAG;
+                }
+
+                if state.auxiliary_control & TIMER1_FREE_RUNNING != 0 {
+                    state.timer1_counter = state.timer1_latch;
+                } else {
+                    state.timer1_armed = false;
+                }
+            }
+
+            if state.auxiliary_control & TIMER2_PULSE_COUNTING == 0 {
+                self.tick_timer2(&mut state);
+            }
+        }
+
+        self.update_irq(&mut state);
+    }
+}
+
+impl InputComponent for M6522 {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.pins.set_input_manager(input_manager, gamepad_ids);
+    }
+}
+
+impl MemoryComponent for M6522 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_register(&mut state, self.register(address + offset), true);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter().enumerate() {
+            self.write_register(&mut state, self.register(address + offset), *byte);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_register(&mut state, self.register(address + offset), false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::{InterruptEvent, TestMachineBuilder};
+
+    #[test]
+    fn timer1_interrupts() {
+        let builder = TestMachineBuilder::new().bus(0, 16);
+        let irq = builder.interrupt_line("irq");
+        let (builder, via) = builder.component::<M6522>(M6522Config {
+            frequency: Ratio::from_integer(1_000_000),
+            assigned_address_space: 0,
+            assigned_range: 0x9000..0x9010,
+            port_a: PortWiring::Unconnected,
+            port_b: PortWiring::Unconnected,
+            gamepads: Vec::new(),
+            irq: Some(Arc::new(irq)),
+        });
+        let machine = builder.build();
+
+        // Port B half outputs, the floating inputs read high
+        machine.load(0, 0x9002, &[0x0f]);
+        machine.load(0, 0x9000, &[0x05]);
+        assert_eq!(machine.peek(0, 0x9000, 1), [0xf5]);
+
+        // Enable timer 1 interrupts and start it one shot at 10 cycles
+        machine.load(0, 0x900e, &[0x80 | TIMER1_FLAG]);
+        machine.load(0, 0x9004, &[10, 0]);
+        machine.run_component::<M6522>(via, 10);
+        assert!(machine.take_interrupts().is_empty());
+        machine.run_component::<M6522>(via, 1);
+        assert_eq!(machine.peek(0, 0x900d, 1), [0x80 | TIMER1_FLAG]);
+
+        // Reading the counter acknowledges it, and one shot mode stays quiet afterwards
+        let mut buffer = [0];
+        machine
+            .machine
+            .memory_translation_table
+            .read(0x9004, &mut buffer, 0)
+            .unwrap();
+        machine.run_component::<M6522>(via, 0x20000);
+        assert_eq!(
+            machine.take_interrupts(),
+            [
+                InterruptEvent {
+                    line: "irq",
+                    raised: true,
+                    source: None
+                },
+                InterruptEvent {
+                    line: "irq",
+                    raised: false,
+                    source: None
+                }
+            ]
+        );
+    }
+}
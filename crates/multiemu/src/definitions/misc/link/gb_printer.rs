@@ -0,0 +1,340 @@
+use crate::{component::link::LinkEndpoint, config::GLOBAL_CONFIG};
+use image::{GrayImage, ImageFormat, Luma};
+use std::{
+    fs::create_dir_all,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Tiles across the paper
+const TILES_PER_ROW: usize = 20;
+const BYTES_PER_TILE: usize = 16;
+/// The printer only has 8 KiB of memory for image data
+const IMAGE_MEMORY_SIZE: usize = 0x2000;
+/// Games that leave the palette zeroed still expect the normal shades
+const DEFAULT_PALETTE: u8 = 0b11100100;
+const SHADES: [u8; 4] = [0xff, 0xaa, 0x55, 0x00];
+
+/// Response to the first of the two bytes that end a packet
+const ALIVE: u8 = 0x81;
+
+const STATUS_CHECKSUM_ERROR: u8 = 0b0000_0001;
+const STATUS_IMAGE_DATA_FULL: u8 = 0b0000_0100;
+const STATUS_UNPROCESSED_DATA: u8 = 0b0000_1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Initialize,
+    Print,
+    Data,
+    Break,
+    Status,
+}
+
+impl TryFrom<u8> for Command {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0x01 => Command::Initialize,
+            0x02 => Command::Print,
+            0x04 => Command::Data,
+            0x08 => Command::Break,
+            0x0f => Command::Status,
+            _ => return Err(value),
+        })
+    }
+}
+
+/// Where in a packet the next byte goes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    #[default]
+    Magic,
+    Sync,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+#[derive(Debug, Default)]
+struct PrinterState {
+    stage: Stage,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    received_checksum: u16,
+    /// Tile data waiting for a print command
+    image_data: Vec<u8>,
+    status: u8,
+}
+
+/// Game Boy Printer, prints go to PNG files in the capture directory
+#[derive(Debug, Default)]
+pub struct GameBoyPrinter {
+    state: Mutex<PrinterState>,
+}
+
+impl LinkEndpoint for GameBoyPrinter {
+    fn name(&self) -> &'static str {
+        "Game Boy Printer"
+    }
+
+    fn exchange(&self, byte: u8) -> u8 {
+        let mut state = self.state.lock().unwrap();
+
+        match state.stage {
+            Stage::Magic => {
+                if byte == 0x88 {
+                    state.stage = Stage::Sync;
+                }
+            }
+            Stage::Sync => {
+                state.stage = if byte == 0x33 {
+                    Stage::Command
+                } else {
+                    Stage::Magic
+                };
+            }
+            Stage::Command => {
+                state.command = byte;
+                state.checksum = byte as u16;
+                state.stage = Stage::Compression;
+            }
+            Stage::Compression => {
+                state.compressed = byte & 1 != 0;
+                state.checksum = state.checksum.wrapping_add(byte as u16);
+                state.stage = Stage::LengthLow;
+            }
+            Stage::LengthLow => {
+                state.length = byte as u16;
+                state.checksum = state.checksum.wrapping_add(byte as u16);
+                state.stage = Stage::LengthHigh;
+            }
+            Stage::LengthHigh => {
+                state.length |= (byte as u16) << 8;
+                state.checksum = state.checksum.wrapping_add(byte as u16);
+                state.data.clear();
+                state.stage = if state.length == 0 {
+                    Stage::ChecksumLow
+                } else {
+                    Stage::Data
+                };
+            }
+            Stage::Data => {
+                state.data.push(byte);
+                state.checksum = state.checksum.wrapping_add(byte as u16);
+
+                if state.data.len() == state.length as usize {
+                    state.stage = Stage::ChecksumLow;
+                }
+            }
+            Stage::ChecksumLow => {
+                state.received_checksum = byte as u16;
+                state.stage = Stage::ChecksumHigh;
+            }
+            Stage::ChecksumHigh => {
+                state.received_checksum |= (byte as u16) << 8;
+                state.stage = Stage::Alive;
+            }
+            Stage::Alive => {
+                state.stage = Stage::Status;
+                return ALIVE;
+            }
+            Stage::Status => {
+                state.stage = Stage::Magic;
+                state.handle_packet();
+                return state.status;
+            }
+        }
+
+        0x00
+    }
+}
+
+impl PrinterState {
+    fn handle_packet(&mut self) {
+        if self.checksum != self.received_checksum {
+            tracing::warn!("Printer packet failed its checksum");
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        match Command::try_from(self.command) {
+            Ok(Command::Initialize) | Ok(Command::Break) => {
+                self.image_data.clear();
+                self.status = 0;
+            }
+            Ok(Command::Data) => {
+                let data = if self.compressed {
+                    decompress(&self.data)
+                } else {
+                    std::mem::take(&mut self.data)
+                };
+                let space = IMAGE_MEMORY_SIZE - self.image_data.len();
+
+                if data.len() > space {
+                    self.status |= STATUS_IMAGE_DATA_FULL;
+                }
+
+                self.image_data.extend(data.into_iter().take(space));
+
+                if !self.image_data.is_empty() {
+                    self.status |= STATUS_UNPROCESSED_DATA;
+                }
+            }
+            Ok(Command::Print) => {
+                // Sheets, margins, palette and exposure, only the palette matters to us
+                let palette = self.data.get(2).copied().unwrap_or(DEFAULT_PALETTE);
+
+                if let Some(image) = render(&self.image_data, palette) {
+                    save(&image);
+                }
+
+                self.image_data.clear();
+                self.status = 0;
+            }
+            Ok(Command::Status) => {}
+            Err(command) => tracing::warn!("Unknown printer command {:#04x}", command),
+        }
+    }
+}
+
+/// Run length encoding, a set top bit repeats the next byte and a clear one copies bytes straight through
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut decompressed = Vec::new();
+    let mut data = data.iter().copied();
+
+    while let Some(control) = data.next() {
+        if control & 0x80 != 0 {
+            if let Some(byte) = data.next() {
+                let length = (control & 0x7f) as usize + 2;
+                decompressed.extend(std::iter::repeat_n(byte, length));
+            }
+        } else {
+            let length = control as usize + 1;
+            decompressed.extend(data.by_ref().take(length));
+        }
+    }
+
+    decompressed
+}
+
+/// Turns the tile data into what would come out on the paper, None if there isn't a full row of tiles
+fn render(image_data: &[u8], palette: u8) -> Option<GrayImage> {
+    let palette = if palette == 0 {
+        DEFAULT_PALETTE
+    } else {
+        palette
+    };
+    let tile_rows = image_data.len() / (TILES_PER_ROW * BYTES_PER_TILE);
+
+    if tile_rows == 0 {
+        return None;
+    }
+
+    let mut image = GrayImage::new((TILES_PER_ROW * 8) as u32, (tile_rows * 8) as u32);
+
+    for (tile_index, tile) in image_data
+        .chunks_exact(BYTES_PER_TILE)
+        .take(tile_rows * TILES_PER_ROW)
+        .enumerate()
+    {
+        let tile_x = (tile_index % TILES_PER_ROW) * 8;
+        let tile_y = (tile_index / TILES_PER_ROW) * 8;
+
+        for (row, planes) in tile.chunks_exact(2).enumerate() {
+            for column in 0..8 {
+                let bit = 7 - column;
+                let color = (((planes[1] >> bit) & 1) << 1) | ((planes[0] >> bit) & 1);
+                let shade = (palette >> (color * 2)) & 0b11;
+
+                image.put_pixel(
+                    (tile_x + column) as u32,
+                    (tile_y + row) as u32,
+                    Luma([SHADES[shade as usize]]),
+                );
+            }
+        }
+    }
+
+    Some(image)
+}
+
+fn save(image: &GrayImage) {
+    let capture_directory = GLOBAL_CONFIG.read().unwrap().capture_directory.clone();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = capture_directory.join(format!("printer-{}.png", timestamp));
+
+    if let Err(error) = create_dir_all(&capture_directory) {
+        tracing::error!(
+            "Could not create {}: {}",
+            capture_directory.display(),
+            error
+        );
+        return;
+    }
+
+    match image.save_with_format(&path, ImageFormat::Png) {
+        Ok(()) => tracing::info!("Printed to {}", path.display()),
+        Err(error) => tracing::error!("Could not save print to {}: {}", path.display(), error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn packet(command: u8, compressed: bool, data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x88, 0x33, command, compressed as u8];
+        packet.extend((data.len() as u16).to_le_bytes());
+        packet.extend(data);
+
+        let checksum = packet[2..]
+            .iter()
+            .fold(0u16, |checksum, byte| checksum.wrapping_add(*byte as u16));
+        packet.extend(checksum.to_le_bytes());
+        packet.extend([0x00, 0x00]);
+
+        packet
+    }
+
+    #[test]
+    fn receives_compressed_band() {
+        let printer = GameBoyPrinter::default();
+
+        let responses: Vec<_> = packet(0x01, false, &[])
+            .into_iter()
+            .map(|byte| printer.exchange(byte))
+            .collect();
+        assert_eq!(responses[responses.len() - 2..], [ALIVE, 0x00]);
+
+        // 640 bytes of 0xff, which is a band of solid color 3 tiles
+        let band: Vec<_> = std::iter::repeat_n([0xfe, 0xff], 5).flatten().collect();
+        let responses: Vec<_> = packet(0x04, true, &band)
+            .into_iter()
+            .map(|byte| printer.exchange(byte))
+            .collect();
+        assert_eq!(responses.last(), Some(&STATUS_UNPROCESSED_DATA));
+
+        let state = printer.state.lock().unwrap();
+        assert_eq!(state.image_data.len(), 640);
+
+        let image = render(&state.image_data, DEFAULT_PALETTE).unwrap();
+        assert_eq!(image.dimensions(), (160, 16));
+        assert_eq!(image.get_pixel(100, 10).0, [0x00]);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    component::{link::LinkEndpoint, Component, FromConfig},
+    config::GLOBAL_CONFIG,
+    machine::ComponentBuilder,
+};
+use gb_printer::GameBoyPrinter;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+pub mod gb_printer;
+
+/// What the line reads as with nothing plugged in, it is pulled high
+const DISCONNECTED: u8 = 0xff;
+
+/// Endpoints that can be plugged into link ports from the config
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkPeripheralConfig {
+    /// Prints into the capture directory
+    GameBoyPrinter,
+}
+
+impl LinkPeripheralConfig {
+    pub fn create(&self) -> Arc<dyn LinkEndpoint> {
+        match self {
+            LinkPeripheralConfig::GameBoyPrinter => Arc::new(GameBoyPrinter::default()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LinkPortConfig;
+
+/// The connector a link cable goes into, serial hardware talks to whatever is attached through this
+#[derive(Debug)]
+pub struct LinkPort {
+    endpoint: RwLock<Option<Arc<dyn LinkEndpoint>>>,
+}
+
+impl LinkPort {
+    /// Replaces whatever is plugged in, None unplugs the cable
+    pub fn attach(&self, endpoint: Option<Arc<dyn LinkEndpoint>>) {
+        match &endpoint {
+            Some(endpoint) => tracing::info!("Attached {} to the link port", endpoint.name()),
+            None => tracing::info!("Unplugged the link port"),
+        }
+
+        *self.endpoint.write().unwrap() = endpoint;
+    }
+
+    pub fn endpoint_name(&self) -> Option<&'static str> {
+        self.endpoint
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|endpoint| endpoint.name())
+    }
+
+    /// Shift a byte out and get one back
+    pub fn exchange(&self, byte: u8) -> u8 {
+        match self.endpoint.read().unwrap().as_ref() {
+            Some(endpoint) => endpoint.exchange(byte),
+            None => DISCONNECTED,
+        }
+    }
+}
+
+impl Component for LinkPort {
+    fn has_link_port(&self) -> bool {
+        true
+    }
+
+    // Whatever is attached keeps its own state, which rewinding can't take back
+    fn rewind_safe(&self) -> bool {
+        self.endpoint.read().unwrap().is_none()
+    }
+}
+
+impl FromConfig for LinkPort {
+    type Config = LinkPortConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, _config: Self::Config) {
+        let endpoint = GLOBAL_CONFIG
+            .read()
+            .unwrap()
+            .link_peripherals
+            .get(&component_builder.machine().system)
+            .map(LinkPeripheralConfig::create);
+
+        component_builder.set_component(Self {
+            endpoint: RwLock::new(endpoint),
+        });
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    component::{memory::MemoryComponent, Component, FromConfig},
+    machine::ComponentBuilder,
+    memory::{
+        AddressSpaceId, MemoryTranslationTable, PreviewMemoryRecord, ReadMemoryRecord,
+        WriteMemoryRecord, VALID_ACCESS_SIZES,
+    },
+    rom::{id::RomId, manager::RomRequirement},
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+#[derive(Debug)]
+pub struct BankedMemoryConfig {
+    pub readable: bool,
+    /// Writes land in the selected bank, ROM banks should leave this off
+    pub writable: bool,
+    /// Window the selected bank shows up in, which is also the size of each bank
+    pub assigned_range: Range<usize>,
+    /// Address space this exists on
+    pub assigned_address_space: AddressSpaceId,
+    pub bank_count: usize,
+    /// Fills the banks from the start of this ROM, otherwise they start zeroed
+    pub rom: Option<RomId>,
+    /// Writes here select the bank, wrapped to the bank count like the unconnected high lines of a latch
+    ///
+    /// It can be inside the window, like the many cartridges that switch on writes to their own ROM
+    pub control_register: Option<(AddressSpaceId, usize)>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BankedMemorySnapshot {
+    selected_bank: usize,
+    /// Left empty for ROM banks, which can't have changed
+    memory: Vec<u8>,
+}
+
+/// A fixed number of equally sized banks, one of which shows up in the window at a time
+#[derive(Debug)]
+pub struct BankedMemory {
+    config: BankedMemoryConfig,
+    memory: Mutex<Vec<u8>>,
+    /// What [Component::reset] goes back to
+    initial_memory: Vec<u8>,
+    selected_bank: Mutex<usize>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+}
+
+impl BankedMemory {
+    fn bank_size(&self) -> usize {
+        self.config.assigned_range.len()
+    }
+
+    pub fn selected_bank(&self) -> usize {
+        *self.selected_bank.lock().unwrap()
+    }
+
+    /// Switches the window to another bank, wrapping around if there aren't that many
+    pub fn select_bank(&self, bank: usize) {
+        *self.selected_bank.lock().unwrap() = bank % self.config.bank_count;
+
+        if let Some(memory_translation_table) = self.memory_translation_table.get() {
+            memory_translation_table.mappings_changed(self.config.assigned_address_space);
+        }
+    }
+
+    fn is_control_register(&self, address: usize, address_space: AddressSpaceId) -> bool {
+        self.config.control_register == Some((address_space, address))
+    }
+
+    fn in_window(&self, address: usize, address_space: AddressSpaceId) -> bool {
+        address_space == self.config.assigned_address_space
+            && self.config.assigned_range.contains(&address)
+    }
+
+    /// Where an address in the window is in the backing memory
+    fn offset(&self, address: usize) -> usize {
+        self.selected_bank() * self.bank_size() + (address - self.config.assigned_range.start)
+    }
+
+    fn read(&self, address: usize, buffer: &mut [u8], address_space: AddressSpaceId) {
+        let memory = self.memory.lock().unwrap();
+
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = if self.in_window(address, address_space) {
+                memory[self.offset(address)]
+            } else {
+                // A control register outside the window is write only
+                0
+            };
+        }
+    }
+}
+
+impl Component for BankedMemory {
+    fn reset(&self) {
+        self.memory
+            .lock()
+            .unwrap()
+            .copy_from_slice(&self.initial_memory);
+        self.select_bank(0);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        let memory = if self.config.writable {
+            self.memory.lock().unwrap().clone()
+        } else {
+            Vec::new()
+        };
+
+        rmpv::ext::to_value(BankedMemorySnapshot {
+            selected_bank: self.selected_bank(),
+            memory,
+        })
+        .unwrap()
+    }
+
+    fn load_snapshot(&self, snapshot: rmpv::Value) {
+        let snapshot: BankedMemorySnapshot = rmpv::ext::from_value(snapshot).unwrap();
+
+        if self.config.writable {
+            let mut memory = self.memory.lock().unwrap();
+            assert_eq!(snapshot.memory.len(), memory.len());
+            memory.copy_from_slice(&snapshot.memory);
+        }
+        self.select_bank(snapshot.selected_bank);
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for BankedMemory {
+    type Config = BankedMemoryConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        assert!(
+            !config.assigned_range.is_empty(),
+            "Memory assigned must be non-empty"
+        );
+        assert!(config.bank_count != 0, "Need at least one bank");
+
+        let mut initial_memory = vec![0; config.assigned_range.len() * config.bank_count];
+        if let Some(rom) = config
+            .rom
+            .and_then(|rom| component_builder.open_rom(rom, RomRequirement::Required))
+        {
+            let length = rom.len().min(initial_memory.len());
+            initial_memory[..length].copy_from_slice(&rom[..length]);
+        }
+
+        let mut ranges = vec![(config.assigned_address_space, config.assigned_range.clone())];
+        ranges.extend(
+            config
+                .control_register
+                .map(|(address_space, address)| (address_space, address..address + 1)),
+        );
+
+        component_builder
+            .set_component(Self {
+                config,
+                memory: Mutex::new(initial_memory.clone()),
+                initial_memory,
+                selected_bank: Mutex::default(),
+                memory_translation_table: OnceLock::new(),
+            })
+            .set_memory(ranges);
+    }
+}
+
+impl MemoryComponent for BankedMemory {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        debug_assert!(
+            VALID_ACCESS_SIZES.contains(&buffer.len()),
+            "Invalid memory access size {}",
+            buffer.len()
+        );
+
+        if !self.config.readable {
+            errors.insert(address..address + buffer.len(), ReadMemoryRecord::Denied);
+            return;
+        }
+
+        self.read(address, buffer, address_space);
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        debug_assert!(
+            VALID_ACCESS_SIZES.contains(&buffer.len()),
+            "Invalid memory access size {}",
+            buffer.len()
+        );
+
+        for (address, value) in (address..).zip(buffer.iter().copied()) {
+            if self.is_control_register(address, address_space) {
+                self.select_bank(value as usize);
+            } else if self.config.writable && self.in_window(address, address_space) {
+                let offset = self.offset(address);
+                self.memory.lock().unwrap()[offset] = value;
+            } else {
+                errors.insert(address..address + 1, WriteMemoryRecord::Denied);
+            }
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        if !self.config.readable {
+            errors.insert(address..address + buffer.len(), PreviewMemoryRecord::Denied);
+            return;
+        }
+
+        self.read(address, buffer, address_space);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn switching_banks() {
+        let (builder, banked) = TestMachineBuilder::new()
+            .bus(0, 16)
+            .component::<BankedMemory>(BankedMemoryConfig {
+                readable: true,
+                writable: true,
+                assigned_range: 0x8000..0xc000,
+                assigned_address_space: 0,
+                bank_count: 4,
+                rom: None,
+                control_register: Some((0, 0xffff)),
+            });
+        let machine = builder.build();
+        let banked = machine.component::<BankedMemory>(banked);
+
+        for bank in 0..4 {
+            machine.load(0, 0xffff, &[bank]);
+            machine.load(0, 0x8000, &[bank + 0x10]);
+        }
+
+        // Only the low bits of the latch are wired up
+        machine.load(0, 0xffff, &[6]);
+        assert_eq!(banked.selected_bank(), 2);
+        assert_eq!(machine.peek(0, 0x8000, 1), [0x12]);
+
+        let snapshot = banked.save_snapshot();
+        banked.select_bank(1);
+        assert_eq!(machine.peek(0, 0x8000, 1), [0x11]);
+
+        banked.load_snapshot(snapshot);
+        assert_eq!(machine.peek(0, 0x8000, 1), [0x12]);
+    }
+}
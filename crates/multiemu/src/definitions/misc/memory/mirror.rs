@@ -1,38 +1,78 @@
 use crate::{
     component::{memory::MemoryComponent, Component, FromConfig},
     machine::ComponentBuilder,
-    memory::{AddressSpaceId, ReadMemoryRecord, WriteMemoryRecord, VALID_ACCESS_SIZES},
+    memory::{
+        AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord,
+        VALID_ACCESS_SIZES,
+    },
 };
 use rangemap::RangeMap;
+use std::ops::Range;
 
 #[derive(Debug)]
 pub struct MirrorMemoryConfig {
     pub readable: bool,
     pub writable: bool,
-    pub assigned_ranges: RangeMap<usize, usize>,
+    /// Where the mirrors show up
+    pub assigned_range: Range<usize>,
+    /// Start of what is being mirrored, which can't be inside the assigned range
+    pub destination: usize,
+    /// Size of what is being mirrored, the mirrors repeat every this many bytes
+    pub stride: usize,
     /// Address space this exists on
     pub assigned_address_space: AddressSpaceId,
 }
 
+/// Redirects every access in its range back to a smaller one, like the NES's 2KB of RAM showing up four times
 #[derive(Debug)]
 pub struct MirrorMemory {
     config: MirrorMemoryConfig,
 }
 
+impl MirrorMemory {
+    /// Splits an access where it crosses from one mirror into the next, giving where each piece really goes
+    fn redirects(
+        &self,
+        address: usize,
+        length: usize,
+    ) -> impl Iterator<Item = (Range<usize>, usize)> + '_ {
+        let end = address + length;
+        let mut current = address;
+
+        std::iter::from_fn(move || {
+            if current >= end {
+                return None;
+            }
+
+            let offset = (current - self.config.assigned_range.start) % self.config.stride;
+            let piece = current..end.min(current + self.config.stride - offset);
+            current = piece.end;
+
+            Some((piece, self.config.destination + offset))
+        })
+    }
+}
+
 impl Component for MirrorMemory {}
 
 impl FromConfig for MirrorMemory {
     type Config = MirrorMemoryConfig;
 
     fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        assert!(config.stride != 0, "Mirror stride can't be zero");
+        // Any part of the destination inside the mirror would redirect back into it forever
+        assert!(
+            config.destination + config.stride <= config.assigned_range.start
+                || config.destination >= config.assigned_range.end,
+            "Mirror would redirect to itself"
+        );
+
         let assigned_address_space = config.assigned_address_space;
-        let assigned_ranges = config.assigned_ranges.clone();
+        let assigned_range = config.assigned_range.clone();
 
-        component_builder.set_component(Self { config }).set_memory(
-            assigned_ranges
-                .into_iter()
-                .map(|(assignment, _)| (assigned_address_space, assignment)),
-        );
+        component_builder
+            .set_component(Self { config })
+            .set_memory([(assigned_address_space, assigned_range)]);
     }
 }
 
@@ -50,26 +90,19 @@ impl MemoryComponent for MirrorMemory {
             buffer.len()
         );
 
-        let affected_range = address..address + buffer.len();
-
         if !self.config.readable {
-            errors.insert(affected_range.clone(), ReadMemoryRecord::Denied);
+            errors.insert(address..address + buffer.len(), ReadMemoryRecord::Denied);
+            return;
         }
 
-        let redirect_base_address = self
-            .config
-            .assigned_ranges
-            .get(&affected_range.start)
-            .unwrap();
-        let adjusted_redirect_base_address =
-            redirect_base_address + (address - affected_range.start);
-
-        errors.insert(
-            affected_range,
-            ReadMemoryRecord::Redirect {
-                address: adjusted_redirect_base_address,
-            },
-        );
+        for (range, redirect_address) in self.redirects(address, buffer.len()) {
+            errors.insert(
+                range,
+                ReadMemoryRecord::Redirect {
+                    address: redirect_address,
+                },
+            );
+        }
     }
 
     fn write_memory(
@@ -85,26 +118,41 @@ impl MemoryComponent for MirrorMemory {
             buffer.len()
         );
 
-        let affected_range = address..address + buffer.len();
-
         if !self.config.writable {
-            errors.insert(affected_range.clone(), WriteMemoryRecord::Denied);
+            errors.insert(address..address + buffer.len(), WriteMemoryRecord::Denied);
+            return;
         }
 
-        let redirect_base_address = self
-            .config
-            .assigned_ranges
-            .get(&affected_range.start)
-            .unwrap();
-        let adjusted_redirect_base_address =
-            redirect_base_address + (address - affected_range.start);
-
-        errors.insert(
-            affected_range,
-            WriteMemoryRecord::Redirect {
-                address: adjusted_redirect_base_address,
-            },
-        );
+        for (range, redirect_address) in self.redirects(address, buffer.len()) {
+            errors.insert(
+                range,
+                WriteMemoryRecord::Redirect {
+                    address: redirect_address,
+                },
+            );
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        if !self.config.readable {
+            errors.insert(address..address + buffer.len(), PreviewMemoryRecord::Denied);
+            return;
+        }
+
+        for (range, redirect_address) in self.redirects(address, buffer.len()) {
+            errors.insert(
+                range,
+                PreviewMemoryRecord::Redirect {
+                    address: redirect_address,
+                },
+            );
+        }
     }
 }
 
@@ -139,7 +187,9 @@ mod test {
             .build_component::<MirrorMemory>(MirrorMemoryConfig {
                 readable: true,
                 writable: true,
-                assigned_ranges: RangeMap::from_iter([(0x10000..0x20000, 0x0000)]),
+                assigned_range: 0x10000..0x20000,
+                destination: 0x0000,
+                stride: 0x10000,
                 assigned_address_space: ADDRESS_SPACE,
             })
             .0
@@ -170,7 +220,9 @@ mod test {
             .build_component::<MirrorMemory>(MirrorMemoryConfig {
                 readable: true,
                 writable: true,
-                assigned_ranges: RangeMap::from_iter([(0x10000..0x20000, 0x0000)]),
+                assigned_range: 0x10000..0x20000,
+                destination: 0x0000,
+                stride: 0x10000,
                 assigned_address_space: ADDRESS_SPACE,
             })
             .0
@@ -182,4 +234,53 @@ mod test {
             .write(0x10000, &buffer, ADDRESS_SPACE)
             .unwrap();
     }
+
+    #[test]
+    fn repeats_every_stride() {
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let machine = Machine::build(GameSystem::Unknown, rom_manager)
+            .insert_bus(ADDRESS_SPACE, 16)
+            .build_component::<StandardMemory>(StandardMemoryConfig {
+                max_word_size: 2,
+                readable: true,
+                writable: true,
+                assigned_range: 0..0x800,
+                assigned_address_space: ADDRESS_SPACE,
+                initial_contents: StandardMemoryInitialContents::Value { value: 0x00 },
+            })
+            .0
+            .build_component::<MirrorMemory>(MirrorMemoryConfig {
+                readable: true,
+                writable: true,
+                assigned_range: 0x800..0x2000,
+                destination: 0x0000,
+                stride: 0x800,
+                assigned_address_space: ADDRESS_SPACE,
+            })
+            .0
+            .build();
+        let memory_translation_table = &machine.memory_translation_table;
+
+        memory_translation_table
+            .write(0x1234, &[0x42], ADDRESS_SPACE)
+            .unwrap();
+        let mut buffer = [0];
+        memory_translation_table
+            .read(0x0234, &mut buffer, ADDRESS_SPACE)
+            .unwrap();
+        assert_eq!(buffer, [0x42]);
+
+        // Straddling two mirrors wraps back around to the start
+        memory_translation_table
+            .write(0x0000, &[0x24], ADDRESS_SPACE)
+            .unwrap();
+        memory_translation_table
+            .write(0x07ff, &[0x99], ADDRESS_SPACE)
+            .unwrap();
+        let mut buffer = [0; 2];
+        memory_translation_table
+            .read(0x0fff, &mut buffer, ADDRESS_SPACE)
+            .unwrap();
+        assert_eq!(buffer, [0x99, 0x24]);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod banked;
 pub mod mirror;
 pub mod rom;
 pub mod standard;
@@ -5,9 +5,8 @@ use crate::{
         AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord,
         VALID_ACCESS_SIZES,
     },
-    rom::{id::RomId, manager::RomRequirement},
+    rom::{handle::RomHandle, id::RomId, manager::RomRequirement},
 };
-use memmap2::{Mmap, MmapOptions};
 use rangemap::RangeMap;
 use std::ops::Range;
 
@@ -25,8 +24,21 @@ pub struct RomMemoryConfig {
 #[derive(Debug)]
 pub struct RomMemory {
     config: RomMemoryConfig,
-    // FIXME: Create a fallback for platforms without mmap
-    rom: Mmap,
+    /// Missing if the ROM could not be found, in which case it reads as open bus
+    rom: Option<RomHandle>,
+}
+
+impl RomMemory {
+    fn read_rom(&self, address: usize, buffer: &mut [u8]) {
+        let Some(rom) = &self.rom else {
+            buffer.fill(0xff);
+            return;
+        };
+
+        let adjusted_offset = address - self.config.assigned_range.start;
+        let amount = rom.read_at(adjusted_offset, buffer);
+        buffer[amount..].fill(0xff);
+    }
 }
 
 impl Component for RomMemory {
@@ -39,15 +51,10 @@ impl FromConfig for RomMemory {
     type Config = RomMemoryConfig;
 
     fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
-        let rom_file = component_builder
-            .machine()
-            .rom_manager
-            .open(config.rom, RomRequirement::Required)
-            .unwrap();
+        let rom = component_builder.open_rom(config.rom, RomRequirement::Required);
 
         let assigned_range = config.assigned_range.clone();
         let assigned_address_space = config.assigned_address_space;
-        let rom = unsafe { MmapOptions::new().map(&rom_file).unwrap() };
 
         component_builder
             .set_component(Self { config, rom })
@@ -75,10 +82,7 @@ impl MemoryComponent for RomMemory {
             errors.insert(affected_range.clone(), ReadMemoryRecord::Denied);
         }
 
-        let adjusted_offset = address - self.config.assigned_range.start;
-        buffer.copy_from_slice(
-            &self.rom[adjusted_offset..(adjusted_offset + buffer.len()).min(self.rom.len())],
-        );
+        self.read_rom(address, buffer);
     }
 
     fn write_memory(
@@ -103,9 +107,6 @@ impl MemoryComponent for RomMemory {
         _address_space: AddressSpaceId,
         _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
     ) {
-        let adjusted_offset = address - self.config.assigned_range.start;
-        buffer.copy_from_slice(
-            &self.rom[adjusted_offset..(adjusted_offset + buffer.len()).min(self.rom.len())],
-        );
+        self.read_rom(address, buffer);
     }
 }
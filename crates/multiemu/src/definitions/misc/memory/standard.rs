@@ -9,11 +9,11 @@ use crate::{
 };
 use rand::RngCore;
 use rangemap::RangeMap;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
-    io::{Read, Write},
+    io::Write,
     ops::Range,
     sync::{Arc, Mutex},
 };
@@ -34,6 +34,26 @@ pub enum StandardMemoryInitialContents {
         offset: usize,
     },
     Random,
+    /// Repeats over the range, like the alternating stripes some machines power on with
+    Pattern {
+        value: Cow<'static, [u8]>,
+        range: Range<usize>,
+    },
+    /// Applied in order, so later segments are layered on top of earlier ones
+    Segments(Vec<StandardMemoryInitialContents>),
+}
+
+impl StandardMemoryInitialContents {
+    /// Every ROM this needs, segments included
+    fn roms(&self) -> Vec<RomId> {
+        match self {
+            StandardMemoryInitialContents::Rom { rom_id, .. } => vec![*rom_id],
+            StandardMemoryInitialContents::Segments(segments) => {
+                segments.iter().flat_map(Self::roms).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -76,6 +96,8 @@ impl Component for StandardMemory {
             let chunk_guard = chunk.lock().unwrap();
             memory.write_all(chunk_guard.as_slice()).unwrap();
         }
+        // The last chunk is usually only partly ours
+        memory.truncate(self.config.assigned_range.len());
 
         let state = StandardMemorySnapshot { memory };
 
@@ -118,6 +140,10 @@ impl FromConfig for StandardMemory {
         let assigned_range = config.assigned_range.clone();
         let assigned_address_space = config.assigned_address_space;
 
+        for rom_id in config.initial_contents.roms() {
+            component_builder.require_rom(rom_id, RomRequirement::Required);
+        }
+
         let me = Self {
             config,
             buffer: buffer.into_iter().collect(),
@@ -296,11 +322,37 @@ impl StandardMemory {
         }
     }
 
+    /// Same as [Self::write_internal] but each chunk is copied on the thread pool, for initializing large memories
+    ///
+    /// Anything past the end of the memory is cut off
+    fn write_internal_parallel(&self, address: usize, buffer: &[u8]) {
+        let start = address - self.config.assigned_range.start;
+        let end = (start + buffer.len()).min(self.config.assigned_range.len());
+
+        if start >= end {
+            return;
+        }
+
+        (start / CHUNK_SIZE..end.div_ceil(CHUNK_SIZE))
+            .into_par_iter()
+            .for_each(|chunk_index| {
+                let chunk_base = chunk_index * CHUNK_SIZE;
+                let copy_start = start.max(chunk_base);
+                let copy_end = end.min(chunk_base + CHUNK_SIZE);
+
+                self.buffer[chunk_index].lock().unwrap()
+                    [copy_start - chunk_base..copy_end - chunk_base]
+                    .copy_from_slice(&buffer[copy_start - start..copy_end - start]);
+            });
+    }
+
     fn initialize_buffer(&self) {
-        let internal_buffer_size = self.config.assigned_range.len();
+        self.apply_initial_contents(&self.config.initial_contents);
+    }
 
+    fn apply_initial_contents(&self, initial_contents: &StandardMemoryInitialContents) {
         // HACK: This overfills the buffer for ease of programming, but its ok because the actual mmu doesn't allow accesses out at runtime
-        match &self.config.initial_contents {
+        match initial_contents {
             StandardMemoryInitialContents::Value { value } => {
                 self.buffer
                     .par_iter()
@@ -312,32 +364,50 @@ impl StandardMemory {
                     .for_each(|chunk| rand::rng().fill_bytes(chunk.lock().unwrap().as_mut_slice()));
             }
             StandardMemoryInitialContents::Array { value, offset } => {
-                self.write_internal(*offset, value);
+                self.write_internal_parallel(*offset, value);
             }
             StandardMemoryInitialContents::Rom { rom_id, offset } => {
-                let mut rom_file = self
-                    .rom_manager
-                    .open(*rom_id, RomRequirement::Required)
-                    .unwrap();
+                // The machine builder already reported this, leave the memory blank
+                let Some(rom) = self.rom_manager.handle(*rom_id, RomRequirement::Required) else {
+                    return;
+                };
 
-                let mut total_read = 0;
-                let mut buffer = [0; 4096];
-
-                while total_read < internal_buffer_size {
-                    let remaining_space = internal_buffer_size - total_read;
-                    let amount_to_read = remaining_space.min(buffer.len());
-                    let amount = rom_file
-                        .read(&mut buffer[..amount_to_read])
-                        .expect("Could not read rom");
+                // Straight from the mapping, no need to read it in first
+                self.write_internal_parallel(*offset, &rom);
+            }
+            StandardMemoryInitialContents::Pattern { value, range } => {
+                // Ranges entirely outside the memory come out empty
+                let start = range.start.max(self.config.assigned_range.start)
+                    - self.config.assigned_range.start;
+                let end = range
+                    .end
+                    .min(self.config.assigned_range.end)
+                    .saturating_sub(self.config.assigned_range.start);
+
+                if value.is_empty() || start >= end {
+                    return;
+                }
 
-                    if amount == 0 {
-                        break;
-                    }
+                // Pattern position is counted from the start of the range, not the memory
+                let phase = self.config.assigned_range.start + start - range.start;
 
-                    total_read += amount;
+                (start / CHUNK_SIZE..end.div_ceil(CHUNK_SIZE))
+                    .into_par_iter()
+                    .for_each(|chunk_index| {
+                        let chunk_base = chunk_index * CHUNK_SIZE;
+                        let fill_start = start.max(chunk_base);
+                        let fill_end = end.min(chunk_base + CHUNK_SIZE);
+                        let mut chunk = self.buffer[chunk_index].lock().unwrap();
 
-                    let write_size = remaining_space.min(amount);
-                    self.write_internal(*offset + total_read - amount, &buffer[..write_size]);
+                        for position in fill_start..fill_end {
+                            chunk[position - chunk_base] =
+                                value[(phase + position - start) % value.len()];
+                        }
+                    });
+            }
+            StandardMemoryInitialContents::Segments(segments) => {
+                for segment in segments {
+                    self.apply_initial_contents(segment);
                 }
             }
         }
@@ -398,6 +468,83 @@ mod test {
         assert_eq!(buffer, [0xff; 4]);
     }
 
+    #[test]
+    fn segmented_initialization() {
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let machine = Machine::build(GameSystem::Unknown, rom_manager)
+            .insert_bus(ADDRESS_SPACE, 64)
+            .build_component::<StandardMemory>(StandardMemoryConfig {
+                max_word_size: 8,
+                readable: true,
+                writable: true,
+                assigned_range: 0x1000..0x4000,
+                assigned_address_space: ADDRESS_SPACE,
+                initial_contents: StandardMemoryInitialContents::Segments(vec![
+                    StandardMemoryInitialContents::Value { value: 0x11 },
+                    // Straddles a chunk boundary and runs off the end
+                    StandardMemoryInitialContents::Array {
+                        value: Cow::Owned(vec![0x22; 0x2000]),
+                        offset: 0x2ffc,
+                    },
+                ]),
+            })
+            .0
+            .build();
+        let mut buffer = [0; 8];
+
+        machine
+            .memory_translation_table
+            .read(0x2ff8, &mut buffer, ADDRESS_SPACE)
+            .unwrap();
+        assert_eq!(buffer, [0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x22]);
+
+        machine
+            .memory_translation_table
+            .read(0x3ff8, &mut buffer, ADDRESS_SPACE)
+            .unwrap();
+        assert_eq!(buffer, [0x22; 8]);
+    }
+
+    #[test]
+    fn pattern_initialization() {
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let machine = Machine::build(GameSystem::Unknown, rom_manager)
+            .insert_bus(ADDRESS_SPACE, 64)
+            .build_component::<StandardMemory>(StandardMemoryConfig {
+                max_word_size: 8,
+                readable: true,
+                writable: true,
+                assigned_range: 0x100..0x200,
+                assigned_address_space: ADDRESS_SPACE,
+                initial_contents: StandardMemoryInitialContents::Segments(vec![
+                    StandardMemoryInitialContents::Value { value: 0x55 },
+                    // Starts before the memory does, so the first byte we see is the pattern's second
+                    StandardMemoryInitialContents::Pattern {
+                        value: Cow::Borrowed(&[0x00, 0xff, 0xaa]),
+                        range: 0xff..0x105,
+                    },
+                    // Misses the memory on both sides, so it changes nothing
+                    StandardMemoryInitialContents::Pattern {
+                        value: Cow::Borrowed(&[0x12]),
+                        range: 0x00..0x80,
+                    },
+                    StandardMemoryInitialContents::Pattern {
+                        value: Cow::Borrowed(&[0x34]),
+                        range: 0x300..0x400,
+                    },
+                ]),
+            })
+            .0
+            .build();
+        let mut buffer = [0; 8];
+
+        machine
+            .memory_translation_table
+            .read(0x100, &mut buffer, ADDRESS_SPACE)
+            .unwrap();
+        assert_eq!(buffer, [0xff, 0xaa, 0x00, 0xff, 0xaa, 0x55, 0x55, 0x55]);
+    }
+
     #[test]
     fn basic_read() {
         let rom_manager = Arc::new(RomManager::new(None).unwrap());
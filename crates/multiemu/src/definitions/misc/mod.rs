@@ -1,2 +1,8 @@
+pub mod audio;
+pub mod expansion;
+pub mod io;
+pub mod link;
 pub mod memory;
+pub mod noise;
 pub mod processor;
+pub mod video;
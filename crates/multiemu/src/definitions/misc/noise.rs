@@ -0,0 +1,252 @@
+use crate::{
+    component::{
+        memory::MemoryComponent, schedulable::SchedulableComponent, Component, FromConfig,
+    },
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, ReadMemoryRecord, WriteMemoryRecord, VALID_ACCESS_SIZES},
+};
+use num::rational::Ratio;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Fibonacci linear feedback shift register, the noise source nearly every sound chip uses
+///
+/// Shifts right every clock, feeding the parity of the tapped bits back in at the top. Taps of `0b11` on a 15 bit
+/// register is the NES noise channel, for example
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lfsr {
+    width: u8,
+    taps: u32,
+    state: u32,
+}
+
+impl Lfsr {
+    pub fn new(width: u8, taps: u32, seed: u32) -> Self {
+        assert!((1..=32).contains(&width), "Invalid LFSR width {}", width);
+
+        let mut lfsr = Self {
+            width,
+            taps: 0,
+            state: 0,
+        };
+        lfsr.set_taps(taps);
+        lfsr.set_state(seed);
+
+        lfsr
+    }
+
+    fn mask(&self) -> u32 {
+        u32::MAX >> (32 - self.width as u32)
+    }
+
+    /// Shifts once, returning the bit that fell out the bottom
+    pub fn clock(&mut self) -> bool {
+        let output = self.state & 1 != 0;
+        let feedback = (self.state & self.taps).count_ones() & 1;
+
+        self.state = (self.state >> 1) | (feedback << (self.width - 1));
+
+        output
+    }
+
+    /// The bit the next clock will shift out, what most chips send to the speaker
+    pub fn output(&self) -> bool {
+        self.state & 1 != 0
+    }
+
+    pub fn state(&self) -> u32 {
+        self.state
+    }
+
+    /// An all zero register never leaves zero, which real hardware avoids by how it powers on
+    pub fn set_state(&mut self, state: u32) {
+        self.state = state & self.mask();
+    }
+
+    /// For chips with a mode bit that changes which bits feed back, like the NES short noise mode
+    pub fn set_taps(&mut self, taps: u32) {
+        self.taps = taps & self.mask();
+    }
+}
+
+#[derive(Debug)]
+pub struct NoiseGeneratorConfig {
+    /// Bits in the shift register
+    pub width: u8,
+    /// Mask of the bits that feed back
+    pub taps: u32,
+    /// What the register holds after power on and reset
+    pub seed: u32,
+    /// How often the register shifts
+    pub frequency: Ratio<u64>,
+    /// Address of a read only register returning the low byte of the shift register, for systems that expose their
+    /// noise source as a random number generator
+    pub register: Option<(AddressSpaceId, usize)>,
+}
+
+/// A free running [Lfsr], for audio chips to read their noise from and for hardware random number registers
+#[derive(Debug)]
+pub struct NoiseGenerator {
+    config: NoiseGeneratorConfig,
+    lfsr: Mutex<Lfsr>,
+}
+
+impl NoiseGenerator {
+    pub fn output(&self) -> bool {
+        self.lfsr.lock().unwrap().output()
+    }
+
+    pub fn state(&self) -> u32 {
+        self.lfsr.lock().unwrap().state()
+    }
+
+    pub fn set_taps(&self, taps: u32) {
+        self.lfsr.lock().unwrap().set_taps(taps);
+    }
+
+    fn initial_lfsr(config: &NoiseGeneratorConfig) -> Lfsr {
+        Lfsr::new(config.width, config.taps, config.seed)
+    }
+}
+
+impl Component for NoiseGenerator {
+    fn reset(&self) {
+        *self.lfsr.lock().unwrap() = Self::initial_lfsr(&self.config);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(*self.lfsr.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.lfsr.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+    }
+}
+
+impl FromConfig for NoiseGenerator {
+    type Config = NoiseGeneratorConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let frequency = config.frequency;
+        let register = config.register;
+        let lfsr = Self::initial_lfsr(&config);
+
+        component_builder
+            .set_component(Self {
+                config,
+                lfsr: Mutex::new(lfsr),
+            })
+            .set_schedulable(frequency, [], [])
+            .set_memory(
+                register.map(|(address_space, address)| (address_space, address..address + 1)),
+            );
+    }
+}
+
+impl SchedulableComponent for NoiseGenerator {
+    fn run(&self, period: u64) {
+        let mut lfsr = self.lfsr.lock().unwrap();
+
+        for _ in 0..period {
+            lfsr.clock();
+        }
+    }
+}
+
+impl MemoryComponent for NoiseGenerator {
+    fn read_memory(
+        &self,
+        _address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        debug_assert!(
+            VALID_ACCESS_SIZES.contains(&buffer.len()),
+            "Invalid memory access size {}",
+            buffer.len()
+        );
+
+        buffer[0] = self.state() as u8;
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        errors.insert(address..address + buffer.len(), WriteMemoryRecord::Denied);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        machine::Machine,
+        rom::{manager::RomManager, system::GameSystem},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn nes_noise_period() {
+        let mut lfsr = Lfsr::new(15, 0b11, 1);
+        let mut period = 0;
+
+        loop {
+            lfsr.clock();
+            period += 1;
+
+            if lfsr.state() == 1 {
+                break;
+            }
+        }
+        assert_eq!(period, 32767);
+
+        // Short mode taps bit 6 instead of bit 1
+        lfsr.set_taps(0b100_0001);
+        let mut period = 0;
+        loop {
+            lfsr.clock();
+            period += 1;
+
+            if lfsr.state() == 1 {
+                break;
+            }
+        }
+        assert_eq!(period, 93);
+    }
+
+    #[test]
+    fn random_register() {
+        let machine = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 16)
+        .build_component::<NoiseGenerator>(NoiseGeneratorConfig {
+            width: 8,
+            taps: 0b1_1101,
+            seed: 0xff,
+            frequency: Ratio::from_integer(60),
+            register: Some((0, 0x10)),
+        })
+        .0
+        .build();
+        let mut value = [0];
+
+        machine
+            .memory_translation_table
+            .read(0x10, &mut value, 0)
+            .unwrap();
+        assert_eq!(value, [0xff]);
+
+        assert!(machine
+            .memory_translation_table
+            .write(0x10, &[0], 0)
+            .is_err());
+    }
+}
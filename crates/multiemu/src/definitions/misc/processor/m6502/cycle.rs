@@ -0,0 +1,339 @@
+use super::{
+    instruction::{AddressingMode, M6502InstructionSet, M6502InstructionSetSpecifier},
+    FlagRegister, M6502Registers,
+};
+use crate::memory::{AddressSpaceId, MemoryTranslationTable};
+use serde::{Deserialize, Serialize};
+
+// https://www.nesdev.org/6502_cpu.txt
+
+const STACK_PAGE: u16 = 0x0100;
+
+/// What the chip puts on the bus during one cycle of an instruction
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BusCycle {
+    /// Reading the opcode or its operands
+    Fetch(u16),
+    /// Reading a value the instruction actually uses, pointers included
+    Read(u16),
+    Write(u16),
+    /// A read the chip throws away while it works something else out, like fixing up the high byte of an address
+    DummyRead(u16),
+    /// Read-modify-write instructions write the unmodified value back before the real write
+    DummyWrite(u16),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum AccessKind {
+    Read,
+    Write,
+    ReadModifyWrite,
+}
+
+impl M6502InstructionSetSpecifier {
+    fn access_kind(&self) -> AccessKind {
+        match self {
+            Self::Sta
+            | Self::Stx
+            | Self::Sty
+            | Self::Sax
+            | Self::Sha
+            | Self::Shs
+            | Self::Shx
+            | Self::Shy => AccessKind::Write,
+            Self::Asl
+            | Self::Lsr
+            | Self::Rol
+            | Self::Ror
+            | Self::Inc
+            | Self::Dec
+            | Self::Slo
+            | Self::Sre
+            | Self::Rla
+            | Self::Rra
+            | Self::Dcp
+            | Self::Isc => AccessKind::ReadModifyWrite,
+            _ => AccessKind::Read,
+        }
+    }
+
+    pub(super) fn branch_taken(&self, registers: &M6502Registers) -> bool {
+        let flags = registers.flags;
+
+        match self {
+            Self::Bcc => !flags.contains(FlagRegister::Carry),
+            Self::Bcs => flags.contains(FlagRegister::Carry),
+            Self::Bne => !flags.contains(FlagRegister::Zero),
+            Self::Beq => flags.contains(FlagRegister::Zero),
+            Self::Bpl => !flags.contains(FlagRegister::Negative),
+            Self::Bmi => flags.contains(FlagRegister::Negative),
+            Self::Bvc => !flags.contains(FlagRegister::Overflow),
+            Self::Bvs => flags.contains(FlagRegister::Overflow),
+            _ => false,
+        }
+    }
+}
+
+/// Every bus cycle the instruction at the program counter goes through, in order
+///
+/// Must be called before the instruction executes, since branches and indexing depend on the registers
+pub fn bus_cycles(
+    instruction: &M6502InstructionSet,
+    registers: &M6502Registers,
+    memory_translation_table: &MemoryTranslationTable,
+    address_space: AddressSpaceId,
+) -> Vec<BusCycle> {
+    let program = registers.program;
+    let stack = |offset: u8| STACK_PAGE | registers.stack_pointer.wrapping_add(offset) as u16;
+    let access_kind = instruction.specifier.access_kind();
+    let [x, y] = registers.index_registers;
+
+    let mut cycles = vec![BusCycle::Fetch(program)];
+
+    // Control flow instructions have their own sequences
+    match (instruction.specifier, instruction.addressing_mode) {
+        (M6502InstructionSetSpecifier::Jmp, Some(AddressingMode::Absolute(_))) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::Fetch(program.wrapping_add(2)),
+            ]);
+            return cycles;
+        }
+        (M6502InstructionSetSpecifier::Jsr, _) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::DummyRead(stack(0)),
+                BusCycle::Write(stack(0)),
+                BusCycle::Write(stack(0xff)),
+                BusCycle::Fetch(program.wrapping_add(2)),
+            ]);
+            return cycles;
+        }
+        (M6502InstructionSetSpecifier::Brk, _) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::Write(stack(0)),
+                BusCycle::Write(stack(0xff)),
+                BusCycle::Write(stack(0xfe)),
+                BusCycle::Read(0xfffe),
+                BusCycle::Read(0xffff),
+            ]);
+            return cycles;
+        }
+        (M6502InstructionSetSpecifier::Rts, _) => {
+            let mut return_address = [0; 2];
+            let _ = memory_translation_table.preview(
+                stack(1) as usize,
+                &mut return_address[..1],
+                address_space,
+            );
+            let _ = memory_translation_table.preview(
+                stack(2) as usize,
+                &mut return_address[1..],
+                address_space,
+            );
+
+            cycles.extend([
+                BusCycle::DummyRead(program.wrapping_add(1)),
+                BusCycle::DummyRead(stack(0)),
+                BusCycle::Read(stack(1)),
+                BusCycle::Read(stack(2)),
+                BusCycle::DummyRead(u16::from_le_bytes(return_address)),
+            ]);
+            return cycles;
+        }
+        (M6502InstructionSetSpecifier::Rti, _) => {
+            cycles.extend([
+                BusCycle::DummyRead(program.wrapping_add(1)),
+                BusCycle::DummyRead(stack(0)),
+                BusCycle::Read(stack(1)),
+                BusCycle::Read(stack(2)),
+                BusCycle::Read(stack(3)),
+            ]);
+            return cycles;
+        }
+        (M6502InstructionSetSpecifier::Pha | M6502InstructionSetSpecifier::Php, _) => {
+            cycles.extend([
+                BusCycle::DummyRead(program.wrapping_add(1)),
+                BusCycle::Write(stack(0)),
+            ]);
+            return cycles;
+        }
+        (M6502InstructionSetSpecifier::Pla | M6502InstructionSetSpecifier::Plp, _) => {
+            cycles.extend([
+                BusCycle::DummyRead(program.wrapping_add(1)),
+                BusCycle::DummyRead(stack(0)),
+                BusCycle::Read(stack(1)),
+            ]);
+            return cycles;
+        }
+        _ => {}
+    }
+
+    let effective_access = |cycles: &mut Vec<BusCycle>, address: u16| match access_kind {
+        AccessKind::Read => cycles.push(BusCycle::Read(address)),
+        AccessKind::Write => cycles.push(BusCycle::Write(address)),
+        AccessKind::ReadModifyWrite => cycles.extend([
+            BusCycle::Read(address),
+            BusCycle::DummyWrite(address),
+            BusCycle::Write(address),
+        ]),
+    };
+
+    // The chip adds the index to the low byte first and reads from there, fixing the high byte a cycle later
+    let indexed_access = |cycles: &mut Vec<BusCycle>, base: u16, index: u8| {
+        let address = base.wrapping_add(index as u16);
+        let unfixed_address = (base & 0xff00) | (address & 0x00ff);
+
+        // Reads skip the extra cycle if the page didn't change, anything that writes always takes it
+        if access_kind != AccessKind::Read || unfixed_address != address {
+            cycles.push(BusCycle::DummyRead(unfixed_address));
+        }
+
+        effective_access(cycles, address);
+    };
+
+    match instruction.addressing_mode {
+        None | Some(AddressingMode::Accumulator) => {
+            cycles.push(BusCycle::DummyRead(program.wrapping_add(1)));
+        }
+        Some(AddressingMode::Immediate(_)) => {
+            cycles.push(BusCycle::Fetch(program.wrapping_add(1)));
+        }
+        Some(AddressingMode::Relative(offset)) => {
+            cycles.push(BusCycle::Fetch(program.wrapping_add(1)));
+
+            if instruction.specifier.branch_taken(registers) {
+                let next = program.wrapping_add(2);
+                let target = next.wrapping_add_signed(offset as i16);
+
+                cycles.push(BusCycle::DummyRead(next));
+
+                if next & 0xff00 != target & 0xff00 {
+                    cycles.push(BusCycle::DummyRead((next & 0xff00) | (target & 0x00ff)));
+                }
+            }
+        }
+        Some(AddressingMode::ZeroPage(address)) => {
+            cycles.push(BusCycle::Fetch(program.wrapping_add(1)));
+            effective_access(&mut cycles, address as u16);
+        }
+        Some(AddressingMode::XIndexedZeroPage(address)) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::DummyRead(address as u16),
+            ]);
+            effective_access(&mut cycles, address.wrapping_add(x) as u16);
+        }
+        Some(AddressingMode::YIndexedZeroPage(address))
+        | Some(AddressingMode::ZeroPageYIndexed(address)) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::DummyRead(address as u16),
+            ]);
+            effective_access(&mut cycles, address.wrapping_add(y) as u16);
+        }
+        Some(AddressingMode::Absolute(address)) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::Fetch(program.wrapping_add(2)),
+            ]);
+            effective_access(&mut cycles, address);
+        }
+        Some(AddressingMode::XIndexedAbsolute(address)) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::Fetch(program.wrapping_add(2)),
+            ]);
+            indexed_access(&mut cycles, address, x);
+        }
+        Some(AddressingMode::YIndexedAbsolute(address)) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::Fetch(program.wrapping_add(2)),
+            ]);
+            indexed_access(&mut cycles, address, y);
+        }
+        Some(AddressingMode::AbsoluteIndirect(address)) => {
+            // The pointer never crosses a page, the famous JMP bug
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::Fetch(program.wrapping_add(2)),
+                BusCycle::Read(address),
+                BusCycle::Read((address & 0xff00) | (address.wrapping_add(1) & 0x00ff)),
+            ]);
+        }
+        Some(AddressingMode::XIndexedZeroPageIndirect(address)) => {
+            let pointer = address.wrapping_add(x);
+
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::DummyRead(address as u16),
+                BusCycle::Read(pointer as u16),
+                BusCycle::Read(pointer.wrapping_add(1) as u16),
+            ]);
+            effective_access(
+                &mut cycles,
+                read_zero_page_pointer(memory_translation_table, address_space, pointer),
+            );
+        }
+        Some(AddressingMode::ZeroPageIndirectYIndexed(address)) => {
+            cycles.extend([
+                BusCycle::Fetch(program.wrapping_add(1)),
+                BusCycle::Read(address as u16),
+                BusCycle::Read(address.wrapping_add(1) as u16),
+            ]);
+            indexed_access(
+                &mut cycles,
+                read_zero_page_pointer(memory_translation_table, address_space, address),
+                y,
+            );
+        }
+    }
+
+    cycles
+}
+
+/// Pointers in the zero page wrap around inside it
+fn read_zero_page_pointer(
+    memory_translation_table: &MemoryTranslationTable,
+    address_space: AddressSpaceId,
+    address: u8,
+) -> u16 {
+    let mut pointer = [0; 2];
+
+    let _ = memory_translation_table.preview(address as usize, &mut pointer[..1], address_space);
+    let _ = memory_translation_table.preview(
+        address.wrapping_add(1) as usize,
+        &mut pointer[1..],
+        address_space,
+    );
+
+    u16::from_le_bytes(pointer)
+}
+
+/// The seven cycles of taking an interrupt, which look like a brk that never fetched its opcode
+///
+/// Reset goes through the same motions with the pushes turned into reads
+pub fn interrupt_bus_cycles(registers: &M6502Registers, vector: u16, reset: bool) -> Vec<BusCycle> {
+    let program = registers.program;
+    let push = |offset: u8| {
+        let address = STACK_PAGE | registers.stack_pointer.wrapping_sub(offset) as u16;
+
+        if reset {
+            BusCycle::DummyRead(address)
+        } else {
+            BusCycle::Write(address)
+        }
+    };
+
+    vec![
+        BusCycle::DummyRead(program),
+        BusCycle::DummyRead(program),
+        push(0),
+        push(1),
+        push(2),
+        BusCycle::Read(vector),
+        BusCycle::Read(vector.wrapping_add(1)),
+    ]
+}
@@ -1,17 +1,12 @@
 use crate::memory::{AddressSpaceId, MemoryTranslationTable};
 
-use super::{
-    instruction::{AddressingMode, M6502InstructionSet, M6502InstructionSetSpecifier},
-    M6502,
-};
-use bitvec::{
-    field::BitField,
-    prelude::{BitSlice, Msb0},
-    view::BitView,
-};
+use super::instruction::{AddressingMode, M6502InstructionSet, M6502InstructionSetSpecifier};
+use bitvec::{field::BitField, prelude::Msb0, view::BitView};
 use std::ops::Range;
 use strum::FromRepr;
 
+// https://www.masswerk.at/6502/6502_instruction_set.html#layout
+
 const INSTRUCTION_IDENTIFIER: Range<usize> = 6..8;
 const SECONDARY_INSTRUCTION_IDENTIFIER: Range<usize> = 0..3;
 const ARGUMENT: Range<usize> = 3..6;
@@ -25,184 +20,330 @@ enum InstructionGroup {
     Undocumented = 0b11,
 }
 
+/// What follows the opcode, before the operand bytes are read
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operand {
+    Implied,
+    Accumulator,
+    Immediate,
+    Absolute,
+    XIndexedAbsolute,
+    YIndexedAbsolute,
+    AbsoluteIndirect,
+    ZeroPage,
+    XIndexedZeroPage,
+    YIndexedZeroPage,
+    XIndexedZeroPageIndirect,
+    ZeroPageIndirectYIndexed,
+    Relative,
+}
+
+impl Operand {
+    /// The modes the middle three bits pick in the 01 and 11 columns
+    fn from_group1_addressing(addressing_mode: u8) -> Self {
+        match addressing_mode {
+            0b000 => Operand::XIndexedZeroPageIndirect,
+            0b001 => Operand::ZeroPage,
+            0b010 => Operand::Immediate,
+            0b011 => Operand::Absolute,
+            0b100 => Operand::ZeroPageIndirectYIndexed,
+            0b101 => Operand::XIndexedZeroPage,
+            0b110 => Operand::YIndexedAbsolute,
+            0b111 => Operand::XIndexedAbsolute,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Reads the operand bytes, only touching as many as the mode has
+    fn read(self, read_u8: impl Fn(u16) -> u8) -> (Option<AddressingMode>, u8) {
+        let byte = || read_u8(1);
+        let word = || u16::from_le_bytes([read_u8(1), read_u8(2)]);
+
+        match self {
+            Operand::Implied => (None, 0),
+            Operand::Accumulator => (Some(AddressingMode::Accumulator), 0),
+            Operand::Immediate => (Some(AddressingMode::Immediate(byte())), 1),
+            Operand::Absolute => (Some(AddressingMode::Absolute(word())), 2),
+            Operand::XIndexedAbsolute => (Some(AddressingMode::XIndexedAbsolute(word())), 2),
+            Operand::YIndexedAbsolute => (Some(AddressingMode::YIndexedAbsolute(word())), 2),
+            Operand::AbsoluteIndirect => (Some(AddressingMode::AbsoluteIndirect(word())), 2),
+            Operand::ZeroPage => (Some(AddressingMode::ZeroPage(byte())), 1),
+            Operand::XIndexedZeroPage => (Some(AddressingMode::XIndexedZeroPage(byte())), 1),
+            Operand::YIndexedZeroPage => (Some(AddressingMode::YIndexedZeroPage(byte())), 1),
+            Operand::XIndexedZeroPageIndirect => {
+                (Some(AddressingMode::XIndexedZeroPageIndirect(byte())), 1)
+            }
+            Operand::ZeroPageIndirectYIndexed => {
+                (Some(AddressingMode::ZeroPageIndirectYIndexed(byte())), 1)
+            }
+            Operand::Relative => (Some(AddressingMode::Relative(byte() as i8)), 1),
+        }
+    }
+}
+
 pub fn decode_instruction(
     cursor: u16,
     address_space: AddressSpaceId,
     memory_translation_table: &MemoryTranslationTable,
 ) -> Result<(M6502InstructionSet, u8), Box<dyn std::error::Error>> {
-    let mut instruction_first_byte = [0];
-    let _ =
-        memory_translation_table.read(cursor as usize, &mut instruction_first_byte, address_space);
-    let instruction_first_byte = u8::from_ne_bytes(instruction_first_byte);
+    let read_u8 = |offset: u16| {
+        let mut value = [0];
+        let _ = memory_translation_table.read(
+            cursor.wrapping_add(offset) as usize,
+            &mut value,
+            address_space,
+        );
+        value[0]
+    };
 
+    let instruction_first_byte = read_u8(0);
     let instruction_first_byte = instruction_first_byte.view_bits::<Msb0>();
     let instruction_identifier =
         InstructionGroup::from_repr(instruction_first_byte[INSTRUCTION_IDENTIFIER].load::<u8>())
             .unwrap();
     let secondary_instruction_identifier =
         instruction_first_byte[SECONDARY_INSTRUCTION_IDENTIFIER].load::<u8>();
-
-    match instruction_identifier {
-        InstructionGroup::Group3 => decode_group3_space_instruction(
-            cursor,
-            memory_translation_table,
-            secondary_instruction_identifier,
-            instruction_first_byte,
-        ),
-        InstructionGroup::Group1 => decode_group1_space_instruction(
-            cursor,
-            address_space,
-            memory_translation_table,
-            secondary_instruction_identifier,
-            instruction_first_byte,
-        ),
-        InstructionGroup::Group2 => decode_group2_space_instruction(
-            cursor,
-            memory_translation_table,
-            secondary_instruction_identifier,
-            instruction_first_byte,
-        ),
-        InstructionGroup::Undocumented => decode_undocumented_space_instruction(
-            cursor,
-            memory_translation_table,
-            secondary_instruction_identifier,
-            instruction_first_byte,
-        ),
-        _ => {
-            unreachable!()
-        }
-    }
-}
-
-pub fn decode_group1_space_instruction(
-    cursor: u16,
-    address_space: AddressSpaceId,
-    memory_translation_table: &MemoryTranslationTable,
-    instruction_identifier: u8,
-    instruction_first_byte: &BitSlice<u8, Msb0>,
-) -> Result<(M6502InstructionSet, u8), Box<dyn std::error::Error>> {
     let addressing_mode = instruction_first_byte[ARGUMENT].load::<u8>();
 
-    match instruction_identifier {
-        0b000 => {
-            let (addressing_mode, added_instruction_length) =
-                AddressingMode::from_group1_addressing(
-                    cursor,
-                    address_space,
-                    memory_translation_table,
-                    addressing_mode,
-                );
-
-            Ok((
-                M6502InstructionSet {
-                    specifier: M6502InstructionSetSpecifier::Ora,
-                    addressing_mode: Some(addressing_mode),
-                },
-                1 + added_instruction_length,
-            ))
+    let (specifier, operand) = match instruction_identifier {
+        InstructionGroup::Group3 => {
+            decode_group3_space_instruction(secondary_instruction_identifier, addressing_mode)
         }
-        0b001 => {
-            todo!()
+        InstructionGroup::Group1 => {
+            decode_group1_space_instruction(secondary_instruction_identifier, addressing_mode)
         }
-        0b010 => {
-            todo!()
+        InstructionGroup::Group2 => {
+            decode_group2_space_instruction(secondary_instruction_identifier, addressing_mode)
         }
-        0b011 => {
-            todo!()
+        InstructionGroup::Undocumented => {
+            decode_undocumented_space_instruction(secondary_instruction_identifier, addressing_mode)
         }
-        0b100 => {
-            todo!()
-        }
-        0b101 => {
-            todo!()
-        }
-        0b110 => {
-            todo!()
-        }
-        0b111 => {
-            todo!()
-        }
-        _ => {
-            unreachable!()
-        }
-    }
+    };
+    let (addressing_mode, operand_length) = operand.read(read_u8);
+
+    Ok((
+        M6502InstructionSet {
+            specifier,
+            addressing_mode,
+        },
+        1 + operand_length,
+    ))
 }
 
-#[inline]
-pub fn decode_group2_space_instruction(
-    cursor: u16,
-    memory_translation_table: &MemoryTranslationTable,
+/// The arithmetic and load/store column, where every instruction takes the same eight modes
+fn decode_group1_space_instruction(
     instruction_identifier: u8,
-    instruction_first_byte: &BitSlice<u8, Msb0>,
-) -> Result<(M6502InstructionSet, u8), Box<dyn std::error::Error>> {
-    todo!()
+    addressing_mode: u8,
+) -> (M6502InstructionSetSpecifier, Operand) {
+    let operand = Operand::from_group1_addressing(addressing_mode);
+
+    let specifier = match instruction_identifier {
+        0b000 => M6502InstructionSetSpecifier::Ora,
+        0b001 => M6502InstructionSetSpecifier::And,
+        0b010 => M6502InstructionSetSpecifier::Eor,
+        0b011 => M6502InstructionSetSpecifier::Adc,
+        // Storing to an immediate makes no sense, so the chip just skips the byte
+        0b100 if operand == Operand::Immediate => M6502InstructionSetSpecifier::Nop,
+        0b100 => M6502InstructionSetSpecifier::Sta,
+        0b101 => M6502InstructionSetSpecifier::Lda,
+        0b110 => M6502InstructionSetSpecifier::Cmp,
+        0b111 => M6502InstructionSetSpecifier::Sbc,
+        _ => unreachable!(),
+    };
+
+    (specifier, operand)
 }
 
-#[inline]
-pub fn decode_undocumented_space_instruction(
-    cursor: u16,
-    memory_translation_table: &MemoryTranslationTable,
+/// The shift and x register column
+fn decode_group2_space_instruction(
     instruction_identifier: u8,
-    instruction_first_byte: &BitSlice<u8, Msb0>,
-) -> Result<(M6502InstructionSet, u8), Box<dyn std::error::Error>> {
-    match instruction_identifier {
-        0b000 => {
-            todo!()
-        }
-        0b001 => {
-            todo!()
-        }
-        0b010 => {
-            todo!()
-        }
-        0b011 => {
-            todo!()
-        }
-        0b100 => {
-            todo!()
+    addressing_mode: u8,
+) -> (M6502InstructionSetSpecifier, Operand) {
+    // stx and ldx index with y where everything else would use x
+    let uses_y = matches!(instruction_identifier, 0b100 | 0b101);
+
+    let operand = match addressing_mode {
+        0b000 => Operand::Immediate,
+        0b001 => Operand::ZeroPage,
+        0b010 if instruction_identifier < 0b100 => Operand::Accumulator,
+        0b011 => Operand::Absolute,
+        0b101 if uses_y => Operand::YIndexedZeroPage,
+        0b101 => Operand::XIndexedZeroPage,
+        0b111 if uses_y => Operand::YIndexedAbsolute,
+        0b111 => Operand::XIndexedAbsolute,
+        _ => Operand::Implied,
+    };
+
+    let specifier = match (addressing_mode, instruction_identifier) {
+        // The x2 column locks up the chip, except where it reads an immediate and ignores it
+        (0b000, 0b101) => M6502InstructionSetSpecifier::Ldx,
+        (0b000, 0b100 | 0b110 | 0b111) => M6502InstructionSetSpecifier::Nop,
+        (0b000, _) | (0b100, _) => {
+            return (M6502InstructionSetSpecifier::Jam, Operand::Implied);
         }
-        0b101 => {
-            todo!()
+        (0b010, 0b100) => M6502InstructionSetSpecifier::Txa,
+        (0b010, 0b101) => M6502InstructionSetSpecifier::Tax,
+        (0b010, 0b110) => M6502InstructionSetSpecifier::Dex,
+        (0b010, 0b111) => M6502InstructionSetSpecifier::Nop,
+        (0b110, 0b100) => M6502InstructionSetSpecifier::Txs,
+        (0b110, 0b101) => M6502InstructionSetSpecifier::Tsx,
+        (0b110, _) => M6502InstructionSetSpecifier::Nop,
+        (0b111, 0b100) => M6502InstructionSetSpecifier::Shx,
+        (_, 0b000) => M6502InstructionSetSpecifier::Asl,
+        (_, 0b001) => M6502InstructionSetSpecifier::Rol,
+        (_, 0b010) => M6502InstructionSetSpecifier::Lsr,
+        (_, 0b011) => M6502InstructionSetSpecifier::Ror,
+        (_, 0b100) => M6502InstructionSetSpecifier::Stx,
+        (_, 0b101) => M6502InstructionSetSpecifier::Ldx,
+        (_, 0b110) => M6502InstructionSetSpecifier::Dec,
+        (_, 0b111) => M6502InstructionSetSpecifier::Inc,
+        _ => unreachable!(),
+    };
+
+    (specifier, operand)
+}
+
+/// What the chip does when both the 01 and 10 columns decode at once, see [decode_group1_space_instruction] and
+/// [decode_group2_space_instruction]
+fn decode_undocumented_space_instruction(
+    instruction_identifier: u8,
+    addressing_mode: u8,
+) -> (M6502InstructionSetSpecifier, Operand) {
+    let operand = Operand::from_group1_addressing(addressing_mode);
+
+    // The immediate row has its own mix of instructions
+    if operand == Operand::Immediate {
+        return (
+            match instruction_identifier {
+                0b000 | 0b001 => M6502InstructionSetSpecifier::Anc,
+                0b010 => M6502InstructionSetSpecifier::Asr,
+                0b011 => M6502InstructionSetSpecifier::Arr,
+                0b100 => M6502InstructionSetSpecifier::Xaa,
+                0b101 => M6502InstructionSetSpecifier::Lax,
+                0b110 => M6502InstructionSetSpecifier::Sbx,
+                0b111 => M6502InstructionSetSpecifier::Sbc,
+                _ => unreachable!(),
+            },
+            operand,
+        );
+    }
+
+    match (instruction_identifier, operand) {
+        (0b100, Operand::ZeroPageIndirectYIndexed) => (M6502InstructionSetSpecifier::Sha, operand),
+        (0b100, Operand::XIndexedAbsolute) => {
+            (M6502InstructionSetSpecifier::Sha, Operand::YIndexedAbsolute)
         }
-        0b110 => {
-            todo!()
+        (0b100, Operand::YIndexedAbsolute) => (M6502InstructionSetSpecifier::Shs, operand),
+        (0b100, Operand::XIndexedZeroPage) => {
+            (M6502InstructionSetSpecifier::Sax, Operand::YIndexedZeroPage)
         }
-        0b111 => {
-            todo!()
+        (0b101, Operand::YIndexedAbsolute) => (M6502InstructionSetSpecifier::Las, operand),
+        (0b101, Operand::XIndexedAbsolute) => {
+            (M6502InstructionSetSpecifier::Lax, Operand::YIndexedAbsolute)
         }
-        _ => {
-            unreachable!()
+        (0b101, Operand::XIndexedZeroPage) => {
+            (M6502InstructionSetSpecifier::Lax, Operand::YIndexedZeroPage)
         }
+        _ => (
+            match instruction_identifier {
+                0b000 => M6502InstructionSetSpecifier::Slo,
+                0b001 => M6502InstructionSetSpecifier::Rla,
+                0b010 => M6502InstructionSetSpecifier::Sre,
+                0b011 => M6502InstructionSetSpecifier::Rra,
+                0b100 => M6502InstructionSetSpecifier::Sax,
+                0b101 => M6502InstructionSetSpecifier::Lax,
+                0b110 => M6502InstructionSetSpecifier::Dcp,
+                0b111 => M6502InstructionSetSpecifier::Isc,
+                _ => unreachable!(),
+            },
+            operand,
+        ),
     }
 }
 
+/// The control flow and y register column
 fn decode_group3_space_instruction(
-    cursor: u16,
-    memory_translation_table: &MemoryTranslationTable,
     instruction_identifier: u8,
-    instruction_first_byte: &BitSlice<u8, Msb0>,
-) -> Result<(M6502InstructionSet, u8), Box<dyn std::error::Error>> {
-    let addressing_mode = instruction_first_byte[ARGUMENT].load::<u8>();
-
-    match instruction_identifier {
-        0b000 => Ok((
-            M6502InstructionSet {
-                specifier: M6502InstructionSetSpecifier::Brk,
-                addressing_mode: None,
+    addressing_mode: u8,
+) -> (M6502InstructionSetSpecifier, Operand) {
+    match addressing_mode {
+        0b000 => match instruction_identifier {
+            0b000 => (M6502InstructionSetSpecifier::Brk, Operand::Implied),
+            0b001 => (M6502InstructionSetSpecifier::Jsr, Operand::Absolute),
+            0b010 => (M6502InstructionSetSpecifier::Rti, Operand::Implied),
+            0b011 => (M6502InstructionSetSpecifier::Rts, Operand::Implied),
+            0b100 => (M6502InstructionSetSpecifier::Nop, Operand::Immediate),
+            0b101 => (M6502InstructionSetSpecifier::Ldy, Operand::Immediate),
+            0b110 => (M6502InstructionSetSpecifier::Cpy, Operand::Immediate),
+            0b111 => (M6502InstructionSetSpecifier::Cpx, Operand::Immediate),
+            _ => unreachable!(),
+        },
+        0b010 => (
+            match instruction_identifier {
+                0b000 => M6502InstructionSetSpecifier::Php,
+                0b001 => M6502InstructionSetSpecifier::Plp,
+                0b010 => M6502InstructionSetSpecifier::Pha,
+                0b011 => M6502InstructionSetSpecifier::Pla,
+                0b100 => M6502InstructionSetSpecifier::Dey,
+                0b101 => M6502InstructionSetSpecifier::Tay,
+                0b110 => M6502InstructionSetSpecifier::Iny,
+                0b111 => M6502InstructionSetSpecifier::Inx,
+                _ => unreachable!(),
             },
-            1,
-        )),
-        0b001 => {
-            todo!()
-        }
-        0b010 => todo!(),
-        0b011 => todo!(),
-        0b100 => todo!(),
-        0b101 => todo!(),
-        0b110 => todo!(),
-        0b111 => todo!(),
+            Operand::Implied,
+        ),
+        0b100 => (
+            match instruction_identifier {
+                0b000 => M6502InstructionSetSpecifier::Bpl,
+                0b001 => M6502InstructionSetSpecifier::Bmi,
+                0b010 => M6502InstructionSetSpecifier::Bvc,
+                0b011 => M6502InstructionSetSpecifier::Bvs,
+                0b100 => M6502InstructionSetSpecifier::Bcc,
+                0b101 => M6502InstructionSetSpecifier::Bcs,
+                0b110 => M6502InstructionSetSpecifier::Bne,
+                0b111 => M6502InstructionSetSpecifier::Beq,
+                _ => unreachable!(),
+            },
+            Operand::Relative,
+        ),
+        0b110 => (
+            match instruction_identifier {
+                0b000 => M6502InstructionSetSpecifier::Clc,
+                0b001 => M6502InstructionSetSpecifier::Sec,
+                0b010 => M6502InstructionSetSpecifier::Cli,
+                0b011 => M6502InstructionSetSpecifier::Sei,
+                0b100 => M6502InstructionSetSpecifier::Tya,
+                0b101 => M6502InstructionSetSpecifier::Clv,
+                0b110 => M6502InstructionSetSpecifier::Cld,
+                0b111 => M6502InstructionSetSpecifier::Sed,
+                _ => unreachable!(),
+            },
+            Operand::Implied,
+        ),
         _ => {
-            unreachable!()
+            let operand = match addressing_mode {
+                0b001 => Operand::ZeroPage,
+                0b011 => Operand::Absolute,
+                0b101 => Operand::XIndexedZeroPage,
+                _ => Operand::XIndexedAbsolute,
+            };
+
+            let specifier = match (instruction_identifier, operand) {
+                (0b001, Operand::ZeroPage | Operand::Absolute) => M6502InstructionSetSpecifier::Bit,
+                (0b010, Operand::Absolute) => M6502InstructionSetSpecifier::Jmp,
+                (0b011, Operand::Absolute) => {
+                    return (M6502InstructionSetSpecifier::Jmp, Operand::AbsoluteIndirect);
+                }
+                (0b100, Operand::XIndexedAbsolute) => M6502InstructionSetSpecifier::Shy,
+                (0b100, _) => M6502InstructionSetSpecifier::Sty,
+                (0b101, _) => M6502InstructionSetSpecifier::Ldy,
+                (0b110, Operand::ZeroPage | Operand::Absolute) => M6502InstructionSetSpecifier::Cpy,
+                (0b111, Operand::ZeroPage | Operand::Absolute) => M6502InstructionSetSpecifier::Cpx,
+                // Everything else in this column reads its operand and does nothing with it
+                _ => M6502InstructionSetSpecifier::Nop,
+            };
+
+            (specifier, operand)
         }
     }
 }
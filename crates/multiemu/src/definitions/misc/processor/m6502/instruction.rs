@@ -1,12 +1,10 @@
-use crate::{
-    memory::{AddressSpaceId, MemoryTranslationTable},
-    processor::{InstructionSet, InstructionTextRepresentation},
-};
+use crate::processor::{InstructionSet, InstructionTextRepresentation};
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
 // https://www.pagetable.com/c64ref/6502/?tab=2
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AddressingMode {
     Accumulator,
     Immediate(u8),
@@ -23,35 +21,7 @@ pub enum AddressingMode {
     Relative(i8),
 }
 
-impl AddressingMode {
-    pub fn from_group1_addressing(
-        cursor: u16,
-        address_space: AddressSpaceId,
-        memory_translation_table: &MemoryTranslationTable,
-        addressing_mode: u8,
-    ) -> (Self, u8) {
-        match addressing_mode {
-            0b000 => {
-                let mut indirect_byte = [0];
-                let _ = memory_translation_table.read(
-                    cursor.wrapping_add(1) as usize,
-                    &mut indirect_byte,
-                    address_space,
-                );
-
-                (
-                    AddressingMode::XIndexedZeroPageIndirect(u8::from_ne_bytes(indirect_byte)),
-                    1,
-                )
-            }
-            _ => {
-                unreachable!()
-            }
-        }
-    }
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum M6502InstructionSetSpecifier {
     Adc,
     Anc,
@@ -130,7 +100,37 @@ pub enum M6502InstructionSetSpecifier {
     Xaa,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+impl M6502InstructionSetSpecifier {
+    /// Instructions that fall out of the decoder by accident rather than being in the datasheet
+    ///
+    /// The undocumented NOPs decode to [M6502InstructionSetSpecifier::Nop] and are left out, they are harmless either way
+    pub fn is_undocumented(&self) -> bool {
+        matches!(
+            self,
+            Self::Anc
+                | Self::Arr
+                | Self::Asr
+                | Self::Dcp
+                | Self::Isc
+                | Self::Jam
+                | Self::Las
+                | Self::Lax
+                | Self::Rla
+                | Self::Rra
+                | Self::Sax
+                | Self::Sbx
+                | Self::Sha
+                | Self::Shs
+                | Self::Shx
+                | Self::Shy
+                | Self::Slo
+                | Self::Sre
+                | Self::Xaa
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct M6502InstructionSet {
     pub specifier: M6502InstructionSetSpecifier,
     pub addressing_mode: Option<AddressingMode>,
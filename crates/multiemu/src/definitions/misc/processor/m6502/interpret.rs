@@ -1,343 +1,397 @@
 use super::{
-    instruction::{M6502InstructionSet, M6502InstructionSetSpecifier},
-    FlagRegister, ProcessorState, M6502,
+    instruction::{AddressingMode, M6502InstructionSet, M6502InstructionSetSpecifier},
+    FlagRegister, M6502Kind, M6502Registers, ProcessorState, UndocumentedOpcodes, IRQ_VECTOR,
+    M6502,
 };
-use crate::definitions::misc::processor::m6502::instruction::AddressingMode;
+use crate::memory::ReadModifyWriteBehavior;
 use bitvec::{order::Lsb0, view::BitView};
-use enumflags2::BitFlag;
+use enumflags2::BitFlags;
 
 // NOTE: The M6502 should ignore all memory errors
 
-macro_rules! load_m6502_addressing_modes {
-    ($instruction:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr, [$($modes:ident),*]) => {{
-        match $instruction.addressing_mode {
-            $(
-                Some(AddressingMode::$modes(argument)) => {
-                    load_m6502_addressing_modes!(@handler $modes, argument, $register_store, $memory_translation_table, $assigned_address_space)
-                },
-            )*
-            _ => unreachable!(),
-        }
-    }};
-
-    (@handler Immediate, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        $argument
-    }};
-
-    (@handler Absolute, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        let mut value = 0;
-
-        let _ = $memory_translation_table
-            .read($argument as usize, bytemuck::bytes_of_mut(&mut value), $assigned_address_space);
-
-        value
-    }};
-
-    (@handler XIndexedAbsolute, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        let mut value = 0;
+const STACK_PAGE: u16 = 0x0100;
 
-        let _ = $memory_translation_table
-            .read($argument as usize, &mut [0], $assigned_address_space);
-
-
-        let actual_address = $argument.wrapping_add($register_store.index_registers[0] as u16);
-        let _ = $memory_translation_table
-            .read(actual_address as usize, bytemuck::bytes_of_mut(&mut value), $assigned_address_space);
-
-        value
-    }};
-
-    (@handler YIndexedAbsolute, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        let mut value: u8 = 0;
+fn set_negative_zero(flags: &mut BitFlags<FlagRegister>, value: u8) {
+    flags.set(FlagRegister::Negative, value.view_bits::<Lsb0>()[7]);
+    flags.set(FlagRegister::Zero, value == 0);
+}
 
-        let _ = $memory_translation_table
-            .read($argument as usize, &mut [0], $assigned_address_space);
+/// cmp, cpx and cpy, a subtraction that only keeps the flags
+fn compare(flags: &mut BitFlags<FlagRegister>, register: u8, value: u8) {
+    flags.set(FlagRegister::Carry, register >= value);
+    set_negative_zero(flags, register.wrapping_sub(value));
+}
 
+impl M6502 {
+    fn read(&self, address: u16) -> u8 {
+        let mut value = [0];
+        let _ = self.memory_translation_table.get().unwrap().read(
+            address as usize,
+            &mut value,
+            self.config.assigned_address_space,
+        );
+
+        value[0]
+    }
 
-        let actual_address = $argument.wrapping_add($register_store.index_registers[1] as u16);
-        let _ = $memory_translation_table
-            .read(actual_address as usize, bytemuck::bytes_of_mut(&mut value), $assigned_address_space);
+    fn write(&self, address: u16, value: u8) {
+        let _ = self.memory_translation_table.get().unwrap().write(
+            address as usize,
+            &[value],
+            self.config.assigned_address_space,
+        );
+    }
 
-        value
-    }};
+    pub(super) fn read_u16(&self, address: u16) -> u16 {
+        u16::from_le_bytes([self.read(address), self.read(address.wrapping_add(1))])
+    }
 
-    (@handler ZeroPage, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        let mut value: u8 = 0;
+    /// Pointers in the zero page wrap around inside it
+    fn read_zero_page_pointer(&self, address: u8) -> u16 {
+        u16::from_le_bytes([
+            self.read(address as u16),
+            self.read(address.wrapping_add(1) as u16),
+        ])
+    }
 
-        let _ = $memory_translation_table
-            .read($argument as usize, bytemuck::bytes_of_mut(&mut value), $assigned_address_space);
+    fn push(&self, state: &mut ProcessorState, value: u8) {
+        self.write(STACK_PAGE | state.registers.stack_pointer as u16, value);
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_sub(1);
+    }
 
-        value
-    }};
+    fn pop(&self, state: &mut ProcessorState) -> u8 {
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_add(1);
+        self.read(STACK_PAGE | state.registers.stack_pointer as u16)
+    }
 
-    (@handler XIndexedZeroPage, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        let mut value: u8 = 0;
+    pub(super) fn push_u16(&self, state: &mut ProcessorState, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.push(state, high);
+        self.push(state, low);
+    }
 
-        let actual_address = $argument.wrapping_add($register_store.index_registers[0]);
+    fn pop_u16(&self, state: &mut ProcessorState) -> u16 {
+        let low = self.pop(state);
+        let high = self.pop(state);
 
-        let _ = $memory_translation_table
-            .read(actual_address as usize, bytemuck::bytes_of_mut(&mut value), $assigned_address_space);
+        u16::from_le_bytes([low, high])
+    }
 
-        value
-    }};
+    /// Pushes the status register, where the break flag tells brk and php apart from a hardware interrupt
+    ///
+    /// https://www.nesdev.org/wiki/Status_flags
+    pub(super) fn push_flags(&self, state: &mut ProcessorState, software: bool) {
+        let mut flags = state.registers.flags | FlagRegister::__Unused;
+        flags.set(FlagRegister::Break, software);
 
-    (@handler YIndexedZeroPage, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        let mut value: u8 = 0;
+        self.push(state, flags.bits());
+    }
 
-        let actual_address = $argument.wrapping_add($register_store.index_registers[1]);
+    fn pop_flags(&self, state: &mut ProcessorState) {
+        let mut flags = BitFlags::from_bits_truncate(self.pop(state));
+        // Neither exists in the register, they only show up on the stack
+        flags.remove(FlagRegister::Break | FlagRegister::__Unused);
 
-        let _ = $memory_translation_table
-            .read(actual_address as usize, bytemuck::bytes_of_mut(&mut value), $assigned_address_space);
+        state.registers.flags = flags;
+    }
 
-        value
-    }};
+    /// Where an addressing mode points, after indexing and following pointers
+    fn effective_address(
+        &self,
+        registers: &M6502Registers,
+        addressing_mode: AddressingMode,
+    ) -> u16 {
+        let [x, y] = registers.index_registers;
+
+        match addressing_mode {
+            AddressingMode::Absolute(address) => address,
+            AddressingMode::XIndexedAbsolute(address) => address.wrapping_add(x as u16),
+            AddressingMode::YIndexedAbsolute(address) => address.wrapping_add(y as u16),
+            AddressingMode::AbsoluteIndirect(address) => {
+                // The pointer never crosses a page, the famous JMP bug
+                let high = (address & 0xff00) | (address.wrapping_add(1) & 0x00ff);
+                u16::from_le_bytes([self.read(address), self.read(high)])
+            }
+            AddressingMode::ZeroPage(address) => address as u16,
+            AddressingMode::XIndexedZeroPage(address) => address.wrapping_add(x) as u16,
+            AddressingMode::YIndexedZeroPage(address)
+            | AddressingMode::ZeroPageYIndexed(address) => address.wrapping_add(y) as u16,
+            AddressingMode::XIndexedZeroPageIndirect(address) => {
+                self.read_zero_page_pointer(address.wrapping_add(x))
+            }
+            AddressingMode::ZeroPageIndirectYIndexed(address) => {
+                self.read_zero_page_pointer(address).wrapping_add(y as u16)
+            }
+            AddressingMode::Accumulator
+            | AddressingMode::Immediate(_)
+            | AddressingMode::Relative(_) => {
+                unreachable!("{:?} doesn't point into memory", addressing_mode)
+            }
+        }
+    }
 
-    (@handler XIndexedZeroPageIndirect, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        let mut value: u8 = 0;
+    fn load(&self, registers: &M6502Registers, addressing_mode: Option<AddressingMode>) -> u8 {
+        match addressing_mode {
+            Some(AddressingMode::Immediate(value)) => value,
+            Some(AddressingMode::Accumulator) | None => registers.accumulator,
+            Some(addressing_mode) => self.read(self.effective_address(registers, addressing_mode)),
+        }
+    }
 
-        let indirection_address = $argument.wrapping_add($register_store.index_registers[0]);
-        let mut actual_address = [0; 2];
+    fn store(
+        &self,
+        registers: &M6502Registers,
+        addressing_mode: Option<AddressingMode>,
+        value: u8,
+    ) {
+        let address = self.effective_address(registers, addressing_mode.unwrap());
+        self.write(address, value);
+    }
 
-        let _ = $memory_translation_table
-            .read(indirection_address as usize, &mut actual_address, $assigned_address_space);
+    /// sha, shs, shx and shy AND the value with the high byte of the address plus one, and when indexing crosses a
+    /// page that value ends up as the high byte of the address too
+    fn unstable_store(
+        &self,
+        registers: &M6502Registers,
+        addressing_mode: Option<AddressingMode>,
+        value: u8,
+    ) {
+        let [x, y] = registers.index_registers;
+        let (base, index) = match addressing_mode {
+            Some(AddressingMode::XIndexedAbsolute(address)) => (address, x),
+            Some(AddressingMode::YIndexedAbsolute(address)) => (address, y),
+            Some(AddressingMode::ZeroPageIndirectYIndexed(address)) => {
+                (self.read_zero_page_pointer(address), y)
+            }
+            _ => unreachable!(),
+        };
 
-        let actual_address = u16::from_le_bytes(actual_address);
+        let address = base.wrapping_add(index as u16);
+        let value = value & base.to_be_bytes()[0].wrapping_add(1);
+        let address = if address & 0xff00 != base & 0xff00 {
+            u16::from_le_bytes([address as u8, value])
+        } else {
+            address
+        };
 
-        let _ = $memory_translation_table
-            .read(actual_address as usize, bytemuck::bytes_of_mut(&mut value), $assigned_address_space);
+        self.write(address, value);
+    }
 
-        value
-    }};
+    /// If adc and sbc work in binary coded decimal right now
+    fn decimal_mode(&self, registers: &M6502Registers) -> bool {
+        registers.flags.contains(FlagRegister::Decimal) && self.config.kind.has_decimal_mode()
+    }
 
-    (@handler ZeroPageIndirectYIndexed, $argument:expr, $register_store:expr, $memory_translation_table:expr, $assigned_address_space:expr) => {{
-        let mut value: u8 = 0;
-        let mut indirection_address: u8= 0;
+    fn add_with_carry(&self, registers: &mut M6502Registers, value: u8) {
+        let accumulator = registers.accumulator;
+        let carry = registers.flags.contains(FlagRegister::Carry) as u16;
+        let binary = accumulator as u16 + value as u16 + carry;
+
+        if !self.decimal_mode(registers) {
+            let result = binary as u8;
+
+            registers.flags.set(
+                FlagRegister::Overflow,
+                (accumulator ^ result) & (value ^ result) & 0x80 != 0,
+            );
+            registers.flags.set(FlagRegister::Carry, binary > 0xff);
+            set_negative_zero(&mut registers.flags, result);
+            registers.accumulator = result;
+            return;
+        }
 
-        let _ = $memory_translation_table
-            .read($argument as usize, bytemuck::bytes_of_mut(&mut indirection_address), $assigned_address_space);
+        let mut low = (accumulator & 0x0f) as u16 + (value & 0x0f) as u16 + carry;
+        if low > 0x09 {
+            low += 0x06;
+        }
+        let mut high = (accumulator >> 4) as u16 + (value >> 4) as u16 + (low > 0x0f) as u16;
+
+        // Zero comes from the binary sum, negative and overflow from the sum before the high digit is fixed up
+        let unadjusted = ((high << 4) | (low & 0x0f)) as u8;
+        registers
+            .flags
+            .set(FlagRegister::Zero, binary.to_le_bytes()[0] == 0);
+        registers
+            .flags
+            .set(FlagRegister::Negative, unadjusted.view_bits::<Lsb0>()[7]);
+        registers.flags.set(
+            FlagRegister::Overflow,
+            (accumulator ^ unadjusted) & (value ^ unadjusted) & 0x80 != 0,
+        );
+
+        if high > 0x09 {
+            high += 0x06;
+        }
+        registers.flags.set(FlagRegister::Carry, high > 0x0f);
+        registers.accumulator = ((high << 4) | (low & 0x0f)) as u8;
+    }
 
-        let indirection_address = (indirection_address as u16)
-            .wrapping_add($register_store.index_registers[1] as u16);
+    fn subtract_with_carry(&self, registers: &mut M6502Registers, value: u8) {
+        let accumulator = registers.accumulator;
+        let borrow = !registers.flags.contains(FlagRegister::Carry) as i16;
+        let binary = accumulator as i16 - value as i16 - borrow;
+        let result = binary as u8;
+
+        // Unlike adc every flag comes from the binary difference, even in decimal mode
+        registers.flags.set(
+            FlagRegister::Overflow,
+            (accumulator ^ value) & (accumulator ^ result) & 0x80 != 0,
+        );
+        registers.flags.set(FlagRegister::Carry, binary >= 0);
+        set_negative_zero(&mut registers.flags, result);
+
+        if !self.decimal_mode(registers) {
+            registers.accumulator = result;
+            return;
+        }
 
-        let _ = $memory_translation_table
-            .read(indirection_address as usize, bytemuck::bytes_of_mut(&mut value), $assigned_address_space);
+        let mut low = (accumulator & 0x0f) as i16 - (value & 0x0f) as i16 - borrow;
+        let mut high = (accumulator >> 4) as i16 - (value >> 4) as i16;
+        if low < 0 {
+            low -= 0x06;
+            high -= 1;
+        }
+        if high < 0 {
+            high -= 0x06;
+        }
 
-        value
-    }};
-}
+        registers.accumulator = ((high << 4) | (low & 0x0f)) as u8;
+    }
 
-impl M6502 {
     pub(super) fn interpret_instruction(
         &self,
         state: &mut ProcessorState,
         instruction: M6502InstructionSet,
     ) {
-        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        if instruction.specifier.is_undocumented() {
+            match self.config.undocumented_opcodes {
+                UndocumentedOpcodes::Full => {}
+                UndocumentedOpcodes::Nop => return,
+                UndocumentedOpcodes::Trap => {
+                    tracing::error!(
+                        "Trapped on undocumented instruction {:?} at {:#06x}",
+                        instruction,
+                        state.registers.program
+                    );
+
+                    state.trapped = Some(instruction);
+                    return;
+                }
+            }
+        }
+
+        let addressing_mode = instruction.addressing_mode;
 
         match instruction.specifier {
             M6502InstructionSetSpecifier::Adc => {
-                let value = load_m6502_addressing_modes!(
-                    instruction,
-                    state.registers,
-                    memory_translation_table,
-                    self.config.assigned_address_space,
-                    [
-                        Immediate,
-                        Absolute,
-                        XIndexedAbsolute,
-                        YIndexedAbsolute,
-                        ZeroPage,
-                        XIndexedZeroPage,
-                        XIndexedZeroPageIndirect,
-                        ZeroPageIndirectYIndexed
-                    ]
-                );
-
-                let carry_value = state.registers.flags.contains(FlagRegister::Carry) as u8;
-
-                let (first_operation_result, first_operation_overflow) =
-                    state.registers.accumulator.overflowing_add(value);
-
-                let (second_operation_result, second_operation_overflow) =
-                    first_operation_result.overflowing_add(carry_value);
-
-                state.registers.flags.set(
-                    FlagRegister::Overflow,
-                    // If it overflowed at any point this is set
-                    first_operation_overflow || second_operation_overflow,
-                );
-
-                state.registers.flags.set(
-                    FlagRegister::Carry,
-                    first_operation_overflow || second_operation_overflow,
-                );
-
-                state.registers.flags.set(
-                    FlagRegister::Negative,
-                    // Check would be sign value
-                    second_operation_result.view_bits::<Lsb0>()[7],
-                );
-
-                state.registers.flags.set(
-                    FlagRegister::Zero,
-                    // Check would be carry value
-                    second_operation_result == 0,
-                );
-
-                state.registers.accumulator = second_operation_result;
+                let value = self.load(&state.registers, addressing_mode);
+                self.add_with_carry(&mut state.registers, value);
             }
             M6502InstructionSetSpecifier::Anc => {
-                let value = load_m6502_addressing_modes!(
-                    instruction,
-                    state.registers,
-                    memory_translation_table,
-                    self.config.assigned_address_space,
-                    [Immediate]
-                );
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
 
-                let new_value = state.registers.accumulator & value;
-
-                state
-                    .registers
-                    .flags
-                    .set(FlagRegister::Negative, new_value.view_bits::<Lsb0>()[7]);
-
-                state
-                    .registers
-                    .flags
-                    .set(FlagRegister::Carry, new_value.view_bits::<Lsb0>()[7]);
-
-                state
-                    .registers
-                    .flags
-                    .set(FlagRegister::Zero, new_value == 0);
-
-                state.registers.accumulator = new_value;
+                registers.accumulator &= value;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+                // Bit 7 goes into carry like the shift that never happens
+                registers.flags.set(
+                    FlagRegister::Carry,
+                    registers.accumulator.view_bits::<Lsb0>()[7],
+                );
             }
             M6502InstructionSetSpecifier::And => {
-                let value = load_m6502_addressing_modes!(
-                    instruction,
-                    state.registers,
-                    memory_translation_table,
-                    self.config.assigned_address_space,
-                    [
-                        Immediate,
-                        Absolute,
-                        XIndexedAbsolute,
-                        YIndexedAbsolute,
-                        ZeroPage,
-                        XIndexedZeroPage,
-                        XIndexedZeroPageIndirect,
-                        ZeroPageIndirectYIndexed
-                    ]
-                );
-
-                let new_value = state.registers.accumulator & value;
-
-                state
-                    .registers
-                    .flags
-                    .set(FlagRegister::Negative, new_value.view_bits::<Lsb0>()[7]);
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
 
-                state
-                    .registers
-                    .flags
-                    .set(FlagRegister::Zero, new_value == 0);
-
-                state.registers.accumulator = new_value;
+                registers.accumulator &= value;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
             }
-            M6502InstructionSetSpecifier::Arr => todo!(),
-            M6502InstructionSetSpecifier::Asl => todo!(),
-            M6502InstructionSetSpecifier::Asr => todo!(),
-            M6502InstructionSetSpecifier::Bcc => {
-                let value = match instruction.addressing_mode {
-                    Some(AddressingMode::Relative(value)) => value,
-                    _ => unreachable!(),
-                };
-
-                if !state.registers.flags.contains(FlagRegister::Carry) {
-                    state.registers.program =
-                        state.registers.program.wrapping_add_signed(value as i16);
+            M6502InstructionSetSpecifier::Arr => {
+                let value = self.load(&state.registers, addressing_mode);
+                let decimal_mode = self.decimal_mode(&state.registers);
+                let registers = &mut state.registers;
+
+                let anded = registers.accumulator & value;
+                let carry = registers.flags.contains(FlagRegister::Carry) as u8;
+                let mut result = (anded >> 1) | (carry << 7);
+
+                set_negative_zero(&mut registers.flags, result);
+                if decimal_mode {
+                    // The adder fixes up each digit as if this were an addition
+                    registers
+                        .flags
+                        .set(FlagRegister::Overflow, (anded ^ result) & 0x40 != 0);
+
+                    if (anded & 0x0f) + (anded & 0x01) > 0x05 {
+                        result = (result & 0xf0) | (result.wrapping_add(0x06) & 0x0f);
+                    }
+                    let carry = (anded & 0xf0) as u16 + (anded & 0x10) as u16 > 0x50;
+                    if carry {
+                        result = result.wrapping_add(0x60);
+                    }
+                    registers.flags.set(FlagRegister::Carry, carry);
+                } else {
+                    let bits = result.view_bits::<Lsb0>();
+                    registers.flags.set(FlagRegister::Carry, bits[6]);
+                    registers
+                        .flags
+                        .set(FlagRegister::Overflow, bits[6] ^ bits[5]);
                 }
-            }
-            M6502InstructionSetSpecifier::Bcs => {
-                let value = match instruction.addressing_mode {
-                    Some(AddressingMode::Relative(value)) => value,
-                    _ => unreachable!(),
-                };
 
-                if state.registers.flags.contains(FlagRegister::Carry) {
-                    state.registers.program =
-                        state.registers.program.wrapping_add_signed(value as i16);
-                }
+                registers.accumulator = result;
             }
-            M6502InstructionSetSpecifier::Beq => {
-                let value = match instruction.addressing_mode {
-                    Some(AddressingMode::Relative(value)) => value,
-                    _ => unreachable!(),
-                };
-
-                if state.registers.flags.contains(FlagRegister::Zero) {
-                    state.registers.program =
-                        state.registers.program.wrapping_add_signed(value as i16);
-                }
+            M6502InstructionSetSpecifier::Asl => {
+                self.read_modify_write(state, addressing_mode, |value, flags| {
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[7]);
+                    value << 1
+                });
             }
-            M6502InstructionSetSpecifier::Bit => todo!(),
-            M6502InstructionSetSpecifier::Bmi => {
-                let value = match instruction.addressing_mode {
-                    Some(AddressingMode::Relative(value)) => value,
-                    _ => unreachable!(),
-                };
-
-                if state.registers.flags.contains(FlagRegister::Negative) {
-                    state.registers.program =
-                        state.registers.program.wrapping_add_signed(value as i16);
-                }
+            M6502InstructionSetSpecifier::Asr => {
+                let value = self.load(&state.registers, addressing_mode);
+                state.registers.accumulator &= value;
+
+                self.read_modify_write(state, None, |value, flags| {
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[0]);
+                    value >> 1
+                });
             }
-            M6502InstructionSetSpecifier::Bne => {
-                let value = match instruction.addressing_mode {
-                    Some(AddressingMode::Relative(value)) => value,
+            M6502InstructionSetSpecifier::Bcc
+            | M6502InstructionSetSpecifier::Bcs
+            | M6502InstructionSetSpecifier::Beq
+            | M6502InstructionSetSpecifier::Bmi
+            | M6502InstructionSetSpecifier::Bne
+            | M6502InstructionSetSpecifier::Bpl
+            | M6502InstructionSetSpecifier::Bvc
+            | M6502InstructionSetSpecifier::Bvs => {
+                let offset = match addressing_mode {
+                    Some(AddressingMode::Relative(offset)) => offset,
                     _ => unreachable!(),
                 };
 
-                if !state.registers.flags.contains(FlagRegister::Zero) {
+                if instruction.specifier.branch_taken(&state.registers) {
                     state.registers.program =
-                        state.registers.program.wrapping_add_signed(value as i16);
+                        state.registers.program.wrapping_add_signed(offset as i16);
                 }
             }
-            M6502InstructionSetSpecifier::Bpl => {
-                let value = match instruction.addressing_mode {
-                    Some(AddressingMode::Relative(value)) => value,
-                    _ => unreachable!(),
-                };
+            M6502InstructionSetSpecifier::Bit => {
+                let value = self.load(&state.registers, addressing_mode);
+                let bits = value.view_bits::<Lsb0>();
+                let registers = &mut state.registers;
 
-                if !state.registers.flags.contains(FlagRegister::Negative) {
-                    state.registers.program =
-                        state.registers.program.wrapping_add_signed(value as i16);
-                }
-            }
-            M6502InstructionSetSpecifier::Brk => todo!(),
-            M6502InstructionSetSpecifier::Bvc => {
-                let value = match instruction.addressing_mode {
-                    Some(AddressingMode::Relative(value)) => value,
-                    _ => unreachable!(),
-                };
-
-                if !state.registers.flags.contains(FlagRegister::Overflow) {
-                    state.registers.program =
-                        state.registers.program.wrapping_add_signed(value as i16);
-                }
+                registers
+                    .flags
+                    .set(FlagRegister::Zero, registers.accumulator & value == 0);
+                registers.flags.set(FlagRegister::Negative, bits[7]);
+                registers.flags.set(FlagRegister::Overflow, bits[6]);
             }
-            M6502InstructionSetSpecifier::Bvs => {
-                let value = match instruction.addressing_mode {
-                    Some(AddressingMode::Relative(value)) => value,
-                    _ => unreachable!(),
-                };
+            M6502InstructionSetSpecifier::Brk => {
+                // The byte after brk is skipped, so there is room for a signature
+                let program = state.registers.program.wrapping_add(1);
+                self.push_u16(state, program);
+                self.push_flags(state, true);
 
-                if state.registers.flags.contains(FlagRegister::Overflow) {
-                    state.registers.program =
-                        state.registers.program.wrapping_add_signed(value as i16);
-                }
+                state.registers.flags.insert(FlagRegister::InterruptDisable);
+                state.registers.program = self.read_u16(IRQ_VECTOR);
             }
             M6502InstructionSetSpecifier::Clc => {
                 state.registers.flags.remove(FlagRegister::Carry);
@@ -351,118 +405,239 @@ impl M6502 {
             M6502InstructionSetSpecifier::Clv => {
                 state.registers.flags.remove(FlagRegister::Overflow);
             }
-            M6502InstructionSetSpecifier::Cmp => todo!(),
-            M6502InstructionSetSpecifier::Cpx => todo!(),
-            M6502InstructionSetSpecifier::Cpy => todo!(),
-            M6502InstructionSetSpecifier::Dcp => todo!(),
-            M6502InstructionSetSpecifier::Dec => todo!(),
-            M6502InstructionSetSpecifier::Dex => todo!(),
-            M6502InstructionSetSpecifier::Dey => todo!(),
-            M6502InstructionSetSpecifier::Eor => todo!(),
-            M6502InstructionSetSpecifier::Inc => todo!(),
-            M6502InstructionSetSpecifier::Inx => todo!(),
-            M6502InstructionSetSpecifier::Iny => todo!(),
-            M6502InstructionSetSpecifier::Isc => todo!(),
-            M6502InstructionSetSpecifier::Jam => todo!(),
-            M6502InstructionSetSpecifier::Jmp => todo!(),
-            M6502InstructionSetSpecifier::Jsr => todo!(),
-            M6502InstructionSetSpecifier::Las => todo!(),
-            M6502InstructionSetSpecifier::Lax => todo!(),
-            M6502InstructionSetSpecifier::Lda => todo!(),
-            M6502InstructionSetSpecifier::Ldx => todo!(),
-            M6502InstructionSetSpecifier::Ldy => todo!(),
-            M6502InstructionSetSpecifier::Lsr => todo!(),
-            M6502InstructionSetSpecifier::Nop => todo!(),
-            M6502InstructionSetSpecifier::Ora => {
-                let value = load_m6502_addressing_modes!(
-                    instruction,
-                    state.registers,
-                    memory_translation_table,
-                    self.config.assigned_address_space,
-                    [
-                        Immediate,
-                        Absolute,
-                        XIndexedAbsolute,
-                        YIndexedAbsolute,
-                        ZeroPage,
-                        XIndexedZeroPage,
-                        XIndexedZeroPageIndirect,
-                        ZeroPageIndirectYIndexed
-                    ]
-                );
+            M6502InstructionSetSpecifier::Cmp
+            | M6502InstructionSetSpecifier::Cpx
+            | M6502InstructionSetSpecifier::Cpy => {
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
+                let register = match instruction.specifier {
+                    M6502InstructionSetSpecifier::Cmp => registers.accumulator,
+                    M6502InstructionSetSpecifier::Cpx => registers.index_registers[0],
+                    _ => registers.index_registers[1],
+                };
 
-                let new_value = state.registers.accumulator | value;
+                compare(&mut registers.flags, register, value);
+            }
+            M6502InstructionSetSpecifier::Dcp => {
+                let value = self
+                    .read_modify_write(state, addressing_mode, |value, _| value.wrapping_sub(1));
+                let registers = &mut state.registers;
 
-                state
-                    .registers
-                    .flags
-                    .set(FlagRegister::Negative, new_value.view_bits::<Lsb0>()[7]);
+                compare(&mut registers.flags, registers.accumulator, value);
+            }
+            M6502InstructionSetSpecifier::Dec => {
+                self.read_modify_write(state, addressing_mode, |value, _| value.wrapping_sub(1));
+            }
+            M6502InstructionSetSpecifier::Dex
+            | M6502InstructionSetSpecifier::Dey
+            | M6502InstructionSetSpecifier::Inx
+            | M6502InstructionSetSpecifier::Iny => {
+                let registers = &mut state.registers;
+                let register = match instruction.specifier {
+                    M6502InstructionSetSpecifier::Dex | M6502InstructionSetSpecifier::Inx => 0,
+                    _ => 1,
+                };
+                let value = &mut registers.index_registers[register];
 
-                state
-                    .registers
-                    .flags
-                    .set(FlagRegister::Zero, new_value == 0);
+                *value = match instruction.specifier {
+                    M6502InstructionSetSpecifier::Dex | M6502InstructionSetSpecifier::Dey => {
+                        value.wrapping_sub(1)
+                    }
+                    _ => value.wrapping_add(1),
+                };
+                let value = *value;
+                set_negative_zero(&mut registers.flags, value);
+            }
+            M6502InstructionSetSpecifier::Eor => {
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
 
-                state.registers.accumulator = new_value;
+                registers.accumulator ^= value;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
             }
-            M6502InstructionSetSpecifier::Pha => {
-                let _ = memory_translation_table.write(
-                    state.registers.stack_pointer as usize,
-                    &state.registers.accumulator.to_le_bytes(),
-                    self.config.assigned_address_space,
-                );
+            M6502InstructionSetSpecifier::Inc => {
+                self.read_modify_write(state, addressing_mode, |value, _| value.wrapping_add(1));
+            }
+            M6502InstructionSetSpecifier::Isc => {
+                let value = self
+                    .read_modify_write(state, addressing_mode, |value, _| value.wrapping_add(1));
 
-                state.registers.stack_pointer = state.registers.stack_pointer.wrapping_sub(1);
+                self.subtract_with_carry(&mut state.registers, value);
             }
-            M6502InstructionSetSpecifier::Php => {
-                // https://www.nesdev.org/wiki/Status_flags
+            M6502InstructionSetSpecifier::Jam => {
+                // The chip locks up until it is reset, which is the same as being trapped as far as anyone can tell
+                tracing::warn!(
+                    "Processor jammed at {:#06x}",
+                    state.registers.program.wrapping_sub(1)
+                );
 
-                let mut flags = state.registers.flags;
-                flags.insert(FlagRegister::__Unused);
+                state.trapped = Some(instruction);
+            }
+            M6502InstructionSetSpecifier::Jmp => {
+                state.registers.program =
+                    self.effective_address(&state.registers, addressing_mode.unwrap());
+            }
+            M6502InstructionSetSpecifier::Jsr => {
+                let address = self.effective_address(&state.registers, addressing_mode.unwrap());
+                // The return address pushed is the last byte of the jsr, rts makes up the difference
+                let program = state.registers.program.wrapping_sub(1);
+                self.push_u16(state, program);
 
-                let _ = memory_translation_table.write(
-                    state.registers.stack_pointer as usize,
-                    &flags.bits().to_be_bytes(),
-                    self.config.assigned_address_space,
-                );
+                state.registers.program = address;
+            }
+            M6502InstructionSetSpecifier::Las => {
+                let value =
+                    self.load(&state.registers, addressing_mode) & state.registers.stack_pointer;
+                let registers = &mut state.registers;
+
+                registers.accumulator = value;
+                registers.index_registers[0] = value;
+                registers.stack_pointer = value;
+                set_negative_zero(&mut registers.flags, value);
+            }
+            M6502InstructionSetSpecifier::Lax => {
+                let new_value = match addressing_mode {
+                    // LXA, unstable so it goes through the magic constant
+                    Some(AddressingMode::Immediate(value)) => {
+                        (state.registers.accumulator | self.config.magic_constant) & value
+                    }
+                    _ => self.load(&state.registers, addressing_mode),
+                };
+                let registers = &mut state.registers;
 
-                state.registers.stack_pointer = state.registers.stack_pointer.wrapping_sub(1);
+                set_negative_zero(&mut registers.flags, new_value);
+                registers.accumulator = new_value;
+                registers.index_registers[0] = new_value;
             }
-            M6502InstructionSetSpecifier::Pla => {
-                state.registers.stack_pointer = state.registers.stack_pointer.wrapping_add(1);
+            M6502InstructionSetSpecifier::Lda => {
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
 
-                let mut value = 0;
+                registers.accumulator = value;
+                set_negative_zero(&mut registers.flags, value);
+            }
+            M6502InstructionSetSpecifier::Ldx | M6502InstructionSetSpecifier::Ldy => {
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
+                let register =
+                    (instruction.specifier == M6502InstructionSetSpecifier::Ldy) as usize;
+
+                registers.index_registers[register] = value;
+                set_negative_zero(&mut registers.flags, value);
+            }
+            M6502InstructionSetSpecifier::Lsr => {
+                self.read_modify_write(state, addressing_mode, |value, flags| {
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[0]);
+                    value >> 1
+                });
+            }
+            M6502InstructionSetSpecifier::Nop => {
+                // The ones with an operand still read it, which registers with side effects notice
+                if let Some(
+                    addressing_mode @ (AddressingMode::Absolute(_)
+                    | AddressingMode::XIndexedAbsolute(_)
+                    | AddressingMode::ZeroPage(_)
+                    | AddressingMode::XIndexedZeroPage(_)),
+                ) = addressing_mode
+                {
+                    self.read(self.effective_address(&state.registers, addressing_mode));
+                }
+            }
+            M6502InstructionSetSpecifier::Ora => {
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
 
-                let _ = memory_translation_table.read(
-                    state.registers.stack_pointer as usize,
-                    std::array::from_mut(&mut value),
-                    self.config.assigned_address_space,
-                );
+                registers.accumulator |= value;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+            }
+            M6502InstructionSetSpecifier::Pha => {
+                let value = state.registers.accumulator;
+                self.push(state, value);
+            }
+            M6502InstructionSetSpecifier::Php => {
+                self.push_flags(state, true);
+            }
+            M6502InstructionSetSpecifier::Pla => {
+                let value = self.pop(state);
+                let registers = &mut state.registers;
 
-                state.registers.accumulator = value;
+                registers.accumulator = value;
+                set_negative_zero(&mut registers.flags, value);
             }
             M6502InstructionSetSpecifier::Plp => {
-                state.registers.stack_pointer = state.registers.stack_pointer.wrapping_add(1);
-
-                let mut value = 0;
+                self.pop_flags(state);
+            }
+            M6502InstructionSetSpecifier::Rla => {
+                let value = self.read_modify_write(state, addressing_mode, |value, flags| {
+                    let carry = flags.contains(FlagRegister::Carry) as u8;
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[7]);
+                    (value << 1) | carry
+                });
+                let registers = &mut state.registers;
+
+                registers.accumulator &= value;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+            }
+            M6502InstructionSetSpecifier::Rol => {
+                self.read_modify_write(state, addressing_mode, |value, flags| {
+                    let carry = flags.contains(FlagRegister::Carry) as u8;
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[7]);
+                    (value << 1) | carry
+                });
+            }
+            M6502InstructionSetSpecifier::Ror => {
+                // The earliest chips shifted left without touching carry instead
+                if let M6502Kind::M6502 {
+                    quirk_broken_ror: true,
+                } = self.config.kind
+                {
+                    self.read_modify_write(state, addressing_mode, |value, _| value << 1);
+                    return;
+                }
 
-                let _ = memory_translation_table.read(
-                    state.registers.stack_pointer as usize,
-                    std::array::from_mut(&mut value),
-                    self.config.assigned_address_space,
+                self.read_modify_write(state, addressing_mode, |value, flags| {
+                    let carry = flags.contains(FlagRegister::Carry) as u8;
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[0]);
+                    (value >> 1) | (carry << 7)
+                });
+            }
+            M6502InstructionSetSpecifier::Rra => {
+                let value = self.read_modify_write(state, addressing_mode, |value, flags| {
+                    let carry = flags.contains(FlagRegister::Carry) as u8;
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[0]);
+                    (value >> 1) | (carry << 7)
+                });
+
+                self.add_with_carry(&mut state.registers, value);
+            }
+            M6502InstructionSetSpecifier::Rti => {
+                self.pop_flags(state);
+                state.registers.program = self.pop_u16(state);
+            }
+            M6502InstructionSetSpecifier::Rts => {
+                state.registers.program = self.pop_u16(state).wrapping_add(1);
+            }
+            M6502InstructionSetSpecifier::Sax => {
+                let [x, _] = state.registers.index_registers;
+                self.store(
+                    &state.registers,
+                    addressing_mode,
+                    state.registers.accumulator & x,
                 );
-
-                state.registers.flags = FlagRegister::from_bits(value).unwrap();
-            }
-            M6502InstructionSetSpecifier::Rla => todo!(),
-            M6502InstructionSetSpecifier::Rol => todo!(),
-            M6502InstructionSetSpecifier::Ror => todo!(),
-            M6502InstructionSetSpecifier::Rra => todo!(),
-            M6502InstructionSetSpecifier::Rti => todo!(),
-            M6502InstructionSetSpecifier::Rts => todo!(),
-            M6502InstructionSetSpecifier::Sax => todo!(),
-            M6502InstructionSetSpecifier::Sbc => todo!(),
-            M6502InstructionSetSpecifier::Sbx => todo!(),
+            }
+            M6502InstructionSetSpecifier::Sbc => {
+                let value = self.load(&state.registers, addressing_mode);
+                self.subtract_with_carry(&mut state.registers, value);
+            }
+            M6502InstructionSetSpecifier::Sbx => {
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
+                let anded = registers.accumulator & registers.index_registers[0];
+
+                // Like cmp, so decimal mode and the old carry don't matter
+                compare(&mut registers.flags, anded, value);
+                registers.index_registers[0] = anded.wrapping_sub(value);
+            }
             M6502InstructionSetSpecifier::Sec => {
                 state.registers.flags.insert(FlagRegister::Carry);
             }
@@ -472,30 +647,166 @@ impl M6502 {
             M6502InstructionSetSpecifier::Sei => {
                 state.registers.flags.insert(FlagRegister::InterruptDisable);
             }
-            M6502InstructionSetSpecifier::Sha => todo!(),
-            M6502InstructionSetSpecifier::Shs => todo!(),
-            M6502InstructionSetSpecifier::Shx => todo!(),
-            M6502InstructionSetSpecifier::Shy => todo!(),
-            M6502InstructionSetSpecifier::Slo => todo!(),
-            M6502InstructionSetSpecifier::Sre => todo!(),
-            M6502InstructionSetSpecifier::Sta => todo!(),
-            M6502InstructionSetSpecifier::Stx => todo!(),
-            M6502InstructionSetSpecifier::Sty => todo!(),
-            M6502InstructionSetSpecifier::Tax => todo!(),
-            M6502InstructionSetSpecifier::Tay => todo!(),
-            M6502InstructionSetSpecifier::Tsx => todo!(),
-            M6502InstructionSetSpecifier::Txa => todo!(),
-            M6502InstructionSetSpecifier::Txs => todo!(),
-            M6502InstructionSetSpecifier::Tya => todo!(),
-            M6502InstructionSetSpecifier::Xaa => {
-                let value = load_m6502_addressing_modes!(
-                    instruction,
-                    state.registers,
-                    memory_translation_table,
-                    self.config.assigned_address_space,
-                    [Immediate]
+            M6502InstructionSetSpecifier::Sha => {
+                let [x, _] = state.registers.index_registers;
+                self.unstable_store(
+                    &state.registers,
+                    addressing_mode,
+                    state.registers.accumulator & x,
+                );
+            }
+            M6502InstructionSetSpecifier::Shs => {
+                let [x, _] = state.registers.index_registers;
+                state.registers.stack_pointer = state.registers.accumulator & x;
+                self.unstable_store(
+                    &state.registers,
+                    addressing_mode,
+                    state.registers.stack_pointer,
+                );
+            }
+            M6502InstructionSetSpecifier::Shx => {
+                let [x, _] = state.registers.index_registers;
+                self.unstable_store(&state.registers, addressing_mode, x);
+            }
+            M6502InstructionSetSpecifier::Shy => {
+                let [_, y] = state.registers.index_registers;
+                self.unstable_store(&state.registers, addressing_mode, y);
+            }
+            M6502InstructionSetSpecifier::Slo => {
+                let value = self.read_modify_write(state, addressing_mode, |value, flags| {
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[7]);
+                    value << 1
+                });
+                let registers = &mut state.registers;
+
+                registers.accumulator |= value;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+            }
+            M6502InstructionSetSpecifier::Sre => {
+                let value = self.read_modify_write(state, addressing_mode, |value, flags| {
+                    flags.set(FlagRegister::Carry, value.view_bits::<Lsb0>()[0]);
+                    value >> 1
+                });
+                let registers = &mut state.registers;
+
+                registers.accumulator ^= value;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+            }
+            M6502InstructionSetSpecifier::Sta => {
+                self.store(
+                    &state.registers,
+                    addressing_mode,
+                    state.registers.accumulator,
                 );
             }
+            M6502InstructionSetSpecifier::Stx => {
+                let [x, _] = state.registers.index_registers;
+                self.store(&state.registers, addressing_mode, x);
+            }
+            M6502InstructionSetSpecifier::Sty => {
+                let [_, y] = state.registers.index_registers;
+                self.store(&state.registers, addressing_mode, y);
+            }
+            M6502InstructionSetSpecifier::Tax => {
+                let registers = &mut state.registers;
+
+                registers.index_registers[0] = registers.accumulator;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+            }
+            M6502InstructionSetSpecifier::Tay => {
+                let registers = &mut state.registers;
+
+                registers.index_registers[1] = registers.accumulator;
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+            }
+            M6502InstructionSetSpecifier::Tsx => {
+                let registers = &mut state.registers;
+
+                registers.index_registers[0] = registers.stack_pointer;
+                set_negative_zero(&mut registers.flags, registers.stack_pointer);
+            }
+            M6502InstructionSetSpecifier::Txa => {
+                let registers = &mut state.registers;
+
+                registers.accumulator = registers.index_registers[0];
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+            }
+            M6502InstructionSetSpecifier::Txs => {
+                // The only transfer that leaves the flags alone
+                state.registers.stack_pointer = state.registers.index_registers[0];
+            }
+            M6502InstructionSetSpecifier::Tya => {
+                let registers = &mut state.registers;
+
+                registers.accumulator = registers.index_registers[1];
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+            }
+            M6502InstructionSetSpecifier::Xaa => {
+                let value = self.load(&state.registers, addressing_mode);
+                let registers = &mut state.registers;
+
+                // ANE, as unstable as LXA
+                let new_value = (registers.accumulator | self.config.magic_constant)
+                    & registers.index_registers[0]
+                    & value;
+
+                set_negative_zero(&mut registers.flags, new_value);
+                registers.accumulator = new_value;
+            }
+        }
+    }
+
+    /// Runs an operation on the accumulator or on memory, setting the negative and zero flags from the result and
+    /// returning it
+    fn read_modify_write(
+        &self,
+        state: &mut ProcessorState,
+        addressing_mode: Option<AddressingMode>,
+        operation: impl FnOnce(u8, &mut BitFlags<FlagRegister>) -> u8,
+    ) -> u8 {
+        let address = match addressing_mode {
+            Some(AddressingMode::Accumulator) | None => {
+                let registers = &mut state.registers;
+
+                registers.accumulator = operation(registers.accumulator, &mut registers.flags);
+                set_negative_zero(&mut registers.flags, registers.accumulator);
+                return registers.accumulator;
+            }
+            Some(addressing_mode) => self.effective_address(&state.registers, addressing_mode),
+        };
+        let read_modify_write_value = state.read_modify_write_value.take();
+        let flags = &mut state.registers.flags;
+
+        // When cycle accurate the bus cycles already did the read and wrote the old value back
+        if let Some(value) = read_modify_write_value {
+            let value = operation(value, flags);
+            self.write(address, value);
+            set_negative_zero(flags, value);
+
+            return value;
         }
+
+        let behavior = if self.config.cycle_accurate {
+            ReadModifyWriteBehavior::Single
+        } else {
+            ReadModifyWriteBehavior::DoubleWrite
+        };
+
+        let mut value = [0];
+        let _ = self
+            .memory_translation_table
+            .get()
+            .unwrap()
+            .read_modify_write(
+                address as usize,
+                &mut value,
+                self.config.assigned_address_space,
+                behavior,
+                |value| value[0] = operation(value[0], flags),
+            );
+
+        set_negative_zero(flags, value[0]);
+
+        value[0]
     }
 }
@@ -1,13 +1,30 @@
-use std::sync::{Arc, Mutex, OnceLock};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
 
+use super::WaitStates;
 use crate::{
-    component::{schedulable::SchedulableComponent, Component, FromConfig},
+    component::{
+        processor::{DebuggableProcessor, ProcessorDebugState, ProcessorRegister},
+        schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::io::InterruptConnection,
     machine::ComponentBuilder,
     memory::{AddressSpaceId, MemoryTranslationTable},
 };
+use cycle::{bus_cycles, interrupt_bus_cycles, BusCycle};
+use decode::decode_instruction;
 use enumflags2::{bitflags, BitFlags};
+use instruction::M6502InstructionSet;
 use num::rational::Ratio;
+use serde::{Deserialize, Serialize};
 
+pub mod cycle;
 pub mod decode;
 pub mod instruction;
 pub mod interpret;
@@ -15,6 +32,7 @@ pub mod interpret;
 #[cfg(test)]
 pub mod test;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum M6502Kind {
     /// Standard
     M6502 {
@@ -29,6 +47,28 @@ pub enum M6502Kind {
     R2A07,
 }
 
+impl M6502Kind {
+    /// The NES chips had the decimal mode circuitry cut out, the flag is still there but does nothing
+    pub fn has_decimal_mode(&self) -> bool {
+        !matches!(self, Self::R2A03 | Self::R2A07)
+    }
+}
+
+const NMI_VECTOR: u16 = 0xfffa;
+const RESET_VECTOR: u16 = 0xfffc;
+const IRQ_VECTOR: u16 = 0xfffe;
+
+/// The interrupt inputs of the chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum M6502Interrupt {
+    /// Level triggered and shared, ignored while the interrupt disable flag is set
+    Irq,
+    /// Edge triggered, taken no matter what
+    Nmi,
+    /// Restarts the program from the reset vector
+    Reset,
+}
+
 #[bitflags]
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -50,6 +90,40 @@ enum FlagRegister {
     Carry = 0b0000_0001,
 }
 
+/// The order [DebuggableProcessor] exposes [M6502Registers] in
+const DEBUG_REGISTERS: &[ProcessorRegister] = &[
+    ProcessorRegister {
+        name: "a",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "x",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "y",
+        size: 1,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "sp",
+        size: 1,
+        generic: Some("sp"),
+    },
+    ProcessorRegister {
+        name: "p",
+        size: 1,
+        generic: Some("flags"),
+    },
+    ProcessorRegister {
+        name: "pc",
+        size: 2,
+        generic: Some("pc"),
+    },
+];
+
 #[derive(Debug)]
 pub struct M6502Registers {
     stack_pointer: u8,
@@ -59,16 +133,49 @@ pub struct M6502Registers {
     program: u16,
 }
 
+/// What to do when the program runs an instruction that isn't in the datasheet
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UndocumentedOpcodes {
+    /// Execute them like the real chip, which some games rely on
+    #[default]
+    Full,
+    /// Skip over them, operands included
+    Nop,
+    /// Stop the processor so the debugger can look at what happened
+    Trap,
+}
+
 #[derive(Debug)]
 pub struct M6502Config {
+    pub kind: M6502Kind,
     pub frequency: Ratio<u64>,
     pub assigned_address_space: AddressSpaceId,
+    pub undocumented_opcodes: UndocumentedOpcodes,
+    /// The value ANE and LXA OR the accumulator with, which depends on the chip and even its temperature
+    ///
+    /// 0xee is what most NES consoles do, 0xff and 0x00 show up on other machines
+    pub magic_constant: u8,
+    /// Spread each instruction over its real cycles, performing the dummy reads and writes on the way
+    ///
+    /// Timing sensitive games and mappers that watch the bus need this, otherwise instructions happen all at
+    /// once on their first cycle
+    pub cycle_accurate: bool,
+    /// Cycles other chips steal the bus for, which the processor sits out
+    pub wait_states: Option<Arc<WaitStates>>,
 }
 
 #[derive(Debug)]
 struct ProcessorState {
     registers: M6502Registers,
-    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+    /// Set when an undocumented instruction was hit with [UndocumentedOpcodes::Trap]
+    trapped: Option<M6502InstructionSet>,
+    /// Cycles left of the current instruction
+    pending_cycles: VecDeque<BusCycle>,
+    /// The instruction to execute once the cycles run out, only used when cycle accurate
+    pending_instruction: Option<M6502InstructionSet>,
+    /// What the read cycle of a read-modify-write fetched, written back on the dummy write and modified when the
+    /// instruction executes
+    read_modify_write_value: Option<u8>,
 }
 
 impl Default for ProcessorState {
@@ -81,7 +188,69 @@ impl Default for ProcessorState {
                 flags: BitFlags::empty(),
                 program: 0,
             },
-            memory_translation_table: OnceLock::default(),
+            trapped: None,
+            pending_cycles: VecDeque::default(),
+            pending_instruction: None,
+            read_modify_write_value: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct M6502Snapshot {
+    accumulator: u8,
+    index_registers: [u8; 2],
+    stack_pointer: u8,
+    flags: u8,
+    program: u16,
+    trapped: Option<M6502InstructionSet>,
+    pending_cycles: VecDeque<BusCycle>,
+    pending_instruction: Option<M6502InstructionSet>,
+    read_modify_write_value: Option<u8>,
+    irq: u32,
+    nmi: bool,
+    reset: bool,
+}
+
+#[derive(Debug)]
+struct InterruptLines {
+    /// How many devices are holding irq low
+    irq: AtomicU32,
+    /// Latched on the edge, so a short pulse is never missed
+    nmi: AtomicBool,
+    reset: AtomicBool,
+}
+
+#[derive(Debug)]
+struct M6502InterruptRequest {
+    lines: Arc<InterruptLines>,
+    interrupt: M6502Interrupt,
+}
+
+impl InterruptConnection for M6502InterruptRequest {
+    fn set_interrupt(&self, raised: bool) {
+        match self.interrupt {
+            M6502Interrupt::Irq => {
+                if raised {
+                    self.lines.irq.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    let _ = self.lines.irq.fetch_update(
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                        |holders| holders.checked_sub(1),
+                    );
+                }
+            }
+            M6502Interrupt::Nmi => {
+                if raised {
+                    self.lines.nmi.store(true, Ordering::Relaxed);
+                }
+            }
+            M6502Interrupt::Reset => {
+                if raised {
+                    self.lines.reset.store(true, Ordering::Relaxed);
+                }
+            }
         }
     }
 }
@@ -90,10 +259,222 @@ impl Default for ProcessorState {
 pub struct M6502 {
     config: M6502Config,
     state: Mutex<ProcessorState>,
-    memory_translation_table: OnceLock<MemoryTranslationTable>,
+    interrupt_lines: Arc<InterruptLines>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+    debug: ProcessorDebugState,
+}
+
+impl M6502 {
+    /// The undocumented instruction that stopped the processor, if it stopped
+    pub fn trapped(&self) -> Option<M6502InstructionSet> {
+        self.state.lock().unwrap().trapped
+    }
+
+    /// Something other components can pull an interrupt line of this processor with
+    pub fn interrupt_connection(&self, interrupt: M6502Interrupt) -> Arc<dyn InterruptConnection> {
+        Arc::new(M6502InterruptRequest {
+            lines: self.interrupt_lines.clone(),
+            interrupt,
+        })
+    }
+
+    /// Starts the interrupt sequence if one is due, reset first, then nmi, then irq
+    ///
+    /// The sequence runs right away, the bus cycles that follow only account for its time
+    fn service_interrupts(&self, state: &mut ProcessorState) -> bool {
+        let interrupt = if self.interrupt_lines.reset.swap(false, Ordering::Relaxed) {
+            M6502Interrupt::Reset
+        } else if self.interrupt_lines.nmi.swap(false, Ordering::Relaxed) {
+            M6502Interrupt::Nmi
+        } else if self.interrupt_lines.irq.load(Ordering::Relaxed) != 0
+            && !state
+                .registers
+                .flags
+                .contains(FlagRegister::InterruptDisable)
+        {
+            M6502Interrupt::Irq
+        } else {
+            return false;
+        };
+
+        let vector = match interrupt {
+            M6502Interrupt::Irq => IRQ_VECTOR,
+            M6502Interrupt::Nmi => NMI_VECTOR,
+            M6502Interrupt::Reset => RESET_VECTOR,
+        };
+
+        state.pending_cycles =
+            interrupt_bus_cycles(&state.registers, vector, interrupt == M6502Interrupt::Reset)
+                .into();
+
+        if interrupt == M6502Interrupt::Reset {
+            // The pushes still happen, but as reads, so only the stack pointer moves
+            state.registers.stack_pointer = state.registers.stack_pointer.wrapping_sub(3);
+            state.trapped = None;
+        } else {
+            let program = state.registers.program;
+            self.push_u16(state, program);
+            self.push_flags(state, false);
+        }
+
+        state.registers.flags.insert(FlagRegister::InterruptDisable);
+        state.registers.program = self.read_u16(vector);
+
+        true
+    }
+
+    fn start_instruction(&self, state: &mut ProcessorState) {
+        if self.service_interrupts(state) {
+            return;
+        }
+
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+
+        let (instruction, length) = match decode_instruction(
+            state.registers.program,
+            self.config.assigned_address_space,
+            memory_translation_table,
+        ) {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                tracing::error!(
+                    "Failed to decode instruction at {:#06x}: {}",
+                    state.registers.program,
+                    error
+                );
+                return;
+            }
+        };
+
+        state.read_modify_write_value = None;
+        state.pending_cycles = bus_cycles(
+            &instruction,
+            &state.registers,
+            memory_translation_table,
+            self.config.assigned_address_space,
+        )
+        .into();
+        state.registers.program = state.registers.program.wrapping_add(length as u16);
+
+        if self.config.cycle_accurate {
+            state.pending_instruction = Some(instruction);
+        } else {
+            self.interpret_instruction(state, instruction);
+        }
+    }
+
+    /// Only the dummy accesses and the read of a read-modify-write go through here, the interpreter does the other
+    /// real ones when the instruction executes
+    fn perform_bus_cycle(&self, state: &mut ProcessorState, cycle: BusCycle) {
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        let address_space = self.config.assigned_address_space;
+
+        match cycle {
+            BusCycle::DummyRead(address) => {
+                let _ = memory_translation_table.read(address as usize, &mut [0], address_space);
+            }
+            // Has to happen before the write back, so it can't wait for the interpreter
+            BusCycle::Read(address)
+                if matches!(state.pending_cycles.front(), Some(BusCycle::DummyWrite(_))) =>
+            {
+                let mut value = [0];
+                let _ = memory_translation_table.read(address as usize, &mut value, address_space);
+                state.read_modify_write_value = Some(value[0]);
+            }
+            BusCycle::DummyWrite(address) => {
+                if let Some(value) = state.read_modify_write_value {
+                    let _ =
+                        memory_translation_table.write(address as usize, &[value], address_space);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs one cycle, starting the next instruction if the last one is done
+    fn cycle(&self, state: &mut ProcessorState) {
+        if state.pending_cycles.is_empty() {
+            self.start_instruction(state);
+        }
+
+        let Some(cycle) = state.pending_cycles.pop_front() else {
+            return;
+        };
+
+        if self.config.cycle_accurate {
+            self.perform_bus_cycle(state, cycle);
+
+            if state.pending_cycles.is_empty() {
+                if let Some(instruction) = state.pending_instruction.take() {
+                    self.interpret_instruction(state, instruction);
+                }
+            }
+        }
+    }
 }
 
-impl Component for M6502 {}
+impl Component for M6502 {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.pending_cycles.clear();
+        state.pending_instruction = None;
+        state.read_modify_write_value = None;
+        self.interrupt_lines.nmi.store(false, Ordering::Relaxed);
+        self.interrupt_lines.reset.store(true, Ordering::Relaxed);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        let state = self.state.lock().unwrap();
+        let registers = &state.registers;
+
+        rmpv::ext::to_value(M6502Snapshot {
+            accumulator: registers.accumulator,
+            index_registers: registers.index_registers,
+            stack_pointer: registers.stack_pointer,
+            flags: registers.flags.bits(),
+            program: registers.program,
+            trapped: state.trapped,
+            pending_cycles: state.pending_cycles.clone(),
+            pending_instruction: state.pending_instruction,
+            read_modify_write_value: state.read_modify_write_value,
+            irq: self.interrupt_lines.irq.load(Ordering::Relaxed),
+            nmi: self.interrupt_lines.nmi.load(Ordering::Relaxed),
+            reset: self.interrupt_lines.reset.load(Ordering::Relaxed),
+        })
+        .unwrap()
+    }
+
+    fn load_snapshot(&self, snapshot: rmpv::Value) {
+        let snapshot: M6502Snapshot = rmpv::ext::from_value(snapshot).unwrap();
+        let mut state = self.state.lock().unwrap();
+
+        state.registers = M6502Registers {
+            stack_pointer: snapshot.stack_pointer,
+            accumulator: snapshot.accumulator,
+            index_registers: snapshot.index_registers,
+            flags: BitFlags::from_bits_truncate(snapshot.flags),
+            program: snapshot.program,
+        };
+        state.trapped = snapshot.trapped;
+        state.pending_cycles = snapshot.pending_cycles;
+        state.pending_instruction = snapshot.pending_instruction;
+        state.read_modify_write_value = snapshot.read_modify_write_value;
+        self.interrupt_lines
+            .irq
+            .store(snapshot.irq, Ordering::Relaxed);
+        self.interrupt_lines
+            .nmi
+            .store(snapshot.nmi, Ordering::Relaxed);
+        self.interrupt_lines
+            .reset
+            .store(snapshot.reset, Ordering::Relaxed);
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
 
 impl FromConfig for M6502 {
     type Config = M6502Config;
@@ -105,12 +486,99 @@ impl FromConfig for M6502 {
             .set_component(Self {
                 config,
                 state: Mutex::default(),
+                // Power on goes through the reset sequence
+                interrupt_lines: Arc::new(InterruptLines {
+                    irq: AtomicU32::new(0),
+                    nmi: AtomicBool::new(false),
+                    reset: AtomicBool::new(true),
+                }),
                 memory_translation_table: OnceLock::default(),
+                debug: ProcessorDebugState::default(),
             })
-            .set_schedulable(frequency, [], []);
+            .set_schedulable(frequency, [], [])
+            .set_debuggable();
     }
 }
 
 impl SchedulableComponent for M6502 {
-    fn run(&self, period: u64) {}
+    fn run(&self, mut period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        // The real chip only stops on a read, but writes are rare enough that waiting right away is close enough
+        if let Some(wait_states) = &self.config.wait_states {
+            period -= wait_states.take(period);
+        }
+
+        for _ in 0..period {
+            // Only a reset gets it going again
+            if state.trapped.is_some() && !self.interrupt_lines.reset.load(Ordering::Relaxed) {
+                return;
+            }
+
+            if state.pending_cycles.is_empty()
+                && self.debug.should_stop(state.registers.program as usize)
+            {
+                return;
+            }
+
+            self.cycle(&mut state);
+        }
+    }
+}
+
+impl DebuggableProcessor for M6502 {
+    fn registers(&self) -> &'static [ProcessorRegister] {
+        DEBUG_REGISTERS
+    }
+
+    fn read_register(&self, index: usize) -> u64 {
+        let registers = &self.state.lock().unwrap().registers;
+
+        match index {
+            0 => registers.accumulator as u64,
+            1 | 2 => registers.index_registers[index - 1] as u64,
+            3 => registers.stack_pointer as u64,
+            4 => registers.flags.bits() as u64,
+            5 => registers.program as u64,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&self, index: usize, value: u64) {
+        let registers = &mut self.state.lock().unwrap().registers;
+
+        match index {
+            0 => registers.accumulator = value as u8,
+            1 | 2 => registers.index_registers[index - 1] = value as u8,
+            3 => registers.stack_pointer = value as u8,
+            4 => registers.flags = BitFlags::from_bits_truncate(value as u8),
+            5 => {
+                // Putting the program counter somewhere by hand replaces the trip through the reset vector
+                self.interrupt_lines.reset.store(false, Ordering::Relaxed);
+                registers.program = value as u16;
+            }
+            _ => {}
+        }
+    }
+
+    fn address_space(&self) -> AddressSpaceId {
+        self.config.assigned_address_space
+    }
+
+    fn step(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        // Halting only happens between instructions, so this always starts a fresh one
+        loop {
+            self.cycle(&mut state);
+
+            if state.pending_cycles.is_empty() || state.trapped.is_some() {
+                break;
+            }
+        }
+    }
+
+    fn debug_state(&self) -> &ProcessorDebugState {
+        &self.debug
+    }
 }
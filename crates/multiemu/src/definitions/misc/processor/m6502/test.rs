@@ -1,16 +1,30 @@
 use indexmap::IndexMap;
 
 use super::instruction::{AddressingMode, M6502InstructionSet, M6502InstructionSetSpecifier};
+use super::{
+    cycle::{bus_cycles, BusCycle},
+    FlagRegister, M6502Config, M6502Interrupt, M6502Kind, UndocumentedOpcodes, M6502,
+};
 use crate::definitions::misc::processor::m6502::decode::decode_instruction;
 use crate::{
+    component::Component,
+    definitions::misc::io::InterruptConnection,
     definitions::misc::memory::standard::{
         StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents,
     },
-    machine::Machine,
+    machine::{
+        test_machine::{BusExpectation, TestMachine, TestMachineBuilder},
+        Machine,
+    },
     memory::AddressSpaceId,
     rom::{manager::RomManager, system::GameSystem},
 };
-use std::{borrow::Cow, collections::HashMap, sync::Arc};
+use num::rational::Ratio;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc},
+};
 
 const ADDRESS_SPACE: AddressSpaceId = 0;
 
@@ -34,7 +48,7 @@ fn m6502_instruction_decode() {
             (
                 M6502InstructionSet {
                     specifier: M6502InstructionSetSpecifier::Ora,
-                    addressing_mode: Some(AddressingMode::Immediate(0xff)),
+                    addressing_mode: Some(AddressingMode::XIndexedZeroPageIndirect(0xff)),
                 },
                 2,
             ),
@@ -336,3 +350,312 @@ fn m6502_instruction_decode() {
         );
     }
 }
+
+fn m6502_machine(undocumented_opcodes: UndocumentedOpcodes) -> (TestMachine, Arc<M6502>) {
+    m6502_machine_with(
+        M6502Kind::M6502 {
+            quirk_broken_ror: false,
+        },
+        undocumented_opcodes,
+        false,
+    )
+}
+
+fn m6502_machine_with(
+    kind: M6502Kind,
+    undocumented_opcodes: UndocumentedOpcodes,
+    cycle_accurate: bool,
+) -> (TestMachine, Arc<M6502>) {
+    let (builder, processor) = TestMachineBuilder::new()
+        .bus(ADDRESS_SPACE, 16)
+        .scratch_ram(ADDRESS_SPACE, 0..0x10000, 0xff)
+        .component::<M6502>(M6502Config {
+            kind,
+            frequency: Ratio::from_integer(1),
+            assigned_address_space: ADDRESS_SPACE,
+            undocumented_opcodes,
+            magic_constant: 0xee,
+            cycle_accurate,
+            wait_states: None,
+        });
+    let machine = builder.build();
+    let processor = machine.component::<M6502>(processor);
+
+    (machine, processor)
+}
+
+#[test]
+fn m6502_undocumented_opcode_modes() {
+    let ane = M6502InstructionSet {
+        specifier: M6502InstructionSetSpecifier::Xaa,
+        addressing_mode: Some(AddressingMode::Immediate(0xff)),
+    };
+
+    let (_machine, processor) = m6502_machine(UndocumentedOpcodes::Full);
+    {
+        let mut state = processor.state.lock().unwrap();
+        state.registers.accumulator = 0x01;
+        state.registers.index_registers[0] = 0x0f;
+        processor.interpret_instruction(&mut state, ane);
+        // (0x01 | 0xee) & 0x0f & 0xff
+        assert_eq!(state.registers.accumulator, 0x0f);
+    }
+
+    let (_machine, processor) = m6502_machine(UndocumentedOpcodes::Nop);
+    {
+        let mut state = processor.state.lock().unwrap();
+        processor.interpret_instruction(&mut state, ane);
+        assert_eq!(state.registers.accumulator, 0x00);
+    }
+
+    let (_machine, processor) = m6502_machine(UndocumentedOpcodes::Trap);
+    {
+        let mut state = processor.state.lock().unwrap();
+        processor.interpret_instruction(&mut state, ane);
+    }
+    assert_eq!(processor.trapped(), Some(ane));
+}
+
+#[test]
+fn m6502_indexed_dummy_reads() {
+    let (machine, processor) = m6502_machine(UndocumentedOpcodes::Full);
+    let mut state = processor.state.lock().unwrap();
+    state.registers.index_registers[0] = 0x10;
+
+    // LDA $20f8,X crosses into the next page so it reads from the wrong one first
+    let lda = M6502InstructionSet {
+        specifier: M6502InstructionSetSpecifier::Lda,
+        addressing_mode: Some(AddressingMode::XIndexedAbsolute(0x20f8)),
+    };
+    assert_eq!(
+        bus_cycles(
+            &lda,
+            &state.registers,
+            &machine.machine.memory_translation_table,
+            ADDRESS_SPACE
+        ),
+        vec![
+            BusCycle::Fetch(0),
+            BusCycle::Fetch(1),
+            BusCycle::Fetch(2),
+            BusCycle::DummyRead(0x2008),
+            BusCycle::Read(0x2108),
+        ]
+    );
+
+    // INC $2000,X always takes the extra read and writes the old value back first
+    let inc = M6502InstructionSet {
+        specifier: M6502InstructionSetSpecifier::Inc,
+        addressing_mode: Some(AddressingMode::XIndexedAbsolute(0x2000)),
+    };
+    assert_eq!(
+        bus_cycles(
+            &inc,
+            &state.registers,
+            &machine.machine.memory_translation_table,
+            ADDRESS_SPACE
+        ),
+        vec![
+            BusCycle::Fetch(0),
+            BusCycle::Fetch(1),
+            BusCycle::Fetch(2),
+            BusCycle::DummyRead(0x2010),
+            BusCycle::Read(0x2010),
+            BusCycle::DummyWrite(0x2010),
+            BusCycle::Write(0x2010),
+        ]
+    );
+}
+
+#[test]
+fn m6502_read_modify_write() {
+    let (mut machine, processor) = m6502_machine(UndocumentedOpcodes::Full);
+
+    let mut watch = machine
+        .machine
+        .memory_translation_table
+        .watch(ADDRESS_SPACE, 0x10..0x11);
+    // The unmodified value is written back before the real write
+    machine.expect_bus([
+        BusExpectation::read(ADDRESS_SPACE, 0x10, &[0xff]),
+        BusExpectation::write(ADDRESS_SPACE, 0x10, &[0xff]),
+        BusExpectation::write(ADDRESS_SPACE, 0x10, &[0x00]),
+    ]);
+    let mut state = processor.state.lock().unwrap();
+
+    processor.interpret_instruction(
+        &mut state,
+        M6502InstructionSet {
+            specifier: M6502InstructionSetSpecifier::Inc,
+            addressing_mode: Some(AddressingMode::ZeroPage(0x10)),
+        },
+    );
+    machine.verify_bus();
+
+    assert_eq!(machine.peek(ADDRESS_SPACE, 0x10, 1), [0x00]);
+    assert!(state.registers.flags.contains(FlagRegister::Zero));
+    assert_eq!(watch.generation(), 2);
+    assert!(watch.changed());
+
+    state.registers.accumulator = 0x81;
+    processor.interpret_instruction(
+        &mut state,
+        M6502InstructionSet {
+            specifier: M6502InstructionSetSpecifier::Asl,
+            addressing_mode: Some(AddressingMode::Accumulator),
+        },
+    );
+    assert_eq!(state.registers.accumulator, 0x02);
+    assert!(state.registers.flags.contains(FlagRegister::Carry));
+    assert!(!watch.changed());
+}
+
+#[test]
+fn m6502_decimal_mode() {
+    let adc = |value| M6502InstructionSet {
+        specifier: M6502InstructionSetSpecifier::Adc,
+        addressing_mode: Some(AddressingMode::Immediate(value)),
+    };
+    let sbc = |value| M6502InstructionSet {
+        specifier: M6502InstructionSetSpecifier::Sbc,
+        addressing_mode: Some(AddressingMode::Immediate(value)),
+    };
+
+    let (_machine, processor) = m6502_machine(UndocumentedOpcodes::Full);
+    let mut state = processor.state.lock().unwrap();
+    state.registers.flags = FlagRegister::Decimal.into();
+
+    state.registers.accumulator = 0x45;
+    processor.interpret_instruction(&mut state, adc(0x38));
+    assert_eq!(state.registers.accumulator, 0x83);
+    assert!(!state.registers.flags.contains(FlagRegister::Carry));
+
+    state.registers.accumulator = 0x99;
+    processor.interpret_instruction(&mut state, adc(0x01));
+    assert_eq!(state.registers.accumulator, 0x00);
+    assert!(state.registers.flags.contains(FlagRegister::Carry));
+
+    state.registers.accumulator = 0x32;
+    state.registers.flags.insert(FlagRegister::Carry);
+    processor.interpret_instruction(&mut state, sbc(0x19));
+    assert_eq!(state.registers.accumulator, 0x13);
+    assert!(state.registers.flags.contains(FlagRegister::Carry));
+
+    state.registers.accumulator = 0x00;
+    processor.interpret_instruction(&mut state, sbc(0x01));
+    assert_eq!(state.registers.accumulator, 0x99);
+    assert!(!state.registers.flags.contains(FlagRegister::Carry));
+    drop(state);
+
+    // The NES chip keeps the flag but adds in binary regardless
+    let (_machine, processor) =
+        m6502_machine_with(M6502Kind::R2A03, UndocumentedOpcodes::Full, false);
+    let mut state = processor.state.lock().unwrap();
+    state.registers.flags = FlagRegister::Decimal.into();
+    state.registers.accumulator = 0x45;
+    processor.interpret_instruction(&mut state, adc(0x38));
+    assert_eq!(state.registers.accumulator, 0x7d);
+}
+
+#[test]
+fn m6502_interrupt_vectors() {
+    let (machine, processor) = m6502_machine(UndocumentedOpcodes::Full);
+    machine.load(ADDRESS_SPACE, 0xfffa, &[0x00, 0x90, 0x00, 0x80, 0x00, 0xa0]);
+    // CLI
+    machine.load(ADDRESS_SPACE, 0x8000, &[0x58]);
+
+    let irq = processor.interrupt_connection(M6502Interrupt::Irq);
+    let nmi = processor.interrupt_connection(M6502Interrupt::Nmi);
+    let mut state = processor.state.lock().unwrap();
+
+    // Power on goes through the reset vector with interrupts disabled
+    processor.start_instruction(&mut state);
+    assert_eq!(state.registers.program, 0x8000);
+    assert_eq!(state.registers.stack_pointer, 0xfc);
+    assert!(state
+        .registers
+        .flags
+        .contains(FlagRegister::InterruptDisable));
+    assert_eq!(state.pending_cycles.len(), 7);
+
+    // Masked until the CLI runs
+    irq.set_interrupt(true);
+    processor.start_instruction(&mut state);
+    assert_eq!(state.registers.program, 0x8001);
+
+    processor.start_instruction(&mut state);
+    assert_eq!(state.registers.program, 0xa000);
+    assert_eq!(state.registers.stack_pointer, 0xf9);
+    // Return address and then the flags, with the break flag clear
+    assert_eq!(machine.peek(ADDRESS_SPACE, 0x01fa, 3), [0x20, 0x01, 0x80]);
+
+    // Still disabled, but nmi doesn't care
+    nmi.set_interrupt(true);
+    processor.start_instruction(&mut state);
+    assert_eq!(state.registers.program, 0x9000);
+}
+
+#[test]
+fn m6502_snapshot_round_trip() {
+    let (machine, processor) = m6502_machine(UndocumentedOpcodes::Full);
+    machine.load(ADDRESS_SPACE, 0xfffc, &[0x00, 0x80]);
+    {
+        let mut state = processor.state.lock().unwrap();
+        processor.start_instruction(&mut state);
+        state.registers.accumulator = 0x42;
+    }
+    processor
+        .interrupt_connection(M6502Interrupt::Irq)
+        .set_interrupt(true);
+    let snapshot = processor.save_snapshot();
+
+    let (_machine, restored) = m6502_machine(UndocumentedOpcodes::Full);
+    restored.load_snapshot(snapshot);
+
+    let state = restored.state.lock().unwrap();
+    assert_eq!(state.registers.program, 0x8000);
+    assert_eq!(state.registers.accumulator, 0x42);
+    // The reset sequence is still running, and the power on reset it came from is gone
+    assert_eq!(state.pending_cycles.len(), 7);
+    assert!(!restored.interrupt_lines.reset.load(Ordering::Relaxed));
+    assert_eq!(restored.interrupt_lines.irq.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn m6502_cycle_accurate_read_modify_write() {
+    let (mut machine, processor) = m6502_machine_with(
+        M6502Kind::M6502 {
+            quirk_broken_ror: false,
+        },
+        UndocumentedOpcodes::Full,
+        true,
+    );
+    let mut state = processor.state.lock().unwrap();
+
+    let inc = M6502InstructionSet {
+        specifier: M6502InstructionSetSpecifier::Inc,
+        addressing_mode: Some(AddressingMode::ZeroPage(0x10)),
+    };
+    state.pending_cycles = bus_cycles(
+        &inc,
+        &state.registers,
+        &machine.machine.memory_translation_table,
+        ADDRESS_SPACE,
+    )
+    .into();
+    state.pending_instruction = Some(inc);
+
+    // The read comes first, then the value it got is written back before the modified one
+    machine.expect_bus([
+        BusExpectation::read(ADDRESS_SPACE, 0x10, &[0xff]),
+        BusExpectation::write(ADDRESS_SPACE, 0x10, &[0xff]),
+        BusExpectation::write(ADDRESS_SPACE, 0x10, &[0x00]),
+    ]);
+    while !state.pending_cycles.is_empty() {
+        processor.cycle(&mut state);
+    }
+    machine.verify_bus();
+
+    assert_eq!(machine.peek(ADDRESS_SPACE, 0x10, 1), [0x00]);
+    assert!(state.registers.flags.contains(FlagRegister::Zero));
+}
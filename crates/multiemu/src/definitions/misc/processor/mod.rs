@@ -1,2 +1,37 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 //pub mod i8080;
 pub mod m6502;
+pub mod sm83;
+pub mod z80;
+
+/// Cycles another chip has taken the bus away from a processor for, like the VIC-II pulling RDY low on the C64
+///
+/// The chip adds to it whenever it steals the bus, and the processor sits out that many of its own cycles before
+/// carrying on
+#[derive(Debug, Default)]
+pub struct WaitStates {
+    pending: AtomicU64,
+}
+
+impl WaitStates {
+    pub fn insert(&self, cycles: u64) {
+        self.pending.fetch_add(cycles, Ordering::Relaxed);
+    }
+
+    /// Takes up to `cycles` of the pending wait states, returning how many there were
+    pub fn take(&self, cycles: u64) -> u64 {
+        let taken = self
+            .pending
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |pending| {
+                Some(pending - pending.min(cycles))
+            })
+            .unwrap();
+
+        taken.min(cycles)
+    }
+
+    pub fn pending(&self) -> u64 {
+        self.pending.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,283 @@
+use super::instruction::{
+    AluOperation, Condition, IndirectAddress, Operand8, Register16, RotateOperation,
+    Sm83InstructionSet, StackRegister,
+};
+use crate::memory::{AddressSpaceId, MemoryTranslationTable};
+use bitvec::{field::BitField, prelude::Msb0, view::BitView};
+use std::ops::Range;
+
+const INSTRUCTION_IDENTIFIER: Range<usize> = 0..2;
+const ARGUMENT: Range<usize> = 2..5;
+/// The top two bits of [ARGUMENT], which picks a register pair
+const REGISTER_PAIR: Range<usize> = 2..4;
+/// The bottom bit of [ARGUMENT], which often picks between two related instructions
+const ARGUMENT_LOW: usize = 4;
+const SECONDARY_INSTRUCTION_IDENTIFIER: Range<usize> = 5..8;
+
+const PREFIX: u8 = 0xcb;
+
+/// Decodes the instruction at `cursor`, returning it and how many bytes it took
+///
+/// Every byte decodes to something, the holes in the table become [Sm83InstructionSet::Illegal]
+pub fn decode_instruction(
+    cursor: u16,
+    address_space: AddressSpaceId,
+    memory_translation_table: &MemoryTranslationTable,
+) -> (Sm83InstructionSet, u8) {
+    let read_u8 = |offset: u16| {
+        let mut value = [0];
+        let _ = memory_translation_table.read(
+            cursor.wrapping_add(offset) as usize,
+            &mut value,
+            address_space,
+        );
+        value[0]
+    };
+    // Operands are little endian, and fetched in one go where the bus allows it
+    let read_u16 = || {
+        let mut value = [0; 2];
+        let _ = memory_translation_table.read(
+            cursor.wrapping_add(1) as usize,
+            &mut value,
+            address_space,
+        );
+        u16::from_le_bytes(value)
+    };
+
+    let opcode = read_u8(0);
+    if opcode == PREFIX {
+        return (decode_prefixed_instruction(read_u8(1)), 2);
+    }
+
+    let opcode_bits = opcode.view_bits::<Msb0>();
+    let argument = opcode_bits[ARGUMENT].load::<u8>();
+    let register_pair = opcode_bits[REGISTER_PAIR].load::<u8>();
+    let argument_low = opcode_bits[ARGUMENT_LOW];
+    let secondary_instruction_identifier =
+        opcode_bits[SECONDARY_INSTRUCTION_IDENTIFIER].load::<u8>();
+
+    let register16 = Register16::from_repr(register_pair).unwrap();
+    let condition = Condition::from_repr(argument & 0b11);
+
+    match opcode_bits[INSTRUCTION_IDENTIFIER].load::<u8>() {
+        0b00 => match secondary_instruction_identifier {
+            0b000 => match argument {
+                0b000 => (Sm83InstructionSet::Nop, 1),
+                0b001 => (Sm83InstructionSet::LdAbsoluteFromSp(read_u16()), 3),
+                // stop is followed by a byte the chip skips over
+                0b010 => (Sm83InstructionSet::Stop, 2),
+                0b011 => (
+                    Sm83InstructionSet::Jr {
+                        condition: None,
+                        offset: read_u8(1) as i8,
+                    },
+                    2,
+                ),
+                _ => (
+                    Sm83InstructionSet::Jr {
+                        condition,
+                        offset: read_u8(1) as i8,
+                    },
+                    2,
+                ),
+            },
+            0b001 => {
+                if argument_low {
+                    (Sm83InstructionSet::AddHl(register16), 1)
+                } else {
+                    (
+                        Sm83InstructionSet::Ld16 {
+                            destination: register16,
+                            value: read_u16(),
+                        },
+                        3,
+                    )
+                }
+            }
+            0b010 => {
+                let address = IndirectAddress::from_repr(register_pair).unwrap();
+
+                if argument_low {
+                    (Sm83InstructionSet::LdAFromIndirect(address), 1)
+                } else {
+                    (Sm83InstructionSet::LdIndirectFromA(address), 1)
+                }
+            }
+            0b011 => {
+                if argument_low {
+                    (Sm83InstructionSet::Dec16(register16), 1)
+                } else {
+                    (Sm83InstructionSet::Inc16(register16), 1)
+                }
+            }
+            0b100 => (Sm83InstructionSet::Inc(Operand8::from_id(argument)), 1),
+            0b101 => (Sm83InstructionSet::Dec(Operand8::from_id(argument)), 1),
+            0b110 => (
+                Sm83InstructionSet::Ld {
+                    destination: Operand8::from_id(argument),
+                    source: Operand8::Immediate(read_u8(1)),
+                },
+                2,
+            ),
+            0b111 => (
+                match argument {
+                    0b000 => Sm83InstructionSet::Rlca,
+                    0b001 => Sm83InstructionSet::Rrca,
+                    0b010 => Sm83InstructionSet::Rla,
+                    0b011 => Sm83InstructionSet::Rra,
+                    0b100 => Sm83InstructionSet::Daa,
+                    0b101 => Sm83InstructionSet::Cpl,
+                    0b110 => Sm83InstructionSet::Scf,
+                    _ => Sm83InstructionSet::Ccf,
+                },
+                1,
+            ),
+            _ => unreachable!(),
+        },
+        0b01 => {
+            let destination = Operand8::from_id(argument);
+            let source = Operand8::from_id(secondary_instruction_identifier);
+
+            // Where ld (hl), (hl) would be
+            if destination == Operand8::HlIndirect && source == Operand8::HlIndirect {
+                (Sm83InstructionSet::Halt, 1)
+            } else {
+                (
+                    Sm83InstructionSet::Ld {
+                        destination,
+                        source,
+                    },
+                    1,
+                )
+            }
+        }
+        0b10 => (
+            Sm83InstructionSet::Alu {
+                operation: AluOperation::from_repr(argument).unwrap(),
+                operand: Operand8::from_id(secondary_instruction_identifier),
+            },
+            1,
+        ),
+        0b11 => match secondary_instruction_identifier {
+            0b000 => match argument {
+                0b100 => (Sm83InstructionSet::LdHighFromA(read_u8(1)), 2),
+                0b101 => (Sm83InstructionSet::AddSp(read_u8(1) as i8), 2),
+                0b110 => (Sm83InstructionSet::LdAFromHigh(read_u8(1)), 2),
+                0b111 => (Sm83InstructionSet::LdHlFromSpOffset(read_u8(1) as i8), 2),
+                _ => (Sm83InstructionSet::Ret { condition }, 1),
+            },
+            0b001 => {
+                if argument_low {
+                    (
+                        match register_pair {
+                            0b00 => Sm83InstructionSet::Ret { condition: None },
+                            0b01 => Sm83InstructionSet::Reti,
+                            0b10 => Sm83InstructionSet::JpHl,
+                            _ => Sm83InstructionSet::LdSpFromHl,
+                        },
+                        1,
+                    )
+                } else {
+                    (
+                        Sm83InstructionSet::Pop(StackRegister::from_repr(register_pair).unwrap()),
+                        1,
+                    )
+                }
+            }
+            0b010 => match argument {
+                0b100 => (Sm83InstructionSet::LdHighCFromA, 1),
+                0b101 => (Sm83InstructionSet::LdAbsoluteFromA(read_u16()), 3),
+                0b110 => (Sm83InstructionSet::LdAFromHighC, 1),
+                0b111 => (Sm83InstructionSet::LdAFromAbsolute(read_u16()), 3),
+                _ => (
+                    Sm83InstructionSet::Jp {
+                        condition,
+                        address: read_u16(),
+                    },
+                    3,
+                ),
+            },
+            0b011 => match argument {
+                0b000 => (
+                    Sm83InstructionSet::Jp {
+                        condition: None,
+                        address: read_u16(),
+                    },
+                    3,
+                ),
+                0b110 => (Sm83InstructionSet::Di, 1),
+                0b111 => (Sm83InstructionSet::Ei, 1),
+                // 0xcb was handled above, the Z80's out, in and exchanges were cut
+                _ => (Sm83InstructionSet::Illegal(opcode), 1),
+            },
+            0b100 => match argument {
+                0b000..=0b011 => (
+                    Sm83InstructionSet::Call {
+                        condition,
+                        address: read_u16(),
+                    },
+                    3,
+                ),
+                _ => (Sm83InstructionSet::Illegal(opcode), 1),
+            },
+            0b101 => {
+                if argument_low {
+                    if register_pair == 0b00 {
+                        (
+                            Sm83InstructionSet::Call {
+                                condition: None,
+                                address: read_u16(),
+                            },
+                            3,
+                        )
+                    } else {
+                        // The Z80's index register and extended prefixes
+                        (Sm83InstructionSet::Illegal(opcode), 1)
+                    }
+                } else {
+                    (
+                        Sm83InstructionSet::Push(StackRegister::from_repr(register_pair).unwrap()),
+                        1,
+                    )
+                }
+            }
+            0b110 => (
+                Sm83InstructionSet::Alu {
+                    operation: AluOperation::from_repr(argument).unwrap(),
+                    operand: Operand8::Immediate(read_u8(1)),
+                },
+                2,
+            ),
+            0b111 => (Sm83InstructionSet::Rst(argument * 8), 1),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// The instructions behind 0xcb, which are all laid out the same way
+fn decode_prefixed_instruction(opcode: u8) -> Sm83InstructionSet {
+    let opcode_bits = opcode.view_bits::<Msb0>();
+    let argument = opcode_bits[ARGUMENT].load::<u8>();
+    let operand = Operand8::from_id(opcode_bits[SECONDARY_INSTRUCTION_IDENTIFIER].load::<u8>());
+
+    match opcode_bits[INSTRUCTION_IDENTIFIER].load::<u8>() {
+        0b00 => Sm83InstructionSet::Rotate {
+            operation: RotateOperation::from_repr(argument).unwrap(),
+            operand,
+        },
+        0b01 => Sm83InstructionSet::Bit {
+            bit: argument,
+            operand,
+        },
+        0b10 => Sm83InstructionSet::Res {
+            bit: argument,
+            operand,
+        },
+        0b11 => Sm83InstructionSet::Set {
+            bit: argument,
+            operand,
+        },
+        _ => unreachable!(),
+    }
+}
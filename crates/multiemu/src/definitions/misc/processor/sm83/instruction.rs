@@ -0,0 +1,288 @@
+use crate::processor::{InstructionSet, InstructionTextRepresentation};
+use std::{borrow::Cow, fmt::Display};
+use strum::FromRepr;
+
+// https://gbdev.io/gb-opcodes/optables/
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum Register8 {
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    /// Slot 6 is (hl), see [Operand8::from_id]
+    A = 7,
+}
+
+/// Where an 8 bit value comes from or goes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operand8 {
+    Register(Register8),
+    /// The byte hl points at
+    HlIndirect,
+    Immediate(u8),
+}
+
+impl Operand8 {
+    /// The 3 bit register field most instructions have
+    pub fn from_id(id: u8) -> Self {
+        Register8::from_repr(id)
+            .map(Operand8::Register)
+            .unwrap_or(Operand8::HlIndirect)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum Register16 {
+    Bc,
+    De,
+    Hl,
+    Sp,
+}
+
+/// push and pop swap the stack pointer for af
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum StackRegister {
+    Bc,
+    De,
+    Hl,
+    Af,
+}
+
+/// The pointers ld a can go through, hl stepping along afterwards
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum IndirectAddress {
+    Bc,
+    De,
+    HlIncrement,
+    HlDecrement,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum Condition {
+    NotZero,
+    Zero,
+    NotCarry,
+    Carry,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum AluOperation {
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    And,
+    Xor,
+    Or,
+    Cp,
+}
+
+/// The shifts and rotates behind the 0xcb prefix
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum RotateOperation {
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    Swap,
+    Srl,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sm83InstructionSet {
+    Nop,
+    Stop,
+    Halt,
+    Di,
+    Ei,
+    Ld {
+        destination: Operand8,
+        source: Operand8,
+    },
+    LdIndirectFromA(IndirectAddress),
+    LdAFromIndirect(IndirectAddress),
+    LdAbsoluteFromA(u16),
+    LdAFromAbsolute(u16),
+    /// ldh, the bottom of a 0xff00 address
+    LdHighFromA(u8),
+    LdAFromHigh(u8),
+    /// ldh with c as the bottom of the 0xff00 address
+    LdHighCFromA,
+    LdAFromHighC,
+    Ld16 {
+        destination: Register16,
+        value: u16,
+    },
+    LdAbsoluteFromSp(u16),
+    LdSpFromHl,
+    LdHlFromSpOffset(i8),
+    Push(StackRegister),
+    Pop(StackRegister),
+    Alu {
+        operation: AluOperation,
+        operand: Operand8,
+    },
+    Inc(Operand8),
+    Dec(Operand8),
+    Inc16(Register16),
+    Dec16(Register16),
+    AddHl(Register16),
+    AddSp(i8),
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+    Jp {
+        condition: Option<Condition>,
+        address: u16,
+    },
+    JpHl,
+    Jr {
+        condition: Option<Condition>,
+        offset: i8,
+    },
+    Call {
+        condition: Option<Condition>,
+        address: u16,
+    },
+    Ret {
+        condition: Option<Condition>,
+    },
+    Reti,
+    Rst(u8),
+    Rotate {
+        operation: RotateOperation,
+        operand: Operand8,
+    },
+    Bit {
+        bit: u8,
+        operand: Operand8,
+    },
+    Res {
+        bit: u8,
+        operand: Operand8,
+    },
+    Set {
+        bit: u8,
+        operand: Operand8,
+    },
+    /// One of the holes in the opcode table, which lock the chip up until it is reset
+    Illegal(u8),
+}
+
+impl Display for Operand8 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand8::Register(register) => write!(f, "{}", name(register)),
+            Operand8::HlIndirect => write!(f, "(hl)"),
+            Operand8::Immediate(value) => write!(f, "{:#04x}", value),
+        }
+    }
+}
+
+impl Display for IndirectAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndirectAddress::Bc => write!(f, "(bc)"),
+            IndirectAddress::De => write!(f, "(de)"),
+            IndirectAddress::HlIncrement => write!(f, "(hl+)"),
+            IndirectAddress::HlDecrement => write!(f, "(hl-)"),
+        }
+    }
+}
+
+/// Lowercase name of a register or operation, which is all the mnemonics need
+fn name(value: impl std::fmt::Debug) -> String {
+    format!("{:?}", value).to_lowercase()
+}
+
+fn condition_name(condition: Condition) -> &'static str {
+    match condition {
+        Condition::NotZero => "nz",
+        Condition::Zero => "z",
+        Condition::NotCarry => "nc",
+        Condition::Carry => "c",
+    }
+}
+
+/// What goes between the mnemonic and the address of a conditional jump
+fn condition_prefix(condition: Option<Condition>) -> String {
+    condition
+        .map(|condition| format!("{}, ", condition_name(condition)))
+        .unwrap_or_default()
+}
+
+impl InstructionSet for Sm83InstructionSet {
+    fn to_text_representation(&self) -> InstructionTextRepresentation {
+        let mnemonic = match *self {
+            Self::Ld {
+                destination,
+                source,
+            } => format!("ld {}, {}", destination, source),
+            Self::LdIndirectFromA(address) => format!("ld {}, a", address),
+            Self::LdAFromIndirect(address) => format!("ld a, {}", address),
+            Self::LdAbsoluteFromA(address) => format!("ld ({:#06x}), a", address),
+            Self::LdAFromAbsolute(address) => format!("ld a, ({:#06x})", address),
+            Self::LdHighFromA(offset) => format!("ldh ({:#04x}), a", offset),
+            Self::LdAFromHigh(offset) => format!("ldh a, ({:#04x})", offset),
+            Self::LdHighCFromA => "ldh (c), a".to_string(),
+            Self::LdAFromHighC => "ldh a, (c)".to_string(),
+            Self::Ld16 { destination, value } => {
+                format!("ld {}, {:#06x}", name(destination), value)
+            }
+            Self::LdAbsoluteFromSp(address) => format!("ld ({:#06x}), sp", address),
+            Self::LdSpFromHl => "ld sp, hl".to_string(),
+            Self::LdHlFromSpOffset(offset) => format!("ld hl, sp{:+}", offset),
+            Self::Push(register) => format!("push {}", name(register)),
+            Self::Pop(register) => format!("pop {}", name(register)),
+            Self::Alu { operation, operand } => format!("{} {}", name(operation), operand),
+            Self::Inc(operand) => format!("inc {}", operand),
+            Self::Dec(operand) => format!("dec {}", operand),
+            Self::Inc16(register) => format!("inc {}", name(register)),
+            Self::Dec16(register) => format!("dec {}", name(register)),
+            Self::AddHl(register) => format!("add hl, {}", name(register)),
+            Self::AddSp(offset) => format!("add sp, {}", offset),
+            Self::Jp { condition, address } => {
+                format!("jp {}{:#06x}", condition_prefix(condition), address)
+            }
+            Self::JpHl => "jp hl".to_string(),
+            Self::Jr { condition, offset } => {
+                format!("jr {}{}", condition_prefix(condition), offset)
+            }
+            Self::Call { condition, address } => {
+                format!("call {}{:#06x}", condition_prefix(condition), address)
+            }
+            Self::Ret { condition: None } => "ret".to_string(),
+            Self::Ret {
+                condition: Some(condition),
+            } => format!("ret {}", condition_name(condition)),
+            Self::Rst(vector) => format!("rst {:#04x}", vector),
+            Self::Rotate { operation, operand } => format!("{} {}", name(operation), operand),
+            Self::Bit { bit, operand } => format!("bit {}, {}", bit, operand),
+            Self::Res { bit, operand } => format!("res {}, {}", bit, operand),
+            Self::Set { bit, operand } => format!("set {}, {}", bit, operand),
+            Self::Illegal(opcode) => format!("illegal {:#04x}", opcode),
+            _ => name(self),
+        };
+
+        InstructionTextRepresentation {
+            instruction_mnemonic: Cow::Owned(mnemonic),
+        }
+    }
+}
@@ -0,0 +1,571 @@
+use super::{
+    instruction::{
+        AluOperation, Condition, IndirectAddress, Operand8, Register16, Register8, RotateOperation,
+        Sm83InstructionSet, StackRegister,
+    },
+    ExecutionState, FlagRegister, ProcessorState, Sm83,
+};
+use enumflags2::BitFlags;
+
+// NOTE: The SM83 ignores all memory errors, like the bus it sits on
+
+/// Where ldh and friends point
+const HIGH_PAGE: u16 = 0xff00;
+
+impl Sm83 {
+    fn read(&self, address: u16) -> u8 {
+        let mut value = [0];
+        let _ = self.memory_translation_table.get().unwrap().read(
+            address as usize,
+            &mut value,
+            self.config.assigned_address_space,
+        );
+
+        value[0]
+    }
+
+    fn write(&self, address: u16, value: u8) {
+        let _ = self.memory_translation_table.get().unwrap().write(
+            address as usize,
+            &[value],
+            self.config.assigned_address_space,
+        );
+    }
+
+    pub(super) fn push(&self, state: &mut ProcessorState, value: u16) {
+        let [high, low] = value.to_be_bytes();
+
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_sub(1);
+        self.write(state.registers.stack_pointer, high);
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_sub(1);
+        self.write(state.registers.stack_pointer, low);
+    }
+
+    fn pop(&self, state: &mut ProcessorState) -> u16 {
+        let low = self.read(state.registers.stack_pointer);
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_add(1);
+        let high = self.read(state.registers.stack_pointer);
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_add(1);
+
+        u16::from_be_bytes([high, low])
+    }
+
+    fn load_operand(&self, state: &ProcessorState, operand: Operand8) -> u8 {
+        match operand {
+            Operand8::Register(Register8::A) => state.registers.accumulator,
+            Operand8::Register(register) => state.registers.general[register as usize],
+            Operand8::HlIndirect => self.read(state.registers.register16(Register16::Hl)),
+            Operand8::Immediate(value) => value,
+        }
+    }
+
+    fn store_operand(&self, state: &mut ProcessorState, operand: Operand8, value: u8) {
+        match operand {
+            Operand8::Register(Register8::A) => state.registers.accumulator = value,
+            Operand8::Register(register) => state.registers.general[register as usize] = value,
+            Operand8::HlIndirect => self.write(state.registers.register16(Register16::Hl), value),
+            Operand8::Immediate(_) => unreachable!("Immediates can't be written to"),
+        }
+    }
+
+    /// Resolves the pointer, stepping hl along if it asks
+    fn indirect_address(&self, state: &mut ProcessorState, address: IndirectAddress) -> u16 {
+        match address {
+            IndirectAddress::Bc => state.registers.register16(Register16::Bc),
+            IndirectAddress::De => state.registers.register16(Register16::De),
+            IndirectAddress::HlIncrement | IndirectAddress::HlDecrement => {
+                let hl = state.registers.register16(Register16::Hl);
+                let stepped = if address == IndirectAddress::HlIncrement {
+                    hl.wrapping_add(1)
+                } else {
+                    hl.wrapping_sub(1)
+                };
+                state.registers.set_register16(Register16::Hl, stepped);
+
+                hl
+            }
+        }
+    }
+
+    /// Runs an instruction whose bytes have already been skipped over, returning the cycles it took
+    pub(super) fn interpret_instruction(
+        &self,
+        state: &mut ProcessorState,
+        instruction: Sm83InstructionSet,
+    ) -> u32 {
+        // Touching (hl) costs a memory cycle on top of the register version
+        let memory_cycles = |operand: Operand8| match operand {
+            Operand8::Register(_) => 0,
+            Operand8::HlIndirect | Operand8::Immediate(_) => 4,
+        };
+
+        match instruction {
+            Sm83InstructionSet::Nop => 4,
+            Sm83InstructionSet::Stop => {
+                state.execution_state = ExecutionState::Stopped;
+                4
+            }
+            Sm83InstructionSet::Halt => {
+                if !state.interrupt_master_enable && self.interrupt_registers.pending() != 0 {
+                    // Doesn't halt at all, and fails to move past the next opcode
+                    state.halt_bug = true;
+                } else {
+                    state.execution_state = ExecutionState::Halted;
+                }
+                4
+            }
+            Sm83InstructionSet::Di => {
+                state.interrupt_master_enable = false;
+                state.enable_interrupts_pending = false;
+                4
+            }
+            Sm83InstructionSet::Ei => {
+                state.enable_interrupts_pending = true;
+                4
+            }
+            Sm83InstructionSet::Ld {
+                destination,
+                source,
+            } => {
+                let value = self.load_operand(state, source);
+                self.store_operand(state, destination, value);
+
+                4 + memory_cycles(destination) + memory_cycles(source)
+            }
+            Sm83InstructionSet::LdIndirectFromA(address) => {
+                let address = self.indirect_address(state, address);
+                self.write(address, state.registers.accumulator);
+                8
+            }
+            Sm83InstructionSet::LdAFromIndirect(address) => {
+                let address = self.indirect_address(state, address);
+                state.registers.accumulator = self.read(address);
+                8
+            }
+            Sm83InstructionSet::LdAbsoluteFromA(address) => {
+                self.write(address, state.registers.accumulator);
+                16
+            }
+            Sm83InstructionSet::LdAFromAbsolute(address) => {
+                state.registers.accumulator = self.read(address);
+                16
+            }
+            Sm83InstructionSet::LdHighFromA(offset) => {
+                self.write(HIGH_PAGE | offset as u16, state.registers.accumulator);
+                12
+            }
+            Sm83InstructionSet::LdAFromHigh(offset) => {
+                state.registers.accumulator = self.read(HIGH_PAGE | offset as u16);
+                12
+            }
+            Sm83InstructionSet::LdHighCFromA => {
+                let address = HIGH_PAGE | state.registers.general[Register8::C as usize] as u16;
+                self.write(address, state.registers.accumulator);
+                8
+            }
+            Sm83InstructionSet::LdAFromHighC => {
+                let address = HIGH_PAGE | state.registers.general[Register8::C as usize] as u16;
+                state.registers.accumulator = self.read(address);
+                8
+            }
+            Sm83InstructionSet::Ld16 { destination, value } => {
+                state.registers.set_register16(destination, value);
+                12
+            }
+            Sm83InstructionSet::LdAbsoluteFromSp(address) => {
+                let [high, low] = state.registers.stack_pointer.to_be_bytes();
+                self.write(address, low);
+                self.write(address.wrapping_add(1), high);
+                20
+            }
+            Sm83InstructionSet::LdSpFromHl => {
+                state.registers.stack_pointer = state.registers.register16(Register16::Hl);
+                8
+            }
+            Sm83InstructionSet::LdHlFromSpOffset(offset) => {
+                let value = state.registers.stack_pointer_offset(offset);
+                state.registers.set_register16(Register16::Hl, value);
+                12
+            }
+            Sm83InstructionSet::Push(register) => {
+                let value = state.registers.stack_register(register);
+                self.push(state, value);
+                16
+            }
+            Sm83InstructionSet::Pop(register) => {
+                let value = self.pop(state);
+                state.registers.set_stack_register(register, value);
+                12
+            }
+            Sm83InstructionSet::Alu { operation, operand } => {
+                let value = self.load_operand(state, operand);
+                state.registers.alu(operation, value);
+
+                4 + memory_cycles(operand)
+            }
+            Sm83InstructionSet::Inc(operand) | Sm83InstructionSet::Dec(operand) => {
+                let value = self.load_operand(state, operand);
+                let increment = matches!(instruction, Sm83InstructionSet::Inc(_));
+                let result = if increment {
+                    value.wrapping_add(1)
+                } else {
+                    value.wrapping_sub(1)
+                };
+                self.store_operand(state, operand, result);
+
+                // Carry is left alone
+                let flags = &mut state.registers.flags;
+                flags.set(FlagRegister::Zero, result == 0);
+                flags.set(FlagRegister::Subtract, !increment);
+                flags.set(
+                    FlagRegister::HalfCarry,
+                    if increment {
+                        value & 0x0f == 0x0f
+                    } else {
+                        value & 0x0f == 0x00
+                    },
+                );
+
+                4 + memory_cycles(operand) * 2
+            }
+            Sm83InstructionSet::Inc16(register) => {
+                let value = state.registers.register16(register).wrapping_add(1);
+                state.registers.set_register16(register, value);
+                8
+            }
+            Sm83InstructionSet::Dec16(register) => {
+                let value = state.registers.register16(register).wrapping_sub(1);
+                state.registers.set_register16(register, value);
+                8
+            }
+            Sm83InstructionSet::AddHl(register) => {
+                let hl = state.registers.register16(Register16::Hl);
+                let value = state.registers.register16(register);
+                let (result, carry) = hl.overflowing_add(value);
+                state.registers.set_register16(Register16::Hl, result);
+
+                // Zero is left alone
+                let flags = &mut state.registers.flags;
+                flags.remove(FlagRegister::Subtract);
+                flags.set(
+                    FlagRegister::HalfCarry,
+                    (hl & 0x0fff) + (value & 0x0fff) > 0x0fff,
+                );
+                flags.set(FlagRegister::Carry, carry);
+                8
+            }
+            Sm83InstructionSet::AddSp(offset) => {
+                state.registers.stack_pointer = state.registers.stack_pointer_offset(offset);
+                16
+            }
+            Sm83InstructionSet::Rlca
+            | Sm83InstructionSet::Rrca
+            | Sm83InstructionSet::Rla
+            | Sm83InstructionSet::Rra => {
+                let operation = match instruction {
+                    Sm83InstructionSet::Rlca => RotateOperation::Rlc,
+                    Sm83InstructionSet::Rrca => RotateOperation::Rrc,
+                    Sm83InstructionSet::Rla => RotateOperation::Rl,
+                    _ => RotateOperation::Rr,
+                };
+                state.registers.accumulator = state
+                    .registers
+                    .rotate(operation, state.registers.accumulator);
+                // Unlike the prefixed versions these always clear zero
+                state.registers.flags.remove(FlagRegister::Zero);
+                4
+            }
+            Sm83InstructionSet::Daa => {
+                state.registers.daa();
+                4
+            }
+            Sm83InstructionSet::Cpl => {
+                state.registers.accumulator = !state.registers.accumulator;
+                state
+                    .registers
+                    .flags
+                    .insert(FlagRegister::Subtract | FlagRegister::HalfCarry);
+                4
+            }
+            Sm83InstructionSet::Scf | Sm83InstructionSet::Ccf => {
+                let flags = &mut state.registers.flags;
+                let carry =
+                    instruction == Sm83InstructionSet::Scf || !flags.contains(FlagRegister::Carry);
+                flags.remove(FlagRegister::Subtract | FlagRegister::HalfCarry);
+                flags.set(FlagRegister::Carry, carry);
+                4
+            }
+            Sm83InstructionSet::Jp { condition, address } => {
+                if !state.registers.condition(condition) {
+                    return 12;
+                }
+
+                state.registers.program = address;
+                16
+            }
+            Sm83InstructionSet::JpHl => {
+                state.registers.program = state.registers.register16(Register16::Hl);
+                4
+            }
+            Sm83InstructionSet::Jr { condition, offset } => {
+                if !state.registers.condition(condition) {
+                    return 8;
+                }
+
+                state.registers.program =
+                    state.registers.program.wrapping_add_signed(offset as i16);
+                12
+            }
+            Sm83InstructionSet::Call { condition, address } => {
+                if !state.registers.condition(condition) {
+                    return 12;
+                }
+
+                let program = state.registers.program;
+                self.push(state, program);
+                state.registers.program = address;
+                24
+            }
+            Sm83InstructionSet::Ret { condition } => {
+                if !state.registers.condition(condition) {
+                    return 8;
+                }
+
+                state.registers.program = self.pop(state);
+                // Checking the condition costs a cycle
+                if condition.is_some() {
+                    20
+                } else {
+                    16
+                }
+            }
+            Sm83InstructionSet::Reti => {
+                state.registers.program = self.pop(state);
+                // Unlike ei this takes effect right away
+                state.interrupt_master_enable = true;
+                16
+            }
+            Sm83InstructionSet::Rst(vector) => {
+                let program = state.registers.program;
+                self.push(state, program);
+                state.registers.program = vector as u16;
+                16
+            }
+            Sm83InstructionSet::Rotate { operation, operand } => {
+                let value = self.load_operand(state, operand);
+                let result = state.registers.rotate(operation, value);
+                self.store_operand(state, operand, result);
+
+                8 + memory_cycles(operand) * 2
+            }
+            Sm83InstructionSet::Bit { bit, operand } => {
+                let value = self.load_operand(state, operand);
+
+                // Carry is left alone
+                let flags = &mut state.registers.flags;
+                flags.set(FlagRegister::Zero, value & (1 << bit) == 0);
+                flags.remove(FlagRegister::Subtract);
+                flags.insert(FlagRegister::HalfCarry);
+
+                8 + memory_cycles(operand)
+            }
+            Sm83InstructionSet::Res { bit, operand } | Sm83InstructionSet::Set { bit, operand } => {
+                let value = self.load_operand(state, operand);
+                let result = if matches!(instruction, Sm83InstructionSet::Set { .. }) {
+                    value | (1 << bit)
+                } else {
+                    value & !(1 << bit)
+                };
+                self.store_operand(state, operand, result);
+
+                8 + memory_cycles(operand) * 2
+            }
+            Sm83InstructionSet::Illegal(opcode) => {
+                tracing::error!(
+                    "Processor locked up on illegal opcode {:#04x} at {:#06x}",
+                    opcode,
+                    state.registers.program.wrapping_sub(1)
+                );
+                state.execution_state = ExecutionState::Locked;
+                4
+            }
+        }
+    }
+}
+
+impl super::Sm83Registers {
+    fn register16(&self, register: Register16) -> u16 {
+        match register {
+            Register16::Sp => self.stack_pointer,
+            _ => {
+                let index = register as usize * 2;
+                u16::from_be_bytes([self.general[index], self.general[index + 1]])
+            }
+        }
+    }
+
+    fn set_register16(&mut self, register: Register16, value: u16) {
+        match register {
+            Register16::Sp => self.stack_pointer = value,
+            _ => {
+                let index = register as usize * 2;
+                [self.general[index], self.general[index + 1]] = value.to_be_bytes();
+            }
+        }
+    }
+
+    fn stack_register(&self, register: StackRegister) -> u16 {
+        match register {
+            StackRegister::Bc => self.register16(Register16::Bc),
+            StackRegister::De => self.register16(Register16::De),
+            StackRegister::Hl => self.register16(Register16::Hl),
+            StackRegister::Af => u16::from_be_bytes([self.accumulator, self.flags.bits()]),
+        }
+    }
+
+    fn set_stack_register(&mut self, register: StackRegister, value: u16) {
+        match register {
+            StackRegister::Bc => self.set_register16(Register16::Bc, value),
+            StackRegister::De => self.set_register16(Register16::De, value),
+            StackRegister::Hl => self.set_register16(Register16::Hl, value),
+            StackRegister::Af => {
+                let [accumulator, flags] = value.to_be_bytes();
+                self.accumulator = accumulator;
+                // The bottom nibble of f doesn't exist
+                self.flags = BitFlags::from_bits_truncate(flags);
+            }
+        }
+    }
+
+    fn condition(&self, condition: Option<Condition>) -> bool {
+        match condition {
+            None => true,
+            Some(Condition::NotZero) => !self.flags.contains(FlagRegister::Zero),
+            Some(Condition::Zero) => self.flags.contains(FlagRegister::Zero),
+            Some(Condition::NotCarry) => !self.flags.contains(FlagRegister::Carry),
+            Some(Condition::Carry) => self.flags.contains(FlagRegister::Carry),
+        }
+    }
+
+    /// sp plus a signed byte, with the flags coming from adding the byte to the bottom of sp unsigned
+    fn stack_pointer_offset(&mut self, offset: i8) -> u16 {
+        let low = self.stack_pointer & 0xff;
+        let unsigned = offset as u8 as u16;
+
+        self.flags = BitFlags::empty();
+        self.flags.set(
+            FlagRegister::HalfCarry,
+            (low & 0x0f) + (unsigned & 0x0f) > 0x0f,
+        );
+        self.flags.set(FlagRegister::Carry, low + unsigned > 0xff);
+
+        self.stack_pointer.wrapping_add_signed(offset as i16)
+    }
+
+    fn alu(&mut self, operation: AluOperation, value: u8) {
+        let accumulator = self.accumulator;
+        let carry = self.flags.contains(FlagRegister::Carry) as u8;
+
+        let (result, half_carry, carry) = match operation {
+            AluOperation::Add | AluOperation::Adc => {
+                let carry = if operation == AluOperation::Adc {
+                    carry
+                } else {
+                    0
+                };
+                let result = accumulator as u16 + value as u16 + carry as u16;
+
+                (
+                    result as u8,
+                    (accumulator & 0x0f) + (value & 0x0f) + carry > 0x0f,
+                    result > 0xff,
+                )
+            }
+            AluOperation::Sub | AluOperation::Sbc | AluOperation::Cp => {
+                let carry = if operation == AluOperation::Sbc {
+                    carry
+                } else {
+                    0
+                };
+                let result = accumulator as i16 - value as i16 - carry as i16;
+
+                (
+                    result as u8,
+                    ((accumulator & 0x0f) as i16) - ((value & 0x0f) as i16) - (carry as i16) < 0,
+                    result < 0,
+                )
+            }
+            AluOperation::And => (accumulator & value, true, false),
+            AluOperation::Xor => (accumulator ^ value, false, false),
+            AluOperation::Or => (accumulator | value, false, false),
+        };
+
+        self.flags = BitFlags::empty();
+        self.flags.set(FlagRegister::Zero, result == 0);
+        self.flags.set(
+            FlagRegister::Subtract,
+            matches!(
+                operation,
+                AluOperation::Sub | AluOperation::Sbc | AluOperation::Cp
+            ),
+        );
+        self.flags.set(FlagRegister::HalfCarry, half_carry);
+        self.flags.set(FlagRegister::Carry, carry);
+
+        // Compare is a subtraction that throws the result away
+        if operation != AluOperation::Cp {
+            self.accumulator = result;
+        }
+    }
+
+    fn rotate(&mut self, operation: RotateOperation, value: u8) -> u8 {
+        let carry = self.flags.contains(FlagRegister::Carry) as u8;
+
+        let (result, carry_out) = match operation {
+            RotateOperation::Rlc => (value.rotate_left(1), value & 0x80 != 0),
+            RotateOperation::Rrc => (value.rotate_right(1), value & 0x01 != 0),
+            RotateOperation::Rl => ((value << 1) | carry, value & 0x80 != 0),
+            RotateOperation::Rr => ((value >> 1) | (carry << 7), value & 0x01 != 0),
+            RotateOperation::Sla => (value << 1, value & 0x80 != 0),
+            RotateOperation::Sra => ((value >> 1) | (value & 0x80), value & 0x01 != 0),
+            RotateOperation::Swap => (value.rotate_left(4), false),
+            RotateOperation::Srl => (value >> 1, value & 0x01 != 0),
+        };
+
+        self.flags = BitFlags::empty();
+        self.flags.set(FlagRegister::Zero, result == 0);
+        self.flags.set(FlagRegister::Carry, carry_out);
+
+        result
+    }
+
+    /// Fixes up the accumulator after adding or subtracting two binary coded decimal numbers
+    fn daa(&mut self) {
+        let mut accumulator = self.accumulator;
+        let subtract = self.flags.contains(FlagRegister::Subtract);
+        let mut carry = self.flags.contains(FlagRegister::Carry);
+        let half_carry = self.flags.contains(FlagRegister::HalfCarry);
+
+        if subtract {
+            if carry {
+                accumulator = accumulator.wrapping_sub(0x60);
+            }
+            if half_carry {
+                accumulator = accumulator.wrapping_sub(0x06);
+            }
+        } else {
+            if carry || accumulator > 0x99 {
+                accumulator = accumulator.wrapping_add(0x60);
+                carry = true;
+            }
+            if half_carry || accumulator & 0x0f > 0x09 {
+                accumulator = accumulator.wrapping_add(0x06);
+            }
+        }
+
+        self.accumulator = accumulator;
+        self.flags.set(FlagRegister::Zero, accumulator == 0);
+        self.flags.remove(FlagRegister::HalfCarry);
+        self.flags.set(FlagRegister::Carry, carry);
+    }
+}
@@ -0,0 +1,542 @@
+use crate::{
+    component::{
+        memory::MemoryComponent,
+        processor::{DebuggableProcessor, ProcessorDebugState, ProcessorRegister},
+        schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::io::InterruptConnection,
+    machine::ComponentBuilder,
+    memory::{
+        AddressSpaceId, MemoryTranslationTable, PreviewMemoryRecord, ReadMemoryRecord,
+        WriteMemoryRecord,
+    },
+};
+use decode::decode_instruction;
+use enumflags2::{bitflags, BitFlags};
+use num::rational::Ratio;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc, Mutex, OnceLock,
+};
+
+pub mod decode;
+pub mod instruction;
+pub mod interpret;
+
+#[cfg(test)]
+pub mod test;
+
+/// Which interrupts are being asked for, one bit per [Sm83Interrupt]
+pub const INTERRUPT_FLAG_ADDRESS: usize = 0xff0f;
+/// Which interrupts the program wants, one bit per [Sm83Interrupt]
+pub const INTERRUPT_ENABLE_ADDRESS: usize = 0xffff;
+/// Only the bottom five bits of the interrupt registers exist
+const INTERRUPT_MASK: u8 = 0b0001_1111;
+/// Cycles taken to push the program counter and jump to an interrupt handler
+const INTERRUPT_DISPATCH_CYCLES: u32 = 20;
+
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum FlagRegister {
+    Zero = 0b1000_0000,
+    /// Set if the last operation was a subtraction, only daa looks at it
+    Subtract = 0b0100_0000,
+    /// Carry out of bit 3, only daa looks at it
+    HalfCarry = 0b0010_0000,
+    Carry = 0b0001_0000,
+}
+
+/// The interrupt sources, in priority order, each of which has a handler at 0x40 plus 8 times its bit
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sm83Interrupt {
+    VBlank,
+    LcdStat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Sm83Interrupt {
+    fn mask(&self) -> u8 {
+        1 << *self as u8
+    }
+}
+
+/// The order [DebuggableProcessor] exposes [Sm83Registers] in, as pairs
+const DEBUG_REGISTERS: &[ProcessorRegister] = &[
+    ProcessorRegister {
+        name: "af",
+        size: 2,
+        generic: Some("flags"),
+    },
+    ProcessorRegister {
+        name: "bc",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "de",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "hl",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "sp",
+        size: 2,
+        generic: Some("sp"),
+    },
+    ProcessorRegister {
+        name: "pc",
+        size: 2,
+        generic: Some("pc"),
+    },
+];
+
+#[derive(Debug, Clone)]
+pub struct Sm83Registers {
+    accumulator: u8,
+    flags: BitFlags<FlagRegister>,
+    /// b, c, d, e, h and l, in the order [instruction::Register8] numbers them
+    general: [u8; 6],
+    stack_pointer: u16,
+    program: u16,
+}
+
+impl Default for Sm83Registers {
+    // What the DMG boot ROM leaves behind, since nothing about a cold start is defined
+    fn default() -> Self {
+        Self {
+            accumulator: 0x01,
+            flags: FlagRegister::Zero | FlagRegister::HalfCarry | FlagRegister::Carry,
+            general: [0x00, 0x13, 0x00, 0xd8, 0x01, 0x4d],
+            stack_pointer: 0xfffe,
+            program: 0x0100,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Sm83Config {
+    /// The clock instruction timings are counted in, 4194304 hz on the Game Boy
+    pub frequency: Ratio<u64>,
+    pub assigned_address_space: AddressSpaceId,
+    /// Start at 0 for a boot ROM to run, otherwise start at 0x100 looking like the boot ROM already did
+    pub boot_rom: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum ExecutionState {
+    #[default]
+    Running,
+    /// Waiting for an enabled interrupt, even with interrupts disabled
+    Halted,
+    /// Low power mode, treated like [ExecutionState::Halted] since only the joypad wakes a real chip from it
+    Stopped,
+    /// Ran an illegal opcode, nothing but a reset gets it going again
+    Locked,
+}
+
+#[derive(Debug, Default)]
+struct ProcessorState {
+    registers: Sm83Registers,
+    /// ime, if interrupts are taken at all
+    interrupt_master_enable: bool,
+    /// ei only takes effect after the instruction following it
+    enable_interrupts_pending: bool,
+    execution_state: ExecutionState,
+    /// Halting with an interrupt already waiting and ime off makes the next opcode get read twice
+    halt_bug: bool,
+    /// Cycles left of the current instruction
+    pending_cycles: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Sm83Snapshot {
+    af: u16,
+    bc: u16,
+    de: u16,
+    hl: u16,
+    stack_pointer: u16,
+    program: u16,
+    interrupt_master_enable: bool,
+    enable_interrupts_pending: bool,
+    execution_state: ExecutionState,
+    halt_bug: bool,
+    pending_cycles: u32,
+    interrupt_enable: u8,
+    interrupt_flag: u8,
+}
+
+/// IE and IF, which peripherals set bits in from the outside while the processor runs
+#[derive(Debug, Default)]
+struct InterruptRegisters {
+    enable: AtomicU8,
+    flag: AtomicU8,
+}
+
+impl InterruptRegisters {
+    /// Interrupts that are both asked for and enabled
+    fn pending(&self) -> u8 {
+        self.enable.load(Ordering::Relaxed) & self.flag.load(Ordering::Relaxed) & INTERRUPT_MASK
+    }
+}
+
+/// Sets an interrupt's bit in IF on a rising edge, the program clears it
+#[derive(Debug)]
+struct Sm83InterruptRequest {
+    interrupt_registers: Arc<InterruptRegisters>,
+    interrupt: Sm83Interrupt,
+}
+
+impl InterruptConnection for Sm83InterruptRequest {
+    fn set_interrupt(&self, raised: bool) {
+        if raised {
+            self.interrupt_registers
+                .flag
+                .fetch_or(self.interrupt.mask(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// The Sharp SM83, the Game Boy's processor, an 8080 with some of the Z80 and some of its own
+///
+/// The interrupt registers live in the processor and are mapped at [INTERRUPT_FLAG_ADDRESS] and
+/// [INTERRUPT_ENABLE_ADDRESS]. Instructions happen all at once on their first cycle
+#[derive(Debug)]
+pub struct Sm83 {
+    config: Sm83Config,
+    state: Mutex<ProcessorState>,
+    /// Outside the state so reads of them by the processor itself don't deadlock
+    interrupt_registers: Arc<InterruptRegisters>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+    debug: ProcessorDebugState,
+}
+
+impl Sm83 {
+    /// What a peripheral raises to ask for an interrupt
+    pub fn interrupt_connection(&self, interrupt: Sm83Interrupt) -> Arc<dyn InterruptConnection> {
+        Arc::new(Sm83InterruptRequest {
+            interrupt_registers: self.interrupt_registers.clone(),
+            interrupt,
+        })
+    }
+
+    fn initial_state(&self) -> ProcessorState {
+        let mut state = ProcessorState::default();
+        if self.config.boot_rom {
+            state.registers = Sm83Registers {
+                accumulator: 0,
+                flags: BitFlags::empty(),
+                general: [0; 6],
+                stack_pointer: 0,
+                program: 0,
+            };
+        }
+
+        state
+    }
+
+    /// Takes an interrupt if one is due, returning the cycles it took
+    fn service_interrupts(&self, state: &mut ProcessorState) -> Option<u32> {
+        let pending = self.interrupt_registers.pending();
+        if pending == 0 {
+            return None;
+        }
+
+        // Any enabled interrupt wakes the chip, even if it isn't going to be taken
+        let woke = matches!(
+            state.execution_state,
+            ExecutionState::Halted | ExecutionState::Stopped
+        );
+        if woke {
+            state.execution_state = ExecutionState::Running;
+        }
+
+        if !state.interrupt_master_enable {
+            return woke.then_some(4);
+        }
+
+        let bit = pending.trailing_zeros() as u8;
+        self.interrupt_registers
+            .flag
+            .fetch_and(!(1 << bit), Ordering::Relaxed);
+        state.interrupt_master_enable = false;
+
+        let program = state.registers.program;
+        self.push(state, program);
+        state.registers.program = 0x40 + bit as u16 * 8;
+
+        Some(INTERRUPT_DISPATCH_CYCLES + if woke { 4 } else { 0 })
+    }
+
+    /// Runs an instruction or takes an interrupt, returning the cycles it took
+    fn step_instruction(&self, state: &mut ProcessorState) -> u32 {
+        if let Some(cycles) = self.service_interrupts(state) {
+            return cycles;
+        }
+
+        match state.execution_state {
+            ExecutionState::Running => {}
+            ExecutionState::Halted | ExecutionState::Stopped | ExecutionState::Locked => return 4,
+        }
+
+        if state.enable_interrupts_pending {
+            state.enable_interrupts_pending = false;
+            state.interrupt_master_enable = true;
+        }
+
+        let (instruction, length) = decode_instruction(
+            state.registers.program,
+            self.config.assigned_address_space,
+            self.memory_translation_table.get().unwrap(),
+        );
+
+        tracing::trace!(
+            "Decoded instruction {:?} from {:#06x}",
+            instruction,
+            state.registers.program
+        );
+
+        let length = if state.halt_bug {
+            state.halt_bug = false;
+            length - 1
+        } else {
+            length
+        };
+        state.registers.program = state.registers.program.wrapping_add(length as u16);
+
+        self.interpret_instruction(state, instruction)
+    }
+}
+
+impl Component for Sm83 {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = self.initial_state();
+        self.interrupt_registers.enable.store(0, Ordering::Relaxed);
+        self.interrupt_registers.flag.store(0, Ordering::Relaxed);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        let state = self.state.lock().unwrap();
+        let registers = &state.registers;
+        let pair = |high: u8, low: u8| u16::from_be_bytes([high, low]);
+
+        rmpv::ext::to_value(Sm83Snapshot {
+            af: pair(registers.accumulator, registers.flags.bits()),
+            bc: pair(registers.general[0], registers.general[1]),
+            de: pair(registers.general[2], registers.general[3]),
+            hl: pair(registers.general[4], registers.general[5]),
+            stack_pointer: registers.stack_pointer,
+            program: registers.program,
+            interrupt_master_enable: state.interrupt_master_enable,
+            enable_interrupts_pending: state.enable_interrupts_pending,
+            execution_state: state.execution_state,
+            halt_bug: state.halt_bug,
+            pending_cycles: state.pending_cycles,
+            interrupt_enable: self.interrupt_registers.enable.load(Ordering::Relaxed),
+            interrupt_flag: self.interrupt_registers.flag.load(Ordering::Relaxed),
+        })
+        .unwrap()
+    }
+
+    fn load_snapshot(&self, snapshot: rmpv::Value) {
+        let snapshot: Sm83Snapshot = rmpv::ext::from_value(snapshot).unwrap();
+        let mut state = self.state.lock().unwrap();
+
+        let [accumulator, flags] = snapshot.af.to_be_bytes();
+        let [b, c] = snapshot.bc.to_be_bytes();
+        let [d, e] = snapshot.de.to_be_bytes();
+        let [h, l] = snapshot.hl.to_be_bytes();
+        state.registers = Sm83Registers {
+            accumulator,
+            flags: BitFlags::from_bits_truncate(flags),
+            general: [b, c, d, e, h, l],
+            stack_pointer: snapshot.stack_pointer,
+            program: snapshot.program,
+        };
+        state.interrupt_master_enable = snapshot.interrupt_master_enable;
+        state.enable_interrupts_pending = snapshot.enable_interrupts_pending;
+        state.execution_state = snapshot.execution_state;
+        state.halt_bug = snapshot.halt_bug;
+        state.pending_cycles = snapshot.pending_cycles;
+        self.interrupt_registers
+            .enable
+            .store(snapshot.interrupt_enable, Ordering::Relaxed);
+        self.interrupt_registers
+            .flag
+            .store(snapshot.interrupt_flag, Ordering::Relaxed);
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for Sm83 {
+    type Config = Sm83Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let frequency = config.frequency;
+        let assigned_address_space = config.assigned_address_space;
+
+        let component = Self {
+            config,
+            state: Mutex::default(),
+            interrupt_registers: Arc::default(),
+            memory_translation_table: OnceLock::default(),
+            debug: ProcessorDebugState::default(),
+        };
+        *component.state.lock().unwrap() = component.initial_state();
+
+        component_builder
+            .set_component(component)
+            .set_schedulable(frequency, [], [])
+            .set_memory([
+                (
+                    assigned_address_space,
+                    INTERRUPT_FLAG_ADDRESS..INTERRUPT_FLAG_ADDRESS + 1,
+                ),
+                (
+                    assigned_address_space,
+                    INTERRUPT_ENABLE_ADDRESS..INTERRUPT_ENABLE_ADDRESS + 1,
+                ),
+            ])
+            .set_debuggable();
+    }
+}
+
+impl SchedulableComponent for Sm83 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..period {
+            if state.pending_cycles == 0 {
+                if state.execution_state == ExecutionState::Locked {
+                    return;
+                }
+
+                if self.debug.should_stop(state.registers.program as usize) {
+                    return;
+                }
+
+                state.pending_cycles = self.step_instruction(&mut state);
+            }
+
+            state.pending_cycles -= 1;
+        }
+    }
+}
+
+impl MemoryComponent for Sm83 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = match address {
+                // The unused bits read as set
+                INTERRUPT_FLAG_ADDRESS => {
+                    self.interrupt_registers.flag.load(Ordering::Relaxed) | !INTERRUPT_MASK
+                }
+                // All 8 bits of this one can be written and read back, only 5 of them do anything
+                _ => self.interrupt_registers.enable.load(Ordering::Relaxed),
+            };
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter()) {
+            match address {
+                INTERRUPT_FLAG_ADDRESS => self
+                    .interrupt_registers
+                    .flag
+                    .store(*byte & INTERRUPT_MASK, Ordering::Relaxed),
+                _ => self
+                    .interrupt_registers
+                    .enable
+                    .store(*byte, Ordering::Relaxed),
+            }
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        self.read_memory(address, buffer, address_space, &mut RangeMap::default());
+    }
+}
+
+impl DebuggableProcessor for Sm83 {
+    fn registers(&self) -> &'static [ProcessorRegister] {
+        DEBUG_REGISTERS
+    }
+
+    fn read_register(&self, index: usize) -> u64 {
+        let registers = &self.state.lock().unwrap().registers;
+
+        match index {
+            0 => u16::from_be_bytes([registers.accumulator, registers.flags.bits()]) as u64,
+            1..=3 => u16::from_be_bytes([
+                registers.general[(index - 1) * 2],
+                registers.general[(index - 1) * 2 + 1],
+            ]) as u64,
+            4 => registers.stack_pointer as u64,
+            5 => registers.program as u64,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&self, index: usize, value: u64) {
+        let registers = &mut self.state.lock().unwrap().registers;
+        let [high, low] = (value as u16).to_be_bytes();
+
+        match index {
+            0 => {
+                registers.accumulator = high;
+                registers.flags = BitFlags::from_bits_truncate(low);
+            }
+            1..=3 => {
+                registers.general[(index - 1) * 2] = high;
+                registers.general[(index - 1) * 2 + 1] = low;
+            }
+            4 => registers.stack_pointer = value as u16,
+            5 => registers.program = value as u16,
+            _ => {}
+        }
+    }
+
+    fn address_space(&self) -> AddressSpaceId {
+        self.config.assigned_address_space
+    }
+
+    fn step(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.pending_cycles = self.step_instruction(&mut state);
+    }
+
+    fn debug_state(&self) -> &ProcessorDebugState {
+        &self.debug
+    }
+}
@@ -0,0 +1,333 @@
+use super::{
+    decode::decode_instruction,
+    instruction::{
+        AluOperation, Condition, IndirectAddress, Operand8, Register16, Register8, RotateOperation,
+        Sm83InstructionSet, StackRegister,
+    },
+    FlagRegister, Sm83, Sm83Config, Sm83Interrupt, INTERRUPT_ENABLE_ADDRESS,
+};
+use crate::{
+    component::processor::DebuggableProcessor,
+    definitions::misc::{
+        io::InterruptConnection,
+        memory::standard::{StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents},
+    },
+    machine::{
+        test_machine::{TestMachine, TestMachineBuilder},
+        Machine,
+    },
+    memory::AddressSpaceId,
+    rom::{manager::RomManager, system::GameSystem},
+};
+use indexmap::IndexMap;
+use num::rational::Ratio;
+use std::{borrow::Cow, sync::Arc};
+
+const ADDRESS_SPACE: AddressSpaceId = 0;
+
+#[test]
+fn sm83_instruction_decode() {
+    let rom_manager = Arc::new(RomManager::new(None).unwrap());
+
+    let map: IndexMap<_, _> = IndexMap::from_iter([
+        ([0x00].as_slice(), (Sm83InstructionSet::Nop, 1)),
+        (
+            [0x01, 0x34, 0x12].as_slice(),
+            (
+                Sm83InstructionSet::Ld16 {
+                    destination: Register16::Bc,
+                    value: 0x1234,
+                },
+                3,
+            ),
+        ),
+        (
+            [0x08, 0x00, 0xc0].as_slice(),
+            (Sm83InstructionSet::LdAbsoluteFromSp(0xc000), 3),
+        ),
+        ([0x10, 0x00].as_slice(), (Sm83InstructionSet::Stop, 2)),
+        (
+            [0x20, 0xfe].as_slice(),
+            (
+                Sm83InstructionSet::Jr {
+                    condition: Some(Condition::NotZero),
+                    offset: -2,
+                },
+                2,
+            ),
+        ),
+        (
+            [0x22].as_slice(),
+            (
+                Sm83InstructionSet::LdIndirectFromA(IndirectAddress::HlIncrement),
+                1,
+            ),
+        ),
+        (
+            [0x3a].as_slice(),
+            (
+                Sm83InstructionSet::LdAFromIndirect(IndirectAddress::HlDecrement),
+                1,
+            ),
+        ),
+        (
+            [0x36, 0xff].as_slice(),
+            (
+                Sm83InstructionSet::Ld {
+                    destination: Operand8::HlIndirect,
+                    source: Operand8::Immediate(0xff),
+                },
+                2,
+            ),
+        ),
+        ([0x27].as_slice(), (Sm83InstructionSet::Daa, 1)),
+        (
+            [0x41].as_slice(),
+            (
+                Sm83InstructionSet::Ld {
+                    destination: Operand8::Register(Register8::B),
+                    source: Operand8::Register(Register8::C),
+                },
+                1,
+            ),
+        ),
+        ([0x76].as_slice(), (Sm83InstructionSet::Halt, 1)),
+        (
+            [0x96].as_slice(),
+            (
+                Sm83InstructionSet::Alu {
+                    operation: AluOperation::Sub,
+                    operand: Operand8::HlIndirect,
+                },
+                1,
+            ),
+        ),
+        (
+            [0xc0].as_slice(),
+            (
+                Sm83InstructionSet::Ret {
+                    condition: Some(Condition::NotZero),
+                },
+                1,
+            ),
+        ),
+        (
+            [0xc3, 0x50, 0x01].as_slice(),
+            (
+                Sm83InstructionSet::Jp {
+                    condition: None,
+                    address: 0x0150,
+                },
+                3,
+            ),
+        ),
+        (
+            [0xcd, 0x00, 0x40].as_slice(),
+            (
+                Sm83InstructionSet::Call {
+                    condition: None,
+                    address: 0x4000,
+                },
+                3,
+            ),
+        ),
+        ([0xd3].as_slice(), (Sm83InstructionSet::Illegal(0xd3), 1)),
+        ([0xd9].as_slice(), (Sm83InstructionSet::Reti, 1)),
+        (
+            [0xe0, 0x40].as_slice(),
+            (Sm83InstructionSet::LdHighFromA(0x40), 2),
+        ),
+        ([0xe2].as_slice(), (Sm83InstructionSet::LdHighCFromA, 1)),
+        (
+            [0xe8, 0x80].as_slice(),
+            (Sm83InstructionSet::AddSp(-128), 2),
+        ),
+        ([0xe9].as_slice(), (Sm83InstructionSet::JpHl, 1)),
+        (
+            [0xf1].as_slice(),
+            (Sm83InstructionSet::Pop(StackRegister::Af), 1),
+        ),
+        (
+            [0xf8, 0x02].as_slice(),
+            (Sm83InstructionSet::LdHlFromSpOffset(2), 2),
+        ),
+        (
+            [0xfa, 0x00, 0xff].as_slice(),
+            (Sm83InstructionSet::LdAFromAbsolute(0xff00), 3),
+        ),
+        (
+            [0xfe, 0x90].as_slice(),
+            (
+                Sm83InstructionSet::Alu {
+                    operation: AluOperation::Cp,
+                    operand: Operand8::Immediate(0x90),
+                },
+                2,
+            ),
+        ),
+        ([0xff].as_slice(), (Sm83InstructionSet::Rst(0x38), 1)),
+        (
+            [0xcb, 0x37].as_slice(),
+            (
+                Sm83InstructionSet::Rotate {
+                    operation: RotateOperation::Swap,
+                    operand: Operand8::Register(Register8::A),
+                },
+                2,
+            ),
+        ),
+        (
+            [0xcb, 0x7e].as_slice(),
+            (
+                Sm83InstructionSet::Bit {
+                    bit: 7,
+                    operand: Operand8::HlIndirect,
+                },
+                2,
+            ),
+        ),
+        (
+            [0xcb, 0xc0].as_slice(),
+            (
+                Sm83InstructionSet::Set {
+                    bit: 0,
+                    operand: Operand8::Register(Register8::B),
+                },
+                2,
+            ),
+        ),
+    ]);
+
+    for (instruction_binary, (decoded_instruction, decoded_instruction_size)) in map {
+        let machine = Machine::build(GameSystem::Unknown, rom_manager.clone())
+            .insert_bus(ADDRESS_SPACE, 16)
+            .build_component::<StandardMemory>(StandardMemoryConfig {
+                max_word_size: 2,
+                readable: true,
+                writable: true,
+                assigned_range: 0..0x4,
+                assigned_address_space: ADDRESS_SPACE,
+                initial_contents: StandardMemoryInitialContents::Array {
+                    value: Cow::Borrowed(instruction_binary),
+                    offset: 0,
+                },
+            })
+            .0
+            .build();
+
+        assert_eq!(
+            (decoded_instruction, decoded_instruction_size),
+            decode_instruction(0x0, ADDRESS_SPACE, &machine.memory_translation_table),
+            "Decoding {:02x?}",
+            instruction_binary
+        );
+    }
+}
+
+fn sm83_machine() -> (TestMachine, Arc<Sm83>) {
+    let (builder, processor) = TestMachineBuilder::new()
+        .bus(ADDRESS_SPACE, 16)
+        .scratch_ram(ADDRESS_SPACE, 0..0xff0f, 0x00)
+        .scratch_ram(ADDRESS_SPACE, 0xff10..0xffff, 0x00)
+        .component::<Sm83>(Sm83Config {
+            frequency: Ratio::from_integer(1),
+            assigned_address_space: ADDRESS_SPACE,
+            boot_rom: false,
+        });
+    let machine = builder.build();
+    let processor = machine.component::<Sm83>(processor);
+
+    (machine, processor)
+}
+
+#[test]
+fn sm83_interrupts() {
+    let (machine, processor) = sm83_machine();
+    // ei, nop, halt, then inc a and reti in the vblank handler
+    machine.load(ADDRESS_SPACE, 0x0100, &[0xfb, 0x00, 0x76, 0x00]);
+    machine.load(ADDRESS_SPACE, 0x0040, &[0x3c, 0xd9]);
+    machine.load(ADDRESS_SPACE, INTERRUPT_ENABLE_ADDRESS, &[0b0_0001]);
+
+    for _ in 0..3 {
+        processor.step();
+    }
+    assert_eq!(processor.read_register(5), 0x0103);
+
+    // Halted, so nothing moves
+    processor.step();
+    assert_eq!(processor.read_register(5), 0x0103);
+
+    let vblank = processor.interrupt_connection(Sm83Interrupt::VBlank);
+    vblank.set_interrupt(true);
+    processor.step();
+    assert_eq!(processor.read_register(5), 0x0040);
+    assert_eq!(processor.read_register(4), 0xfffc);
+    // Taking the interrupt acknowledges it
+    assert_eq!(machine.peek(ADDRESS_SPACE, 0xff0f, 1), [0b1110_0000]);
+
+    processor.step();
+    processor.step();
+    assert_eq!(processor.read_register(5), 0x0103);
+    assert_eq!(processor.read_register(0) >> 8, 0x02);
+}
+
+#[test]
+fn sm83_halt_bug() {
+    let (machine, processor) = sm83_machine();
+    // halt with ime off and an interrupt already waiting, then inc a
+    machine.load(ADDRESS_SPACE, 0x0100, &[0x76, 0x3c]);
+    machine.load(ADDRESS_SPACE, 0xff0f, &[0b0_0100]);
+    machine.load(ADDRESS_SPACE, INTERRUPT_ENABLE_ADDRESS, &[0b0_0100]);
+
+    processor.step();
+    processor.step();
+    processor.step();
+
+    // inc a runs twice since the program counter didn't move past it the first time
+    assert_eq!(processor.read_register(0) >> 8, 0x03);
+    assert_eq!(processor.read_register(5), 0x0102);
+}
+
+#[test]
+fn sm83_arithmetic_flags() {
+    let (_machine, processor) = sm83_machine();
+    let mut state = processor.state.lock().unwrap();
+
+    // 45 + 38 in binary coded decimal
+    state.registers.accumulator = 0x45;
+    processor.interpret_instruction(
+        &mut state,
+        Sm83InstructionSet::Alu {
+            operation: AluOperation::Add,
+            operand: Operand8::Immediate(0x38),
+        },
+    );
+    assert_eq!(state.registers.accumulator, 0x7d);
+    processor.interpret_instruction(&mut state, Sm83InstructionSet::Daa);
+    assert_eq!(state.registers.accumulator, 0x83);
+    assert!(!state.registers.flags.contains(FlagRegister::Carry));
+
+    // 0x3e - 0x3f borrows out of both nibbles
+    state.registers.accumulator = 0x3e;
+    processor.interpret_instruction(
+        &mut state,
+        Sm83InstructionSet::Alu {
+            operation: AluOperation::Cp,
+            operand: Operand8::Immediate(0x3f),
+        },
+    );
+    assert_eq!(state.registers.accumulator, 0x3e);
+    assert_eq!(
+        state.registers.flags,
+        FlagRegister::Subtract | FlagRegister::HalfCarry | FlagRegister::Carry
+    );
+
+    // Only the bottom byte of sp counts for the flags
+    state.registers.stack_pointer = 0x00ff;
+    processor.interpret_instruction(&mut state, Sm83InstructionSet::AddSp(-1));
+    assert_eq!(state.registers.stack_pointer, 0x00fe);
+    assert_eq!(
+        state.registers.flags,
+        FlagRegister::HalfCarry | FlagRegister::Carry
+    );
+}
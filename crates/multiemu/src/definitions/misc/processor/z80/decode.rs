@@ -0,0 +1,485 @@
+use super::instruction::{
+    AluOperation, BlockOperation, Condition, IndexRegister, Operand8, Register8, RegisterPair,
+    RotateOperation, Z80InstructionSet,
+};
+use crate::memory::{AddressSpaceId, MemoryTranslationTable};
+use bitvec::{field::BitField, prelude::Msb0, view::BitView};
+use std::ops::Range;
+
+const INSTRUCTION_IDENTIFIER: Range<usize> = 0..2;
+const ARGUMENT: Range<usize> = 2..5;
+/// The top two bits of [ARGUMENT], which picks a register pair
+const REGISTER_PAIR: Range<usize> = 2..4;
+/// The bottom bit of [ARGUMENT], which often picks between two related instructions
+const ARGUMENT_LOW: usize = 4;
+const SECONDARY_INSTRUCTION_IDENTIFIER: Range<usize> = 5..8;
+
+const BIT_PREFIX: u8 = 0xcb;
+const EXTENDED_PREFIX: u8 = 0xed;
+const IX_PREFIX: u8 = 0xdd;
+const IY_PREFIX: u8 = 0xfd;
+
+/// Where the fields of an opcode are, split out once
+struct OpcodeFields {
+    identifier: u8,
+    argument: u8,
+    register_pair: u8,
+    argument_low: bool,
+    secondary_identifier: u8,
+}
+
+impl OpcodeFields {
+    fn new(opcode: u8) -> Self {
+        let opcode_bits = opcode.view_bits::<Msb0>();
+
+        Self {
+            identifier: opcode_bits[INSTRUCTION_IDENTIFIER].load::<u8>(),
+            argument: opcode_bits[ARGUMENT].load::<u8>(),
+            register_pair: opcode_bits[REGISTER_PAIR].load::<u8>(),
+            argument_low: opcode_bits[ARGUMENT_LOW],
+            secondary_identifier: opcode_bits[SECONDARY_INSTRUCTION_IDENTIFIER].load::<u8>(),
+        }
+    }
+}
+
+/// Decodes the instruction at `cursor`, returning it and how many bytes it took
+///
+/// A dd or fd prefix in front of something that doesn't touch hl does nothing but take time, so it comes back as a one
+/// byte [Z80InstructionSet::Nop] and the instruction after it is decoded on its own
+pub fn decode_instruction(
+    cursor: u16,
+    address_space: AddressSpaceId,
+    memory_translation_table: &MemoryTranslationTable,
+) -> (Z80InstructionSet, u8) {
+    let read_u8 = |offset: u16| {
+        let mut value = [0];
+        let _ = memory_translation_table.read(
+            cursor.wrapping_add(offset) as usize,
+            &mut value,
+            address_space,
+        );
+        value[0]
+    };
+
+    match read_u8(0) {
+        BIT_PREFIX => (
+            decode_bit_instruction(read_u8(1), Operand8::from_id, IndexRegister::Hl),
+            2,
+        ),
+        EXTENDED_PREFIX => {
+            let (instruction, length) =
+                decode_extended_instruction(read_u8(1), |offset| read_u8(offset + 1));
+            (instruction, length + 1)
+        }
+        prefix @ (IX_PREFIX | IY_PREFIX) => {
+            let index = if prefix == IX_PREFIX {
+                IndexRegister::Ix
+            } else {
+                IndexRegister::Iy
+            };
+
+            match read_u8(1) {
+                // The displacement comes before the opcode, and the operand is always the indexed byte
+                BIT_PREFIX => {
+                    let displacement = read_u8(2) as i8;
+                    (
+                        decode_bit_instruction(
+                            read_u8(3),
+                            |_, _, _| Operand8::Indirect(index, displacement),
+                            index,
+                        ),
+                        4,
+                    )
+                }
+                // Only the last prefix counts
+                IX_PREFIX | IY_PREFIX | EXTENDED_PREFIX => (Z80InstructionSet::Nop, 1),
+                opcode => {
+                    let (instruction, length) =
+                        decode_main_instruction(opcode, index, |offset| read_u8(offset + 1));
+
+                    if instruction.uses_index() {
+                        (instruction, length + 1)
+                    } else {
+                        (Z80InstructionSet::Nop, 1)
+                    }
+                }
+            }
+        }
+        opcode => decode_main_instruction(opcode, IndexRegister::Hl, read_u8),
+    }
+}
+
+/// The unprefixed table, with `index` standing in for hl
+///
+/// `read_u8` is relative to the opcode
+fn decode_main_instruction(
+    opcode: u8,
+    index: IndexRegister,
+    read_u8: impl Fn(u16) -> u8,
+) -> (Z80InstructionSet, u8) {
+    let read_u16 = |offset: u16| u16::from_le_bytes([read_u8(offset), read_u8(offset + 1)]);
+    let fields = OpcodeFields::new(opcode);
+
+    // Behind a prefix (hl) becomes (ix+d), with the displacement before any immediate
+    let displacement = read_u8(1) as i8;
+    let displacement_length = |id: u8| (index != IndexRegister::Hl && id == 6) as u8;
+    let operand = |id: u8| Operand8::from_id(id, index, displacement);
+    let register_pair = RegisterPair::from_id(fields.register_pair, index);
+    let condition = Condition::from_repr(fields.argument).unwrap();
+
+    match fields.identifier {
+        0b00 => match fields.secondary_identifier {
+            0b000 => match fields.argument {
+                0b000 => (Z80InstructionSet::Nop, 1),
+                0b001 => (Z80InstructionSet::ExAf, 1),
+                0b010 => (Z80InstructionSet::Djnz(read_u8(1) as i8), 2),
+                0b011 => (
+                    Z80InstructionSet::Jr {
+                        condition: None,
+                        offset: read_u8(1) as i8,
+                    },
+                    2,
+                ),
+                _ => (
+                    Z80InstructionSet::Jr {
+                        condition: Condition::from_repr(fields.argument & 0b11),
+                        offset: read_u8(1) as i8,
+                    },
+                    2,
+                ),
+            },
+            0b001 => {
+                if fields.argument_low {
+                    (
+                        Z80InstructionSet::Add16 {
+                            destination: index,
+                            source: register_pair,
+                        },
+                        1,
+                    )
+                } else {
+                    (
+                        Z80InstructionSet::Ld16 {
+                            destination: register_pair,
+                            value: read_u16(1),
+                        },
+                        3,
+                    )
+                }
+            }
+            0b010 => match (fields.register_pair, fields.argument_low) {
+                (0b00 | 0b01, false) => (Z80InstructionSet::LdIndirectFromA(register_pair), 1),
+                (0b00 | 0b01, true) => (Z80InstructionSet::LdAFromIndirect(register_pair), 1),
+                (0b10, false) => (
+                    Z80InstructionSet::LdAbsoluteFrom16 {
+                        address: read_u16(1),
+                        source: index.into(),
+                    },
+                    3,
+                ),
+                (0b10, true) => (
+                    Z80InstructionSet::Ld16FromAbsolute {
+                        destination: index.into(),
+                        address: read_u16(1),
+                    },
+                    3,
+                ),
+                (_, false) => (Z80InstructionSet::LdAbsoluteFromA(read_u16(1)), 3),
+                (_, true) => (Z80InstructionSet::LdAFromAbsolute(read_u16(1)), 3),
+            },
+            0b011 => {
+                if fields.argument_low {
+                    (Z80InstructionSet::Dec16(register_pair), 1)
+                } else {
+                    (Z80InstructionSet::Inc16(register_pair), 1)
+                }
+            }
+            0b100 => (
+                Z80InstructionSet::Inc(operand(fields.argument)),
+                1 + displacement_length(fields.argument),
+            ),
+            0b101 => (
+                Z80InstructionSet::Dec(operand(fields.argument)),
+                1 + displacement_length(fields.argument),
+            ),
+            0b110 => {
+                let immediate_offset = 1 + displacement_length(fields.argument);
+
+                (
+                    Z80InstructionSet::Ld {
+                        destination: operand(fields.argument),
+                        source: Operand8::Immediate(read_u8(immediate_offset as u16)),
+                    },
+                    immediate_offset + 1,
+                )
+            }
+            0b111 => (
+                match fields.argument {
+                    0b000 => Z80InstructionSet::Rlca,
+                    0b001 => Z80InstructionSet::Rrca,
+                    0b010 => Z80InstructionSet::Rla,
+                    0b011 => Z80InstructionSet::Rra,
+                    0b100 => Z80InstructionSet::Daa,
+                    0b101 => Z80InstructionSet::Cpl,
+                    0b110 => Z80InstructionSet::Scf,
+                    _ => Z80InstructionSet::Ccf,
+                },
+                1,
+            ),
+            _ => unreachable!(),
+        },
+        0b01 => {
+            let destination = fields.argument;
+            let source = fields.secondary_identifier;
+
+            // Where ld (hl), (hl) would be
+            if destination == 6 && source == 6 {
+                return (Z80InstructionSet::Halt, 1);
+            }
+
+            // Next to (ix+d) h and l stay themselves
+            let (destination, source) = if destination == 6 {
+                (
+                    operand(destination),
+                    Operand8::from_id(source, IndexRegister::Hl, 0),
+                )
+            } else if source == 6 {
+                (
+                    Operand8::from_id(destination, IndexRegister::Hl, 0),
+                    operand(source),
+                )
+            } else {
+                (operand(destination), operand(source))
+            };
+
+            (
+                Z80InstructionSet::Ld {
+                    destination,
+                    source,
+                },
+                1 + displacement_length(fields.argument)
+                    + displacement_length(fields.secondary_identifier),
+            )
+        }
+        0b10 => (
+            Z80InstructionSet::Alu {
+                operation: AluOperation::from_repr(fields.argument).unwrap(),
+                operand: operand(fields.secondary_identifier),
+            },
+            1 + displacement_length(fields.secondary_identifier),
+        ),
+        0b11 => match fields.secondary_identifier {
+            0b000 => (
+                Z80InstructionSet::Ret {
+                    condition: Some(condition),
+                },
+                1,
+            ),
+            0b001 => {
+                if fields.argument_low {
+                    (
+                        match fields.register_pair {
+                            0b00 => Z80InstructionSet::Ret { condition: None },
+                            0b01 => Z80InstructionSet::Exx,
+                            0b10 => Z80InstructionSet::JpIndex(index),
+                            _ => Z80InstructionSet::LdSpFromIndex(index),
+                        },
+                        1,
+                    )
+                } else {
+                    (
+                        Z80InstructionSet::Pop(RegisterPair::from_stack_id(
+                            fields.register_pair,
+                            index,
+                        )),
+                        1,
+                    )
+                }
+            }
+            0b010 => (
+                Z80InstructionSet::Jp {
+                    condition: Some(condition),
+                    address: read_u16(1),
+                },
+                3,
+            ),
+            0b011 => match fields.argument {
+                0b000 => (
+                    Z80InstructionSet::Jp {
+                        condition: None,
+                        address: read_u16(1),
+                    },
+                    3,
+                ),
+                0b010 => (Z80InstructionSet::OutImmediate(read_u8(1)), 2),
+                0b011 => (Z80InstructionSet::InImmediate(read_u8(1)), 2),
+                0b100 => (Z80InstructionSet::ExStack(index), 1),
+                // ex de, hl is left alone by the prefixes
+                0b101 => (Z80InstructionSet::ExDeHl, 1),
+                0b110 => (Z80InstructionSet::Di, 1),
+                0b111 => (Z80InstructionSet::Ei, 1),
+                // 0xcb was handled by the caller
+                _ => unreachable!(),
+            },
+            0b100 => (
+                Z80InstructionSet::Call {
+                    condition: Some(condition),
+                    address: read_u16(1),
+                },
+                3,
+            ),
+            0b101 => {
+                if fields.argument_low {
+                    // The other three are the prefixes, which the caller handled
+                    (
+                        Z80InstructionSet::Call {
+                            condition: None,
+                            address: read_u16(1),
+                        },
+                        3,
+                    )
+                } else {
+                    (
+                        Z80InstructionSet::Push(RegisterPair::from_stack_id(
+                            fields.register_pair,
+                            index,
+                        )),
+                        1,
+                    )
+                }
+            }
+            0b110 => (
+                Z80InstructionSet::Alu {
+                    operation: AluOperation::from_repr(fields.argument).unwrap(),
+                    operand: Operand8::Immediate(read_u8(1)),
+                },
+                2,
+            ),
+            0b111 => (Z80InstructionSet::Rst(fields.argument * 8), 1),
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// The instructions behind 0xcb, which are all laid out the same way
+///
+/// `operand` turns the register field into an operand. Behind an index prefix that is always the indexed byte, and the
+/// register field instead names where the undocumented forms copy the result
+fn decode_bit_instruction(
+    opcode: u8,
+    operand: impl Fn(u8, IndexRegister, i8) -> Operand8,
+    index: IndexRegister,
+) -> Z80InstructionSet {
+    let fields = OpcodeFields::new(opcode);
+    let register = fields.secondary_identifier;
+    let operand = operand(register, IndexRegister::Hl, 0);
+    let copy = if index == IndexRegister::Hl {
+        None
+    } else {
+        Register8::from_repr(register)
+    };
+
+    match fields.identifier {
+        0b00 => Z80InstructionSet::Rotate {
+            operation: RotateOperation::from_repr(fields.argument).unwrap(),
+            operand,
+            copy,
+        },
+        0b01 => Z80InstructionSet::Bit {
+            bit: fields.argument,
+            operand,
+        },
+        0b10 => Z80InstructionSet::Res {
+            bit: fields.argument,
+            operand,
+            copy,
+        },
+        0b11 => Z80InstructionSet::Set {
+            bit: fields.argument,
+            operand,
+            copy,
+        },
+        _ => unreachable!(),
+    }
+}
+
+/// The instructions behind 0xed, anything not listed is a two byte nop
+///
+/// `read_u8` is relative to the opcode
+fn decode_extended_instruction(opcode: u8, read_u8: impl Fn(u16) -> u8) -> (Z80InstructionSet, u8) {
+    let fields = OpcodeFields::new(opcode);
+    let register = Register8::from_repr(fields.argument);
+    let register_pair = RegisterPair::from_id(fields.register_pair, IndexRegister::Hl);
+
+    match fields.identifier {
+        0b01 => match fields.secondary_identifier {
+            0b000 => (Z80InstructionSet::In(register), 1),
+            0b001 => (Z80InstructionSet::Out(register), 1),
+            0b010 => {
+                if fields.argument_low {
+                    (Z80InstructionSet::Adc16(register_pair), 1)
+                } else {
+                    (Z80InstructionSet::Sbc16(register_pair), 1)
+                }
+            }
+            // ed 63 and ed 6b are slower copies of the unprefixed hl versions, close enough to treat as them
+            0b011 => {
+                let address = u16::from_le_bytes([read_u8(1), read_u8(2)]);
+
+                if fields.argument_low {
+                    (
+                        Z80InstructionSet::Ld16FromAbsolute {
+                            destination: register_pair,
+                            address,
+                        },
+                        3,
+                    )
+                } else {
+                    (
+                        Z80InstructionSet::LdAbsoluteFrom16 {
+                            address,
+                            source: register_pair,
+                        },
+                        3,
+                    )
+                }
+            }
+            // The mirrors of these behave the same
+            0b100 => (Z80InstructionSet::Neg, 1),
+            0b101 => {
+                if fields.argument == 0b001 {
+                    (Z80InstructionSet::Reti, 1)
+                } else {
+                    (Z80InstructionSet::Retn, 1)
+                }
+            }
+            0b110 => (
+                Z80InstructionSet::Im([0, 0, 1, 2][fields.argument as usize & 0b11]),
+                1,
+            ),
+            0b111 => (
+                match fields.argument {
+                    0b000 => Z80InstructionSet::LdIFromA,
+                    0b001 => Z80InstructionSet::LdRFromA,
+                    0b010 => Z80InstructionSet::LdAFromI,
+                    0b011 => Z80InstructionSet::LdAFromR,
+                    0b100 => Z80InstructionSet::Rrd,
+                    0b101 => Z80InstructionSet::Rld,
+                    _ => Z80InstructionSet::Nop,
+                },
+                1,
+            ),
+            _ => unreachable!(),
+        },
+        0b10 if fields.argument >= 0b100 && fields.secondary_identifier <= 0b011 => (
+            Z80InstructionSet::Block {
+                operation: BlockOperation::from_repr(fields.secondary_identifier).unwrap(),
+                decrement: fields.argument_low,
+                repeat: fields.register_pair == 0b11,
+            },
+            1,
+        ),
+        _ => (Z80InstructionSet::Nop, 1),
+    }
+}
@@ -0,0 +1,525 @@
+use crate::processor::{InstructionSet, InstructionTextRepresentation};
+use std::{borrow::Cow, fmt::Display};
+use strum::FromRepr;
+
+// http://www.z80.info/decoding.htm
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum Register8 {
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    /// Slot 6 is (hl), see [Operand8::from_id]
+    A = 7,
+}
+
+/// hl, or the index register a dd or fd prefix swaps in for it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IndexRegister {
+    Hl,
+    Ix,
+    Iy,
+}
+
+/// Where an 8 bit value comes from or goes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operand8 {
+    Register(Register8),
+    /// ixh or iyh, what h turns into behind a prefix
+    IndexHigh(IndexRegister),
+    /// ixl or iyl, what l turns into behind a prefix
+    IndexLow(IndexRegister),
+    /// (hl), or (ix+d) and (iy+d) behind a prefix
+    Indirect(IndexRegister, i8),
+    Immediate(u8),
+}
+
+impl Operand8 {
+    /// The 3 bit register field most instructions have, with h, l and (hl) swapped for whatever `index` stands in for
+    /// hl
+    pub fn from_id(id: u8, index: IndexRegister, displacement: i8) -> Self {
+        match (Register8::from_repr(id), index) {
+            (None, _) => Operand8::Indirect(index, displacement),
+            (Some(Register8::H), IndexRegister::Ix | IndexRegister::Iy) => {
+                Operand8::IndexHigh(index)
+            }
+            (Some(Register8::L), IndexRegister::Ix | IndexRegister::Iy) => {
+                Operand8::IndexLow(index)
+            }
+            (Some(register), _) => Operand8::Register(register),
+        }
+    }
+
+    /// The index register this touches, if a prefix put one here
+    pub fn index(&self) -> Option<IndexRegister> {
+        match *self {
+            Operand8::IndexHigh(index) | Operand8::IndexLow(index) => Some(index),
+            Operand8::Indirect(index, _) if index != IndexRegister::Hl => Some(index),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RegisterPair {
+    Bc,
+    De,
+    Hl,
+    Sp,
+    Af,
+    Ix,
+    Iy,
+}
+
+impl RegisterPair {
+    /// The 2 bit pair field most 16 bit instructions have, which ends in sp
+    pub fn from_id(id: u8, index: IndexRegister) -> Self {
+        match id {
+            0b00 => RegisterPair::Bc,
+            0b01 => RegisterPair::De,
+            0b10 => index.into(),
+            _ => RegisterPair::Sp,
+        }
+    }
+
+    /// The pair field of push and pop, which ends in af instead
+    pub fn from_stack_id(id: u8, index: IndexRegister) -> Self {
+        match id {
+            0b11 => RegisterPair::Af,
+            _ => Self::from_id(id, index),
+        }
+    }
+}
+
+impl From<IndexRegister> for RegisterPair {
+    fn from(index: IndexRegister) -> Self {
+        match index {
+            IndexRegister::Hl => RegisterPair::Hl,
+            IndexRegister::Ix => RegisterPair::Ix,
+            IndexRegister::Iy => RegisterPair::Iy,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum Condition {
+    NotZero,
+    Zero,
+    NotCarry,
+    Carry,
+    ParityOdd,
+    ParityEven,
+    Positive,
+    Negative,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum AluOperation {
+    Add,
+    Adc,
+    Sub,
+    Sbc,
+    And,
+    Xor,
+    Or,
+    Cp,
+}
+
+/// The shifts and rotates behind the 0xcb prefix
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum RotateOperation {
+    Rlc,
+    Rrc,
+    Rl,
+    Rr,
+    Sla,
+    Sra,
+    /// Undocumented, shifts left and sets bit 0
+    Sll,
+    Srl,
+}
+
+/// What ldi, cpi, ini, outi and their repeating versions do each step
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromRepr)]
+#[repr(u8)]
+pub enum BlockOperation {
+    Ld,
+    Cp,
+    In,
+    Out,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Z80InstructionSet {
+    Nop,
+    Halt,
+    Di,
+    Ei,
+    /// ex af, af'
+    ExAf,
+    Exx,
+    ExDeHl,
+    /// ex (sp), hl
+    ExStack(IndexRegister),
+    Djnz(i8),
+    Jr {
+        condition: Option<Condition>,
+        offset: i8,
+    },
+    Ld {
+        destination: Operand8,
+        source: Operand8,
+    },
+    /// Through bc or de
+    LdIndirectFromA(RegisterPair),
+    LdAFromIndirect(RegisterPair),
+    LdAbsoluteFromA(u16),
+    LdAFromAbsolute(u16),
+    Ld16 {
+        destination: RegisterPair,
+        value: u16,
+    },
+    LdAbsoluteFrom16 {
+        address: u16,
+        source: RegisterPair,
+    },
+    Ld16FromAbsolute {
+        destination: RegisterPair,
+        address: u16,
+    },
+    LdSpFromIndex(IndexRegister),
+    Push(RegisterPair),
+    Pop(RegisterPair),
+    Alu {
+        operation: AluOperation,
+        operand: Operand8,
+    },
+    Inc(Operand8),
+    Dec(Operand8),
+    Inc16(RegisterPair),
+    Dec16(RegisterPair),
+    Add16 {
+        destination: IndexRegister,
+        source: RegisterPair,
+    },
+    /// adc hl, which unlike add sets every flag
+    Adc16(RegisterPair),
+    Sbc16(RegisterPair),
+    Rlca,
+    Rrca,
+    Rla,
+    Rra,
+    Daa,
+    Cpl,
+    Scf,
+    Ccf,
+    Neg,
+    Jp {
+        condition: Option<Condition>,
+        address: u16,
+    },
+    /// jp (hl), which jumps to hl rather than what it points at
+    JpIndex(IndexRegister),
+    Call {
+        condition: Option<Condition>,
+        address: u16,
+    },
+    Ret {
+        condition: Option<Condition>,
+    },
+    Reti,
+    Retn,
+    Rst(u8),
+    /// out (n), a, with a on the top half of the port address
+    OutImmediate(u8),
+    InImmediate(u8),
+    /// in r, (c), None only sets the flags
+    In(Option<Register8>),
+    /// out (c), r, None writes 0
+    Out(Option<Register8>),
+    Im(u8),
+    LdIFromA,
+    LdRFromA,
+    LdAFromI,
+    LdAFromR,
+    Rrd,
+    Rld,
+    Block {
+        operation: BlockOperation,
+        decrement: bool,
+        repeat: bool,
+    },
+    /// `copy` is where the undocumented dd cb and fd cb forms also put the result
+    Rotate {
+        operation: RotateOperation,
+        operand: Operand8,
+        copy: Option<Register8>,
+    },
+    Bit {
+        bit: u8,
+        operand: Operand8,
+    },
+    Res {
+        bit: u8,
+        operand: Operand8,
+        copy: Option<Register8>,
+    },
+    Set {
+        bit: u8,
+        operand: Operand8,
+        copy: Option<Register8>,
+    },
+}
+
+impl Z80InstructionSet {
+    /// If a dd or fd prefix changed what this does, otherwise the prefix was wasted
+    pub fn uses_index(&self) -> bool {
+        let operand = |operand: &Operand8| operand.index().is_some();
+        let pair = |pair: &RegisterPair| matches!(pair, RegisterPair::Ix | RegisterPair::Iy);
+        let index = |index: &IndexRegister| *index != IndexRegister::Hl;
+
+        match self {
+            Self::Ld {
+                destination,
+                source,
+            } => operand(destination) || operand(source),
+            Self::Alu { operand: value, .. }
+            | Self::Inc(value)
+            | Self::Dec(value)
+            | Self::Rotate { operand: value, .. }
+            | Self::Bit { operand: value, .. }
+            | Self::Res { operand: value, .. }
+            | Self::Set { operand: value, .. } => operand(value),
+            Self::Ld16 {
+                destination: register,
+                ..
+            }
+            | Self::LdAbsoluteFrom16 {
+                source: register, ..
+            }
+            | Self::Ld16FromAbsolute {
+                destination: register,
+                ..
+            }
+            | Self::Push(register)
+            | Self::Pop(register)
+            | Self::Inc16(register)
+            | Self::Dec16(register) => pair(register),
+            Self::Add16 {
+                destination,
+                source,
+            } => index(destination) || pair(source),
+            Self::ExStack(register) | Self::LdSpFromIndex(register) | Self::JpIndex(register) => {
+                index(register)
+            }
+            _ => false,
+        }
+    }
+
+    /// Cycles the prefix and any displacement add on top of the hl version
+    pub fn index_cycles(&self) -> u32 {
+        let indexed_memory = |operand: &Operand8| {
+            matches!(
+                operand,
+                Operand8::Indirect(IndexRegister::Ix | IndexRegister::Iy, _)
+            )
+        };
+
+        match self {
+            Self::Rotate { operand, .. }
+            | Self::Bit { operand, .. }
+            | Self::Res { operand, .. }
+            | Self::Set { operand, .. }
+                if indexed_memory(operand) =>
+            {
+                8
+            }
+            Self::Ld {
+                destination,
+                source: Operand8::Immediate(_),
+            } if indexed_memory(destination) => 9,
+            Self::Ld {
+                destination: operand,
+                ..
+            }
+            | Self::Ld {
+                source: operand, ..
+            }
+            | Self::Alu { operand, .. }
+            | Self::Inc(operand)
+            | Self::Dec(operand)
+                if indexed_memory(operand) =>
+            {
+                12
+            }
+            _ if self.uses_index() => 4,
+            _ => 0,
+        }
+    }
+}
+
+impl Display for Operand8 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand8::Register(register) => write!(f, "{}", name(register)),
+            Operand8::IndexHigh(index) => write!(f, "{}h", name(index)),
+            Operand8::IndexLow(index) => write!(f, "{}l", name(index)),
+            Operand8::Indirect(IndexRegister::Hl, _) => write!(f, "(hl)"),
+            Operand8::Indirect(index, displacement) => {
+                write!(f, "({}{:+})", name(index), displacement)
+            }
+            Operand8::Immediate(value) => write!(f, "{:#04x}", value),
+        }
+    }
+}
+
+/// Lowercase name of a register or operation, which is all the mnemonics need
+fn name(value: impl std::fmt::Debug) -> String {
+    format!("{:?}", value).to_lowercase()
+}
+
+fn condition_name(condition: Condition) -> &'static str {
+    match condition {
+        Condition::NotZero => "nz",
+        Condition::Zero => "z",
+        Condition::NotCarry => "nc",
+        Condition::Carry => "c",
+        Condition::ParityOdd => "po",
+        Condition::ParityEven => "pe",
+        Condition::Positive => "p",
+        Condition::Negative => "m",
+    }
+}
+
+/// What goes between the mnemonic and the address of a conditional jump
+fn condition_prefix(condition: Option<Condition>) -> String {
+    condition
+        .map(|condition| format!("{}, ", condition_name(condition)))
+        .unwrap_or_default()
+}
+
+/// The register the undocumented index bit instructions copy into, tacked on the end
+fn copy_suffix(copy: Option<Register8>) -> String {
+    copy.map(|register| format!(", {}", name(register)))
+        .unwrap_or_default()
+}
+
+impl InstructionSet for Z80InstructionSet {
+    fn to_text_representation(&self) -> InstructionTextRepresentation {
+        let mnemonic = match *self {
+            Self::ExAf => "ex af, af'".to_string(),
+            Self::ExDeHl => "ex de, hl".to_string(),
+            Self::ExStack(index) => format!("ex (sp), {}", name(index)),
+            Self::Djnz(offset) => format!("djnz {}", offset),
+            Self::Jr { condition, offset } => {
+                format!("jr {}{}", condition_prefix(condition), offset)
+            }
+            Self::Ld {
+                destination,
+                source,
+            } => format!("ld {}, {}", destination, source),
+            Self::LdIndirectFromA(register) => format!("ld ({}), a", name(register)),
+            Self::LdAFromIndirect(register) => format!("ld a, ({})", name(register)),
+            Self::LdAbsoluteFromA(address) => format!("ld ({:#06x}), a", address),
+            Self::LdAFromAbsolute(address) => format!("ld a, ({:#06x})", address),
+            Self::Ld16 { destination, value } => {
+                format!("ld {}, {:#06x}", name(destination), value)
+            }
+            Self::LdAbsoluteFrom16 { address, source } => {
+                format!("ld ({:#06x}), {}", address, name(source))
+            }
+            Self::Ld16FromAbsolute {
+                destination,
+                address,
+            } => format!("ld {}, ({:#06x})", name(destination), address),
+            Self::LdSpFromIndex(index) => format!("ld sp, {}", name(index)),
+            Self::Push(register) => format!("push {}", name(register)),
+            Self::Pop(register) => format!("pop {}", name(register)),
+            Self::Alu { operation, operand } => match operation {
+                // The ones that take two operands in the official syntax
+                AluOperation::Add | AluOperation::Adc | AluOperation::Sbc => {
+                    format!("{} a, {}", name(operation), operand)
+                }
+                _ => format!("{} {}", name(operation), operand),
+            },
+            Self::Inc(operand) => format!("inc {}", operand),
+            Self::Dec(operand) => format!("dec {}", operand),
+            Self::Inc16(register) => format!("inc {}", name(register)),
+            Self::Dec16(register) => format!("dec {}", name(register)),
+            Self::Add16 {
+                destination,
+                source,
+            } => format!("add {}, {}", name(destination), name(source)),
+            Self::Adc16(register) => format!("adc hl, {}", name(register)),
+            Self::Sbc16(register) => format!("sbc hl, {}", name(register)),
+            Self::Jp { condition, address } => {
+                format!("jp {}{:#06x}", condition_prefix(condition), address)
+            }
+            Self::JpIndex(index) => format!("jp ({})", name(index)),
+            Self::Call { condition, address } => {
+                format!("call {}{:#06x}", condition_prefix(condition), address)
+            }
+            Self::Ret { condition: None } => "ret".to_string(),
+            Self::Ret {
+                condition: Some(condition),
+            } => format!("ret {}", condition_name(condition)),
+            Self::Rst(vector) => format!("rst {:#04x}", vector),
+            Self::OutImmediate(port) => format!("out ({:#04x}), a", port),
+            Self::InImmediate(port) => format!("in a, ({:#04x})", port),
+            Self::In(None) => "in (c)".to_string(),
+            Self::In(Some(register)) => format!("in {}, (c)", name(register)),
+            Self::Out(None) => "out (c), 0".to_string(),
+            Self::Out(Some(register)) => format!("out (c), {}", name(register)),
+            Self::Im(mode) => format!("im {}", mode),
+            Self::LdIFromA => "ld i, a".to_string(),
+            Self::LdRFromA => "ld r, a".to_string(),
+            Self::LdAFromI => "ld a, i".to_string(),
+            Self::LdAFromR => "ld a, r".to_string(),
+            Self::Block {
+                operation,
+                decrement,
+                repeat,
+            } => {
+                let operation = match operation {
+                    BlockOperation::Ld => "ld",
+                    BlockOperation::Cp => "cp",
+                    BlockOperation::In => "in",
+                    // otir and otdr don't follow the pattern
+                    BlockOperation::Out if repeat => "ot",
+                    BlockOperation::Out => "out",
+                };
+
+                format!(
+                    "{}{}{}",
+                    operation,
+                    if decrement { "d" } else { "i" },
+                    if repeat { "r" } else { "" }
+                )
+            }
+            Self::Rotate {
+                operation,
+                operand,
+                copy,
+            } => format!("{} {}{}", name(operation), operand, copy_suffix(copy)),
+            Self::Bit { bit, operand } => format!("bit {}, {}", bit, operand),
+            Self::Res { bit, operand, copy } => {
+                format!("res {}, {}{}", bit, operand, copy_suffix(copy))
+            }
+            Self::Set { bit, operand, copy } => {
+                format!("set {}, {}{}", bit, operand, copy_suffix(copy))
+            }
+            _ => name(self),
+        };
+
+        InstructionTextRepresentation {
+            instruction_mnemonic: Cow::Owned(mnemonic),
+        }
+    }
+}
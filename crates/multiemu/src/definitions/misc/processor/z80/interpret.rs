@@ -0,0 +1,952 @@
+use super::{
+    instruction::{
+        AluOperation, BlockOperation, Condition, IndexRegister, Operand8, Register8, RegisterPair,
+        RotateOperation, Z80InstructionSet,
+    },
+    ExecutionState, FlagRegister, ProcessorState, Z80Registers, Z80,
+};
+use enumflags2::BitFlags;
+
+// NOTE: The Z80 has no way to notice bus errors, so they are all ignored
+
+const PREFIXES: [u8; 4] = [0xcb, 0xdd, 0xed, 0xfd];
+
+/// Sign, zero and the undocumented bits, which all come straight from the result
+fn sign_zero(value: u8) -> BitFlags<FlagRegister> {
+    let mut flags = BitFlags::from_bits_truncate(value & 0b1010_1000);
+    flags.set(FlagRegister::Zero, value == 0);
+
+    flags
+}
+
+/// [sign_zero] plus parity, like the logic instructions set
+fn sign_zero_parity(value: u8) -> BitFlags<FlagRegister> {
+    let mut flags = sign_zero(value);
+    flags.set(FlagRegister::ParityOverflow, value.count_ones() % 2 == 0);
+
+    flags
+}
+
+/// Bits 5 and 3 of a value, where they land in f
+fn undocumented(value: u8) -> BitFlags<FlagRegister> {
+    BitFlags::from_bits_truncate(value & 0b0010_1000)
+}
+
+impl Z80 {
+    fn read(&self, address: u16) -> u8 {
+        let mut value = [0];
+        let _ = self.memory_translation_table.get().unwrap().read(
+            address as usize,
+            &mut value,
+            self.config.assigned_address_space,
+        );
+
+        value[0]
+    }
+
+    fn write(&self, address: u16, value: u8) {
+        let _ = self.memory_translation_table.get().unwrap().write(
+            address as usize,
+            &[value],
+            self.config.assigned_address_space,
+        );
+    }
+
+    pub(super) fn read_u16(&self, address: u16) -> u16 {
+        u16::from_le_bytes([self.read(address), self.read(address.wrapping_add(1))])
+    }
+
+    fn write_u16(&self, address: u16, value: u16) {
+        let [low, high] = value.to_le_bytes();
+        self.write(address, low);
+        self.write(address.wrapping_add(1), high);
+    }
+
+    /// Nothing answering a port leaves the bus floating high
+    fn port_in(&self, port: u16) -> u8 {
+        let mut value = [0xff];
+        let _ = self.memory_translation_table.get().unwrap().read(
+            port as usize,
+            &mut value,
+            self.config.io_address_space,
+        );
+
+        value[0]
+    }
+
+    fn port_out(&self, port: u16, value: u8) {
+        let _ = self.memory_translation_table.get().unwrap().write(
+            port as usize,
+            &[value],
+            self.config.io_address_space,
+        );
+    }
+
+    /// If the opcode at `address` is one of the prefixes, which costs an extra fetch
+    pub(super) fn prefixed(&self, address: u16) -> bool {
+        PREFIXES.contains(&self.read(address))
+    }
+
+    pub(super) fn push(&self, state: &mut ProcessorState, value: u16) {
+        let [high, low] = value.to_be_bytes();
+
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_sub(1);
+        self.write(state.registers.stack_pointer, high);
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_sub(1);
+        self.write(state.registers.stack_pointer, low);
+    }
+
+    fn pop(&self, state: &mut ProcessorState) -> u16 {
+        let value = self.read_u16(state.registers.stack_pointer);
+        state.registers.stack_pointer = state.registers.stack_pointer.wrapping_add(2);
+
+        value
+    }
+
+    fn load_operand(&self, state: &ProcessorState, operand: Operand8) -> u8 {
+        let registers = &state.registers;
+
+        match operand {
+            Operand8::Register(Register8::A) => registers.accumulator,
+            Operand8::Register(register) => registers.general[register as usize],
+            Operand8::IndexHigh(index) => registers.index(index).to_be_bytes()[0],
+            Operand8::IndexLow(index) => registers.index(index).to_be_bytes()[1],
+            Operand8::Indirect(index, displacement) => {
+                self.read(registers.indirect_address(index, displacement))
+            }
+            Operand8::Immediate(value) => value,
+        }
+    }
+
+    fn store_operand(&self, state: &mut ProcessorState, operand: Operand8, value: u8) {
+        let registers = &mut state.registers;
+
+        match operand {
+            Operand8::Register(Register8::A) => registers.accumulator = value,
+            Operand8::Register(register) => registers.general[register as usize] = value,
+            Operand8::IndexHigh(index) => {
+                let [_, low] = registers.index(index).to_be_bytes();
+                registers.set_pair(index.into(), u16::from_be_bytes([value, low]));
+            }
+            Operand8::IndexLow(index) => {
+                let [high, _] = registers.index(index).to_be_bytes();
+                registers.set_pair(index.into(), u16::from_be_bytes([high, value]));
+            }
+            Operand8::Indirect(index, displacement) => {
+                self.write(registers.indirect_address(index, displacement), value)
+            }
+            Operand8::Immediate(_) => unreachable!("Immediates can't be written to"),
+        }
+    }
+
+    /// Writes a bit instruction's result back, and to the register the undocumented index forms also copy it to
+    fn store_bit_result(
+        &self,
+        state: &mut ProcessorState,
+        operand: Operand8,
+        copy: Option<Register8>,
+        value: u8,
+    ) {
+        self.store_operand(state, operand, value);
+
+        if let Some(register) = copy {
+            self.store_operand(state, Operand8::Register(register), value);
+        }
+    }
+
+    /// Runs an instruction whose bytes have already been skipped over, returning the cycles it took
+    pub(super) fn interpret_instruction(
+        &self,
+        state: &mut ProcessorState,
+        instruction: Z80InstructionSet,
+    ) -> u32 {
+        // Touching memory or reading an immediate costs a memory cycle on top of the register version
+        let memory_cycles = |operand: Operand8| match operand {
+            Operand8::Indirect(..) | Operand8::Immediate(_) => 3,
+            _ => 0,
+        };
+        let read_modify_write_cycles = |operand: Operand8| match operand {
+            Operand8::Indirect(..) => 7,
+            _ => 0,
+        };
+
+        let cycles = match instruction {
+            Z80InstructionSet::Nop => 4,
+            Z80InstructionSet::Halt => {
+                state.execution_state = ExecutionState::Halted;
+                4
+            }
+            Z80InstructionSet::Di => {
+                state.interrupts_enabled = false;
+                state.interrupts_enabled_backup = false;
+                4
+            }
+            Z80InstructionSet::Ei => {
+                state.interrupts_enabled = true;
+                state.interrupts_enabled_backup = true;
+                state.interrupts_blocked = true;
+                4
+            }
+            Z80InstructionSet::ExAf => {
+                let registers = &mut state.registers;
+                std::mem::swap(
+                    &mut registers.accumulator,
+                    &mut registers.shadow_accumulator,
+                );
+                std::mem::swap(&mut registers.flags, &mut registers.shadow_flags);
+                4
+            }
+            Z80InstructionSet::Exx => {
+                let registers = &mut state.registers;
+                std::mem::swap(&mut registers.general, &mut registers.shadow_general);
+                4
+            }
+            Z80InstructionSet::ExDeHl => {
+                let de = state.registers.pair(RegisterPair::De);
+                let hl = state.registers.pair(RegisterPair::Hl);
+                state.registers.set_pair(RegisterPair::De, hl);
+                state.registers.set_pair(RegisterPair::Hl, de);
+                4
+            }
+            Z80InstructionSet::ExStack(index) => {
+                let stack_pointer = state.registers.stack_pointer;
+                let value = self.read_u16(stack_pointer);
+                self.write_u16(stack_pointer, state.registers.index(index));
+                state.registers.set_pair(index.into(), value);
+                19
+            }
+            Z80InstructionSet::Djnz(offset) => {
+                let b = &mut state.registers.general[Register8::B as usize];
+                *b = b.wrapping_sub(1);
+                if *b == 0 {
+                    return 8;
+                }
+
+                state.registers.program =
+                    state.registers.program.wrapping_add_signed(offset as i16);
+                13
+            }
+            Z80InstructionSet::Jr { condition, offset } => {
+                if !state.registers.condition(condition) {
+                    return 7;
+                }
+
+                state.registers.program =
+                    state.registers.program.wrapping_add_signed(offset as i16);
+                12
+            }
+            Z80InstructionSet::Ld {
+                destination,
+                source,
+            } => {
+                let value = self.load_operand(state, source);
+                self.store_operand(state, destination, value);
+
+                4 + memory_cycles(destination) + memory_cycles(source)
+            }
+            Z80InstructionSet::LdIndirectFromA(register) => {
+                let address = state.registers.pair(register);
+                self.write(address, state.registers.accumulator);
+                7
+            }
+            Z80InstructionSet::LdAFromIndirect(register) => {
+                let address = state.registers.pair(register);
+                state.registers.accumulator = self.read(address);
+                7
+            }
+            Z80InstructionSet::LdAbsoluteFromA(address) => {
+                self.write(address, state.registers.accumulator);
+                13
+            }
+            Z80InstructionSet::LdAFromAbsolute(address) => {
+                state.registers.accumulator = self.read(address);
+                13
+            }
+            Z80InstructionSet::Ld16 { destination, value } => {
+                state.registers.set_pair(destination, value);
+                10
+            }
+            Z80InstructionSet::LdAbsoluteFrom16 { address, source } => {
+                self.write_u16(address, state.registers.pair(source));
+                extended_pair_cycles(source)
+            }
+            Z80InstructionSet::Ld16FromAbsolute {
+                destination,
+                address,
+            } => {
+                let value = self.read_u16(address);
+                state.registers.set_pair(destination, value);
+                extended_pair_cycles(destination)
+            }
+            Z80InstructionSet::LdSpFromIndex(index) => {
+                state.registers.stack_pointer = state.registers.index(index);
+                6
+            }
+            Z80InstructionSet::Push(register) => {
+                let value = state.registers.pair(register);
+                self.push(state, value);
+                11
+            }
+            Z80InstructionSet::Pop(register) => {
+                let value = self.pop(state);
+                state.registers.set_pair(register, value);
+                10
+            }
+            Z80InstructionSet::Alu { operation, operand } => {
+                let value = self.load_operand(state, operand);
+                state.registers.alu(operation, value);
+
+                4 + memory_cycles(operand)
+            }
+            Z80InstructionSet::Inc(operand) | Z80InstructionSet::Dec(operand) => {
+                let value = self.load_operand(state, operand);
+                let result = state
+                    .registers
+                    .increment(value, matches!(instruction, Z80InstructionSet::Dec(_)));
+                self.store_operand(state, operand, result);
+
+                4 + read_modify_write_cycles(operand)
+            }
+            Z80InstructionSet::Inc16(register) => {
+                let value = state.registers.pair(register).wrapping_add(1);
+                state.registers.set_pair(register, value);
+                6
+            }
+            Z80InstructionSet::Dec16(register) => {
+                let value = state.registers.pair(register).wrapping_sub(1);
+                state.registers.set_pair(register, value);
+                6
+            }
+            Z80InstructionSet::Add16 {
+                destination,
+                source,
+            } => {
+                let left = state.registers.index(destination);
+                let right = state.registers.pair(source);
+                let result = state.registers.add16(left, right);
+                state.registers.set_pair(destination.into(), result);
+                11
+            }
+            Z80InstructionSet::Adc16(register) => {
+                let value = state.registers.pair(register);
+                state.registers.add16_with_carry(value, false);
+                15
+            }
+            Z80InstructionSet::Sbc16(register) => {
+                let value = state.registers.pair(register);
+                state.registers.add16_with_carry(value, true);
+                15
+            }
+            Z80InstructionSet::Rlca
+            | Z80InstructionSet::Rrca
+            | Z80InstructionSet::Rla
+            | Z80InstructionSet::Rra => {
+                let operation = match instruction {
+                    Z80InstructionSet::Rlca => RotateOperation::Rlc,
+                    Z80InstructionSet::Rrca => RotateOperation::Rrc,
+                    Z80InstructionSet::Rla => RotateOperation::Rl,
+                    _ => RotateOperation::Rr,
+                };
+                let kept = state.registers.flags
+                    & (FlagRegister::Sign | FlagRegister::Zero | FlagRegister::ParityOverflow);
+                let result = state
+                    .registers
+                    .rotate(operation, state.registers.accumulator);
+
+                // Unlike the prefixed versions these leave sign, zero and parity alone
+                state.registers.accumulator = result;
+                state.registers.flags =
+                    kept | undocumented(result) | (state.registers.flags & FlagRegister::Carry);
+                4
+            }
+            Z80InstructionSet::Daa => {
+                state.registers.daa();
+                4
+            }
+            Z80InstructionSet::Cpl => {
+                let registers = &mut state.registers;
+                registers.accumulator = !registers.accumulator;
+                registers.flags = (registers.flags
+                    & (FlagRegister::Sign
+                        | FlagRegister::Zero
+                        | FlagRegister::ParityOverflow
+                        | FlagRegister::Carry))
+                    | FlagRegister::HalfCarry
+                    | FlagRegister::Subtract
+                    | undocumented(registers.accumulator);
+                4
+            }
+            Z80InstructionSet::Scf | Z80InstructionSet::Ccf => {
+                let registers = &mut state.registers;
+                let carry = registers.flags.contains(FlagRegister::Carry);
+                let mut flags = (registers.flags
+                    & (FlagRegister::Sign | FlagRegister::Zero | FlagRegister::ParityOverflow))
+                    | undocumented(registers.accumulator);
+
+                if instruction == Z80InstructionSet::Scf {
+                    flags.insert(FlagRegister::Carry);
+                } else {
+                    // Half carry gets the carry from before it was flipped
+                    flags.set(FlagRegister::HalfCarry, carry);
+                    flags.set(FlagRegister::Carry, !carry);
+                }
+                registers.flags = flags;
+                4
+            }
+            Z80InstructionSet::Neg => {
+                let value = state.registers.accumulator;
+                state.registers.accumulator = 0;
+                state.registers.alu(AluOperation::Sub, value);
+                8
+            }
+            Z80InstructionSet::Jp { condition, address } => {
+                if state.registers.condition(condition) {
+                    state.registers.program = address;
+                }
+                10
+            }
+            Z80InstructionSet::JpIndex(index) => {
+                state.registers.program = state.registers.index(index);
+                4
+            }
+            Z80InstructionSet::Call { condition, address } => {
+                if !state.registers.condition(condition) {
+                    return 10;
+                }
+
+                let program = state.registers.program;
+                self.push(state, program);
+                state.registers.program = address;
+                17
+            }
+            Z80InstructionSet::Ret { condition } => {
+                if !state.registers.condition(condition) {
+                    return 5;
+                }
+
+                state.registers.program = self.pop(state);
+                // Checking the condition costs a cycle
+                if condition.is_some() {
+                    11
+                } else {
+                    10
+                }
+            }
+            Z80InstructionSet::Reti | Z80InstructionSet::Retn => {
+                state.registers.program = self.pop(state);
+                // Both put back what an nmi saved, reti only differs to the peripherals watching for it
+                state.interrupts_enabled = state.interrupts_enabled_backup;
+                14
+            }
+            Z80InstructionSet::Rst(vector) => {
+                let program = state.registers.program;
+                self.push(state, program);
+                state.registers.program = vector as u16;
+                11
+            }
+            Z80InstructionSet::OutImmediate(port) => {
+                let accumulator = state.registers.accumulator;
+                self.port_out(u16::from_be_bytes([accumulator, port]), accumulator);
+                11
+            }
+            Z80InstructionSet::InImmediate(port) => {
+                let port = u16::from_be_bytes([state.registers.accumulator, port]);
+                state.registers.accumulator = self.port_in(port);
+                11
+            }
+            Z80InstructionSet::In(register) => {
+                let value = self.port_in(state.registers.pair(RegisterPair::Bc));
+                if let Some(register) = register {
+                    self.store_operand(state, Operand8::Register(register), value);
+                }
+
+                let registers = &mut state.registers;
+                registers.flags = sign_zero_parity(value) | (registers.flags & FlagRegister::Carry);
+                12
+            }
+            Z80InstructionSet::Out(register) => {
+                let value = register.map_or(0, |register| {
+                    self.load_operand(state, Operand8::Register(register))
+                });
+                self.port_out(state.registers.pair(RegisterPair::Bc), value);
+                12
+            }
+            Z80InstructionSet::Im(mode) => {
+                state.interrupt_mode = mode;
+                8
+            }
+            Z80InstructionSet::LdIFromA => {
+                state.registers.interrupt_vector = state.registers.accumulator;
+                9
+            }
+            Z80InstructionSet::LdRFromA => {
+                state.registers.refresh = state.registers.accumulator;
+                9
+            }
+            Z80InstructionSet::LdAFromI | Z80InstructionSet::LdAFromR => {
+                let registers = &mut state.registers;
+                registers.accumulator = if instruction == Z80InstructionSet::LdAFromI {
+                    registers.interrupt_vector
+                } else {
+                    registers.refresh
+                };
+
+                let mut flags =
+                    sign_zero(registers.accumulator) | (registers.flags & FlagRegister::Carry);
+                flags.set(
+                    FlagRegister::ParityOverflow,
+                    state.interrupts_enabled_backup,
+                );
+                registers.flags = flags;
+                9
+            }
+            Z80InstructionSet::Rrd | Z80InstructionSet::Rld => {
+                let address = state.registers.pair(RegisterPair::Hl);
+                let value = self.read(address);
+                let accumulator = state.registers.accumulator;
+
+                // The low nibble of a and the two nibbles of (hl) rotate as one 12 bit number
+                let (memory, low_nibble) = if instruction == Z80InstructionSet::Rld {
+                    ((value << 4) | (accumulator & 0x0f), value >> 4)
+                } else {
+                    ((accumulator << 4) | (value >> 4), value & 0x0f)
+                };
+                self.write(address, memory);
+
+                let registers = &mut state.registers;
+                registers.accumulator = (accumulator & 0xf0) | low_nibble;
+                registers.flags = sign_zero_parity(registers.accumulator)
+                    | (registers.flags & FlagRegister::Carry);
+                18
+            }
+            Z80InstructionSet::Block {
+                operation,
+                decrement,
+                repeat,
+            } => {
+                let again = self.block_step(state, operation, decrement);
+
+                if !(repeat && again) {
+                    return 16;
+                }
+
+                // Runs itself again, so interrupts can get in between steps
+                state.registers.program = state.registers.program.wrapping_sub(2);
+                21
+            }
+            Z80InstructionSet::Rotate {
+                operation,
+                operand,
+                copy,
+            } => {
+                let value = self.load_operand(state, operand);
+                let result = state.registers.rotate(operation, value);
+                state.registers.flags =
+                    sign_zero_parity(result) | (state.registers.flags & FlagRegister::Carry);
+                self.store_bit_result(state, operand, copy, result);
+
+                8 + read_modify_write_cycles(operand)
+            }
+            Z80InstructionSet::Bit { bit, operand } => {
+                let value = self.load_operand(state, operand);
+                let clear = value & (1 << bit) == 0;
+
+                let registers = &mut state.registers;
+                // The undocumented bits come from whatever is on the internal address latch
+                let hidden = match operand {
+                    Operand8::Indirect(index, displacement) => registers
+                        .indirect_address(index, displacement)
+                        .to_be_bytes()[0],
+                    _ => value,
+                };
+                let mut flags = (registers.flags & FlagRegister::Carry)
+                    | FlagRegister::HalfCarry
+                    | undocumented(hidden);
+                flags.set(FlagRegister::Zero, clear);
+                flags.set(FlagRegister::ParityOverflow, clear);
+                flags.set(FlagRegister::Sign, bit == 7 && !clear);
+                registers.flags = flags;
+
+                // Only reads, so it skips the write back
+                match operand {
+                    Operand8::Indirect(..) => 12,
+                    _ => 8,
+                }
+            }
+            Z80InstructionSet::Res { bit, operand, copy }
+            | Z80InstructionSet::Set { bit, operand, copy } => {
+                let value = self.load_operand(state, operand);
+                let result = if matches!(instruction, Z80InstructionSet::Set { .. }) {
+                    value | (1 << bit)
+                } else {
+                    value & !(1 << bit)
+                };
+                self.store_bit_result(state, operand, copy, result);
+
+                8 + read_modify_write_cycles(operand)
+            }
+        };
+
+        cycles + instruction.index_cycles()
+    }
+
+    /// One step of a block instruction, returning if a repeating one would go again
+    fn block_step(
+        &self,
+        state: &mut ProcessorState,
+        operation: BlockOperation,
+        decrement: bool,
+    ) -> bool {
+        let step = |value: u16| {
+            if decrement {
+                value.wrapping_sub(1)
+            } else {
+                value.wrapping_add(1)
+            }
+        };
+        let hl = state.registers.pair(RegisterPair::Hl);
+        state.registers.set_pair(RegisterPair::Hl, step(hl));
+
+        match operation {
+            BlockOperation::Ld | BlockOperation::Cp => {
+                let value = self.read(hl);
+                let bc = state.registers.pair(RegisterPair::Bc).wrapping_sub(1);
+                state.registers.set_pair(RegisterPair::Bc, bc);
+
+                let registers = &mut state.registers;
+                let accumulator = registers.accumulator;
+
+                if operation == BlockOperation::Ld {
+                    let de = registers.pair(RegisterPair::De);
+                    registers.set_pair(RegisterPair::De, step(de));
+                    self.write(de, value);
+
+                    // The undocumented bits come from bits 1 and 3 of the byte plus a
+                    let hidden = value.wrapping_add(accumulator);
+                    let mut flags = registers.flags
+                        & (FlagRegister::Sign | FlagRegister::Zero | FlagRegister::Carry);
+                    flags.set(FlagRegister::Y, hidden & 0b0000_0010 != 0);
+                    flags.set(FlagRegister::X, hidden & 0b0000_1000 != 0);
+                    flags.set(FlagRegister::ParityOverflow, bc != 0);
+                    registers.flags = flags;
+
+                    bc != 0
+                } else {
+                    let result = accumulator.wrapping_sub(value);
+                    let half_carry = accumulator & 0x0f < value & 0x0f;
+
+                    let hidden = result.wrapping_sub(half_carry as u8);
+                    let mut flags =
+                        (registers.flags & FlagRegister::Carry) | FlagRegister::Subtract;
+                    flags.set(FlagRegister::Sign, result & 0x80 != 0);
+                    flags.set(FlagRegister::Zero, result == 0);
+                    flags.set(FlagRegister::HalfCarry, half_carry);
+                    flags.set(FlagRegister::Y, hidden & 0b0000_0010 != 0);
+                    flags.set(FlagRegister::X, hidden & 0b0000_1000 != 0);
+                    flags.set(FlagRegister::ParityOverflow, bc != 0);
+                    registers.flags = flags;
+
+                    bc != 0 && result != 0
+                }
+            }
+            BlockOperation::In | BlockOperation::Out => {
+                let b = state.registers.general[Register8::B as usize].wrapping_sub(1);
+
+                // in uses bc before b counts down, out after
+                let (value, port_low) = if operation == BlockOperation::In {
+                    let bc = state.registers.pair(RegisterPair::Bc);
+                    let value = self.port_in(bc);
+                    self.write(hl, value);
+                    state.registers.general[Register8::B as usize] = b;
+
+                    (value, step(bc) as u8)
+                } else {
+                    let value = self.read(hl);
+                    state.registers.general[Register8::B as usize] = b;
+                    self.port_out(state.registers.pair(RegisterPair::Bc), value);
+
+                    (value, step(hl) as u8)
+                };
+
+                let registers = &mut state.registers;
+                let hidden = value as u16 + port_low as u16;
+                let mut flags = sign_zero(b);
+                flags.set(FlagRegister::Subtract, value & 0x80 != 0);
+                flags.set(FlagRegister::HalfCarry, hidden > 0xff);
+                flags.set(FlagRegister::Carry, hidden > 0xff);
+                flags.set(
+                    FlagRegister::ParityOverflow,
+                    ((hidden as u8 & 0b111) ^ b).count_ones() % 2 == 0,
+                );
+                registers.flags = flags;
+
+                b != 0
+            }
+        }
+    }
+}
+
+/// ld (nn), hl has its own opcode, the other pairs pay for the ed prefix
+fn extended_pair_cycles(register: RegisterPair) -> u32 {
+    match register {
+        RegisterPair::Hl | RegisterPair::Ix | RegisterPair::Iy => 16,
+        _ => 20,
+    }
+}
+
+impl Z80Registers {
+    fn pair(&self, register: RegisterPair) -> u16 {
+        let general = |index: usize| {
+            u16::from_be_bytes([self.general[index * 2], self.general[index * 2 + 1]])
+        };
+
+        match register {
+            RegisterPair::Bc => general(0),
+            RegisterPair::De => general(1),
+            RegisterPair::Hl => general(2),
+            RegisterPair::Sp => self.stack_pointer,
+            RegisterPair::Af => u16::from_be_bytes([self.accumulator, self.flags.bits()]),
+            RegisterPair::Ix => self.index_x,
+            RegisterPair::Iy => self.index_y,
+        }
+    }
+
+    fn set_pair(&mut self, register: RegisterPair, value: u16) {
+        let [high, low] = value.to_be_bytes();
+        let mut general = |index: usize| {
+            self.general[index * 2] = high;
+            self.general[index * 2 + 1] = low;
+        };
+
+        match register {
+            RegisterPair::Bc => general(0),
+            RegisterPair::De => general(1),
+            RegisterPair::Hl => general(2),
+            RegisterPair::Sp => self.stack_pointer = value,
+            RegisterPair::Af => {
+                self.accumulator = high;
+                self.flags = BitFlags::from_bits_truncate(low);
+            }
+            RegisterPair::Ix => self.index_x = value,
+            RegisterPair::Iy => self.index_y = value,
+        }
+    }
+
+    fn index(&self, index: IndexRegister) -> u16 {
+        self.pair(index.into())
+    }
+
+    /// (hl), or (ix+d) and (iy+d)
+    fn indirect_address(&self, index: IndexRegister, displacement: i8) -> u16 {
+        self.index(index).wrapping_add_signed(displacement as i16)
+    }
+
+    fn condition(&self, condition: Option<Condition>) -> bool {
+        let flag = |flag: FlagRegister| self.flags.contains(flag);
+
+        match condition {
+            None => true,
+            Some(Condition::NotZero) => !flag(FlagRegister::Zero),
+            Some(Condition::Zero) => flag(FlagRegister::Zero),
+            Some(Condition::NotCarry) => !flag(FlagRegister::Carry),
+            Some(Condition::Carry) => flag(FlagRegister::Carry),
+            Some(Condition::ParityOdd) => !flag(FlagRegister::ParityOverflow),
+            Some(Condition::ParityEven) => flag(FlagRegister::ParityOverflow),
+            Some(Condition::Positive) => !flag(FlagRegister::Sign),
+            Some(Condition::Negative) => flag(FlagRegister::Sign),
+        }
+    }
+
+    fn alu(&mut self, operation: AluOperation, value: u8) {
+        let accumulator = self.accumulator;
+        let carry = (matches!(operation, AluOperation::Adc | AluOperation::Sbc)
+            && self.flags.contains(FlagRegister::Carry)) as u8;
+
+        let (result, mut flags) = match operation {
+            AluOperation::Add | AluOperation::Adc => {
+                let wide = accumulator as u16 + value as u16 + carry as u16;
+                let result = wide as u8;
+
+                let mut flags = sign_zero(result);
+                flags.set(
+                    FlagRegister::HalfCarry,
+                    (accumulator & 0x0f) + (value & 0x0f) + carry > 0x0f,
+                );
+                flags.set(
+                    FlagRegister::ParityOverflow,
+                    (accumulator ^ result) & (value ^ result) & 0x80 != 0,
+                );
+                flags.set(FlagRegister::Carry, wide > 0xff);
+
+                (result, flags)
+            }
+            AluOperation::Sub | AluOperation::Sbc | AluOperation::Cp => {
+                let wide = accumulator as i16 - value as i16 - carry as i16;
+                let result = wide as u8;
+
+                let mut flags = sign_zero(result) | FlagRegister::Subtract;
+                flags.set(
+                    FlagRegister::HalfCarry,
+                    ((accumulator & 0x0f) as i16) - ((value & 0x0f) as i16) - (carry as i16) < 0,
+                );
+                flags.set(
+                    FlagRegister::ParityOverflow,
+                    (accumulator ^ value) & (accumulator ^ result) & 0x80 != 0,
+                );
+                flags.set(FlagRegister::Carry, wide < 0);
+
+                (result, flags)
+            }
+            AluOperation::And => {
+                let result = accumulator & value;
+                (result, sign_zero_parity(result) | FlagRegister::HalfCarry)
+            }
+            AluOperation::Xor => {
+                let result = accumulator ^ value;
+                (result, sign_zero_parity(result))
+            }
+            AluOperation::Or => {
+                let result = accumulator | value;
+                (result, sign_zero_parity(result))
+            }
+        };
+
+        // Compare is a subtraction that throws the result away, and takes the undocumented bits from the operand
+        if operation == AluOperation::Cp {
+            flags.remove(FlagRegister::Y | FlagRegister::X);
+            flags |= undocumented(value);
+        } else {
+            self.accumulator = result;
+        }
+
+        self.flags = flags;
+    }
+
+    /// inc and dec, which leave carry alone
+    fn increment(&mut self, value: u8, decrement: bool) -> u8 {
+        let result = if decrement {
+            value.wrapping_sub(1)
+        } else {
+            value.wrapping_add(1)
+        };
+
+        let mut flags = sign_zero(result) | (self.flags & FlagRegister::Carry);
+        if decrement {
+            flags.insert(FlagRegister::Subtract);
+            flags.set(FlagRegister::HalfCarry, value & 0x0f == 0x00);
+            flags.set(FlagRegister::ParityOverflow, value == 0x80);
+        } else {
+            flags.set(FlagRegister::HalfCarry, value & 0x0f == 0x0f);
+            flags.set(FlagRegister::ParityOverflow, value == 0x7f);
+        }
+        self.flags = flags;
+
+        result
+    }
+
+    /// add hl, which leaves sign, zero and parity alone
+    fn add16(&mut self, left: u16, right: u16) -> u16 {
+        let result = left.wrapping_add(right);
+
+        let mut flags = (self.flags
+            & (FlagRegister::Sign | FlagRegister::Zero | FlagRegister::ParityOverflow))
+            | undocumented(result.to_be_bytes()[0]);
+        flags.set(
+            FlagRegister::HalfCarry,
+            (left & 0x0fff) + (right & 0x0fff) > 0x0fff,
+        );
+        flags.set(FlagRegister::Carry, left as u32 + right as u32 > 0xffff);
+        self.flags = flags;
+
+        result
+    }
+
+    /// adc hl and sbc hl, which set every flag from the 16 bit result
+    fn add16_with_carry(&mut self, value: u16, subtract: bool) {
+        let hl = self.pair(RegisterPair::Hl);
+        let carry = self.flags.contains(FlagRegister::Carry) as i32;
+
+        let (wide, half_carry) = if subtract {
+            (
+                hl as i32 - value as i32 - carry,
+                ((hl & 0x0fff) as i32) - ((value & 0x0fff) as i32) - carry < 0,
+            )
+        } else {
+            (
+                hl as i32 + value as i32 + carry,
+                ((hl & 0x0fff) as i32) + ((value & 0x0fff) as i32) + carry > 0x0fff,
+            )
+        };
+        let result = wide as u16;
+        let overflow = if subtract {
+            (hl ^ value) & (hl ^ result) & 0x8000 != 0
+        } else {
+            (hl ^ result) & (value ^ result) & 0x8000 != 0
+        };
+
+        let mut flags = sign_zero(result.to_be_bytes()[0]);
+        flags.set(FlagRegister::Zero, result == 0);
+        flags.set(FlagRegister::HalfCarry, half_carry);
+        flags.set(FlagRegister::ParityOverflow, overflow);
+        flags.set(FlagRegister::Subtract, subtract);
+        flags.set(FlagRegister::Carry, !(0..=0xffff).contains(&wide));
+        self.flags = flags;
+
+        self.set_pair(RegisterPair::Hl, result);
+    }
+
+    /// Returns the result and sets carry, the callers decide what else the flags get
+    fn rotate(&mut self, operation: RotateOperation, value: u8) -> u8 {
+        let carry = self.flags.contains(FlagRegister::Carry) as u8;
+
+        let (result, carry_out) = match operation {
+            RotateOperation::Rlc => (value.rotate_left(1), value & 0x80 != 0),
+            RotateOperation::Rrc => (value.rotate_right(1), value & 0x01 != 0),
+            RotateOperation::Rl => ((value << 1) | carry, value & 0x80 != 0),
+            RotateOperation::Rr => ((value >> 1) | (carry << 7), value & 0x01 != 0),
+            RotateOperation::Sla => (value << 1, value & 0x80 != 0),
+            RotateOperation::Sra => ((value >> 1) | (value & 0x80), value & 0x01 != 0),
+            RotateOperation::Sll => ((value << 1) | 0x01, value & 0x80 != 0),
+            RotateOperation::Srl => (value >> 1, value & 0x01 != 0),
+        };
+
+        self.flags.set(FlagRegister::Carry, carry_out);
+
+        result
+    }
+
+    /// Fixes up the accumulator after adding or subtracting two binary coded decimal numbers
+    fn daa(&mut self) {
+        let accumulator = self.accumulator;
+        let subtract = self.flags.contains(FlagRegister::Subtract);
+        let mut carry = self.flags.contains(FlagRegister::Carry);
+        let half_carry = self.flags.contains(FlagRegister::HalfCarry);
+
+        let mut correction = 0;
+        if half_carry || accumulator & 0x0f > 0x09 {
+            correction |= 0x06;
+        }
+        if carry || accumulator > 0x99 {
+            correction |= 0x60;
+            carry = true;
+        }
+
+        let result = if subtract {
+            accumulator.wrapping_sub(correction)
+        } else {
+            accumulator.wrapping_add(correction)
+        };
+        let half_carry = if subtract {
+            half_carry && accumulator & 0x0f < 0x06
+        } else {
+            accumulator & 0x0f > 0x09
+        };
+
+        let mut flags = sign_zero_parity(result);
+        flags.set(FlagRegister::Subtract, subtract);
+        flags.set(FlagRegister::HalfCarry, half_carry);
+        flags.set(FlagRegister::Carry, carry);
+        self.flags = flags;
+        self.accumulator = result;
+    }
+}
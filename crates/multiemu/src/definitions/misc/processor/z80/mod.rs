@@ -0,0 +1,588 @@
+use crate::{
+    component::{
+        processor::{DebuggableProcessor, ProcessorDebugState, ProcessorRegister},
+        schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::io::InterruptConnection,
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, MemoryTranslationTable},
+};
+use decode::decode_instruction;
+use enumflags2::{bitflags, BitFlags};
+use num::rational::Ratio;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc, Mutex, OnceLock,
+};
+
+pub mod decode;
+pub mod instruction;
+pub mod interpret;
+
+#[cfg(test)]
+pub mod test;
+
+/// Where a non maskable interrupt jumps
+const NMI_VECTOR: u16 = 0x0066;
+/// Where interrupt mode 1 jumps
+const INTERRUPT_MODE_1_VECTOR: u16 = 0x0038;
+
+#[bitflags]
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum FlagRegister {
+    Sign = 0b1000_0000,
+    Zero = 0b0100_0000,
+    /// Undocumented, usually bit 5 of the result
+    Y = 0b0010_0000,
+    /// Carry out of bit 3
+    HalfCarry = 0b0001_0000,
+    /// Undocumented, usually bit 3 of the result
+    X = 0b0000_1000,
+    /// Parity for logic, overflow for arithmetic
+    ParityOverflow = 0b0000_0100,
+    /// Set if the last operation was a subtraction, only daa looks at it
+    Subtract = 0b0000_0010,
+    Carry = 0b0000_0001,
+}
+
+/// The two interrupt inputs
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Z80Interrupt {
+    /// /INT, held by as many devices as want it, and taken according to the interrupt mode
+    Maskable,
+    /// /NMI, taken on the edge no matter what
+    NonMaskable,
+}
+
+/// The order [DebuggableProcessor] exposes [Z80Registers] in, as pairs
+const DEBUG_REGISTERS: &[ProcessorRegister] = &[
+    ProcessorRegister {
+        name: "af",
+        size: 2,
+        generic: Some("flags"),
+    },
+    ProcessorRegister {
+        name: "bc",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "de",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "hl",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "ix",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "iy",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "sp",
+        size: 2,
+        generic: Some("sp"),
+    },
+    ProcessorRegister {
+        name: "pc",
+        size: 2,
+        generic: Some("pc"),
+    },
+    ProcessorRegister {
+        name: "af'",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "bc'",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "de'",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "hl'",
+        size: 2,
+        generic: None,
+    },
+    ProcessorRegister {
+        name: "ir",
+        size: 2,
+        generic: None,
+    },
+];
+
+#[derive(Debug, Clone)]
+pub struct Z80Registers {
+    accumulator: u8,
+    flags: BitFlags<FlagRegister>,
+    /// b, c, d, e, h and l, in the order [instruction::Register8] numbers them
+    general: [u8; 6],
+    /// af', swapped in by ex af, af'
+    shadow_accumulator: u8,
+    shadow_flags: BitFlags<FlagRegister>,
+    /// bc', de' and hl', swapped in by exx
+    shadow_general: [u8; 6],
+    index_x: u16,
+    index_y: u16,
+    stack_pointer: u16,
+    program: u16,
+    /// i, the top half of the mode 2 vector table address
+    interrupt_vector: u8,
+    /// r, counts opcode fetches in its bottom 7 bits for DRAM refresh
+    refresh: u8,
+}
+
+impl Default for Z80Registers {
+    // Only pc, i and r are defined after a reset, but af and sp come up as all ones on every chip anyone checked
+    fn default() -> Self {
+        Self {
+            accumulator: 0xff,
+            flags: BitFlags::all(),
+            general: [0xff; 6],
+            shadow_accumulator: 0xff,
+            shadow_flags: BitFlags::all(),
+            shadow_general: [0xff; 6],
+            index_x: 0xffff,
+            index_y: 0xffff,
+            stack_pointer: 0xffff,
+            program: 0,
+            interrupt_vector: 0,
+            refresh: 0,
+        }
+    }
+}
+
+impl Z80Registers {
+    /// Counts `fetches` opcode fetches, bit 7 is left as the program set it
+    fn refresh(&mut self, fetches: u8) {
+        self.refresh = (self.refresh & 0x80) | (self.refresh.wrapping_add(fetches) & 0x7f);
+    }
+}
+
+#[derive(Debug)]
+pub struct Z80Config {
+    /// The clock instruction timings are counted in, 3579545 hz on most NTSC machines
+    pub frequency: Ratio<u64>,
+    pub assigned_address_space: AddressSpaceId,
+    /// Where in and out go, with the full 16 bit port address on the bus
+    pub io_address_space: AddressSpaceId,
+    /// What the data bus holds while a maskable interrupt is acknowledged, 0xff where nothing drives it
+    ///
+    /// Mode 0 runs it as an instruction, where only rst is supported, and mode 2 uses it as the bottom half of the
+    /// vector table address
+    pub interrupt_data: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+enum ExecutionState {
+    #[default]
+    Running,
+    /// Running nops until an interrupt comes along
+    Halted,
+}
+
+#[derive(Debug, Default)]
+struct ProcessorState {
+    registers: Z80Registers,
+    /// iff1, if maskable interrupts are taken at all
+    interrupts_enabled: bool,
+    /// iff2, where iff1 is kept while an nmi runs so retn can put it back
+    interrupts_enabled_backup: bool,
+    interrupt_mode: u8,
+    /// ei holds interrupts off until the instruction after it is done
+    interrupts_blocked: bool,
+    execution_state: ExecutionState,
+    /// Cycles left of the current instruction
+    pending_cycles: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Z80Snapshot {
+    af: u16,
+    bc: u16,
+    de: u16,
+    hl: u16,
+    shadow_af: u16,
+    shadow_bc: u16,
+    shadow_de: u16,
+    shadow_hl: u16,
+    index_x: u16,
+    index_y: u16,
+    stack_pointer: u16,
+    program: u16,
+    interrupt_vector: u8,
+    refresh: u8,
+    interrupts_enabled: bool,
+    interrupts_enabled_backup: bool,
+    interrupt_mode: u8,
+    interrupts_blocked: bool,
+    execution_state: ExecutionState,
+    pending_cycles: u32,
+    maskable_interrupt: u32,
+    non_maskable_interrupt: bool,
+}
+
+/// The interrupt inputs, which peripherals drive from the outside while the processor runs
+#[derive(Debug, Default)]
+struct InterruptLines {
+    /// How many devices are holding /INT, it stays asserted until all of them let go
+    maskable: AtomicU32,
+    /// Set on an edge of /NMI, cleared once it is taken
+    non_maskable: AtomicBool,
+}
+
+#[derive(Debug)]
+struct Z80InterruptRequest {
+    interrupt_lines: Arc<InterruptLines>,
+    interrupt: Z80Interrupt,
+}
+
+impl InterruptConnection for Z80InterruptRequest {
+    fn set_interrupt(&self, raised: bool) {
+        match (self.interrupt, raised) {
+            (Z80Interrupt::Maskable, true) => {
+                self.interrupt_lines
+                    .maskable
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            (Z80Interrupt::Maskable, false) => {
+                let _ = self.interrupt_lines.maskable.fetch_update(
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    |holders| Some(holders.saturating_sub(1)),
+                );
+            }
+            (Z80Interrupt::NonMaskable, true) => {
+                self.interrupt_lines
+                    .non_maskable
+                    .store(true, Ordering::Relaxed);
+            }
+            (Z80Interrupt::NonMaskable, false) => {}
+        }
+    }
+}
+
+/// The Zilog Z80, with the undocumented index register halves, dd cb copies and flag bits
+///
+/// Instructions happen all at once on their first cycle
+#[derive(Debug)]
+pub struct Z80 {
+    config: Z80Config,
+    state: Mutex<ProcessorState>,
+    interrupt_lines: Arc<InterruptLines>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+    debug: ProcessorDebugState,
+}
+
+impl Z80 {
+    /// What a peripheral raises to ask for an interrupt, every device sharing /INT should get its own
+    pub fn interrupt_connection(&self, interrupt: Z80Interrupt) -> Arc<dyn InterruptConnection> {
+        Arc::new(Z80InterruptRequest {
+            interrupt_lines: self.interrupt_lines.clone(),
+            interrupt,
+        })
+    }
+
+    /// Takes an interrupt if one is due, returning the cycles it took
+    fn service_interrupts(&self, state: &mut ProcessorState) -> Option<u32> {
+        if self
+            .interrupt_lines
+            .non_maskable
+            .swap(false, Ordering::Relaxed)
+        {
+            state.execution_state = ExecutionState::Running;
+            state.interrupts_enabled = false;
+            state.registers.refresh(1);
+
+            let program = state.registers.program;
+            self.push(state, program);
+            state.registers.program = NMI_VECTOR;
+
+            return Some(11);
+        }
+
+        if !state.interrupts_enabled
+            || state.interrupts_blocked
+            || self.interrupt_lines.maskable.load(Ordering::Relaxed) == 0
+        {
+            return None;
+        }
+
+        state.execution_state = ExecutionState::Running;
+        state.interrupts_enabled = false;
+        state.interrupts_enabled_backup = false;
+        state.registers.refresh(1);
+
+        let program = state.registers.program;
+        self.push(state, program);
+
+        Some(match state.interrupt_mode {
+            // Everything we emulate either leaves the bus floating, which reads as rst 0x38, or puts an rst there
+            0 => {
+                state.registers.program = (self.config.interrupt_data & 0b0011_1000) as u16;
+                13
+            }
+            1 => {
+                state.registers.program = INTERRUPT_MODE_1_VECTOR;
+                13
+            }
+            _ => {
+                let address = u16::from_be_bytes([
+                    state.registers.interrupt_vector,
+                    self.config.interrupt_data,
+                ]);
+                state.registers.program = self.read_u16(address);
+                19
+            }
+        })
+    }
+
+    /// Runs an instruction or takes an interrupt, returning the cycles it took
+    fn step_instruction(&self, state: &mut ProcessorState) -> u32 {
+        if let Some(cycles) = self.service_interrupts(state) {
+            return cycles;
+        }
+        state.interrupts_blocked = false;
+
+        if state.execution_state == ExecutionState::Halted {
+            // Still fetching, so refresh keeps counting
+            state.registers.refresh(1);
+            return 4;
+        }
+
+        let (instruction, length) = decode_instruction(
+            state.registers.program,
+            self.config.assigned_address_space,
+            self.memory_translation_table.get().unwrap(),
+        );
+
+        tracing::trace!(
+            "Decoded instruction {:?} from {:#06x}",
+            instruction,
+            state.registers.program
+        );
+
+        // Prefixed instructions fetch two opcodes, even dd cb which reads its last byte like an operand
+        let fetches = if length > 1 && self.prefixed(state.registers.program) {
+            2
+        } else {
+            1
+        };
+        state.registers.refresh(fetches);
+        state.registers.program = state.registers.program.wrapping_add(length as u16);
+
+        self.interpret_instruction(state, instruction)
+    }
+}
+
+impl Component for Z80 {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = ProcessorState::default();
+        // What devices hold the lines at is up to them
+        self.interrupt_lines
+            .non_maskable
+            .store(false, Ordering::Relaxed);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        let state = self.state.lock().unwrap();
+        let registers = &state.registers;
+        let pair = |high: u8, low: u8| u16::from_be_bytes([high, low]);
+
+        rmpv::ext::to_value(Z80Snapshot {
+            af: pair(registers.accumulator, registers.flags.bits()),
+            bc: pair(registers.general[0], registers.general[1]),
+            de: pair(registers.general[2], registers.general[3]),
+            hl: pair(registers.general[4], registers.general[5]),
+            shadow_af: pair(registers.shadow_accumulator, registers.shadow_flags.bits()),
+            shadow_bc: pair(registers.shadow_general[0], registers.shadow_general[1]),
+            shadow_de: pair(registers.shadow_general[2], registers.shadow_general[3]),
+            shadow_hl: pair(registers.shadow_general[4], registers.shadow_general[5]),
+            index_x: registers.index_x,
+            index_y: registers.index_y,
+            stack_pointer: registers.stack_pointer,
+            program: registers.program,
+            interrupt_vector: registers.interrupt_vector,
+            refresh: registers.refresh,
+            interrupts_enabled: state.interrupts_enabled,
+            interrupts_enabled_backup: state.interrupts_enabled_backup,
+            interrupt_mode: state.interrupt_mode,
+            interrupts_blocked: state.interrupts_blocked,
+            execution_state: state.execution_state,
+            pending_cycles: state.pending_cycles,
+            maskable_interrupt: self.interrupt_lines.maskable.load(Ordering::Relaxed),
+            non_maskable_interrupt: self.interrupt_lines.non_maskable.load(Ordering::Relaxed),
+        })
+        .unwrap()
+    }
+
+    fn load_snapshot(&self, snapshot: rmpv::Value) {
+        let snapshot: Z80Snapshot = rmpv::ext::from_value(snapshot).unwrap();
+        let mut state = self.state.lock().unwrap();
+
+        let [accumulator, flags] = snapshot.af.to_be_bytes();
+        let [b, c] = snapshot.bc.to_be_bytes();
+        let [d, e] = snapshot.de.to_be_bytes();
+        let [h, l] = snapshot.hl.to_be_bytes();
+        let [shadow_accumulator, shadow_flags] = snapshot.shadow_af.to_be_bytes();
+        let [shadow_b, shadow_c] = snapshot.shadow_bc.to_be_bytes();
+        let [shadow_d, shadow_e] = snapshot.shadow_de.to_be_bytes();
+        let [shadow_h, shadow_l] = snapshot.shadow_hl.to_be_bytes();
+        state.registers = Z80Registers {
+            accumulator,
+            flags: BitFlags::from_bits_truncate(flags),
+            general: [b, c, d, e, h, l],
+            shadow_accumulator,
+            shadow_flags: BitFlags::from_bits_truncate(shadow_flags),
+            shadow_general: [shadow_b, shadow_c, shadow_d, shadow_e, shadow_h, shadow_l],
+            index_x: snapshot.index_x,
+            index_y: snapshot.index_y,
+            stack_pointer: snapshot.stack_pointer,
+            program: snapshot.program,
+            interrupt_vector: snapshot.interrupt_vector,
+            refresh: snapshot.refresh,
+        };
+        state.interrupts_enabled = snapshot.interrupts_enabled;
+        state.interrupts_enabled_backup = snapshot.interrupts_enabled_backup;
+        state.interrupt_mode = snapshot.interrupt_mode;
+        state.interrupts_blocked = snapshot.interrupts_blocked;
+        state.execution_state = snapshot.execution_state;
+        state.pending_cycles = snapshot.pending_cycles;
+        self.interrupt_lines
+            .maskable
+            .store(snapshot.maskable_interrupt, Ordering::Relaxed);
+        self.interrupt_lines
+            .non_maskable
+            .store(snapshot.non_maskable_interrupt, Ordering::Relaxed);
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for Z80 {
+    type Config = Z80Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let frequency = config.frequency;
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                interrupt_lines: Arc::default(),
+                memory_translation_table: OnceLock::default(),
+                debug: ProcessorDebugState::default(),
+            })
+            .set_schedulable(frequency, [], [])
+            .set_debuggable();
+    }
+}
+
+impl SchedulableComponent for Z80 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..period {
+            if state.pending_cycles == 0 {
+                if self.debug.should_stop(state.registers.program as usize) {
+                    return;
+                }
+
+                state.pending_cycles = self.step_instruction(&mut state);
+            }
+
+            state.pending_cycles -= 1;
+        }
+    }
+}
+
+impl DebuggableProcessor for Z80 {
+    fn registers(&self) -> &'static [ProcessorRegister] {
+        DEBUG_REGISTERS
+    }
+
+    fn read_register(&self, index: usize) -> u64 {
+        let registers = &self.state.lock().unwrap().registers;
+        let pair = |general: &[u8; 6], index: usize| {
+            u16::from_be_bytes([general[index * 2], general[index * 2 + 1]]) as u64
+        };
+
+        match index {
+            0 => u16::from_be_bytes([registers.accumulator, registers.flags.bits()]) as u64,
+            1..=3 => pair(&registers.general, index - 1),
+            4 => registers.index_x as u64,
+            5 => registers.index_y as u64,
+            6 => registers.stack_pointer as u64,
+            7 => registers.program as u64,
+            8 => u16::from_be_bytes([registers.shadow_accumulator, registers.shadow_flags.bits()])
+                as u64,
+            9..=11 => pair(&registers.shadow_general, index - 9),
+            12 => u16::from_be_bytes([registers.interrupt_vector, registers.refresh]) as u64,
+            _ => 0,
+        }
+    }
+
+    fn write_register(&self, index: usize, value: u64) {
+        let registers = &mut self.state.lock().unwrap().registers;
+        let [high, low] = (value as u16).to_be_bytes();
+
+        match index {
+            0 => {
+                registers.accumulator = high;
+                registers.flags = BitFlags::from_bits_truncate(low);
+            }
+            1..=3 => {
+                registers.general[(index - 1) * 2] = high;
+                registers.general[(index - 1) * 2 + 1] = low;
+            }
+            4 => registers.index_x = value as u16,
+            5 => registers.index_y = value as u16,
+            6 => registers.stack_pointer = value as u16,
+            7 => registers.program = value as u16,
+            8 => {
+                registers.shadow_accumulator = high;
+                registers.shadow_flags = BitFlags::from_bits_truncate(low);
+            }
+            9..=11 => {
+                registers.shadow_general[(index - 9) * 2] = high;
+                registers.shadow_general[(index - 9) * 2 + 1] = low;
+            }
+            12 => {
+                registers.interrupt_vector = high;
+                registers.refresh = low;
+            }
+            _ => {}
+        }
+    }
+
+    fn address_space(&self) -> AddressSpaceId {
+        self.config.assigned_address_space
+    }
+
+    fn step(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        state.pending_cycles = self.step_instruction(&mut state);
+    }
+
+    fn debug_state(&self) -> &ProcessorDebugState {
+        &self.debug
+    }
+}
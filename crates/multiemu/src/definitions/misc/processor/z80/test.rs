@@ -0,0 +1,427 @@
+use super::{
+    decode::decode_instruction,
+    instruction::{
+        AluOperation, BlockOperation, IndexRegister, Operand8, Register8, RegisterPair,
+        RotateOperation, Z80InstructionSet,
+    },
+    FlagRegister, Z80Config, Z80Interrupt, Z80,
+};
+use crate::{
+    component::processor::DebuggableProcessor,
+    definitions::misc::memory::standard::{
+        StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents,
+    },
+    machine::{
+        test_machine::{TestMachine, TestMachineBuilder},
+        Machine,
+    },
+    memory::AddressSpaceId,
+    rom::{manager::RomManager, system::GameSystem},
+};
+use enumflags2::BitFlags;
+use indexmap::IndexMap;
+use num::rational::Ratio;
+use std::{borrow::Cow, sync::Arc};
+
+const ADDRESS_SPACE: AddressSpaceId = 0;
+const IO_ADDRESS_SPACE: AddressSpaceId = 1;
+
+#[test]
+fn z80_instruction_decode() {
+    let rom_manager = Arc::new(RomManager::new(None).unwrap());
+
+    let map: IndexMap<_, _> = IndexMap::from_iter([
+        ([0x00].as_slice(), (Z80InstructionSet::Nop, 1)),
+        (
+            [0x01, 0x34, 0x12].as_slice(),
+            (
+                Z80InstructionSet::Ld16 {
+                    destination: RegisterPair::Bc,
+                    value: 0x1234,
+                },
+                3,
+            ),
+        ),
+        ([0x10, 0xfe].as_slice(), (Z80InstructionSet::Djnz(-2), 2)),
+        (
+            [0x36, 0xff].as_slice(),
+            (
+                Z80InstructionSet::Ld {
+                    destination: Operand8::Indirect(IndexRegister::Hl, 0),
+                    source: Operand8::Immediate(0xff),
+                },
+                2,
+            ),
+        ),
+        ([0x76].as_slice(), (Z80InstructionSet::Halt, 1)),
+        (
+            [0x9e].as_slice(),
+            (
+                Z80InstructionSet::Alu {
+                    operation: AluOperation::Sbc,
+                    operand: Operand8::Indirect(IndexRegister::Hl, 0),
+                },
+                1,
+            ),
+        ),
+        (
+            [0xd3, 0xbe].as_slice(),
+            (Z80InstructionSet::OutImmediate(0xbe), 2),
+        ),
+        (
+            [0xcb, 0x7e].as_slice(),
+            (
+                Z80InstructionSet::Bit {
+                    bit: 7,
+                    operand: Operand8::Indirect(IndexRegister::Hl, 0),
+                },
+                2,
+            ),
+        ),
+        (
+            [0xcb, 0x30].as_slice(),
+            (
+                Z80InstructionSet::Rotate {
+                    operation: RotateOperation::Sll,
+                    operand: Operand8::Register(Register8::B),
+                    copy: None,
+                },
+                2,
+            ),
+        ),
+        ([0xed, 0x56].as_slice(), (Z80InstructionSet::Im(1), 2)),
+        ([0xed, 0x5f].as_slice(), (Z80InstructionSet::LdAFromR, 2)),
+        (
+            [0xed, 0x78].as_slice(),
+            (Z80InstructionSet::In(Some(Register8::A)), 2),
+        ),
+        (
+            [0xed, 0x43, 0x00, 0xc0].as_slice(),
+            (
+                Z80InstructionSet::LdAbsoluteFrom16 {
+                    address: 0xc000,
+                    source: RegisterPair::Bc,
+                },
+                4,
+            ),
+        ),
+        (
+            [0xed, 0xb0].as_slice(),
+            (
+                Z80InstructionSet::Block {
+                    operation: BlockOperation::Ld,
+                    decrement: false,
+                    repeat: true,
+                },
+                2,
+            ),
+        ),
+        ([0xed, 0x00].as_slice(), (Z80InstructionSet::Nop, 2)),
+        (
+            [0xdd, 0x21, 0x34, 0x12].as_slice(),
+            (
+                Z80InstructionSet::Ld16 {
+                    destination: RegisterPair::Ix,
+                    value: 0x1234,
+                },
+                4,
+            ),
+        ),
+        (
+            [0xdd, 0x36, 0x05, 0x42].as_slice(),
+            (
+                Z80InstructionSet::Ld {
+                    destination: Operand8::Indirect(IndexRegister::Ix, 5),
+                    source: Operand8::Immediate(0x42),
+                },
+                4,
+            ),
+        ),
+        (
+            [0xfd, 0x66, 0xff].as_slice(),
+            (
+                Z80InstructionSet::Ld {
+                    destination: Operand8::Register(Register8::H),
+                    source: Operand8::Indirect(IndexRegister::Iy, -1),
+                },
+                3,
+            ),
+        ),
+        (
+            [0xfd, 0x7c].as_slice(),
+            (
+                Z80InstructionSet::Ld {
+                    destination: Operand8::Register(Register8::A),
+                    source: Operand8::IndexHigh(IndexRegister::Iy),
+                },
+                2,
+            ),
+        ),
+        (
+            [0xdd, 0xe9].as_slice(),
+            (Z80InstructionSet::JpIndex(IndexRegister::Ix), 2),
+        ),
+        // Nothing to index, so the prefix is skipped on its own
+        ([0xdd, 0x00].as_slice(), (Z80InstructionSet::Nop, 1)),
+        ([0xfd, 0xdd].as_slice(), (Z80InstructionSet::Nop, 1)),
+        (
+            [0xfd, 0xcb, 0xfe, 0x16].as_slice(),
+            (
+                Z80InstructionSet::Rotate {
+                    operation: RotateOperation::Rl,
+                    operand: Operand8::Indirect(IndexRegister::Iy, -2),
+                    copy: None,
+                },
+                4,
+            ),
+        ),
+        (
+            [0xdd, 0xcb, 0x01, 0xc0].as_slice(),
+            (
+                Z80InstructionSet::Set {
+                    bit: 0,
+                    operand: Operand8::Indirect(IndexRegister::Ix, 1),
+                    copy: Some(Register8::B),
+                },
+                4,
+            ),
+        ),
+    ]);
+
+    for (instruction_binary, (decoded_instruction, decoded_instruction_size)) in map {
+        let machine = Machine::build(GameSystem::Unknown, rom_manager.clone())
+            .insert_bus(ADDRESS_SPACE, 16)
+            .build_component::<StandardMemory>(StandardMemoryConfig {
+                max_word_size: 2,
+                readable: true,
+                writable: true,
+                assigned_range: 0..0x4,
+                assigned_address_space: ADDRESS_SPACE,
+                initial_contents: StandardMemoryInitialContents::Array {
+                    value: Cow::Borrowed(instruction_binary),
+                    offset: 0,
+                },
+            })
+            .0
+            .build();
+
+        assert_eq!(
+            (decoded_instruction, decoded_instruction_size),
+            decode_instruction(0x0, ADDRESS_SPACE, &machine.memory_translation_table),
+            "Decoding {:02x?}",
+            instruction_binary
+        );
+    }
+}
+
+fn z80_machine() -> (TestMachine, Arc<Z80>) {
+    let (builder, processor) = TestMachineBuilder::new()
+        .bus(ADDRESS_SPACE, 16)
+        .bus(IO_ADDRESS_SPACE, 16)
+        .scratch_ram(ADDRESS_SPACE, 0..0x10000, 0x00)
+        .scratch_ram(IO_ADDRESS_SPACE, 0..0x10000, 0x00)
+        .component::<Z80>(Z80Config {
+            frequency: Ratio::from_integer(1),
+            assigned_address_space: ADDRESS_SPACE,
+            io_address_space: IO_ADDRESS_SPACE,
+            interrupt_data: 0xff,
+        });
+    let machine = builder.build();
+    let processor = machine.component::<Z80>(processor);
+
+    (machine, processor)
+}
+
+#[test]
+fn z80_interrupt_mode_1() {
+    let (machine, processor) = z80_machine();
+    // ld sp, 0x8000, im 1, ei, halt, then inc a, ei and reti in the handler
+    machine.load(
+        ADDRESS_SPACE,
+        0x0000,
+        &[0x31, 0x00, 0x80, 0xed, 0x56, 0xfb, 0x76],
+    );
+    machine.load(ADDRESS_SPACE, 0x0038, &[0x3c, 0xfb, 0xed, 0x4d]);
+
+    for _ in 0..4 {
+        processor.step();
+    }
+    assert_eq!(processor.read_register(7), 0x0007);
+
+    // Halted, so nothing moves
+    processor.step();
+    assert_eq!(processor.read_register(7), 0x0007);
+
+    let interrupt = processor.interrupt_connection(Z80Interrupt::Maskable);
+    interrupt.set_interrupt(true);
+    processor.step();
+    interrupt.set_interrupt(false);
+    assert_eq!(processor.read_register(7), 0x0038);
+    assert_eq!(processor.read_register(6), 0x7ffe);
+    assert_eq!(machine.peek(ADDRESS_SPACE, 0x7ffe, 2), [0x07, 0x00]);
+
+    for _ in 0..3 {
+        processor.step();
+    }
+    assert_eq!(processor.read_register(7), 0x0007);
+    assert_eq!(processor.read_register(0) >> 8, 0x00);
+}
+
+#[test]
+fn z80_interrupt_mode_2() {
+    let (machine, processor) = z80_machine();
+    // ld sp, 0x8000, ld a, 0x12, ld i, a, im 2, ei, nop
+    machine.load(
+        ADDRESS_SPACE,
+        0x0000,
+        &[
+            0x31, 0x00, 0x80, 0x3e, 0x12, 0xed, 0x47, 0xed, 0x5e, 0xfb, 0x00,
+        ],
+    );
+    // Nothing drives the data bus, so the vector comes from 0x12ff
+    machine.load(ADDRESS_SPACE, 0x12ff, &[0x00, 0x40]);
+
+    for _ in 0..5 {
+        processor.step();
+    }
+    processor
+        .interrupt_connection(Z80Interrupt::Maskable)
+        .set_interrupt(true);
+
+    // The instruction after ei always gets to run first
+    processor.step();
+    assert_eq!(processor.read_register(7), 0x000b);
+    processor.step();
+    assert_eq!(processor.read_register(7), 0x4000);
+    assert_eq!(machine.peek(ADDRESS_SPACE, 0x7ffe, 2), [0x0b, 0x00]);
+}
+
+#[test]
+fn z80_non_maskable_interrupt() {
+    let (machine, processor) = z80_machine();
+    // ld sp, 0x8000 with interrupts disabled, then retn in the handler
+    machine.load(ADDRESS_SPACE, 0x0000, &[0x31, 0x00, 0x80, 0x00]);
+    machine.load(ADDRESS_SPACE, 0x0066, &[0xed, 0x45]);
+
+    processor.step();
+    processor
+        .interrupt_connection(Z80Interrupt::NonMaskable)
+        .set_interrupt(true);
+    processor.step();
+    assert_eq!(processor.read_register(7), 0x0066);
+
+    processor.step();
+    assert_eq!(processor.read_register(7), 0x0003);
+}
+
+#[test]
+fn z80_refresh_register() {
+    let (machine, processor) = z80_machine();
+    // ld a, 0x80, ld r, a, nop, nop, ld ix, 0x0000, ld a, r
+    machine.load(
+        ADDRESS_SPACE,
+        0x0000,
+        &[
+            0x3e, 0x80, 0xed, 0x4f, 0x00, 0x00, 0xdd, 0x21, 0x00, 0x00, 0xed, 0x5f,
+        ],
+    );
+
+    for _ in 0..6 {
+        processor.step();
+    }
+
+    // Prefixed instructions count twice, and bit 7 is left where it was set
+    assert_eq!(processor.read_register(0) >> 8, 0x86);
+    assert_eq!(processor.read_register(12) & 0xff, 0x86);
+}
+
+#[test]
+fn z80_arithmetic_flags() {
+    let (_machine, processor) = z80_machine();
+    let mut state = processor.state.lock().unwrap();
+
+    // 45 + 38 in binary coded decimal
+    state.registers.accumulator = 0x45;
+    processor.interpret_instruction(
+        &mut state,
+        Z80InstructionSet::Alu {
+            operation: AluOperation::Add,
+            operand: Operand8::Immediate(0x38),
+        },
+    );
+    assert_eq!(state.registers.accumulator, 0x7d);
+    processor.interpret_instruction(&mut state, Z80InstructionSet::Daa);
+    assert_eq!(state.registers.accumulator, 0x83);
+    assert!(!state.registers.flags.contains(FlagRegister::Carry));
+
+    // 0x3e - 0x3f borrows out of both nibbles, and the undocumented bits come from the operand
+    state.registers.accumulator = 0x3e;
+    processor.interpret_instruction(
+        &mut state,
+        Z80InstructionSet::Alu {
+            operation: AluOperation::Cp,
+            operand: Operand8::Immediate(0x3f),
+        },
+    );
+    assert_eq!(state.registers.accumulator, 0x3e);
+    assert_eq!(
+        state.registers.flags,
+        FlagRegister::Sign
+            | FlagRegister::Y
+            | FlagRegister::HalfCarry
+            | FlagRegister::X
+            | FlagRegister::Subtract
+            | FlagRegister::Carry
+    );
+
+    // Signed overflow into bit 15
+    state.registers.flags = BitFlags::empty();
+    state.registers.general = [0x00, 0x01, 0x00, 0x00, 0x7f, 0xff];
+    let cycles =
+        processor.interpret_instruction(&mut state, Z80InstructionSet::Adc16(RegisterPair::Bc));
+    assert_eq!(cycles, 15);
+    assert_eq!(state.registers.general[4..], [0x80, 0x00]);
+    assert_eq!(
+        state.registers.flags,
+        FlagRegister::Sign | FlagRegister::HalfCarry | FlagRegister::ParityOverflow
+    );
+}
+
+#[test]
+fn z80_block_copy() {
+    let (machine, processor) = z80_machine();
+    // ld hl, 0x1000, ld de, 0x2000, ld bc, 4, ldir, ld a, 0x42, out (0x10), a
+    machine.load(
+        ADDRESS_SPACE,
+        0x0000,
+        &[
+            0x21, 0x00, 0x10, 0x11, 0x00, 0x20, 0x01, 0x04, 0x00, 0xed, 0xb0, 0x3e, 0x42, 0xd3,
+            0x10,
+        ],
+    );
+    machine.load(ADDRESS_SPACE, 0x1000, &[0xde, 0xad, 0xbe, 0xef]);
+
+    for _ in 0..3 {
+        processor.step();
+    }
+
+    // ldir runs itself once per byte
+    for _ in 0..3 {
+        processor.step();
+        assert_eq!(processor.read_register(7), 0x0009);
+    }
+    processor.step();
+    assert_eq!(processor.read_register(7), 0x000b);
+    assert_eq!(
+        machine.peek(ADDRESS_SPACE, 0x2000, 4),
+        [0xde, 0xad, 0xbe, 0xef]
+    );
+    assert_eq!(processor.read_register(1), 0x0000);
+    assert_eq!(processor.read_register(2), 0x2004);
+    assert_eq!(processor.read_register(3), 0x1004);
+
+    // a goes out on the top half of the port address
+    processor.step();
+    processor.step();
+    assert_eq!(machine.peek(IO_ADDRESS_SPACE, 0x4210, 1), [0x42]);
+}
@@ -0,0 +1,4 @@
+pub mod output;
+pub mod tilemap;
+pub mod tms9918;
+pub mod vic2;
@@ -0,0 +1,202 @@
+use crate::runtime::rendering_backend::{
+    DisplayComponentFramebuffer, DisplayComponentInitializationData,
+};
+use nalgebra::{DMatrix, Vector2};
+use palette::Srgba;
+use std::sync::{Arc, Mutex, OnceLock};
+
+#[derive(Debug)]
+enum OutputState {
+    Software(Arc<Mutex<DMatrix<Srgba<u8>>>>),
+    #[cfg(graphics_vulkan)]
+    Vulkan(vulkan::VulkanOutput),
+}
+
+/// Where a video chip hands its finished frames, in whatever form the renderer wants them
+///
+/// Chips that draw a line at a time keep their own buffer and present it once a frame. A frame of a different size
+/// than the last resizes the framebuffer, so chips with modes of different resolutions just present what they drew
+#[derive(Debug, Default)]
+pub struct FrameOutput {
+    state: OnceLock<OutputState>,
+}
+
+impl FrameOutput {
+    /// Sets up the framebuffer at its starting size, filled with one color
+    pub fn set_display_data(
+        &self,
+        initialization_data: DisplayComponentInitializationData,
+        resolution: Vector2<usize>,
+        fill: Srgba<u8>,
+    ) {
+        let _ = self.state.set(match initialization_data {
+            DisplayComponentInitializationData::Software => OutputState::Software(Arc::new(
+                Mutex::new(DMatrix::from_element(resolution.x, resolution.y, fill)),
+            )),
+            #[cfg(graphics_vulkan)]
+            DisplayComponentInitializationData::Vulkan(initialization_data) => OutputState::Vulkan(
+                vulkan::VulkanOutput::new(initialization_data, resolution, fill),
+            ),
+        });
+    }
+
+    /// Shows a frame, resizing the framebuffer if it changed size
+    pub fn present(&self, frame: &DMatrix<Srgba<u8>>) {
+        match self.state.get() {
+            Some(OutputState::Software(framebuffer)) => {
+                framebuffer.lock().unwrap().clone_from(frame);
+            }
+            #[cfg(graphics_vulkan)]
+            Some(OutputState::Vulkan(vulkan_output)) => vulkan_output.present(frame),
+            // Nothing is showing the chip, like in tests
+            None => {}
+        }
+    }
+
+    pub fn get_framebuffer(&self) -> DisplayComponentFramebuffer {
+        match self.state.get() {
+            Some(OutputState::Software(framebuffer)) => {
+                DisplayComponentFramebuffer::Software(framebuffer.clone())
+            }
+            #[cfg(graphics_vulkan)]
+            Some(OutputState::Vulkan(vulkan_output)) => vulkan_output.get_framebuffer(),
+            None => panic!("Internal state not initialized"),
+        }
+    }
+}
+
+#[cfg(graphics_vulkan)]
+mod vulkan {
+    use crate::runtime::{
+        platform::desktop::renderer::vulkan::VulkanDisplayComponentInitializationData,
+        rendering_backend::DisplayComponentFramebuffer,
+    };
+    use nalgebra::{DMatrix, Vector2};
+    use palette::Srgba;
+    use std::sync::{Arc, Mutex};
+    use vulkano::{
+        buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
+        command_buffer::{
+            allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder,
+            CommandBufferUsage, CopyBufferToImageInfo, PrimaryCommandBufferAbstract,
+        },
+        device::Queue,
+        format::Format,
+        image::{Image, ImageCreateInfo, ImageType, ImageUsage},
+        memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+        sync::GpuFuture,
+    };
+
+    #[derive(Debug)]
+    struct VulkanScreen {
+        resolution: Vector2<usize>,
+        staging_buffer: Subbuffer<[Srgba<u8>]>,
+        render_image: Arc<Image>,
+    }
+
+    #[derive(Debug)]
+    pub struct VulkanOutput {
+        memory_allocator: Arc<StandardMemoryAllocator>,
+        queue: Arc<Queue>,
+        command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+        screen: Mutex<VulkanScreen>,
+    }
+
+    impl VulkanOutput {
+        pub fn new(
+            initialization_data: VulkanDisplayComponentInitializationData,
+            resolution: Vector2<usize>,
+            fill: Srgba<u8>,
+        ) -> Self {
+            let screen = allocate_screen(&initialization_data.memory_allocator, resolution, fill);
+
+            Self {
+                memory_allocator: initialization_data.memory_allocator,
+                queue: initialization_data.queue,
+                command_buffer_allocator: initialization_data.command_buffer_allocator,
+                screen: Mutex::new(screen),
+            }
+        }
+
+        pub fn present(&self, frame: &DMatrix<Srgba<u8>>) {
+            let mut screen = self.screen.lock().unwrap();
+            let resolution = Vector2::new(frame.nrows(), frame.ncols());
+
+            if screen.resolution != resolution {
+                *screen = allocate_screen(&self.memory_allocator, resolution, Srgba::default());
+            }
+
+            screen
+                .staging_buffer
+                .write()
+                .unwrap()
+                .copy_from_slice(frame.as_slice());
+
+            let mut command_buffer = AutoCommandBufferBuilder::primary(
+                &self.command_buffer_allocator,
+                self.queue.queue_family_index(),
+                CommandBufferUsage::OneTimeSubmit,
+            )
+            .unwrap();
+
+            command_buffer
+                .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+                    screen.staging_buffer.clone(),
+                    screen.render_image.clone(),
+                ))
+                .unwrap();
+            command_buffer
+                .build()
+                .unwrap()
+                .execute(self.queue.clone())
+                .unwrap()
+                .then_signal_fence_and_flush()
+                .unwrap()
+                .wait(None)
+                .unwrap();
+        }
+
+        pub fn get_framebuffer(&self) -> DisplayComponentFramebuffer {
+            DisplayComponentFramebuffer::Vulkan(self.screen.lock().unwrap().render_image.clone())
+        }
+    }
+
+    fn allocate_screen(
+        memory_allocator: &Arc<StandardMemoryAllocator>,
+        resolution: Vector2<usize>,
+        fill: Srgba<u8>,
+    ) -> VulkanScreen {
+        let staging_buffer = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            vec![fill; resolution.x * resolution.y],
+        )
+        .unwrap();
+
+        let render_image = Image::new(
+            memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format: Format::R8G8B8A8_SRGB,
+                extent: [resolution.x as u32, resolution.y as u32, 1],
+                usage: ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+        .unwrap();
+
+        VulkanScreen {
+            resolution,
+            staging_buffer,
+            render_image,
+        }
+    }
+}
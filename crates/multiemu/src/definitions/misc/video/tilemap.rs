@@ -0,0 +1,430 @@
+use std::ops::Range;
+
+/// How the bitplanes of a tile are arranged in video memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileFormat {
+    /// Every plane of a row before the next row, like the Game Boy and Master System
+    RowInterleaved,
+    /// Every row of a plane before the next plane, like the NES and the TMS9918
+    Planar,
+    /// Pixels next to each other in a byte, most significant first, like the Mega Drive
+    Packed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileLayout {
+    pub width: usize,
+    pub height: usize,
+    pub bits_per_pixel: u8,
+    pub format: TileFormat,
+}
+
+impl TileLayout {
+    /// Bytes of video memory each tile takes
+    ///
+    /// Planes are stored a whole byte per row, so tiles narrower than 8 pixels like TMS9918 text still take a byte
+    pub fn tile_size(&self) -> usize {
+        match self.format {
+            TileFormat::RowInterleaved | TileFormat::Planar => {
+                self.width.div_ceil(8) * self.height * self.bits_per_pixel as usize
+            }
+            TileFormat::Packed => self.width * self.height * self.bits_per_pixel as usize / 8,
+        }
+    }
+
+    /// Color of one pixel of a tile, 0 being transparent everywhere but the bottom layer
+    ///
+    /// Tiles past the end of the data read as transparent
+    pub fn pixel(&self, tile_data: &[u8], tile: usize, x: usize, y: usize) -> u8 {
+        let base = tile * self.tile_size();
+        let bits_per_pixel = self.bits_per_pixel as usize;
+        let bytes_per_plane_row = self.width.div_ceil(8);
+        let byte = |offset: usize| tile_data.get(base + offset).copied().unwrap_or(0);
+
+        match self.format {
+            TileFormat::RowInterleaved | TileFormat::Planar => (0..bits_per_pixel)
+                .map(|plane| {
+                    let offset = match self.format {
+                        TileFormat::RowInterleaved => {
+                            (y * bits_per_pixel + plane) * bytes_per_plane_row
+                        }
+                        _ => (plane * self.height + y) * bytes_per_plane_row,
+                    } + x / 8;
+
+                    ((byte(offset) >> (7 - x % 8)) & 1) << plane
+                })
+                .sum(),
+            TileFormat::Packed => {
+                let bit = (y * self.width + x) * bits_per_pixel;
+                let shift = 8 - bits_per_pixel - bit % 8;
+
+                (byte(bit / 8) >> shift) & ((1 << bits_per_pixel) - 1) as u8
+            }
+        }
+    }
+}
+
+/// One entry of a tilemap, however the chip happens to pack it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TileAttributes {
+    pub tile: u16,
+    pub palette: u8,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Drawn over sprites wherever the tile isn't transparent
+    pub priority: bool,
+}
+
+/// A scrolling background, looked up a tile at a time so chips can decode their own map format
+pub struct TileLayer<'a> {
+    pub tile_data: &'a [u8],
+    pub layout: TileLayout,
+    /// Size of the map in tiles, scrolling wraps around it
+    pub columns: usize,
+    pub rows: usize,
+    pub map: &'a dyn Fn(usize, usize) -> TileAttributes,
+    /// Pixel of the map that lands on the top left of the screen
+    pub scroll_x: i32,
+    pub scroll_y: i32,
+    /// Columns of the scanline the layer covers, like the Game Boy window only covering the right of the screen
+    pub visible: Range<usize>,
+    /// Color 0 is drawn from the tile's palette instead of letting what is below show through, like the Master
+    /// System background. Sprites still go over it
+    pub opaque: bool,
+}
+
+impl TileLayer<'_> {
+    /// Color index and priority of the pixel at a screen position
+    fn pixel(&self, x: usize, y: usize) -> (u16, bool) {
+        let map_width = (self.columns * self.layout.width) as i32;
+        let map_height = (self.rows * self.layout.height) as i32;
+        let map_x = (x as i32 + self.scroll_x).rem_euclid(map_width) as usize;
+        let map_y = (y as i32 + self.scroll_y).rem_euclid(map_height) as usize;
+
+        let attributes = (self.map)(map_x / self.layout.width, map_y / self.layout.height);
+        let (pixel_x, pixel_y) = flip(
+            &attributes,
+            map_x % self.layout.width,
+            map_y % self.layout.height,
+            &self.layout,
+        );
+        let color = self
+            .layout
+            .pixel(self.tile_data, attributes.tile as usize, pixel_x, pixel_y);
+
+        (
+            palette_index(&self.layout, attributes.palette, color),
+            attributes.priority && color != 0,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sprite {
+    /// Position of the top left corner, may be partly off screen
+    pub x: i32,
+    pub y: i32,
+    pub tile: u16,
+    pub palette: u8,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Hidden behind any background pixel that isn't transparent
+    pub behind_background: bool,
+}
+
+/// Which way the tiles of a sprite bigger than one tile are numbered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileOrder {
+    RowMajor,
+    /// Down the left column first, like 16x16 sprites on the TMS9918
+    ColumnMajor,
+}
+
+/// Every sprite on the screen, earlier ones drawn in front of later ones
+pub struct SpriteLayer<'a> {
+    pub tile_data: &'a [u8],
+    pub layout: TileLayout,
+    /// Size of every sprite in pixels, before zooming
+    pub width: usize,
+    pub height: usize,
+    pub tile_order: TileOrder,
+    /// Each sprite pixel is drawn this many pixels wide and tall
+    pub zoom: usize,
+    /// Sprites past this many on one line aren't drawn, and flag an overflow
+    pub per_line_limit: Option<usize>,
+    pub sprites: &'a [Sprite],
+}
+
+impl SpriteLayer<'_> {
+    fn tile(&self, sprite: &Sprite, x: usize, y: usize) -> usize {
+        let columns = self.width / self.layout.width;
+        let rows = self.height / self.layout.height;
+        let (column, row) = (x / self.layout.width, y / self.layout.height);
+
+        sprite.tile as usize
+            + match self.tile_order {
+                TileOrder::RowMajor => row * columns + column,
+                TileOrder::ColumnMajor => column * rows + row,
+            }
+    }
+
+    /// Color index of a sprite pixel, None where it is transparent
+    fn pixel(&self, sprite: &Sprite, x: usize, y: usize) -> Option<u16> {
+        let attributes = TileAttributes {
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+            ..Default::default()
+        };
+        let sprite_layout = TileLayout {
+            width: self.width,
+            height: self.height,
+            ..self.layout
+        };
+        let (x, y) = flip(&attributes, x, y, &sprite_layout);
+        let color = self.layout.pixel(
+            self.tile_data,
+            self.tile(sprite, x, y),
+            x % self.layout.width,
+            y % self.layout.height,
+        );
+
+        (color != 0).then(|| palette_index(&self.layout, sprite.palette, color))
+    }
+}
+
+/// Sprite conditions chips report in their status registers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanlineFlags {
+    pub sprite_overflow: bool,
+    /// Two sprites had pixels that weren't transparent in the same place
+    pub sprite_collision: bool,
+}
+
+fn flip(attributes: &TileAttributes, x: usize, y: usize, layout: &TileLayout) -> (usize, usize) {
+    (
+        if attributes.flip_x {
+            layout.width - 1 - x
+        } else {
+            x
+        },
+        if attributes.flip_y {
+            layout.height - 1 - y
+        } else {
+            y
+        },
+    )
+}
+
+fn palette_index(layout: &TileLayout, palette: u8, color: u8) -> u16 {
+    ((palette as u16) << layout.bits_per_pixel) | color as u16
+}
+
+/// Draws one scanline of palette indices into `line`
+///
+/// Layers are drawn back to front over the backdrop, then sprites go over them unless a background pixel with
+/// priority or a sprite behind the background says otherwise. Chips with raster effects change the layers between
+/// calls
+pub fn render_scanline(
+    y: usize,
+    backdrop: u16,
+    layers: &[TileLayer<'_>],
+    sprites: Option<&SpriteLayer<'_>>,
+    line: &mut [u16],
+) -> ScanlineFlags {
+    let mut flags = ScanlineFlags::default();
+    // Whether each background pixel isn't transparent, and whether it also has priority
+    let mut background = vec![(false, false); line.len()];
+
+    line.fill(backdrop);
+
+    for layer in layers {
+        for (x, (pixel, background)) in line
+            .iter_mut()
+            .zip(background.iter_mut())
+            .enumerate()
+            .take(layer.visible.end)
+            .skip(layer.visible.start)
+        {
+            let (color, priority) = layer.pixel(x, y);
+            let transparent = color & ((1 << layer.layout.bits_per_pixel) - 1) == 0;
+
+            if !transparent || layer.opaque {
+                *pixel = color;
+                *background = (!transparent, priority);
+            }
+        }
+    }
+
+    let Some(sprite_layer) = sprites else {
+        return flags;
+    };
+
+    let sprite_width = sprite_layer.width * sprite_layer.zoom;
+    let sprite_height = (sprite_layer.height * sprite_layer.zoom) as i32;
+    let mut drawn = vec![false; line.len()];
+    let mut on_line = 0;
+
+    for sprite in sprite_layer.sprites {
+        let row = y as i32 - sprite.y;
+        if !(0..sprite_height).contains(&row) {
+            continue;
+        }
+
+        on_line += 1;
+        if sprite_layer
+            .per_line_limit
+            .is_some_and(|limit| on_line > limit)
+        {
+            flags.sprite_overflow = true;
+            break;
+        }
+
+        for column in 0..sprite_width {
+            let x = sprite.x + column as i32;
+            if x < 0 || x as usize >= line.len() {
+                continue;
+            }
+            let x = x as usize;
+
+            let Some(color) = sprite_layer.pixel(
+                sprite,
+                column / sprite_layer.zoom,
+                row as usize / sprite_layer.zoom,
+            ) else {
+                continue;
+            };
+
+            if drawn[x] {
+                flags.sprite_collision = true;
+                continue;
+            }
+            drawn[x] = true;
+
+            let (opaque, priority) = background[x];
+            if !(opaque && (priority || sprite.behind_background)) {
+                line[x] = color;
+            }
+        }
+    }
+
+    flags
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GAME_BOY_TILE: TileLayout = TileLayout {
+        width: 8,
+        height: 8,
+        bits_per_pixel: 2,
+        format: TileFormat::RowInterleaved,
+    };
+
+    #[test]
+    fn tile_formats() {
+        // Left pixel color 3, right pixel color 1, on every row
+        let interleaved = [0b1000_0001, 0b1000_0000].repeat(8);
+        assert_eq!(GAME_BOY_TILE.pixel(&interleaved, 0, 0, 5), 3);
+        assert_eq!(GAME_BOY_TILE.pixel(&interleaved, 0, 7, 5), 1);
+        assert_eq!(GAME_BOY_TILE.pixel(&interleaved, 1, 0, 0), 0);
+
+        let planar = TileLayout {
+            format: TileFormat::Planar,
+            ..GAME_BOY_TILE
+        };
+        let data = [[0b1000_0001; 8], [0b1000_0000; 8]].concat();
+        assert_eq!(planar.pixel(&data, 0, 0, 2), 3);
+        assert_eq!(planar.pixel(&data, 0, 7, 2), 1);
+
+        let packed = TileLayout {
+            bits_per_pixel: 4,
+            format: TileFormat::Packed,
+            ..GAME_BOY_TILE
+        };
+        assert_eq!(packed.pixel(&[0x12, 0x34, 0x56, 0x78], 0, 3, 0), 4);
+    }
+
+    #[test]
+    fn layers_and_sprites() {
+        // Tile 0 empty, tile 1 solid color 1, tile 2 solid color 3
+        let tiles = [vec![0; 16], [0xff, 0x00].repeat(8), [0xff, 0xff].repeat(8)].concat();
+        // Checkerboard of tiles 0 and 1, the top right one with priority
+        let map = |column: usize, row: usize| TileAttributes {
+            tile: ((column + row) % 2) as u16,
+            priority: column == 1 && row == 0,
+            ..Default::default()
+        };
+        let layer = TileLayer {
+            tile_data: &tiles,
+            layout: GAME_BOY_TILE,
+            columns: 2,
+            rows: 2,
+            map: &map,
+            scroll_x: 4,
+            scroll_y: 0,
+            visible: 0..16,
+            opaque: false,
+        };
+        let mut line = [0; 16];
+
+        render_scanline(0, 9, std::slice::from_ref(&layer), None, &mut line);
+        assert_eq!(line[..4], [9; 4]);
+        assert_eq!(line[4..12], [1; 8]);
+        // Wrapped back around to the left of the map
+        assert_eq!(line[12..], [9; 4]);
+
+        let sprites = [
+            // Goes under the priority tile
+            Sprite {
+                x: 2,
+                y: 0,
+                tile: 2,
+                palette: 1,
+                ..Default::default()
+            },
+            Sprite {
+                x: 8,
+                y: 0,
+                tile: 2,
+                ..Default::default()
+            },
+            // One too many for the line
+            Sprite {
+                x: 0,
+                y: 0,
+                tile: 1,
+                ..Default::default()
+            },
+        ];
+        let sprite_layer = SpriteLayer {
+            tile_data: &tiles,
+            layout: GAME_BOY_TILE,
+            width: 8,
+            height: 8,
+            tile_order: TileOrder::RowMajor,
+            zoom: 1,
+            per_line_limit: Some(2),
+            sprites: &sprites,
+        };
+
+        let flags = render_scanline(
+            0,
+            9,
+            std::slice::from_ref(&layer),
+            Some(&sprite_layer),
+            &mut line,
+        );
+        assert_eq!(line[..2], [9; 2]);
+        assert_eq!(line[2..4], [7; 2]);
+        assert_eq!(line[4..12], [1; 8]);
+        assert_eq!(line[12..], [3; 4]);
+        assert_eq!(
+            flags,
+            ScanlineFlags {
+                sprite_overflow: true,
+                sprite_collision: true
+            }
+        );
+    }
+}
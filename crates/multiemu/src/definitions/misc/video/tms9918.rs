@@ -0,0 +1,1064 @@
+use super::{
+    output::FrameOutput,
+    tilemap::{
+        render_scanline, ScanlineFlags, Sprite, SpriteLayer, TileAttributes, TileFormat, TileLayer,
+        TileLayout, TileOrder,
+    },
+};
+use crate::{
+    component::{
+        display::DisplayComponent, memory::MemoryComponent, schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::io::{InterruptConnection, InterruptOutput},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+    runtime::{
+        color::Palette,
+        rendering_backend::{DisplayComponentFramebuffer, DisplayComponentInitializationData},
+    },
+};
+use nalgebra::{DMatrix, Vector2};
+use num::rational::Ratio;
+use palette::Srgba;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+const VRAM_SIZE: usize = 0x4000;
+const CRAM_SIZE: usize = 32;
+const ACTIVE_LINES: u16 = 192;
+const SPRITE_COUNT: usize = 32;
+const MODE4_SPRITE_COUNT: usize = 64;
+/// A sprite Y position of this ends the sprite list early
+const SPRITE_TERMINATOR: u8 = 0xd0;
+
+const FRAME_FLAG: u8 = 0b1000_0000;
+const FIFTH_SPRITE_FLAG: u8 = 0b0100_0000;
+const COLLISION_FLAG: u8 = 0b0010_0000;
+
+const PATTERN: TileLayout = TileLayout {
+    width: 8,
+    height: 8,
+    bits_per_pixel: 1,
+    format: TileFormat::Planar,
+};
+const TEXT_PATTERN: TileLayout = TileLayout {
+    width: 6,
+    ..PATTERN
+};
+const MULTICOLOR_BLOCK: TileLayout = TileLayout {
+    width: 4,
+    height: 4,
+    ..PATTERN
+};
+/// Master System tiles, 4 bytes a row with a byte for each plane
+const MODE4_PATTERN: TileLayout = TileLayout {
+    width: 8,
+    height: 8,
+    bits_per_pixel: 4,
+    format: TileFormat::RowInterleaved,
+};
+/// Drawn under the patterns so the 0 bits show the background color instead of the backdrop
+const SOLID_TILE: [u8; 8] = [0xff; 8];
+
+/// The TMS9918A colors used when the user has not provided a .pal file, 0 is transparent
+#[rustfmt::skip]
+pub const TMS9918_DEFAULT_PALETTE: [u8; 48] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x21, 0xc8, 0x42, 0x5e, 0xdc, 0x78,
+    0x54, 0x55, 0xed, 0x7d, 0x76, 0xfc, 0xd4, 0x52, 0x4d, 0x42, 0xeb, 0xf5,
+    0xfc, 0x55, 0x54, 0xff, 0x79, 0x78, 0xd4, 0xc1, 0x54, 0xe6, 0xce, 0x80,
+    0x21, 0xb0, 0x3b, 0xc9, 0x5b, 0xba, 0xcc, 0xcc, 0xcc, 0xff, 0xff, 0xff,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tms9918Region {
+    /// TMS9918A, 262 lines at 60 Hz
+    Ntsc,
+    /// TMS9929A, 313 lines at 50 Hz
+    Pal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tms9918Variant {
+    #[default]
+    Tms9918a,
+    /// Sega 315-5124 of the Master System, adding mode 4, color RAM, scrolling and a line interrupt
+    Sms,
+}
+
+impl Tms9918Region {
+    fn lines(self) -> u16 {
+        match self {
+            Tms9918Region::Ntsc => 262,
+            Tms9918Region::Pal => 313,
+        }
+    }
+
+    /// Lines a second, 342 pixels a line at half the master clock
+    fn line_rate(self) -> Ratio<u64> {
+        match self {
+            Tms9918Region::Ntsc => Ratio::new(10_738_635, 684),
+            Tms9918Region::Pal => Ratio::new(10_687_500, 684),
+        }
+    }
+
+    pub fn frame_rate(self) -> Ratio<u64> {
+        self.line_rate() / self.lines() as u64
+    }
+
+    /// What the Master System VDP reports for a line, which jumps back partway through blanking to fit in 8 bits
+    fn v_counter(self, line: u16) -> u8 {
+        let (last_before_jump, jump) = match self {
+            Tms9918Region::Ntsc => (0xda, 6),
+            Tms9918Region::Pal => (0xf2, 57),
+        };
+
+        if line > last_before_jump {
+            (line - jump) as u8
+        } else {
+            line as u8
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tms9918Mode {
+    /// Mode 0, 32x24 patterns with a color pair for every 8 of them
+    Graphics1,
+    /// Mode 1, 40x24 patterns 6 pixels wide in the two colors of register 7, without sprites
+    Text,
+    /// Mode 2, 768 patterns with a color pair for every row of each
+    Graphics2,
+    /// Mode 3, 64x48 blocks of 4x4 pixels each with its own color
+    Multicolor,
+    /// Master System mode, 32x28 tiles of 16 colors out of color RAM with 8 sprites a line
+    Mode4,
+}
+
+#[derive(Debug)]
+pub struct Tms9918Config {
+    pub variant: Tms9918Variant,
+    pub region: Tms9918Region,
+    pub palette: Palette,
+    pub assigned_address_space: AddressSpaceId,
+    /// Even addresses are the data port and odd ones the control port, as the MODE pin is wired to A0 on the
+    /// ColecoVision and MSX
+    pub assigned_range: Range<usize>,
+    pub irq: Option<Arc<dyn InterruptConnection>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Tms9918State {
+    vram: Vec<u8>,
+    /// Only the Master System VDP has more than 8
+    registers: [u8; 16],
+    /// Master System palette, 2 bits for each of red, green and blue
+    cram: [u8; CRAM_SIZE],
+    status: u8,
+    /// VRAM address the data port accesses next
+    address: u16,
+    /// The data port writes color RAM instead of VRAM
+    cram_selected: bool,
+    /// Reads return the byte fetched by the access before
+    read_buffer: u8,
+    /// First byte of a control port write, waiting for the second
+    latch: Option<u8>,
+    line: u16,
+    /// Lines left until the line interrupt, reloaded from register 10
+    line_counter: u8,
+    line_irq: bool,
+    irq: bool,
+}
+
+impl Default for Tms9918State {
+    fn default() -> Self {
+        Self {
+            vram: vec![0; VRAM_SIZE],
+            registers: [0; 16],
+            cram: [0; CRAM_SIZE],
+            status: 0,
+            address: 0,
+            cram_selected: false,
+            read_buffer: 0,
+            latch: None,
+            line: 0,
+            line_counter: 0,
+            line_irq: false,
+            irq: false,
+        }
+    }
+}
+
+impl Tms9918State {
+    fn mode(&self, variant: Tms9918Variant) -> Tms9918Mode {
+        if variant == Tms9918Variant::Sms && self.registers[0] & 0b0000_0100 != 0 {
+            Tms9918Mode::Mode4
+        } else if self.registers[1] & 0b0001_0000 != 0 {
+            Tms9918Mode::Text
+        } else if self.registers[1] & 0b0000_1000 != 0 {
+            Tms9918Mode::Multicolor
+        } else if self.registers[0] & 0b0000_0010 != 0 {
+            Tms9918Mode::Graphics2
+        } else {
+            Tms9918Mode::Graphics1
+        }
+    }
+
+    fn advance_address(&mut self) {
+        self.address = (self.address + 1) % VRAM_SIZE as u16;
+    }
+
+    fn large_sprites(&self) -> bool {
+        self.registers[1] & 0b0000_0010 != 0
+    }
+
+    fn sprite_zoom(&self) -> usize {
+        if self.registers[1] & 0b0000_0001 != 0 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// The sprite attribute table up to the terminator
+    fn sprites(&self) -> Vec<Sprite> {
+        let attribute_table = (self.registers[5] as usize & 0x7f) << 7;
+        let tile_mask = if self.large_sprites() { 0xfc } else { 0xff };
+
+        self.vram[attribute_table..attribute_table + SPRITE_COUNT * 4]
+            .chunks(4)
+            .take_while(|attributes| attributes[0] != SPRITE_TERMINATOR)
+            .map(|attributes| {
+                // Positions past the bottom of the screen wrap around to just above it
+                let y = attributes[0] as i32;
+                let y = if y > 0xe0 { y - 0x100 } else { y };
+                let early_clock = attributes[3] & 0x80 != 0;
+                let color = attributes[3] & 0x0f;
+
+                Sprite {
+                    x: attributes[1] as i32 - if early_clock { 32 } else { 0 },
+                    // Sprites show up a line below their position
+                    y: y + 1,
+                    tile: (attributes[2] & tile_mask) as u16,
+                    palette: color,
+                    // Transparent sprites still collide and count towards the limit, they just hide under the
+                    // background layer that covers everything
+                    behind_background: color == 0,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// The mode 4 sprite table, Y positions first and then pairs of X and tile
+    fn mode4_sprites(&self) -> Vec<Sprite> {
+        let attribute_table = (self.registers[5] as usize & 0x7e) << 7;
+        let tile_base = if self.registers[6] & 0b0000_0100 != 0 {
+            0x100
+        } else {
+            0
+        };
+        let tile_mask = if self.large_sprites() { 0xfe } else { 0xff };
+        let early_clock = self.registers[0] & 0b0000_1000 != 0;
+
+        (0..MODE4_SPRITE_COUNT)
+            .map(|number| self.vram[attribute_table + number])
+            .take_while(|y| *y != SPRITE_TERMINATOR)
+            .enumerate()
+            .map(|(number, y)| {
+                let x = self.vram[attribute_table + 0x80 + number * 2] as i32;
+                let tile = self.vram[attribute_table + 0x81 + number * 2] & tile_mask;
+                let y = y as i32;
+                let y = if y > 0xe0 { y - 0x100 } else { y };
+
+                Sprite {
+                    x: x - if early_clock { 8 } else { 0 },
+                    y: y + 1,
+                    tile: tile_base | tile as u16,
+                    // Sprites always take the second half of color RAM
+                    palette: 1,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+}
+
+/// Texas Instruments TMS9918A video display processor, the video of the ColecoVision, MSX1, SG-1000 and TI-99/4A
+///
+/// Renders a line at a time with 16KB of its own VRAM behind an auto incrementing port, raising its interrupt at
+/// the start of vertical blanking. With [Tms9918Variant::Sms] it is the Master System VDP instead, which keeps the
+/// old modes and adds mode 4
+#[derive(Debug)]
+pub struct Tms9918 {
+    config: Tms9918Config,
+    state: Mutex<Tms9918State>,
+    /// Frame being drawn, handed to the output once it is done
+    frame: Mutex<DMatrix<Srgba<u8>>>,
+    output: FrameOutput,
+    irq: InterruptOutput,
+}
+
+impl Tms9918 {
+    pub fn mode(&self) -> Tms9918Mode {
+        self.state.lock().unwrap().mode(self.config.variant)
+    }
+
+    /// Whether the INT output is pulled
+    pub fn irq(&self) -> bool {
+        self.state.lock().unwrap().irq
+    }
+
+    /// Line the beam is on, as the Master System reports it
+    pub fn v_counter(&self) -> u8 {
+        self.config
+            .region
+            .v_counter(self.state.lock().unwrap().line)
+    }
+
+    /// Position of the beam along the line
+    ///
+    /// The chip is only stepped a line at a time, so this is always the start of one
+    pub fn h_counter(&self) -> u8 {
+        0
+    }
+
+    fn update_irq(&self, state: &mut Tms9918State) {
+        let frame = state.status & FRAME_FLAG != 0 && state.registers[1] & 0b0010_0000 != 0;
+        let line = state.line_irq && state.registers[0] & 0b0001_0000 != 0;
+        self.irq.update(&mut state.irq, frame || line);
+    }
+
+    fn read(&self, address: usize, side_effects: bool) -> u8 {
+        let mut state = self.state.lock().unwrap();
+
+        if address & 1 == 0 {
+            let value = state.read_buffer;
+
+            if side_effects {
+                state.latch = None;
+                state.read_buffer = state.vram[state.address as usize];
+                state.advance_address();
+            }
+
+            value
+        } else {
+            let status = state.status;
+
+            if side_effects {
+                state.latch = None;
+                state.status &= !(FRAME_FLAG | FIFTH_SPRITE_FLAG | COLLISION_FLAG);
+                state.line_irq = false;
+                self.update_irq(&mut state);
+            }
+
+            status
+        }
+    }
+
+    fn write(&self, state: &mut Tms9918State, address: usize, value: u8) {
+        if address & 1 == 0 {
+            let vram_address = state.address as usize;
+
+            state.latch = None;
+            if state.cram_selected {
+                state.cram[vram_address % CRAM_SIZE] = value;
+            } else {
+                state.vram[vram_address] = value;
+            }
+            state.read_buffer = value;
+            state.advance_address();
+            return;
+        }
+
+        let Some(low) = state.latch.take() else {
+            state.latch = Some(value);
+            return;
+        };
+
+        let sms = self.config.variant == Tms9918Variant::Sms;
+        let address = u16::from_le_bytes([low, value & 0x3f]);
+        // The Master System VDP loads the address whatever the command, and its last command picks color RAM
+        if sms {
+            state.address = address;
+            state.cram_selected = value >> 6 == 0b11;
+        }
+
+        match value >> 6 {
+            0b11 if sms => {}
+            0b10 | 0b11 => {
+                let register_mask = if sms { 0x0f } else { 0x07 };
+                state.registers[value as usize & register_mask] = low;
+                self.update_irq(state);
+            }
+            command => {
+                state.address = address;
+
+                // Setting up a read fetches the first byte right away
+                if command == 0b00 {
+                    state.read_buffer = state.vram[state.address as usize];
+                    state.advance_address();
+                }
+            }
+        }
+    }
+
+    /// The frame being drawn, resized if a mode change changed the width
+    fn frame(&self, width: usize) -> MutexGuard<'_, DMatrix<Srgba<u8>>> {
+        let mut frame = self.frame.lock().unwrap();
+
+        // The rest of the frame is drawn at the new width
+        if frame.nrows() != width {
+            *frame = DMatrix::from_element(width, ACTIVE_LINES as usize, Srgba::default());
+        }
+
+        frame
+    }
+
+    fn render_line(&self, state: &mut Tms9918State, y: usize) {
+        let mode = state.mode(self.config.variant);
+        if mode == Tms9918Mode::Mode4 {
+            self.render_mode4_line(state, y);
+            return;
+        }
+
+        let width = if mode == Tms9918Mode::Text { 240 } else { 256 };
+        let mut line = vec![0; width];
+
+        // Blanked screens show only the backdrop, and sprites aren't even looked at
+        if state.registers[1] & 0b0100_0000 != 0 {
+            let sprites = if mode == Tms9918Mode::Text {
+                Vec::new()
+            } else {
+                state.sprites()
+            };
+            let flags = self.compose_line(state, mode, &sprites, y, &mut line);
+
+            if flags.sprite_collision {
+                state.status |= COLLISION_FLAG;
+            }
+
+            if state.status & FIFTH_SPRITE_FLAG == 0 {
+                // The fifth sprite on the line if there was one, otherwise the last sprite looked at
+                let height =
+                    (if state.large_sprites() { 16 } else { 8 }) * state.sprite_zoom() as i32;
+                let number = if flags.sprite_overflow {
+                    state.status |= FIFTH_SPRITE_FLAG;
+
+                    sprites
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, sprite)| (0..height).contains(&(y as i32 - sprite.y)))
+                        .nth(4)
+                        .map_or(0, |(number, _)| number)
+                } else {
+                    sprites.len().min(SPRITE_COUNT - 1)
+                };
+                state.status = (state.status & 0xe0) | number as u8;
+            }
+        }
+
+        let mut frame = self.frame(width);
+        let backdrop = state.registers[7] & 0x0f;
+        for (x, index) in line.into_iter().enumerate() {
+            // Layers are all 1 bit, so the palette of each layer is the color
+            let color = match (index >> 1) as u8 {
+                0 => backdrop,
+                color => color,
+            };
+
+            frame[(x, y)] = self.config.palette.get(color as usize);
+        }
+    }
+
+    fn render_mode4_line(&self, state: &mut Tms9918State, y: usize) {
+        // The backdrop comes out of the sprite half of color RAM
+        let backdrop = 0x10 | (state.registers[7] & 0x0f) as u16;
+        let mut line = vec![backdrop; 256];
+
+        if state.registers[1] & 0b0100_0000 != 0 {
+            let flags = self.compose_mode4_line(state, y, &mut line);
+
+            if flags.sprite_overflow {
+                state.status |= FIFTH_SPRITE_FLAG;
+            }
+            if flags.sprite_collision {
+                state.status |= COLLISION_FLAG;
+            }
+
+            // Hides the column scrolling brings in
+            if state.registers[0] & 0b0010_0000 != 0 {
+                line[..8].fill(backdrop);
+            }
+        }
+
+        let mut frame = self.frame(256);
+        for (x, index) in line.into_iter().enumerate() {
+            frame[(x, y)] = cram_color(state.cram[index as usize % CRAM_SIZE]);
+        }
+    }
+
+    /// Draws the tiles and sprites of a mode 4 line as indices into color RAM
+    fn compose_mode4_line(
+        &self,
+        state: &Tms9918State,
+        y: usize,
+        line: &mut [u16],
+    ) -> ScanlineFlags {
+        let vram = &state.vram;
+        let registers = &state.registers;
+        let name_table = (registers[2] as usize & 0x0e) << 10;
+        let map = |column: usize, row: usize| {
+            let entry = name_table + (row * 32 + column) * 2;
+            let entry = u16::from_le_bytes([vram[entry], vram[entry + 1]]);
+
+            TileAttributes {
+                tile: entry & 0x01ff,
+                flip_x: entry & 0x0200 != 0,
+                flip_y: entry & 0x0400 != 0,
+                palette: (entry >> 11) as u8 & 1,
+                priority: entry & 0x1000 != 0,
+            }
+        };
+
+        // The top 2 rows can be kept from scrolling sideways and the right 8 columns from scrolling down, for
+        // status bars
+        let scroll_x = if registers[0] & 0b0100_0000 != 0 && y < 16 {
+            0
+        } else {
+            -(registers[8] as i32)
+        };
+        let locked_columns = if registers[0] & 0b1000_0000 != 0 {
+            192
+        } else {
+            256
+        };
+        let layer = |scroll_y, visible| TileLayer {
+            tile_data: vram,
+            layout: MODE4_PATTERN,
+            columns: 32,
+            rows: 28,
+            map: &map,
+            scroll_x,
+            scroll_y,
+            visible,
+            opaque: true,
+        };
+
+        let sprites = state.mode4_sprites();
+        let sprite_layer = SpriteLayer {
+            tile_data: vram,
+            layout: MODE4_PATTERN,
+            width: 8,
+            height: if state.large_sprites() { 16 } else { 8 },
+            tile_order: TileOrder::RowMajor,
+            zoom: state.sprite_zoom(),
+            per_line_limit: Some(8),
+            sprites: &sprites,
+        };
+
+        render_scanline(
+            y,
+            0,
+            &[
+                layer(registers[9] as i32, 0..locked_columns),
+                layer(0, locked_columns..256),
+            ],
+            Some(&sprite_layer),
+            line,
+        )
+    }
+
+    /// Draws the patterns and sprites of a line as colors shifted up a bit, 0 being the backdrop
+    fn compose_line(
+        &self,
+        state: &Tms9918State,
+        mode: Tms9918Mode,
+        sprites: &[Sprite],
+        y: usize,
+        line: &mut [u16],
+    ) -> ScanlineFlags {
+        let vram = &state.vram;
+        let registers = &state.registers;
+        let name_table = (registers[2] as usize & 0x0f) << 10;
+        let columns = if mode == Tms9918Mode::Text { 40 } else { 32 };
+        let name = |column: usize, row: usize| vram[name_table + row * columns + column] as usize;
+
+        let sprite_pattern_table = (registers[6] as usize & 0x07) << 11;
+        let sprite_size = if state.large_sprites() { 16 } else { 8 };
+        let sprite_layer = SpriteLayer {
+            tile_data: &vram[sprite_pattern_table..],
+            layout: PATTERN,
+            width: sprite_size,
+            height: sprite_size,
+            tile_order: TileOrder::ColumnMajor,
+            zoom: state.sprite_zoom(),
+            per_line_limit: Some(4),
+            sprites,
+        };
+        let sprite_layer = (mode != Tms9918Mode::Text).then_some(&sprite_layer);
+
+        let width = line.len();
+
+        match mode {
+            Tms9918Mode::Graphics1 => {
+                let color_table = (registers[3] as usize) << 6;
+                let pattern_table = (registers[4] as usize & 0x07) << 11;
+                let color = |column, row| vram[color_table + name(column, row) / 8];
+                let background = |column, row| TileAttributes {
+                    palette: color(column, row) & 0x0f,
+                    ..Default::default()
+                };
+                let foreground = |column, row| TileAttributes {
+                    tile: name(column, row) as u16,
+                    palette: color(column, row) >> 4,
+                    ..Default::default()
+                };
+
+                render_scanline(
+                    y,
+                    0,
+                    &[
+                        screen_layer(&SOLID_TILE, PATTERN, 32, 24, width, &background),
+                        screen_layer(&vram[pattern_table..], PATTERN, 32, 24, width, &foreground),
+                    ],
+                    sprite_layer,
+                    line,
+                )
+            }
+            Tms9918Mode::Graphics2 => {
+                // The low bits of registers 3 and 4 mask which thirds of the tables the screen thirds use
+                let color_table = (registers[3] as usize & 0x80) << 6;
+                let pattern_table = (registers[4] as usize & 0x04) << 11;
+                let color_mask = ((registers[3] as usize & 0x7f) << 3) | 0x07;
+                let pattern_mask = ((registers[4] as usize & 0x03) << 8) | 0xff;
+                let character = |column, row: usize| ((row / 8) << 8) | name(column, row);
+                // Every row of a pattern has its own colors, and only this line is being drawn
+                let color = |column, row| {
+                    vram[color_table + (character(column, row) & color_mask) * 8 + y % 8]
+                };
+                let background = |column, row| TileAttributes {
+                    palette: color(column, row) & 0x0f,
+                    ..Default::default()
+                };
+                let foreground = |column, row| TileAttributes {
+                    tile: (character(column, row) & pattern_mask) as u16,
+                    palette: color(column, row) >> 4,
+                    ..Default::default()
+                };
+
+                render_scanline(
+                    y,
+                    0,
+                    &[
+                        screen_layer(&SOLID_TILE, PATTERN, 32, 24, width, &background),
+                        screen_layer(&vram[pattern_table..], PATTERN, 32, 24, width, &foreground),
+                    ],
+                    sprite_layer,
+                    line,
+                )
+            }
+            Tms9918Mode::Multicolor => {
+                let pattern_table = (registers[4] as usize & 0x07) << 11;
+                // Each name picks 2 of the 8 bytes of its pattern depending on the row, each byte being 2 blocks
+                let block = |column: usize, row: usize| {
+                    let colors = vram[pattern_table
+                        + name(column / 2, row / 2) * 8
+                        + (row / 2 % 4) * 2
+                        + row % 2];
+
+                    TileAttributes {
+                        palette: if column % 2 == 0 {
+                            colors >> 4
+                        } else {
+                            colors & 0x0f
+                        },
+                        ..Default::default()
+                    }
+                };
+
+                render_scanline(
+                    y,
+                    0,
+                    &[screen_layer(
+                        &SOLID_TILE,
+                        MULTICOLOR_BLOCK,
+                        64,
+                        48,
+                        width,
+                        &block,
+                    )],
+                    sprite_layer,
+                    line,
+                )
+            }
+            Tms9918Mode::Text => {
+                let pattern_table = (registers[4] as usize & 0x07) << 11;
+                let background = |_: usize, _: usize| TileAttributes {
+                    palette: registers[7] & 0x0f,
+                    ..Default::default()
+                };
+                let foreground = |column, row| TileAttributes {
+                    tile: name(column, row) as u16,
+                    palette: registers[7] >> 4,
+                    ..Default::default()
+                };
+
+                render_scanline(
+                    y,
+                    0,
+                    &[
+                        screen_layer(&SOLID_TILE, TEXT_PATTERN, 40, 24, width, &background),
+                        screen_layer(
+                            &vram[pattern_table..],
+                            TEXT_PATTERN,
+                            40,
+                            24,
+                            width,
+                            &foreground,
+                        ),
+                    ],
+                    None,
+                    line,
+                )
+            }
+        }
+    }
+}
+
+/// Expands a color RAM entry, 2 bits each of blue, green and red from the top
+fn cram_color(value: u8) -> Srgba<u8> {
+    let level = |shift: u8| ((value >> shift) & 0b11) * 0x55;
+
+    Srgba::new(level(0), level(2), level(4), 0xff)
+}
+
+/// A layer covering the screen without scrolling
+fn screen_layer<'a>(
+    tile_data: &'a [u8],
+    layout: TileLayout,
+    columns: usize,
+    rows: usize,
+    width: usize,
+    map: &'a dyn Fn(usize, usize) -> TileAttributes,
+) -> TileLayer<'a> {
+    TileLayer {
+        tile_data,
+        layout,
+        columns,
+        rows,
+        map,
+        scroll_x: 0,
+        scroll_y: 0,
+        visible: 0..width,
+        opaque: false,
+    }
+}
+
+impl Component for Tms9918 {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let vram = std::mem::take(&mut state.vram);
+        let irq = state.irq;
+
+        // Reset leaves VRAM alone
+        *state = Tms9918State {
+            vram,
+            irq,
+            ..Default::default()
+        };
+        self.update_irq(&mut state);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let mut state_guard = self.state.lock().unwrap();
+        let irq = state_guard.irq;
+
+        *state_guard = rmpv::ext::from_value(state).unwrap();
+        // Tell the connection about the loaded level
+        let raised = state_guard.irq;
+        state_guard.irq = irq;
+        self.irq.update(&mut state_guard.irq, raised);
+    }
+}
+
+impl FromConfig for Tms9918 {
+    type Config = Tms9918Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let line_rate = config.region.line_rate();
+        let assigned_address_space = config.assigned_address_space;
+        let assigned_range = config.assigned_range.clone();
+        let irq = InterruptOutput::new(config.irq.take());
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                frame: Mutex::new(DMatrix::from_element(
+                    256,
+                    ACTIVE_LINES as usize,
+                    Srgba::default(),
+                )),
+                output: FrameOutput::default(),
+                irq,
+            })
+            .set_schedulable(line_rate, [], [])
+            .set_memory([(assigned_address_space, assigned_range)])
+            .set_display();
+    }
+}
+
+impl SchedulableComponent for Tms9918 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..period {
+            let line = state.line;
+
+            // The line counter only counts through the picture and the line after, reloading everywhere else
+            if self.config.variant == Tms9918Variant::Sms {
+                if line > ACTIVE_LINES {
+                    state.line_counter = state.registers[10];
+                } else if let Some(line_counter) = state.line_counter.checked_sub(1) {
+                    state.line_counter = line_counter;
+                } else {
+                    state.line_counter = state.registers[10];
+                    state.line_irq = true;
+                    self.update_irq(&mut state);
+                }
+            }
+
+            if line < ACTIVE_LINES {
+                self.render_line(&mut state, line as usize);
+            } else if line == ACTIVE_LINES {
+                state.status |= FRAME_FLAG;
+                self.update_irq(&mut state);
+                self.output.present(&self.frame.lock().unwrap());
+            }
+
+            state.line = (line + 1) % self.config.region.lines();
+        }
+    }
+}
+
+impl DisplayComponent for Tms9918 {
+    fn set_display_data(&self, initialization_data: DisplayComponentInitializationData) {
+        self.output.set_display_data(
+            initialization_data,
+            Vector2::new(256, ACTIVE_LINES as usize),
+            self.config.palette.get(0),
+        );
+    }
+
+    fn get_framebuffer(&self) -> DisplayComponentFramebuffer {
+        self.output.get_framebuffer()
+    }
+
+    fn refresh_rate(&self) -> Option<Ratio<u64>> {
+        Some(self.config.region.frame_rate())
+    }
+}
+
+impl MemoryComponent for Tms9918 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address + offset, true);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter().enumerate() {
+            self.write(&mut state, address + offset, *byte);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address + offset, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn graphics1_and_sprites() {
+        let builder = TestMachineBuilder::new().bus(0, 8);
+        let irq = builder.interrupt_line("irq");
+        let (builder, vdp) = builder.component::<Tms9918>(Tms9918Config {
+            variant: Tms9918Variant::Tms9918a,
+            region: Tms9918Region::Ntsc,
+            palette: Palette::from_bytes(&TMS9918_DEFAULT_PALETTE, 16).unwrap(),
+            assigned_address_space: 0,
+            assigned_range: 0x98..0x9a,
+            irq: Some(Arc::new(irq)),
+        });
+        let machine = builder.build();
+
+        // Display on with interrupts, patterns at 0x0800, colors at 0x2000, sprites at 0x1000 and 0x1800, dark blue
+        // backdrop
+        for (register, value) in [
+            (1, 0xe0),
+            (3, 0x80),
+            (4, 0x01),
+            (5, 0x20),
+            (6, 0x03),
+            (7, 0x04),
+        ] {
+            machine.load(0, 0x99, &[value]);
+            machine.load(0, 0x99, &[0x80 | register]);
+        }
+        let write_vram = |address: u16, data: &[u8]| {
+            let [low, high] = address.to_le_bytes();
+            machine.load(0, 0x99, &[low]);
+            machine.load(0, 0x99, &[high | 0x40]);
+            for byte in data {
+                machine.load(0, 0x98, &[*byte]);
+            }
+        };
+        // Every name is pattern 0, white on transparent
+        write_vram(0x0800, &[0xf0]);
+        write_vram(0x2000, &[0xf0]);
+        // Two overlapping red sprites, then the end of the list
+        write_vram(
+            0x1000,
+            &[0x10, 0x20, 0x00, 0x08, 0x10, 0x20, 0x00, 0x08, 0xd0],
+        );
+        write_vram(0x1800, &[0x80]);
+
+        machine.run_component::<Tms9918>(vdp, 193);
+
+        let component = machine.component::<Tms9918>(vdp);
+        let palette = &component.config.palette;
+        let frame = component.frame.lock().unwrap();
+        assert_eq!(frame[(0, 0)], palette.get(15));
+        assert_eq!(frame[(4, 0)], palette.get(4));
+        assert_eq!(frame[(0, 1)], palette.get(4));
+        assert_eq!(frame[(32, 17)], palette.get(8));
+        assert_eq!(frame[(33, 17)], palette.get(4));
+        drop(frame);
+
+        assert!(component.irq());
+        assert_eq!(machine.peek(0, 0x99, 1), [FRAME_FLAG | COLLISION_FLAG | 2]);
+        component.read(0x99, true);
+        assert!(!component.irq());
+        assert_eq!(machine.take_interrupts().len(), 2);
+
+        // Reading back through the buffer
+        machine.load(0, 0x99, &[0x00]);
+        machine.load(0, 0x99, &[0x18]);
+        assert_eq!(machine.peek(0, 0x98, 1), [0x80]);
+    }
+
+    #[test]
+    fn mode4_and_line_interrupt() {
+        let builder = TestMachineBuilder::new().bus(0, 8);
+        let irq = builder.interrupt_line("irq");
+        let (builder, vdp) = builder.component::<Tms9918>(Tms9918Config {
+            variant: Tms9918Variant::Sms,
+            region: Tms9918Region::Ntsc,
+            palette: Palette::from_bytes(&TMS9918_DEFAULT_PALETTE, 16).unwrap(),
+            assigned_address_space: 0,
+            assigned_range: 0xbe..0xc0,
+            irq: Some(Arc::new(irq)),
+        });
+        let machine = builder.build();
+        let control = |low: u8, high: u8| {
+            machine.load(0, 0xbf, &[low]);
+            machine.load(0, 0xbf, &[high]);
+        };
+        let data = |data: &[u8]| {
+            for byte in data {
+                machine.load(0, 0xbe, &[*byte]);
+            }
+        };
+
+        // Mode 4 with line interrupts every 16 lines, names at 0x3800, sprites at 0x3f00 using the low tiles, and
+        // scrolled 4 pixels right
+        for (register, value) in [
+            (0, 0x14),
+            (1, 0x40),
+            (2, 0xff),
+            (5, 0xff),
+            (6, 0xfb),
+            (8, 0x04),
+            (10, 0x0f),
+        ] {
+            control(value, 0x80 | register);
+        }
+        // Black, red, then a blue backdrop and green sprites
+        control(0x00, 0xc0);
+        data(&[0x00, 0x03]);
+        control(0x10, 0xc0);
+        data(&[0x30, 0x0c]);
+        // Tile 1 is solid color 1, and only the top left name uses it
+        control(0x20, 0x40);
+        data(&[0xff, 0x00, 0x00, 0x00].repeat(8));
+        control(0x00, 0x78);
+        data(&[0x01, 0x00]);
+        // One sprite using tile 1
+        control(0x00, 0x7f);
+        data(&[0x09, 0xd0]);
+        control(0x80, 0x7f);
+        data(&[100, 0x01]);
+
+        let component = machine.component::<Tms9918>(vdp);
+        assert_eq!(component.mode(), Tms9918Mode::Mode4);
+
+        // The counter starts out empty, then takes 16 lines to run out again
+        machine.run_component::<Tms9918>(vdp, 1);
+        assert!(component.irq());
+        component.read(0xbf, true);
+        assert!(!component.irq());
+        machine.run_component::<Tms9918>(vdp, 15);
+        assert!(!component.irq());
+        machine.run_component::<Tms9918>(vdp, 1);
+        assert!(component.irq());
+
+        machine.run_component::<Tms9918>(vdp, 176);
+        assert_eq!(component.v_counter(), 0xc1);
+        assert_eq!(Tms9918Region::Ntsc.v_counter(0xdb), 0xd5);
+
+        let frame = component.frame.lock().unwrap();
+        let red = Srgba::new(0xff, 0x00, 0x00, 0xff);
+        let black = Srgba::new(0x00, 0x00, 0x00, 0xff);
+        assert_eq!(frame[(0, 0)], black);
+        assert_eq!(frame[(4, 0)], red);
+        assert_eq!(frame[(11, 7)], red);
+        assert_eq!(frame[(12, 0)], black);
+        assert_eq!(frame[(100, 9)], black);
+        assert_eq!(frame[(100, 10)], Srgba::new(0x00, 0xff, 0x00, 0xff));
+    }
+}
@@ -0,0 +1,844 @@
+use super::output::FrameOutput;
+use crate::{
+    component::{
+        display::DisplayComponent, memory::MemoryComponent, schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::{
+        io::{InterruptConnection, InterruptOutput},
+        processor::WaitStates,
+    },
+    machine::ComponentBuilder,
+    memory::{
+        AddressSpaceId, MemoryTranslationTable, PreviewMemoryRecord, ReadMemoryRecord,
+        WriteMemoryRecord,
+    },
+    runtime::{
+        color::Palette,
+        rendering_backend::{DisplayComponentFramebuffer, DisplayComponentInitializationData},
+    },
+};
+use nalgebra::{DMatrix, Vector2};
+use num::rational::Ratio;
+use palette::Srgba;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// Registers that are really there, the rest of the 64 the chip decodes read back 0xff
+const REGISTER_COUNT: usize = 0x2f;
+
+/// The ninth bit of each sprite's X position
+const SPRITE_X_HIGH: usize = 0x10;
+const CONTROL_1: usize = 0x11;
+const RASTER: usize = 0x12;
+const SPRITE_ENABLE: usize = 0x15;
+const CONTROL_2: usize = 0x16;
+const SPRITE_EXPAND_Y: usize = 0x17;
+const MEMORY_POINTERS: usize = 0x18;
+const INTERRUPT_LATCH: usize = 0x19;
+const INTERRUPT_ENABLE: usize = 0x1a;
+const SPRITE_PRIORITY: usize = 0x1b;
+const SPRITE_MULTICOLOR: usize = 0x1c;
+const SPRITE_EXPAND_X: usize = 0x1d;
+const SPRITE_SPRITE_COLLISION: usize = 0x1e;
+const SPRITE_BACKGROUND_COLLISION: usize = 0x1f;
+const BORDER_COLOR: usize = 0x20;
+const BACKGROUND_COLOR: usize = 0x21;
+const SPRITE_MULTICOLOR_0: usize = 0x25;
+const SPRITE_MULTICOLOR_1: usize = 0x26;
+const SPRITE_COLOR: usize = 0x27;
+
+// Control register 1
+const EXTENDED_COLOR: u8 = 0b0100_0000;
+const BITMAP: u8 = 0b0010_0000;
+const DISPLAY_ENABLE: u8 = 0b0001_0000;
+const ROW_SELECT: u8 = 0b0000_1000;
+// Control register 2
+const MULTICOLOR: u8 = 0b0001_0000;
+const COLUMN_SELECT: u8 = 0b0000_1000;
+
+const RASTER_INTERRUPT: u8 = 0b0000_0001;
+const SPRITE_BACKGROUND_INTERRUPT: u8 = 0b0000_0010;
+const SPRITE_SPRITE_INTERRUPT: u8 = 0b0000_0100;
+
+const SCREEN_WIDTH: usize = 320;
+const COLUMNS: u16 = 40;
+/// Border shown either side of the screen
+const SIDE_BORDER: usize = 32;
+const FRAME_WIDTH: usize = SCREEN_WIDTH + SIDE_BORDER * 2;
+/// Lines where fetching a row of the video matrix can happen
+const BADLINES: Range<u16> = 0x30..0xf8;
+/// Where the 25 and 24 row screens start and end
+const TALL_SCREEN: Range<u16> = 0x33..0xfb;
+const SHORT_SCREEN: Range<u16> = 0x37..0xf7;
+
+const SPRITE_COUNT: usize = 8;
+const SPRITE_WIDTH: i32 = 24;
+const SPRITE_HEIGHT: u16 = 21;
+/// Sprite X coordinate of the left edge of the screen
+const SPRITE_SCREEN_LEFT: i32 = 24;
+/// Where the sprite pointers sit past the start of the video matrix
+const SPRITE_POINTERS: u16 = 0x3f8;
+
+/// Cycles the processor loses to fetching a row of the video matrix
+const BADLINE_CYCLES: u64 = 40;
+/// Cycles the processor loses to each sprite shown on a line
+const SPRITE_CYCLES: u64 = 2;
+
+/// Colors of the Pepto palette, used when the user has not provided a .pal file
+#[rustfmt::skip]
+pub const VIC2_DEFAULT_PALETTE: [u8; 48] = [
+    0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0x68, 0x37, 0x2b, 0x70, 0xa4, 0xb2,
+    0x6f, 0x3d, 0x86, 0x58, 0x8d, 0x43, 0x35, 0x28, 0x79, 0xb8, 0xc7, 0x6f,
+    0x6f, 0x4f, 0x25, 0x43, 0x39, 0x00, 0x9a, 0x67, 0x59, 0x44, 0x44, 0x44,
+    0x6c, 0x6c, 0x6c, 0x9a, 0xd2, 0x84, 0x6c, 0x5e, 0xb5, 0x95, 0x95, 0x95,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vic2Region {
+    /// 6567R8, 263 lines of 65 cycles
+    Ntsc,
+    /// 6569, 312 lines of 63 cycles
+    Pal,
+}
+
+impl Vic2Region {
+    fn lines(self) -> u16 {
+        match self {
+            Vic2Region::Ntsc => 263,
+            Vic2Region::Pal => 312,
+        }
+    }
+
+    fn cycles_per_line(self) -> u16 {
+        match self {
+            Vic2Region::Ntsc => 65,
+            Vic2Region::Pal => 63,
+        }
+    }
+
+    /// Lines a TV shows, the rest is blanking
+    fn visible_lines(self) -> Range<u16> {
+        match self {
+            Vic2Region::Ntsc => 28..262,
+            Vic2Region::Pal => 16..288,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Vic2Config {
+    pub region: Vic2Region,
+    /// One cycle of the chip is one cycle of the processor
+    pub frequency: Ratio<u64>,
+    pub palette: Palette,
+    pub assigned_address_space: AddressSpaceId,
+    /// The 64 registers repeat over the whole range
+    pub assigned_range: Range<usize>,
+    /// The 16KB the chip fetches from, 0x0000 to 0x3fff
+    pub memory_address_space: AddressSpaceId,
+    /// The 4 bit color RAM, read alongside the video matrix
+    pub color_ram_address_space: AddressSpaceId,
+    pub color_ram_address: usize,
+    pub irq: Option<Arc<dyn InterruptConnection>>,
+    /// Where badlines and sprites take the bus away from the processor
+    pub wait_states: Option<Arc<WaitStates>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Vic2State {
+    registers: Vec<u8>,
+    line: u16,
+    /// Cycle along the line
+    cycle: u16,
+    /// Interrupts that happened, whether or not they are enabled
+    interrupts: u8,
+    sprite_collisions: u8,
+    background_collisions: u8,
+    /// Video matrix position of the start of the character row
+    video_counter_base: u16,
+    /// Line within the character row
+    row_counter: u8,
+    /// Showing the video matrix, rather than idling between badlines
+    displaying: bool,
+    /// The display was enabled on the first badline, which has to happen for any to follow in the frame
+    badlines_enabled: bool,
+    irq: bool,
+}
+
+impl Default for Vic2State {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; REGISTER_COUNT],
+            line: 0,
+            cycle: 0,
+            interrupts: 0,
+            sprite_collisions: 0,
+            background_collisions: 0,
+            video_counter_base: 0,
+            row_counter: 0,
+            displaying: false,
+            badlines_enabled: false,
+            irq: false,
+        }
+    }
+}
+
+impl Vic2State {
+    fn raster_compare(&self) -> u16 {
+        u16::from_le_bytes([self.registers[RASTER], self.registers[CONTROL_1] >> 7])
+    }
+
+    fn badline(&self) -> bool {
+        self.badlines_enabled
+            && BADLINES.contains(&self.line)
+            && self.line as u8 & 0b111 == self.registers[CONTROL_1] & 0b111
+    }
+
+    fn video_matrix(&self) -> u16 {
+        (self.registers[MEMORY_POINTERS] as u16 >> 4) << 10
+    }
+
+    fn sprite_height(&self, sprite: usize) -> u16 {
+        if self.registers[SPRITE_EXPAND_Y] & (1 << sprite) != 0 {
+            SPRITE_HEIGHT * 2
+        } else {
+            SPRITE_HEIGHT
+        }
+    }
+
+    /// The row of the sprite's data shown on the current line, if it is shown at all
+    fn sprite_row(&self, sprite: usize) -> Option<u16> {
+        if self.registers[SPRITE_ENABLE] & (1 << sprite) == 0 {
+            return None;
+        }
+
+        // Sprites start the line after their Y position
+        let top = self.registers[sprite * 2 + 1] as u16 + 1;
+        let row = self.line.checked_sub(top)?;
+
+        (row < self.sprite_height(sprite)).then(|| row * SPRITE_HEIGHT / self.sprite_height(sprite))
+    }
+}
+
+/// One pixel of the screen before sprites are drawn over it
+#[derive(Debug, Clone, Copy, Default)]
+struct GraphicsPixel {
+    color: u8,
+    /// Sprites with priority go behind it, and it collides with sprites
+    foreground: bool,
+}
+
+/// MOS 6567/6569 VIC-II, the video of the Commodore 64
+///
+/// Renders a line at a time from the 16KB it sees, with its 4 bit color RAM next to it. Badlines and sprites on a
+/// line take the cycles they really would away from the processor through [WaitStates]. Register writes in the
+/// middle of a line show up from the next one on, and the video matrix is read again every line instead of being
+/// kept from the badline
+#[derive(Debug)]
+pub struct Vic2 {
+    config: Vic2Config,
+    state: Mutex<Vic2State>,
+    frame: Mutex<DMatrix<Srgba<u8>>>,
+    output: FrameOutput,
+    irq: InterruptOutput,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+}
+
+impl Vic2 {
+    /// Whether the IRQ output is pulled
+    pub fn irq(&self) -> bool {
+        self.state.lock().unwrap().irq
+    }
+
+    /// Line the beam is on
+    pub fn line(&self) -> u16 {
+        self.state.lock().unwrap().line
+    }
+
+    fn update_irq(&self, state: &mut Vic2State) {
+        let raised = state.interrupts & state.registers[INTERRUPT_ENABLE] & 0x0f != 0;
+        self.irq.update(&mut state.irq, raised);
+    }
+
+    fn fetch(&self, address: u16) -> u8 {
+        let mut value = [0xff];
+        let _ = self.memory_translation_table.get().unwrap().preview(
+            address as usize & 0x3fff,
+            &mut value,
+            self.config.memory_address_space,
+        );
+
+        value[0]
+    }
+
+    fn fetch_color(&self, offset: u16) -> u8 {
+        let mut value = [0];
+        let _ = self.memory_translation_table.get().unwrap().preview(
+            self.config.color_ram_address + offset as usize,
+            &mut value,
+            self.config.color_ram_address_space,
+        );
+
+        value[0] & 0x0f
+    }
+
+    fn read(&self, address: usize, side_effects: bool) -> u8 {
+        let mut state = self.state.lock().unwrap();
+        let register = address & 0x3f;
+
+        match register {
+            CONTROL_1 => (state.registers[CONTROL_1] & 0x7f) | ((state.line >> 1) as u8 & 0x80),
+            RASTER => state.line as u8,
+            CONTROL_2 => state.registers[CONTROL_2] | 0xc0,
+            MEMORY_POINTERS => state.registers[MEMORY_POINTERS] | 0x01,
+            INTERRUPT_LATCH => state.interrupts | 0x70 | if state.irq { 0x80 } else { 0 },
+            INTERRUPT_ENABLE => state.registers[INTERRUPT_ENABLE] | 0xf0,
+            SPRITE_SPRITE_COLLISION | SPRITE_BACKGROUND_COLLISION => {
+                let collisions = if register == SPRITE_SPRITE_COLLISION {
+                    &mut state.sprite_collisions
+                } else {
+                    &mut state.background_collisions
+                };
+                let value = *collisions;
+
+                // Reading clears them
+                if side_effects {
+                    *collisions = 0;
+                }
+
+                value
+            }
+            BORDER_COLOR..REGISTER_COUNT => state.registers[register] | 0xf0,
+            REGISTER_COUNT.. => 0xff,
+            _ => state.registers[register],
+        }
+    }
+
+    fn write(&self, state: &mut Vic2State, address: usize, value: u8) {
+        let register = address & 0x3f;
+
+        match register {
+            // Writing a 1 acknowledges an interrupt
+            INTERRUPT_LATCH => state.interrupts &= !value,
+            SPRITE_SPRITE_COLLISION | SPRITE_BACKGROUND_COLLISION | REGISTER_COUNT.. => return,
+            _ => state.registers[register] = value,
+        }
+
+        // Moving the compare onto the current line triggers it right away
+        if matches!(register, CONTROL_1 | RASTER) && state.raster_compare() == state.line {
+            state.interrupts |= RASTER_INTERRUPT;
+        }
+
+        self.update_irq(state);
+    }
+
+    fn start_line(&self, state: &mut Vic2State) {
+        if state.line == state.raster_compare() {
+            state.interrupts |= RASTER_INTERRUPT;
+            self.update_irq(state);
+        }
+
+        if state.line == 0 {
+            state.video_counter_base = 0;
+        }
+        if state.line == BADLINES.start {
+            state.badlines_enabled = state.registers[CONTROL_1] & DISPLAY_ENABLE != 0;
+        }
+
+        let badline = state.badline();
+        if badline {
+            state.displaying = true;
+            state.row_counter = 0;
+        }
+
+        if let Some(wait_states) = &self.config.wait_states {
+            let sprites = (0..SPRITE_COUNT)
+                .filter(|sprite| state.sprite_row(*sprite).is_some())
+                .count() as u64;
+            let stolen = (if badline { BADLINE_CYCLES } else { 0 }) + sprites * SPRITE_CYCLES;
+
+            if stolen != 0 {
+                wait_states.insert(stolen);
+            }
+        }
+
+        let visible_lines = self.config.region.visible_lines();
+        if visible_lines.contains(&state.line) {
+            self.render_line(state, (state.line - visible_lines.start) as usize);
+        }
+    }
+
+    fn end_line(&self, state: &mut Vic2State) {
+        if state.displaying {
+            if state.row_counter == 7 {
+                state.video_counter_base = (state.video_counter_base + COLUMNS) & 0x3ff;
+                // A badline keeps the display going
+                state.displaying = state.badline();
+            }
+
+            if state.displaying {
+                state.row_counter = (state.row_counter + 1) & 0b111;
+            }
+        }
+
+        state.line += 1;
+        if state.line == self.config.region.lines() {
+            state.line = 0;
+            self.output.present(&self.frame.lock().unwrap());
+        }
+    }
+
+    fn render_line(&self, state: &mut Vic2State, y: usize) {
+        let registers = &state.registers;
+        let border = self
+            .config
+            .palette
+            .get(registers[BORDER_COLOR] as usize & 0x0f);
+        let screen = if registers[CONTROL_1] & ROW_SELECT != 0 {
+            TALL_SCREEN
+        } else {
+            SHORT_SCREEN
+        };
+        let columns = if registers[CONTROL_2] & COLUMN_SELECT != 0 {
+            0..SCREEN_WIDTH
+        } else {
+            7..SCREEN_WIDTH - 9
+        };
+
+        let mut frame = self.frame.lock().unwrap();
+
+        if !state.badlines_enabled || !screen.contains(&state.line) {
+            for x in 0..FRAME_WIDTH {
+                frame[(x, y)] = border;
+            }
+            return;
+        }
+
+        let mut line = self.graphics_line(state);
+        self.draw_sprites(state, &mut line);
+
+        for x in 0..FRAME_WIDTH {
+            frame[(x, y)] = match x.checked_sub(SIDE_BORDER) {
+                Some(x) if columns.contains(&x) => self.config.palette.get(line[x].color as usize),
+                _ => border,
+            };
+        }
+    }
+
+    /// The characters or bitmap of the current line, shifted by the fine scroll
+    fn graphics_line(&self, state: &Vic2State) -> Vec<GraphicsPixel> {
+        let registers = &state.registers;
+        let extended_color = registers[CONTROL_1] & EXTENDED_COLOR != 0;
+        let bitmap = registers[CONTROL_1] & BITMAP != 0;
+        let multicolor = registers[CONTROL_2] & MULTICOLOR != 0;
+        let background = |index: usize| registers[BACKGROUND_COLOR + index] & 0x0f;
+        let character_base = ((registers[MEMORY_POINTERS] as u16 >> 1) & 0b111) << 11;
+        let bitmap_base = (registers[MEMORY_POINTERS] as u16 & 0b1000) << 10;
+        // Extended color mode holds two address lines low
+        let address_mask = if extended_color { 0x39ff } else { 0x3fff };
+        let scroll = (registers[CONTROL_2] & 0b111) as usize;
+
+        let mut line = vec![
+            GraphicsPixel {
+                color: background(0),
+                foreground: false,
+            };
+            SCREEN_WIDTH
+        ];
+
+        for column in 0..COLUMNS {
+            let video_counter = (state.video_counter_base + column) & 0x3ff;
+            // Idling between badlines shows the last byte of the bank with everything else 0
+            let (code, color, pattern) = if state.displaying {
+                let code = self.fetch(state.video_matrix() + video_counter);
+                let pattern_address = if bitmap {
+                    bitmap_base | (video_counter << 3) | state.row_counter as u16
+                } else {
+                    let code = if extended_color { code & 0x3f } else { code };
+                    character_base + code as u16 * 8 + state.row_counter as u16
+                };
+
+                (
+                    code,
+                    self.fetch_color(video_counter),
+                    self.fetch(pattern_address & address_mask),
+                )
+            } else {
+                (0, 0, self.fetch(address_mask))
+            };
+
+            // Two bits a pixel in the multicolor modes, with a set high bit counting as foreground
+            let cell_multicolor = multicolor && (bitmap || color & 0b1000 != 0);
+            let cell = (0..8).map(|x| {
+                if cell_multicolor {
+                    let bits = (pattern >> (6 - x / 2 * 2)) & 0b11;
+                    let color = match (bitmap, bits) {
+                        (_, 0b00) => background(0),
+                        (false, 0b01) => background(1),
+                        (false, 0b10) => background(2),
+                        (false, _) => color & 0b111,
+                        (true, 0b01) => code >> 4,
+                        (true, 0b10) => code & 0x0f,
+                        (true, _) => color,
+                    };
+
+                    (color, bits & 0b10 != 0)
+                } else {
+                    let set = pattern & (0x80 >> x) != 0;
+                    let color = match (bitmap, set) {
+                        (false, true) if multicolor => color & 0b111,
+                        (false, true) => color,
+                        (false, false) if extended_color => background(code as usize >> 6),
+                        (false, false) => background(0),
+                        (true, true) => code >> 4,
+                        (true, false) => code & 0x0f,
+                    };
+
+                    (color, set)
+                }
+            });
+
+            for (x, (color, foreground)) in cell.enumerate() {
+                let Some(pixel) = line.get_mut(column as usize * 8 + x + scroll) else {
+                    break;
+                };
+
+                // Combinations that don't make sense show black, but still collide
+                let invalid = extended_color && (bitmap || multicolor);
+                *pixel = GraphicsPixel {
+                    color: if invalid { 0 } else { color },
+                    foreground,
+                };
+            }
+        }
+
+        line
+    }
+
+    fn draw_sprites(&self, state: &mut Vic2State, line: &mut [GraphicsPixel]) {
+        let background_collisions = state.background_collisions;
+        let registers = &state.registers;
+        let video_matrix = state.video_matrix();
+        // Which sprites have a pixel at each position, lowest number in front
+        let mut coverage = vec![0u8; SCREEN_WIDTH];
+        let mut colors = vec![None; SCREEN_WIDTH];
+
+        for sprite in (0..SPRITE_COUNT).rev() {
+            let Some(row) = state.sprite_row(sprite) else {
+                continue;
+            };
+
+            let pointer = self.fetch(video_matrix + SPRITE_POINTERS + sprite as u16) as u16;
+            let data = (0..3).fold(0u32, |data, byte| {
+                (data << 8) | self.fetch(pointer * 64 + row * 3 + byte) as u32
+            });
+
+            let bit = 1 << sprite;
+            let x = registers[sprite * 2] as i32
+                | if registers[SPRITE_X_HIGH] & bit != 0 {
+                    0x100
+                } else {
+                    0
+                };
+            let zoom = if registers[SPRITE_EXPAND_X] & bit != 0 {
+                2
+            } else {
+                1
+            };
+            let multicolor = registers[SPRITE_MULTICOLOR] & bit != 0;
+            let behind = registers[SPRITE_PRIORITY] & bit != 0;
+
+            for offset in 0..SPRITE_WIDTH * zoom {
+                let Ok(screen_x) = usize::try_from(x - SPRITE_SCREEN_LEFT + offset) else {
+                    continue;
+                };
+                if screen_x >= SCREEN_WIDTH {
+                    break;
+                }
+
+                let pixel = offset / zoom;
+                let color = if multicolor {
+                    match (data >> (22 - pixel / 2 * 2)) & 0b11 {
+                        0b00 => None,
+                        0b01 => Some(registers[SPRITE_MULTICOLOR_0]),
+                        0b10 => Some(registers[SPRITE_COLOR + sprite]),
+                        _ => Some(registers[SPRITE_MULTICOLOR_1]),
+                    }
+                } else {
+                    (data & (0x80_0000 >> pixel) != 0).then_some(registers[SPRITE_COLOR + sprite])
+                };
+                let Some(color) = color else {
+                    continue;
+                };
+
+                coverage[screen_x] |= bit;
+                if line[screen_x].foreground {
+                    state.background_collisions |= bit;
+                }
+                colors[screen_x] = Some((color & 0x0f, behind));
+            }
+        }
+
+        let mut sprite_collisions = state.sprite_collisions;
+        for (x, coverage) in coverage.into_iter().enumerate() {
+            if coverage.count_ones() > 1 {
+                sprite_collisions |= coverage;
+            }
+
+            if let Some((color, behind)) = colors[x] {
+                if !(behind && line[x].foreground) {
+                    line[x].color = color;
+                }
+            }
+        }
+
+        // Only the first collision since the register was read interrupts
+        if state.sprite_collisions == 0 && sprite_collisions != 0 {
+            state.interrupts |= SPRITE_SPRITE_INTERRUPT;
+        }
+        state.sprite_collisions = sprite_collisions;
+        if background_collisions == 0 && state.background_collisions != 0 {
+            state.interrupts |= SPRITE_BACKGROUND_INTERRUPT;
+        }
+        self.update_irq(state);
+    }
+}
+
+impl Component for Vic2 {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let irq = state.irq;
+
+        *state = Vic2State {
+            irq,
+            ..Default::default()
+        };
+        self.update_irq(&mut state);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let mut state_guard = self.state.lock().unwrap();
+        let irq = state_guard.irq;
+
+        *state_guard = rmpv::ext::from_value(state).unwrap();
+        // Tell the connection about the loaded level
+        let raised = state_guard.irq;
+        state_guard.irq = irq;
+        self.irq.update(&mut state_guard.irq, raised);
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for Vic2 {
+    type Config = Vic2Config;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let frequency = config.frequency;
+        let assigned_address_space = config.assigned_address_space;
+        let assigned_range = config.assigned_range.clone();
+        let frame_height = config.region.visible_lines().len();
+        let irq = InterruptOutput::new(config.irq.take());
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                frame: Mutex::new(DMatrix::from_element(
+                    FRAME_WIDTH,
+                    frame_height,
+                    Srgba::default(),
+                )),
+                output: FrameOutput::default(),
+                irq,
+                memory_translation_table: OnceLock::default(),
+            })
+            .set_schedulable(frequency, [], [])
+            .set_memory([(assigned_address_space, assigned_range)])
+            .set_display();
+    }
+}
+
+impl SchedulableComponent for Vic2 {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+        let cycles_per_line = self.config.region.cycles_per_line();
+
+        for _ in 0..period {
+            if state.cycle == 0 {
+                self.start_line(&mut state);
+            }
+
+            state.cycle += 1;
+            if state.cycle == cycles_per_line {
+                state.cycle = 0;
+                self.end_line(&mut state);
+            }
+        }
+    }
+}
+
+impl DisplayComponent for Vic2 {
+    fn set_display_data(&self, initialization_data: DisplayComponentInitializationData) {
+        self.output.set_display_data(
+            initialization_data,
+            Vector2::new(FRAME_WIDTH, self.config.region.visible_lines().len()),
+            self.config.palette.get(0),
+        );
+    }
+
+    fn get_framebuffer(&self) -> DisplayComponentFramebuffer {
+        self.output.get_framebuffer()
+    }
+
+    fn refresh_rate(&self) -> Option<Ratio<u64>> {
+        let region = self.config.region;
+
+        Some(self.config.frequency / (region.lines() as u64 * region.cycles_per_line() as u64))
+    }
+}
+
+impl MemoryComponent for Vic2 {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address + offset, true);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter().enumerate() {
+            self.write(&mut state, address + offset, *byte);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address + offset, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn badlines_sprites_and_raster_interrupt() {
+        let wait_states = Arc::new(WaitStates::default());
+        let builder = TestMachineBuilder::new()
+            .bus(0, 16)
+            .bus(1, 14)
+            .bus(2, 16)
+            .scratch_ram(1, 0x0000..0x4000, 0x00)
+            .scratch_ram(2, 0xd800..0xdc00, 0x01);
+        let irq = builder.interrupt_line("irq");
+        let (builder, vic) = builder.component::<Vic2>(Vic2Config {
+            region: Vic2Region::Pal,
+            frequency: Ratio::from_integer(985_248),
+            palette: Palette::from_bytes(&VIC2_DEFAULT_PALETTE, 16).unwrap(),
+            assigned_address_space: 0,
+            assigned_range: 0xd000..0xd400,
+            memory_address_space: 1,
+            color_ram_address_space: 2,
+            color_ram_address: 0xd800,
+            irq: Some(Arc::new(irq)),
+            wait_states: Some(wait_states.clone()),
+        });
+        let machine = builder.build();
+        let lines = |count: u64| machine.run_component::<Vic2>(vic, count * 63);
+
+        // A raster interrupt on line 0x40, display on with 25 rows and 40 columns scrolled down 3, the matrix at
+        // 0x0400, characters at 0x1000, and a light blue border on blue
+        for (register, value) in [
+            (0x12, 0x40),
+            (0x11, 0x1b),
+            (0x16, 0x08),
+            (0x18, 0x14),
+            (0x1a, 0x01),
+            (0x20, 0x0e),
+            (0x21, 0x06),
+        ] {
+            machine.load(0, 0xd000 + register, &[value]);
+        }
+        // Every character is 1, its top row a single dot and its seventh row solid
+        machine.load(1, 0x0400, &[0x01; 1000]);
+        machine.load(1, 0x1008, &[0x80, 0, 0, 0, 0, 0, 0xff, 0]);
+        // Sprite 0 in red, a bar along its top row, over the second column of characters
+        machine.load(1, 0x07f8, &[0x20]);
+        machine.load(1, 0x0800, &[0xff, 0xff, 0xff]);
+        for (register, value) in [(0x00, 0x20), (0x01, 0x40), (0x15, 0x01), (0x27, 0x02)] {
+            machine.load(0, 0xd000 + register, &[value]);
+        }
+
+        // The first badline is the first line of the screen with the scroll
+        lines(0x33);
+        assert_eq!(wait_states.pending(), 0);
+        lines(1);
+        assert_eq!(wait_states.pending(), BADLINE_CYCLES);
+        assert_eq!(wait_states.take(100), BADLINE_CYCLES);
+
+        lines(0x40 - 0x34);
+        let component = machine.component::<Vic2>(vic);
+        assert!(!component.irq());
+        lines(1);
+        assert!(component.irq());
+        assert_eq!(machine.peek(0, 0xd019, 1), [0xf1]);
+        assert_eq!(machine.peek(0, 0xd012, 1), [0x41]);
+        // From the badline of the second character row
+        assert_eq!(wait_states.take(100), BADLINE_CYCLES);
+
+        // The sprite steals its cycles, and lands on the solid row of the second character row
+        lines(1);
+        assert_eq!(wait_states.pending(), SPRITE_CYCLES);
+        assert_eq!(machine.peek(0, 0xd01f, 1), [0x01]);
+        assert_eq!(machine.peek(0, 0xd019, 1), [0xf3]);
+
+        let palette = &component.config.palette;
+        let frame = component.frame.lock().unwrap();
+        let row = |line: usize| line - 16;
+        assert_eq!(frame[(0, row(0x33))], palette.get(0x0e));
+        assert_eq!(frame[(SIDE_BORDER, row(0x33))], palette.get(0x01));
+        assert_eq!(frame[(SIDE_BORDER + 1, row(0x33))], palette.get(0x06));
+        assert_eq!(frame[(SIDE_BORDER, row(0x41))], palette.get(0x01));
+        assert_eq!(frame[(SIDE_BORDER + 8, row(0x41))], palette.get(0x02));
+        assert_eq!(frame[(SIDE_BORDER + 31, row(0x41))], palette.get(0x02));
+        assert_eq!(frame[(SIDE_BORDER + 32, row(0x41))], palette.get(0x01));
+        drop(frame);
+
+        // Acknowledging lets go of the line
+        machine.load(0, 0xd019, &[0x01]);
+        assert!(!component.irq());
+    }
+}
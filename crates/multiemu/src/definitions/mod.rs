@@ -1,3 +1,6 @@
+pub mod c64;
 pub mod chip8;
 pub mod misc;
+pub mod msx;
 pub mod nes;
+pub mod sms;
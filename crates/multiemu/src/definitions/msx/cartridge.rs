@@ -0,0 +1,128 @@
+use crate::{
+    component::{memory::MemoryComponent, Component, FromConfig},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+    rom::{handle::RomHandle, id::RomId, manager::RomRequirement},
+};
+use rangemap::RangeMap;
+use std::ops::Range;
+
+const PAGE_SIZE: usize = 0x4000;
+/// Cartridges the BIOS should start begin with this, followed by the address to call
+const HEADER_MAGIC: &[u8] = b"AB";
+
+#[derive(Debug)]
+pub(super) struct MsxCartridgeConfig {
+    pub rom: RomId,
+    /// Bus of the slot the cartridge is plugged into
+    pub assigned_address_space: AddressSpaceId,
+}
+
+/// A plain ROM cartridge, placed where its size and header say it wants to be
+///
+/// TODO: MegaROM mappers, which bank cartridges bigger than 48KB
+#[derive(Debug)]
+pub(super) struct MsxCartridge {
+    /// Missing if the ROM could not be found, in which case it reads as open bus
+    rom: Option<RomHandle>,
+    assigned_range: Range<usize>,
+}
+
+impl MsxCartridge {
+    fn read(&self, address: usize) -> u8 {
+        self.rom
+            .as_ref()
+            .filter(|rom| !rom.is_empty())
+            // Anything smaller than the range it sits in shows up again across the rest of it
+            .map_or(0xff, |rom| {
+                rom[(address - self.assigned_range.start) % rom.len()]
+            })
+    }
+}
+
+/// Where a ROM of this size sits in the slot
+fn placement(rom: &[u8]) -> Range<usize> {
+    match rom.len() {
+        length if length > 2 * PAGE_SIZE => 0x0000..0xc000,
+        length if length > PAGE_SIZE => 0x4000..0xc000,
+        // BASIC programs in ROM start at 0x8000, which the init address gives away
+        _ if rom.starts_with(HEADER_MAGIC)
+            && rom.get(3).is_some_and(|high| (0x80..0xc0).contains(high)) =>
+        {
+            0x8000..0xc000
+        }
+        _ => 0x4000..0x8000,
+    }
+}
+
+impl Component for MsxCartridge {
+    fn reset(&self) {
+        // Nothing to page, so nothing to reset
+    }
+}
+
+impl FromConfig for MsxCartridge {
+    type Config = MsxCartridgeConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let rom = component_builder.open_rom(config.rom, RomRequirement::Required);
+        let assigned_range = rom.as_deref().map_or(0x4000..0x8000, placement);
+
+        component_builder
+            .set_component(Self {
+                rom,
+                assigned_range: assigned_range.clone(),
+            })
+            .set_memory([(config.assigned_address_space, assigned_range)]);
+    }
+}
+
+impl MemoryComponent for MsxCartridge {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = self.read(address);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        errors.insert(address..address + buffer.len(), WriteMemoryRecord::Denied);
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = self.read(address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn placement_by_size_and_header() {
+        assert_eq!(placement(&[0; 0xc000]), 0x0000..0xc000);
+        assert_eq!(placement(&[0; 0x8000]), 0x4000..0xc000);
+        assert_eq!(placement(&[0; 0x2000]), 0x4000..0x8000);
+        assert_eq!(placement(b"AB\x10\x40"), 0x4000..0x8000);
+        assert_eq!(placement(b"AB\x10\x80"), 0x8000..0xc000);
+    }
+}
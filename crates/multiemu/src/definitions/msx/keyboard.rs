@@ -0,0 +1,261 @@
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        Component, FromConfig,
+    },
+    definitions::misc::io::{PortConnection, PortWiring},
+    input::{keyboard::KeyboardInput, manager::InputManager, EmulatedGamepadId, Input},
+    machine::ComponentBuilder,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+pub const MSX_KEYBOARD_GAMEPAD_TYPE: EmulatedGamepadTypeId =
+    EmulatedGamepadTypeId::new("MSX Keyboard");
+
+/// Row and column of every key on the international layout, with the host keys standing in for them
+///
+/// Host keys that share a matrix position, like both shift keys, all press the same one
+const MATRIX: &[(u8, u8, KeyboardInput)] = &[
+    (0, 0, KeyboardInput::Digit0),
+    (0, 1, KeyboardInput::Digit1),
+    (0, 2, KeyboardInput::Digit2),
+    (0, 3, KeyboardInput::Digit3),
+    (0, 4, KeyboardInput::Digit4),
+    (0, 5, KeyboardInput::Digit5),
+    (0, 6, KeyboardInput::Digit6),
+    (0, 7, KeyboardInput::Digit7),
+    (1, 0, KeyboardInput::Digit8),
+    (1, 1, KeyboardInput::Digit9),
+    (1, 2, KeyboardInput::Minus),
+    (1, 3, KeyboardInput::Equal),
+    (1, 4, KeyboardInput::Backslash),
+    (1, 5, KeyboardInput::BracketLeft),
+    (1, 6, KeyboardInput::BracketRight),
+    (1, 7, KeyboardInput::Semicolon),
+    (2, 0, KeyboardInput::Quote),
+    (2, 1, KeyboardInput::Backquote),
+    (2, 2, KeyboardInput::Comma),
+    (2, 3, KeyboardInput::Period),
+    (2, 4, KeyboardInput::Slash),
+    // Dead key for accents
+    (2, 5, KeyboardInput::IntlBackslash),
+    (2, 6, KeyboardInput::KeyA),
+    (2, 7, KeyboardInput::KeyB),
+    (3, 0, KeyboardInput::KeyC),
+    (3, 1, KeyboardInput::KeyD),
+    (3, 2, KeyboardInput::KeyE),
+    (3, 3, KeyboardInput::KeyF),
+    (3, 4, KeyboardInput::KeyG),
+    (3, 5, KeyboardInput::KeyH),
+    (3, 6, KeyboardInput::KeyI),
+    (3, 7, KeyboardInput::KeyJ),
+    (4, 0, KeyboardInput::KeyK),
+    (4, 1, KeyboardInput::KeyL),
+    (4, 2, KeyboardInput::KeyM),
+    (4, 3, KeyboardInput::KeyN),
+    (4, 4, KeyboardInput::KeyO),
+    (4, 5, KeyboardInput::KeyP),
+    (4, 6, KeyboardInput::KeyQ),
+    (4, 7, KeyboardInput::KeyR),
+    (5, 0, KeyboardInput::KeyS),
+    (5, 1, KeyboardInput::KeyT),
+    (5, 2, KeyboardInput::KeyU),
+    (5, 3, KeyboardInput::KeyV),
+    (5, 4, KeyboardInput::KeyW),
+    (5, 5, KeyboardInput::KeyX),
+    (5, 6, KeyboardInput::KeyY),
+    (5, 7, KeyboardInput::KeyZ),
+    (6, 0, KeyboardInput::ShiftLeft),
+    (6, 0, KeyboardInput::ShiftRight),
+    (6, 1, KeyboardInput::ControlLeft),
+    (6, 1, KeyboardInput::ControlRight),
+    // GRAPH
+    (6, 2, KeyboardInput::AltLeft),
+    (6, 3, KeyboardInput::CapsLock),
+    // CODE
+    (6, 4, KeyboardInput::AltRight),
+    (6, 5, KeyboardInput::F1),
+    (6, 6, KeyboardInput::F2),
+    (6, 7, KeyboardInput::F3),
+    (7, 0, KeyboardInput::F4),
+    (7, 1, KeyboardInput::F5),
+    (7, 2, KeyboardInput::Escape),
+    (7, 3, KeyboardInput::Tab),
+    // STOP
+    (7, 4, KeyboardInput::F8),
+    (7, 5, KeyboardInput::Backspace),
+    // SELECT
+    (7, 6, KeyboardInput::F7),
+    (7, 7, KeyboardInput::Enter),
+    (8, 0, KeyboardInput::Space),
+    (8, 1, KeyboardInput::Home),
+    (8, 2, KeyboardInput::Insert),
+    (8, 3, KeyboardInput::Delete),
+    (8, 4, KeyboardInput::ArrowLeft),
+    (8, 5, KeyboardInput::ArrowUp),
+    (8, 6, KeyboardInput::ArrowDown),
+    (8, 7, KeyboardInput::ArrowRight),
+    (9, 0, KeyboardInput::NumpadMultiply),
+    (9, 1, KeyboardInput::NumpadAdd),
+    (9, 2, KeyboardInput::NumpadDivide),
+    (9, 3, KeyboardInput::Numpad0),
+    (9, 4, KeyboardInput::Numpad1),
+    (9, 5, KeyboardInput::Numpad2),
+    (9, 6, KeyboardInput::Numpad3),
+    (9, 7, KeyboardInput::Numpad4),
+    (10, 0, KeyboardInput::Numpad5),
+    (10, 1, KeyboardInput::Numpad6),
+    (10, 2, KeyboardInput::Numpad7),
+    (10, 3, KeyboardInput::Numpad8),
+    (10, 4, KeyboardInput::Numpad9),
+    (10, 5, KeyboardInput::NumpadSubtract),
+    (10, 6, KeyboardInput::NumpadComma),
+    (10, 7, KeyboardInput::NumpadDecimal),
+];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct MsxKeyboardState {
+    /// Row the PPI is scanning, from the bottom of port C
+    row: u8,
+}
+
+/// The keyboard matrix behind the PPI, port C picking a row and port B reading its columns back
+///
+/// Port C also drives the CAPS LED and the key click, which aren't emulated
+#[derive(Debug)]
+pub(super) struct MsxKeyboard {
+    state: Mutex<MsxKeyboardState>,
+    input_manager: OnceLock<(Arc<InputManager>, Vec<EmulatedGamepadId>)>,
+}
+
+impl MsxKeyboard {
+    /// What to wire ports B and C of the PPI to
+    pub fn ports(self: &Arc<Self>) -> (PortWiring, PortWiring) {
+        (
+            PortWiring::Connection(Arc::new(KeyboardColumns(self.clone()))),
+            PortWiring::Connection(Arc::new(KeyboardRowSelect(self.clone()))),
+        )
+    }
+
+    fn held(&self, key: KeyboardInput) -> bool {
+        let Some((input_manager, gamepad_ids)) = self.input_manager.get() else {
+            return false;
+        };
+
+        gamepad_ids.first().is_some_and(|gamepad_id| {
+            input_manager
+                .get_input(*gamepad_id, Input::Keyboard(key))
+                .as_digital()
+        })
+    }
+
+    /// Columns of the row being scanned, low while held
+    fn columns(&self) -> u8 {
+        let row = self.state.lock().unwrap().row;
+
+        MATRIX
+            .iter()
+            .filter(|(key_row, _, key)| *key_row == row && self.held(*key))
+            .fold(0xff, |columns, (_, column, _)| columns & !(1 << column))
+    }
+}
+
+impl Component for MsxKeyboard {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = MsxKeyboardState::default();
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+    }
+}
+
+impl FromConfig for MsxKeyboard {
+    type Config = ();
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, _config: Self::Config) {
+        component_builder
+            .set_component(Self {
+                state: Mutex::default(),
+                input_manager: OnceLock::default(),
+            })
+            .set_input(
+                [(
+                    MSX_KEYBOARD_GAMEPAD_TYPE,
+                    EmulatedGamepadMetadata {
+                        present_inputs: present_inputs(),
+                        default_bindings: present_inputs()
+                            .into_iter()
+                            .map(|input| (input, input))
+                            .collect::<HashMap<_, _>>(),
+                    },
+                )],
+                [MSX_KEYBOARD_GAMEPAD_TYPE],
+            );
+    }
+}
+
+impl InputComponent for MsxKeyboard {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.input_manager
+            .set((input_manager, gamepad_ids.to_vec()))
+            .expect("Input manager set multiple times");
+    }
+}
+
+#[derive(Debug)]
+struct KeyboardColumns(Arc<MsxKeyboard>);
+
+impl PortConnection for KeyboardColumns {
+    fn read_pins(&self) -> u8 {
+        self.0.columns()
+    }
+}
+
+#[derive(Debug)]
+struct KeyboardRowSelect(Arc<MsxKeyboard>);
+
+impl PortConnection for KeyboardRowSelect {
+    fn write_pins(&self, value: u8, direction: u8) {
+        // Pins the PPI isn't driving float high
+        let value = (value & direction) | !direction;
+
+        self.0.state.lock().unwrap().row = value & 0x0f;
+    }
+}
+
+fn present_inputs() -> HashSet<Input> {
+    MATRIX
+        .iter()
+        .map(|(_, _, key)| Input::Keyboard(*key))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn matrix_has_no_duplicates() {
+        // Every host key is bound once, and no matrix position is left out
+        assert_eq!(present_inputs().len(), MATRIX.len());
+
+        let positions: HashSet<_> = MATRIX
+            .iter()
+            .map(|(row, column, _)| (row, column))
+            .collect();
+        assert_eq!(positions.len(), 11 * 8);
+    }
+}
@@ -0,0 +1,199 @@
+use super::misc::{
+    audio::ay3_8910::{Ay38910, Ay38910Config, Ay38910Kind},
+    io::{
+        ppi::{I8255Config, I8255},
+        PortWiring,
+    },
+    memory::{
+        rom::{RomMemory, RomMemoryConfig},
+        standard::{StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents},
+    },
+    processor::z80::{Z80Config, Z80Interrupt, Z80},
+    video::tms9918::{
+        Tms9918, Tms9918Config, Tms9918Region, Tms9918Variant, TMS9918_DEFAULT_PALETTE,
+    },
+};
+use crate::{
+    component::input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId},
+    config::GLOBAL_CONFIG,
+    input::{gamepad::GamepadInput, Input},
+    machine::{validation::MachineConfigurationError, Machine},
+    memory::AddressSpaceId,
+    rom::{
+        id::RomId,
+        info::RomInfo,
+        manager::RomManager,
+        region::RomRegion,
+        system::{GameSystem, OtherSystem},
+    },
+    runtime::color::Palette,
+};
+use cartridge::{MsxCartridge, MsxCartridgeConfig};
+use keyboard::MsxKeyboard;
+use num::rational::Ratio;
+use slots::{MsxSlots, MsxSlotsConfig};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+mod cartridge;
+mod keyboard;
+mod slots;
+
+pub const MSX_MEMORY_ADDRESS_SPACE_ID: AddressSpaceId = 0;
+/// The Z80 port space, of which MSX only decodes the low byte
+pub const MSX_IO_ADDRESS_SPACE_ID: AddressSpaceId = 1;
+
+pub const MSX_JOYSTICK_GAMEPAD_TYPE: EmulatedGamepadTypeId =
+    EmulatedGamepadTypeId::new("MSX Joystick");
+
+/// MSX.ROM, the international BIOS and BASIC
+pub const MSX_BIOS: RomId = RomId::new([
+    0xe9, 0x98, 0xf0, 0xc4, 0x41, 0xf4, 0xf1, 0x80, 0x0e, 0xf4, 0x4e, 0x42, 0xcd, 0x16, 0x59, 0x15,
+    0x02, 0x06, 0xcf, 0x79,
+]);
+
+/// Joystick pins in the order port A of the PSG reads them, low while held
+const JOYSTICK_BUTTONS: [GamepadInput; 6] = [
+    GamepadInput::DPadUp,
+    GamepadInput::DPadDown,
+    GamepadInput::DPadLeft,
+    GamepadInput::DPadRight,
+    GamepadInput::FPadDown,
+    GamepadInput::FPadRight,
+];
+
+/// Every slot gets a bus of its own, these come after the memory and I/O ones
+pub fn msx_slot_address_space(primary: usize, secondary: usize) -> AddressSpaceId {
+    2 + primary * 4 + secondary
+}
+
+/// Machines sold where the game came out ran PAL in Europe and NTSC nearly everywhere else
+fn video_region(rom_manager: &RomManager, rom_id: RomId) -> Tms9918Region {
+    let regions = rom_manager
+        .rom_information
+        .r_transaction()
+        .unwrap()
+        .get()
+        .primary::<RomInfo>(rom_id)
+        .unwrap()
+        .map(|info| info.regions)
+        .unwrap_or_default();
+    let preference = GLOBAL_CONFIG.read().unwrap().region_preference.clone();
+
+    match preference
+        .iter()
+        .find(|region| regions.contains(region))
+        .or(regions.first())
+        .or(preference.first())
+    {
+        Some(RomRegion::Europe | RomRegion::Australia) => Tms9918Region::Pal,
+        _ => Tms9918Region::Ntsc,
+    }
+}
+
+/// An MSX1 with 64KB of RAM and the cartridge in the first slot
+///
+/// Slot 0 has the BIOS, slot 1 the cartridge, slot 2 is left empty and slot 3 is expanded with the RAM in its first
+/// secondary slot, which is how a lot of the later machines were laid out
+pub fn msx_machine(
+    user_specified_roms: Vec<RomId>,
+    rom_manager: Arc<RomManager>,
+) -> Result<Machine, MachineConfigurationError> {
+    let region = video_region(&rom_manager, user_specified_roms[0]);
+    let machine = Machine::build(GameSystem::Other(OtherSystem::Msx), rom_manager);
+    let machine = machine.insert_bus(MSX_MEMORY_ADDRESS_SPACE_ID, 16);
+    let machine = machine.insert_bus(MSX_IO_ADDRESS_SPACE_ID, 8);
+    let machine = (0..16).fold(machine, |machine, slot| {
+        machine.insert_bus(msx_slot_address_space(slot / 4, slot % 4), 16)
+    });
+    let machine = machine.display_clock(region.frame_rate());
+
+    let (machine, processor) = machine.build_component::<Z80>(Z80Config {
+        frequency: Ratio::from_integer(3_579_545),
+        assigned_address_space: MSX_MEMORY_ADDRESS_SPACE_ID,
+        io_address_space: MSX_IO_ADDRESS_SPACE_ID,
+        // The BIOS runs in mode 1, so nothing drives the bus during the acknowledge
+        interrupt_data: 0xff,
+    });
+    let processor = machine.get_component::<Z80>(processor).unwrap();
+
+    let (machine, _) = machine.build_component::<RomMemory>(RomMemoryConfig {
+        rom: MSX_BIOS,
+        max_word_size: 2,
+        assigned_range: 0x0000..0x8000,
+        assigned_address_space: msx_slot_address_space(0, 0),
+    });
+    let (machine, _) = machine.build_component::<MsxCartridge>(MsxCartridgeConfig {
+        rom: user_specified_roms[0],
+        assigned_address_space: msx_slot_address_space(1, 0),
+    });
+    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
+        readable: true,
+        writable: true,
+        max_word_size: 2,
+        assigned_range: 0x0000..0x10000,
+        assigned_address_space: msx_slot_address_space(3, 0),
+        initial_contents: StandardMemoryInitialContents::Random,
+    });
+    let (machine, slots) = machine.build_component::<MsxSlots>(MsxSlotsConfig {
+        expanded: [false, false, false, true],
+    });
+    let slots = machine.get_component::<MsxSlots>(slots).unwrap();
+
+    let (machine, keyboard) = machine.build_component::<MsxKeyboard>(());
+    let (keyboard_columns, keyboard_row_select) = machine
+        .get_component::<MsxKeyboard>(keyboard)
+        .unwrap()
+        .ports();
+    let (machine, _) = machine.build_component::<I8255>(I8255Config {
+        assigned_address_space: MSX_IO_ADDRESS_SPACE_ID,
+        assigned_range: 0xa8..0xac,
+        port_a: PortWiring::Connection(slots),
+        port_b: keyboard_columns,
+        port_c: keyboard_row_select,
+        gamepads: Vec::default(),
+        interrupt_a: None,
+        interrupt_b: None,
+    });
+
+    let palette = Palette::load_for_system(machine.system, &TMS9918_DEFAULT_PALETTE);
+    let (machine, _) = machine.build_component::<Tms9918>(Tms9918Config {
+        variant: Tms9918Variant::Tms9918a,
+        region,
+        palette,
+        assigned_address_space: MSX_IO_ADDRESS_SPACE_ID,
+        assigned_range: 0x98..0x9a,
+        irq: Some(processor.interrupt_connection(Z80Interrupt::Maskable)),
+    });
+
+    // Only the first joystick port is wired up, port B of the PSG choosing the second isn't emulated
+    let mut joystick = [None; 8];
+    for (pin, button) in JOYSTICK_BUTTONS.into_iter().enumerate() {
+        joystick[pin] = Some((0, Input::Gamepad(button)));
+    }
+    let joystick_inputs: HashSet<_> = JOYSTICK_BUTTONS.into_iter().map(Input::Gamepad).collect();
+    let (machine, _) = machine.build_component::<Ay38910>(Ay38910Config {
+        kind: Ay38910Kind::Ay38910,
+        frequency: Ratio::new(3_579_545, 2),
+        assigned_address_space: MSX_IO_ADDRESS_SPACE_ID,
+        select_address: 0xa0,
+        write_address: 0xa1,
+        read_address: 0xa2,
+        port_a: PortWiring::Gamepads(joystick),
+        port_b: PortWiring::Unconnected,
+        gamepads: vec![(
+            MSX_JOYSTICK_GAMEPAD_TYPE,
+            EmulatedGamepadMetadata {
+                default_bindings: joystick_inputs
+                    .iter()
+                    .map(|input| (*input, *input))
+                    .collect::<HashMap<_, _>>(),
+                present_inputs: joystick_inputs,
+            },
+        )],
+    });
+
+    machine.try_build()
+}
@@ -0,0 +1,262 @@
+use super::{msx_slot_address_space, MSX_MEMORY_ADDRESS_SPACE_ID};
+use crate::{
+    component::{memory::MemoryComponent, Component, FromConfig},
+    definitions::misc::io::PortConnection,
+    machine::ComponentBuilder,
+    memory::{
+        AddressSpaceId, MemoryTranslationTable, PreviewMemoryRecord, ReadMemoryRecord,
+        WriteMemoryRecord,
+    },
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, OnceLock};
+
+const PAGE_SIZE: usize = 0x4000;
+/// Where an expanded slot keeps its secondary slot register, when it is selected for the last page
+const SECONDARY_SLOT_REGISTER: usize = 0xffff;
+
+#[derive(Debug)]
+pub(super) struct MsxSlotsConfig {
+    /// Primary slots split into four secondary slots
+    pub expanded: [bool; 4],
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct MsxSlotsState {
+    /// Primary slot of each 16KB page, two bits each, from port A of the PPI
+    primary: u8,
+    /// Secondary slot of each page for every expanded primary slot, in the same layout
+    secondary: [u8; 4],
+}
+
+impl MsxSlotsState {
+    fn primary_slot(&self, page: usize) -> usize {
+        (self.primary >> (page * 2)) as usize & 0b11
+    }
+}
+
+/// Primary and secondary slot selection, putting one of up to 16 slots in each 16KB page of the processor's memory
+///
+/// Every slot is a bus of its own, with [msx_slot_address_space] giving its id. Accesses are passed through to the
+/// slot selected for the page they fall in, and a slot with nothing there reads 0xff
+#[derive(Debug)]
+pub(super) struct MsxSlots {
+    config: MsxSlotsConfig,
+    state: Mutex<MsxSlotsState>,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+}
+
+impl MsxSlots {
+    /// Bus of the slot selected for an address
+    fn slot_address_space(&self, state: &MsxSlotsState, address: usize) -> AddressSpaceId {
+        let page = address / PAGE_SIZE;
+        let primary = state.primary_slot(page);
+        let secondary = if self.config.expanded[primary] {
+            (state.secondary[primary] >> (page * 2)) as usize & 0b11
+        } else {
+            0
+        };
+
+        msx_slot_address_space(primary, secondary)
+    }
+
+    /// The primary slot whose secondary slot register sits at this address, if there is one there
+    fn secondary_slot_register(&self, state: &MsxSlotsState, address: usize) -> Option<usize> {
+        let primary = state.primary_slot(3);
+
+        (address == SECONDARY_SLOT_REGISTER && self.config.expanded[primary]).then_some(primary)
+    }
+
+    fn mappings_changed(&self) {
+        if let Some(memory_translation_table) = self.memory_translation_table.get() {
+            memory_translation_table.mappings_changed(MSX_MEMORY_ADDRESS_SPACE_ID);
+        }
+    }
+}
+
+impl Component for MsxSlots {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = MsxSlotsState::default();
+        self.mappings_changed();
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+        self.mappings_changed();
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl FromConfig for MsxSlots {
+    type Config = MsxSlotsConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                memory_translation_table: OnceLock::new(),
+            })
+            .set_memory([(MSX_MEMORY_ADDRESS_SPACE_ID, 0x0000..0x10000)]);
+    }
+}
+
+/// Port A of the PPI picks the primary slots
+impl PortConnection for MsxSlots {
+    fn read_pins(&self) -> u8 {
+        self.state.lock().unwrap().primary
+    }
+
+    fn write_pins(&self, value: u8, direction: u8) {
+        let mut state = self.state.lock().unwrap();
+        let primary = (value & direction) | (state.primary & !direction);
+
+        if primary != state.primary {
+            state.primary = primary;
+            drop(state);
+            self.mappings_changed();
+        }
+    }
+}
+
+impl MemoryComponent for MsxSlots {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        let state = self.state.lock().unwrap();
+
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            if let Some(primary) = self.secondary_slot_register(&state, address) {
+                // Read back inverted, which is how the BIOS finds out which slots are expanded
+                *byte = !state.secondary[primary];
+                continue;
+            }
+
+            let mut value = [0xff];
+            // Nothing answering in the slot leaves the bus floating high
+            let _ = memory_translation_table.read(
+                address,
+                &mut value,
+                self.slot_address_space(&state, address),
+            );
+            *byte = value[0];
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        let mut state = self.state.lock().unwrap();
+
+        for (address, value) in (address..).zip(buffer.iter().copied()) {
+            if let Some(primary) = self.secondary_slot_register(&state, address) {
+                state.secondary[primary] = value;
+                self.mappings_changed();
+                continue;
+            }
+
+            // Writes to ROM just go nowhere
+            let _ = memory_translation_table.write(
+                address,
+                &[value],
+                self.slot_address_space(&state, address),
+            );
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        let state = self.state.lock().unwrap();
+
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            if let Some(primary) = self.secondary_slot_register(&state, address) {
+                *byte = !state.secondary[primary];
+                continue;
+            }
+
+            let mut value = [0xff];
+            let _ = memory_translation_table.preview(
+                address,
+                &mut value,
+                self.slot_address_space(&state, address),
+            );
+            *byte = value[0];
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn primary_and_secondary_slots() {
+        let mut builder = TestMachineBuilder::new().bus(MSX_MEMORY_ADDRESS_SPACE_ID, 16);
+        for slot in 0..16 {
+            builder = builder.bus(msx_slot_address_space(slot / 4, slot % 4), 16);
+        }
+        // Every slot filled with its own number, except 2 which is empty
+        for (primary, secondary) in [(0, 0), (1, 0), (3, 0), (3, 1), (3, 2), (3, 3)] {
+            builder = builder.scratch_ram(
+                msx_slot_address_space(primary, secondary),
+                0x0000..0x10000,
+                (primary * 4 + secondary) as u8,
+            );
+        }
+        let (builder, slots) = builder.component::<MsxSlots>(MsxSlotsConfig {
+            expanded: [false, false, false, true],
+        });
+        let machine = builder.build();
+        let slots = machine.component::<MsxSlots>(slots);
+
+        // Everything starts on slot 0
+        assert_eq!(machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0x4000, 1), [0]);
+
+        // Page 1 on slot 1, page 2 on the empty slot 2, page 3 on the expanded slot 3
+        slots.write_pins(0b11_10_01_00, 0xff);
+        assert_eq!(machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0x0000, 1), [0]);
+        assert_eq!(machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0x4000, 1), [4]);
+        assert_eq!(machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0x8000, 1), [0xff]);
+        assert_eq!(machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0xc000, 1), [12]);
+
+        // The secondary slot register, read back inverted
+        machine.load(MSX_MEMORY_ADDRESS_SPACE_ID, 0xffff, &[0b10_00_00_00]);
+        assert_eq!(
+            machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0xffff, 1),
+            [0b01_11_11_11]
+        );
+        assert_eq!(machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0xc000, 1), [14]);
+
+        // Writes land in whatever slot is selected
+        machine.load(MSX_MEMORY_ADDRESS_SPACE_ID, 0xc000, &[0x42]);
+        slots.write_pins(0b00_10_01_00, 0xff);
+        assert_eq!(machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0xc000, 1), [0]);
+        slots.write_pins(0b11_10_01_00, 0xff);
+        assert_eq!(machine.peek(MSX_MEMORY_ADDRESS_SPACE_ID, 0xc000, 1), [0x42]);
+    }
+}
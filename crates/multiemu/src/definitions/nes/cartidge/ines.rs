@@ -0,0 +1,217 @@
+use thiserror::Error;
+
+pub const INES_MAGIC: &[u8] = b"NES\x1a";
+pub const INES_HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_ROM_UNIT: usize = 0x4000;
+const CHR_ROM_UNIT: usize = 0x2000;
+/// What iNES files that don't say how much PRG RAM there is get, since so many games expect it
+const DEFAULT_PRG_RAM_SIZE: usize = 0x2000;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum INesError {
+    #[error("File does not start with an iNES header")]
+    NotINes,
+    #[error("Header asks for {expected} bytes of ROM but the file only has {actual}")]
+    Truncated { expected: usize, actual: usize },
+    #[error("Header describes more ROM than can be addressed")]
+    InvalidSize,
+}
+
+/// How the four nametables map onto the 2KB of RAM in the console
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mirroring {
+    /// Top two and bottom two are the same, for games that scroll vertically
+    #[default]
+    Horizontal,
+    /// Left two and right two are the same, for games that scroll horizontally
+    Vertical,
+    /// The cartridge brings its own RAM for the other two
+    FourScreen,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct INesHeader {
+    pub mapper: u16,
+    /// Only NES 2.0 headers have one, picking between boards that share a mapper number
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    /// PRG RAM keeps its contents with the power off
+    pub battery: bool,
+    /// 512 bytes loaded into PRG RAM at 0x7000, left over from copier hardware
+    pub trainer: bool,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    /// Only used when there is no CHR ROM
+    pub chr_ram_size: usize,
+    pub nes2: bool,
+}
+
+/// A NES 2.0 ROM size, either a count of units or, with the top nibble all set, an exponent and multiplier
+fn nes2_rom_size(low: u8, high: u8, unit: usize) -> Result<usize, INesError> {
+    if high == 0x0f {
+        let exponent = low >> 2;
+        let multiplier = (low & 0b11) as usize * 2 + 1;
+
+        1usize
+            .checked_shl(exponent as u32)
+            .and_then(|size| size.checked_mul(multiplier))
+            .ok_or(INesError::InvalidSize)
+    } else {
+        Ok((((high as usize) << 8) | low as usize) * unit)
+    }
+}
+
+/// A NES 2.0 RAM size, a shift count of 64 bytes with 0 meaning none
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+impl INesHeader {
+    /// Reads an iNES or NES 2.0 header, checking the file is big enough to hold what it describes
+    pub fn parse(rom: &[u8]) -> Result<Self, INesError> {
+        if rom.len() < INES_HEADER_SIZE || !rom.starts_with(INES_MAGIC) {
+            return Err(INesError::NotINes);
+        }
+
+        let header = &rom[..INES_HEADER_SIZE];
+        let nes2 = header[7] & 0x0c == 0x08;
+        let mirroring = if header[6] & 0b1000 != 0 {
+            Mirroring::FourScreen
+        } else if header[6] & 0b0001 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let mapper_low = (header[6] >> 4) as u16;
+
+        let parsed = if nes2 {
+            Self {
+                mapper: mapper_low | (header[7] & 0xf0) as u16 | (((header[8] & 0x0f) as u16) << 8),
+                submapper: header[8] >> 4,
+                mirroring,
+                battery: header[6] & 0b0010 != 0,
+                trainer: header[6] & 0b0100 != 0,
+                prg_rom_size: nes2_rom_size(header[4], header[9] & 0x0f, PRG_ROM_UNIT)?,
+                chr_rom_size: nes2_rom_size(header[5], header[9] >> 4, CHR_ROM_UNIT)?,
+                // Battery backed RAM is counted on its own
+                prg_ram_size: nes2_ram_size(header[10] & 0x0f) + nes2_ram_size(header[10] >> 4),
+                chr_ram_size: nes2_ram_size(header[11] & 0x0f) + nes2_ram_size(header[11] >> 4),
+                nes2,
+            }
+        } else {
+            // Old dumping tools left their name in the end of the header, over everything after byte 6
+            let clean = header[12..].iter().all(|byte| *byte == 0);
+            let mapper_high = if clean { (header[7] & 0xf0) as u16 } else { 0 };
+            let prg_ram_units = if clean { header[8] as usize } else { 0 };
+
+            Self {
+                mapper: mapper_low | mapper_high,
+                submapper: 0,
+                mirroring,
+                battery: header[6] & 0b0010 != 0,
+                trainer: header[6] & 0b0100 != 0,
+                prg_rom_size: header[4] as usize * PRG_ROM_UNIT,
+                chr_rom_size: header[5] as usize * CHR_ROM_UNIT,
+                prg_ram_size: (prg_ram_units * DEFAULT_PRG_RAM_SIZE).max(DEFAULT_PRG_RAM_SIZE),
+                chr_ram_size: if header[5] == 0 { CHR_ROM_UNIT } else { 0 },
+                nes2,
+            }
+        };
+
+        // Checked here so the offsets are safe to add up once the header is accepted
+        let expected = parsed
+            .prg_rom_offset()
+            .checked_add(parsed.prg_rom_size)
+            .and_then(|chr_rom_offset| chr_rom_offset.checked_add(parsed.chr_rom_size))
+            .ok_or(INesError::InvalidSize)?;
+        if rom.len() < expected {
+            return Err(INesError::Truncated {
+                expected,
+                actual: rom.len(),
+            });
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn trainer_offset(&self) -> Option<usize> {
+        self.trainer.then_some(INES_HEADER_SIZE)
+    }
+
+    pub fn prg_rom_offset(&self) -> usize {
+        INES_HEADER_SIZE + if self.trainer { TRAINER_SIZE } else { 0 }
+    }
+
+    pub fn chr_rom_offset(&self) -> usize {
+        self.prg_rom_offset() + self.prg_rom_size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn image(header: [u8; 16]) -> Vec<u8> {
+        let mut rom = header.to_vec();
+        rom.resize(0x10000, 0);
+        rom
+    }
+
+    #[test]
+    fn header_versions() {
+        // Super Mario Bros, a 32KB NROM-256 with vertical mirroring
+        let header = INesHeader::parse(&image(
+            *b"NES\x1a\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00",
+        ))
+        .unwrap();
+        assert_eq!(header.mapper, 0);
+        assert_eq!(header.mirroring, Mirroring::Vertical);
+        assert_eq!(header.prg_rom_size, 0x8000);
+        assert_eq!(header.chr_rom_size, 0x2000);
+        assert_eq!(header.prg_ram_size, 0x2000);
+        assert_eq!(header.chr_rom_offset(), 0x8010);
+        assert!(!header.nes2);
+
+        // A signature in the padding throws out the upper mapper nibble
+        let header = INesHeader::parse(&image(*b"NES\x1a\x02\x00\x10\x40DiskDude")).unwrap();
+        assert_eq!(header.mapper, 1);
+        assert_eq!(header.chr_ram_size, 0x2000);
+
+        // NES 2.0 with a 12 bit mapper, a submapper and 8KB of battery backed RAM
+        let header = INesHeader::parse(&image(
+            *b"NES\x1a\x02\x00\x12\x08\x21\x00\x70\x07\x00\x00\x00\x00",
+        ))
+        .unwrap();
+        assert!(header.nes2);
+        assert!(header.battery);
+        assert_eq!(header.mapper, 0x101);
+        assert_eq!(header.submapper, 2);
+        assert_eq!(header.prg_ram_size, 0x2000);
+        assert_eq!(header.chr_ram_size, 0x2000);
+
+        assert_eq!(
+            INesHeader::parse(&image(
+                *b"NES\x1a\x08\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00"
+            )),
+            Err(INesError::Truncated {
+                expected: 0x20010,
+                actual: 0x10000
+            })
+        );
+        assert_eq!(INesHeader::parse(b"FDS\x1a"), Err(INesError::NotINes));
+
+        // 7 * 2^63 bytes of PRG ROM in exponent notation
+        assert_eq!(
+            INesHeader::parse(&image(
+                *b"NES\x1a\xff\x00\x00\x08\x00\x0f\x00\x00\x00\x00\x00\x00"
+            )),
+            Err(INesError::InvalidSize)
+        );
+    }
+}
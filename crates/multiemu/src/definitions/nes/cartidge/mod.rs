@@ -0,0 +1,235 @@
+use super::{NES_CARTRIDGE_ADDRESS_SPACE_ID, NES_PPU_ADDRESS_SPACE_ID};
+use crate::{
+    component::{memory::MemoryComponent, Component, FromConfig},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+    rom::{handle::RomHandle, id::RomId, manager::RomRequirement},
+};
+use ines::{INesHeader, Mirroring};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+pub mod ines;
+
+const PRG_RAM_START: usize = 0x6000;
+const PRG_ROM_START: usize = 0x8000;
+/// Where the trainer goes in PRG RAM
+const TRAINER_START: usize = 0x1000;
+/// What reads of nothing at all return, the high byte of the address left on the bus by most reads
+const OPEN_BUS: u8 = 0xff;
+
+#[derive(Debug)]
+pub(super) struct NesCartridgeConfig {
+    pub rom: RomId,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct NesCartridgeState {
+    prg_ram: Vec<u8>,
+    chr_ram: Vec<u8>,
+}
+
+/// A cartridge from an iNES or NES 2.0 image, on both the cartridge slot's and the PPU's bus
+///
+/// Only mapper 0, NROM, is supported. Anything else is run as if it were NROM, which gets as far as the title
+/// screen for some games and nowhere for most
+///
+/// TODO: Other mappers, and saving battery backed PRG RAM
+#[derive(Debug)]
+pub(super) struct NesCartridge {
+    /// Missing if the ROM could not be found or isn't an iNES image, in which case it reads as open bus
+    rom: Option<RomHandle>,
+    header: Option<INesHeader>,
+    state: Mutex<NesCartridgeState>,
+    /// What [Component::reset] goes back to
+    initial_state: NesCartridgeState,
+}
+
+impl NesCartridge {
+    /// How the nametables should be wired up in the console
+    pub fn mirroring(&self) -> Mirroring {
+        self.header
+            .as_ref()
+            .map(|header| header.mirroring)
+            .unwrap_or_default()
+    }
+
+    fn prg_rom(&self, address: usize) -> u8 {
+        let (Some(rom), Some(header)) = (&self.rom, &self.header) else {
+            return OPEN_BUS;
+        };
+        if header.prg_rom_size == 0 {
+            return OPEN_BUS;
+        }
+
+        // NROM-128 shows up twice
+        rom[header.prg_rom_offset() + (address - PRG_ROM_START) % header.prg_rom_size]
+    }
+
+    fn chr_rom(&self, address: usize) -> Option<u8> {
+        let (Some(rom), Some(header)) = (&self.rom, &self.header) else {
+            return None;
+        };
+        if header.chr_rom_size == 0 {
+            return None;
+        }
+
+        Some(rom[header.chr_rom_offset() + address % header.chr_rom_size])
+    }
+
+    fn read(&self, address: usize, address_space: AddressSpaceId) -> u8 {
+        if address_space == NES_PPU_ADDRESS_SPACE_ID {
+            return self.chr_rom(address).unwrap_or_else(|| {
+                let state = self.state.lock().unwrap();
+                mirrored(&state.chr_ram, address).map_or(OPEN_BUS, |offset| state.chr_ram[offset])
+            });
+        }
+
+        if address >= PRG_ROM_START {
+            self.prg_rom(address)
+        } else {
+            let state = self.state.lock().unwrap();
+            mirrored(&state.prg_ram, address - PRG_RAM_START)
+                .map_or(OPEN_BUS, |offset| state.prg_ram[offset])
+        }
+    }
+
+    /// Returns false if nothing is there to be written
+    fn write(&self, address: usize, value: u8, address_space: AddressSpaceId) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        let (memory, offset) = if address_space == NES_PPU_ADDRESS_SPACE_ID {
+            if self.chr_rom(address).is_some() {
+                return false;
+            }
+            (&mut state.chr_ram, address)
+        } else if address < PRG_ROM_START {
+            (&mut state.prg_ram, address - PRG_RAM_START)
+        } else {
+            return false;
+        };
+
+        let Some(offset) = mirrored(memory, offset) else {
+            return false;
+        };
+        memory[offset] = value;
+        true
+    }
+}
+
+/// Boards with less RAM than the space it sits in see it repeated
+fn mirrored(memory: &[u8], offset: usize) -> Option<usize> {
+    (!memory.is_empty()).then(|| offset % memory.len())
+}
+
+impl Component for NesCartridge {
+    fn reset(&self) {
+        // PRG RAM is usually battery backed, and nobody clears it on reset anyway
+        self.state
+            .lock()
+            .unwrap()
+            .chr_ram
+            .clone_from(&self.initial_state.chr_ram);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+    }
+}
+
+impl FromConfig for NesCartridge {
+    type Config = NesCartridgeConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let rom = component_builder.open_rom(config.rom, RomRequirement::Required);
+        let header = rom.as_deref().and_then(|rom| match INesHeader::parse(rom) {
+            Ok(header) => Some(header),
+            Err(error) => {
+                tracing::error!("Could not load NES cartridge: {}", error);
+                None
+            }
+        });
+
+        let mut initial_state = NesCartridgeState::default();
+        if let (Some(rom), Some(header)) = (&rom, &header) {
+            if header.mapper != 0 {
+                tracing::warn!(
+                    "Mapper {} is not supported, running it as NROM",
+                    header.mapper
+                );
+            }
+
+            initial_state.prg_ram = vec![0; header.prg_ram_size];
+            initial_state.chr_ram = vec![0; header.chr_ram_size];
+
+            if let Some(trainer) = header.trainer_offset() {
+                let length = initial_state.prg_ram.len().min(TRAINER_START + 512);
+                if let Some(destination) = initial_state.prg_ram.get_mut(TRAINER_START..length) {
+                    destination.copy_from_slice(&rom[trainer..trainer + destination.len()]);
+                }
+            }
+        }
+
+        let mut ranges = vec![
+            (NES_CARTRIDGE_ADDRESS_SPACE_ID, PRG_ROM_START..0x10000),
+            (NES_PPU_ADDRESS_SPACE_ID, 0x0000..0x2000),
+        ];
+        if !initial_state.prg_ram.is_empty() {
+            ranges.push((NES_CARTRIDGE_ADDRESS_SPACE_ID, PRG_RAM_START..PRG_ROM_START));
+        }
+
+        component_builder
+            .set_component(Self {
+                rom,
+                header,
+                state: Mutex::new(initial_state.clone()),
+                initial_state,
+            })
+            .set_memory(ranges);
+    }
+}
+
+impl MemoryComponent for NesCartridge {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = self.read(address, address_space);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter()) {
+            if !self.write(address, *byte, address_space) {
+                errors.insert(address..address + 1, WriteMemoryRecord::Denied);
+            }
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = self.read(address, address_space);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        memory::MemoryComponent,
+        Component, ComponentId, FromConfig,
+    },
+    input::{
+        gamepad::GamepadInput, keyboard::KeyboardInput, manager::InputManager, EmulatedGamepadId,
+        Input,
+    },
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use rangemap::RangeMap;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use super::{zapper::Zapper, NES_CPU_ADDRESS_SPACE_ID};
+
+pub const NES_CONTROLLER_GAMEPAD_TYPE: EmulatedGamepadTypeId =
+    EmulatedGamepadTypeId::new("NES Controller");
+
+const JOY1_ADDRESS: usize = 0x4016;
+const JOY2_ADDRESS: usize = 0x4017;
+
+/// Buttons in the order the shift register reports them
+const REPORT_ORDER: [GamepadInput; 8] = [
+    GamepadInput::FPadRight,
+    GamepadInput::FPadDown,
+    GamepadInput::Select,
+    GamepadInput::Start,
+    GamepadInput::DPadUp,
+    GamepadInput::DPadDown,
+    GamepadInput::DPadLeft,
+    GamepadInput::DPadRight,
+];
+
+/// What the Four Score sends after both controllers on a port, so games can detect it
+const FOUR_SCORE_SIGNATURES: [u32; 2] = [0b0001_0000, 0b0010_0000];
+
+/// Upper bits of the controller registers are whatever was last on the bus, which is almost always this
+const OPEN_BUS: u8 = 0x40;
+
+#[derive(Debug, Default)]
+pub(super) struct NesControllersConfig {
+    /// Four Score plugged in, giving four controllers instead of two
+    pub four_score: bool,
+    /// Zapper in the second port, in place of the second controller
+    pub zapper: Option<ComponentId>,
+}
+
+#[derive(Debug, Default)]
+struct ControllerState {
+    strobe: bool,
+    shift_registers: [u32; 2],
+}
+
+/// The two controller ports, or the Four Score multitap if configured
+#[derive(Debug)]
+pub(super) struct NesControllers {
+    config: NesControllersConfig,
+    state: Mutex<ControllerState>,
+    input_manager: OnceLock<(Arc<InputManager>, Vec<EmulatedGamepadId>)>,
+    zapper: Option<Arc<Zapper>>,
+}
+
+impl NesControllers {
+    fn gamepad_report(&self, gamepad_index: usize) -> u32 {
+        let Some((input_manager, gamepad_ids)) = self.input_manager.get() else {
+            return 0;
+        };
+        let Some(gamepad_id) = gamepad_ids.get(gamepad_index) else {
+            return 0;
+        };
+
+        REPORT_ORDER
+            .iter()
+            .enumerate()
+            .filter(|(_, button)| {
+                input_manager
+                    .get_input(*gamepad_id, Input::Gamepad(**button))
+                    .as_digital()
+            })
+            .fold(0, |report, (bit, _)| report | (1 << bit))
+    }
+
+    fn latch(&self, state: &mut ControllerState) {
+        for port in 0..2 {
+            state.shift_registers[port] = if self.config.four_score {
+                // Controllers 1 and 3 on the first port, 2 and 4 on the second
+                self.gamepad_report(port)
+                    | (self.gamepad_report(port + 2) << 8)
+                    | (FOUR_SCORE_SIGNATURES[port] << 16)
+            } else {
+                self.gamepad_report(port)
+            };
+        }
+    }
+
+    fn report_length(&self) -> u32 {
+        if self.config.four_score {
+            24
+        } else {
+            8
+        }
+    }
+}
+
+impl Component for NesControllers {
+    fn reset(&self) {
+        *self.state.lock().unwrap() = ControllerState::default();
+    }
+}
+
+impl FromConfig for NesControllers {
+    type Config = NesControllersConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let zapper = config
+            .zapper
+            .and_then(|zapper| component_builder.machine().get_component::<Zapper>(zapper));
+        let gamepad_count = match (config.four_score, &zapper) {
+            (true, _) => 4,
+            (false, Some(_)) => 1,
+            (false, None) => 2,
+        };
+
+        component_builder
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                input_manager: OnceLock::default(),
+                zapper,
+            })
+            .set_memory([(NES_CPU_ADDRESS_SPACE_ID, JOY1_ADDRESS..JOY2_ADDRESS + 1)])
+            .set_input(
+                [(
+                    NES_CONTROLLER_GAMEPAD_TYPE,
+                    EmulatedGamepadMetadata {
+                        present_inputs: present_inputs(),
+                        default_bindings: default_bindings(),
+                    },
+                )],
+                std::iter::repeat_n(NES_CONTROLLER_GAMEPAD_TYPE, gamepad_count),
+            );
+    }
+}
+
+impl InputComponent for NesControllers {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.input_manager
+            .set((input_manager, gamepad_ids.to_vec()))
+            .expect("Input manager set multiple times");
+    }
+}
+
+impl MemoryComponent for NesControllers {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            let port = match address + offset {
+                JOY1_ADDRESS => 0,
+                JOY2_ADDRESS => 1,
+                _ => {
+                    errors.insert(address..address + buffer.len(), ReadMemoryRecord::Denied);
+                    return;
+                }
+            };
+
+            // Held strobe keeps reloading, so only the first button is ever seen
+            if state.strobe {
+                self.latch(&mut state);
+            }
+
+            // The Zapper only drives the light and trigger lines
+            if let (1, Some(zapper)) = (port, &self.zapper) {
+                *byte = OPEN_BUS | zapper.report();
+                continue;
+            }
+
+            *byte = OPEN_BUS | (state.shift_registers[port] & 1) as u8;
+
+            // Official controllers shift in ones once empty
+            state.shift_registers[port] =
+                (state.shift_registers[port] >> 1) | (1 << (self.report_length() - 1));
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        // Writes to 0x4017 go to the APU frame counter, not us
+        if address != JOY1_ADDRESS {
+            errors.insert(address..address + buffer.len(), WriteMemoryRecord::Denied);
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let strobe = buffer[0] & 1 != 0;
+
+        // The shift registers are loaded on the falling edge
+        if state.strobe && !strobe {
+            self.latch(&mut state);
+        }
+
+        state.strobe = strobe;
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        // Reading shifts the register, so peeking would change the game
+        errors.insert(
+            address..address + buffer.len(),
+            PreviewMemoryRecord::Impossible,
+        );
+    }
+}
+
+fn present_inputs() -> HashSet<Input> {
+    REPORT_ORDER.into_iter().map(Input::Gamepad).collect()
+}
+
+fn default_bindings() -> HashMap<Input, Input> {
+    REPORT_ORDER
+        .into_iter()
+        .map(|button| (Input::Gamepad(button), Input::Gamepad(button)))
+        .chain([
+            (
+                Input::Keyboard(KeyboardInput::KeyX),
+                Input::Gamepad(GamepadInput::FPadRight),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::KeyZ),
+                Input::Gamepad(GamepadInput::FPadDown),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ShiftRight),
+                Input::Gamepad(GamepadInput::Select),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::Enter),
+                Input::Gamepad(GamepadInput::Start),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ArrowUp),
+                Input::Gamepad(GamepadInput::DPadUp),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ArrowDown),
+                Input::Gamepad(GamepadInput::DPadDown),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ArrowLeft),
+                Input::Gamepad(GamepadInput::DPadLeft),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ArrowRight),
+                Input::Gamepad(GamepadInput::DPadRight),
+            ),
+        ])
+        .collect()
+}
@@ -1,33 +1,74 @@
-use super::misc::memory::{
-    mirror::{MirrorMemory, MirrorMemoryConfig},
-    standard::{StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents},
+use super::misc::{
+    expansion::{ExpansionPort, ExpansionPortConfig},
+    memory::{
+        mirror::{MirrorMemory, MirrorMemoryConfig},
+        standard::{StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents},
+    },
+    processor::{
+        m6502::{M6502Config, M6502Interrupt, M6502Kind, UndocumentedOpcodes, M6502},
+        WaitStates,
+    },
 };
 use crate::{
-    machine::Machine,
+    config::GLOBAL_CONFIG,
+    machine::{validation::MachineConfigurationError, Machine},
     memory::AddressSpaceId,
     rom::{
         id::RomId,
         manager::RomManager,
         system::{GameSystem, NintendoSystem},
     },
+    runtime::color::Palette,
 };
-use ppu::NesPPU;
-use rangemap::RangeMap;
-use std::sync::Arc;
+use cartidge::{ines::Mirroring, NesCartridge, NesCartridgeConfig};
+use controller::{NesControllers, NesControllersConfig};
+use num::rational::Ratio;
+use ppu::{NesPPU, NesPPUConfig, NES_DEFAULT_PALETTE};
+use std::{ops::Range, sync::Arc};
+use zapper::{Zapper, ZapperConfig};
 
 pub const NES_CPU_ADDRESS_SPACE_ID: AddressSpaceId = 0;
 pub const NES_PPU_ADDRESS_SPACE_ID: AddressSpaceId = 1;
+/// The cartridge's side of the cartridge slot, the processor reaches it through the expansion port
+pub const NES_CARTRIDGE_ADDRESS_SPACE_ID: AddressSpaceId = 2;
+/// 341 dots by 262 lines with one dot skipped every other frame, at a quarter of the 236.25/11 MHz master clock
+pub const NES_NTSC_FRAME_RATE: Ratio<u64> = Ratio::new_raw(118_125_000, 1_965_513);
+/// The master clock divided by 12
+pub const NES_NTSC_CPU_FREQUENCY: Ratio<u64> = Ratio::new_raw(236_250_000, 11 * 12);
 
+mod cartidge;
+mod controller;
 mod ppu;
+mod zapper;
+
+#[cfg(test)]
+mod test;
 
-pub fn nes_machine(user_specified_roms: Vec<RomId>, rom_manager: Arc<RomManager>) -> Machine {
+pub fn nes_machine(
+    user_specified_roms: Vec<RomId>,
+    rom_manager: Arc<RomManager>,
+) -> Result<Machine, MachineConfigurationError> {
     let machine = Machine::build(
         GameSystem::Nintendo(NintendoSystem::NintendoEntertainmentSystem),
         rom_manager,
     );
-    // TODO: This is guesswork
     let machine = machine.insert_bus(NES_CPU_ADDRESS_SPACE_ID, 16);
     let machine = machine.insert_bus(NES_PPU_ADDRESS_SPACE_ID, 16);
+    let machine = machine.insert_bus(NES_CARTRIDGE_ADDRESS_SPACE_ID, 16);
+    let machine = machine.display_clock(NES_NTSC_FRAME_RATE);
+    // OAM DMA takes the bus away from the processor
+    let wait_states = Arc::new(WaitStates::default());
+
+    // The 2A03 is a 6502 with the decimal mode cut out and the APU and controller ports added on
+    let (machine, processor) = machine.build_component::<M6502>(M6502Config {
+        kind: M6502Kind::R2A03,
+        frequency: NES_NTSC_CPU_FREQUENCY,
+        assigned_address_space: NES_CPU_ADDRESS_SPACE_ID,
+        undocumented_opcodes: UndocumentedOpcodes::Full,
+        magic_constant: 0xee,
+        cycle_accurate: true,
+        wait_states: Some(wait_states.clone()),
+    });
 
     // Set up the NES workram
     let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
@@ -41,78 +82,118 @@ pub fn nes_machine(user_specified_roms: Vec<RomId>, rom_manager: Arc<RomManager>
     let (machine, _) = machine.build_component::<MirrorMemory>(MirrorMemoryConfig {
         readable: true,
         writable: true,
-        assigned_ranges: RangeMap::from_iter([
-            (0x0800..0x1000, 0x0000),
-            (0x1000..0x1800, 0x0000),
-            (0x1800..0x2000, 0x0000),
-        ]),
+        assigned_range: 0x0800..0x2000,
+        destination: 0x0000,
+        stride: 0x0800,
         assigned_address_space: NES_CPU_ADDRESS_SPACE_ID,
     });
 
     // Set up the PPU
-    let (machine, _) = machine.default_component::<NesPPU>();
+    let palette = Palette::load_for_system(machine.system, &NES_DEFAULT_PALETTE);
+    let nmi = machine
+        .get_component::<M6502>(processor)
+        .unwrap()
+        .interrupt_connection(M6502Interrupt::Nmi);
+    let (machine, ppu) = machine.build_component::<NesPPU>(NesPPUConfig {
+        palette,
+        nmi: Some(nmi),
+        wait_states: Some(wait_states),
+    });
     let (machine, _) = machine.build_component::<MirrorMemory>(MirrorMemoryConfig {
         readable: true,
         writable: true,
-        // Repeats every 8 bytes, not writing it out manually
-        assigned_ranges: RangeMap::from_iter(
-            (0x2008..0x4000)
-                .step_by(8)
-                .map(|base| (base..base + 8, 0x2000)),
-        ),
+        // The 8 registers repeat all the way up to the APU
+        assigned_range: 0x2008..0x4000,
+        destination: 0x2000,
+        stride: 8,
         assigned_address_space: NES_CPU_ADDRESS_SPACE_ID,
     });
-    // Set up the PPU address space
-    // Pattern tables
-    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
-        readable: true,
-        writable: true,
-        max_word_size: 2,
-        assigned_range: 0x0000..0x1000,
-        assigned_address_space: NES_PPU_ADDRESS_SPACE_ID,
-        initial_contents: StandardMemoryInitialContents::Random,
-    });
-    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
-        readable: true,
-        writable: true,
-        max_word_size: 2,
-        assigned_range: 0x1000..0x2000,
-        assigned_address_space: NES_PPU_ADDRESS_SPACE_ID,
-        initial_contents: StandardMemoryInitialContents::Random,
-    });
-    // Name tables
-    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
-        readable: true,
-        writable: true,
-        max_word_size: 2,
-        assigned_range: 0x2000..0x2400,
-        assigned_address_space: NES_PPU_ADDRESS_SPACE_ID,
-        initial_contents: StandardMemoryInitialContents::Random,
-    });
-    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
-        readable: true,
-        writable: true,
-        max_word_size: 2,
-        assigned_range: 0x2400..0x2800,
-        assigned_address_space: NES_PPU_ADDRESS_SPACE_ID,
-        initial_contents: StandardMemoryInitialContents::Random,
+    // Controllers, or the Four Score if the user has one, with the Zapper taking the second port if plugged in
+    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+    let four_score = global_config_guard
+        .multitap
+        .get(&machine.system)
+        .copied()
+        .unwrap_or_default();
+    let light_gun = global_config_guard
+        .light_gun
+        .get(&machine.system)
+        .copied()
+        .unwrap_or_default();
+    drop(global_config_guard);
+
+    let (machine, zapper) = if light_gun {
+        let (machine, zapper) = machine.build_component::<Zapper>(ZapperConfig { ppu });
+        (machine, Some(zapper))
+    } else {
+        (machine, None)
+    };
+    let (machine, _) =
+        machine.build_component::<NesControllers>(NesControllersConfig { four_score, zapper });
+
+    // The cartridge brings the program and the pattern tables, and decides how the nametables are mirrored
+    let (machine, cartridge) = machine.build_component::<NesCartridge>(NesCartridgeConfig {
+        rom: user_specified_roms[0],
     });
-    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
-        readable: true,
-        writable: true,
-        max_word_size: 2,
-        assigned_range: 0x2800..0x2c00,
-        assigned_address_space: NES_PPU_ADDRESS_SPACE_ID,
-        initial_contents: StandardMemoryInitialContents::Random,
+    // Things like the Game Genie sit between the console and the cartridge
+    let (machine, _) = machine.build_component::<ExpansionPort>(ExpansionPortConfig {
+        name: "cartridge".into(),
+        assigned_ranges: vec![0x6000..0x10000],
+        assigned_address_space: NES_CPU_ADDRESS_SPACE_ID,
+        passthrough_address_space: Some(NES_CARTRIDGE_ADDRESS_SPACE_ID),
     });
-    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
+    let mirroring = machine
+        .get_component::<NesCartridge>(cartridge)
+        .unwrap()
+        .mirroring();
+
+    // Each range of the nametables and where its contents really are
+    let nametables: &[(Range<usize>, usize)] = match mirroring {
+        Mirroring::Horizontal => &[
+            (0x2000..0x2400, 0x2000),
+            (0x2400..0x2800, 0x2000),
+            (0x2800..0x2c00, 0x2800),
+            (0x2c00..0x3000, 0x2800),
+        ],
+        Mirroring::Vertical => &[(0x2000..0x2800, 0x2000), (0x2800..0x3000, 0x2000)],
+        Mirroring::FourScreen => &[(0x2000..0x3000, 0x2000)],
+    };
+    let machine = nametables
+        .iter()
+        .fold(machine, |machine, (assigned_range, destination)| {
+            if assigned_range.start == *destination {
+                machine
+                    .build_component::<StandardMemory>(StandardMemoryConfig {
+                        readable: true,
+                        writable: true,
+                        max_word_size: 2,
+                        assigned_range: assigned_range.clone(),
+                        assigned_address_space: NES_PPU_ADDRESS_SPACE_ID,
+                        initial_contents: StandardMemoryInitialContents::Random,
+                    })
+                    .0
+            } else {
+                machine
+                    .build_component::<MirrorMemory>(MirrorMemoryConfig {
+                        readable: true,
+                        writable: true,
+                        assigned_range: assigned_range.clone(),
+                        destination: *destination,
+                        stride: assigned_range.len(),
+                        assigned_address_space: NES_PPU_ADDRESS_SPACE_ID,
+                    })
+                    .0
+            }
+        });
+    // Right up to the palette, which the PPU keeps to itself
+    let (machine, _) = machine.build_component::<MirrorMemory>(MirrorMemoryConfig {
         readable: true,
         writable: true,
-        max_word_size: 2,
-        assigned_range: 0x2c00..0x3000,
+        assigned_range: 0x3000..0x3f00,
+        destination: 0x2000,
+        stride: 0x1000,
         assigned_address_space: NES_PPU_ADDRESS_SPACE_ID,
-        initial_contents: StandardMemoryInitialContents::Random,
     });
 
-    machine.build()
+    machine.try_build()
 }
@@ -1,53 +1,702 @@
+use super::{
+    zapper::{BeamPosition, LightSource},
+    NES_CPU_ADDRESS_SPACE_ID, NES_NTSC_FRAME_RATE, NES_PPU_ADDRESS_SPACE_ID,
+};
 use crate::{
-    component::{memory::MemoryComponent, Component, FromConfig},
+    component::{
+        display::DisplayComponent, memory::MemoryComponent, schedulable::SchedulableComponent,
+        Component, FromConfig,
+    },
+    definitions::misc::{
+        io::{InterruptConnection, InterruptOutput},
+        processor::WaitStates,
+        video::output::FrameOutput,
+    },
     machine::ComponentBuilder,
-    memory::{AddressSpaceId, MemoryTranslationTable, ReadMemoryRecord, WriteMemoryRecord},
+    memory::{
+        AddressSpaceId, MemoryTranslationTable, PreviewMemoryRecord, ReadMemoryRecord,
+        WriteMemoryRecord,
+    },
+    runtime::{
+        color::Palette,
+        rendering_backend::{DisplayComponentFramebuffer, DisplayComponentInitializationData},
+    },
 };
-use std::sync::Arc;
-
-use super::{NES_CPU_ADDRESS_SPACE_ID, NES_PPU_ADDRESS_SPACE_ID};
-
-// We store ppu state registers in normal struct sizes for easier gpu access
+use nalgebra::{DMatrix, Vector2};
+use num::rational::Ratio;
+use palette::Srgba;
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, OnceLock};
 
 const PPUCTRL_ADDRESS: usize = 0x2000;
 const PPUMASK_ADDRESS: usize = 0x2001;
 const PPUSTATUS_ADDRESS: usize = 0x2002;
 const OAMADDR_ADDRESS: usize = 0x2003;
-
-pub struct OamData {}
-
-impl OamData {
-    const ADDRESS: usize = 0x2004;
-}
-
+const OAMDATA_ADDRESS: usize = 0x2004;
 const PPUSCROLL_ADDRESS: usize = 0x2005;
 const PPUADDR_ADDRESS: usize = 0x2006;
 const PPUDATA_ADDRESS: usize = 0x2007;
 const OAMDMA_ADDRESS: usize = 0x4014;
 
-struct State {
-    oamdata: u8,
+const CONTROL_INCREMENT_32: u8 = 0b0000_0100;
+const CONTROL_SPRITE_TABLE: u8 = 0b0000_1000;
+const CONTROL_BACKGROUND_TABLE: u8 = 0b0001_0000;
+const CONTROL_LARGE_SPRITES: u8 = 0b0010_0000;
+const CONTROL_NMI: u8 = 0b1000_0000;
+
+const MASK_GREYSCALE: u8 = 0b0000_0001;
+const MASK_BACKGROUND_LEFT: u8 = 0b0000_0010;
+const MASK_SPRITES_LEFT: u8 = 0b0000_0100;
+const MASK_BACKGROUND: u8 = 0b0000_1000;
+const MASK_SPRITES: u8 = 0b0001_0000;
+
+const STATUS_SPRITE_OVERFLOW: u8 = 0b0010_0000;
+const STATUS_SPRITE_ZERO_HIT: u8 = 0b0100_0000;
+const STATUS_VBLANK: u8 = 0b1000_0000;
+
+const VISIBLE_WIDTH: usize = 256;
+const VISIBLE_LINES: u16 = 240;
+const VBLANK_LINE: u16 = 241;
+const PRE_RENDER_LINE: u16 = 261;
+const LINES: u16 = 262;
+const SPRITES_PER_LINE: usize = 8;
+/// Where the palette shows up on the PPU bus, on top of the mirrors of the nametables
+const PALETTE_START: u16 = 0x3f00;
+/// Bits of the VRAM address that the horizontal scroll is stored in, the rest are the vertical scroll
+const HORIZONTAL_BITS: u16 = 0x041f;
+/// Cycles the processor is held off the bus while OAM DMA copies a page
+const OAM_DMA_CYCLES: u64 = 513;
+/// 341 dots a line, at a quarter of the 236.25/11 MHz master clock
+pub(super) const NES_NTSC_LINE_RATE: Ratio<u64> = Ratio::new_raw(236_250_000, 11 * 4 * 341);
+
+/// The 2C02 palette used when the user has not provided a .pal file
+#[rustfmt::skip]
+pub(super) const NES_DEFAULT_PALETTE: [u8; 192] = [
+    0x54, 0x54, 0x54, 0x00, 0x1e, 0x74, 0x08, 0x10, 0x90, 0x30, 0x00, 0x88,
+    0x44, 0x00, 0x64, 0x5c, 0x00, 0x30, 0x54, 0x04, 0x00, 0x3c, 0x18, 0x00,
+    0x20, 0x2a, 0x00, 0x08, 0x3a, 0x00, 0x00, 0x40, 0x00, 0x00, 0x3c, 0x00,
+    0x00, 0x32, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x98, 0x96, 0x98, 0x08, 0x4c, 0xc4, 0x30, 0x32, 0xec, 0x5c, 0x1e, 0xe4,
+    0x88, 0x14, 0xb0, 0xa0, 0x14, 0x64, 0x98, 0x22, 0x20, 0x78, 0x3c, 0x00,
+    0x54, 0x5a, 0x00, 0x28, 0x72, 0x00, 0x08, 0x7c, 0x00, 0x00, 0x76, 0x28,
+    0x00, 0x66, 0x78, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xec, 0xee, 0xec, 0x4c, 0x9a, 0xec, 0x78, 0x7c, 0xec, 0xb0, 0x62, 0xec,
+    0xe4, 0x54, 0xec, 0xec, 0x58, 0xb4, 0xec, 0x6a, 0x64, 0xd4, 0x88, 0x20,
+    0xa0, 0xaa, 0x00, 0x74, 0xc4, 0x00, 0x4c, 0xd0, 0x20, 0x38, 0xcc, 0x6c,
+    0x38, 0xb4, 0xcc, 0x3c, 0x3c, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xec, 0xee, 0xec, 0xa8, 0xcc, 0xec, 0xbc, 0xbc, 0xec, 0xd4, 0xb2, 0xec,
+    0xec, 0xae, 0xec, 0xec, 0xae, 0xd4, 0xec, 0xb4, 0xb0, 0xe4, 0xc4, 0x90,
+    0xcc, 0xd2, 0x78, 0xb4, 0xde, 0x78, 0xa8, 0xe2, 0x90, 0x98, 0xe2, 0xb4,
+    0xa0, 0xd6, 0xe4, 0xa0, 0xa2, 0xa0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+#[derive(Debug)]
+pub(super) struct NesPPUConfig {
+    pub palette: Palette,
+    /// Pulled at the start of vblank if PPUCTRL asks for it
+    pub nmi: Option<Arc<dyn InterruptConnection>>,
+    /// Shared with the processor, which OAM DMA takes the bus from
+    pub wait_states: Option<Arc<WaitStates>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NesPPUState {
+    control: u8,
+    mask: u8,
+    status: u8,
+    oam_address: u8,
+    oam: Vec<u8>,
+    palette_ram: [u8; 32],
+    /// The VRAM address, which doubles as the scroll position while rendering
+    vram_address: u16,
+    /// Where the VRAM address is reloaded from, written by PPUSCROLL and PPUADDR
+    temporary_address: u16,
+    fine_x: u8,
+    /// Whether the next PPUSCROLL or PPUADDR write is the second of the pair
+    write_toggle: bool,
+    /// PPUDATA reads return the byte fetched by the read before, outside of the palette
+    read_buffer: u8,
+    /// Whatever was last on the data lines between the processor and the PPU, which write only registers read as
+    open_bus: u8,
+    line: u16,
+    nmi: bool,
 }
 
+impl Default for NesPPUState {
+    fn default() -> Self {
+        Self {
+            control: 0,
+            mask: 0,
+            status: 0,
+            oam_address: 0,
+            oam: vec![0; 256],
+            palette_ram: [0; 32],
+            vram_address: 0,
+            temporary_address: 0,
+            fine_x: 0,
+            write_toggle: false,
+            read_buffer: 0,
+            open_bus: 0,
+            line: 0,
+            nmi: false,
+        }
+    }
+}
+
+impl NesPPUState {
+    fn rendering(&self) -> bool {
+        self.mask & (MASK_BACKGROUND | MASK_SPRITES) != 0
+    }
+
+    fn sprite_height(&self) -> u16 {
+        if self.control & CONTROL_LARGE_SPRITES != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn advance_address(&mut self) {
+        let increment = if self.control & CONTROL_INCREMENT_32 != 0 {
+            32
+        } else {
+            1
+        };
+        self.vram_address = self.vram_address.wrapping_add(increment) & 0x7fff;
+    }
+
+    /// Moves the VRAM address down a line, wrapping into the nametable below after the 30th row of tiles
+    fn increment_y(&mut self) {
+        let address = self.vram_address;
+
+        if address & 0x7000 != 0x7000 {
+            self.vram_address = address + 0x1000;
+            return;
+        }
+
+        let coarse_y = match (address >> 5) & 0x1f {
+            29 => {
+                self.vram_address ^= 0x0800;
+                0
+            }
+            // Rows past the attribute table wrap without switching nametables
+            31 => 0,
+            coarse_y => coarse_y + 1,
+        };
+        self.vram_address = (self.vram_address & !0x73e0) | (coarse_y << 5);
+    }
+}
+
+/// A single sprite pixel that won out on its column
+#[derive(Debug, Clone, Copy)]
+struct SpritePixel {
+    /// Palette in the top two bits, color in the bottom two
+    color: u8,
+    behind_background: bool,
+    sprite_zero: bool,
+}
+
+/// Ricoh 2C02, drawing the background and sprites a line at a time out of the pattern tables and nametables on its
+/// own bus
+///
+/// Fetches are done all at once at the start of each line, which is enough for scrolling split with PPUADDR writes
+/// between lines but not for mappers that count the fetches themselves
 #[derive(Debug)]
-pub(super) struct NesPPU {}
+pub(super) struct NesPPU {
+    config: NesPPUConfig,
+    state: Mutex<NesPPUState>,
+    /// Frame being drawn, handed to the output once it is done
+    frame: Mutex<DMatrix<Srgba<u8>>>,
+    output: FrameOutput,
+    nmi: InterruptOutput,
+    memory_translation_table: OnceLock<Arc<MemoryTranslationTable>>,
+}
+
+impl NesPPU {
+    /// Whether the NMI output is pulled
+    pub fn nmi(&self) -> bool {
+        self.state.lock().unwrap().nmi
+    }
+
+    fn update_nmi(&self, state: &mut NesPPUState) {
+        let raised = state.status & STATUS_VBLANK != 0 && state.control & CONTROL_NMI != 0;
+        self.nmi.update(&mut state.nmi, raised);
+    }
+
+    fn fetch(&self, address: u16) -> u8 {
+        let mut value = [0];
+        let _ = self.memory_translation_table.get().unwrap().preview(
+            address as usize & 0x3fff,
+            &mut value,
+            NES_PPU_ADDRESS_SPACE_ID,
+        );
+
+        value[0]
+    }
+
+    /// Palette RAM index of an address, where the backdrop of each sprite palette is the background one
+    fn palette_index(address: u16) -> usize {
+        let index = address as usize & 0x1f;
+
+        if index & 0x13 == 0x10 {
+            index & 0x0f
+        } else {
+            index
+        }
+    }
+
+    fn read(&self, address: usize, side_effects: bool) -> u8 {
+        let mut state = self.state.lock().unwrap();
+
+        let value = match address {
+            PPUSTATUS_ADDRESS => {
+                let value = (state.status & 0xe0) | (state.open_bus & 0x1f);
+
+                if side_effects {
+                    state.status &= !STATUS_VBLANK;
+                    state.write_toggle = false;
+                    self.update_nmi(&mut state);
+                }
+
+                value
+            }
+            OAMDATA_ADDRESS => state.oam[state.oam_address as usize],
+            PPUDATA_ADDRESS => {
+                let address = state.vram_address & 0x3fff;
+                // The palette comes back right away, with the nametable under it going into the buffer
+                let (value, buffered) = if address >= PALETTE_START {
+                    (
+                        state.palette_ram[Self::palette_index(address)] | (state.open_bus & 0xc0),
+                        self.fetch(address - 0x1000),
+                    )
+                } else {
+                    (state.read_buffer, self.fetch(address))
+                };
+
+                if side_effects {
+                    state.read_buffer = buffered;
+                    state.advance_address();
+                }
+
+                value
+            }
+            _ => return state.open_bus,
+        };
+
+        if side_effects {
+            state.open_bus = value;
+        }
+
+        value
+    }
+
+    fn write(&self, address: usize, value: u8) {
+        if address == OAMDMA_ADDRESS {
+            self.oam_dma(value);
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.open_bus = value;
+
+        match address {
+            PPUCTRL_ADDRESS => {
+                state.control = value;
+                state.temporary_address =
+                    (state.temporary_address & !0x0c00) | ((value as u16 & 0b11) << 10);
+                self.update_nmi(&mut state);
+            }
+            PPUMASK_ADDRESS => state.mask = value,
+            OAMADDR_ADDRESS => state.oam_address = value,
+            OAMDATA_ADDRESS => {
+                let oam_address = state.oam_address;
+                state.oam[oam_address as usize] = value;
+                state.oam_address = oam_address.wrapping_add(1);
+            }
+            PPUSCROLL_ADDRESS => {
+                if state.write_toggle {
+                    state.temporary_address = (state.temporary_address & !0x73e0)
+                        | ((value as u16 & 0b111) << 12)
+                        | ((value as u16 & 0xf8) << 2);
+                } else {
+                    state.temporary_address =
+                        (state.temporary_address & !0x001f) | (value as u16 >> 3);
+                    state.fine_x = value & 0b111;
+                }
+                state.write_toggle = !state.write_toggle;
+            }
+            PPUADDR_ADDRESS => {
+                if state.write_toggle {
+                    state.temporary_address = (state.temporary_address & 0xff00) | value as u16;
+                    state.vram_address = state.temporary_address;
+                } else {
+                    state.temporary_address =
+                        (state.temporary_address & 0x00ff) | ((value as u16 & 0x3f) << 8);
+                }
+                state.write_toggle = !state.write_toggle;
+            }
+            PPUDATA_ADDRESS => {
+                let address = state.vram_address & 0x3fff;
+
+                if address >= PALETTE_START {
+                    state.palette_ram[Self::palette_index(address)] = value & 0x3f;
+                } else {
+                    let _ = self.memory_translation_table.get().unwrap().write(
+                        address as usize,
+                        &[value],
+                        NES_PPU_ADDRESS_SPACE_ID,
+                    );
+                }
+                state.advance_address();
+            }
+            // PPUSTATUS is read only
+            _ => {}
+        }
+    }
+
+    /// Copies a page of the processor's memory into OAM, starting from wherever OAMADDR points
+    fn oam_dma(&self, page: u8) {
+        let memory_translation_table = self.memory_translation_table.get().unwrap();
+        let mut data = [0; 256];
+
+        // The reads can land on our own registers, so the state isn't held while doing them
+        for (offset, byte) in data.iter_mut().enumerate() {
+            let mut value = [0];
+            let _ = memory_translation_table.read(
+                ((page as usize) << 8) | offset,
+                &mut value,
+                NES_CPU_ADDRESS_SPACE_ID,
+            );
+            *byte = value[0];
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let oam_address = state.oam_address as usize;
+        for (offset, byte) in data.into_iter().enumerate() {
+            state.oam[(oam_address + offset) & 0xff] = byte;
+        }
+
+        if let Some(wait_states) = &self.config.wait_states {
+            wait_states.insert(OAM_DMA_CYCLES);
+        }
+    }
+
+    /// Palette and color of each pixel of the background on this line, starting from the VRAM address
+    fn background_line(&self, state: &NesPPUState) -> [u8; VISIBLE_WIDTH] {
+        let mut line = [0; VISIBLE_WIDTH];
+        let mut address = state.vram_address;
+        let fine_y = (address >> 12) & 0b111;
+        let pattern_table = if state.control & CONTROL_BACKGROUND_TABLE != 0 {
+            0x1000
+        } else {
+            0
+        };
+
+        let mut x = 0;
+        // A fine scroll makes the line straddle one more tile
+        for tile in 0..=VISIBLE_WIDTH / 8 {
+            let name = self.fetch(0x2000 | (address & 0x0fff)) as u16;
+            let attribute = self.fetch(
+                0x23c0 | (address & 0x0c00) | ((address >> 4) & 0x38) | ((address >> 2) & 0x07),
+            );
+            // Each attribute byte covers 4 by 4 tiles, in 2 by 2 quadrants
+            let palette = (attribute >> (((address >> 4) & 0b100) | (address & 0b10))) & 0b11;
+            let low = self.fetch(pattern_table + name * 16 + fine_y);
+            let high = self.fetch(pattern_table + name * 16 + fine_y + 8);
+
+            let first = if tile == 0 { state.fine_x } else { 0 };
+            for bit in first..8 {
+                if x == VISIBLE_WIDTH {
+                    break;
+                }
+
+                let color = ((low >> (7 - bit)) & 1) | (((high >> (7 - bit)) & 1) << 1);
+                line[x] = if color == 0 {
+                    0
+                } else {
+                    (palette << 2) | color
+                };
+                x += 1;
+            }
+
+            // Next tile over, into the nametable to the right after the 32nd
+            address = if address & 0x001f == 0x001f {
+                (address & !0x001f) ^ 0x0400
+            } else {
+                address + 1
+            };
+        }
+
+        line
+    }
+
+    /// The first 8 sprites in OAM on this line, front to back, flagging an overflow if there were more
+    fn sprite_line(&self, state: &mut NesPPUState, y: u16) -> [Option<SpritePixel>; VISIBLE_WIDTH] {
+        let mut line = [None; VISIBLE_WIDTH];
+        let height = state.sprite_height();
+        let mut found = 0;
+        let mut overflow = false;
+
+        for (number, sprite) in state.oam.chunks_exact(4).enumerate() {
+            // OAM holds the line before the sprite's top, since they are looked for a line ahead
+            let Some(row) = y
+                .checked_sub(sprite[0] as u16 + 1)
+                .filter(|row| *row < height)
+            else {
+                continue;
+            };
+
+            found += 1;
+            if found > SPRITES_PER_LINE {
+                overflow = true;
+                break;
+            }
+
+            let attributes = sprite[2];
+            let row = if attributes & 0x80 != 0 {
+                height - 1 - row
+            } else {
+                row
+            };
+            let tile = sprite[1] as u16;
+            // Tall sprites pick their pattern table with the bottom bit of the tile number
+            let pattern = if height == 16 {
+                (tile & 1) * 0x1000 + (tile & 0xfe) * 16 + (row / 8) * 16 + row % 8
+            } else {
+                let table = if state.control & CONTROL_SPRITE_TABLE != 0 {
+                    0x1000
+                } else {
+                    0
+                };
+                table + tile * 16 + row
+            };
+            let low = self.fetch(pattern);
+            let high = self.fetch(pattern + 8);
+
+            for column in 0..8 {
+                let x = sprite[3] as usize + column;
+                if x >= VISIBLE_WIDTH || line[x].is_some() {
+                    continue;
+                }
+
+                let bit = if attributes & 0x40 != 0 {
+                    column
+                } else {
+                    7 - column
+                };
+                let color = ((low >> bit) & 1) | (((high >> bit) & 1) << 1);
+                if color != 0 {
+                    line[x] = Some(SpritePixel {
+                        color: ((attributes & 0b11) << 2) | color,
+                        behind_background: attributes & 0x20 != 0,
+                        sprite_zero: number == 0,
+                    });
+                }
+            }
+        }
+
+        if overflow {
+            state.status |= STATUS_SPRITE_OVERFLOW;
+        }
+
+        line
+    }
+
+    fn render_line(&self, state: &mut NesPPUState, y: u16) {
+        let mut frame = self.frame.lock().unwrap();
+        let greyscale = if state.mask & MASK_GREYSCALE != 0 {
+            0x30
+        } else {
+            0x3f
+        };
+
+        if !state.rendering() {
+            let backdrop = self.config.palette.get(state.palette_ram[0] as usize);
+            for x in 0..VISIBLE_WIDTH {
+                frame[(x, y as usize)] = backdrop;
+            }
+            return;
+        }
+
+        let background = if state.mask & MASK_BACKGROUND != 0 {
+            self.background_line(state)
+        } else {
+            [0; VISIBLE_WIDTH]
+        };
+        let sprites = if state.mask & MASK_SPRITES != 0 {
+            self.sprite_line(state, y)
+        } else {
+            [None; VISIBLE_WIDTH]
+        };
+
+        for x in 0..VISIBLE_WIDTH {
+            let left = x < 8;
+            let background = if left && state.mask & MASK_BACKGROUND_LEFT == 0 {
+                0
+            } else {
+                background[x]
+            };
+            let sprite = sprites[x].filter(|_| !left || state.mask & MASK_SPRITES_LEFT != 0);
+
+            if let Some(sprite) = sprite {
+                // The last column never registers a hit
+                if sprite.sprite_zero && background != 0 && x != VISIBLE_WIDTH - 1 {
+                    state.status |= STATUS_SPRITE_ZERO_HIT;
+                }
+            }
+
+            let index = match sprite {
+                Some(sprite) if background == 0 || !sprite.behind_background => {
+                    0x10 | sprite.color as usize
+                }
+                _ => background as usize,
+            };
+
+            frame[(x, y as usize)] = self
+                .config
+                .palette
+                .get((state.palette_ram[index] & greyscale) as usize);
+        }
+
+        // Down a line, and back to the left edge the scroll registers say
+        state.increment_y();
+        state.vram_address =
+            (state.vram_address & !HORIZONTAL_BITS) | (state.temporary_address & HORIZONTAL_BITS);
+    }
+}
 
 impl Component for NesPPU {
-    fn set_memory_translation_table(&self, _memory_translation_table: Arc<MemoryTranslationTable>) {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let oam = std::mem::take(&mut state.oam);
+        let palette_ram = state.palette_ram;
+        let nmi = state.nmi;
+
+        // Reset leaves OAM and the palette alone
+        *state = NesPPUState {
+            oam,
+            palette_ram,
+            nmi,
+            ..Default::default()
+        };
+        self.update_nmi(&mut state);
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let mut state_guard = self.state.lock().unwrap();
+        let nmi = state_guard.nmi;
+
+        *state_guard = rmpv::ext::from_value(state).unwrap();
+        // Tell the connection about the loaded level
+        let raised = state_guard.nmi;
+        state_guard.nmi = nmi;
+        self.nmi.update(&mut state_guard.nmi, raised);
+    }
+
+    fn set_memory_translation_table(&self, memory_translation_table: Arc<MemoryTranslationTable>) {
+        let _ = self.memory_translation_table.set(memory_translation_table);
+    }
+}
+
+impl LightSource for NesPPU {
+    fn beam_position(&self) -> Option<BeamPosition> {
+        let state = self.state.lock().unwrap();
+
+        // Lines are drawn all at once, so the beam is always at the start of one
+        (state.line < VISIBLE_LINES && state.rendering()).then_some(BeamPosition {
+            scanline: state.line,
+            dot: 0,
+        })
+    }
+
+    fn luminance(&self, x: u16, y: u16) -> f32 {
+        let color = self.frame.lock().unwrap()[(x as usize, y as usize)];
+
+        (color.red as f32 + color.green as f32 + color.blue as f32) / (3.0 * 255.0)
     }
 }
 
 impl FromConfig for NesPPU {
-    type Config = ();
+    type Config = NesPPUConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let nmi = InterruptOutput::new(config.nmi.take());
 
-    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
         component_builder
-            .set_component(Self {})
+            .set_component(Self {
+                config,
+                state: Mutex::default(),
+                frame: Mutex::new(DMatrix::from_element(
+                    VISIBLE_WIDTH,
+                    VISIBLE_LINES as usize,
+                    Srgba::default(),
+                )),
+                output: FrameOutput::default(),
+                nmi,
+                memory_translation_table: OnceLock::new(),
+            })
+            .set_schedulable(NES_NTSC_LINE_RATE, [], [])
             // Claim our registers
             .set_memory([
                 (NES_CPU_ADDRESS_SPACE_ID, 0x2000..0x2008),
                 (NES_CPU_ADDRESS_SPACE_ID, 0x4014..0x4015),
-            ]);
+            ])
+            .set_display();
+    }
+}
+
+impl SchedulableComponent for NesPPU {
+    fn run(&self, period: u64) {
+        let mut state = self.state.lock().unwrap();
+
+        for _ in 0..period {
+            let line = state.line;
+
+            match line {
+                0..VISIBLE_LINES => self.render_line(&mut state, line),
+                VISIBLE_LINES => self.output.present(&self.frame.lock().unwrap()),
+                VBLANK_LINE => {
+                    state.status |= STATUS_VBLANK;
+                    self.update_nmi(&mut state);
+                }
+                PRE_RENDER_LINE => {
+                    state.status &=
+                        !(STATUS_VBLANK | STATUS_SPRITE_ZERO_HIT | STATUS_SPRITE_OVERFLOW);
+                    self.update_nmi(&mut state);
+
+                    // Both halves of the scroll go back into the VRAM address ready for the top of the frame
+                    if state.rendering() {
+                        state.vram_address = state.temporary_address;
+                    }
+                }
+                _ => {}
+            }
+
+            state.line = (line + 1) % LINES;
+        }
+    }
+}
+
+impl DisplayComponent for NesPPU {
+    fn set_display_data(&self, initialization_data: DisplayComponentInitializationData) {
+        self.output.set_display_data(
+            initialization_data,
+            Vector2::new(VISIBLE_WIDTH, VISIBLE_LINES as usize),
+            self.config.palette.get(0x0f),
+        );
+    }
+
+    fn get_framebuffer(&self) -> DisplayComponentFramebuffer {
+        self.output.get_framebuffer()
+    }
+
+    fn refresh_rate(&self) -> Option<Ratio<u64>> {
+        Some(NES_NTSC_FRAME_RATE)
     }
 }
 
@@ -57,21 +706,10 @@ impl MemoryComponent for NesPPU {
         address: usize,
         buffer: &mut [u8],
         _address_space: AddressSpaceId,
-        errors: &mut rangemap::RangeMap<usize, ReadMemoryRecord>,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
     ) {
-        match address {
-            PPUCTRL_ADDRESS => {}
-            PPUMASK_ADDRESS => {}
-            PPUSTATUS_ADDRESS => {}
-            OAMADDR_ADDRESS => {}
-            OamData::ADDRESS => {}
-            PPUSCROLL_ADDRESS => {}
-            PPUADDR_ADDRESS => {}
-            PPUDATA_ADDRESS => {}
-            OAMDMA_ADDRESS => {}
-            _ => {
-                unreachable!()
-            }
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = self.read(address, true);
         }
     }
 
@@ -80,21 +718,96 @@ impl MemoryComponent for NesPPU {
         address: usize,
         buffer: &[u8],
         _address_space: AddressSpaceId,
-        errors: &mut rangemap::RangeMap<usize, WriteMemoryRecord>,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
     ) {
-        match address {
-            PPUCTRL_ADDRESS => {}
-            PPUMASK_ADDRESS => {}
-            PPUSTATUS_ADDRESS => {}
-            OAMADDR_ADDRESS => {}
-            OamData::ADDRESS => {}
-            PPUSCROLL_ADDRESS => {}
-            PPUADDR_ADDRESS => {}
-            PPUDATA_ADDRESS => {}
-            OAMDMA_ADDRESS => {}
-            _ => {
-                unreachable!()
-            }
+        for (address, byte) in (address..).zip(buffer.iter()) {
+            self.write(address, *byte);
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        for (address, byte) in (address..).zip(buffer.iter_mut()) {
+            *byte = self.read(address, false);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn background_and_vblank() {
+        let builder = TestMachineBuilder::new()
+            .bus(NES_CPU_ADDRESS_SPACE_ID, 16)
+            .bus(NES_PPU_ADDRESS_SPACE_ID, 16)
+            .scratch_ram(NES_PPU_ADDRESS_SPACE_ID, 0x0000..0x3000, 0);
+        let nmi = builder.interrupt_line("nmi");
+        let (builder, ppu) = builder.component::<NesPPU>(NesPPUConfig {
+            palette: Palette::from_bytes(&NES_DEFAULT_PALETTE, 64).unwrap(),
+            nmi: Some(Arc::new(nmi)),
+            wait_states: None,
+        });
+        let machine = builder.build();
+        let write_vram = |address: u16, data: &[u8]| {
+            let [low, high] = address.to_le_bytes();
+            machine.load(NES_CPU_ADDRESS_SPACE_ID, PPUADDR_ADDRESS, &[high]);
+            machine.load(NES_CPU_ADDRESS_SPACE_ID, PPUADDR_ADDRESS, &[low]);
+            for byte in data {
+                machine.load(NES_CPU_ADDRESS_SPACE_ID, PPUDATA_ADDRESS, &[*byte]);
+            }
+        };
+
+        // Tile 1 has its top left pixel in color 1, and is the first tile of the nametable
+        write_vram(0x0010, &[0x80]);
+        write_vram(0x2000, &[0x01]);
+        // Black backdrop, white for color 1
+        write_vram(0x3f00, &[0x0f, 0x30]);
+        // Back to the top left for rendering
+        write_vram(0x0000, &[]);
+        machine.load(NES_CPU_ADDRESS_SPACE_ID, PPUCTRL_ADDRESS, &[CONTROL_NMI]);
+        machine.load(
+            NES_CPU_ADDRESS_SPACE_ID,
+            PPUMASK_ADDRESS,
+            &[MASK_BACKGROUND | MASK_BACKGROUND_LEFT],
+        );
+
+        machine.run_component::<NesPPU>(ppu, VBLANK_LINE as u64 + 1);
+
+        let component = machine.component::<NesPPU>(ppu);
+        let palette = &component.config.palette;
+        let frame = component.frame.lock().unwrap();
+        assert_eq!(frame[(0, 0)], palette.get(0x30));
+        assert_eq!(frame[(1, 0)], palette.get(0x0f));
+        assert_eq!(frame[(0, 1)], palette.get(0x0f));
+        drop(frame);
+
+        assert!(component.nmi());
+        assert_eq!(
+            machine.peek(NES_CPU_ADDRESS_SPACE_ID, PPUSTATUS_ADDRESS, 1)[0] & STATUS_VBLANK,
+            STATUS_VBLANK
+        );
+        component.read(PPUSTATUS_ADDRESS, true);
+        assert!(!component.nmi());
+        assert_eq!(machine.take_interrupts().len(), 2);
+
+        // Reads go through the buffer, except for the palette
+        write_vram(0x2000, &[]);
+        assert_eq!(
+            [
+                component.read(PPUDATA_ADDRESS, true),
+                component.read(PPUDATA_ADDRESS, true)
+            ],
+            [0x00, 0x01]
+        );
+        write_vram(0x3f01, &[]);
+        assert_eq!(component.read(PPUDATA_ADDRESS, true), 0x30);
+    }
+}
@@ -0,0 +1,175 @@
+//! Harness for blargg's test ROMs, which report their results through memory at $6000
+//!
+//! They aren't shipped with the repository, point MULTIEMU_NES_TEST_ROMS at a folder with the suites unpacked
+//! in it and run `cargo test -- --ignored`
+
+use super::NES_CPU_ADDRESS_SPACE_ID;
+use crate::{
+    machine::Machine,
+    rom::{
+        id::RomId,
+        manager::RomManager,
+        system::{GameSystem, NintendoSystem},
+    },
+};
+use std::{
+    fmt::Display,
+    fs::{read_dir, File},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const TEST_ROM_DIRECTORY_VARIABLE: &str = "MULTIEMU_NES_TEST_ROMS";
+const STATUS_ADDRESS: usize = 0x6000;
+/// Written after the status byte once the ROM has started reporting
+const SIGNATURE_ADDRESS: usize = 0x6001;
+const SIGNATURE: [u8; 3] = [0xde, 0xb0, 0x61];
+const TEXT_ADDRESS: usize = 0x6004;
+const STATUS_RUNNING: u8 = 0x80;
+const STATUS_RESET_REQUESTED: u8 = 0x81;
+const PASSED: u8 = 0x00;
+/// The ROMs want at least 100 milliseconds before the reset button is pressed
+const RESET_DELAY_FRAMES: u64 = 6;
+/// An emulated minute, longer than any of the suites take on hardware
+const FRAME_LIMIT: u64 = 60 * 60;
+const MAX_TEXT_LENGTH: usize = 0x1000;
+
+#[derive(Debug, PartialEq, Eq)]
+enum TestRomResult {
+    Passed,
+    Failed {
+        code: u8,
+        message: String,
+    },
+    /// Never wrote the signature, or never finished
+    TimedOut {
+        message: String,
+    },
+}
+
+impl Display for TestRomResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestRomResult::Passed => write!(f, "Passed"),
+            TestRomResult::Failed { code, message } => {
+                write!(f, "Failed with code {}: {}", code, message.trim())
+            }
+            TestRomResult::TimedOut { message } => write!(f, "Timed out: {}", message.trim()),
+        }
+    }
+}
+
+fn read_byte(machine: &Machine, address: usize) -> u8 {
+    let mut buffer = [0];
+    let _ =
+        machine
+            .memory_translation_table
+            .preview(address, &mut buffer, NES_CPU_ADDRESS_SPACE_ID);
+    buffer[0]
+}
+
+fn read_text(machine: &Machine) -> String {
+    let text: Vec<u8> = (TEXT_ADDRESS..TEXT_ADDRESS + MAX_TEXT_LENGTH)
+        .map(|address| read_byte(machine, address))
+        .take_while(|byte| *byte != 0)
+        .collect();
+
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+fn run_test_rom(path: &Path) -> TestRomResult {
+    let rom_manager = Arc::new(RomManager::new(None).unwrap());
+    let rom_id = RomId::from_read(&mut File::open(path).unwrap());
+    rom_manager.rom_paths.insert(rom_id, path.to_path_buf());
+
+    let mut machine = Machine::from_system(
+        vec![rom_id],
+        rom_manager,
+        GameSystem::Nintendo(NintendoSystem::NintendoEntertainmentSystem),
+    );
+    let mut reset_at = None;
+
+    for frame in 0..FRAME_LIMIT {
+        machine.run();
+
+        if reset_at == Some(frame) {
+            reset_at = None;
+
+            for component_table in machine.component_store.components() {
+                component_table.component.reset();
+            }
+        }
+
+        let signature = [0, 1, 2].map(|offset| read_byte(&machine, SIGNATURE_ADDRESS + offset));
+        if signature != SIGNATURE {
+            continue;
+        }
+
+        match read_byte(&machine, STATUS_ADDRESS) {
+            STATUS_RUNNING => {}
+            STATUS_RESET_REQUESTED => {
+                reset_at.get_or_insert(frame + RESET_DELAY_FRAMES);
+            }
+            PASSED => return TestRomResult::Passed,
+            code => {
+                return TestRomResult::Failed {
+                    code,
+                    message: read_text(&machine),
+                }
+            }
+        }
+    }
+
+    TestRomResult::TimedOut {
+        message: read_text(&machine),
+    }
+}
+
+/// Runs every ROM in a suite's folder and fails with a summary if any of them did
+fn run_suite(suite: &str) {
+    let Some(directory) = std::env::var_os(TEST_ROM_DIRECTORY_VARIABLE) else {
+        panic!(
+            "Set {} to the folder blargg's test ROMs are in",
+            TEST_ROM_DIRECTORY_VARIABLE
+        );
+    };
+
+    let mut roms: Vec<PathBuf> = read_dir(PathBuf::from(directory).join(suite))
+        .unwrap()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "nes"))
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty(), "No ROMs found for {}", suite);
+
+    let failures: Vec<_> = roms
+        .iter()
+        .filter_map(|rom| {
+            let result = run_test_rom(rom);
+            tracing::info!("{}: {}", rom.display(), result);
+
+            (result != TestRomResult::Passed).then(|| format!("{}: {}", rom.display(), result))
+        })
+        .collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+#[ignore = "needs blargg's test ROMs"]
+fn cpu_interrupts() {
+    run_suite("cpu_interrupts_v2/rom_singles");
+}
+
+#[test]
+#[ignore = "needs blargg's test ROMs"]
+fn ppu_vbl_nmi() {
+    run_suite("ppu_vbl_nmi/rom_singles");
+}
+
+#[test]
+#[ignore = "needs blargg's test ROMs"]
+fn instruction_timing() {
+    run_suite("instr_timing/rom_singles");
+}
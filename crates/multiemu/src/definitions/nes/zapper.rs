@@ -0,0 +1,198 @@
+use super::ppu::NesPPU;
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        Component, ComponentId, FromConfig,
+    },
+    input::{manager::InputManager, pointer::PointerInput, EmulatedGamepadId, Input, InputState},
+    machine::ComponentBuilder,
+};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, OnceLock},
+};
+use strum::IntoEnumIterator;
+
+pub const NES_ZAPPER_GAMEPAD_TYPE: EmulatedGamepadTypeId = EmulatedGamepadTypeId::new("NES Zapper");
+
+const VISIBLE_WIDTH: u16 = 256;
+const VISIBLE_HEIGHT: u16 = 240;
+/// Scanlines the photodiode keeps reporting light after the beam passes, the sensor is slow to let go
+const LIGHT_SCANLINES: u16 = 20;
+/// How bright the aimed at pixel must be, only near white targets trip the sensor
+const LIGHT_THRESHOLD: f32 = 0.85;
+
+/// Set when the photodiode does not see light
+const LIGHT_NOT_SENSED: u8 = 0b0000_1000;
+const TRIGGER_PULLED: u8 = 0b0001_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct BeamPosition {
+    pub scanline: u16,
+    pub dot: u16,
+}
+
+/// What the photodiode is looking at, the PPU in a real machine
+pub(super) trait LightSource: Debug + Send + Sync {
+    /// Where the PPU is drawing right now, None during vblank or while rendering is off
+    fn beam_position(&self) -> Option<BeamPosition>;
+    /// Brightness of a pixel drawn this frame, from 0.0 to 1.0
+    fn luminance(&self, x: u16, y: u16) -> f32;
+}
+
+/// If the photodiode aimed at a pixel sees light right now
+///
+/// The beam has to have drawn the pixel already, and recently enough that the sensor hasn't decayed, which is why
+/// games flash targets for exactly a frame
+pub(super) fn light_sensed(light_source: &dyn LightSource, x: u16, y: u16) -> bool {
+    let Some(beam) = light_source.beam_position() else {
+        return false;
+    };
+
+    let drawn = beam.scanline > y || (beam.scanline == y && beam.dot > x);
+
+    drawn && beam.scanline < y + LIGHT_SCANLINES && light_source.luminance(x, y) >= LIGHT_THRESHOLD
+}
+
+#[derive(Debug)]
+pub(super) struct ZapperConfig {
+    /// The PPU the Zapper gets its light from
+    pub ppu: ComponentId,
+}
+
+/// Light gun for the second controller port, aimed with a pointer
+#[derive(Debug)]
+pub(super) struct Zapper {
+    light_source: Option<Arc<dyn LightSource>>,
+    input_manager: OnceLock<(Arc<InputManager>, EmulatedGamepadId)>,
+}
+
+impl Zapper {
+    /// Bits 3 and 4 of the port register, everything else is left clear
+    pub fn report(&self) -> u8 {
+        let Some((input_manager, gamepad_id)) = self.input_manager.get() else {
+            return LIGHT_NOT_SENSED;
+        };
+        let input = |input| input_manager.get_input(*gamepad_id, Input::Pointer(input));
+
+        let mut report = 0;
+
+        if input(PointerInput::Primary).as_digital() {
+            report |= TRIGGER_PULLED;
+        }
+
+        let aimed_at_light = input(PointerInput::OnScreen).as_digital()
+            && self.light_source.as_ref().is_some_and(|light_source| {
+                let x = (input(PointerInput::X).as_analog() * VISIBLE_WIDTH as f32) as u16;
+                let y = (input(PointerInput::Y).as_analog() * VISIBLE_HEIGHT as f32) as u16;
+
+                light_sensed(
+                    light_source.as_ref(),
+                    x.min(VISIBLE_WIDTH - 1),
+                    y.min(VISIBLE_HEIGHT - 1),
+                )
+            });
+
+        if !aimed_at_light {
+            report |= LIGHT_NOT_SENSED;
+        }
+
+        report
+    }
+}
+
+impl Component for Zapper {}
+
+impl FromConfig for Zapper {
+    type Config = ZapperConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let light_source = component_builder
+            .machine()
+            .get_component::<NesPPU>(config.ppu)
+            .map(|ppu| ppu as Arc<dyn LightSource>);
+
+        component_builder
+            .set_component(Self {
+                light_source,
+                input_manager: OnceLock::default(),
+            })
+            .set_input(
+                [(
+                    NES_ZAPPER_GAMEPAD_TYPE,
+                    EmulatedGamepadMetadata {
+                        present_inputs: PointerInput::iter().map(Input::Pointer).collect(),
+                        default_bindings: default_bindings(),
+                    },
+                )],
+                [NES_ZAPPER_GAMEPAD_TYPE],
+            );
+    }
+}
+
+impl InputComponent for Zapper {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.input_manager
+            .set((input_manager, gamepad_ids[0]))
+            .expect("Input manager set multiple times");
+    }
+}
+
+fn default_bindings() -> HashMap<Input, Input> {
+    PointerInput::iter()
+        .map(|input| (Input::Pointer(input), Input::Pointer(input)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// White square at 100, 100 on a black screen
+    #[derive(Debug)]
+    struct Target {
+        beam: Mutex<Option<BeamPosition>>,
+    }
+
+    impl LightSource for Target {
+        fn beam_position(&self) -> Option<BeamPosition> {
+            *self.beam.lock().unwrap()
+        }
+
+        fn luminance(&self, x: u16, y: u16) -> f32 {
+            if (96..104).contains(&x) && (96..104).contains(&y) {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    }
+
+    #[test]
+    fn photodiode_timing() {
+        let target = Target {
+            beam: Mutex::new(None),
+        };
+        let sensed_at = |scanline, dot| {
+            *target.beam.lock().unwrap() = Some(BeamPosition { scanline, dot });
+            light_sensed(&target, 100, 100)
+        };
+
+        // Not drawn yet
+        assert!(!sensed_at(50, 0));
+        assert!(!sensed_at(100, 90));
+        // Just drawn and for a while after
+        assert!(sensed_at(100, 120));
+        assert!(sensed_at(110, 0));
+        // The sensor has let go
+        assert!(!sensed_at(100 + LIGHT_SCANLINES, 0));
+        // Aiming at something dark never works
+        assert!(!light_sensed(&target, 10, 10));
+    }
+}
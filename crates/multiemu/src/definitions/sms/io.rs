@@ -0,0 +1,420 @@
+use super::{SmsRegion, SMS_IO_ADDRESS_SPACE_ID};
+use crate::{
+    component::{
+        input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
+        memory::MemoryComponent,
+        schedulable::SchedulableComponent,
+        Component, ComponentId, FromConfig,
+    },
+    definitions::misc::{
+        audio::sn76489::Sn76489,
+        io::{InterruptConnection, InterruptOutput},
+        video::tms9918::Tms9918,
+    },
+    input::{
+        gamepad::GamepadInput, keyboard::KeyboardInput, manager::InputManager, EmulatedGamepadId,
+        Input,
+    },
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+pub const SMS_CONTROLLER_GAMEPAD_TYPE: EmulatedGamepadTypeId =
+    EmulatedGamepadTypeId::new("Master System Controller");
+
+/// Buttons in the order the controller port pins report them, low while held
+const CONTROLLER_BUTTONS: [GamepadInput; 6] = [
+    GamepadInput::DPadUp,
+    GamepadInput::DPadDown,
+    GamepadInput::DPadLeft,
+    GamepadInput::DPadRight,
+    GamepadInput::FPadDown,
+    GamepadInput::FPadRight,
+];
+/// The pause and reset buttons are on the console, they are read from the first controller so players can reach them
+const PAUSE_BUTTON: GamepadInput = GamepadInput::Start;
+const RESET_BUTTON: GamepadInput = GamepadInput::Select;
+
+/// Set in port 0x3e while the I/O chip is switched off, leaving the controller ports floating
+const IO_DISABLED: u8 = 0b0000_0100;
+
+#[derive(Debug)]
+pub(super) struct SmsIoConfig {
+    pub region: SmsRegion,
+    pub vdp: ComponentId,
+    pub psg: ComponentId,
+    /// The NMI input of the processor, which the pause button pulls
+    pub nmi: Option<Arc<dyn InterruptConnection>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SmsIoState {
+    /// Port 0x3e, which parts of the machine are switched on
+    memory_control: u8,
+    /// Port 0x3f, the direction and output level of the TR and TH pins of both controller ports
+    io_control: u8,
+    nmi: bool,
+}
+
+/// The port decoding and I/O chip of the Master System
+///
+/// Ports are decoded from A7, A6 and A0 alone, so every port is mirrored across a quarter of the port space: memory
+/// and I/O control, then the V and H counters on read and the PSG on write, then the VDP, then the controllers
+#[derive(Debug)]
+pub(super) struct SmsIo {
+    region: SmsRegion,
+    vdp: Arc<Tms9918>,
+    psg: Arc<Sn76489>,
+    state: Mutex<SmsIoState>,
+    input_manager: OnceLock<(Arc<InputManager>, Vec<EmulatedGamepadId>)>,
+    nmi: InterruptOutput,
+}
+
+impl SmsIo {
+    fn held(&self, gamepad_index: usize, button: GamepadInput) -> bool {
+        let Some((input_manager, gamepad_ids)) = self.input_manager.get() else {
+            return false;
+        };
+
+        gamepad_ids.get(gamepad_index).is_some_and(|gamepad_id| {
+            input_manager
+                .get_input(*gamepad_id, Input::Gamepad(button))
+                .as_digital()
+        })
+    }
+
+    /// Level of an output pin of port 0x3f as read back, with `pin` being 0 for TR and 1 for TH
+    fn output_pin(&self, state: &SmsIoState, controller: usize, pin: usize) -> Option<bool> {
+        let direction = 1 << (controller * 2 + pin);
+        let level = state.io_control & (direction << 4) != 0;
+
+        if state.io_control & direction != 0 {
+            return None;
+        }
+
+        // Japanese consoles read TH back the other way around, which is how games tell them apart
+        Some(if pin == 1 && self.region == SmsRegion::Japan {
+            !level
+        } else {
+            level
+        })
+    }
+
+    /// Port 0xdc, the first controller and the up and down of the second
+    fn port_a(&self, state: &SmsIoState) -> u8 {
+        let mut value = 0xff;
+
+        for (bit, button) in CONTROLLER_BUTTONS.into_iter().enumerate() {
+            if self.held(0, button) {
+                value &= !(1 << bit);
+            }
+        }
+        for (bit, button) in CONTROLLER_BUTTONS[..2].iter().enumerate() {
+            if self.held(1, *button) {
+                value &= !(1 << (bit + 6));
+            }
+        }
+        if let Some(level) = self.output_pin(state, 0, 0) {
+            value = (value & !0b0010_0000) | ((level as u8) << 5);
+        }
+
+        value
+    }
+
+    /// Port 0xdd, the rest of the second controller, the reset button and both TH pins
+    fn port_b(&self, state: &SmsIoState) -> u8 {
+        let mut value = 0xff;
+
+        for (bit, button) in CONTROLLER_BUTTONS[2..].iter().enumerate() {
+            if self.held(1, *button) {
+                value &= !(1 << bit);
+            }
+        }
+        if self.held(0, RESET_BUTTON) {
+            value &= !0b0001_0000;
+        }
+        if let Some(level) = self.output_pin(state, 1, 0) {
+            value = (value & !0b0000_1000) | ((level as u8) << 3);
+        }
+        for controller in 0..2 {
+            if let Some(level) = self.output_pin(state, controller, 1) {
+                value =
+                    (value & !(0b0100_0000 << controller)) | ((level as u8) << (6 + controller));
+            }
+        }
+
+        value
+    }
+
+    fn read(&self, address: usize) -> u8 {
+        let state = self.state.lock().unwrap();
+        let odd = address & 1 != 0;
+
+        match address & 0xc0 {
+            // Control ports are write only
+            0x00 => 0xff,
+            0x40 if odd => self.vdp.h_counter(),
+            0x40 => self.vdp.v_counter(),
+            _ if state.memory_control & IO_DISABLED != 0 => 0xff,
+            _ if odd => self.port_b(&state),
+            _ => self.port_a(&state),
+        }
+    }
+}
+
+impl Component for SmsIo {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let nmi = state.nmi;
+
+        // Every pin starts out as an input
+        *state = SmsIoState {
+            io_control: 0xff,
+            nmi,
+            ..Default::default()
+        };
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        let mut state_guard = self.state.lock().unwrap();
+        let nmi = state_guard.nmi;
+
+        *state_guard = rmpv::ext::from_value(state).unwrap();
+        // Tell the connection about the loaded level
+        let raised = state_guard.nmi;
+        state_guard.nmi = nmi;
+        self.nmi.update(&mut state_guard.nmi, raised);
+    }
+}
+
+impl FromConfig for SmsIo {
+    type Config = SmsIoConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, mut config: Self::Config) {
+        let vdp = component_builder
+            .machine()
+            .get_component::<Tms9918>(config.vdp)
+            .expect("Master System VDP is missing");
+        let psg = component_builder
+            .machine()
+            .get_component::<Sn76489>(config.psg)
+            .expect("Master System PSG is missing");
+        let nmi = InterruptOutput::new(config.nmi.take());
+        // The pause button is looked at once a frame, which is as often as any game could notice it
+        let frame_rate = config.region.video().frame_rate();
+
+        component_builder
+            .set_component(Self {
+                region: config.region,
+                vdp,
+                psg,
+                state: Mutex::new(SmsIoState {
+                    io_control: 0xff,
+                    ..Default::default()
+                }),
+                input_manager: OnceLock::default(),
+                nmi,
+            })
+            .set_schedulable(frame_rate, [], [])
+            .set_memory([
+                (SMS_IO_ADDRESS_SPACE_ID, 0x00..0x80),
+                (SMS_IO_ADDRESS_SPACE_ID, 0xc0..0x100),
+            ])
+            .set_input(
+                [(
+                    SMS_CONTROLLER_GAMEPAD_TYPE,
+                    EmulatedGamepadMetadata {
+                        present_inputs: present_inputs(),
+                        default_bindings: default_bindings(),
+                    },
+                )],
+                std::iter::repeat_n(SMS_CONTROLLER_GAMEPAD_TYPE, 2),
+            );
+    }
+}
+
+impl SchedulableComponent for SmsIo {
+    fn run(&self, _period: u64) {
+        let mut state = self.state.lock().unwrap();
+        let pressed = self.held(0, PAUSE_BUTTON);
+
+        // The NMI only fires on the edge, so holding pause doesn't keep pausing
+        self.nmi.update(&mut state.nmi, pressed);
+    }
+}
+
+impl InputComponent for SmsIo {
+    fn set_input_manager(
+        &self,
+        input_manager: Arc<InputManager>,
+        gamepad_ids: &[EmulatedGamepadId],
+    ) {
+        self.input_manager
+            .set((input_manager, gamepad_ids.to_vec()))
+            .expect("Input manager set multiple times");
+    }
+}
+
+impl MemoryComponent for SmsIo {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address + offset);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        for (address, value) in (address..).zip(buffer.iter().copied()) {
+            match address & 0xc1 {
+                0x00 => self.state.lock().unwrap().memory_control = value,
+                0x01 => self.state.lock().unwrap().io_control = value,
+                0x40 | 0x41 => self.psg.write(value),
+                // The controller ports can't be written
+                _ => {}
+            }
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        // Reading has no side effects on any of these ports
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address + offset);
+        }
+    }
+}
+
+fn present_inputs() -> HashSet<Input> {
+    CONTROLLER_BUTTONS
+        .into_iter()
+        .chain([PAUSE_BUTTON, RESET_BUTTON])
+        .map(Input::Gamepad)
+        .collect()
+}
+
+fn default_bindings() -> HashMap<Input, Input> {
+    present_inputs()
+        .into_iter()
+        .map(|input| (input, input))
+        .chain([
+            (
+                Input::Keyboard(KeyboardInput::KeyZ),
+                Input::Gamepad(GamepadInput::FPadDown),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::KeyX),
+                Input::Gamepad(GamepadInput::FPadRight),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::Enter),
+                Input::Gamepad(PAUSE_BUTTON),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::Backspace),
+                Input::Gamepad(RESET_BUTTON),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ArrowUp),
+                Input::Gamepad(GamepadInput::DPadUp),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ArrowDown),
+                Input::Gamepad(GamepadInput::DPadDown),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ArrowLeft),
+                Input::Gamepad(GamepadInput::DPadLeft),
+            ),
+            (
+                Input::Keyboard(KeyboardInput::ArrowRight),
+                Input::Gamepad(GamepadInput::DPadRight),
+            ),
+        ])
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        definitions::misc::{
+            audio::sn76489::{Sn76489Config, Sn76489Kind},
+            video::tms9918::{Tms9918Config, Tms9918Variant, TMS9918_DEFAULT_PALETTE},
+        },
+        machine::test_machine::TestMachineBuilder,
+        runtime::color::Palette,
+    };
+
+    #[test]
+    fn region_detection() {
+        for (region, th_levels) in [(SmsRegion::NorthAmerica, 0xc0), (SmsRegion::Japan, 0x00)] {
+            let builder = TestMachineBuilder::new().bus(SMS_IO_ADDRESS_SPACE_ID, 8);
+            let (builder, vdp) = builder.component::<Tms9918>(Tms9918Config {
+                variant: Tms9918Variant::Sms,
+                region: region.video(),
+                palette: Palette::from_bytes(&TMS9918_DEFAULT_PALETTE, 16).unwrap(),
+                assigned_address_space: SMS_IO_ADDRESS_SPACE_ID,
+                assigned_range: 0x80..0xc0,
+                irq: None,
+            });
+            let (builder, psg) = builder.component::<Sn76489>(Sn76489Config {
+                kind: Sn76489Kind::Sega,
+                frequency: region.psg_frequency(),
+                assigned_ports: None,
+            });
+            let (builder, _) = builder.component::<SmsIo>(SmsIoConfig {
+                region,
+                vdp,
+                psg,
+                nmi: None,
+            });
+            let machine = builder.build();
+
+            // Nothing held and every pin an input
+            assert_eq!(machine.peek(SMS_IO_ADDRESS_SPACE_ID, 0xdc, 2), [0xff, 0xff]);
+            assert_eq!(machine.peek(SMS_IO_ADDRESS_SPACE_ID, 0x7e, 1), [0x00]);
+
+            // Both TH pins driven high, then low, which is what games check the region with
+            machine.load(SMS_IO_ADDRESS_SPACE_ID, 0x3f, &[0xf5]);
+            assert_eq!(
+                machine.peek(SMS_IO_ADDRESS_SPACE_ID, 0xdd, 1)[0] & 0xc0,
+                th_levels
+            );
+            machine.load(SMS_IO_ADDRESS_SPACE_ID, 0x3f, &[0x55]);
+            assert_eq!(
+                machine.peek(SMS_IO_ADDRESS_SPACE_ID, 0xdd, 1)[0] & 0xc0,
+                th_levels ^ 0xc0
+            );
+
+            // Switching the I/O chip off leaves the ports floating
+            machine.load(SMS_IO_ADDRESS_SPACE_ID, 0x3e, &[IO_DISABLED]);
+            assert_eq!(machine.peek(SMS_IO_ADDRESS_SPACE_ID, 0xc1, 1), [0xff]);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use super::SMS_MEMORY_ADDRESS_SPACE_ID;
+use crate::{
+    component::{memory::MemoryComponent, Component, FromConfig},
+    machine::ComponentBuilder,
+    memory::{AddressSpaceId, PreviewMemoryRecord, ReadMemoryRecord, WriteMemoryRecord},
+    rom::{handle::RomHandle, id::RomId, manager::RomRequirement},
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+const BANK_SIZE: usize = 0x4000;
+const CARTRIDGE_RAM_SIZE: usize = 0x8000;
+/// Copiers put a header this big in front of the ROM, which is told apart by not being a whole number of banks
+const COPIER_HEADER_SIZE: usize = 0x200;
+/// The first kilobyte stays on the first bank so the interrupt vectors can't be paged out
+const FIXED_SIZE: usize = 0x400;
+
+const CONTROL_REGISTER: usize = 0xfffc;
+const REGISTERS_END: usize = 0x10000;
+
+#[derive(Debug)]
+pub(super) struct SegaMapperConfig {
+    pub rom: RomId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SegaMapperState {
+    /// Cartridge RAM enable and bank, from 0xfffc
+    control: u8,
+    /// ROM banks in the three slots, from 0xfffd to 0xffff
+    banks: [u8; 3],
+    cartridge_ram: Vec<u8>,
+}
+
+impl Default for SegaMapperState {
+    fn default() -> Self {
+        Self {
+            control: 0,
+            banks: [0, 1, 2],
+            cartridge_ram: vec![0; CARTRIDGE_RAM_SIZE],
+        }
+    }
+}
+
+impl SegaMapperState {
+    /// Where in cartridge RAM an access to the third slot goes, if RAM is paged in there
+    fn cartridge_ram_offset(&self, address: usize) -> Option<usize> {
+        (self.control & 0b1000 != 0 && (0x8000..0xc000).contains(&address)).then(|| {
+            let bank = (self.control >> 2) as usize & 1;
+            bank * BANK_SIZE + address - 0x8000
+        })
+    }
+}
+
+/// The mapper in nearly every Master System cartridge, paging 16KB ROM banks into three slots through registers at
+/// the top of memory
+///
+/// The registers sit over the top of the work RAM mirror. On hardware writes there go to both, here they only reach
+/// the registers, so a game reading the RAM back at 0xdffc sees what it had there before
+#[derive(Debug)]
+pub(super) struct SegaMapper {
+    /// Missing if the ROM could not be found, in which case it reads as open bus
+    rom: Option<RomHandle>,
+    /// Where the ROM starts past any copier header
+    rom_offset: usize,
+    bank_count: usize,
+    state: Mutex<SegaMapperState>,
+}
+
+impl SegaMapper {
+    fn read(&self, state: &SegaMapperState, address: usize) -> u8 {
+        if address >= CONTROL_REGISTER {
+            return match address - CONTROL_REGISTER {
+                0 => state.control,
+                slot => state.banks[slot - 1],
+            };
+        }
+
+        if let Some(offset) = state.cartridge_ram_offset(address) {
+            return state.cartridge_ram[offset];
+        }
+
+        let bank = if address < FIXED_SIZE {
+            0
+        } else {
+            state.banks[address / BANK_SIZE] as usize % self.bank_count
+        };
+
+        self.rom
+            .as_ref()
+            .and_then(|rom| {
+                rom.get(self.rom_offset + bank * BANK_SIZE + address % BANK_SIZE)
+                    .copied()
+            })
+            .unwrap_or(0xff)
+    }
+}
+
+impl Component for SegaMapper {
+    fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let cartridge_ram = std::mem::take(&mut state.cartridge_ram);
+
+        // Battery backed RAM survives
+        *state = SegaMapperState {
+            cartridge_ram,
+            ..Default::default()
+        };
+    }
+
+    fn save_snapshot(&self) -> rmpv::Value {
+        rmpv::ext::to_value(&*self.state.lock().unwrap()).unwrap()
+    }
+
+    fn load_snapshot(&self, state: rmpv::Value) {
+        *self.state.lock().unwrap() = rmpv::ext::from_value(state).unwrap();
+    }
+}
+
+impl FromConfig for SegaMapper {
+    type Config = SegaMapperConfig;
+
+    fn from_config(component_builder: &mut ComponentBuilder<Self>, config: Self::Config) {
+        let rom = component_builder.open_rom(config.rom, RomRequirement::Required);
+        let rom_length = rom.as_ref().map_or(0, |rom| rom.len());
+        let rom_offset = if rom_length % BANK_SIZE == COPIER_HEADER_SIZE {
+            COPIER_HEADER_SIZE
+        } else {
+            0
+        };
+        let bank_count = (rom_length - rom_offset).div_ceil(BANK_SIZE).max(1);
+
+        component_builder
+            .set_component(Self {
+                rom,
+                rom_offset,
+                bank_count,
+                state: Mutex::default(),
+            })
+            .set_memory([
+                (SMS_MEMORY_ADDRESS_SPACE_ID, 0x0000..0xc000),
+                (SMS_MEMORY_ADDRESS_SPACE_ID, CONTROL_REGISTER..REGISTERS_END),
+            ]);
+    }
+}
+
+impl MemoryComponent for SegaMapper {
+    fn read_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, ReadMemoryRecord>,
+    ) {
+        let state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(&state, address + offset);
+        }
+    }
+
+    fn write_memory(
+        &self,
+        address: usize,
+        buffer: &[u8],
+        _address_space: AddressSpaceId,
+        errors: &mut RangeMap<usize, WriteMemoryRecord>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+
+        for (address, value) in (address..).zip(buffer.iter().copied()) {
+            if address >= CONTROL_REGISTER {
+                match address - CONTROL_REGISTER {
+                    0 => state.control = value,
+                    slot => state.banks[slot - 1] = value,
+                }
+            } else if let Some(offset) = state.cartridge_ram_offset(address) {
+                state.cartridge_ram[offset] = value;
+            } else {
+                errors.insert(address..address + 1, WriteMemoryRecord::Denied);
+            }
+        }
+    }
+
+    fn preview_memory(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        _address_space: AddressSpaceId,
+        _errors: &mut RangeMap<usize, PreviewMemoryRecord>,
+    ) {
+        let state = self.state.lock().unwrap();
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(&state, address + offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn banking_and_cartridge_ram() {
+        // 8 banks each filled with their own number, behind a copier header
+        let rom: Vec<u8> = std::iter::repeat_n(0xaa, COPIER_HEADER_SIZE)
+            .chain((0..8).flat_map(|bank| std::iter::repeat_n(bank, BANK_SIZE)))
+            .collect();
+        let rom_id = RomId::from_read(&mut rom.as_slice());
+
+        let (builder, _) = TestMachineBuilder::new()
+            .bus(SMS_MEMORY_ADDRESS_SPACE_ID, 16)
+            .map(|builder| {
+                builder.rom_manager.insert_bytes(rom_id, rom);
+                builder
+            })
+            .component::<SegaMapper>(SegaMapperConfig { rom: rom_id });
+        let machine = builder.build();
+
+        assert_eq!(machine.peek(0, 0x0000, 1), [0]);
+        assert_eq!(machine.peek(0, 0x4000, 1), [1]);
+        assert_eq!(machine.peek(0, 0x8000, 1), [2]);
+
+        // The first kilobyte stays put, and banks past the end wrap around
+        machine.load(0, 0xfffd, &[5]);
+        machine.load(0, 0xffff, &[11]);
+        assert_eq!(machine.peek(0, 0x03ff, 1), [0]);
+        assert_eq!(machine.peek(0, 0x0400, 1), [5]);
+        assert_eq!(machine.peek(0, 0x8000, 1), [3]);
+        assert_eq!(machine.peek(0, 0xffff, 1), [11]);
+
+        // The second bank of cartridge RAM over the third slot
+        machine.load(0, 0xfffc, &[0b1100]);
+        machine.load(0, 0x8000, &[0x42]);
+        assert_eq!(machine.peek(0, 0x8000, 1), [0x42]);
+        machine.load(0, 0xfffc, &[0b1000]);
+        assert_eq!(machine.peek(0, 0x8000, 1), [0x00]);
+        machine.load(0, 0xfffc, &[0b0000]);
+        assert_eq!(machine.peek(0, 0x8000, 1), [3]);
+    }
+}
@@ -0,0 +1,154 @@
+use super::misc::{
+    audio::sn76489::{Sn76489, Sn76489Config, Sn76489Kind},
+    memory::{
+        mirror::{MirrorMemory, MirrorMemoryConfig},
+        standard::{StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents},
+    },
+    processor::z80::{Z80Config, Z80Interrupt, Z80},
+    video::tms9918::{
+        Tms9918, Tms9918Config, Tms9918Region, Tms9918Variant, TMS9918_DEFAULT_PALETTE,
+    },
+};
+use crate::{
+    config::GLOBAL_CONFIG,
+    machine::{validation::MachineConfigurationError, Machine},
+    memory::AddressSpaceId,
+    rom::{
+        id::RomId,
+        info::RomInfo,
+        manager::RomManager,
+        region::RomRegion,
+        system::{GameSystem, SegaSystem},
+    },
+    runtime::color::Palette,
+};
+use io::{SmsIo, SmsIoConfig};
+use mapper::{SegaMapper, SegaMapperConfig};
+use num::rational::Ratio;
+use std::sync::Arc;
+
+mod io;
+mod mapper;
+
+pub const SMS_MEMORY_ADDRESS_SPACE_ID: AddressSpaceId = 0;
+/// The Z80 port space, of which the Master System only decodes the low byte
+pub const SMS_IO_ADDRESS_SPACE_ID: AddressSpaceId = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmsRegion {
+    /// Mark III and Japanese Master System
+    Japan,
+    NorthAmerica,
+    /// PAL consoles, running at 50 Hz
+    Europe,
+}
+
+impl SmsRegion {
+    /// Consoles sold where the game came out, in the order the user prefers regions
+    fn for_rom(rom_manager: &RomManager, rom_id: RomId) -> Self {
+        let regions = rom_manager
+            .rom_information
+            .r_transaction()
+            .unwrap()
+            .get()
+            .primary::<RomInfo>(rom_id)
+            .unwrap()
+            .map(|info| info.regions)
+            .unwrap_or_default();
+        let preference = GLOBAL_CONFIG.read().unwrap().region_preference.clone();
+
+        preference
+            .iter()
+            .find(|region| regions.contains(region))
+            .or(regions.first())
+            .or(preference.first())
+            .map_or(SmsRegion::NorthAmerica, |region| match region {
+                RomRegion::Japan => SmsRegion::Japan,
+                RomRegion::Europe | RomRegion::Australia => SmsRegion::Europe,
+                // Brazil is PAL-M, which is 60 Hz like NTSC
+                _ => SmsRegion::NorthAmerica,
+            })
+    }
+
+    pub fn video(self) -> Tms9918Region {
+        match self {
+            SmsRegion::Europe => Tms9918Region::Pal,
+            _ => Tms9918Region::Ntsc,
+        }
+    }
+
+    /// The Z80 and the PSG both run off the color burst clock of the TV standard
+    pub fn psg_frequency(self) -> Ratio<u64> {
+        match self.video() {
+            Tms9918Region::Ntsc => Ratio::from_integer(3_579_545),
+            Tms9918Region::Pal => Ratio::from_integer(3_546_893),
+        }
+    }
+}
+
+/// A Master System booting straight into the cartridge, as the BIOS would after checking it
+pub fn sms_machine(
+    user_specified_roms: Vec<RomId>,
+    rom_manager: Arc<RomManager>,
+) -> Result<Machine, MachineConfigurationError> {
+    let region = SmsRegion::for_rom(&rom_manager, user_specified_roms[0]);
+    let machine = Machine::build(GameSystem::Sega(SegaSystem::MasterSystem), rom_manager);
+    let machine = machine.insert_bus(SMS_MEMORY_ADDRESS_SPACE_ID, 16);
+    let machine = machine.insert_bus(SMS_IO_ADDRESS_SPACE_ID, 8);
+    let machine = machine.display_clock(region.video().frame_rate());
+
+    let (machine, processor) = machine.build_component::<Z80>(Z80Config {
+        frequency: region.psg_frequency(),
+        assigned_address_space: SMS_MEMORY_ADDRESS_SPACE_ID,
+        io_address_space: SMS_IO_ADDRESS_SPACE_ID,
+        // Games all run in mode 1, so nothing drives the bus during the acknowledge
+        interrupt_data: 0xff,
+    });
+    let processor = machine.get_component::<Z80>(processor).unwrap();
+
+    // Cartridge, and 8KB of work RAM mirrored up to the mapper registers
+    let (machine, _) = machine.build_component::<SegaMapper>(SegaMapperConfig {
+        rom: user_specified_roms[0],
+    });
+    let (machine, _) = machine.build_component::<StandardMemory>(StandardMemoryConfig {
+        readable: true,
+        writable: true,
+        max_word_size: 2,
+        assigned_range: 0xc000..0xe000,
+        assigned_address_space: SMS_MEMORY_ADDRESS_SPACE_ID,
+        initial_contents: StandardMemoryInitialContents::Random,
+    });
+    let (machine, _) = machine.build_component::<MirrorMemory>(MirrorMemoryConfig {
+        readable: true,
+        writable: true,
+        assigned_range: 0xe000..0xfffc,
+        destination: 0xc000,
+        stride: 0x2000,
+        assigned_address_space: SMS_MEMORY_ADDRESS_SPACE_ID,
+    });
+
+    // The old TMS9918 modes still work, with colors close to the ones they had
+    let palette = Palette::load_for_system(machine.system, &TMS9918_DEFAULT_PALETTE);
+    let (machine, vdp) = machine.build_component::<Tms9918>(Tms9918Config {
+        variant: Tms9918Variant::Sms,
+        region: region.video(),
+        palette,
+        assigned_address_space: SMS_IO_ADDRESS_SPACE_ID,
+        assigned_range: 0x80..0xc0,
+        irq: Some(processor.interrupt_connection(Z80Interrupt::Maskable)),
+    });
+    let (machine, psg) = machine.build_component::<Sn76489>(Sn76489Config {
+        kind: Sn76489Kind::Sega,
+        frequency: region.psg_frequency(),
+        assigned_ports: None,
+    });
+    let (machine, _) = machine.build_component::<SmsIo>(SmsIoConfig {
+        region,
+        vdp,
+        psg,
+        // The pause button
+        nmi: Some(processor.interrupt_connection(Z80Interrupt::NonMaskable)),
+    });
+
+    machine.try_build()
+}
@@ -0,0 +1,82 @@
+use crate::config::GLOBAL_CONFIG;
+use egui::{Color32, Context, PlatformOutput, Stroke, Visuals};
+use std::process::{Command, Stdio};
+
+/// Turns on the parts of egui that describe what the user is interacting with
+pub fn setup(context: &Context) {
+    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+
+    context.options_mut(|options| {
+        options.screen_reader = global_config_guard.screen_reader.is_some();
+    });
+
+    context.set_visuals(if global_config_guard.high_contrast_ui {
+        high_contrast_visuals()
+    } else {
+        Visuals::dark()
+    });
+}
+
+/// Pure black and white with yellow highlights, and thicker outlines on everything
+fn high_contrast_visuals() -> Visuals {
+    let mut visuals = Visuals::dark();
+
+    visuals.override_text_color = Some(Color32::WHITE);
+    visuals.panel_fill = Color32::BLACK;
+    visuals.window_fill = Color32::BLACK;
+    visuals.extreme_bg_color = Color32::BLACK;
+    visuals.window_stroke = Stroke::new(2.0, Color32::WHITE);
+    visuals.selection.bg_fill = Color32::YELLOW;
+    visuals.selection.stroke = Stroke::new(2.0, Color32::BLACK);
+
+    for widget in [
+        &mut visuals.widgets.noninteractive,
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        widget.bg_fill = Color32::BLACK;
+        widget.weak_bg_fill = Color32::BLACK;
+        widget.bg_stroke = Stroke::new(2.0, Color32::WHITE);
+        widget.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+    }
+
+    visuals.widgets.hovered.bg_stroke = Stroke::new(3.0, Color32::YELLOW);
+    visuals.widgets.active.bg_stroke = Stroke::new(3.0, Color32::YELLOW);
+
+    visuals
+}
+
+/// Hands descriptions of focused widgets to the users speech command
+pub fn announce(platform_output: &PlatformOutput) {
+    let Some(command) = GLOBAL_CONFIG.read().unwrap().screen_reader.clone() else {
+        return;
+    };
+
+    for event in &platform_output.events {
+        let description = event.widget_info().description();
+
+        if description.is_empty() {
+            continue;
+        }
+
+        tracing::debug!("Announcing \"{}\"", description);
+
+        let mut arguments = command.split_whitespace();
+        let Some(program) = arguments.next() else {
+            return;
+        };
+
+        if let Err(error) = Command::new(program)
+            .args(arguments)
+            .arg(&description)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            tracing::warn!("Could not run screen reader command {}: {}", command, error);
+            return;
+        }
+    }
+}
@@ -1,13 +1,59 @@
-use crate::config::{GraphicsSettings, GLOBAL_CONFIG};
-use egui::{CentralPanel, ComboBox, Context, ScrollArea, SidePanel};
+use crate::{
+    component::{input::EmulatedGamepadTypeId, text_output::TextOutputComponent},
+    config::{
+        ColorBlindFilter, DisplayScaling, FullscreenMode, GraphicsSettings, ScalerFilter,
+        WindowSizing, GLOBAL_CONFIG,
+    },
+    gui::{accessibility, navigation::UiNavigation, progress},
+    input::{EmulatedGamepadId, GamepadId},
+    localization::{self, tr},
+    logging::{self, LogLevel, LOG_BUFFER},
+    machine::{capabilities::MachineCapabilities, from_system::FAST_BOOT_SYSTEMS},
+    rom::{
+        manager::{MissingRom, RomRequirement},
+        verification::RomWarning,
+        watch::IngestedRom,
+    },
+    runtime::{
+        audio::{AudioHost, AUDIO_STATS},
+        debug_view::DebugView,
+    },
+};
+use egui::{
+    Button, CentralPanel, ComboBox, Context, DragValue, RawInput, ScrollArea, SidePanel, Window,
+};
 use file_browser::{FileBrowserSortingMethod, FileBrowserState};
 use std::fmt::Display;
 use std::path::PathBuf;
+use std::sync::Arc;
 use strum::{EnumIter, IntoEnumIterator};
 mod file_browser;
 
 pub enum UiOutput {
-    OpenGame { path: PathBuf },
+    OpenGame {
+        path: PathBuf,
+    },
+    OpenDebugView(DebugView),
+    /// Write logs and optionally the machine state to this zip
+    SaveBugReport {
+        path: PathBuf,
+        include_state: bool,
+    },
+    /// Let the machine run again after the watchdog stopped it
+    ResumeMachine,
+    /// Route a host device to an emulated port, or unplug it with None
+    AssignGamepad {
+        host: GamepadId,
+        port: Option<EmulatedGamepadId>,
+    },
+}
+
+/// A host device and which emulated port it drives, if any
+#[derive(Clone, Debug)]
+pub struct HostDeviceAssignment {
+    pub id: GamepadId,
+    pub name: String,
+    pub port: Option<EmulatedGamepadId>,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Default, EnumIter)]
@@ -16,7 +62,9 @@ pub enum MenuItem {
     Main,
     FileBrowser,
     Options,
+    Input,
     Database,
+    Debug,
 }
 
 impl Display for MenuItem {
@@ -25,10 +73,12 @@ impl Display for MenuItem {
             f,
             "{}",
             match self {
-                MenuItem::Main => "Main",
-                MenuItem::FileBrowser => "File Browser",
-                MenuItem::Options => "Options",
-                MenuItem::Database => "Database",
+                MenuItem::Main => tr!("menu-main"),
+                MenuItem::FileBrowser => tr!("menu-file-browser"),
+                MenuItem::Options => tr!("menu-options"),
+                MenuItem::Input => tr!("menu-input"),
+                MenuItem::Database => tr!("menu-database"),
+                MenuItem::Debug => tr!("menu-debug"),
             }
         )
     }
@@ -40,6 +90,31 @@ pub struct MenuState {
     file_browser_state: FileBrowserState,
     pub egui_context: egui::Context,
     pub active: bool,
+    /// ROMs the last launched machine could not find, shown to the user until dismissed
+    pub missing_roms: Vec<MissingRom>,
+    /// Same as above but for ROMs that were found yet look wrong
+    pub rom_warnings: Vec<RomWarning>,
+    /// Views the running machine supports opening in their own window
+    pub debug_views: Vec<DebugView>,
+    /// Printers and serial ports of the running machine
+    pub text_outputs: Vec<Arc<dyn TextOutputComponent>>,
+    /// Controller ports of the running machine
+    pub emulated_gamepads: Vec<(EmulatedGamepadId, EmulatedGamepadTypeId)>,
+    pub host_devices: Vec<HostDeviceAssignment>,
+    /// What the running machine supports, None if nothing is running
+    pub capabilities: Option<MachineCapabilities>,
+    /// ROMs the watch folders added since startup, newest last
+    pub recently_added: Vec<IngestedRom>,
+    /// Why the last ROM the user picked could not be loaded, shown until dismissed
+    pub load_error: Option<String>,
+    /// What the watchdog caught when it stopped the running machine, shown until dismissed
+    pub watchdog_report: Option<String>,
+    /// What a panicking component said and where the crash report went, shown until dismissed
+    pub crash_report: Option<String>,
+    /// Save states can be large and hold things from the game the user would rather not share
+    bug_report_without_state: bool,
+    /// Steps from a gamepad waiting to be handed to egui with the next frame's input
+    pending_navigation: Vec<UiNavigation>,
 }
 
 impl MenuState {
@@ -47,13 +122,85 @@ impl MenuState {
     pub fn run_menu(&mut self, ctx: &Context) -> Option<UiOutput> {
         let mut output = None;
 
+        accessibility::setup(ctx);
+
+        if self.has_boot_problems() {
+            self.boot_problems_prompt(ctx);
+        }
+
+        progress::show(ctx, true);
+
+        if let Some(load_error) = &self.load_error {
+            let mut dismissed = false;
+
+            Window::new(tr!("load-error-title"))
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(load_error);
+                    dismissed = ui.button(tr!("dialog-ok")).clicked();
+                });
+
+            if dismissed {
+                self.load_error = None;
+            }
+        }
+
+        if let Some(watchdog_report) = &self.watchdog_report {
+            let mut dismissed = false;
+
+            Window::new(tr!("watchdog-title"))
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(tr!("watchdog-explanation"));
+                    ui.monospace(watchdog_report);
+
+                    ui.horizontal(|ui| {
+                        if ui.button(tr!("watchdog-keep-waiting")).clicked() {
+                            output = Some(UiOutput::ResumeMachine);
+                            dismissed = true;
+                        }
+
+                        dismissed |= ui.button(tr!("dialog-ok")).clicked();
+                    });
+                });
+
+            if dismissed {
+                self.watchdog_report = None;
+            }
+        }
+
+        if let Some(crash_report) = &self.crash_report {
+            let mut dismissed = false;
+
+            Window::new(tr!("crash-title"))
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(tr!("crash-explanation"));
+                    ui.monospace(crash_report);
+                    dismissed = ui.button(tr!("dialog-ok")).clicked();
+                });
+
+            if dismissed {
+                self.crash_report = None;
+            }
+        }
+
         SidePanel::left("options_panel")
             .resizable(true)
             .show(ctx, |ui| {
                 ScrollArea::vertical().show(ui, |ui| {
                     ui.vertical_centered_justified(|ui| {
                         for item in MenuItem::iter() {
-                            if ui.button(format!("{}", item)).clicked() {
+                            if ui
+                                .add_enabled(
+                                    self.menu_item_available(item),
+                                    Button::new(format!("{}", item)),
+                                )
+                                .clicked()
+                            {
                                 self.open_menu_item = item;
                             }
                         }
@@ -65,7 +212,7 @@ impl MenuState {
             ui.with_layout(
                 egui::Layout::top_down_justified(egui::Align::LEFT),
                 |ui| match self.open_menu_item {
-                    MenuItem::Main => if ui.button("Resume").clicked() {},
+                    MenuItem::Main => if ui.button(tr!("menu-resume")).clicked() {},
                     MenuItem::FileBrowser => {
                         let mut new_dir = None;
 
@@ -92,18 +239,18 @@ impl MenuState {
                             }
 
                             let mut selected_sorting = self.file_browser_state.get_sorting_method();
-                            egui::ComboBox::from_label("Sorting")
+                            egui::ComboBox::from_label(tr!("file-browser-sorting"))
                                 .selected_text(format!("{:?}", selected_sorting))
                                 .show_ui(ui, |ui| {
                                     ui.selectable_value(
                                         &mut selected_sorting,
                                         FileBrowserSortingMethod::Name,
-                                        "Name",
+                                        tr!("file-browser-sort-name"),
                                     );
                                     ui.selectable_value(
                                         &mut selected_sorting,
                                         FileBrowserSortingMethod::Date,
-                                        "Date",
+                                        tr!("file-browser-sort-date"),
                                     );
                                 });
                             self.file_browser_state.set_sorting_method(selected_sorting);
@@ -136,12 +283,39 @@ impl MenuState {
                         let mut global_config_guard = GLOBAL_CONFIG.write().unwrap();
 
                         ui.horizontal(|ui| {
-                            if ui.button("Save Config").clicked() {
+                            if ui.button(tr!("options-save-config")).clicked() {
                                 global_config_guard.save().unwrap();
                             }
                         });
 
-                        ComboBox::from_label("Graphics Setting")
+                        let previous_language = global_config_guard.language.clone();
+                        ComboBox::from_label(tr!("options-language"))
+                            .selected_text(
+                                global_config_guard
+                                    .language
+                                    .clone()
+                                    .unwrap_or_else(|| tr!("options-language-system")),
+                            )
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut global_config_guard.language,
+                                    None,
+                                    tr!("options-language-system"),
+                                );
+
+                                for language in localization::available_languages() {
+                                    ui.selectable_value(
+                                        &mut global_config_guard.language,
+                                        Some(language.clone()),
+                                        language,
+                                    );
+                                }
+                            });
+                        if global_config_guard.language != previous_language {
+                            localization::set_language(global_config_guard.language.as_deref());
+                        }
+
+                        ComboBox::from_label(tr!("options-graphics-setting"))
                             .selected_text(global_config_guard.graphics_setting.to_string())
                             .show_ui(ui, |ui| {
                                 for setting in GraphicsSettings::iter() {
@@ -153,13 +327,499 @@ impl MenuState {
                                 }
                             });
 
-                        ui.checkbox(&mut global_config_guard.vsync, "VSync");
+                        ui.checkbox(&mut global_config_guard.vsync, tr!("options-vsync"));
+                        ui.checkbox(
+                            &mut global_config_guard.variable_refresh_rate,
+                            tr!("options-variable-refresh-rate"),
+                        );
+                        ui.checkbox(
+                            &mut global_config_guard.stats_overlay,
+                            tr!("options-stats-overlay"),
+                        );
+
+                        ComboBox::from_label(tr!("options-audio-host"))
+                            .selected_text(global_config_guard.audio_host.to_string())
+                            .show_ui(ui, |ui| {
+                                for setting in AudioHost::iter().filter(AudioHost::supported) {
+                                    ui.selectable_value(
+                                        &mut global_config_guard.audio_host,
+                                        setting,
+                                        setting.to_string(),
+                                    );
+                                }
+                            });
+
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                DragValue::new(&mut global_config_guard.audio_buffer_size)
+                                    .range(32..=8192),
+                            );
+                            ui.label(tr!("options-audio-buffer-size"));
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                DragValue::new(&mut global_config_guard.audio_latency)
+                                    .range(5..=500),
+                            );
+                            ui.label(tr!("options-audio-latency"));
+                        });
+
+                        ui.checkbox(&mut global_config_guard.av_sync, tr!("options-av-sync"));
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                DragValue::new(&mut global_config_guard.av_offset)
+                                    .range(-200..=200),
+                            );
+                            ui.label(tr!("options-av-offset"));
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                DragValue::new(&mut global_config_guard.watchdog_budget)
+                                    .range(0..=60000),
+                            );
+                            ui.label(tr!("options-watchdog-budget"));
+                        });
+
+                        ComboBox::from_label(tr!("options-display-scaling"))
+                            .selected_text(global_config_guard.display_scaling.to_string())
+                            .show_ui(ui, |ui| {
+                                for setting in DisplayScaling::iter() {
+                                    ui.selectable_value(
+                                        &mut global_config_guard.display_scaling,
+                                        setting,
+                                        setting.to_string(),
+                                    );
+                                }
+                            });
+
+                        // Only the software renderer scales on the CPU
+                        if global_config_guard.graphics_setting == GraphicsSettings::Software {
+                            ComboBox::from_label(tr!("options-scaler-filter"))
+                                .selected_text(global_config_guard.scaler_filter.to_string())
+                                .show_ui(ui, |ui| {
+                                    for setting in ScalerFilter::iter() {
+                                        ui.selectable_value(
+                                            &mut global_config_guard.scaler_filter,
+                                            setting,
+                                            setting.to_string(),
+                                        );
+                                    }
+                                });
+                        }
+
+                        ComboBox::from_label(tr!("options-color-blind-filter"))
+                            .selected_text(global_config_guard.color_blind_filter.to_string())
+                            .show_ui(ui, |ui| {
+                                for setting in ColorBlindFilter::iter() {
+                                    ui.selectable_value(
+                                        &mut global_config_guard.color_blind_filter,
+                                        setting,
+                                        setting.to_string(),
+                                    );
+                                }
+                            });
+
+                        ui.checkbox(
+                            &mut global_config_guard.high_contrast_ui,
+                            tr!("options-high-contrast"),
+                        );
+
+                        ComboBox::from_label(tr!("options-fullscreen-mode"))
+                            .selected_text(global_config_guard.fullscreen_mode.to_string())
+                            .show_ui(ui, |ui| {
+                                for setting in FullscreenMode::iter() {
+                                    ui.selectable_value(
+                                        &mut global_config_guard.fullscreen_mode,
+                                        setting,
+                                        setting.to_string(),
+                                    );
+                                }
+                            });
+
+                        ui.checkbox(
+                            &mut global_config_guard.start_fullscreen,
+                            tr!("options-start-fullscreen"),
+                        );
+
+                        ComboBox::from_label(tr!("options-window-sizing"))
+                            .selected_text(global_config_guard.window_sizing.to_string())
+                            .show_ui(ui, |ui| {
+                                for setting in WindowSizing::iter() {
+                                    ui.selectable_value(
+                                        &mut global_config_guard.window_sizing,
+                                        setting,
+                                        setting.to_string(),
+                                    );
+                                }
+                            });
+
+                        ui.separator();
+                        ui.label(tr!("options-fast-boot"));
+                        for system in FAST_BOOT_SYSTEMS {
+                            let mut fast_boot = global_config_guard
+                                .fast_boot
+                                .get(system)
+                                .copied()
+                                .unwrap_or_default();
+
+                            if ui.checkbox(&mut fast_boot, system.to_string()).changed() {
+                                global_config_guard.fast_boot.insert(*system, fast_boot);
+                            }
+                        }
+                    }
+                    MenuItem::Input => {
+                        match self.capabilities {
+                            None => {
+                                ui.label(tr!("no-machine-running"));
+                            }
+                            Some(capabilities) if capabilities.controller_ports == 0 => {
+                                ui.label(tr!("input-no-controller-ports"));
+                            }
+                            _ => {}
+                        }
+
+                        for host_device in self.host_devices.iter_mut() {
+                            let port_name = |port: Option<EmulatedGamepadId>| match port {
+                                Some(port) => self
+                                    .emulated_gamepads
+                                    .iter()
+                                    .find(|(id, _)| *id == port)
+                                    .map(|(id, kind)| {
+                                        tr!(
+                                            "input-port-kind",
+                                            port = id + 1,
+                                            kind = kind.to_string()
+                                        )
+                                    })
+                                    .unwrap_or_else(|| tr!("input-port", port = port + 1)),
+                                None => tr!("input-unplugged"),
+                            };
+                            let previous_port = host_device.port;
+
+                            ComboBox::from_label(&host_device.name)
+                                .selected_text(port_name(host_device.port))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(
+                                        &mut host_device.port,
+                                        None,
+                                        port_name(None),
+                                    );
+
+                                    for (port, _) in self.emulated_gamepads.iter() {
+                                        ui.selectable_value(
+                                            &mut host_device.port,
+                                            Some(*port),
+                                            port_name(Some(*port)),
+                                        );
+                                    }
+                                });
+
+                            if host_device.port != previous_port {
+                                output = Some(UiOutput::AssignGamepad {
+                                    host: host_device.id,
+                                    port: host_device.port,
+                                });
+                            }
+                        }
+                    }
+                    MenuItem::Database => {
+                        let mut global_config_guard = GLOBAL_CONFIG.write().unwrap();
+
+                        ui.label(tr!("database-watch-folders"));
+
+                        let mut removed = None;
+                        for (index, folder) in global_config_guard.watch_folders.iter().enumerate()
+                        {
+                            ui.horizontal(|ui| {
+                                ui.label(folder.display().to_string());
+
+                                if ui.button(tr!("database-remove")).clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+                        }
+
+                        if let Some(removed) = removed {
+                            global_config_guard.watch_folders.remove(removed);
+                        }
+
+                        let current_directory = self.file_browser_state.directory().to_path_buf();
+                        if !global_config_guard
+                            .watch_folders
+                            .contains(&current_directory)
+                            && ui
+                                .button(tr!(
+                                    "database-watch",
+                                    folder = current_directory.display().to_string()
+                                ))
+                                .clicked()
+                        {
+                            global_config_guard.watch_folders.push(current_directory);
+                        }
+
+                        drop(global_config_guard);
+
+                        if !self.recently_added.is_empty() {
+                            ui.separator();
+                            ui.label(tr!("database-recently-added"));
+
+                            for rom in self.recently_added.iter().rev() {
+                                ui.label(format!(
+                                    "{} ({})",
+                                    rom.name
+                                        .clone()
+                                        .unwrap_or_else(|| tr!("database-unknown-rom")),
+                                    rom.system
+                                ));
+                            }
+                        }
+                    }
+                    MenuItem::Debug => {
+                        if self.debug_views.is_empty() {
+                            ui.label(tr!("no-machine-running"));
+                        }
+
+                        for view in &self.debug_views {
+                            if ui.button(view.to_string()).clicked() {
+                                output = Some(UiOutput::OpenDebugView(*view));
+                            }
+                        }
+
+                        for text_output in &self.text_outputs {
+                            ui.collapsing(text_output.name(), |ui| {
+                                ScrollArea::vertical()
+                                    .id_salt(text_output.name())
+                                    .max_height(200.0)
+                                    .stick_to_bottom(true)
+                                    .show(ui, |ui| {
+                                        ui.monospace(text_output.text());
+                                    });
+
+                                ui.horizontal(|ui| {
+                                    if ui.button(tr!("debug-text-output-save")).clicked() {
+                                        save_text_output(text_output.as_ref());
+                                    }
+
+                                    if ui.button(tr!("debug-text-output-clear")).clicked() {
+                                        text_output.clear();
+                                    }
+                                });
+                            });
+                        }
+
+                        ui.separator();
+
+                        {
+                            let mut global_config_guard = GLOBAL_CONFIG.write().unwrap();
+                            let mut levels_changed = false;
+
+                            levels_changed |= log_level_combo(
+                                ui,
+                                &tr!("debug-log-level"),
+                                &mut global_config_guard.log_level,
+                            );
+
+                            for (module, level) in global_config_guard.module_log_levels.iter_mut()
+                            {
+                                levels_changed |= log_level_combo(ui, module, level);
+                            }
+
+                            drop(global_config_guard);
+
+                            if levels_changed {
+                                logging::reload_levels();
+                            }
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.label(tr!(
+                                "debug-audio-stats",
+                                underruns = AUDIO_STATS.underruns(),
+                                overruns = AUDIO_STATS.overruns()
+                            ));
+
+                            if ui.button(tr!("debug-reset")).clicked() {
+                                AUDIO_STATS.reset();
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            if ui.button(tr!("debug-save-bug-report")).clicked() {
+                                output = Some(UiOutput::SaveBugReport {
+                                    path: self.file_browser_state.directory().join(format!(
+                                        "multiemu-bug-report-{}.zip",
+                                        std::time::SystemTime::now()
+                                            .duration_since(std::time::UNIX_EPOCH)
+                                            .unwrap_or_default()
+                                            .as_secs()
+                                    )),
+                                    include_state: !self.bug_report_without_state,
+                                });
+                            }
+
+                            ui.checkbox(
+                                &mut self.bug_report_without_state,
+                                tr!("debug-bug-report-without-state"),
+                            );
+                        });
+
+                        // Copied out so nothing logged while drawing can deadlock on the buffer
+                        let records: Vec<_> = LOG_BUFFER.lock().unwrap().iter().cloned().collect();
+
+                        ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+                            for record in records {
+                                ui.monospace(format!(
+                                    "{} {}: {}",
+                                    record.level, record.target, record.message
+                                ));
+                            }
+                        });
                     }
-                    MenuItem::Database => {}
                 },
             );
         });
 
         output
     }
+
+    /// Moves around the menu without a mouse
+    ///
+    /// Switching sections and backing out are handled here, everything else moves egui's focus once
+    /// [Self::inject_navigation] passes it along
+    pub fn navigate(&mut self, navigation: UiNavigation) {
+        match navigation {
+            UiNavigation::NextTab | UiNavigation::PreviousTab => {
+                let items: Vec<_> = MenuItem::iter()
+                    .filter(|item| self.menu_item_available(*item))
+                    .collect();
+                let current = items
+                    .iter()
+                    .position(|item| *item == self.open_menu_item)
+                    .unwrap_or_default();
+
+                self.open_menu_item = if navigation == UiNavigation::NextTab {
+                    items[(current + 1) % items.len()]
+                } else {
+                    items[(current + items.len() - 1) % items.len()]
+                };
+            }
+            UiNavigation::Back => {
+                if self.load_error.is_some() {
+                    self.load_error = None;
+                } else if self.open_menu_item != MenuItem::Main {
+                    self.open_menu_item = MenuItem::Main;
+                } else if self.capabilities.is_some() {
+                    // Back out of the top level goes back to the game
+                    self.active = false;
+                }
+            }
+            _ => self.pending_navigation.push(navigation),
+        }
+    }
+
+    /// Feeds queued navigation into egui, call on the raw input before running the menu
+    pub fn inject_navigation(&mut self, raw_input: &mut RawInput) {
+        for navigation in self.pending_navigation.drain(..) {
+            navigation.inject(raw_input);
+        }
+    }
+
+    /// If the last launched machine had ROM issues the user has not acknowledged yet
+    /// Greys out tabs the running machine has nothing to show in
+    fn menu_item_available(&self, item: MenuItem) -> bool {
+        match item {
+            MenuItem::Input => self
+                .capabilities
+                .is_none_or(|capabilities| capabilities.controller_ports != 0),
+            _ => true,
+        }
+    }
+
+    pub fn has_boot_problems(&self) -> bool {
+        !self.missing_roms.is_empty() || !self.rom_warnings.is_empty()
+    }
+
+    fn boot_problems_prompt(&mut self, ctx: &Context) {
+        let bootable = self
+            .missing_roms
+            .iter()
+            .all(|missing_rom| missing_rom.requirement != RomRequirement::Required);
+
+        Window::new(tr!("rom-problems-title"))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if !self.missing_roms.is_empty() {
+                    if bootable {
+                        ui.label(tr!("rom-problems-missing-optional"));
+                    } else {
+                        ui.label(tr!("rom-problems-missing-required"));
+                    }
+
+                    for missing_rom in self.missing_roms.iter() {
+                        ui.label(format!("{} ({})", missing_rom.id, missing_rom.requirement));
+                    }
+                }
+
+                if !self.rom_warnings.is_empty() {
+                    ui.separator();
+                    ui.label(tr!("rom-problems-mismatch"));
+
+                    for rom_warning in self.rom_warnings.iter() {
+                        ui.label(format!("{}: {}", rom_warning.id, rom_warning.verification));
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    if bootable {
+                        if ui.button(tr!("dialog-continue")).clicked() {
+                            self.missing_roms.clear();
+                            self.rom_warnings.clear();
+                            self.active = false;
+                        }
+                    } else if ui.button(tr!("dialog-ok")).clicked() {
+                        self.missing_roms.clear();
+                        self.rom_warnings.clear();
+                    }
+                });
+            });
+    }
+}
+
+/// Returns if the level was changed
+fn log_level_combo(ui: &mut egui::Ui, label: &str, level: &mut LogLevel) -> bool {
+    let previous_level = *level;
+
+    ComboBox::from_label(label)
+        .selected_text(level.to_string())
+        .show_ui(ui, |ui| {
+            for setting in LogLevel::iter() {
+                ui.selectable_value(level, setting, setting.to_string());
+            }
+        });
+
+    previous_level != *level
+}
+
+/// Writes everything a printer or serial port has printed to the capture directory
+fn save_text_output(text_output: &dyn TextOutputComponent) {
+    let capture_directory = GLOBAL_CONFIG.read().unwrap().capture_directory.clone();
+    let path = capture_directory.join(format!(
+        "{}-{}.txt",
+        text_output.name().to_lowercase().replace(' ', "-"),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+    ));
+
+    if let Err(error) = std::fs::create_dir_all(&capture_directory)
+        .and_then(|_| std::fs::write(&path, text_output.text()))
+    {
+        tracing::error!("Could not save {}: {}", path.display(), error);
+    } else {
+        tracing::info!("Saved {}", path.display());
+    }
 }
@@ -1,2 +1,7 @@
+pub mod accessibility;
 pub mod menu;
+pub mod navigation;
+pub mod pause_menu;
+pub mod progress;
 pub mod software_rasterizer;
+pub mod stats_overlay;
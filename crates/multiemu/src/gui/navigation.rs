@@ -0,0 +1,118 @@
+use crate::input::{gamepad::GamepadInput, keyboard::KeyboardInput, Input};
+use egui::{Event, Key, Modifiers, RawInput};
+
+/// A step through any of the built in menus, from a keyboard or any gamepad including the 3DS buttons
+///
+/// Follows the usual console layout, the bottom face button confirms and the right one goes back
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum UiNavigation {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Back,
+    /// Shoulder buttons flip between the sections of a menu
+    NextTab,
+    PreviousTab,
+}
+
+impl UiNavigation {
+    pub fn from_input(input: Input) -> Option<Self> {
+        match input {
+            Input::Keyboard(KeyboardInput::ArrowUp)
+            | Input::Gamepad(GamepadInput::DPadUp | GamepadInput::LeftStickUp) => Some(Self::Up),
+            Input::Keyboard(KeyboardInput::ArrowDown)
+            | Input::Gamepad(GamepadInput::DPadDown | GamepadInput::LeftStickDown) => {
+                Some(Self::Down)
+            }
+            Input::Keyboard(KeyboardInput::ArrowLeft)
+            | Input::Gamepad(GamepadInput::DPadLeft | GamepadInput::LeftStickLeft) => {
+                Some(Self::Left)
+            }
+            Input::Keyboard(KeyboardInput::ArrowRight)
+            | Input::Gamepad(GamepadInput::DPadRight | GamepadInput::LeftStickRight) => {
+                Some(Self::Right)
+            }
+            Input::Keyboard(KeyboardInput::Enter | KeyboardInput::NumpadEnter)
+            | Input::Gamepad(GamepadInput::FPadDown) => Some(Self::Confirm),
+            Input::Keyboard(KeyboardInput::Escape) | Input::Gamepad(GamepadInput::FPadRight) => {
+                Some(Self::Back)
+            }
+            Input::Keyboard(KeyboardInput::PageDown)
+            | Input::Gamepad(GamepadInput::RightTrigger) => Some(Self::NextTab),
+            Input::Keyboard(KeyboardInput::PageUp) | Input::Gamepad(GamepadInput::LeftTrigger) => {
+                Some(Self::PreviousTab)
+            }
+            _ => None,
+        }
+    }
+
+    /// Turns a step into the key presses egui already understands for moving focus and activating widgets
+    ///
+    /// Up and down walk the focus order like tab does, left and right nudge sliders and drag values, and confirm
+    /// presses whatever is focused. Back and the tab switches mean nothing to egui, so the menu handles those itself
+    pub fn inject(self, raw_input: &mut RawInput) {
+        let (key, modifiers) = match self {
+            Self::Up => (Key::Tab, Modifiers::SHIFT),
+            Self::Down => (Key::Tab, Modifiers::NONE),
+            Self::Left => (Key::ArrowLeft, Modifiers::NONE),
+            Self::Right => (Key::ArrowRight, Modifiers::NONE),
+            Self::Confirm => (Key::Enter, Modifiers::NONE),
+            Self::Back | Self::NextTab | Self::PreviousTab => return,
+        };
+
+        for pressed in [true, false] {
+            raw_input.events.push(Event::Key {
+                key,
+                physical_key: None,
+                pressed,
+                repeat: false,
+                modifiers,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn gamepad_moves_focus() {
+        let mut raw_input = RawInput::default();
+
+        for input in [
+            Input::Gamepad(GamepadInput::DPadUp),
+            Input::Gamepad(GamepadInput::FPadDown),
+            Input::Gamepad(GamepadInput::RightTrigger),
+        ] {
+            UiNavigation::from_input(input)
+                .unwrap()
+                .inject(&mut raw_input);
+        }
+
+        let keys: Vec<_> = raw_input
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => Some((*key, *modifiers)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            keys,
+            [(Key::Tab, Modifiers::SHIFT), (Key::Enter, Modifiers::NONE)]
+        );
+        assert_eq!(
+            UiNavigation::from_input(Input::Gamepad(GamepadInput::FPadRight)),
+            Some(UiNavigation::Back)
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{gui::navigation::UiNavigation, localization::tr};
+use egui::{Align2, Button, Context, Frame, RichText, Vec2};
+use std::fmt::Display;
+use strum::{EnumIter, IntoEnumIterator};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, EnumIter)]
+pub enum PauseMenuItem {
+    Resume,
+    Reset,
+    SaveState,
+    LoadState,
+    ExportArchive,
+    Settings,
+    TapePlay,
+    TapeStop,
+    TapeRewind,
+    TapeFastLoad,
+    QuitToLauncher,
+}
+
+impl Display for PauseMenuItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PauseMenuItem::Resume => tr!("pause-resume"),
+                PauseMenuItem::Reset => tr!("pause-reset"),
+                PauseMenuItem::SaveState => tr!("pause-save-state"),
+                PauseMenuItem::LoadState => tr!("pause-load-state"),
+                PauseMenuItem::ExportArchive => tr!("pause-export-archive"),
+                PauseMenuItem::Settings => tr!("pause-settings"),
+                PauseMenuItem::TapePlay => tr!("pause-tape-play"),
+                PauseMenuItem::TapeStop => tr!("pause-tape-stop"),
+                PauseMenuItem::TapeRewind => tr!("pause-tape-rewind"),
+                PauseMenuItem::TapeFastLoad => tr!("pause-tape-fast-load"),
+                PauseMenuItem::QuitToLauncher => tr!("pause-quit-to-launcher"),
+            }
+        )
+    }
+}
+
+/// The small menu shown over a running game, so fullscreen players never need the full launcher
+///
+/// Drawn through the overlay layer, so the game stays visible behind it
+#[derive(Debug, Default, Clone)]
+pub struct PauseMenu {
+    pub open: bool,
+    selected: usize,
+    /// Items that make no sense right now, like saving without a ROM to save against
+    pub snapshots_available: bool,
+    /// If the machine has a tape deck to press the buttons of
+    pub tape_available: bool,
+}
+
+impl PauseMenu {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.selected = 0;
+    }
+
+    fn enabled(&self, item: PauseMenuItem) -> bool {
+        match item {
+            PauseMenuItem::SaveState | PauseMenuItem::LoadState | PauseMenuItem::ExportArchive => {
+                self.snapshots_available
+            }
+            PauseMenuItem::TapePlay
+            | PauseMenuItem::TapeStop
+            | PauseMenuItem::TapeRewind
+            | PauseMenuItem::TapeFastLoad => self.tape_available,
+            _ => true,
+        }
+    }
+
+    /// Moves the selection, returning the chosen item on confirm and resume on back
+    pub fn navigate(&mut self, navigation: UiNavigation) -> Option<PauseMenuItem> {
+        let items: Vec<_> = PauseMenuItem::iter().collect();
+
+        match navigation {
+            UiNavigation::Up | UiNavigation::Down => {
+                // Skips disabled items, Resume is always there so this can't loop forever
+                loop {
+                    self.selected = if navigation == UiNavigation::Up {
+                        (self.selected + items.len() - 1) % items.len()
+                    } else {
+                        (self.selected + 1) % items.len()
+                    };
+
+                    if self.enabled(items[self.selected]) {
+                        break None;
+                    }
+                }
+            }
+            UiNavigation::Confirm => Some(items[self.selected]),
+            UiNavigation::Back => Some(PauseMenuItem::Resume),
+            // A single column, nothing to move sideways through
+            UiNavigation::Left
+            | UiNavigation::Right
+            | UiNavigation::NextTab
+            | UiNavigation::PreviousTab => None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context) -> Option<PauseMenuItem> {
+        let mut chosen = None;
+
+        egui::Area::new("pause_menu".into())
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.vertical_centered_justified(|ui| {
+                        ui.heading(tr!("pause-title"));
+
+                        for (index, item) in PauseMenuItem::iter().enumerate() {
+                            let mut text = RichText::new(item.to_string());
+                            if index == self.selected {
+                                text = text.strong();
+                            }
+
+                            let response = ui.add_enabled(
+                                self.enabled(item),
+                                Button::new(text).selected(index == self.selected),
+                            );
+
+                            if response.hovered() {
+                                self.selected = index;
+                            }
+
+                            if response.clicked() {
+                                chosen = Some(item);
+                            }
+                        }
+                    });
+                });
+            });
+
+        chosen
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn navigation_skips_disabled() {
+        let mut menu = PauseMenu::default();
+        menu.toggle();
+
+        assert_eq!(menu.navigate(UiNavigation::Down), None);
+        assert_eq!(
+            menu.navigate(UiNavigation::Down),
+            None,
+            "Should have skipped the snapshot items"
+        );
+        assert_eq!(
+            menu.navigate(UiNavigation::Confirm),
+            Some(PauseMenuItem::Settings)
+        );
+        menu.navigate(UiNavigation::Down);
+        menu.navigate(UiNavigation::Down);
+        assert_eq!(
+            menu.navigate(UiNavigation::Confirm),
+            Some(PauseMenuItem::Resume)
+        );
+        assert_eq!(
+            menu.navigate(UiNavigation::Back),
+            Some(PauseMenuItem::Resume)
+        );
+    }
+}
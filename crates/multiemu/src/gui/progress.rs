@@ -0,0 +1,50 @@
+use crate::{localization::tr, runtime::progress::PROGRESS};
+use egui::{Align2, Context, Frame, ProgressBar, Vec2};
+
+/// Bars for every task on [PROGRESS], in the corner so they don't cover the menu or game
+///
+/// Cancel buttons only show when the user can actually click them, over the running game input goes to the machine
+pub fn show(ctx: &Context, cancellable: bool) {
+    let tasks = PROGRESS.tasks();
+
+    if tasks.is_empty() {
+        return;
+    }
+
+    egui::Area::new("progress".into())
+        .anchor(Align2::RIGHT_BOTTOM, Vec2::splat(-8.0))
+        .interactable(cancellable)
+        .show(ctx, |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                for task in tasks {
+                    ui.horizontal(|ui| {
+                        let text = match task.total {
+                            Some(total) => tr!(
+                                "progress-count",
+                                label = task.label.clone(),
+                                done = task.done,
+                                total = total
+                            ),
+                            None => task.label.clone(),
+                        };
+                        let bar = match task.fraction {
+                            Some(fraction) => ProgressBar::new(fraction),
+                            None => ProgressBar::new(0.0).animate(true),
+                        };
+                        ui.add(bar.desired_width(240.0).text(text));
+
+                        if cancellable {
+                            if task.cancelled {
+                                ui.label(tr!("progress-cancelling"));
+                            } else if ui.button(tr!("progress-cancel")).clicked() {
+                                PROGRESS.cancel(task.id);
+                            }
+                        }
+                    });
+                }
+            });
+        });
+
+    // Nothing else would redraw while work finishes in the background
+    ctx.request_repaint();
+}
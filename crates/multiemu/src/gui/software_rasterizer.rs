@@ -100,8 +100,10 @@ impl SoftwareEguiRenderer {
                             .map(|&index| {
                                 let vertex = mesh.vertices[index as usize];
 
+                                // egui works in points, we need physical pixels
                                 EguiVertex {
-                                    pos: Point2::new(vertex.pos.x, vertex.pos.y),
+                                    pos: Point2::new(vertex.pos.x, vertex.pos.y)
+                                        * full_output.pixels_per_point,
                                     uv: Point2::new(vertex.uv.x, vertex.uv.y),
                                     color: Srgba::from_components(vertex.color.to_tuple()),
                                 }
@@ -0,0 +1,196 @@
+use crate::{
+    localization::tr,
+    machine::Machine,
+    runtime::{audio::AUDIO_STATS, av_sync::AV_SYNC},
+};
+use egui::{Align2, Color32, Context, Frame, ProgressBar, Sense, Shape, Stroke, Vec2};
+use ringbuffer::{AllocRingBuffer, RingBuffer};
+use std::time::{Duration, Instant};
+
+/// Frames of history the overlay averages and graphs over
+const HISTORY: usize = 120;
+
+/// How long a frame can take at 60 hz, drawn as a line on the graph
+const FRAME_BUDGET: Duration = Duration::from_nanos(16_666_667);
+
+#[derive(Debug, Clone, Copy)]
+struct FrameSample {
+    /// Host time since the previous frame started
+    interval: Duration,
+    /// Emulated time that passed in the same interval
+    emulated: Duration,
+    /// Host time spent running the machine and drawing it
+    frame_time: Duration,
+}
+
+/// Speed, framerate and audio numbers drawn over the running machine
+#[derive(Debug)]
+pub struct StatsOverlay {
+    samples: AllocRingBuffer<FrameSample>,
+    last_frame: Option<(Instant, Duration)>,
+}
+
+impl Default for StatsOverlay {
+    fn default() -> Self {
+        Self {
+            samples: AllocRingBuffer::new(HISTORY),
+            last_frame: None,
+        }
+    }
+}
+
+impl StatsOverlay {
+    /// Called once per frame with how long the frame took and where the machine clock is now
+    pub fn record_frame(&mut self, frame_time: Duration, emulated_time: Duration) {
+        let now = Instant::now();
+
+        if let Some((last_instant, last_emulated_time)) = self.last_frame {
+            self.samples.push(FrameSample {
+                interval: now - last_instant,
+                emulated: emulated_time.saturating_sub(last_emulated_time),
+                frame_time,
+            });
+        }
+
+        self.last_frame = Some((now, emulated_time));
+    }
+
+    /// Forget everything, so a pause or a new machine doesn't drag the averages down
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.last_frame = None;
+    }
+
+    pub fn fps(&self) -> f32 {
+        let total = self
+            .samples
+            .iter()
+            .map(|sample| sample.interval)
+            .sum::<Duration>();
+
+        if total.is_zero() {
+            return 0.0;
+        }
+
+        self.samples.len() as f32 / total.as_secs_f32()
+    }
+
+    /// Emulated time compared to host time, 100% is full speed
+    pub fn speed(&self) -> f32 {
+        let interval = self
+            .samples
+            .iter()
+            .map(|sample| sample.interval)
+            .sum::<Duration>();
+        let emulated = self
+            .samples
+            .iter()
+            .map(|sample| sample.emulated)
+            .sum::<Duration>();
+
+        if interval.is_zero() {
+            return 0.0;
+        }
+
+        emulated.as_secs_f32() / interval.as_secs_f32() * 100.0
+    }
+
+    /// The device is whatever the rendering backend reports, if it has one worth naming
+    pub fn show(&self, ctx: &Context, machine: &Machine, device_name: Option<&str>) {
+        egui::Area::new("stats_overlay".into())
+            .anchor(Align2::LEFT_TOP, Vec2::splat(8.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                Frame::popup(ui.style()).show(ui, |ui| {
+                    // Numbers are formatted here so every language gets the same precision
+                    ui.monospace(tr!("overlay-speed", speed = format!("{:.0}", self.speed())));
+                    ui.monospace(tr!("overlay-fps", fps = format!("{:.1}", self.fps())));
+                    if let Some(device_name) = device_name {
+                        ui.monospace(tr!("overlay-device", device = device_name));
+                    }
+
+                    self.frame_time_graph(ui);
+
+                    ui.add(
+                        ProgressBar::new(AUDIO_STATS.fill_level().clamp(0.0, 1.0))
+                            .desired_width(160.0)
+                            .text(tr!(
+                                "overlay-audio-buffer",
+                                fill = format!("{:.0}", AUDIO_STATS.fill_level() * 100.0)
+                            )),
+                    );
+                    ui.monospace(tr!(
+                        "overlay-av-skew",
+                        skew = format!("{:+.0}", AV_SYNC.skew() * 1000.0)
+                    ));
+
+                    let mut component_times: Vec<_> = machine
+                        .scheduler
+                        .component_time()
+                        .iter()
+                        .map(|(component_id, time)| (*component_id, *time))
+                        .collect();
+                    component_times.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+                    for (component_id, time) in component_times {
+                        let name = machine
+                            .component_store
+                            .get(component_id)
+                            .map(|table| table.name.rsplit("::").next().unwrap_or(table.name))
+                            .unwrap_or("Unknown");
+
+                        ui.monospace(format!("{}: {:.2}ms", name, time.as_secs_f64() * 1000.0));
+                    }
+                });
+            });
+    }
+
+    fn frame_time_graph(&self, ui: &mut egui::Ui) {
+        let (response, painter) = ui.allocate_painter(Vec2::new(160.0, 40.0), Sense::hover());
+        let rect = response.rect;
+        // Scale so a frame over budget is clearly visible without the budget line hugging the top
+        let scale = FRAME_BUDGET.as_secs_f32() * 2.0;
+        let height =
+            |time: Duration| rect.bottom() - (time.as_secs_f32() / scale).min(1.0) * rect.height();
+
+        painter.rect_filled(rect, 0.0, Color32::from_black_alpha(128));
+        painter.hline(
+            rect.x_range(),
+            height(FRAME_BUDGET),
+            Stroke::new(1.0, Color32::DARK_GREEN),
+        );
+
+        let step = rect.width() / (HISTORY - 1) as f32;
+        let points = self
+            .samples
+            .iter()
+            .enumerate()
+            .map(|(index, sample)| {
+                egui::pos2(rect.left() + index as f32 * step, height(sample.frame_time))
+            })
+            .collect();
+
+        painter.add(Shape::line(points, Stroke::new(1.0, Color32::WHITE)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn half_speed() {
+        let mut overlay = StatsOverlay::default();
+
+        for _ in 0..4 {
+            overlay.samples.push(FrameSample {
+                interval: Duration::from_millis(20),
+                emulated: Duration::from_millis(10),
+                frame_time: Duration::from_millis(5),
+            });
+        }
+
+        assert_eq!(overlay.fps(), 50.0);
+        assert_eq!(overlay.speed(), 50.0);
+    }
+}
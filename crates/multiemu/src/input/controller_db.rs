@@ -0,0 +1,360 @@
+//! Support for the SDL_GameControllerDB mapping format
+//!
+//! <https://github.com/mdqinc/SDL_GameControllerDB>
+
+use super::{gamepad::GamepadInput, Input, InputState};
+use crate::config::GLOBAL_CONFIG;
+use std::{collections::HashMap, fs::read_to_string, path::Path, sync::LazyLock};
+
+/// Shipped with the emulator so common controllers work without the user finding a database
+const BUNDLED_DATABASE: &str = include_str!("../../assets/gamecontrollerdb.txt");
+
+/// The bundled mappings, then the users database file and the SDL_GAMECONTROLLERCONFIG environment variable over them
+pub static CONTROLLER_DATABASE: LazyLock<ControllerDatabase> = LazyLock::new(|| {
+    let mut database = ControllerDatabase::default();
+    database.parse(BUNDLED_DATABASE);
+
+    let path = GLOBAL_CONFIG.read().unwrap().controller_database.clone();
+
+    if path.is_file() {
+        if let Err(error) = database.load(&path) {
+            tracing::warn!(
+                "Could not load controller database {}: {}",
+                path.display(),
+                error
+            );
+        }
+    }
+
+    // Same variable SDL itself reads, so Steam and friends can hand us mappings
+    if let Ok(mappings) = std::env::var("SDL_GAMECONTROLLERCONFIG") {
+        database.parse(&mappings);
+    }
+
+    tracing::info!("Loaded {} controller mappings", database.len());
+
+    database
+});
+
+#[cfg(target_os = "linux")]
+const CURRENT_PLATFORM: &str = "Linux";
+#[cfg(target_os = "windows")]
+const CURRENT_PLATFORM: &str = "Windows";
+#[cfg(target_os = "macos")]
+const CURRENT_PLATFORM: &str = "Mac OS X";
+#[cfg(target_os = "android")]
+const CURRENT_PLATFORM: &str = "Android";
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "android"
+)))]
+const CURRENT_PLATFORM: &str = "";
+
+/// An input as the device reports it, before any mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RawInput {
+    Button(u8),
+    Axis(u8),
+    /// Hat index and the direction bit
+    Hat(u8, u8),
+}
+
+/// Which part of a raw axis a mapping uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AxisRange {
+    Full,
+    Positive,
+    Negative,
+}
+
+/// Where a raw input ends up on the standard layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MappingTarget {
+    Button(GamepadInput),
+    /// A stick axis, split into its two directions
+    Stick {
+        negative: GamepadInput,
+        positive: GamepadInput,
+    },
+    /// An analog trigger
+    Trigger(GamepadInput),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MappingEntry {
+    target: MappingTarget,
+    range: AxisRange,
+    inverted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControllerMapping {
+    pub name: String,
+    entries: HashMap<RawInput, Vec<MappingEntry>>,
+}
+
+impl ControllerMapping {
+    /// Turns a raw event into inputs on the standard layout
+    ///
+    /// Buttons and hats take 0.0 or 1.0, axes go from -1.0 to 1.0
+    pub fn translate(&self, raw: RawInput, value: f32) -> Vec<(Input, InputState)> {
+        let Some(entries) = self.entries.get(&raw) else {
+            return Vec::new();
+        };
+
+        let mut translated = Vec::new();
+
+        for entry in entries {
+            let value = if entry.inverted { -value } else { value };
+            let value = match entry.range {
+                AxisRange::Full => value,
+                AxisRange::Positive => value.max(0.0),
+                AxisRange::Negative => -value.min(0.0),
+            };
+
+            match entry.target {
+                MappingTarget::Button(input) => translated.push((
+                    Input::Gamepad(input),
+                    InputState::Digital(value.abs() >= 0.5),
+                )),
+                MappingTarget::Stick { negative, positive } => {
+                    translated.push((
+                        Input::Gamepad(negative),
+                        InputState::Analog((-value).clamp(0.0, 1.0)),
+                    ));
+                    translated.push((
+                        Input::Gamepad(positive),
+                        InputState::Analog(value.clamp(0.0, 1.0)),
+                    ));
+                }
+                MappingTarget::Trigger(input) => {
+                    // A trigger on a whole axis rests at -1.0, not in the middle
+                    let value = match entry.range {
+                        AxisRange::Full => (value + 1.0) / 2.0,
+                        AxisRange::Positive | AxisRange::Negative => value,
+                    };
+
+                    translated.push((
+                        Input::Gamepad(input),
+                        InputState::Analog(value.clamp(0.0, 1.0)),
+                    ));
+                }
+            }
+        }
+
+        translated
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ControllerDatabase {
+    mappings: HashMap<String, ControllerMapping>,
+}
+
+impl ControllerDatabase {
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+        self.parse(&read_to_string(path)?);
+
+        Ok(())
+    }
+
+    /// Parses mapping lines, later lines replace earlier ones for the same GUID
+    pub fn parse(&mut self, contents: &str) {
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some((guid, mapping)) = parse_line(line) {
+                self.mappings.insert(guid, mapping);
+            }
+        }
+    }
+
+    pub fn get(&self, guid: &str) -> Option<&ControllerMapping> {
+        self.mappings.get(&guid.to_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.mappings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+}
+
+fn parse_line(line: &str) -> Option<(String, ControllerMapping)> {
+    let mut fields = line.split(',');
+    let guid = fields.next()?.to_lowercase();
+    let name = fields.next()?.to_string();
+    let mut entries: HashMap<RawInput, Vec<MappingEntry>> = HashMap::new();
+
+    for field in fields {
+        let Some((target, source)) = field.split_once(':') else {
+            continue;
+        };
+
+        if target == "platform" {
+            if source != CURRENT_PLATFORM {
+                return None;
+            }
+            continue;
+        }
+
+        // Output half markers like +leftx
+        let (target, output_half) = match target.split_at_checked(1) {
+            Some(("+", target)) => (target, Some(true)),
+            Some(("-", target)) => (target, Some(false)),
+            _ => (target, None),
+        };
+        let Some(target) = parse_target(target, output_half) else {
+            continue;
+        };
+
+        let Some((raw, range, inverted)) = parse_source(source) else {
+            continue;
+        };
+
+        entries.entry(raw).or_default().push(MappingEntry {
+            target,
+            range,
+            inverted,
+        });
+    }
+
+    Some((guid, ControllerMapping { name, entries }))
+}
+
+fn parse_target(target: &str, output_half: Option<bool>) -> Option<MappingTarget> {
+    let button = |input| Some(MappingTarget::Button(input));
+    let stick = |negative, positive| match output_half {
+        Some(true) => Some(MappingTarget::Button(positive)),
+        Some(false) => Some(MappingTarget::Button(negative)),
+        None => Some(MappingTarget::Stick { negative, positive }),
+    };
+
+    // SDL names buttons by their position on an xbox controller, which matches our face button layout
+    match target {
+        "a" => button(GamepadInput::FPadDown),
+        "b" => button(GamepadInput::FPadRight),
+        "x" => button(GamepadInput::FPadLeft),
+        "y" => button(GamepadInput::FPadUp),
+        "back" => button(GamepadInput::Select),
+        "start" => button(GamepadInput::Start),
+        "guide" => button(GamepadInput::Mode),
+        "leftstick" => button(GamepadInput::LeftThumb),
+        "rightstick" => button(GamepadInput::RightThumb),
+        "leftshoulder" => button(GamepadInput::LeftTrigger),
+        "rightshoulder" => button(GamepadInput::RightTrigger),
+        "dpup" => button(GamepadInput::DPadUp),
+        "dpdown" => button(GamepadInput::DPadDown),
+        "dpleft" => button(GamepadInput::DPadLeft),
+        "dpright" => button(GamepadInput::DPadRight),
+        "lefttrigger" => Some(MappingTarget::Trigger(GamepadInput::LeftSecondaryTrigger)),
+        "righttrigger" => Some(MappingTarget::Trigger(GamepadInput::RightSecondaryTrigger)),
+        "leftx" => stick(GamepadInput::LeftStickLeft, GamepadInput::LeftStickRight),
+        "lefty" => stick(GamepadInput::LeftStickUp, GamepadInput::LeftStickDown),
+        "rightx" => stick(GamepadInput::RightStickLeft, GamepadInput::RightStickRight),
+        "righty" => stick(GamepadInput::RightStickUp, GamepadInput::RightStickDown),
+        // Paddles, touchpads and the misc button have nowhere to go
+        _ => None,
+    }
+}
+
+fn parse_source(source: &str) -> Option<(RawInput, AxisRange, bool)> {
+    let (source, inverted) = match source.strip_suffix('~') {
+        Some(source) => (source, true),
+        None => (source, false),
+    };
+    let (source, range) = match source.split_at_checked(1) {
+        Some(("+", source)) => (source, AxisRange::Positive),
+        Some(("-", source)) => (source, AxisRange::Negative),
+        _ => (source, AxisRange::Full),
+    };
+
+    let raw = match source.split_at_checked(1)? {
+        ("b", index) => RawInput::Button(index.parse().ok()?),
+        ("a", index) => RawInput::Axis(index.parse().ok()?),
+        ("h", hat) => {
+            let (index, mask) = hat.split_once('.')?;
+            RawInput::Hat(index.parse().ok()?, mask.parse().ok()?)
+        }
+        _ => return None,
+    };
+
+    Some((raw, range, inverted))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_and_translate() {
+        let mut database = ControllerDatabase::default();
+        database.parse(
+            "# Comment\n\
+             030000005e0400008e02000010010000,Test Pad,a:b0,dpup:h0.1,leftx:a0,lefty:a1~,+rightx:a3,lefttrigger:a2,\n",
+        );
+
+        let mapping = database.get("030000005E0400008E02000010010000").unwrap();
+        assert_eq!(mapping.name, "Test Pad");
+
+        assert_eq!(
+            mapping.translate(RawInput::Button(0), 1.0),
+            vec![(
+                Input::Gamepad(GamepadInput::FPadDown),
+                InputState::Digital(true)
+            )]
+        );
+        assert_eq!(
+            mapping.translate(RawInput::Hat(0, 1), 1.0),
+            vec![(
+                Input::Gamepad(GamepadInput::DPadUp),
+                InputState::Digital(true)
+            )]
+        );
+        assert_eq!(
+            mapping.translate(RawInput::Axis(0), -1.0),
+            vec![
+                (
+                    Input::Gamepad(GamepadInput::LeftStickLeft),
+                    InputState::Analog(1.0)
+                ),
+                (
+                    Input::Gamepad(GamepadInput::LeftStickRight),
+                    InputState::Analog(0.0)
+                ),
+            ]
+        );
+        // Inverted axis
+        assert_eq!(
+            mapping.translate(RawInput::Axis(1), 1.0)[0],
+            (
+                Input::Gamepad(GamepadInput::LeftStickUp),
+                InputState::Analog(1.0)
+            )
+        );
+        assert_eq!(
+            mapping.translate(RawInput::Axis(2), -1.0),
+            vec![(
+                Input::Gamepad(GamepadInput::LeftSecondaryTrigger),
+                InputState::Analog(0.0)
+            )]
+        );
+        assert!(mapping.translate(RawInput::Button(7), 1.0).is_empty());
+    }
+
+    #[test]
+    fn bundled_database_parses() {
+        let mut database = ControllerDatabase::default();
+        database.parse(BUNDLED_DATABASE);
+
+        // Mappings for other platforms are skipped, so all we can say is that nothing broke
+        #[cfg(target_os = "linux")]
+        assert!(!database.is_empty());
+    }
+}
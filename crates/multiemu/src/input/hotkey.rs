@@ -10,6 +10,14 @@ pub enum Hotkey {
     FastForward,
     LoadSnapshot,
     SaveSnapshot,
+    ToggleFullscreen,
+    ToggleStatsOverlay,
+    Screenshot,
+    ToggleRecording,
+    /// Steps back to the last rewind snapshot
+    Rewind,
+    /// Types the host clipboard into the machine's keyboard
+    Paste,
 }
 
 pub static DEFAULT_HOTKEYS: LazyLock<IndexMap<BTreeSet<Input>, Hotkey>> = LazyLock::new(|| {
@@ -62,6 +70,32 @@ pub static DEFAULT_HOTKEYS: LazyLock<IndexMap<BTreeSet<Input>, Hotkey>> = LazyLo
             [Input::Keyboard(KeyboardInput::F4)].into(),
             Hotkey::LoadSnapshot,
         ),
+        (
+            [Input::Keyboard(KeyboardInput::F11)].into(),
+            Hotkey::ToggleFullscreen,
+        ),
+        (
+            [Input::Keyboard(KeyboardInput::F5)].into(),
+            Hotkey::ToggleStatsOverlay,
+        ),
+        (
+            [Input::Keyboard(KeyboardInput::F12)].into(),
+            Hotkey::Screenshot,
+        ),
+        (
+            [Input::Keyboard(KeyboardInput::F10)].into(),
+            Hotkey::ToggleRecording,
+        ),
+        (
+            [
+                Input::Gamepad(GamepadInput::Mode),
+                Input::Gamepad(GamepadInput::LeftTrigger),
+            ]
+            .into(),
+            Hotkey::Rewind,
+        ),
+        ([Input::Keyboard(KeyboardInput::F6)].into(), Hotkey::Rewind),
+        ([Input::Keyboard(KeyboardInput::F8)].into(), Hotkey::Paste),
     ]
     .into()
 });
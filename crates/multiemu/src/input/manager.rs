@@ -4,9 +4,16 @@ use crate::{
     rom::system::GameSystem,
 };
 
-use super::{EmulatedGamepadId, GamepadId, Input, InputState};
+use super::{
+    controller_db::{RawInput, CONTROLLER_DATABASE},
+    profile::{find_profile, ControllerProfile, DeviceIdentity},
+    EmulatedGamepadId, GamepadId, Input, InputSampling, InputState,
+};
 use dashmap::DashMap;
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 #[derive(Debug)]
 /// Stores what each gamepad is cached to be at right now
@@ -20,56 +27,228 @@ pub struct InputManager {
     pub gamepad_types: HashMap<EmulatedGamepadTypeId, EmulatedGamepadMetadata>,
     emulated_gamepads: DashMap<EmulatedGamepadId, EmulatedGamepadState>,
     real_to_emulated_gamepad_mappings: DashMap<GamepadId, EmulatedGamepadId>,
+    /// Physical controllers currently plugged in, and the name of the profile they use
+    connected_devices: DashMap<GamepadId, (DeviceIdentity, String)>,
+    sampling: InputSampling,
+    /// Movies force [InputSampling::PerFrame] while they record or play back
+    movie_active: AtomicBool,
+    /// What each gamepad was at the start of the frame, only used when sampling per frame
+    latched: DashMap<EmulatedGamepadId, HashMap<Input, InputState>>,
 }
 
 impl InputManager {
     pub fn get_input(&self, port: EmulatedGamepadId, input: Input) -> InputState {
+        if self.sampling() == InputSampling::PerFrame {
+            return self
+                .latched
+                .get(&port)
+                .and_then(|state| state.get(&input).cloned())
+                .unwrap_or_default();
+        }
+
         self.emulated_gamepads
             .get(&port)
-            .unwrap()
-            .state
-            .get(&input)
-            .cloned()
+            .and_then(|gamepad| gamepad.state.get(&input).cloned())
             .unwrap_or_default()
     }
 
+    /// The policy in effect, which is always per frame while a movie is going
+    pub fn sampling(&self) -> InputSampling {
+        if self.movie_active.load(Ordering::Relaxed) {
+            InputSampling::PerFrame
+        } else {
+            self.sampling
+        }
+    }
+
+    pub fn set_sampling(&mut self, sampling: InputSampling) {
+        self.sampling = sampling;
+    }
+
+    /// A movie only replays the same if the input it records is exactly what each frame saw
+    pub fn set_movie_active(&self, movie_active: bool) {
+        self.movie_active.store(movie_active, Ordering::Relaxed);
+        // Otherwise the rest of this frame would see nothing pressed
+        self.latch();
+    }
+
+    /// Takes the input the rest of the frame will see, the machine calls this before every frame it runs
+    pub fn latch(&self) {
+        if self.sampling() != InputSampling::PerFrame {
+            return;
+        }
+
+        for gamepad in self.emulated_gamepads.iter() {
+            self.latched
+                .insert(*gamepad.key(), gamepad.value().state.clone());
+        }
+    }
+
+    /// Every emulated gamepad the machine has, in port order
+    pub fn emulated_gamepads(&self) -> Vec<(EmulatedGamepadId, EmulatedGamepadTypeId)> {
+        let mut emulated_gamepads: Vec<_> = self
+            .emulated_gamepads
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().kind.clone()))
+            .collect();
+        emulated_gamepads.sort_unstable_by_key(|(port, _)| *port);
+
+        emulated_gamepads
+    }
+
+    /// Controllers plugged in right now with a name to show the user
+    pub fn host_devices(&self) -> Vec<(GamepadId, String)> {
+        let mut host_devices: Vec<_> = self
+            .connected_devices
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().0.to_string()))
+            .collect();
+        host_devices.sort_unstable_by_key(|(id, _)| *id);
+
+        host_devices
+    }
+
+    pub fn real_to_emulated_mapping(&self, gamepad_id: GamepadId) -> Option<EmulatedGamepadId> {
+        self.real_to_emulated_gamepad_mappings
+            .get(&gamepad_id)
+            .map(|entry| *entry.value())
+    }
+
+    /// Unplugs a host device from whatever port it was driving
+    pub fn clear_real_to_emulated_mapping(&self, gamepad_id: GamepadId) {
+        self.real_to_emulated_gamepad_mappings.remove(&gamepad_id);
+    }
+
     pub fn insert_input(&self, system: GameSystem, id: GamepadId, input: Input, state: InputState) {
         let global_config = GLOBAL_CONFIG.read().unwrap();
+        let profile = self
+            .connected_devices
+            .get(&id)
+            .and_then(|entry| global_config.controller_profiles.get(&entry.value().1));
+        let input = profile.map_or(input, |profile| profile.correct_input(input));
 
         // Find out which real controller is hooked up to which emulated one
         if let Some(mut emulated_gamepad_state) = self
             .real_to_emulated_gamepad_mappings
             .get(&id)
-            .and_then(|entry| self.emulated_gamepads.get_mut(entry.key()))
+            .and_then(|entry| self.emulated_gamepads.get_mut(entry.value()))
         {
             let metadata = self
                 .gamepad_types
                 .get(&emulated_gamepad_state.kind)
                 .unwrap();
 
-            // Translate the input according to the global config
-            let Some(translated_input) = global_config
-                .gamepad_configs
-                .get(&system)
-                .and_then(|emulated_gamepad_infos| {
-                    emulated_gamepad_infos.get(&emulated_gamepad_state.kind)
+            // Translate the input according to the device profile, falling back on the global config
+            let Some(translated_input) = profile
+                .and_then(|profile| profile.binding(system, &emulated_gamepad_state.kind, input))
+                .or_else(|| {
+                    global_config
+                        .gamepad_configs
+                        .get(&system)
+                        .and_then(|emulated_gamepad_infos| {
+                            emulated_gamepad_infos.get(&emulated_gamepad_state.kind)
+                        })
+                        .and_then(|gamepad_specific_mappings| gamepad_specific_mappings.get(&input))
+                        .copied()
                 })
-                .and_then(|gamepad_specific_mappings| gamepad_specific_mappings.get(&input))
             else {
                 tracing::warn!("Unbound input {:?}", input);
                 return;
             };
 
-            if metadata.present_inputs.contains(translated_input) {
-                emulated_gamepad_state
-                    .state
-                    .insert(*translated_input, state);
+            if metadata.present_inputs.contains(&translated_input) {
+                emulated_gamepad_state.state.insert(translated_input, state);
             } else {
                 tracing::warn!("We have a bound from {:?} to {:?}, but emulated gamepad doesn't support this input", input, translated_input);
             }
         }
     }
 
+    /// Called when a controller is plugged in, so its profile follows it to whatever id it gets
+    ///
+    /// Devices without a profile get an empty one made for them, so the user has something to fill in
+    pub fn device_connected(&self, gamepad_id: GamepadId, device: DeviceIdentity) {
+        // The database reads the config when first touched so this has to happen before we lock it
+        if let Some(mapping) = device
+            .guid
+            .as_ref()
+            .and_then(|guid| CONTROLLER_DATABASE.get(guid))
+        {
+            tracing::info!(
+                "{} is known to the controller database as {}",
+                device,
+                mapping.name
+            );
+        }
+
+        let mut global_config = GLOBAL_CONFIG.write().unwrap();
+
+        let profile_name = match find_profile(&global_config.controller_profiles, &device) {
+            Some((profile_name, _)) => {
+                tracing::info!("Applying controller profile {} to {}", profile_name, device);
+
+                profile_name.clone()
+            }
+            None => {
+                tracing::info!("Creating controller profile for {}", device);
+
+                let profile_name = device.to_string();
+                global_config.controller_profiles.insert(
+                    profile_name.clone(),
+                    ControllerProfile {
+                        device: Some(device.clone()),
+                        ..Default::default()
+                    },
+                );
+
+                profile_name
+            }
+        };
+
+        self.connected_devices
+            .insert(gamepad_id, (device, profile_name));
+    }
+
+    pub fn device_disconnected(&self, gamepad_id: GamepadId) {
+        if let Some((_, (device, _))) = self.connected_devices.remove(&gamepad_id) {
+            tracing::info!("{} disconnected", device);
+        }
+    }
+
+    /// For backends that only know button and axis numbers, the controller database turns them into real inputs
+    pub fn insert_raw_input(
+        &self,
+        system: GameSystem,
+        id: GamepadId,
+        raw_input: RawInput,
+        value: f32,
+    ) {
+        let Some(mapping) = self
+            .connected_devices
+            .get(&id)
+            .and_then(|entry| entry.value().0.guid.clone())
+            .and_then(|guid| CONTROLLER_DATABASE.get(&guid))
+        else {
+            tracing::debug!(
+                "No controller mapping for gamepad {}, dropping {:?}",
+                id,
+                raw_input
+            );
+            return;
+        };
+
+        for (input, state) in mapping.translate(raw_input, value) {
+            self.insert_input(system, id, input, state);
+        }
+    }
+
+    /// Sets an input of an emulated gamepad directly, without going through any host bindings
+    pub fn set_emulated_input(&self, port: EmulatedGamepadId, input: Input, state: InputState) {
+        if let Some(mut gamepad) = self.emulated_gamepads.get_mut(&port) {
+            gamepad.state.insert(input, state);
+        }
+    }
+
     pub fn set_real_to_emulated_mapping(&self, gamepad_id: GamepadId, index: EmulatedGamepadId) {
         self.real_to_emulated_gamepad_mappings
             .insert(gamepad_id, index);
@@ -97,3 +276,45 @@ impl InputManager {
         self.gamepad_types.insert(kind, metadata);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::keyboard::KeyboardInput;
+
+    #[test]
+    fn per_frame_sampling_holds_input_until_latched() {
+        let mut input_manager = InputManager::default();
+        input_manager.register_emulated_gamepad(0, EmulatedGamepadTypeId::new("keyboard"));
+        input_manager.set_sampling(InputSampling::PerFrame);
+        let input = Input::Keyboard(KeyboardInput::KeyA);
+
+        input_manager.set_emulated_input(0, input, InputState::PRESSED);
+        assert_eq!(input_manager.get_input(0, input), InputState::RELEASED);
+
+        input_manager.latch();
+        assert_eq!(input_manager.get_input(0, input), InputState::PRESSED);
+
+        input_manager.set_emulated_input(0, input, InputState::RELEASED);
+        assert_eq!(input_manager.get_input(0, input), InputState::PRESSED);
+    }
+
+    #[test]
+    fn movies_force_per_frame_sampling() {
+        let mut input_manager = InputManager::default();
+        input_manager.register_emulated_gamepad(0, EmulatedGamepadTypeId::new("keyboard"));
+        let input = Input::Keyboard(KeyboardInput::KeyA);
+
+        input_manager.set_emulated_input(0, input, InputState::PRESSED);
+        assert_eq!(input_manager.sampling(), InputSampling::Continuous);
+        assert_eq!(input_manager.get_input(0, input), InputState::PRESSED);
+
+        input_manager.set_movie_active(true);
+        assert_eq!(input_manager.sampling(), InputSampling::PerFrame);
+        input_manager.set_emulated_input(0, input, InputState::RELEASED);
+        assert_eq!(input_manager.get_input(0, input), InputState::PRESSED);
+
+        input_manager.set_movie_active(false);
+        assert_eq!(input_manager.get_input(0, input), InputState::RELEASED);
+    }
+}
@@ -1,17 +1,23 @@
 use gamepad::GamepadInput;
 use keyboard::KeyboardInput;
+use pointer::PointerInput;
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 
+pub mod controller_db;
 pub mod gamepad;
 pub mod hotkey;
 pub mod keyboard;
 pub mod manager;
+pub mod paste;
+pub mod pointer;
+pub mod profile;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Input {
     Gamepad(GamepadInput),
     Keyboard(KeyboardInput),
+    Pointer(PointerInput),
 }
 
 impl Input {
@@ -19,9 +25,21 @@ impl Input {
         GamepadInput::iter()
             .map(Input::Gamepad)
             .chain(KeyboardInput::iter().map(Input::Keyboard))
+            .chain(PointerInput::iter().map(Input::Pointer))
     }
 }
 
+/// When the host input is handed to the machine
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputSampling {
+    /// Whatever the host has right now, the lowest latency
+    #[default]
+    Continuous,
+    /// Latched as the frame starts, like a game reading its pads in vblank, so a frame never sees input change
+    /// halfway through and the same input always plays out the same
+    PerFrame,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum InputState {
     /// 0 or 1
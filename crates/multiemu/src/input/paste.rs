@@ -0,0 +1,100 @@
+use super::{manager::InputManager, EmulatedGamepadId, Input, InputState};
+use std::collections::VecDeque;
+
+/// Types text into a machine a key at a time, holding each one down and letting go for a few frames so the
+/// keyboard scan has a chance to see it
+#[derive(Debug)]
+pub struct TextPaste {
+    keys: VecDeque<(EmulatedGamepadId, Vec<Input>)>,
+    /// Keys down right now
+    held: Option<(EmulatedGamepadId, Vec<Input>)>,
+    /// Frames left before the next key goes down or the held one comes up
+    frames: u32,
+    hold_frames: u32,
+}
+
+impl TextPaste {
+    /// Works out the keys for all of `text` up front, skipping characters the machine has no way to type
+    pub fn new(
+        text: &str,
+        hold_frames: u32,
+        character_keys: impl Fn(char) -> Option<(EmulatedGamepadId, Vec<Input>)>,
+    ) -> Self {
+        let keys = text
+            .chars()
+            // Windows line endings would otherwise press return twice
+            .filter(|character| *character != '\r')
+            .filter_map(|character| {
+                let keys = character_keys(character);
+                if keys.is_none() {
+                    tracing::warn!("Can't type {:?} on this machine, skipping it", character);
+                }
+                keys
+            })
+            .collect();
+
+        Self {
+            keys,
+            held: None,
+            frames: 0,
+            hold_frames: hold_frames.max(1),
+        }
+    }
+
+    /// Called once before every frame, returns false once everything has been typed
+    pub fn step(&mut self, input_manager: &InputManager) -> bool {
+        if self.frames != 0 {
+            self.frames -= 1;
+            return true;
+        }
+
+        if let Some((gamepad_id, inputs)) = self.held.take() {
+            for input in inputs {
+                input_manager.set_emulated_input(gamepad_id, input, InputState::RELEASED);
+            }
+            self.frames = self.hold_frames - 1;
+            return true;
+        }
+
+        let Some((gamepad_id, inputs)) = self.keys.pop_front() else {
+            return false;
+        };
+        for input in inputs.iter() {
+            input_manager.set_emulated_input(gamepad_id, *input, InputState::PRESSED);
+        }
+        self.held = Some((gamepad_id, inputs));
+        self.frames = self.hold_frames - 1;
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{component::input::EmulatedGamepadTypeId, input::keyboard::KeyboardInput};
+
+    #[test]
+    fn keys_are_held_then_released() {
+        let mut input_manager = InputManager::default();
+        input_manager.register_emulated_gamepad(0, EmulatedGamepadTypeId::new("Keyboard"));
+        let key = |character| match character {
+            'a' => Some((0, vec![Input::Keyboard(KeyboardInput::KeyA)])),
+            _ => None,
+        };
+        let held = |input_manager: &InputManager| {
+            input_manager
+                .get_input(0, Input::Keyboard(KeyboardInput::KeyA))
+                .as_digital()
+        };
+
+        let mut paste = TextPaste::new("a?a", 2, key);
+        let mut frames = Vec::new();
+        while paste.step(&input_manager) {
+            frames.push(held(&input_manager));
+        }
+
+        // The same key twice needs letting go in between
+        assert_eq!(frames, [true, true, false, false, true, true, false, false]);
+    }
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use strum::EnumIter;
+
+/// A mouse or touchscreen, for light guns and other things aimed at the screen
+///
+/// Positions are analog inputs across the emulated display, so they don't care about window size or scaling
+#[derive(
+    Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, EnumIter,
+)]
+pub enum PointerInput {
+    /// 0.0 is the left edge of the display, 1.0 the right
+    X,
+    /// 0.0 is the top edge of the display, 1.0 the bottom
+    Y,
+    /// Whether the pointer is over the display at all, aiming off screen is how some games reload
+    OnScreen,
+    Primary,
+    Secondary,
+}
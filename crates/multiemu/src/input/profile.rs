@@ -0,0 +1,177 @@
+use super::{gamepad::GamepadInput, Input};
+use crate::{component::input::EmulatedGamepadTypeId, rom::system::GameSystem};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt::Display};
+use strum::EnumIter;
+
+/// What we know about a physical controller that stays the same between sessions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceIdentity {
+    /// SDL style GUID, not every backend can provide one
+    pub guid: Option<String>,
+    pub name: String,
+}
+
+impl Display for DeviceIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.guid {
+            Some(guid) => write!(f, "{} ({})", self.name, guid),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumIter,
+    strum::Display,
+)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+}
+
+impl GamepadAxis {
+    /// Swaps the direction of a stick input if it is on this axis
+    pub fn invert(&self, input: GamepadInput) -> GamepadInput {
+        match (self, input) {
+            (GamepadAxis::LeftStickX, GamepadInput::LeftStickLeft) => GamepadInput::LeftStickRight,
+            (GamepadAxis::LeftStickX, GamepadInput::LeftStickRight) => GamepadInput::LeftStickLeft,
+            (GamepadAxis::LeftStickY, GamepadInput::LeftStickUp) => GamepadInput::LeftStickDown,
+            (GamepadAxis::LeftStickY, GamepadInput::LeftStickDown) => GamepadInput::LeftStickUp,
+            (GamepadAxis::RightStickX, GamepadInput::RightStickLeft) => {
+                GamepadInput::RightStickRight
+            }
+            (GamepadAxis::RightStickX, GamepadInput::RightStickRight) => {
+                GamepadInput::RightStickLeft
+            }
+            (GamepadAxis::RightStickY, GamepadInput::RightStickUp) => GamepadInput::RightStickDown,
+            (GamepadAxis::RightStickY, GamepadInput::RightStickDown) => GamepadInput::RightStickUp,
+            (_, input) => input,
+        }
+    }
+}
+
+/// Bindings for one specific physical controller, used instead of the global bindings when it is plugged in
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ControllerProfile {
+    pub device: Option<DeviceIdentity>,
+    #[serde(default)]
+    pub bindings: IndexMap<GameSystem, IndexMap<EmulatedGamepadTypeId, IndexMap<Input, Input>>>,
+    #[serde(default)]
+    pub inverted_axes: BTreeSet<GamepadAxis>,
+}
+
+impl ControllerProfile {
+    /// Applies axis inversion to a raw input coming from the device
+    pub fn correct_input(&self, input: Input) -> Input {
+        match input {
+            Input::Gamepad(gamepad_input) => Input::Gamepad(
+                self.inverted_axes
+                    .iter()
+                    .fold(gamepad_input, |input, axis| axis.invert(input)),
+            ),
+            input => input,
+        }
+    }
+
+    pub fn binding(
+        &self,
+        system: GameSystem,
+        kind: &EmulatedGamepadTypeId,
+        input: Input,
+    ) -> Option<Input> {
+        self.bindings
+            .get(&system)
+            .and_then(|emulated_gamepad_infos| emulated_gamepad_infos.get(kind))
+            .and_then(|gamepad_specific_mappings| gamepad_specific_mappings.get(&input))
+            .copied()
+    }
+}
+
+/// Finds the profile for a device, an exact GUID match wins over a matching name
+///
+/// Profiles are keyed by a user editable name so the same model of controller can share one
+pub fn find_profile<'a>(
+    profiles: &'a IndexMap<String, ControllerProfile>,
+    device: &DeviceIdentity,
+) -> Option<(&'a String, &'a ControllerProfile)> {
+    profiles
+        .iter()
+        .find(|(_, profile)| {
+            profile.device.as_ref().is_some_and(|profile_device| {
+                profile_device.guid.is_some() && profile_device.guid == device.guid
+            })
+        })
+        .or_else(|| {
+            profiles.iter().find(|(_, profile)| {
+                profile
+                    .device
+                    .as_ref()
+                    .is_some_and(|profile_device| profile_device.name == device.name)
+            })
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn guid_beats_name() {
+        let dualshock = DeviceIdentity {
+            guid: Some("030000004c050000c405000000010000".to_string()),
+            name: "Wireless Controller".to_string(),
+        };
+        let profiles = IndexMap::from([
+            (
+                "By name".to_string(),
+                ControllerProfile {
+                    device: Some(DeviceIdentity {
+                        guid: None,
+                        name: "Wireless Controller".to_string(),
+                    }),
+                    ..Default::default()
+                },
+            ),
+            (
+                "By guid".to_string(),
+                ControllerProfile {
+                    device: Some(dualshock.clone()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+
+        assert_eq!(find_profile(&profiles, &dualshock).unwrap().0, "By guid");
+    }
+
+    #[test]
+    fn axis_inversion() {
+        let profile = ControllerProfile {
+            inverted_axes: BTreeSet::from([GamepadAxis::LeftStickY]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            profile.correct_input(Input::Gamepad(GamepadInput::LeftStickUp)),
+            Input::Gamepad(GamepadInput::LeftStickDown)
+        );
+        assert_eq!(
+            profile.correct_input(Input::Gamepad(GamepadInput::LeftStickLeft)),
+            Input::Gamepad(GamepadInput::LeftStickLeft)
+        );
+    }
+}
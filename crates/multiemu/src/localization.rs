@@ -0,0 +1,217 @@
+use crate::config::{GLOBAL_CONFIG, STORAGE_DIRECTORY};
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use std::{
+    fs::{read_dir, read_to_string},
+    path::PathBuf,
+    sync::{LazyLock, RwLock},
+};
+use unic_langid::LanguageIdentifier;
+
+/// Language every string is written in first, and what anything missing from a translation falls back to
+pub const FALLBACK_LANGUAGE: &str = "en-US";
+
+/// Translations shipped inside the binary
+const BUNDLED_LANGUAGES: &[(&str, &str)] =
+    &[(FALLBACK_LANGUAGE, include_str!("../locales/en-US/main.ftl"))];
+
+/// Where community translations can be dropped in as `<language>.ftl` without rebuilding
+pub static TRANSLATION_DIRECTORY: LazyLock<PathBuf> =
+    LazyLock::new(|| STORAGE_DIRECTORY.join("locales"));
+
+/// The strings for the language currently picked, rebuilt by [set_language]
+static LOCALIZATION: LazyLock<RwLock<Localization>> = LazyLock::new(|| {
+    RwLock::new(Localization::new(
+        GLOBAL_CONFIG.read().unwrap().language.as_deref(),
+    ))
+});
+
+/// Looks up a message by id, with optional named arguments
+///
+/// ```ignore
+/// tr!("menu-resume");
+/// tr!("database-watch", folder = folder.display().to_string());
+/// ```
+macro_rules! tr {
+    ($id:expr) => {
+        $crate::localization::translate($id, None)
+    };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        $crate::localization::translate($id, Some(&args))
+    }};
+}
+pub(crate) use tr;
+
+struct Localization {
+    /// Picked language first, then the fallback
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+impl Localization {
+    fn new(language: Option<&str>) -> Self {
+        let language = language
+            .map(str::to_string)
+            .or_else(system_language)
+            .unwrap_or_else(|| FALLBACK_LANGUAGE.to_string());
+
+        let mut bundles = Vec::new();
+
+        if language != FALLBACK_LANGUAGE {
+            match load_bundle(&language) {
+                Some(bundle) => bundles.push(bundle),
+                None => tracing::warn!(
+                    "No translation for {}, using {}",
+                    language,
+                    FALLBACK_LANGUAGE
+                ),
+            }
+        }
+
+        bundles.extend(load_bundle(FALLBACK_LANGUAGE));
+
+        Self { bundles }
+    }
+
+    fn translate(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        for bundle in &self.bundles {
+            let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) else {
+                continue;
+            };
+
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, args, &mut errors);
+
+            for error in errors {
+                tracing::warn!("Problem formatting message {}: {}", id, error);
+            }
+
+            return text.into_owned();
+        }
+
+        // Better to show something than nothing, and it makes the missing id easy to spot
+        tracing::warn!("No message for {}", id);
+        id.to_string()
+    }
+}
+
+fn load_bundle(language: &str) -> Option<FluentBundle<FluentResource>> {
+    let identifier: LanguageIdentifier = match language.parse() {
+        Ok(identifier) => identifier,
+        Err(error) => {
+            tracing::error!("{} is not a language identifier: {}", language, error);
+            return None;
+        }
+    };
+
+    // A file on disk overrides the bundled copy, so translators can check their work without rebuilding
+    let source = read_to_string(TRANSLATION_DIRECTORY.join(format!("{}.ftl", language)))
+        .ok()
+        .or_else(|| {
+            BUNDLED_LANGUAGES
+                .iter()
+                .find(|(bundled, _)| *bundled == language)
+                .map(|(_, source)| source.to_string())
+        })?;
+
+    let resource = FluentResource::try_new(source).unwrap_or_else(|(resource, errors)| {
+        for error in errors {
+            tracing::error!("Problem parsing {} translation: {}", language, error);
+        }
+
+        // Whatever parsed is still usable
+        resource
+    });
+
+    let mut bundle = FluentBundle::new_concurrent(vec![identifier]);
+    // egui has no bidi support and would draw the isolation marks as boxes
+    bundle.set_use_isolating(false);
+
+    if let Err(errors) = bundle.add_resource(resource) {
+        for error in errors {
+            tracing::error!("Problem loading {} translation: {}", language, error);
+        }
+    }
+
+    Some(bundle)
+}
+
+/// The user's language from the environment, like "de-DE" out of "de_DE.UTF-8"
+fn system_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|variable| std::env::var(variable).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+        .and_then(|value| value.split('.').next().map(|value| value.replace('_', "-")))
+}
+
+pub fn translate(id: &str, args: Option<&FluentArgs>) -> String {
+    LOCALIZATION.read().unwrap().translate(id, args)
+}
+
+/// Switches every string looked up from now on to this language, None for the system's
+pub fn set_language(language: Option<&str>) {
+    *LOCALIZATION.write().unwrap() = Localization::new(language);
+}
+
+/// Languages that can be picked, bundled ones and any found in [TRANSLATION_DIRECTORY]
+pub fn available_languages() -> Vec<String> {
+    let mut languages: Vec<_> = BUNDLED_LANGUAGES
+        .iter()
+        .map(|(language, _)| language.to_string())
+        .collect();
+
+    if let Ok(entries) = read_dir(TRANSLATION_DIRECTORY.as_path()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.extension().is_some_and(|extension| extension == "ftl") {
+                if let Some(language) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    if !languages.iter().any(|known| known == language) {
+                        languages.push(language.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    languages.sort();
+    languages
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_english() {
+        let localization = Localization::new(Some("xx-XX"));
+        assert_eq!(localization.bundles.len(), 1);
+
+        assert_eq!(localization.translate("menu-resume", None), "Resume");
+        assert_eq!(
+            localization.translate("no-such-message", None),
+            "no-such-message"
+        );
+
+        let mut args = FluentArgs::new();
+        args.set("label", "Hashing");
+        args.set("done", 3);
+        args.set("total", 10);
+        assert_eq!(
+            localization.translate("progress-count", Some(&args)),
+            "Hashing (3/10)"
+        );
+    }
+
+    #[test]
+    fn bundled_languages_parse() {
+        for (language, source) in BUNDLED_LANGUAGES {
+            assert!(
+                FluentResource::try_new(source.to_string()).is_ok(),
+                "{} has syntax errors",
+                language
+            );
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use crate::config::GLOBAL_CONFIG;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    backtrace::Backtrace,
+    collections::VecDeque,
+    fmt::{Debug, Display},
+    panic::PanicHookInfo,
+    sync::{LazyLock, Mutex, OnceLock},
+    time::SystemTime,
+};
+use strum::{Display, EnumIter};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    Event, Subscriber,
+};
+use tracing_subscriber::{
+    filter::Targets,
+    layer::{Context, SubscriberExt},
+    reload,
+    util::SubscriberInitExt,
+    Layer, Registry,
+};
+
+/// How many log lines the in app viewer and bug reports keep
+const LOG_BUFFER_CAPACITY: usize = 2000;
+/// How many machine events bug reports keep, these are rare compared to log lines
+const EVENT_TRACE_CAPACITY: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => LevelFilter::OFF,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub time: SystemTime,
+    pub level: tracing::Level,
+    /// Module path the event came from, like "multiemu::machine"
+    pub target: String,
+    pub message: String,
+}
+
+/// The most recent log lines, oldest first
+pub static LOG_BUFFER: LazyLock<Mutex<VecDeque<LogRecord>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(LOG_BUFFER_CAPACITY)));
+
+/// Things that happened to the running machine, like states loaded or peripherals attached, oldest first
+///
+/// Kept apart from the log so they survive a noisy log level pushing everything else out
+pub static EVENT_TRACE: LazyLock<Mutex<VecDeque<LogRecord>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::with_capacity(EVENT_TRACE_CAPACITY)));
+
+/// The last panic the hook saw, for the crash report written once the runtime catches it
+static LAST_PANIC: Mutex<Option<PanicRecord>> = Mutex::new(None);
+
+/// What a panic said and where, captured before the stack unwinds
+#[derive(Debug, Clone)]
+pub struct PanicRecord {
+    pub message: String,
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: String,
+}
+
+impl Display for PanicRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Thread {} panicked",
+            self.thread.as_deref().unwrap_or("<unnamed>")
+        )?;
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+static FILTER_HANDLE: OnceLock<reload::Handle<Targets, Registry>> = OnceLock::new();
+/// Levels given on the command line, kept out of the config so they only last for this run
+static CLI_DIRECTIVES: OnceLock<Vec<(Option<String>, LogLevel)>> = OnceLock::new();
+
+/// Sets up logging to the terminal and the in app buffer
+///
+/// `RUST_LOG` is the base, the config goes over it and the command line over both
+pub fn init(cli_directives: Vec<(Option<String>, LogLevel)>) {
+    let _ = CLI_DIRECTIVES.set(cli_directives);
+
+    let (filter, handle) = reload::Layer::new(current_targets());
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .with(RingBufferLayer)
+        .init();
+
+    let _ = FILTER_HANDLE.set(handle);
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        *LAST_PANIC.lock().unwrap_or_else(|error| error.into_inner()) =
+            Some(PanicRecord::new(info));
+        default_hook(info);
+    }));
+}
+
+impl PanicRecord {
+    fn new(info: &PanicHookInfo<'_>) -> Self {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_string());
+
+        Self {
+            message,
+            location: info.location().map(ToString::to_string),
+            thread: std::thread::current().name().map(str::to_string),
+            backtrace: Backtrace::force_capture().to_string(),
+        }
+    }
+}
+
+/// The panic caught most recently, cleared so an old one doesn't end up in the next report
+pub fn take_panic() -> Option<PanicRecord> {
+    LAST_PANIC
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .take()
+}
+
+/// Notes something that happened to the machine in the event trace, and in the log at debug level
+pub fn trace_event(message: impl Display) {
+    let message = message.to_string();
+    tracing::debug!("{}", message);
+
+    let mut event_trace = EVENT_TRACE.lock().unwrap();
+
+    if event_trace.len() == EVENT_TRACE_CAPACITY {
+        event_trace.pop_front();
+    }
+
+    event_trace.push_back(LogRecord {
+        time: SystemTime::now(),
+        level: tracing::Level::INFO,
+        target: "event".to_string(),
+        message,
+    });
+}
+
+/// Applies the log levels in the config, for after they were changed
+pub fn reload_levels() {
+    if let Some(handle) = FILTER_HANDLE.get() {
+        if let Err(error) = handle.reload(current_targets()) {
+            tracing::error!("Could not change log levels: {}", error);
+        }
+    }
+}
+
+fn current_targets() -> Targets {
+    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+    let environment: Vec<_> = std::env::var("RUST_LOG")
+        .map(|directives| {
+            directives
+                .split(',')
+                .filter_map(|directive| parse_directive(directive.trim()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // The configured default always has a value, so it goes under RUST_LOG or RUST_LOG could never change it
+    targets(
+        std::iter::once((None, global_config_guard.log_level))
+            .chain(environment)
+            .chain(
+                global_config_guard
+                    .module_log_levels
+                    .iter()
+                    .map(|(module, level)| (Some(module.clone()), *level)),
+            )
+            .chain(CLI_DIRECTIVES.get().into_iter().flatten().cloned()),
+    )
+}
+
+/// Later directives replace earlier ones for the same module
+fn targets(directives: impl IntoIterator<Item = (Option<String>, LogLevel)>) -> Targets {
+    let mut default = LogLevel::default();
+    let mut modules = IndexMap::new();
+
+    for (module, level) in directives {
+        match module {
+            Some(module) => {
+                modules.insert(module, level);
+            }
+            None => default = level,
+        }
+    }
+
+    Targets::new().with_default(default).with_targets(modules)
+}
+
+/// Parses "module=level" or a bare "level" for the default, like RUST_LOG but without the fancy parts
+pub fn parse_directive(directive: &str) -> Option<(Option<String>, LogLevel)> {
+    let (module, level) = match directive.split_once('=') {
+        Some((module, level)) => (Some(module.to_string()), level),
+        None => (None, directive),
+    };
+
+    let level = match level.to_ascii_lowercase().as_str() {
+        "off" => LogLevel::Off,
+        "error" => LogLevel::Error,
+        "warn" => LogLevel::Warn,
+        "info" => LogLevel::Info,
+        "debug" => LogLevel::Debug,
+        "trace" => LogLevel::Trace,
+        _ => return None,
+    };
+
+    Some((module, level))
+}
+
+struct RingBufferLayer;
+
+impl<S: Subscriber> Layer<S> for RingBufferLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let mut log_buffer = LOG_BUFFER.lock().unwrap();
+
+        if log_buffer.len() == LOG_BUFFER_CAPACITY {
+            log_buffer.pop_front();
+        }
+
+        log_buffer.push_back(LogRecord {
+            time: SystemTime::now(),
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_string(),
+            message: visitor.message,
+        });
+    }
+}
+
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.message
+                .push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// Everything we know that would help someone debug a problem, zipped up
+///
+/// `panic` is set when the report is written after a component panicked, the save state is taken on a best effort
+/// basis then since the machine may have been left half way through something
+#[cfg(platform_desktop)]
+pub fn write_bug_report(
+    path: impl AsRef<std::path::Path>,
+    machine: Option<&crate::machine::Machine>,
+    panic: Option<&PanicRecord>,
+    include_state: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::{
+        fs::File,
+        io::Write,
+        panic::{catch_unwind, AssertUnwindSafe},
+    };
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let options = SimpleFileOptions::default();
+    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+
+    zip.start_file("report.txt", options)?;
+    writeln!(zip, "Version: {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(
+        zip,
+        "Platform: {} {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::FAMILY
+    )?;
+    writeln!(zip, "Graphics: {}", global_config_guard.graphics_setting)?;
+    writeln!(zip, "Time: {:?}", SystemTime::now())?;
+    match panic {
+        Some(panic) => {
+            writeln!(zip, "Trigger: panic")?;
+            writeln!(zip, "{}", panic)?;
+            writeln!(zip, "\n{}", panic.backtrace)?;
+        }
+        None => writeln!(zip, "Trigger: user")?,
+    }
+
+    zip.start_file("log.txt", options)?;
+    write_records(&mut zip, &LOG_BUFFER)?;
+
+    zip.start_file("events.txt", options)?;
+    write_records(&mut zip, &EVENT_TRACE)?;
+
+    zip.start_file("config.ron", options)?;
+    zip.write_all(
+        ron::ser::to_string_pretty(&*global_config_guard, Default::default())?.as_bytes(),
+    )?;
+    drop(global_config_guard);
+
+    if let Some(machine) = machine {
+        zip.start_file("machine.txt", options)?;
+        writeln!(zip, "System: {}", machine.system)?;
+        writeln!(zip, "Time: {:?}", machine.clock.now())?;
+        writeln!(zip, "Frames: {}", machine.clock.frames())?;
+        writeln!(zip, "Fast boot: {}", machine.fast_boot)?;
+        for rom_id in machine.user_specified_roms.iter().flatten() {
+            writeln!(zip, "ROM: {}", rom_id)?;
+        }
+        for rom_warning in &machine.rom_warnings {
+            writeln!(zip, "ROM {}: {}", rom_warning.id, rom_warning.verification)?;
+        }
+        for missing_rom in &machine.missing_roms {
+            writeln!(
+                zip,
+                "Missing ROM {}: {}",
+                missing_rom.id, missing_rom.requirement
+            )?;
+        }
+        for (component_id, table) in machine.component_store.iter() {
+            writeln!(zip, "Component {:?}: {}", component_id, table.name)?;
+        }
+
+        if include_state {
+            // A component that panicked can leave its locks poisoned, which would take the report down with it
+            let mut state = Vec::new();
+            match catch_unwind(AssertUnwindSafe(|| machine.write_state(&mut state))) {
+                Ok(Ok(())) => {
+                    zip.start_file("machine.mss", options)?;
+                    zip.write_all(&state)?;
+                }
+                Ok(Err(error)) => {
+                    tracing::warn!("Leaving the save state out of the report: {}", error)
+                }
+                Err(_) => tracing::warn!(
+                    "Leaving the save state out of the report, the machine is broken"
+                ),
+            }
+        }
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+#[cfg(platform_desktop)]
+fn write_records(
+    writer: &mut impl std::io::Write,
+    records: &Mutex<VecDeque<LogRecord>>,
+) -> std::io::Result<()> {
+    for record in records.lock().unwrap().iter() {
+        writeln!(
+            writer,
+            "{:?} {} {}: {}",
+            record.time, record.level, record.target, record.message
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn directives() {
+        assert_eq!(parse_directive("debug"), Some((None, LogLevel::Debug)));
+        assert_eq!(
+            parse_directive("multiemu::memory=trace"),
+            Some((Some("multiemu::memory".to_string()), LogLevel::Trace))
+        );
+        assert_eq!(parse_directive("multiemu::memory=loud"), None);
+    }
+
+    #[test]
+    fn later_directives_win() {
+        let targets = targets([
+            (None, LogLevel::Info),
+            (Some("multiemu::memory".to_string()), LogLevel::Trace),
+            (None, LogLevel::Warn),
+            (Some("multiemu::memory".to_string()), LogLevel::Error),
+        ]);
+
+        assert!(targets.would_enable("multiemu::machine", &tracing::Level::WARN));
+        assert!(!targets.would_enable("multiemu::machine", &tracing::Level::INFO));
+        assert!(targets.would_enable("multiemu::memory", &tracing::Level::ERROR));
+        assert!(!targets.would_enable("multiemu::memory", &tracing::Level::WARN));
+    }
+
+    #[test]
+    fn event_trace_is_bounded() {
+        for frame in 0..EVENT_TRACE_CAPACITY * 2 {
+            trace_event(format_args!("Frame {}: Bounded trace test", frame));
+        }
+
+        let event_trace = EVENT_TRACE.lock().unwrap();
+        assert_eq!(event_trace.len(), EVENT_TRACE_CAPACITY);
+        // Other tests building machines can slip events in, but ours were the most recent batch
+        assert!(event_trace.iter().any(|record| record.message
+            == format!("Frame {}: Bounded trace test", EVENT_TRACE_CAPACITY * 2 - 1)));
+    }
+}
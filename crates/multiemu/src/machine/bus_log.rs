@@ -0,0 +1,279 @@
+use crate::{
+    component::{memory::MemoryComponent, ComponentId},
+    memory::AddressSpaceId,
+};
+use rangemap::RangeMap;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum BusLogError {
+    #[error("Could not access bus log {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Could not write bus log: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error("Could not read bus log: {0}")]
+    Decode(#[from] rmp_serde::decode::Error),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusAccessKind {
+    Read,
+    Write,
+}
+
+/// One access that went through the memory translation table
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BusAccess {
+    pub kind: BusAccessKind,
+    pub address_space: AddressSpaceId,
+    pub address: usize,
+    /// What was read or written
+    #[serde_as(as = "Bytes")]
+    pub data: Vec<u8>,
+    /// Component the scheduler was running, None for accesses from outside it like the debugger
+    pub source: Option<ComponentId>,
+}
+
+/// Appends accesses to a file as they happen, see [crate::memory::MemoryTranslationTable::start_bus_log]
+///
+/// Entries are packed msgpack one after the other, so a log cut short by a crash is still readable up to that point
+#[derive(Debug)]
+pub struct BusLogWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    failed: bool,
+}
+
+impl BusLogWriter {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, BusLogError> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path).map_err(|error| BusLogError::Io {
+            path: path.clone(),
+            error,
+        })?;
+
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+            failed: false,
+        })
+    }
+
+    /// Failures are only reported once, a log is not worth stopping the machine over
+    pub fn record(&mut self, access: &BusAccess) {
+        if self.failed {
+            return;
+        }
+
+        if let Err(error) = rmp_serde::encode::write(&mut self.writer, access) {
+            tracing::error!("Stopped writing bus log {}: {}", self.path.display(), error);
+            self.failed = true;
+        }
+    }
+
+    pub fn finish(mut self) -> Result<(), BusLogError> {
+        self.writer.flush().map_err(|error| BusLogError::Io {
+            path: self.path.clone(),
+            error,
+        })
+    }
+}
+
+/// Reads a log back one access at a time
+#[derive(Debug)]
+pub struct BusLogReader<R: Read> {
+    reader: R,
+}
+
+impl BusLogReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BusLogError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| BusLogError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R: Read> BusLogReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: Read> Iterator for BusLogReader<R> {
+    type Item = Result<BusAccess, BusLogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match rmp_serde::decode::from_read(&mut self.reader) {
+            Ok(access) => Some(Ok(access)),
+            // Running out between entries is just the end of the log
+            Err(rmp_serde::decode::Error::InvalidMarkerRead(error))
+                if error.kind() == ErrorKind::UnexpectedEof =>
+            {
+                None
+            }
+            Err(error) => Some(Err(error.into())),
+        }
+    }
+}
+
+/// A read that came back different when replayed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayMismatch {
+    /// Position of the access in the log
+    pub index: usize,
+    pub access: BusAccess,
+    pub replayed: Vec<u8>,
+}
+
+/// Feeds the logged accesses that land inside a range to one component on its own, in their original order
+///
+/// Writes are replayed as is, reads are compared with what was logged. Meant for checking a new mapper or PPU
+/// against a log from a known good run without bringing up the rest of the machine
+pub fn replay(
+    log: impl IntoIterator<Item = Result<BusAccess, BusLogError>>,
+    component: &dyn MemoryComponent,
+    address_space: AddressSpaceId,
+    range: Range<usize>,
+) -> Result<Vec<ReplayMismatch>, BusLogError> {
+    let mut mismatches = Vec::new();
+
+    for (index, access) in log.into_iter().enumerate() {
+        let access = access?;
+
+        if access.address_space != address_space
+            || access.address < range.start
+            || access.address + access.data.len() > range.end
+        {
+            continue;
+        }
+
+        match access.kind {
+            BusAccessKind::Read => {
+                let mut replayed = vec![0; access.data.len()];
+                component.read_memory(
+                    access.address,
+                    &mut replayed,
+                    address_space,
+                    &mut RangeMap::default(),
+                );
+
+                if replayed != access.data {
+                    mismatches.push(ReplayMismatch {
+                        index,
+                        access,
+                        replayed,
+                    });
+                }
+            }
+            BusAccessKind::Write => {
+                component.write_memory(
+                    access.address,
+                    &access.data,
+                    address_space,
+                    &mut RangeMap::default(),
+                );
+            }
+        }
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        definitions::misc::memory::standard::{
+            StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents,
+        },
+        machine::Machine,
+        rom::{manager::RomManager, system::GameSystem},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("multiemu-bus-log-test-{}", std::process::id()));
+        let memory = || StandardMemoryConfig {
+            max_word_size: 2,
+            readable: true,
+            writable: true,
+            assigned_range: 0..16,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value: 0 },
+        };
+
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .build_component::<StandardMemory>(memory());
+        let machine = machine.build();
+        let memory_translation_table = &machine.memory_translation_table;
+
+        memory_translation_table.start_bus_log(BusLogWriter::create(&path).unwrap());
+        memory_translation_table.write(4, &[1, 2], 0).unwrap();
+        memory_translation_table.read(4, &mut [0; 2], 0).unwrap();
+        memory_translation_table
+            .stop_bus_log()
+            .unwrap()
+            .finish()
+            .unwrap();
+
+        let log: Vec<_> = BusLogReader::open(&path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[1].kind, BusAccessKind::Read);
+        assert_eq!(log[1].data, [1, 2]);
+
+        // A fresh copy of the memory behaves the same
+        let (machine, fresh) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .build_component::<StandardMemory>(memory());
+        let fresh = machine.get_component::<StandardMemory>(fresh).unwrap();
+        let mismatches = replay(log.iter().cloned().map(Ok), fresh.as_ref(), 0, 0..16).unwrap();
+        assert!(mismatches.is_empty());
+
+        // Skipping the write shows up as a mismatch on the read
+        let (machine, fresh) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .build_component::<StandardMemory>(memory());
+        let fresh = machine.get_component::<StandardMemory>(fresh).unwrap();
+        let mismatches = replay(
+            log.iter().skip(1).cloned().map(Ok),
+            fresh.as_ref(),
+            0,
+            0..16,
+        )
+        .unwrap();
+        assert_eq!(mismatches[0].replayed, [0, 0]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,80 @@
+use super::Machine;
+use serde::{Deserialize, Serialize};
+
+/// What a machine can do, so frontends know which affordances make sense for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachineCapabilities {
+    pub displays: usize,
+    pub audio_channels: usize,
+    pub controller_ports: usize,
+    /// Every component can be snapshotted and restored
+    pub savestates: bool,
+    /// Snapshots can be restored over and over without anything outside the machine noticing
+    pub rewind_safe: bool,
+    /// Some component can talk to another machine
+    pub link_cable: bool,
+}
+
+impl Machine {
+    pub fn capabilities(&self) -> MachineCapabilities {
+        let components = || {
+            self.component_store
+                .components()
+                .map(|component_table| &component_table.component)
+        };
+
+        let savestates = components().all(|component| component.supports_snapshots());
+
+        MachineCapabilities {
+            displays: self.display_components().count(),
+            audio_channels: components()
+                .map(|component| component.audio_channels())
+                .sum(),
+            controller_ports: self.input_manager.emulated_gamepads().len(),
+            savestates,
+            rewind_safe: savestates && components().all(|component| component.rewind_safe()),
+            link_cable: components().any(|component| component.has_link_port()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        definitions::misc::memory::standard::{
+            StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents,
+        },
+        rom::{manager::RomManager, system::GameSystem},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn bare_machine() {
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .build_component::<StandardMemory>(StandardMemoryConfig {
+            max_word_size: 1,
+            readable: true,
+            writable: true,
+            assigned_range: 0x00..0x100,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value: 0 },
+        });
+
+        assert_eq!(
+            machine.build().capabilities(),
+            MachineCapabilities {
+                displays: 0,
+                audio_channels: 0,
+                controller_ports: 0,
+                savestates: true,
+                rewind_safe: true,
+                link_cable: false,
+            }
+        );
+    }
+}
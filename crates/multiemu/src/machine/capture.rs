@@ -0,0 +1,146 @@
+use super::Machine;
+use crate::{
+    runtime::{
+        rendering_backend::DisplayComponentInitializationData,
+        video_sink::{save_png, VideoSinkError},
+    },
+    save::snapshot::{write_snapshot, SnapshotError},
+};
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CaptureError {
+    #[error("Machine is already on frame {current}, past the requested frame {target}")]
+    AlreadyPast { current: u64, target: u64 },
+    #[error("Machine was stopped by the watchdog on frame {0}")]
+    Stopped(u64),
+    #[error("Machine has no software display to take a screenshot of")]
+    NoDisplay,
+    #[error("Could not write screenshot: {0}")]
+    Screenshot(#[from] VideoSinkError),
+    #[error("Could not write savestate: {0}")]
+    Snapshot(#[from] SnapshotError),
+}
+
+/// What to write once a machine reaches an exact frame, for regression tests, bug reports and checking TAS runs
+#[derive(Debug, Clone, Default)]
+pub struct FrameCapture {
+    /// Frames completed before capturing, 0 captures the machine as it was built
+    pub frame: u64,
+    pub screenshot: Option<PathBuf>,
+    /// Written like a snapshot slot, so it can be copied into the snapshot directory and loaded
+    pub state: Option<PathBuf>,
+}
+
+impl Machine {
+    /// Switches every display to drawing into memory, for running without a window
+    pub fn render_in_software(&self) {
+        for display in self.display_components() {
+            display
+                .component
+                .set_display_data(DisplayComponentInitializationData::Software);
+        }
+    }
+
+    /// Runs whole frames until exactly `frame` of them have completed
+    ///
+    /// Frames always run for their full emulated length whatever the host does, so the same inputs reach the same
+    /// frame every time
+    pub fn run_to_frame(&mut self, frame: u64) -> Result<(), CaptureError> {
+        let current = self.clock.frames();
+        if current > frame {
+            return Err(CaptureError::AlreadyPast {
+                current,
+                target: frame,
+            });
+        }
+
+        while self.clock.frames() < frame {
+            self.run_frame();
+
+            if self.stopped() {
+                return Err(CaptureError::Stopped(self.clock.frames()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs to the frame the capture asks for and writes whatever it wants there
+    pub fn capture_at(&mut self, capture: &FrameCapture) -> Result<(), CaptureError> {
+        self.run_to_frame(capture.frame)?;
+
+        if let Some(path) = &capture.screenshot {
+            self.read_raw_frame(|frame| save_png(frame, path))
+                .ok_or(CaptureError::NoDisplay)??;
+            tracing::info!(
+                "Saved frame {} screenshot to {}",
+                capture.frame,
+                path.display()
+            );
+        }
+
+        if let Some(path) = &capture.state {
+            write_snapshot(self, path, 0)?;
+            tracing::info!("Saved frame {} state to {}", capture.frame, path.display());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        rom::{
+            id::RomId,
+            manager::RomManager,
+            system::{GameSystem, OtherSystem},
+        },
+        save::snapshot::SnapshotMetadata,
+    };
+    use std::{fs::File, io::BufReader, sync::Arc};
+
+    #[test]
+    fn captures_exact_frame() {
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        // Jumps to itself forever
+        let program = vec![0x12, 0x00];
+        let rom_id = RomId::from_read(&mut program.as_slice());
+        rom_manager.insert_bytes(rom_id, program);
+
+        let mut machine = Machine::from_system(
+            vec![rom_id],
+            rom_manager,
+            GameSystem::Other(OtherSystem::Chip8),
+        );
+        machine.render_in_software();
+
+        let directory =
+            std::env::temp_dir().join(format!("multiemu-capture-test-{}", std::process::id()));
+        let capture = FrameCapture {
+            frame: 30,
+            screenshot: Some(directory.join("frame.png")),
+            state: Some(directory.join("frame.snapshot")),
+        };
+        machine.capture_at(&capture).unwrap();
+        assert_eq!(machine.clock.frames(), 30);
+
+        assert!(image::open(capture.screenshot.as_ref().unwrap()).is_ok());
+        let mut reader = BufReader::new(File::open(capture.state.as_ref().unwrap()).unwrap());
+        let metadata: SnapshotMetadata = rmp_serde::decode::from_read(&mut reader).unwrap();
+        assert_eq!(metadata.timestamp.frame, 30);
+
+        assert!(matches!(
+            machine.run_to_frame(10),
+            Err(CaptureError::AlreadyPast {
+                current: 30,
+                target: 10
+            })
+        ));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+use crate::memory::{AddressSpaceId, MemoryTranslationTable};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::ops::Range;
+
+/// Something the fault injector did, kept so a failing run can be understood and repeated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InjectedFault {
+    BitFlip {
+        address_space: AddressSpaceId,
+        address: usize,
+        bit: u8,
+    },
+    /// The frontend missed a frame and the next one covered the time of both
+    DroppedFrame,
+    InterruptDelay {
+        cycles: u64,
+    },
+}
+
+/// Randomly breaks things while a machine runs, for checking cores and snapshots cope instead of corrupting saves
+///
+/// Seeded, so the same seed and settings against the same machine inject the same faults
+#[derive(Debug)]
+pub struct FaultInjection {
+    rng: StdRng,
+    frame: u64,
+    /// Chance each frame of one bit flipping somewhere in the targets
+    pub bit_flip_chance: f64,
+    /// Ranges that are fair game for bit flips, which should only be RAM
+    pub bit_flip_targets: Vec<(AddressSpaceId, Range<usize>)>,
+    pub frame_drop_chance: f64,
+    /// Chance of [FaultInjection::interrupt_delay] holding an interrupt back
+    pub interrupt_delay_chance: f64,
+    pub max_interrupt_delay: u64,
+    /// Everything injected so far, by the frame it happened on
+    pub injected: Vec<(u64, InjectedFault)>,
+}
+
+impl FaultInjection {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            frame: 0,
+            bit_flip_chance: 0.0,
+            bit_flip_targets: Vec::new(),
+            frame_drop_chance: 0.0,
+            interrupt_delay_chance: 0.0,
+            max_interrupt_delay: 0,
+            injected: Vec::new(),
+        }
+    }
+
+    pub fn bit_flips(
+        mut self,
+        chance: f64,
+        address_space: AddressSpaceId,
+        range: Range<usize>,
+    ) -> Self {
+        self.bit_flip_chance = chance;
+        self.bit_flip_targets.push((address_space, range));
+        self
+    }
+
+    pub fn frame_drops(mut self, chance: f64) -> Self {
+        self.frame_drop_chance = chance;
+        self
+    }
+
+    pub fn interrupt_delays(mut self, chance: f64, max_delay: u64) -> Self {
+        self.interrupt_delay_chance = chance;
+        self.max_interrupt_delay = max_delay;
+        self
+    }
+
+    /// Cycles an interrupt should arrive late by, for cores to ask when they raise one
+    pub fn interrupt_delay(&mut self) -> u64 {
+        if self.max_interrupt_delay == 0 || !self.rng.random_bool(self.interrupt_delay_chance) {
+            return 0;
+        }
+
+        let cycles = self.rng.random_range(1..=self.max_interrupt_delay);
+        self.injected
+            .push((self.frame, InjectedFault::InterruptDelay { cycles }));
+
+        cycles
+    }
+
+    /// Called before each frame, returning if the frame should be dropped
+    pub(super) fn before_frame(
+        &mut self,
+        frame: u64,
+        memory_translation_table: &MemoryTranslationTable,
+    ) -> bool {
+        self.frame = frame;
+
+        if !self.bit_flip_targets.is_empty() && self.rng.random_bool(self.bit_flip_chance) {
+            let (address_space, range) = self.bit_flip_targets
+                [self.rng.random_range(0..self.bit_flip_targets.len())]
+            .clone();
+            let address = self.rng.random_range(range);
+            let bit = self.rng.random_range(0..8);
+
+            // Goes through the bus so watches and logs see it like any other write
+            let mut value = [0];
+            if memory_translation_table
+                .preview(address, &mut value, address_space)
+                .is_ok()
+            {
+                value[0] ^= 1 << bit;
+                let _ = memory_translation_table.write(address, &value, address_space);
+
+                self.injected.push((
+                    frame,
+                    InjectedFault::BitFlip {
+                        address_space,
+                        address,
+                        bit,
+                    },
+                ));
+            }
+        }
+
+        let dropped = self.rng.random_bool(self.frame_drop_chance);
+        if dropped {
+            self.injected.push((frame, InjectedFault::DroppedFrame));
+        }
+
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        definitions::chip8::assembler::assemble,
+        machine::Machine,
+        rom::{
+            id::RomId,
+            manager::RomManager,
+            system::{GameSystem, OtherSystem},
+        },
+        runtime::rendering_backend::DisplayComponentInitializationData,
+    };
+    use std::{fs::File, sync::Arc};
+
+    #[test]
+    fn snapshots_survive_faults() {
+        let rom_path =
+            std::env::temp_dir().join(format!("multiemu-chaos-test-{}.ch8", std::process::id()));
+        std::fs::write(
+            &rom_path,
+            assemble("loop i := hex v0 sprite v1 v1 5 v0 += 1 v1 += 3 again").unwrap(),
+        )
+        .unwrap();
+
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let rom_id = RomId::from_read(&mut File::open(&rom_path).unwrap());
+        rom_manager.rom_paths.insert(rom_id, rom_path.clone());
+
+        let machine = || {
+            let machine = Machine::from_system(
+                vec![rom_id],
+                rom_manager.clone(),
+                GameSystem::Other(OtherSystem::Chip8),
+            );
+            for display in machine.display_components() {
+                display
+                    .component
+                    .set_display_data(DisplayComponentInitializationData::Software);
+            }
+            machine
+        };
+
+        let mut chaotic = machine();
+        chaotic.fault_injection = Some(
+            FaultInjection::new(0x5eed)
+                // Past the program, the core panics on instructions it can't decode
+                .bit_flips(1.0, 0, 0x400..0x1000)
+                .frame_drops(0.25),
+        );
+        for _ in 0..60 {
+            chaotic.run_frame();
+        }
+
+        let fault_injection = chaotic.fault_injection.as_ref().unwrap();
+        assert!(fault_injection
+            .injected
+            .iter()
+            .any(|(_, fault)| matches!(fault, InjectedFault::BitFlip { .. })));
+        assert!(fault_injection
+            .injected
+            .iter()
+            .any(|(_, fault)| *fault == InjectedFault::DroppedFrame));
+
+        // Whatever state the faults left behind still has to round trip
+        let mut encoded = Vec::new();
+        rmp_serde::encode::write_named(&mut encoded, &chaotic.state()).unwrap();
+
+        let mut restored = machine();
+        restored
+            .restore_state(rmp_serde::decode::from_slice(&encoded).unwrap())
+            .unwrap();
+
+        let mut reencoded = Vec::new();
+        rmp_serde::encode::write_named(&mut reencoded, &restored.state()).unwrap();
+        assert_eq!(encoded, reencoded);
+
+        std::fs::remove_file(&rom_path).unwrap();
+    }
+
+    #[test]
+    fn interrupt_delays_are_bounded() {
+        let mut fault_injection = FaultInjection::new(1).interrupt_delays(1.0, 4);
+
+        for _ in 0..32 {
+            assert!((1..=4).contains(&fault_injection.interrupt_delay()));
+        }
+        assert_eq!(fault_injection.injected.len(), 32);
+    }
+}
@@ -0,0 +1,134 @@
+use num::{rational::Ratio, ToPrimitive};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// Gaps between frames longer than this are considered the user pausing, not the host falling behind
+const PAUSE_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// A point in the emulated timeline
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct MachineTimestamp {
+    /// Total times the machine has been run by the frontend
+    pub frame: u64,
+    /// Total scheduler ticks, which are the finest unit of time a machine has
+    pub tick: u64,
+}
+
+#[derive(Debug, Default)]
+struct ClockState {
+    timestamp: MachineTimestamp,
+    wall_time: Duration,
+    /// Like wall time, but never rewound by loading a snapshot
+    session_time: Duration,
+    last_frame: Option<Instant>,
+}
+
+/// Keeps track of how much emulated time has passed for a machine
+///
+/// Shared with components, so things like movies and traces have a common timestamp
+#[derive(Debug, Default)]
+pub struct MachineClock {
+    tick_real_time: OnceLock<Ratio<u64>>,
+    state: Mutex<ClockState>,
+}
+
+impl MachineClock {
+    pub(super) fn set_tick_real_time(&self, tick_real_time: Ratio<u64>) {
+        let _ = self.tick_real_time.set(tick_real_time);
+    }
+
+    /// Called once per frame with how many ticks the scheduler went through
+    pub(super) fn advance(&self, ticks: u64) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        if let Some(last_frame) = state.last_frame {
+            let elapsed = now - last_frame;
+
+            if elapsed < PAUSE_THRESHOLD {
+                state.wall_time += elapsed;
+                state.session_time += elapsed;
+            }
+        }
+
+        state.last_frame = Some(now);
+        state.timestamp.frame += 1;
+        state.timestamp.tick += ticks;
+    }
+
+    pub fn now(&self) -> MachineTimestamp {
+        self.state.lock().unwrap().timestamp
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.now().frame
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.now().tick
+    }
+
+    /// How long the machine thinks it has been running
+    pub fn emulated_time(&self) -> Duration {
+        self.timestamp_to_duration(self.now())
+    }
+
+    /// How long the host has actually spent running the machine, ignoring pauses
+    pub fn wall_time(&self) -> Duration {
+        self.state.lock().unwrap().wall_time
+    }
+
+    /// How long the host has spent running the machine since it was built, whatever snapshots were loaded
+    pub fn session_time(&self) -> Duration {
+        self.state.lock().unwrap().session_time
+    }
+
+    /// Seconds the emulated time is ahead of the wall time, negative if it is behind
+    pub fn drift(&self) -> f64 {
+        self.emulated_time().as_secs_f64() - self.wall_time().as_secs_f64()
+    }
+
+    pub fn timestamp_to_duration(&self, timestamp: MachineTimestamp) -> Duration {
+        let Some(tick_real_time) = self.tick_real_time.get() else {
+            return Duration::ZERO;
+        };
+
+        Duration::from_secs_f64(
+            (*tick_real_time * timestamp.tick)
+                .to_f64()
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Used when loading a snapshot, wall time starts counting again from here
+    pub fn restore(&self, timestamp: MachineTimestamp) {
+        let mut state = self.state.lock().unwrap();
+
+        state.wall_time = self.timestamp_to_duration(timestamp);
+        state.timestamp = timestamp;
+        state.last_frame = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn emulated_time() {
+        let clock = MachineClock::default();
+        clock.set_tick_real_time(Ratio::new(1, 60));
+
+        for _ in 0..120 {
+            clock.advance(1);
+        }
+
+        assert_eq!(clock.frames(), 120);
+        assert_eq!(clock.emulated_time(), Duration::from_secs(2));
+    }
+}
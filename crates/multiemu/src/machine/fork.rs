@@ -0,0 +1,105 @@
+use super::Machine;
+use crate::runtime::rendering_backend::DisplayComponentInitializationData;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ForkError {
+    #[error("Machine was put together by hand, so there is no recipe to build a copy from")]
+    NoRecipe,
+    #[error("A component in this machine can't be snapshotted")]
+    Unsupported,
+}
+
+impl Machine {
+    /// An independent copy of this machine, for rollback and looking ahead
+    ///
+    /// The copy is rebuilt from the same ROMs and then has this machines state restored into it. It renders in
+    /// software, doesn't capture maps, and peripherals attached while running are not carried over
+    pub fn fork(&self) -> Result<Machine, ForkError> {
+        let Some(user_specified_roms) = &self.user_specified_roms else {
+            return Err(ForkError::NoRecipe);
+        };
+
+        if !self.capabilities().savestates {
+            return Err(ForkError::Unsupported);
+        }
+
+        let mut fork = Machine::from_system(
+            user_specified_roms.clone(),
+            self.rom_manager.clone(),
+            self.system,
+        );
+        fork.map_capture = None;
+        fork.play_session = None;
+
+        // Displays won't take their state until they have somewhere to put it
+        for display in fork.display_components() {
+            display
+                .component
+                .set_display_data(DisplayComponentInitializationData::Software);
+        }
+
+        fork.restore_state(self.state())
+            .expect("A machine's own state never needs converting");
+        fork.fast_boot = self.fast_boot;
+
+        Ok(fork)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        definitions::chip8::CHIP8_ADDRESS_SPACE_ID,
+        rom::{
+            id::RomId,
+            manager::RomManager,
+            system::{GameSystem, OtherSystem},
+        },
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn fork_is_independent() {
+        // Jumps to itself forever
+        let rom = vec![0x12, 0x00];
+
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let rom_id = RomId::from_read(&mut rom.as_slice());
+        rom_manager.insert_bytes(rom_id, rom);
+
+        let machine = Machine::from_system(
+            vec![rom_id],
+            rom_manager,
+            GameSystem::Other(OtherSystem::Chip8),
+        );
+        for display in machine.display_components() {
+            display
+                .component
+                .set_display_data(DisplayComponentInitializationData::Software);
+        }
+
+        let write_byte = |machine: &Machine, value| {
+            machine
+                .memory_translation_table
+                .write(0x300, &[value], CHIP8_ADDRESS_SPACE_ID)
+                .unwrap();
+        };
+        let read_byte = |machine: &Machine| {
+            let mut buffer = [0];
+            machine
+                .memory_translation_table
+                .preview(0x300, &mut buffer, CHIP8_ADDRESS_SPACE_ID)
+                .unwrap();
+            buffer[0]
+        };
+
+        write_byte(&machine, 0xaa);
+        let fork = machine.fork().unwrap();
+        write_byte(&machine, 0xbb);
+
+        assert_eq!(read_byte(&fork), 0xaa);
+        assert_eq!(read_byte(&machine), 0xbb);
+    }
+}
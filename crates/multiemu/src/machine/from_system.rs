@@ -1,21 +1,47 @@
-use super::Machine;
+use super::{
+    map_capture::MapCapture, trigger::TriggerEngine, validation::MachineConfigurationError, Machine,
+};
 use crate::{
-    definitions::{chip8::chip8_machine, nes::nes_machine},
+    config::GLOBAL_CONFIG,
+    definitions::{
+        c64::c64_machine,
+        chip8::{chip8_machine, xochip_machine},
+        msx::msx_machine,
+        nes::nes_machine,
+        sms::sms_machine,
+    },
     rom::{
         id::RomId,
         manager::RomManager,
-        system::{GameSystem, NintendoSystem, OtherSystem},
+        statistics::PlaySession,
+        system::{GameSystem, NintendoSystem, OtherSystem, SegaSystem},
     },
 };
 use std::sync::Arc;
 
+/// Systems whose definition knows how to get past its firmware, offered as fast boot options
+pub const FAST_BOOT_SYSTEMS: &[GameSystem] = &[GameSystem::Other(OtherSystem::Commodore64)];
+
 impl Machine {
+    /// For callers that know the system definition is sound, like tests and the command line
     pub fn from_system(
         user_specified_roms: Vec<RomId>,
         rom_manager: Arc<RomManager>,
         system: GameSystem,
     ) -> Machine {
-        match system {
+        Self::try_from_system(user_specified_roms, rom_manager, system)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_from_system(
+        user_specified_roms: Vec<RomId>,
+        rom_manager: Arc<RomManager>,
+        system: GameSystem,
+    ) -> Result<Machine, MachineConfigurationError> {
+        let triggers = TriggerEngine::load(&user_specified_roms);
+        let recipe = user_specified_roms.clone();
+
+        let mut machine = match system {
             GameSystem::Nintendo(NintendoSystem::GameBoy) => todo!(),
             GameSystem::Nintendo(NintendoSystem::GameBoyColor) => todo!(),
             GameSystem::Nintendo(NintendoSystem::GameBoyAdvance) => todo!(),
@@ -23,16 +49,38 @@ impl Machine {
                 nes_machine(user_specified_roms, rom_manager)
             }
             GameSystem::Nintendo(NintendoSystem::SuperNintendoEntertainmentSystem) => todo!(),
+            GameSystem::Sega(SegaSystem::MasterSystem) => {
+                sms_machine(user_specified_roms, rom_manager)
+            }
             GameSystem::Sega(sega_system) => todo!(),
             GameSystem::Sony(sony_system) => todo!(),
             GameSystem::Atari(atari_system) => todo!(),
             GameSystem::Other(OtherSystem::Chip8) => {
                 chip8_machine(user_specified_roms, rom_manager)
             }
+            GameSystem::Other(OtherSystem::XoChip) => {
+                xochip_machine(user_specified_roms, rom_manager)
+            }
+            GameSystem::Other(OtherSystem::Msx) => msx_machine(user_specified_roms, rom_manager),
+            GameSystem::Other(OtherSystem::Commodore64) => {
+                c64_machine(user_specified_roms, rom_manager)
+            }
             GameSystem::Unknown => todo!(),
             _ => {
                 unimplemented!("This system is not supported by this emulator");
             }
+        }?;
+
+        machine.triggers = triggers;
+        if GLOBAL_CONFIG.read().unwrap().map_capture {
+            machine.map_capture = recipe.first().copied().map(MapCapture::new);
+        }
+        if machine.bootable() {
+            machine.play_session = recipe.first().copied().map(|rom_id| {
+                PlaySession::start(machine.rom_manager.clone(), rom_id, machine.clock.clone())
+            });
         }
+        machine.user_specified_roms = Some(recipe);
+        Ok(machine)
     }
 }
@@ -0,0 +1,271 @@
+use super::Machine;
+use crate::{
+    rom::id::RomId,
+    runtime::rendering_backend::{DisplayComponentFramebuffer, DisplayComponentInitializationData},
+};
+use image::{ImageFormat, RgbaImage};
+use nalgebra::DMatrix;
+use palette::Srgba;
+use std::{fmt::Display, path::PathBuf};
+
+/// Set to anything to overwrite the golden images with whatever the machines draw now
+pub const BLESS_VARIABLE: &str = "MULTIEMU_BLESS";
+
+/// Runs a machine with no window for a number of frames and returns what its main display shows
+///
+/// Displays are switched to software rendering, so this works for any machine without a GPU
+pub fn run_headless(machine: &mut Machine, frames: u32) -> DMatrix<Srgba<u8>> {
+    for display in machine.display_components() {
+        display
+            .component
+            .set_display_data(DisplayComponentInitializationData::Software);
+    }
+
+    for _ in 0..frames {
+        machine.run_frame();
+    }
+
+    let display = machine
+        .display_components()
+        .next()
+        .expect("Machine has no display to compare");
+
+    let DisplayComponentFramebuffer::Software(framebuffer) = display.component.get_framebuffer()
+    else {
+        unreachable!("Display was set up for software rendering");
+    };
+
+    let frame = framebuffer.lock().unwrap();
+    frame.clone()
+}
+
+/// SHA-1 of the frame, row by row, for tests that only want to store a short string
+pub fn frame_hash(frame: &DMatrix<Srgba<u8>>) -> RomId {
+    let mut bytes = Vec::with_capacity(frame.len() * 4 + 8);
+    bytes.extend_from_slice(&(frame.nrows() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(frame.ncols() as u32).to_le_bytes());
+
+    for y in 0..frame.ncols() {
+        for x in 0..frame.nrows() {
+            let pixel = frame[(x, y)];
+            bytes.extend_from_slice(&[pixel.red, pixel.green, pixel.blue, pixel.alpha]);
+        }
+    }
+
+    RomId::from_read(&mut bytes.as_slice())
+}
+
+#[derive(Debug)]
+pub struct GoldenMismatch {
+    pub name: String,
+    /// Pixels that differ, or None if the frames aren't even the same size
+    pub differing_pixels: Option<usize>,
+    /// Where the actual frame and the difference image were written for looking at
+    pub artifacts: Vec<PathBuf>,
+}
+
+impl Display for GoldenMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.differing_pixels {
+            Some(differing_pixels) => write!(
+                f,
+                "{} differs from its golden image in {} pixels",
+                self.name, differing_pixels
+            )?,
+            None => write!(f, "{} is not the same size as its golden image", self.name)?,
+        }
+
+        for artifact in &self.artifacts {
+            write!(f, "\n  see {}", artifact.display())?;
+        }
+
+        write!(
+            f,
+            "\nrun with {}=1 if the change is intended",
+            BLESS_VARIABLE
+        )
+    }
+}
+
+/// Compares frames against images checked into the repository
+#[derive(Debug)]
+pub struct GoldenImages {
+    /// Where the expected images live
+    pub directory: PathBuf,
+    /// Where actual and difference images are written on failure
+    pub artifacts: PathBuf,
+    pub bless: bool,
+}
+
+impl Default for GoldenImages {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden"),
+            artifacts: std::env::temp_dir().join("multiemu-golden"),
+            bless: std::env::var_os(BLESS_VARIABLE).is_some(),
+        }
+    }
+}
+
+impl GoldenImages {
+    /// Checks a frame against `<name>.png`, writing it there instead when blessing
+    ///
+    /// A missing golden image is a failure too, so new tests have to be blessed on purpose
+    pub fn check(&self, name: &str, frame: &DMatrix<Srgba<u8>>) -> Result<(), GoldenMismatch> {
+        let golden_path = self.directory.join(name).with_extension("png");
+
+        if self.bless {
+            std::fs::create_dir_all(&self.directory).unwrap();
+            to_image(frame)
+                .save_with_format(&golden_path, ImageFormat::Png)
+                .unwrap();
+
+            return Ok(());
+        }
+
+        let golden = image::open(&golden_path)
+            .ok()
+            .map(|golden| from_image(&golden.into_rgba8()));
+
+        let differing_pixels = match &golden {
+            Some(golden) if golden.shape() == frame.shape() => {
+                let differing_pixels = golden
+                    .iter()
+                    .zip(frame.iter())
+                    .filter(|(expected, actual)| expected != actual)
+                    .count();
+
+                if differing_pixels == 0 {
+                    return Ok(());
+                }
+
+                Some(differing_pixels)
+            }
+            _ => None,
+        };
+
+        std::fs::create_dir_all(&self.artifacts).unwrap();
+        let mut artifacts = vec![self.artifacts.join(format!("{}.actual.png", name))];
+        to_image(frame)
+            .save_with_format(&artifacts[0], ImageFormat::Png)
+            .unwrap();
+
+        if let (Some(golden), Some(_)) = (&golden, differing_pixels) {
+            let diff_path = self.artifacts.join(format!("{}.diff.png", name));
+            to_image(&difference(golden, frame))
+                .save_with_format(&diff_path, ImageFormat::Png)
+                .unwrap();
+            artifacts.push(diff_path);
+        }
+
+        Err(GoldenMismatch {
+            name: name.to_string(),
+            differing_pixels,
+            artifacts,
+        })
+    }
+}
+
+/// Dimmed copy of the expected frame with differing pixels in red
+fn difference(golden: &DMatrix<Srgba<u8>>, frame: &DMatrix<Srgba<u8>>) -> DMatrix<Srgba<u8>> {
+    golden.zip_map(frame, |expected, actual| {
+        if expected == actual {
+            Srgba::new(
+                expected.red / 4,
+                expected.green / 4,
+                expected.blue / 4,
+                0xff,
+            )
+        } else {
+            Srgba::new(0xff, 0, 0, 0xff)
+        }
+    })
+}
+
+fn to_image(frame: &DMatrix<Srgba<u8>>) -> RgbaImage {
+    RgbaImage::from_fn(frame.nrows() as u32, frame.ncols() as u32, |x, y| {
+        let pixel = frame[(x as usize, y as usize)];
+        image::Rgba([pixel.red, pixel.green, pixel.blue, pixel.alpha])
+    })
+}
+
+fn from_image(image: &RgbaImage) -> DMatrix<Srgba<u8>> {
+    DMatrix::from_fn(image.width() as usize, image.height() as usize, |x, y| {
+        let [red, green, blue, alpha] = image.get_pixel(x as u32, y as u32).0;
+        Srgba::new(red, green, blue, alpha)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        definitions::chip8::assembler::assemble,
+        rom::{
+            manager::RomManager,
+            system::{GameSystem, OtherSystem},
+        },
+    };
+    use std::{fs::File, sync::Arc};
+
+    #[test]
+    fn bless_then_detect_change() {
+        let directory =
+            std::env::temp_dir().join(format!("multiemu-golden-test-{}", std::process::id()));
+        let mut golden_images = GoldenImages {
+            directory: directory.join("golden"),
+            artifacts: directory.join("artifacts"),
+            ..GoldenImages::default()
+        };
+        golden_images.bless = true;
+
+        let mut frame = DMatrix::from_element(8, 4, Srgba::new(0, 0, 0, 0xff));
+        golden_images.check("checker", &frame).unwrap();
+
+        golden_images.bless = false;
+        golden_images.check("checker", &frame).unwrap();
+
+        frame[(3, 2)] = Srgba::new(0xff, 0xff, 0xff, 0xff);
+        let mismatch = golden_images.check("checker", &frame).unwrap_err();
+        assert_eq!(mismatch.differing_pixels, Some(1));
+        assert!(mismatch.artifacts.iter().all(|artifact| artifact.is_file()));
+
+        assert_ne!(
+            frame_hash(&frame),
+            frame_hash(&DMatrix::from_element(8, 4, Srgba::new(0, 0, 0, 0xff)))
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn headless_chip8_is_deterministic() {
+        let rom_path =
+            std::env::temp_dir().join(format!("multiemu-golden-test-{}.ch8", std::process::id()));
+        std::fs::write(
+            &rom_path,
+            assemble("i := hex v0 sprite v0 v0 5 loop again").unwrap(),
+        )
+        .unwrap();
+
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let rom_id = RomId::from_read(&mut File::open(&rom_path).unwrap());
+        rom_manager.rom_paths.insert(rom_id, rom_path.clone());
+
+        let machine = || {
+            Machine::from_system(
+                vec![rom_id],
+                rom_manager.clone(),
+                GameSystem::Other(OtherSystem::Chip8),
+            )
+        };
+
+        let blank = frame_hash(&run_headless(&mut machine(), 0));
+        let drawn = frame_hash(&run_headless(&mut machine(), 10));
+
+        assert_ne!(blank, drawn);
+        assert_eq!(drawn, frame_hash(&run_headless(&mut machine(), 10)));
+
+        std::fs::remove_file(&rom_path).unwrap();
+    }
+}
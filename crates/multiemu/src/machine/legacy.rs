@@ -0,0 +1,390 @@
+//! Recognizing and converting saves and savestates made by other emulators
+
+use super::serialization::SAVE_STATE_MAGIC;
+use crate::rom::system::{GameSystem, NintendoSystem, SegaSystem};
+use std::path::Path;
+use thiserror::Error;
+
+/// 48K of RAM starting at 0x4000, everything a 48K Spectrum has that isn't ROM
+const SPECTRUM_RAM_SIZE: usize = 0xc000;
+const SNA_HEADER_SIZE: usize = 27;
+
+#[derive(Error, Debug)]
+pub enum LegacyImportError {
+    #[error("Could not read file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("File is not a save or savestate format we know of")]
+    Unrecognized,
+    #[error("This is a {emulator} savestate for {system}, their internal layout can't be converted to our components")]
+    Identified {
+        emulator: &'static str,
+        system: GameSystem,
+    },
+    #[error("File claims to be a {0} but it is truncated or corrupt")]
+    Corrupt(&'static str),
+    #[error("Recognized {0} but there is no machine that can load it yet")]
+    NoMachine(&'static str),
+    #[error("This is one of our own save states, load it instead of importing it")]
+    Native,
+}
+
+/// Z80 register file as it is stored in Spectrum snapshots
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Z80Registers {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub af_shadow: u16,
+    pub bc_shadow: u16,
+    pub de_shadow: u16,
+    pub hl_shadow: u16,
+    pub ix: u16,
+    pub iy: u16,
+    pub sp: u16,
+    pub pc: u16,
+    pub i: u8,
+    pub r: u8,
+    pub iff1: bool,
+    pub iff2: bool,
+    pub interrupt_mode: u8,
+}
+
+/// The machine state contained in a .sna or .z80 file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpectrumSnapshot {
+    pub registers: Z80Registers,
+    pub border_color: u8,
+    /// Memory from 0x4000 to 0xffff
+    pub ram: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LegacySave {
+    /// Raw battery backed memory, which is the same no matter who wrote it
+    Battery(Vec<u8>),
+    Spectrum(SpectrumSnapshot),
+}
+
+impl LegacySave {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, LegacyImportError> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        Self::from_bytes(&contents, extension.as_deref())
+    }
+
+    pub fn from_bytes(contents: &[u8], extension: Option<&str>) -> Result<Self, LegacyImportError> {
+        // Battery saves have no header at all so anything could be at the start of them
+        if let Some("sav" | "srm" | "eep" | "fla") = extension {
+            return Ok(LegacySave::Battery(contents.to_vec()));
+        }
+
+        // Ours starts with the same letters as Mesen's, so it has to be ruled out first
+        if contents.starts_with(SAVE_STATE_MAGIC) {
+            return Err(LegacyImportError::Native);
+        }
+
+        if let Some((emulator, system)) = identify_savestate(contents) {
+            return Err(LegacyImportError::Identified { emulator, system });
+        }
+
+        match extension {
+            Some("sna") => parse_sna(contents).map(LegacySave::Spectrum),
+            Some("z80") => parse_z80(contents).map(LegacySave::Spectrum),
+            _ => Err(LegacyImportError::Unrecognized),
+        }
+    }
+}
+
+fn identify_savestate(contents: &[u8]) -> Option<(&'static str, GameSystem)> {
+    const MAGICS: &[(&[u8], &str, GameSystem)] = &[
+        (b"GST", "Gens", GameSystem::Sega(SegaSystem::Genesis)),
+        (
+            b"FCSX",
+            "FCEUX",
+            GameSystem::Nintendo(NintendoSystem::NintendoEntertainmentSystem),
+        ),
+        (
+            b"FCS\xff",
+            "FCEUX",
+            GameSystem::Nintendo(NintendoSystem::NintendoEntertainmentSystem),
+        ),
+        (
+            b"MSS",
+            "Mesen",
+            GameSystem::Nintendo(NintendoSystem::NintendoEntertainmentSystem),
+        ),
+    ];
+
+    MAGICS
+        .iter()
+        .find(|(magic, _, _)| contents.starts_with(magic))
+        .map(|(_, emulator, system)| (*emulator, *system))
+}
+
+fn read_u16(contents: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([contents[offset], contents[offset + 1]])
+}
+
+fn parse_sna(contents: &[u8]) -> Result<SpectrumSnapshot, LegacyImportError> {
+    // 128K snapshots tack the PC and the other banks on the end of a 48K one
+    if contents.len() < SNA_HEADER_SIZE + SPECTRUM_RAM_SIZE {
+        return Err(LegacyImportError::Corrupt("SNA snapshot"));
+    }
+
+    let ram = contents[SNA_HEADER_SIZE..SNA_HEADER_SIZE + SPECTRUM_RAM_SIZE].to_vec();
+    let sp = read_u16(contents, 23);
+
+    let pc = if contents.len() > SNA_HEADER_SIZE + SPECTRUM_RAM_SIZE {
+        read_u16(contents, SNA_HEADER_SIZE + SPECTRUM_RAM_SIZE)
+    } else {
+        // 48K snapshots are taken inside an interrupt so the PC sits on the stack
+        let stack = sp.wrapping_sub(0x4000) as usize;
+        if stack + 1 >= ram.len() {
+            return Err(LegacyImportError::Corrupt("SNA snapshot"));
+        }
+        read_u16(&ram, stack)
+    };
+
+    let registers = Z80Registers {
+        i: contents[0],
+        hl_shadow: read_u16(contents, 1),
+        de_shadow: read_u16(contents, 3),
+        bc_shadow: read_u16(contents, 5),
+        af_shadow: read_u16(contents, 7),
+        hl: read_u16(contents, 9),
+        de: read_u16(contents, 11),
+        bc: read_u16(contents, 13),
+        iy: read_u16(contents, 15),
+        ix: read_u16(contents, 17),
+        iff1: contents[19] & 0b100 != 0,
+        iff2: contents[19] & 0b100 != 0,
+        r: contents[20],
+        af: read_u16(contents, 21),
+        // The 48K format expects a RETN to have been executed
+        sp: if contents.len() == SNA_HEADER_SIZE + SPECTRUM_RAM_SIZE {
+            sp.wrapping_add(2)
+        } else {
+            sp
+        },
+        pc,
+        interrupt_mode: contents[25],
+    };
+
+    Ok(SpectrumSnapshot {
+        registers,
+        border_color: contents[26] & 0b111,
+        ram,
+    })
+}
+
+/// Undoes the ED ED run length encoding .z80 files use
+fn decompress_z80_block(data: &[u8], expected: usize) -> Vec<u8> {
+    let mut output = Vec::with_capacity(expected);
+    let mut index = 0;
+
+    while index < data.len() && output.len() < expected {
+        if data[index..].starts_with(&[0xed, 0xed]) && index + 3 < data.len() {
+            let count = data[index + 2] as usize;
+            let value = data[index + 3];
+
+            output.extend(std::iter::repeat_n(value, count));
+            index += 4;
+        } else {
+            output.push(data[index]);
+            index += 1;
+        }
+    }
+
+    output
+}
+
+fn parse_z80(contents: &[u8]) -> Result<SpectrumSnapshot, LegacyImportError> {
+    const Z80_HEADER_SIZE: usize = 30;
+    const PAGE_SIZE: usize = 0x4000;
+
+    if contents.len() < Z80_HEADER_SIZE {
+        return Err(LegacyImportError::Corrupt("Z80 snapshot"));
+    }
+
+    // Old files used 255 here to mean 1
+    let flags = if contents[12] == 0xff {
+        1
+    } else {
+        contents[12]
+    };
+
+    let mut registers = Z80Registers {
+        af: u16::from_be_bytes([contents[0], contents[1]]),
+        bc: read_u16(contents, 2),
+        hl: read_u16(contents, 4),
+        pc: read_u16(contents, 6),
+        sp: read_u16(contents, 8),
+        i: contents[10],
+        r: (contents[11] & 0x7f) | ((flags & 1) << 7),
+        de: read_u16(contents, 13),
+        bc_shadow: read_u16(contents, 15),
+        de_shadow: read_u16(contents, 17),
+        hl_shadow: read_u16(contents, 19),
+        af_shadow: u16::from_be_bytes([contents[21], contents[22]]),
+        iy: read_u16(contents, 23),
+        ix: read_u16(contents, 25),
+        iff1: contents[27] != 0,
+        iff2: contents[28] != 0,
+        interrupt_mode: contents[29] & 0b11,
+    };
+    let border_color = (flags >> 1) & 0b111;
+
+    // Version 1 files are a plain 48K memory dump
+    if registers.pc != 0 {
+        let data = &contents[Z80_HEADER_SIZE..];
+        let ram = if flags & 0b10_0000 != 0 {
+            decompress_z80_block(data, SPECTRUM_RAM_SIZE)
+        } else {
+            data.to_vec()
+        };
+
+        if ram.len() < SPECTRUM_RAM_SIZE {
+            return Err(LegacyImportError::Corrupt("Z80 snapshot"));
+        }
+
+        return Ok(SpectrumSnapshot {
+            registers,
+            border_color,
+            ram: ram[..SPECTRUM_RAM_SIZE].to_vec(),
+        });
+    }
+
+    // Version 2 and 3 files have an extended header followed by memory pages
+    if contents.len() < Z80_HEADER_SIZE + 5 {
+        return Err(LegacyImportError::Corrupt("Z80 snapshot"));
+    }
+
+    let extended_header_size = read_u16(contents, Z80_HEADER_SIZE) as usize;
+    registers.pc = read_u16(contents, Z80_HEADER_SIZE + 2);
+    let hardware_mode = contents[Z80_HEADER_SIZE + 4];
+
+    // Anything past mode 1 on version 2 and mode 3 on version 3 is some form of 128K machine
+    let is_48k = if extended_header_size == 23 {
+        hardware_mode <= 1
+    } else {
+        hardware_mode <= 3
+    };
+
+    if !is_48k {
+        return Err(LegacyImportError::NoMachine("128K Spectrum snapshot"));
+    }
+
+    let mut ram = vec![0; SPECTRUM_RAM_SIZE];
+    let mut offset = Z80_HEADER_SIZE + 2 + extended_header_size;
+
+    while offset + 3 <= contents.len() {
+        let length = read_u16(contents, offset) as usize;
+        let page = contents[offset + 2];
+        offset += 3;
+
+        let (data, consumed) = if length == 0xffff {
+            (contents.get(offset..offset + PAGE_SIZE), PAGE_SIZE)
+        } else {
+            (contents.get(offset..offset + length), length)
+        };
+        let Some(data) = data else {
+            return Err(LegacyImportError::Corrupt("Z80 snapshot"));
+        };
+        let data = if length == 0xffff {
+            data.to_vec()
+        } else {
+            decompress_z80_block(data, PAGE_SIZE)
+        };
+        offset += consumed;
+
+        let address = match page {
+            8 => 0x4000,
+            4 => 0x8000,
+            5 => 0xc000,
+            // ROM pages and interface pages have nowhere to go
+            _ => continue,
+        };
+        let start = address - 0x4000;
+        let end = (start + data.len()).min(start + PAGE_SIZE);
+
+        ram[start..end].copy_from_slice(&data[..end - start]);
+    }
+
+    Ok(SpectrumSnapshot {
+        registers,
+        border_color,
+        ram,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identifies_foreign_savestates() {
+        assert!(matches!(
+            LegacySave::from_bytes(b"FCSX\x00\x00", Some("fc0")),
+            Err(LegacyImportError::Identified {
+                emulator: "FCEUX",
+                ..
+            })
+        ));
+        assert_eq!(
+            LegacySave::from_bytes(&[1, 2, 3], Some("sav")).unwrap(),
+            LegacySave::Battery(vec![1, 2, 3])
+        );
+        assert!(matches!(
+            LegacySave::from_bytes(&[1, 2, 3], Some("bin")),
+            Err(LegacyImportError::Unrecognized)
+        ));
+    }
+
+    #[test]
+    fn native_save_states_are_not_mesen() {
+        // Mesen follows the letters with its version number
+        assert!(matches!(
+            LegacySave::from_bytes(b"MSS\x00\x00\x02\x00", Some("mss")),
+            Err(LegacyImportError::Identified {
+                emulator: "Mesen",
+                ..
+            })
+        ));
+        assert!(matches!(
+            LegacySave::from_bytes(b"MSS\x1a\x01\x00\x00\x00", Some("mss")),
+            Err(LegacyImportError::Native)
+        ));
+    }
+
+    #[test]
+    fn sna_pops_pc_off_the_stack() {
+        let mut contents = vec![0; SNA_HEADER_SIZE + SPECTRUM_RAM_SIZE];
+        // SP = 0x8000
+        contents[23..25].copy_from_slice(&0x8000u16.to_le_bytes());
+        contents[SNA_HEADER_SIZE + 0x4000..SNA_HEADER_SIZE + 0x4002]
+            .copy_from_slice(&0x1234u16.to_le_bytes());
+
+        let LegacySave::Spectrum(snapshot) =
+            LegacySave::from_bytes(&contents, Some("sna")).unwrap()
+        else {
+            panic!("Not parsed as a spectrum snapshot");
+        };
+
+        assert_eq!(snapshot.registers.pc, 0x1234);
+        assert_eq!(snapshot.registers.sp, 0x8002);
+    }
+
+    #[test]
+    fn z80_decompression() {
+        assert_eq!(
+            decompress_z80_block(&[1, 0xed, 0xed, 4, 7, 2], 6),
+            vec![1, 7, 7, 7, 7, 2]
+        );
+    }
+}
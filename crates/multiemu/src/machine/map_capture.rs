@@ -0,0 +1,254 @@
+use super::Machine;
+use crate::{
+    config::GLOBAL_CONFIG, rom::id::RomId, runtime::rendering_backend::DisplayComponentFramebuffer,
+};
+use image::{ImageFormat, RgbaImage};
+use nalgebra::{DMatrix, Vector2};
+use palette::Srgba;
+use std::{collections::HashMap, fs::create_dir_all, path::Path};
+
+/// The stitched map is stored in square pieces so it can grow in any direction
+const CHUNK_SIZE: i64 = 256;
+/// Furthest the screen is expected to scroll in a frame when it has to be guessed from the picture
+const MAX_ESTIMATED_SCROLL: i32 = 8;
+/// Only every this many pixels are compared when guessing, whole frames are too slow
+const ESTIMATE_SAMPLE_STEP: usize = 4;
+/// Frames that match their predecessor worse than this are treated as a new scene
+const SCENE_CUT_THRESHOLD: f32 = 0.5;
+
+/// Stitches the frames of a scrolling game into one big picture of the level, like WideNES
+#[derive(Debug)]
+pub struct MapCapture {
+    rom_id: RomId,
+    /// Where the top left of the screen is on the map
+    position: Vector2<i64>,
+    previous_frame: Option<DMatrix<Srgba<u8>>>,
+    previous_scroll: Option<Vector2<i32>>,
+    chunks: HashMap<(i64, i64), DMatrix<Srgba<u8>>>,
+}
+
+impl MapCapture {
+    pub fn new(rom_id: RomId) -> Self {
+        Self {
+            rom_id,
+            position: Vector2::zeros(),
+            previous_frame: None,
+            previous_scroll: None,
+            chunks: HashMap::default(),
+        }
+    }
+
+    /// Captures the main display of the machine, called every frame
+    pub fn capture_machine(&mut self, machine: &Machine) {
+        let Some(display) = machine.display_components().next() else {
+            return;
+        };
+
+        // Hardware framebuffers would need a readback, not worth it for this
+        let DisplayComponentFramebuffer::Software(framebuffer) =
+            display.component.get_framebuffer()
+        else {
+            return;
+        };
+
+        let frame = framebuffer.lock().unwrap().clone();
+        self.capture(frame, display.component.scroll_position());
+    }
+
+    /// Adds a frame to the map, using the scroll position to place it if the display knows it
+    pub fn capture(&mut self, frame: DMatrix<Srgba<u8>>, scroll: Option<Vector2<i32>>) {
+        let delta = match (scroll, self.previous_scroll) {
+            (Some(scroll), Some(previous_scroll))
+                if (scroll - previous_scroll).x.unsigned_abs() < frame.nrows() as u32
+                    && (scroll - previous_scroll).y.unsigned_abs() < frame.ncols() as u32 =>
+            {
+                Some(scroll - previous_scroll)
+            }
+            // Wrapped around or jumped somewhere new, look at the picture instead
+            _ => self
+                .previous_frame
+                .as_ref()
+                .and_then(|previous_frame| estimate_scroll(previous_frame, &frame)),
+        };
+
+        if let Some(delta) = delta {
+            self.position += delta.cast();
+        }
+
+        self.paint(&frame);
+        self.previous_frame = Some(frame);
+        self.previous_scroll = scroll;
+    }
+
+    fn paint(&mut self, frame: &DMatrix<Srgba<u8>>) {
+        for ((x, y), pixel) in (0..frame.ncols())
+            .flat_map(|y| (0..frame.nrows()).map(move |x| (x, y)))
+            .map(|(x, y)| ((x, y), frame[(x, y)]))
+        {
+            let map_x = self.position.x + x as i64;
+            let map_y = self.position.y + y as i64;
+
+            let chunk = self
+                .chunks
+                .entry((map_x.div_euclid(CHUNK_SIZE), map_y.div_euclid(CHUNK_SIZE)))
+                .or_insert_with(|| {
+                    DMatrix::from_element(
+                        CHUNK_SIZE as usize,
+                        CHUNK_SIZE as usize,
+                        Srgba::new(0, 0, 0, 0),
+                    )
+                });
+
+            chunk[(
+                map_x.rem_euclid(CHUNK_SIZE) as usize,
+                map_y.rem_euclid(CHUNK_SIZE) as usize,
+            )] = pixel;
+        }
+    }
+
+    /// The whole map so far, with anything never seen left transparent
+    pub fn render(&self) -> DMatrix<Srgba<u8>> {
+        let Some((min_x, min_y, max_x, max_y)) =
+            self.chunks
+                .keys()
+                .fold(None, |bounds: Option<(i64, i64, i64, i64)>, (x, y)| {
+                    Some(match bounds {
+                        Some((min_x, min_y, max_x, max_y)) => {
+                            (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y))
+                        }
+                        None => (*x, *y, *x, *y),
+                    })
+                })
+        else {
+            return DMatrix::from_element(1, 1, Srgba::new(0, 0, 0, 0));
+        };
+
+        let width = ((max_x - min_x + 1) * CHUNK_SIZE) as usize;
+        let height = ((max_y - min_y + 1) * CHUNK_SIZE) as usize;
+
+        DMatrix::from_fn(width, height, |x, y| {
+            let map_x = x as i64 + min_x * CHUNK_SIZE;
+            let map_y = y as i64 + min_y * CHUNK_SIZE;
+
+            self.chunks
+                .get(&(map_x.div_euclid(CHUNK_SIZE), map_y.div_euclid(CHUNK_SIZE)))
+                .map(|chunk| {
+                    chunk[(
+                        map_x.rem_euclid(CHUNK_SIZE) as usize,
+                        map_y.rem_euclid(CHUNK_SIZE) as usize,
+                    )]
+                })
+                .unwrap_or(Srgba::new(0, 0, 0, 0))
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), image::ImageError> {
+        let map = self.render();
+        let mut image = RgbaImage::new(map.nrows() as u32, map.ncols() as u32);
+
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let color = map[(x as usize, y as usize)];
+            pixel.0 = [color.red, color.green, color.blue, color.alpha];
+        }
+
+        image.save_with_format(path, ImageFormat::WebP)
+    }
+}
+
+impl Drop for MapCapture {
+    /// Maps are written out when the machine goes away, one per ROM
+    fn drop(&mut self) {
+        if self.chunks.is_empty() {
+            return;
+        }
+
+        let map_directory = GLOBAL_CONFIG.read().unwrap().map_directory.clone();
+        let path = map_directory.join(format!("{}.webp", self.rom_id));
+
+        if let Err(error) = create_dir_all(&map_directory) {
+            tracing::error!("Could not create {}: {}", map_directory.display(), error);
+            return;
+        }
+
+        match self.save(&path) {
+            Ok(()) => tracing::info!("Saved stitched map to {}", path.display()),
+            Err(error) => tracing::error!("Could not save {}: {}", path.display(), error),
+        }
+    }
+}
+
+/// Guesses how far the screen scrolled by finding the offset where the two frames agree the most
+fn estimate_scroll(
+    previous_frame: &DMatrix<Srgba<u8>>,
+    frame: &DMatrix<Srgba<u8>>,
+) -> Option<Vector2<i32>> {
+    if previous_frame.shape() != frame.shape() {
+        return None;
+    }
+
+    let (width, height) = frame.shape();
+    let mut best = None;
+
+    for dy in -MAX_ESTIMATED_SCROLL..=MAX_ESTIMATED_SCROLL {
+        for dx in -MAX_ESTIMATED_SCROLL..=MAX_ESTIMATED_SCROLL {
+            let mut compared = 0;
+            let mut matching = 0;
+
+            for y in (0..height).step_by(ESTIMATE_SAMPLE_STEP) {
+                for x in (0..width).step_by(ESTIMATE_SAMPLE_STEP) {
+                    let (Some(previous_x), Some(previous_y)) = (
+                        x.checked_add_signed(dx as isize).filter(|x| *x < width),
+                        y.checked_add_signed(dy as isize).filter(|y| *y < height),
+                    ) else {
+                        continue;
+                    };
+
+                    compared += 1;
+                    if frame[(x, y)] == previous_frame[(previous_x, previous_y)] {
+                        matching += 1;
+                    }
+                }
+            }
+
+            if compared == 0 {
+                continue;
+            }
+
+            let delta = Vector2::new(dx, dy);
+            let score = matching as f32 / compared as f32;
+
+            // Ties go to the smaller movement, so still screens stay still
+            if best.is_none_or(|(best_delta, best_score): (Vector2<i32>, f32)| {
+                score > best_score
+                    || (score == best_score && delta.abs().sum() < best_delta.abs().sum())
+            }) {
+                best = Some((delta, score));
+            }
+        }
+    }
+
+    best.filter(|(_, score)| *score >= SCENE_CUT_THRESHOLD)
+        .map(|(delta, _)| delta)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn estimates_horizontal_scroll() {
+        let level = |x: usize, y: usize| {
+            let shade = ((x * 7 + y * 13) % 251) as u8;
+            Srgba::new(shade, shade.wrapping_mul(3), 0, 0xff)
+        };
+
+        let previous_frame = DMatrix::from_fn(64, 48, level);
+        // Camera moved 3 pixels to the right
+        let frame = DMatrix::from_fn(64, 48, |x, y| level(x + 3, y));
+
+        assert_eq!(
+            estimate_scroll(&previous_frame, &frame),
+            Some(Vector2::new(3, 0))
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Converts a component snapshot from the version it is registered under to the one after it
+pub type SnapshotMigration = fn(rmpv::Value) -> Result<rmpv::Value, String>;
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error(
+        "{component} snapshot is version {found}, but this build only understands up to {current}"
+    )]
+    TooNew {
+        component: &'static str,
+        found: u32,
+        current: u32,
+    },
+    #[error("{component} has no way to convert snapshots from version {version}")]
+    Missing {
+        component: &'static str,
+        version: u32,
+    },
+    #[error("Could not convert {component} snapshot from version {version}: {reason}")]
+    Failed {
+        component: &'static str,
+        version: u32,
+        reason: String,
+    },
+}
+
+/// Which snapshot version a component writes, and how to bring older ones up to it
+///
+/// Components start at version 0. When a component changes its snapshot layout it bumps its version and registers a
+/// migration from the previous one, so states saved by older builds are walked forward one step at a time
+#[derive(Debug, Default, Clone)]
+pub struct SnapshotMigrations {
+    version: u32,
+    steps: BTreeMap<u32, SnapshotMigration>,
+}
+
+impl SnapshotMigrations {
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    /// Registers the conversion from `from` to `from + 1`
+    pub fn register(&mut self, from: u32, migration: SnapshotMigration) {
+        if self.steps.insert(from, migration).is_some() {
+            tracing::warn!("Replaced the snapshot migration from version {}", from);
+        }
+    }
+
+    /// Brings a snapshot written at `version` up to the current one
+    pub fn migrate(
+        &self,
+        component: &'static str,
+        version: u32,
+        mut snapshot: rmpv::Value,
+    ) -> Result<rmpv::Value, MigrationError> {
+        if version > self.version {
+            return Err(MigrationError::TooNew {
+                component,
+                found: version,
+                current: self.version,
+            });
+        }
+
+        for version in version..self.version {
+            let migration = self
+                .steps
+                .get(&version)
+                .ok_or(MigrationError::Missing { component, version })?;
+
+            snapshot = migration(snapshot).map_err(|reason| MigrationError::Failed {
+                component,
+                version,
+                reason,
+            })?;
+
+            tracing::debug!(
+                "Migrated {} snapshot from version {} to {}",
+                component,
+                version,
+                version + 1
+            );
+        }
+
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn migrations_chain() {
+        let mut migrations = SnapshotMigrations::default();
+        migrations.set_version(2);
+        // Version 0 was a bare counter, 1 wrapped it in an array, 2 added a flag
+        migrations.register(0, |snapshot| Ok(rmpv::Value::Array(vec![snapshot])));
+        migrations.register(1, |snapshot| match snapshot {
+            rmpv::Value::Array(mut fields) => {
+                fields.push(rmpv::Value::Boolean(false));
+                Ok(rmpv::Value::Array(fields))
+            }
+            _ => Err("Expected an array".to_string()),
+        });
+
+        assert_eq!(
+            migrations
+                .migrate("Counter", 0, rmpv::Value::from(5))
+                .unwrap(),
+            rmpv::Value::Array(vec![rmpv::Value::from(5), rmpv::Value::Boolean(false)])
+        );
+        assert!(matches!(
+            migrations.migrate("Counter", 1, rmpv::Value::Nil),
+            Err(MigrationError::Failed { version: 1, .. })
+        ));
+        assert!(matches!(
+            migrations.migrate("Counter", 3, rmpv::Value::Nil),
+            Err(MigrationError::TooNew { found: 3, .. })
+        ));
+
+        migrations.set_version(3);
+        assert!(matches!(
+            migrations.migrate("Counter", 2, rmpv::Value::Nil),
+            Err(MigrationError::Missing { version: 2, .. })
+        ));
+    }
+}
@@ -1,29 +1,69 @@
 use crate::{
     component::{
+        audio::AudioComponent,
         display::DisplayComponent,
         input::{EmulatedGamepadMetadata, EmulatedGamepadTypeId, InputComponent},
         memory::MemoryComponent,
+        processor::DebuggableProcessor,
         schedulable::SchedulableComponent,
+        tape::TapeComponent,
+        text_output::TextOutputComponent,
         Component, ComponentId, FromConfig,
     },
-    input::manager::InputManager,
-    memory::{AddressSpaceId, MemoryTranslationTable},
-    rom::{manager::RomManager, system::GameSystem},
-    scheduler::Scheduler,
+    config::GLOBAL_CONFIG,
+    definitions::misc::expansion::{ExpansionPort, PeripheralConfig},
+    input::{manager::InputManager, InputSampling},
+    logging::trace_event,
+    memory::{AddressSpaceId, AlignmentPolicy, BusConflictPolicy, MemoryTranslationTable},
+    rom::{
+        handle::RomHandle,
+        id::RomId,
+        manager::{MissingRom, RomManager, RomRequirement},
+        statistics::PlaySession,
+        system::GameSystem,
+        verification::{RomVerification, RomWarning},
+    },
+    runtime::rendering_backend::RawFrame,
+    scheduler::{watchdog::Watchdog, Scheduler},
 };
+use clock::MachineClock;
 use component_store::ComponentStore;
-use num::rational::Ratio;
+use map_capture::MapCapture;
+use migration::{SnapshotMigration, SnapshotMigrations};
+use num::{rational::Ratio, ToPrimitive};
 use rangemap::RangeSet;
 use std::{
     collections::{HashMap, HashSet},
+    error::Error,
     ops::Range,
     sync::Arc,
     time::Duration,
 };
-
+use trigger::TriggerEngine;
+use validation::MachineConfigurationError;
+
+pub mod bus_log;
+pub mod capabilities;
+pub mod capture;
+#[cfg(test)]
+pub mod chaos;
+pub mod clock;
 pub mod component_store;
+pub mod fork;
 pub mod from_system;
+#[cfg(test)]
+pub mod golden;
+pub mod legacy;
+pub mod map_capture;
+pub mod migration;
 pub mod serialization;
+#[cfg(test)]
+pub mod test_machine;
+pub mod trigger;
+pub mod validation;
+
+/// Frame rate of machines that don't say otherwise
+const DEFAULT_DISPLAY_CLOCK: Ratio<u64> = Ratio::new_raw(60, 1);
 
 #[derive(Debug)]
 pub struct SchedulableComponentInfo {
@@ -33,6 +73,11 @@ pub struct SchedulableComponentInfo {
     pub run_before: HashSet<ComponentId>,
 }
 
+#[derive(Debug)]
+pub struct AudioComponentInfo {
+    pub component: Arc<dyn AudioComponent>,
+}
+
 #[derive(Debug)]
 pub struct DisplayComponentInfo {
     pub component: Arc<dyn DisplayComponent>,
@@ -49,15 +94,39 @@ pub struct InputComponentInfo {
 pub struct MemoryComponentInfo {
     pub component: Arc<dyn MemoryComponent>,
     pub assigned_ranges: HashMap<AddressSpaceId, RangeSet<usize>>,
+    /// Used to pick who answers on busses with [BusConflictPolicy::Priority]
+    pub priority: i8,
+}
+
+#[derive(Debug)]
+pub struct DebuggableProcessorInfo {
+    pub component: Arc<dyn DebuggableProcessor>,
+}
+
+#[derive(Debug)]
+pub struct TapeComponentInfo {
+    pub component: Arc<dyn TapeComponent>,
+}
+
+#[derive(Debug)]
+pub struct TextOutputComponentInfo {
+    pub component: Arc<dyn TextOutputComponent>,
 }
 
 #[derive(Debug)]
 pub struct ComponentTable {
+    /// Type name of the component, for error messages
+    pub name: &'static str,
     pub component: Arc<dyn Component>,
     pub as_schedulable: Option<SchedulableComponentInfo>,
     pub as_display: Option<DisplayComponentInfo>,
+    pub as_audio: Option<AudioComponentInfo>,
     pub as_input: Option<InputComponentInfo>,
     pub as_memory: Option<MemoryComponentInfo>,
+    pub as_debuggable: Option<DebuggableProcessorInfo>,
+    pub as_tape: Option<TapeComponentInfo>,
+    pub as_text_output: Option<TextOutputComponentInfo>,
+    pub snapshot_migrations: SnapshotMigrations,
 }
 
 pub struct Machine {
@@ -67,17 +136,64 @@ pub struct Machine {
     pub input_manager: Arc<InputManager>,
     pub system: GameSystem,
     pub scheduler: Scheduler,
+    pub clock: Arc<MachineClock>,
+    /// ROMs components asked for during construction that could not be found
+    pub missing_roms: Vec<MissingRom>,
+    /// ROMs that were found but don't look like what the database expects
+    pub rom_warnings: Vec<RomWarning>,
+    /// If the firmware intro was skipped, which changes the machines timeline
+    pub fast_boot: bool,
+    /// Memory conditions the loaded ROMs define, checked every frame
+    pub triggers: TriggerEngine,
+    /// Ports peripherals can be plugged into, by name
+    pub expansion_ports: HashMap<String, ComponentId>,
+    /// Stitches the display into a map of the level, if the user turned it on
+    pub map_capture: Option<MapCapture>,
+    /// What the machine was built from, missing if it was put together by hand
+    pub user_specified_roms: Option<Vec<RomId>>,
+    /// Records play time for the main ROM once the machine goes away
+    pub play_session: Option<PlaySession>,
+    /// How many frames a second the machine presents, which everything paced per frame follows
+    pub display_clock: Ratio<u64>,
+    /// Stops the machine when a component gets stuck, None if the user turned it off
+    pub watchdog: Option<Watchdog>,
+    /// Faults the test harness wants injected while running
+    #[cfg(test)]
+    pub fault_injection: Option<chaos::FaultInjection>,
 }
 
 impl Machine {
     pub fn build(game_system: GameSystem, rom_manager: Arc<RomManager>) -> MachineBuilder {
+        let global_config = GLOBAL_CONFIG.read().unwrap();
+        let fast_boot = global_config
+            .fast_boot
+            .get(&game_system)
+            .copied()
+            .unwrap_or_default();
+        let mut input_manager = InputManager::default();
+        input_manager.set_sampling(
+            global_config
+                .input_sampling
+                .get(&game_system)
+                .copied()
+                .unwrap_or_default(),
+        );
+        drop(global_config);
+
         MachineBuilder {
             current_component_index: ComponentId(0),
             component_store: ComponentStore::new(),
             rom_manager,
-            input_manager: InputManager::default(),
+            input_manager,
             system: game_system,
             memory_translation_table: MemoryTranslationTable::default(),
+            clock: Arc::default(),
+            missing_roms: Vec::default(),
+            rom_warnings: Vec::default(),
+            fast_boot,
+            boot_duration: None,
+            expansion_ports: HashMap::default(),
+            display_clock: None,
         }
     }
 
@@ -87,8 +203,159 @@ impl Machine {
             .filter_map(|table| table.as_display.as_ref())
     }
 
+    pub fn audio_components(&self) -> impl Iterator<Item = &AudioComponentInfo> {
+        self.component_store
+            .components()
+            .filter_map(|table| table.as_audio.as_ref())
+    }
+
+    pub fn debuggable_processors(&self) -> impl Iterator<Item = &DebuggableProcessorInfo> {
+        self.component_store
+            .components()
+            .filter_map(|table| table.as_debuggable.as_ref())
+    }
+
+    pub fn input_components(&self) -> impl Iterator<Item = &InputComponentInfo> {
+        self.component_store
+            .components()
+            .filter_map(|table| table.as_input.as_ref())
+    }
+
+    pub fn tape_components(&self) -> impl Iterator<Item = &TapeComponentInfo> {
+        self.component_store
+            .components()
+            .filter_map(|table| table.as_tape.as_ref())
+    }
+
+    pub fn text_outputs(&self) -> impl Iterator<Item = &TextOutputComponentInfo> {
+        self.component_store
+            .components()
+            .filter_map(|table| table.as_text_output.as_ref())
+    }
+
+    /// Reads the main displays current frame as RGBA bytes, see [DisplayComponentFramebuffer::read_raw]
+    pub fn read_raw_frame<R>(&self, reader: impl FnOnce(RawFrame<'_>) -> R) -> Option<R> {
+        self.display_components()
+            .next()?
+            .component
+            .get_framebuffer()
+            .read_raw(reader)
+    }
+
+    /// If the machine has every ROM it cannot run without
+    pub fn bootable(&self) -> bool {
+        self.missing_roms
+            .iter()
+            .all(|missing_rom| missing_rom.requirement != RomRequirement::Required)
+    }
+
+    /// Plugs a peripheral into a port while the machine runs, None unplugs whatever is there
+    pub fn attach_peripheral(
+        &self,
+        port: &str,
+        peripheral: Option<&PeripheralConfig>,
+    ) -> Result<(), Box<dyn Error>> {
+        let expansion_port = self
+            .expansion_ports
+            .get(port)
+            .and_then(|component_id| self.component_store.get(*component_id))
+            .and_then(|table| {
+                table
+                    .component
+                    .clone()
+                    .into_any_arc()
+                    .downcast::<ExpansionPort>()
+                    .ok()
+            })
+            .ok_or_else(|| format!("{} has no expansion port named {}", self.system, port))?;
+
+        expansion_port.attach(peripheral.map(PeripheralConfig::create).transpose()?);
+        match peripheral {
+            Some(peripheral) => trace_event(format_args!(
+                "Frame {}: Plugged {:?} into {}",
+                self.clock.frames(),
+                peripheral,
+                port
+            )),
+            None => trace_event(format_args!(
+                "Frame {}: Unplugged {}",
+                self.clock.frames(),
+                port
+            )),
+        }
+
+        Ok(())
+    }
+
+    /// Runs the machine for however long the scheduler thinks fits in a host frame
     pub fn run(&mut self) {
-        self.scheduler.run(&self.component_store);
+        if self.stopped() {
+            return;
+        }
+
+        self.input_manager.latch();
+        let ticks = self.scheduler.run(&self.component_store);
+        self.finish_frame(ticks);
+    }
+
+    /// Runs exactly one emulated frame, see [Self::frame_rate]
+    pub fn run_frame(&mut self) {
+        if self.stopped() {
+            return;
+        }
+
+        self.input_manager.latch();
+
+        #[cfg(test)]
+        if let Some(mut fault_injection) = self.fault_injection.take() {
+            let dropped =
+                fault_injection.before_frame(self.clock.frames(), &self.memory_translation_table);
+            self.fault_injection = Some(fault_injection);
+
+            if dropped {
+                // Like a frontend that stalled and catches up, two frames of time pass in one
+                let ticks = self
+                    .scheduler
+                    .run_for(&self.component_store, self.frame_period() * 2);
+                self.finish_frame(ticks);
+                return;
+            }
+        }
+
+        let ticks = self
+            .scheduler
+            .run_for(&self.component_store, self.frame_period());
+        self.finish_frame(ticks);
+    }
+
+    /// If the watchdog caught a component running away, nothing runs until it is resumed
+    pub fn stopped(&self) -> bool {
+        self.watchdog.as_ref().is_some_and(Watchdog::tripped)
+    }
+
+    /// How many frames per second the machine shows, see [MachineBuilder::display_clock]
+    pub fn frame_rate(&self) -> Ratio<u64> {
+        self.display_clock
+    }
+
+    pub fn frame_period(&self) -> Duration {
+        Duration::from_secs_f64(self.frame_rate().recip().to_f64().unwrap_or_default())
+    }
+
+    /// Audio samples per channel the output has to take each frame to keep up, not necessarily a whole number
+    pub fn samples_per_frame(&self, sample_rate: u32) -> Ratio<u64> {
+        Ratio::from_integer(sample_rate as u64) / self.frame_rate()
+    }
+
+    fn finish_frame(&mut self, ticks: u64) {
+        self.clock.advance(ticks);
+        self.triggers
+            .evaluate(&self.memory_translation_table, self.clock.now());
+
+        if let Some(mut map_capture) = self.map_capture.take() {
+            map_capture.capture_machine(self);
+            self.map_capture = Some(map_capture);
+        }
     }
 }
 
@@ -97,6 +364,13 @@ pub struct MachineBuilder {
     current_component_index: ComponentId,
     component_store: ComponentStore,
     input_manager: InputManager,
+    clock: Arc<MachineClock>,
+    missing_roms: Vec<MissingRom>,
+    rom_warnings: Vec<RomWarning>,
+    fast_boot: bool,
+    boot_duration: Option<Duration>,
+    expansion_ports: HashMap<String, ComponentId>,
+    display_clock: Option<Ratio<u64>>,
     pub rom_manager: Arc<RomManager>,
     pub system: GameSystem,
 }
@@ -120,8 +394,13 @@ impl MachineBuilder {
             component: None,
             as_schedulable: None,
             as_display: None,
+            as_audio: None,
             as_input: None,
             as_memory: None,
+            as_debuggable: None,
+            as_tape: None,
+            as_text_output: None,
+            snapshot_migrations: SnapshotMigrations::default(),
         };
         C::from_config(&mut component_builder, config);
 
@@ -141,6 +420,73 @@ impl MachineBuilder {
         self
     }
 
+    /// Declares how often the machine presents a frame, like 50hz for PAL systems
+    ///
+    /// Should come before the components, so ones that run once a frame can ask for it. Machines that don't
+    /// declare one go by their first display, and failing that 60hz
+    pub fn display_clock(mut self, display_clock: Ratio<u64>) -> MachineBuilder {
+        self.display_clock = Some(display_clock);
+        self
+    }
+
+    /// The declared display clock, or 60hz, for components that run once a frame
+    pub fn display_clock_rate(&self) -> Ratio<u64> {
+        self.display_clock.unwrap_or(DEFAULT_DISPLAY_CLOCK)
+    }
+
+    /// How components mapped to the same addresses on a bus are treated, the bus must be inserted first
+    pub fn bus_conflict_policy(
+        mut self,
+        id: AddressSpaceId,
+        policy: BusConflictPolicy,
+    ) -> MachineBuilder {
+        self.memory_translation_table
+            .set_conflict_policy(id, policy);
+        self
+    }
+
+    /// What a bus does with accesses not aligned to their size, the bus must be inserted first
+    pub fn bus_alignment_policy(
+        mut self,
+        id: AddressSpaceId,
+        alignment: AlignmentPolicy,
+    ) -> MachineBuilder {
+        self.memory_translation_table
+            .set_alignment_policy(id, alignment);
+        self
+    }
+
+    /// Overrides the users fast boot setting, for when something like a movie needs a specific one
+    pub fn fast_boot(mut self, fast_boot: bool) -> MachineBuilder {
+        self.fast_boot = fast_boot;
+        self
+    }
+
+    /// How long the firmware takes before the game or the user takes over
+    ///
+    /// With fast boot on, whatever the components can't skip on their own runs out of sight for this long before
+    /// the machine is handed over, the same way every time so movies still line up
+    pub fn boot_duration(mut self, boot_duration: Duration) -> MachineBuilder {
+        self.boot_duration = Some(boot_duration);
+        self
+    }
+
+    /// Overrides the users input sampling setting, movies force per frame sampling on their own anyway
+    pub fn input_sampling(mut self, sampling: InputSampling) -> MachineBuilder {
+        self.input_manager.set_sampling(sampling);
+        self
+    }
+
+    /// Components that set up differently when the firmware is skipped can check this while building
+    pub fn is_fast_boot(&self) -> bool {
+        self.fast_boot
+    }
+
+    /// The clock the finished machine will use, for components that want to timestamp things
+    pub fn clock(&self) -> Arc<MachineClock> {
+        self.clock.clone()
+    }
+
     pub fn get_component<C: Component>(&self, id: ComponentId) -> Option<Arc<C>> {
         self.component_store
             .get(id)?
@@ -151,20 +497,32 @@ impl MachineBuilder {
             .ok()
     }
 
-    pub fn build(mut self) -> Machine {
-        for (address_space_id, assigned_ranges, component_id) in self
+    /// For machines that are known to be put together right, like in tests
+    pub fn build(self) -> Machine {
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_build(mut self) -> Result<Machine, MachineConfigurationError> {
+        // Better to refuse to start with a list of everything wrong than panic somewhere mid frame
+        self.validate()?;
+
+        for (address_space_id, assigned_ranges, component_id, priority) in self
             .component_store
             .iter()
             .filter_map(|(component_id, component_table)| {
                 if let Some(memory_component_info) = &component_table.as_memory {
-                    return Some((memory_component_info.assigned_ranges.iter(), component_id));
+                    return Some((
+                        memory_component_info.assigned_ranges.iter(),
+                        component_id,
+                        memory_component_info.priority,
+                    ));
                 }
 
                 None
             })
-            .flat_map(|(ranges, component_id)| {
+            .flat_map(|(ranges, component_id, priority)| {
                 ranges.map(move |(address_space_id, assigned_ranges)| {
-                    (address_space_id, assigned_ranges, component_id)
+                    (address_space_id, assigned_ranges, component_id, priority)
                 })
             })
         {
@@ -172,6 +530,7 @@ impl MachineBuilder {
                 *address_space_id,
                 component_id,
                 assigned_ranges.clone(),
+                priority,
             );
         }
 
@@ -229,13 +588,49 @@ impl MachineBuilder {
             .set_component_store(component_store.clone());
         let memory_translation_table = Arc::new(self.memory_translation_table);
 
-        let machine = Machine {
-            scheduler: Scheduler::new(&component_store),
+        let mut scheduler = Scheduler::new(&component_store);
+        self.clock.set_tick_real_time(scheduler.tick_real_time());
+
+        let watchdog_budget = GLOBAL_CONFIG.read().unwrap().watchdog_budget;
+        let watchdog = (watchdog_budget != 0).then(|| {
+            Watchdog::new(
+                Arc::downgrade(&component_store),
+                Duration::from_millis(watchdog_budget as u64),
+            )
+        });
+        scheduler.set_heartbeat(watchdog.as_ref().map(Watchdog::heartbeat));
+
+        let display_clock = self
+            .display_clock
+            .or_else(|| {
+                component_store
+                    .components()
+                    .filter_map(|table| table.as_display.as_ref())
+                    .find_map(|display| display.component.refresh_rate())
+            })
+            .unwrap_or(DEFAULT_DISPLAY_CLOCK);
+
+        let boot_duration = self.boot_duration;
+        let mut machine = Machine {
+            scheduler,
             rom_manager: self.rom_manager,
             memory_translation_table,
             component_store,
             input_manager: Arc::new(self.input_manager),
             system: self.system,
+            clock: self.clock,
+            missing_roms: self.missing_roms,
+            rom_warnings: self.rom_warnings,
+            fast_boot: self.fast_boot,
+            triggers: TriggerEngine::default(),
+            expansion_ports: self.expansion_ports,
+            map_capture: None,
+            user_specified_roms: None,
+            play_session: None,
+            display_clock,
+            watchdog,
+            #[cfg(test)]
+            fault_injection: None,
         };
 
         // Set the memory translation tables for everything
@@ -260,7 +655,35 @@ impl MachineBuilder {
                 .set_input_manager(machine.input_manager.clone(), &gamepad_ids);
         }
 
-        machine
+        if machine.fast_boot {
+            tracing::info!("Skipping firmware for {}", machine.system);
+
+            // In component order so the result is the same every time
+            for component_table in machine.component_store.components() {
+                component_table.component.skip_boot();
+            }
+
+            if let Some(boot_duration) = boot_duration {
+                while machine.clock.emulated_time() < boot_duration && !machine.stopped() {
+                    machine.run_frame();
+                }
+
+                // Nobody heard the firmware, so it shouldn't be queued up in front of the game either
+                let mut samples = Vec::new();
+                for audio_component in machine.audio_components() {
+                    audio_component.component.drain_samples(&mut samples);
+                    samples.clear();
+                }
+            }
+        }
+
+        trace_event(format_args!(
+            "Built {} with {} components",
+            machine.system,
+            machine.component_store.iter().count()
+        ));
+
+        Ok(machine)
     }
 }
 
@@ -269,8 +692,13 @@ pub struct ComponentBuilder<C: Component> {
     component: Option<Arc<C>>,
     as_schedulable: Option<SchedulableComponentInfo>,
     as_display: Option<DisplayComponentInfo>,
+    as_audio: Option<AudioComponentInfo>,
     as_input: Option<InputComponentInfo>,
     as_memory: Option<MemoryComponentInfo>,
+    as_debuggable: Option<DebuggableProcessorInfo>,
+    as_tape: Option<TapeComponentInfo>,
+    as_text_output: Option<TextOutputComponentInfo>,
+    snapshot_migrations: SnapshotMigrations,
     machine: MachineBuilder,
 }
 
@@ -314,6 +742,18 @@ impl<C: Component> ComponentBuilder<C> {
         self
     }
 
+    pub fn set_audio(&mut self) -> &mut Self
+    where
+        C: AudioComponent,
+    {
+        self.as_audio = self
+            .component
+            .clone()
+            .map(|c| AudioComponentInfo { component: c });
+
+        self
+    }
+
     pub fn set_memory(
         &mut self,
         ranges: impl IntoIterator<Item = (AddressSpaceId, Range<usize>)>,
@@ -333,11 +773,67 @@ impl<C: Component> ComponentBuilder<C> {
         self.as_memory = self.component.clone().map(|c| MemoryComponentInfo {
             component: c,
             assigned_ranges,
+            priority: 0,
         });
 
         self
     }
 
+    /// Lets a debugger attach to this processor
+    pub fn set_debuggable(&mut self) -> &mut Self
+    where
+        C: DebuggableProcessor,
+    {
+        self.as_debuggable = self
+            .component
+            .clone()
+            .map(|c| DebuggableProcessorInfo { component: c });
+
+        self
+    }
+
+    /// Puts this components transport controls in front of the user
+    pub fn set_tape(&mut self) -> &mut Self
+    where
+        C: TapeComponent,
+    {
+        self.as_tape = self
+            .component
+            .clone()
+            .map(|c| TapeComponentInfo { component: c });
+
+        self
+    }
+
+    /// Shows what this component prints in the debug menu
+    pub fn set_text_output(&mut self) -> &mut Self
+    where
+        C: TextOutputComponent,
+    {
+        self.as_text_output = self
+            .component
+            .clone()
+            .map(|c| TextOutputComponentInfo { component: c });
+
+        self
+    }
+
+    /// Lets the frontend find this component by name to plug peripherals into it
+    pub fn set_expansion_port(&mut self, name: impl Into<String>) -> &mut Self {
+        self.machine.expansion_ports.insert(name.into(), self.id);
+
+        self
+    }
+
+    /// Components with a higher priority shadow lower ones on busses using [BusConflictPolicy::Priority]
+    pub fn set_memory_priority(&mut self, priority: i8) -> &mut Self {
+        if let Some(memory_info) = &mut self.as_memory {
+            memory_info.priority = priority;
+        }
+
+        self
+    }
+
     pub fn set_input(
         &mut self,
         emulated_gamepad_types: impl IntoIterator<
@@ -357,6 +853,76 @@ impl<C: Component> ComponentBuilder<C> {
         self
     }
 
+    /// Version of the layout [Component::save_snapshot] writes, bump it whenever that layout changes
+    pub fn set_snapshot_version(&mut self, version: u32) -> &mut Self {
+        self.snapshot_migrations.set_version(version);
+
+        self
+    }
+
+    /// Lets snapshots written at version `from` still load, by converting them to `from + 1`
+    pub fn add_snapshot_migration(&mut self, from: u32, migration: SnapshotMigration) -> &mut Self {
+        self.snapshot_migrations.register(from, migration);
+
+        self
+    }
+
+    /// Checks if a ROM is present, noting it down for the frontend if it isn't
+    pub fn require_rom(&mut self, id: RomId, requirement: RomRequirement) -> bool {
+        let available = self.machine.rom_manager.is_available(id);
+
+        if available {
+            self.verify_rom(id);
+        } else {
+            self.machine
+                .missing_roms
+                .push(MissingRom { id, requirement });
+        }
+
+        available
+    }
+
+    /// Opens a ROM, noting it down for the frontend if it can't be found
+    pub fn open_rom(&mut self, id: RomId, requirement: RomRequirement) -> Option<RomHandle> {
+        let file = self.machine.rom_manager.handle(id, requirement);
+
+        if file.is_some() {
+            self.verify_rom(id);
+        } else {
+            self.machine
+                .missing_roms
+                .push(MissingRom { id, requirement });
+        }
+
+        file
+    }
+
+    fn verify_rom(&mut self, id: RomId) {
+        // Components may ask for the same ROM multiple times
+        if self
+            .machine
+            .rom_warnings
+            .iter()
+            .any(|rom_warning| rom_warning.id == id)
+        {
+            return;
+        }
+
+        match self.machine.rom_manager.verify(id) {
+            Ok(RomVerification::Verified) => {}
+            Ok(verification) => {
+                tracing::warn!("ROM {}: {}", id, verification);
+
+                self.machine
+                    .rom_warnings
+                    .push(RomWarning { id, verification });
+            }
+            Err(error) => {
+                tracing::error!("Could not verify ROM {}: {}", id, error);
+            }
+        }
+    }
+
     pub fn id(&self) -> ComponentId {
         self.id
     }
@@ -369,11 +935,17 @@ impl<C: Component> ComponentBuilder<C> {
         assert!(self.machine.component_store.0.len() == self.id.0 as usize);
 
         self.machine.component_store.0.push(ComponentTable {
+            name: std::any::type_name::<C>(),
             component: self.component.expect("Component did not initialize itself"),
             as_schedulable: self.as_schedulable,
             as_display: self.as_display,
+            as_audio: self.as_audio,
             as_input: self.as_input,
             as_memory: self.as_memory,
+            as_debuggable: self.as_debuggable,
+            as_tape: self.as_tape,
+            as_text_output: self.as_text_output,
+            snapshot_migrations: self.snapshot_migrations,
         });
 
         self.machine
@@ -1,47 +1,328 @@
-use super::Machine;
-use crate::{component::ComponentId, scheduler::Scheduler};
+use super::{clock::MachineTimestamp, migration::MigrationError, Machine};
+use crate::{
+    component::ComponentId,
+    logging::trace_event,
+    rom::{id::RomId, system::GameSystem},
+    scheduler::Scheduler,
+};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs::File, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
 
-#[derive(Serialize, Deserialize)]
+pub const SAVE_STATE_EXTENSION: &str = "mss";
+/// Start of every save state file, so anything else is turned away before decoding it
+pub(super) const SAVE_STATE_MAGIC: &[u8; 4] = b"MSS\x1a";
+/// Layout of the file around the machine state, bumped when the header or framing changes
+const SAVE_STATE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum SaveStateError {
+    #[error("Could not access save state {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Could not transfer save state: {0}")]
+    Stream(#[from] std::io::Error),
+    #[error("Could not write save state: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error("Could not read save state: {0}")]
+    Decode(#[from] rmp_serde::decode::Error),
+    #[error("File is not a save state")]
+    NotASaveState,
+    #[error(
+        "Save state format is version {found}, but this build only understands up to {current}"
+    )]
+    TooNew { found: u32, current: u32 },
+    #[error("Save state is for {found}, but the machine is {expected}")]
+    WrongSystem {
+        expected: GameSystem,
+        found: GameSystem,
+    },
+    #[error("Save state was made with different ROMs than the machine was built from")]
+    WrongRoms,
+    #[error("Save state was made by a machine built from different components, it has {found:?} where this one has {expected:?}")]
+    WrongComponents {
+        expected: Vec<String>,
+        found: Vec<String>,
+    },
+    #[error("{0}")]
+    Migration(#[from] MigrationError),
+}
+
+/// Written in front of the machine state, describing the machine it came from so a state is never loaded into one
+/// it doesn't fit
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SaveStateHeader {
+    pub format_version: u32,
+    /// Version of the emulator that wrote it, only for telling the user
+    pub crate_version: String,
+    pub system: GameSystem,
+    /// What the machine was built from, empty if it was put together by hand
+    pub roms: Vec<RomId>,
+    /// Type name of every component, in id order
+    pub components: Vec<String>,
+}
+
+impl SaveStateHeader {
+    fn new(machine: &Machine) -> Self {
+        Self {
+            format_version: SAVE_STATE_FORMAT_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            system: machine.system,
+            roms: machine.user_specified_roms.clone().unwrap_or_default(),
+            components: machine
+                .component_store
+                .iter()
+                .map(|(_, table)| table.name.to_string())
+                .collect(),
+        }
+    }
+
+    /// Checks a header read from a file against the machine it is about to be loaded into
+    fn validate(&self, machine: &Machine) -> Result<(), SaveStateError> {
+        let expected = Self::new(machine);
+
+        if self.format_version > SAVE_STATE_FORMAT_VERSION {
+            return Err(SaveStateError::TooNew {
+                found: self.format_version,
+                current: SAVE_STATE_FORMAT_VERSION,
+            });
+        }
+
+        if self.system != expected.system {
+            return Err(SaveStateError::WrongSystem {
+                expected: expected.system,
+                found: self.system,
+            });
+        }
+
+        if self.roms != expected.roms {
+            return Err(SaveStateError::WrongRoms);
+        }
+
+        // Component ids are only positions, so the same ids on a different lineup would load into the wrong ones
+        if self.components != expected.components {
+            return Err(SaveStateError::WrongComponents {
+                expected: expected.components,
+                found: self.components.clone(),
+            });
+        }
+
+        if self.crate_version != expected.crate_version {
+            tracing::info!(
+                "Save state was made by version {}, converting it to {}",
+                self.crate_version,
+                expected.crate_version
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct MachineState {
     pub scheduler: Scheduler,
-    pub components: HashMap<ComponentId, rmpv::Value>,
+    #[serde(default)]
+    pub timestamp: MachineTimestamp,
+    #[serde(default)]
+    pub fast_boot: bool,
+    /// Kept in id order so the same machine always encodes the same way, which rewinding relies on to diff them
+    pub components: BTreeMap<ComponentId, rmpv::Value>,
+    /// Snapshot version of each component, states from before versioning are all version 0
+    #[serde(default)]
+    pub versions: BTreeMap<ComponentId, u32>,
 }
 
-// TODO: Replace this with a system that does less copying and supports versioning
+// TODO: Replace this with a system that does less copying
 // TODO: Replace this with a system that uses a stable id system, component ids are not stable
 
 impl Machine {
-    pub fn save_snapshot(&self, path: impl AsRef<Path>) {
-        let mut file = File::create(path).unwrap();
-
-        rmp_serde::encode::write_named(
-            &mut file,
-            &MachineState {
-                scheduler: self.scheduler.clone(),
-                components: self
-                    .component_store
-                    .iter()
-                    .map(|(component_id, table)| (component_id, table.component.save_snapshot()))
-                    .collect(),
-            },
-        )
-        .unwrap();
-    }
-
-    pub fn load_snapshot(&mut self, path: impl AsRef<Path>) {
-        let mut file = File::create(path).unwrap();
-        let state: MachineState = rmp_serde::decode::from_read(&mut file).unwrap();
+    /// Everything needed to put a machine built the same way where this one is now
+    pub fn state(&self) -> MachineState {
+        MachineState {
+            scheduler: self.scheduler.clone(),
+            timestamp: self.clock.now(),
+            fast_boot: self.fast_boot,
+            components: self
+                .component_store
+                .iter()
+                .filter(|(_, table)| !table.component.volatile())
+                .map(|(component_id, table)| (component_id, table.component.save_snapshot()))
+                .collect(),
+            versions: self
+                .component_store
+                .iter()
+                .filter(|(_, table)| !table.component.volatile())
+                .map(|(component_id, table)| (component_id, table.snapshot_migrations.version()))
+                .collect(),
+        }
+    }
 
-        self.scheduler = state.scheduler;
+    /// Writes a save state, a header describing this machine followed by its state
+    pub fn write_state(&self, writer: &mut impl Write) -> Result<(), SaveStateError> {
+        writer.write_all(SAVE_STATE_MAGIC)?;
+        rmp_serde::encode::write_named(writer, &SaveStateHeader::new(self))?;
+        rmp_serde::encode::write_named(writer, &self.state())?;
+
+        Ok(())
+    }
+
+    /// Loads a save state written by [Self::write_state], leaving the machine alone if it was made for another one
+    pub fn read_state(&mut self, reader: &mut impl Read) -> Result<(), SaveStateError> {
+        let mut magic = [0; SAVE_STATE_MAGIC.len()];
+        if reader.read_exact(&mut magic).is_err() || &magic != SAVE_STATE_MAGIC {
+            return Err(SaveStateError::NotASaveState);
+        }
+
+        let header: SaveStateHeader = rmp_serde::decode::from_read(&mut *reader)?;
+        header.validate(self)?;
+
+        let state: MachineState = rmp_serde::decode::from_read(reader)?;
+        self.restore_state(state)?;
+
+        Ok(())
+    }
+
+    pub fn save_state(&self, path: impl AsRef<Path>) -> Result<(), SaveStateError> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|error| SaveStateError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        self.write_state(&mut writer)?;
+        writer.flush().map_err(|error| SaveStateError::Io {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    pub fn load_state(&mut self, path: impl AsRef<Path>) -> Result<(), SaveStateError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| SaveStateError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        self.read_state(&mut BufReader::new(file))
+    }
+
+    /// Puts the machine back into a saved state, converting snapshots older components wrote along the way
+    ///
+    /// Every component is converted before any is loaded, so a state that can't be converted leaves the machine as it
+    /// was. Once all are loaded each gets [crate::component::Component::after_load_snapshot]
+    pub fn restore_state(&mut self, state: MachineState) -> Result<(), MigrationError> {
+        let mut components = Vec::with_capacity(state.components.len());
 
         for (component_id, component_state) in state.components {
+            let table = self
+                .component_store
+                .get(component_id)
+                .expect("Missing component from manifest!");
+            if table.component.volatile() {
+                continue;
+            }
+            let version = state.versions.get(&component_id).copied().unwrap_or(0);
+
+            components.push((
+                component_id,
+                table
+                    .snapshot_migrations
+                    .migrate(table.name, version, component_state)?,
+            ));
+        }
+
+        if state.fast_boot != self.fast_boot {
+            tracing::warn!(
+                "Snapshot was taken with fast boot {}, but this machine has it {}",
+                if state.fast_boot { "on" } else { "off" },
+                if self.fast_boot { "on" } else { "off" }
+            );
+        }
+
+        // The watchdog and hooks belong to this machine, not the state
+        let heartbeat = self.scheduler.heartbeat();
+        let frame_hooks = self.scheduler.frame_hooks();
+        self.scheduler = state.scheduler;
+        self.scheduler.set_heartbeat(heartbeat);
+        self.scheduler.set_frame_hooks(frame_hooks);
+        self.clock.restore(state.timestamp);
+
+        for (component_id, component_state) in components {
             self.component_store
                 .get(component_id)
                 .expect("Missing component from manifest!")
                 .component
                 .load_snapshot(component_state);
         }
+
+        for (_, table) in self.component_store.iter() {
+            table.component.after_load_snapshot();
+        }
+
+        trace_event(format_args!(
+            "Frame {}: Restored a state",
+            self.clock.frames()
+        ));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        rom::{manager::RomManager, system::OtherSystem},
+        runtime::rendering_backend::DisplayComponentInitializationData,
+    };
+    use std::{fs, io::Cursor, sync::Arc};
+
+    #[test]
+    fn save_states_only_load_into_matching_machines() {
+        let directory =
+            std::env::temp_dir().join(format!("multiemu-save-state-test-{}", std::process::id()));
+        let rom_path = directory.join("rom.ch8");
+        fs::create_dir_all(&directory).unwrap();
+        // Jumps to itself forever
+        fs::write(&rom_path, [0x12, 0x00]).unwrap();
+
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let rom_id = RomId::from_read(&mut File::open(&rom_path).unwrap());
+        rom_manager.rom_paths.insert(rom_id, rom_path);
+        let build = |system| {
+            let machine = Machine::from_system(vec![rom_id], rom_manager.clone(), system);
+            for display in machine.display_components() {
+                display
+                    .component
+                    .set_display_data(DisplayComponentInitializationData::Software);
+            }
+            machine
+        };
+
+        let mut machine = build(GameSystem::Other(OtherSystem::Chip8));
+        let mut saved = Vec::new();
+        machine.write_state(&mut saved).unwrap();
+        machine.read_state(&mut Cursor::new(&saved)).unwrap();
+
+        let mut other = build(GameSystem::Other(OtherSystem::XoChip));
+        assert!(matches!(
+            other.read_state(&mut Cursor::new(&saved)),
+            Err(SaveStateError::WrongSystem { .. })
+        ));
+        assert!(matches!(
+            machine.read_state(&mut Cursor::new(b"not a save state")),
+            Err(SaveStateError::NotASaveState)
+        ));
+
+        fs::remove_dir_all(&directory).unwrap();
     }
 }
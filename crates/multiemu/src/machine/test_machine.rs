@@ -0,0 +1,369 @@
+use super::{
+    bus_log::{BusAccess, BusAccessKind, BusLogReader, BusLogWriter},
+    Machine, MachineBuilder,
+};
+use crate::{
+    component::{schedulable::SchedulableComponent, Component, ComponentId, FromConfig},
+    definitions::misc::{
+        io::InterruptConnection,
+        memory::standard::{StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents},
+    },
+    memory::AddressSpaceId,
+    rom::{manager::RomManager, system::GameSystem},
+    scheduler::running_component,
+};
+use std::{
+    ops::Range,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Builds a bare machine around the components under test, without any system definition or roms
+///
+/// ```ignore
+/// let (builder, cpu) = TestMachineBuilder::new()
+///     .bus(0, 16)
+///     .scratch_ram(0, 0..0x10000, 0x00)
+///     .component::<M6502>(config);
+/// let mut machine = builder.build();
+/// ```
+pub struct TestMachineBuilder {
+    builder: MachineBuilder,
+    interrupts: Arc<Mutex<Vec<InterruptEvent>>>,
+}
+
+impl Default for TestMachineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestMachineBuilder {
+    pub fn new() -> Self {
+        Self {
+            builder: Machine::build(
+                GameSystem::Unknown,
+                Arc::new(RomManager::new(None).unwrap()),
+            ),
+            interrupts: Arc::default(),
+        }
+    }
+
+    pub fn bus(mut self, address_space: AddressSpaceId, width: u8) -> Self {
+        self.builder = self.builder.insert_bus(address_space, width);
+        self
+    }
+
+    /// Readable and writable memory over the range, every byte starting out as `fill`
+    pub fn scratch_ram(self, address_space: AddressSpaceId, range: Range<usize>, fill: u8) -> Self {
+        self.component::<StandardMemory>(StandardMemoryConfig {
+            max_word_size: 8,
+            readable: true,
+            writable: true,
+            assigned_range: range,
+            assigned_address_space: address_space,
+            initial_contents: StandardMemoryInitialContents::Value { value: fill },
+        })
+        .0
+    }
+
+    pub fn component<C: FromConfig>(mut self, config: C::Config) -> (Self, ComponentId) {
+        let (builder, id) = self.builder.build_component::<C>(config);
+        self.builder = builder;
+
+        (self, id)
+    }
+
+    /// A line for stub components to pulse, which the built machine keeps a record of
+    pub fn interrupt_line(&self, name: &'static str) -> InterruptLine {
+        InterruptLine {
+            name,
+            raised: Arc::default(),
+            events: self.interrupts.clone(),
+        }
+    }
+
+    /// Escape hatch for anything the conveniences here don't cover
+    pub fn map(mut self, f: impl FnOnce(MachineBuilder) -> MachineBuilder) -> Self {
+        self.builder = f(self.builder);
+        self
+    }
+
+    pub fn build(self) -> TestMachine {
+        TestMachine {
+            machine: self.builder.build(),
+            interrupts: self.interrupts,
+            bus_script: None,
+        }
+    }
+}
+
+/// One edge on an [InterruptLine]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterruptEvent {
+    pub line: &'static str,
+    pub raised: bool,
+    /// Component the scheduler was running when the edge happened
+    pub source: Option<ComponentId>,
+}
+
+/// Stand in for an interrupt line, recording edges rather than delivering them
+#[derive(Debug, Clone)]
+pub struct InterruptLine {
+    name: &'static str,
+    raised: Arc<AtomicBool>,
+    events: Arc<Mutex<Vec<InterruptEvent>>>,
+}
+
+impl InterruptLine {
+    pub fn raise(&self) {
+        self.set(true);
+    }
+
+    pub fn lower(&self) {
+        self.set(false);
+    }
+
+    pub fn is_raised(&self) -> bool {
+        self.raised.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, raised: bool) {
+        // Only edges are interesting, holding a line doesn't need recording every cycle
+        if self.raised.swap(raised, Ordering::Relaxed) != raised {
+            self.events.lock().unwrap().push(InterruptEvent {
+                line: self.name,
+                raised,
+                source: running_component(),
+            });
+        }
+    }
+}
+
+impl InterruptConnection for InterruptLine {
+    fn set_interrupt(&self, raised: bool) {
+        self.set(raised);
+    }
+}
+
+/// An access a test expects to see on the bus, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusExpectation {
+    pub kind: BusAccessKind,
+    pub address_space: AddressSpaceId,
+    pub address: usize,
+    pub data: Vec<u8>,
+}
+
+impl BusExpectation {
+    pub fn read(address_space: AddressSpaceId, address: usize, data: &[u8]) -> Self {
+        Self {
+            kind: BusAccessKind::Read,
+            address_space,
+            address,
+            data: data.to_vec(),
+        }
+    }
+
+    pub fn write(address_space: AddressSpaceId, address: usize, data: &[u8]) -> Self {
+        Self {
+            kind: BusAccessKind::Write,
+            address_space,
+            address,
+            data: data.to_vec(),
+        }
+    }
+
+    fn matches(&self, access: &BusAccess) -> bool {
+        self.kind == access.kind
+            && self.address_space == access.address_space
+            && self.address == access.address
+            && self.data == access.data
+    }
+}
+
+struct BusScript {
+    path: PathBuf,
+    expected: Vec<BusExpectation>,
+}
+
+/// A built [TestMachineBuilder], with helpers for poking at it and stepping it by hand
+pub struct TestMachine {
+    pub machine: Machine,
+    interrupts: Arc<Mutex<Vec<InterruptEvent>>>,
+    bus_script: Option<BusScript>,
+}
+
+impl TestMachine {
+    pub fn component<C: Component>(&self, id: ComponentId) -> Arc<C> {
+        self.machine
+            .component_store
+            .get(id)
+            .expect("No such component")
+            .component
+            .clone()
+            .into_any_arc()
+            .downcast::<C>()
+            .ok()
+            .expect("Component is not of the requested type")
+    }
+
+    /// Advances the whole machine by this many scheduler ticks, with no regard for real time
+    pub fn step(&mut self, ticks: u64) -> u64 {
+        self.machine
+            .scheduler
+            .run_ticks(&self.machine.component_store, ticks)
+    }
+
+    /// Runs a single component for the period, skipping the scheduler entirely
+    pub fn run_component<C: SchedulableComponent>(&self, id: ComponentId, period: u64) {
+        self.component::<C>(id).run(period);
+    }
+
+    pub fn load(&self, address_space: AddressSpaceId, address: usize, data: &[u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            self.machine
+                .memory_translation_table
+                .write(address + offset, std::slice::from_ref(byte), address_space)
+                .unwrap();
+        }
+    }
+
+    pub fn peek(&self, address_space: AddressSpaceId, address: usize, length: usize) -> Vec<u8> {
+        let mut buffer = vec![0; length];
+
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            self.machine
+                .memory_translation_table
+                .preview(address + offset, std::slice::from_mut(byte), address_space)
+                .unwrap();
+        }
+
+        buffer
+    }
+
+    /// Edges seen on lines from [TestMachineBuilder::interrupt_line] since the last call
+    pub fn take_interrupts(&self) -> Vec<InterruptEvent> {
+        std::mem::take(&mut self.interrupts.lock().unwrap())
+    }
+
+    /// Starts recording the bus, to be checked against the script by [Self::verify_bus]
+    pub fn expect_bus(&mut self, expected: impl IntoIterator<Item = BusExpectation>) {
+        static NEXT_LOG: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "multiemu-test-bus-{}-{}.log",
+            std::process::id(),
+            NEXT_LOG.fetch_add(1, Ordering::Relaxed)
+        ));
+        self.machine
+            .memory_translation_table
+            .start_bus_log(BusLogWriter::create(&path).unwrap());
+        self.bus_script = Some(BusScript {
+            path,
+            expected: expected.into_iter().collect(),
+        });
+    }
+
+    /// Stops recording and panics on the first access that differs from the script
+    pub fn verify_bus(&mut self) {
+        let script = self
+            .bus_script
+            .take()
+            .expect("No bus script to verify against");
+        self.machine
+            .memory_translation_table
+            .stop_bus_log()
+            .unwrap()
+            .finish()
+            .unwrap();
+
+        let accesses: Vec<_> = BusLogReader::open(&script.path)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let _ = std::fs::remove_file(&script.path);
+
+        for (index, expected) in script.expected.iter().enumerate() {
+            let access = accesses.get(index).unwrap_or_else(|| {
+                panic!(
+                    "Bus access {} never happened, expected {:x?}",
+                    index, expected
+                )
+            });
+
+            assert!(
+                expected.matches(access),
+                "Bus access {} was {:x?}, expected {:x?}",
+                index,
+                access,
+                expected
+            );
+        }
+
+        assert_eq!(
+            accesses.len(),
+            script.expected.len(),
+            "Unexpected bus accesses after the script ended: {:x?}",
+            &accesses[script.expected.len()..]
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scripted_bus() {
+        let mut machine = TestMachineBuilder::new()
+            .bus(0, 16)
+            .scratch_ram(0, 0..0x100, 0xaa)
+            .build();
+        assert_eq!(machine.peek(0, 0x10, 2), [0xaa, 0xaa]);
+
+        machine.expect_bus([
+            BusExpectation::write(0, 0x10, &[0x12]),
+            BusExpectation::read(0, 0x10, &[0x12]),
+        ]);
+        machine.load(0, 0x10, &[0x12]);
+        let mut buffer = [0];
+        machine
+            .machine
+            .memory_translation_table
+            .read(0x10, &mut buffer, 0)
+            .unwrap();
+        machine.verify_bus();
+    }
+
+    #[test]
+    fn interrupt_edges() {
+        let builder = TestMachineBuilder::new().bus(0, 16);
+        let irq = builder.interrupt_line("irq");
+        let machine = builder.build();
+
+        irq.raise();
+        irq.raise();
+        irq.lower();
+
+        assert_eq!(
+            machine.take_interrupts(),
+            [
+                InterruptEvent {
+                    line: "irq",
+                    raised: true,
+                    source: None
+                },
+                InterruptEvent {
+                    line: "irq",
+                    raised: false,
+                    source: None
+                }
+            ]
+        );
+        assert!(machine.take_interrupts().is_empty());
+    }
+}
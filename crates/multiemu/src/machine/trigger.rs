@@ -0,0 +1,261 @@
+use super::clock::MachineTimestamp;
+use crate::{
+    config::GLOBAL_CONFIG,
+    memory::{AddressSpaceId, MemoryTranslationTable, VALID_ACCESS_SIZES},
+    rom::id::RomId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    /// The value is different from last frame, the compared value is ignored
+    Changed,
+    /// The value went up by exactly the compared value since last frame
+    IncreasedBy,
+}
+
+/// "The value at this address compares to this number"
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MemoryCondition {
+    pub address_space: AddressSpaceId,
+    pub address: usize,
+    /// Bytes read little endian, one of the valid memory access sizes
+    #[serde(default = "default_condition_size")]
+    pub size: u8,
+    pub comparison: Comparison,
+    #[serde(default)]
+    pub value: u64,
+}
+
+fn default_condition_size() -> u8 {
+    1
+}
+
+impl MemoryCondition {
+    fn read(&self, memory_translation_table: &MemoryTranslationTable) -> Option<u64> {
+        let mut buffer = [0; 8];
+
+        memory_translation_table
+            .preview(
+                self.address,
+                &mut buffer[..self.size as usize],
+                self.address_space,
+            )
+            .ok()?;
+
+        Some(u64::from_le_bytes(buffer))
+    }
+
+    fn holds(&self, current: u64, previous: Option<u64>) -> bool {
+        match self.comparison {
+            Comparison::Equal => current == self.value,
+            Comparison::NotEqual => current != self.value,
+            Comparison::Less => current < self.value,
+            Comparison::LessOrEqual => current <= self.value,
+            Comparison::Greater => current > self.value,
+            Comparison::GreaterOrEqual => current >= self.value,
+            Comparison::Changed => previous.is_some_and(|previous| previous != current),
+            Comparison::IncreasedBy => {
+                previous.is_some_and(|previous| current.wrapping_sub(previous) == self.value)
+            }
+        }
+    }
+}
+
+/// A named game event, like a level being cleared, defined by conditions on memory
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TriggerDefinition {
+    pub name: String,
+    /// Every condition must hold at the same time for the trigger to fire
+    pub conditions: Vec<MemoryCondition>,
+    /// Fire only the first time, like an achievement, instead of every time the conditions start holding
+    #[serde(default)]
+    pub once: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriggerEvent {
+    pub name: String,
+    pub timestamp: MachineTimestamp,
+}
+
+#[derive(Debug)]
+struct TriggerState {
+    definition: TriggerDefinition,
+    previous_values: Vec<Option<u64>>,
+    /// Triggers fire on the frame their conditions start holding, not every frame they hold
+    held: bool,
+    fired: bool,
+}
+
+/// Checks the triggers registered for the running ROMs every frame and tells whoever is listening when they fire
+#[derive(Debug, Default)]
+pub struct TriggerEngine {
+    triggers: Vec<TriggerState>,
+    subscribers: Vec<Sender<TriggerEvent>>,
+}
+
+impl TriggerEngine {
+    /// Loads `<rom id>.ron` files, holding lists of trigger definitions, from the trigger directory
+    pub fn load(roms: &[RomId]) -> Self {
+        let trigger_directory = GLOBAL_CONFIG.read().unwrap().trigger_directory.clone();
+        let mut engine = Self::default();
+
+        for rom_id in roms {
+            let path = trigger_directory.join(format!("{}.ron", rom_id));
+            let Ok(file) = File::open(&path) else {
+                continue;
+            };
+
+            match ron::de::from_reader::<_, Vec<TriggerDefinition>>(file) {
+                Ok(definitions) => {
+                    tracing::info!("Loaded {} triggers for ROM {}", definitions.len(), rom_id);
+                    engine.register(definitions);
+                }
+                Err(error) => {
+                    tracing::error!("Could not parse triggers {}: {}", path.display(), error);
+                }
+            }
+        }
+
+        engine
+    }
+
+    /// Definitions reading a size memory can't be accessed with are logged and left out
+    pub fn register(&mut self, definitions: impl IntoIterator<Item = TriggerDefinition>) {
+        self.triggers.extend(
+            definitions
+                .into_iter()
+                .filter(|definition| {
+                    let valid = definition
+                        .conditions
+                        .iter()
+                        .all(|condition| VALID_ACCESS_SIZES.contains(&(condition.size as usize)));
+
+                    if !valid {
+                        tracing::error!(
+                            "Trigger {} reads a size other than {:?}, ignoring it",
+                            definition.name,
+                            VALID_ACCESS_SIZES
+                        );
+                    }
+
+                    valid
+                })
+                .map(|definition| TriggerState {
+                    previous_values: vec![None; definition.conditions.len()],
+                    definition,
+                    held: false,
+                    fired: false,
+                }),
+        );
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = &TriggerDefinition> {
+        self.triggers.iter().map(|trigger| &trigger.definition)
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<TriggerEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    pub fn evaluate(
+        &mut self,
+        memory_translation_table: &MemoryTranslationTable,
+        timestamp: MachineTimestamp,
+    ) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        for trigger in self.triggers.iter_mut() {
+            let mut holds = true;
+
+            for (condition, previous_value) in trigger
+                .definition
+                .conditions
+                .iter()
+                .zip(trigger.previous_values.iter_mut())
+            {
+                let current_value = condition.read(memory_translation_table);
+
+                holds &= current_value
+                    .is_some_and(|current_value| condition.holds(current_value, *previous_value));
+                *previous_value = current_value;
+            }
+
+            if holds && !trigger.held && !(trigger.definition.once && trigger.fired) {
+                trigger.fired = true;
+
+                let event = TriggerEvent {
+                    name: trigger.definition.name.clone(),
+                    timestamp,
+                };
+                tracing::debug!("Trigger {} fired at {:?}", event.name, timestamp);
+
+                self.subscribers
+                    .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+            }
+
+            trigger.held = holds;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delta_comparisons() {
+        let condition = MemoryCondition {
+            address_space: 0,
+            address: 0,
+            size: 1,
+            comparison: Comparison::IncreasedBy,
+            value: 1,
+        };
+
+        assert!(!condition.holds(5, None));
+        assert!(condition.holds(5, Some(4)));
+        assert!(!condition.holds(5, Some(5)));
+    }
+
+    #[test]
+    fn invalid_sizes_are_ignored() {
+        let definition = |size| TriggerDefinition {
+            name: format!("Size {}", size),
+            conditions: vec![MemoryCondition {
+                address_space: 0,
+                address: 0,
+                size,
+                comparison: Comparison::Equal,
+                value: 0,
+            }],
+            once: false,
+        };
+        let mut engine = TriggerEngine::default();
+
+        engine.register([definition(3), definition(16), definition(2)]);
+
+        assert_eq!(
+            engine
+                .definitions()
+                .map(|definition| definition.name.as_str())
+                .collect::<Vec<_>>(),
+            ["Size 2"]
+        );
+    }
+}
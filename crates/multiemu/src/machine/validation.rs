@@ -0,0 +1,290 @@
+use super::MachineBuilder;
+use crate::{
+    component::{input::EmulatedGamepadTypeId, ComponentId},
+    memory::{AddressSpaceId, BusConflictPolicy},
+};
+use rangemap::RangeMap;
+use std::{collections::HashMap, fmt::Display, ops::Range};
+use thiserror::Error;
+
+/// Something about how a machine was put together that would break once it runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigurationProblem {
+    OverlappingMemory {
+        address_space: AddressSpaceId,
+        range: Range<usize>,
+        first: String,
+        second: String,
+    },
+    MissingBus {
+        address_space: AddressSpaceId,
+        component: String,
+    },
+    ExceedsBus {
+        address_space: AddressSpaceId,
+        range: Range<usize>,
+        width: u8,
+        component: String,
+    },
+    ZeroFrequency {
+        component: String,
+    },
+    UnknownScheduleDependency {
+        component: String,
+        dependency: ComponentId,
+    },
+    /// Neither scheduled nor memory mapped, so nothing can ever change what it shows
+    UndrivenDisplay {
+        component: String,
+    },
+    UnknownGamepadType {
+        component: String,
+        gamepad_type: EmulatedGamepadTypeId,
+    },
+}
+
+impl Display for ConfigurationProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigurationProblem::OverlappingMemory {
+                address_space,
+                range,
+                first,
+                second,
+            } => write!(
+                f,
+                "{} and {} are both assigned {:#x}..{:#x} in address space {}",
+                first, second, range.start, range.end, address_space
+            ),
+            ConfigurationProblem::MissingBus {
+                address_space,
+                component,
+            } => write!(
+                f,
+                "{} is assigned memory in address space {} which was never inserted",
+                component, address_space
+            ),
+            ConfigurationProblem::ExceedsBus {
+                address_space,
+                range,
+                width,
+                component,
+            } => write!(
+                f,
+                "{} is assigned {:#x}..{:#x} but address space {} is only {} bits wide",
+                component, range.start, range.end, address_space, width
+            ),
+            ConfigurationProblem::ZeroFrequency { component } => {
+                write!(f, "{} is scheduled to run zero times per second", component)
+            }
+            ConfigurationProblem::UnknownScheduleDependency {
+                component,
+                dependency,
+            } => write!(
+                f,
+                "{} is ordered against {:?} which is not a schedulable component",
+                component, dependency
+            ),
+            ConfigurationProblem::UndrivenDisplay { component } => write!(
+                f,
+                "{} is a display but is neither scheduled nor memory mapped",
+                component
+            ),
+            ConfigurationProblem::UnknownGamepadType {
+                component,
+                gamepad_type,
+            } => write!(
+                f,
+                "{} has a gamepad of type {} which no component registered",
+                component, gamepad_type
+            ),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub struct MachineConfigurationError(pub Vec<ConfigurationProblem>);
+
+impl Display for MachineConfigurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Machine is misconfigured:")?;
+
+        for problem in &self.0 {
+            write!(f, "\n - {}", problem)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MachineBuilder {
+    /// Looks over the whole machine and reports every problem found, not just the first
+    pub fn validate(&self) -> Result<(), MachineConfigurationError> {
+        let mut problems = Vec::new();
+        let mut populations: HashMap<AddressSpaceId, RangeMap<usize, ComponentId>> =
+            HashMap::default();
+        let registered_gamepad_types: Vec<_> = self
+            .component_store
+            .components()
+            .filter_map(|table| table.as_input.as_ref())
+            .flat_map(|info| info.registered_gamepad_types.keys())
+            .collect();
+
+        for (component_id, table) in self.component_store.iter() {
+            let name = || self.component_name(component_id);
+
+            if let Some(memory_info) = &table.as_memory {
+                for (address_space, ranges) in &memory_info.assigned_ranges {
+                    let Some(width) = self
+                        .memory_translation_table
+                        .address_space_width(*address_space)
+                    else {
+                        problems.push(ConfigurationProblem::MissingBus {
+                            address_space: *address_space,
+                            component: name(),
+                        });
+                        continue;
+                    };
+
+                    // A 64 bit bus can't be exceeded
+                    let bus_end = 1usize.checked_shl(width as u32).unwrap_or(0);
+                    let population = populations.entry(*address_space).or_default();
+
+                    for range in ranges.iter() {
+                        if bus_end != 0 && range.end > bus_end {
+                            problems.push(ConfigurationProblem::ExceedsBus {
+                                address_space: *address_space,
+                                range: range.clone(),
+                                width,
+                                component: name(),
+                            });
+                        }
+
+                        let overlaps_allowed = self
+                            .memory_translation_table
+                            .conflict_policy(*address_space)
+                            != Some(BusConflictPolicy::Reject);
+
+                        for (overlapping_range, other_id) in
+                            population.overlapping(range).filter(|_| !overlaps_allowed)
+                        {
+                            problems.push(ConfigurationProblem::OverlappingMemory {
+                                address_space: *address_space,
+                                range: overlapping_range.start.max(range.start)
+                                    ..overlapping_range.end.min(range.end),
+                                first: self.component_name(*other_id),
+                                second: name(),
+                            });
+                        }
+
+                        population.insert(range.clone(), component_id);
+                    }
+                }
+            }
+
+            if let Some(schedulable_info) = &table.as_schedulable {
+                if *schedulable_info.timings.numer() == 0 {
+                    problems.push(ConfigurationProblem::ZeroFrequency { component: name() });
+                }
+
+                for dependency in schedulable_info
+                    .run_after
+                    .iter()
+                    .chain(schedulable_info.run_before.iter())
+                {
+                    if self
+                        .component_store
+                        .get(*dependency)
+                        .is_none_or(|table| table.as_schedulable.is_none())
+                    {
+                        problems.push(ConfigurationProblem::UnknownScheduleDependency {
+                            component: name(),
+                            dependency: *dependency,
+                        });
+                    }
+                }
+            }
+
+            if table.as_display.is_some()
+                && table.as_schedulable.is_none()
+                && table.as_memory.is_none()
+            {
+                problems.push(ConfigurationProblem::UndrivenDisplay { component: name() });
+            }
+
+            if let Some(input_info) = &table.as_input {
+                for gamepad_type in &input_info.registered_gamepads {
+                    if !registered_gamepad_types.contains(&gamepad_type) {
+                        problems.push(ConfigurationProblem::UnknownGamepadType {
+                            component: name(),
+                            gamepad_type: gamepad_type.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(MachineConfigurationError(problems))
+        }
+    }
+
+    fn component_name(&self, component_id: ComponentId) -> String {
+        let name = self
+            .component_store
+            .get(component_id)
+            .map(|table| table.name)
+            .unwrap_or("Unknown");
+
+        format!("{} ({:?})", name, component_id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        definitions::misc::memory::standard::{
+            StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents,
+        },
+        machine::Machine,
+        rom::{manager::RomManager, system::GameSystem},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn reports_every_problem() {
+        let memory = |assigned_range| StandardMemoryConfig {
+            max_word_size: 1,
+            readable: true,
+            writable: true,
+            assigned_range,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value: 0 },
+        };
+
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .build_component::<StandardMemory>(memory(0x00..0x80));
+        let (machine, _) = machine.build_component::<StandardMemory>(memory(0x40..0x200));
+
+        let problems = machine.validate().unwrap_err().0;
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(
+            problems[0],
+            ConfigurationProblem::ExceedsBus { .. }
+        ));
+        assert!(matches!(
+            &problems[1],
+            ConfigurationProblem::OverlappingMemory { range, .. } if *range == (0x40..0x80)
+        ));
+
+        // Refused instead of panicking, so the launcher can show the list
+        assert!(machine.try_build().is_err());
+    }
+}
@@ -16,24 +16,34 @@ mod config;
 mod definitions;
 mod gui;
 mod input;
+mod localization;
+mod logging;
 mod machine;
 mod memory;
 mod processor;
 mod rom;
 mod runtime;
+mod save;
 mod scheduler;
+mod tape;
 
 fn main() {
-    tracing_subscriber::fmt::init();
+    #[cfg(platform_desktop)]
+    let cli = {
+        use clap::Parser;
+
+        cli::Cli::parse()
+    };
+
+    #[cfg(platform_desktop)]
+    logging::init(cli.log_directives.clone());
+    #[cfg(not(platform_desktop))]
+    logging::init(Vec::new());
     tracing::info!("MultiEMU v{}", env!("CARGO_PKG_VERSION"));
 
     #[cfg(platform_desktop)]
     {
-        use clap::Parser;
         use cli::handle_cli;
-        use cli::Cli;
-
-        let cli = Cli::parse();
 
         if let Some(action) = cli.action {
             handle_cli(action).unwrap();
@@ -44,6 +54,8 @@ fn main() {
     let global_config_guard = GLOBAL_CONFIG.try_read().unwrap();
     let rom_manager = Arc::new(RomManager::new(Some(&global_config_guard.database_file)).unwrap());
     let graphics_setting = global_config_guard.graphics_setting;
+    #[cfg(graphics_vulkan)]
+    let vulkan_device = global_config_guard.vulkan_device.clone();
     drop(global_config_guard);
 
     match graphics_setting {
@@ -54,7 +66,19 @@ fn main() {
         GraphicsSettings::Vulkan => {
             use runtime::platform::desktop::renderer::vulkan::VulkanRenderingRuntime;
 
-            PlatformRuntime::<VulkanRenderingRuntime>::launch_gui(rom_manager);
+            match VulkanRenderingRuntime::probe(&vulkan_device) {
+                Ok(device_name) => {
+                    tracing::info!("Vulkan device {} is usable", device_name);
+                    PlatformRuntime::<VulkanRenderingRuntime>::launch_gui(rom_manager);
+                }
+                Err(error) => {
+                    tracing::error!(
+                        "Could not initialize vulkan ({}), falling back to software rendering",
+                        error
+                    );
+                    PlatformRuntime::<SoftwareRenderingRuntime>::launch_gui(rom_manager);
+                }
+            }
         }
     }
 }
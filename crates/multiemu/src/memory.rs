@@ -1,8 +1,23 @@
-use crate::{component::ComponentId, machine::component_store::ComponentStore};
+use crate::{
+    component::{memory::MemoryComponent, ComponentId},
+    machine::{
+        bus_log::{BusAccess, BusAccessKind, BusLogWriter},
+        component_store::ComponentStore,
+    },
+    scheduler::running_component,
+};
 use arrayvec::ArrayVec;
 use bitvec::{field::BitField, order::Lsb0, view::BitView};
 use rangemap::RangeMap;
-use std::{collections::HashMap, ops::Range, sync::Arc};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex, Weak,
+    },
+};
 use thiserror::Error;
 
 pub const VALID_ACCESS_SIZES: &[usize] = &[1, 2, 4, 8];
@@ -11,6 +26,8 @@ pub const VALID_ACCESS_SIZES: &[usize] = &[1, 2, 4, 8];
 pub enum ReadMemoryOperationErrorFailureType {
     Denied,
     OutOfBus,
+    /// The bus faults on accesses not aligned to their size, see [AlignmentPolicy::Fault]
+    Misaligned,
 }
 
 #[derive(Error, Debug)]
@@ -21,17 +38,27 @@ pub struct ReadMemoryOperationError(RangeMap<usize, ReadMemoryOperationErrorFail
 pub enum WriteMemoryOperationErrorFailureType {
     Denied,
     OutOfBus,
+    Misaligned,
 }
 
 #[derive(Error, Debug)]
 #[error("Write operation failed: {0:#?}")]
 pub struct WriteMemoryOperationError(RangeMap<usize, WriteMemoryOperationErrorFailureType>);
 
+#[derive(Error, Debug)]
+pub enum ReadModifyWriteMemoryOperationError {
+    #[error(transparent)]
+    Read(#[from] ReadMemoryOperationError),
+    #[error(transparent)]
+    Write(#[from] WriteMemoryOperationError),
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum PreviewMemoryOperationErrorFailureType {
     Denied,
     OutOfBus,
     Impossible,
+    Misaligned,
 }
 
 #[derive(Error, Debug)]
@@ -81,37 +108,341 @@ const MAX_ACCESS_SIZE: u8 = const {
 
 pub type AddressSpaceId = u8;
 
+/// What happens when more than one component is mapped to the same address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BusConflictPolicy {
+    /// Overlaps are a mistake and machine validation refuses them
+    #[default]
+    Reject,
+    /// The component with the highest memory priority answers, ties go to the one inserted last
+    Priority,
+    /// Every component answers and the bus sees the bitwise AND, like a cartridge ROM fighting the CPU on a write
+    And,
+    /// Same as above but for busses that are pulled low
+    Or,
+}
+
+/// How a read-modify-write shows up on the bus, which matters to anything with side effects on access
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadModifyWriteBehavior {
+    /// One read and one write
+    #[default]
+    Single,
+    /// The NMOS 6502 writes the unmodified value back before the modified one, which mappers and IO registers see
+    DoubleWrite,
+    /// The 65C02 reads a second time instead of writing the old value back
+    DoubleRead,
+}
+
+/// What a bus does with an access that isn't aligned to its size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AlignmentPolicy {
+    /// Any address works, like on 8 bit busses and x86
+    #[default]
+    Unrestricted,
+    /// The access fails with a misaligned error, for processors that raise an exception on it
+    Fault,
+    /// The low bits are dropped and the aligned word is accessed instead
+    Ignore,
+    /// Same as ignore but reads come back rotated so the addressed byte is first, like the ARM7
+    ///
+    /// Writes are just aligned, as there is nothing to rotate
+    Rotate,
+}
+
+impl AlignmentPolicy {
+    /// Where an access really goes and how many bytes the read gets rotated by, None if it faults
+    fn resolve(&self, address: usize, size: usize) -> Option<(usize, usize)> {
+        let misalignment = address % size;
+
+        if misalignment == 0 {
+            return Some((address, 0));
+        }
+
+        match self {
+            AlignmentPolicy::Unrestricted => Some((address, 0)),
+            AlignmentPolicy::Fault => None,
+            AlignmentPolicy::Ignore => Some((address - misalignment, 0)),
+            AlignmentPolicy::Rotate => Some((address - misalignment, misalignment)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct BusInfo {
     population: RangeMap<usize, ComponentId>,
+    /// Components that share addresses already in the population, only used with [BusConflictPolicy::And] and [BusConflictPolicy::Or]
+    conflicts: Vec<(Range<usize>, ComponentId)>,
+    priorities: HashMap<ComponentId, i8>,
+    policy: BusConflictPolicy,
+    alignment: AlignmentPolicy,
     width: u8,
+    /// Bumped every time what answers an address may have changed
+    generation: AtomicU64,
+}
+
+#[derive(Debug)]
+struct WatchState {
+    address_space: AddressSpaceId,
+    range: Range<usize>,
+    generation: AtomicU64,
+}
+
+/// A subscription to writes landing in a range of an address space
+///
+/// Meant for RAM watch windows and overlays, which can check [MemoryWatch::changed] every frame instead of previewing the whole range
+///
+/// Only writes going through the [MemoryTranslationTable] are seen, a component changing its own memory internally will not bump the generation
+#[derive(Debug)]
+pub struct MemoryWatch {
+    state: Arc<WatchState>,
+    last_seen: u64,
+}
+
+impl MemoryWatch {
+    /// Whether anything was written to the range since the last time this was called
+    pub fn changed(&mut self) -> bool {
+        let generation = self.generation();
+        let changed = generation != self.last_seen;
+        self.last_seen = generation;
+
+        changed
+    }
+
+    /// Total writes that touched the range since the watch was created
+    pub fn generation(&self) -> u64 {
+        self.state.generation.load(Ordering::Acquire)
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.state.range.clone()
+    }
+
+    pub fn address_space(&self) -> AddressSpaceId {
+        self.state.address_space
+    }
+}
+
+/// Which accesses a [Watchpoint] stops on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchpointKind {
+    Read,
+    Write,
+    /// Reads and writes
+    Access,
+}
+
+impl WatchpointKind {
+    fn matches(self, kind: BusAccessKind) -> bool {
+        match self {
+            WatchpointKind::Read => kind == BusAccessKind::Read,
+            WatchpointKind::Write => kind == BusAccessKind::Write,
+            WatchpointKind::Access => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct WatchpointState {
+    address_space: AddressSpaceId,
+    range: Range<usize>,
+    kind: WatchpointKind,
+    hits: Sender<BusAccess>,
+}
+
+/// Reports every read or write touching a range of an address space, with the value and the component behind it
+///
+/// Unlike a [MemoryWatch] every hit is kept, for debuggers that need to know exactly what happened. Previews never
+/// hit, and dropping the watchpoint removes it
+#[derive(Debug)]
+pub struct Watchpoint {
+    state: Arc<WatchpointState>,
+    hits: Receiver<BusAccess>,
+}
+
+impl Watchpoint {
+    /// Hits since the last call, oldest first
+    pub fn hits(&self) -> impl Iterator<Item = BusAccess> + '_ {
+        self.hits.try_iter()
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.state.range.clone()
+    }
+
+    pub fn address_space(&self) -> AddressSpaceId {
+        self.state.address_space
+    }
+
+    pub fn kind(&self) -> WatchpointKind {
+        self.state.kind
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct MemoryTranslationTable {
     busses: HashMap<AddressSpaceId, BusInfo>,
     component_store: Option<Arc<ComponentStore>>,
+    watches: Mutex<Vec<Weak<WatchState>>>,
+    /// Lets writes skip the lock when nobody is watching
+    watch_count: AtomicUsize,
+    bus_log: Mutex<Option<BusLogWriter>>,
+    /// Same idea as the watch count, so accesses only lock when a log is being written
+    bus_logging: AtomicBool,
+    watchpoints: Mutex<Vec<Weak<WatchpointState>>>,
+    watchpoint_count: AtomicUsize,
 }
 
 impl MemoryTranslationTable {
     pub fn insert_bus(&mut self, id: AddressSpaceId, width: u8) {
         self.busses.entry(id).or_insert_with(|| BusInfo {
             population: RangeMap::default(),
+            conflicts: Vec::default(),
+            priorities: HashMap::default(),
+            policy: BusConflictPolicy::default(),
+            alignment: AlignmentPolicy::default(),
             width,
+            generation: AtomicU64::new(0),
         });
     }
 
+    pub fn set_conflict_policy(&mut self, id: AddressSpaceId, policy: BusConflictPolicy) {
+        self.busses
+            .get_mut(&id)
+            .expect("Bus must be initialized before setting its conflict policy")
+            .policy = policy;
+    }
+
+    pub fn conflict_policy(&self, id: AddressSpaceId) -> Option<BusConflictPolicy> {
+        self.busses.get(&id).map(|bus_info| bus_info.policy)
+    }
+
+    pub fn set_alignment_policy(&mut self, id: AddressSpaceId, alignment: AlignmentPolicy) {
+        self.busses
+            .get_mut(&id)
+            .expect("Bus must be initialized before setting its alignment policy")
+            .alignment = alignment;
+    }
+
+    pub fn alignment_policy(&self, id: AddressSpaceId) -> Option<AlignmentPolicy> {
+        self.busses.get(&id).map(|bus_info| bus_info.alignment)
+    }
+
     pub fn insert_component(
         &mut self,
         id: AddressSpaceId,
         component_id: ComponentId,
         ranges: impl IntoIterator<Item = Range<usize>>,
+        priority: i8,
     ) {
-        self.busses
+        let bus_info = self
+            .busses
             .get_mut(&id)
-            .expect("Bus must be initialized before inserting component")
-            .population
-            .extend(ranges.into_iter().map(|range| (range, component_id)));
+            .expect("Bus must be initialized before inserting component");
+        bus_info.priorities.insert(component_id, priority);
+        *bus_info.generation.get_mut() += 1;
+
+        for range in ranges {
+            match bus_info.policy {
+                BusConflictPolicy::Reject => {
+                    bus_info.population.insert(range, component_id);
+                }
+                BusConflictPolicy::Priority => {
+                    let lost: Vec<_> = bus_info
+                        .population
+                        .overlapping(&range)
+                        .filter(|(_, other_id)| bus_info.priorities[other_id] > priority)
+                        .map(|(other_range, other_id)| (other_range.clone(), *other_id))
+                        .collect();
+
+                    bus_info.population.insert(range, component_id);
+
+                    // Put back whatever outranks us
+                    for (other_range, other_id) in lost {
+                        bus_info.population.insert(other_range, other_id);
+                    }
+                }
+                BusConflictPolicy::And | BusConflictPolicy::Or => {
+                    let overlaps: Vec<_> = bus_info
+                        .population
+                        .overlapping(&range)
+                        .map(|(other_range, _)| {
+                            other_range.start.max(range.start)..other_range.end.min(range.end)
+                        })
+                        .collect();
+                    let gaps: Vec<_> = bus_info.population.gaps(&range).collect();
+
+                    for gap in gaps {
+                        bus_info.population.insert(gap, component_id);
+                    }
+
+                    bus_info
+                        .conflicts
+                        .extend(overlaps.into_iter().map(|overlap| (overlap, component_id)));
+                }
+            }
+        }
+    }
+
+    fn memory_component(&self, component_id: ComponentId) -> &Arc<dyn MemoryComponent> {
+        self.component_store
+            .as_ref()
+            .unwrap()
+            .get(component_id)
+            .and_then(|table| table.as_memory.as_ref().map(|info| &info.component))
+            .unwrap()
+    }
+
+    /// Mixes in what every other component on a shared address answers, errors from them leave the bus alone
+    fn resolve_read_conflicts(
+        &self,
+        bus_info: &BusInfo,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        preview: bool,
+    ) {
+        let accessing_range = address..address + buffer.len();
+
+        for (conflict_range, component_id) in bus_info.conflicts.iter() {
+            let overlap_start = accessing_range.start.max(conflict_range.start);
+            let overlap_end = accessing_range.end.min(conflict_range.end);
+
+            if overlap_start >= overlap_end {
+                continue;
+            }
+
+            let component = self.memory_component(*component_id);
+            let buffer_subrange = (overlap_start - address)..(overlap_end - address);
+            let mut other_value = buffer[buffer_subrange.clone()].to_vec();
+
+            let failed = if preview {
+                let mut errors = RangeMap::default();
+                component.preview_memory(
+                    overlap_start,
+                    &mut other_value,
+                    address_space,
+                    &mut errors,
+                );
+                !errors.is_empty()
+            } else {
+                let mut errors = RangeMap::default();
+                component.read_memory(overlap_start, &mut other_value, address_space, &mut errors);
+                !errors.is_empty()
+            };
+
+            if failed {
+                continue;
+            }
+
+            for (byte, other_byte) in buffer[buffer_subrange].iter_mut().zip(other_value) {
+                match bus_info.policy {
+                    BusConflictPolicy::And => *byte &= other_byte,
+                    BusConflictPolicy::Or => *byte |= other_byte,
+                    _ => unreachable!(),
+                }
+            }
+        }
     }
 
     pub fn set_component_store(&mut self, component_store: Arc<ComponentStore>) {
@@ -125,6 +456,37 @@ impl MemoryTranslationTable {
             .expect("Too many address spaces!")
     }
 
+    pub fn address_space_ids(&self) -> impl Iterator<Item = AddressSpaceId> + '_ {
+        let mut ids: Vec<_> = self.busses.keys().copied().collect();
+        ids.sort_unstable();
+        ids.into_iter()
+    }
+
+    /// Address width of a bus in bits
+    pub fn address_space_width(&self, id: AddressSpaceId) -> Option<u8> {
+        self.busses.get(&id).map(|bus_info| bus_info.width)
+    }
+
+    /// Counter bumped whenever the mappings of a bus change
+    ///
+    /// Decoded instruction caches, recompiled blocks and page table fast paths can remember the generation they were
+    /// built at and throw themselves away when it moves, instead of rescanning the bus
+    pub fn generation(&self, id: AddressSpaceId) -> Option<u64> {
+        self.busses
+            .get(&id)
+            .map(|bus_info| bus_info.generation.load(Ordering::Acquire))
+    }
+
+    /// Tells everything caching this bus that an address may now answer differently
+    ///
+    /// Components that remap themselves while running, like bank switching cartridges or expansion ports, must call
+    /// this after every switch
+    pub fn mappings_changed(&self, id: AddressSpaceId) {
+        if let Some(bus_info) = self.busses.get(&id) {
+            bus_info.generation.fetch_add(1, Ordering::Release);
+        }
+    }
+
     /// Step through the memory translation table to fill the buffer with data
     ///
     /// Contents of the buffer upon failure are usually component specific
@@ -149,6 +511,16 @@ impl MemoryTranslationTable {
         // Cut off address
         let address = address.view_bits::<Lsb0>()[..bus_info.width as usize].load_le::<usize>();
 
+        let Some((address, rotation)) = bus_info.alignment.resolve(address, buffer.len()) else {
+            let mut errors = RangeMap::default();
+            errors.insert(
+                address..address + buffer.len(),
+                ReadMemoryOperationErrorFailureType::Misaligned,
+            );
+
+            return Err(ReadMemoryOperationError(errors));
+        };
+
         let mut needed_accesses =
             ArrayVec::<_, { MAX_ACCESS_SIZE as usize }>::from_iter([(address, 0..buffer.len())]);
 
@@ -209,6 +581,14 @@ impl MemoryTranslationTable {
             }
         }
 
+        if !bus_info.conflicts.is_empty() {
+            self.resolve_read_conflicts(bus_info, address, buffer, address_space, false);
+        }
+
+        buffer.rotate_left(rotation);
+        self.log_access(BusAccessKind::Read, address, buffer, address_space);
+        self.check_watchpoints(BusAccessKind::Read, address, buffer, address_space);
+
         Ok(())
     }
 
@@ -235,6 +615,16 @@ impl MemoryTranslationTable {
 
         let address = address.view_bits::<Lsb0>()[..bus_info.width as usize].load_le::<usize>();
 
+        let Some((address, _)) = bus_info.alignment.resolve(address, buffer.len()) else {
+            let mut errors = RangeMap::default();
+            errors.insert(
+                address..address + buffer.len(),
+                WriteMemoryOperationErrorFailureType::Misaligned,
+            );
+
+            return Err(WriteMemoryOperationError(errors));
+        };
+
         let mut needed_accesses =
             ArrayVec::<_, { MAX_ACCESS_SIZE as usize }>::from_iter([(address, 0..buffer.len())]);
 
@@ -295,9 +685,215 @@ impl MemoryTranslationTable {
             }
         }
 
+        // Everything sharing the address sees the write
+        for (conflict_range, component_id) in bus_info.conflicts.iter() {
+            let overlap_start = address.max(conflict_range.start);
+            let overlap_end = (address + buffer.len()).min(conflict_range.end);
+
+            if overlap_start < overlap_end {
+                self.memory_component(*component_id).write_memory(
+                    overlap_start,
+                    &buffer[(overlap_start - address)..(overlap_end - address)],
+                    address_space,
+                    &mut RangeMap::default(),
+                );
+            }
+        }
+
+        if self.watch_count.load(Ordering::Relaxed) != 0 {
+            self.notify_watches(address_space, address..address + buffer.len());
+        }
+
+        self.log_access(BusAccessKind::Write, address, buffer, address_space);
+        self.check_watchpoints(BusAccessKind::Write, address, buffer, address_space);
+
         Ok(())
     }
 
+    /// Reads into the buffer, lets the closure change it, then writes it back as one operation
+    ///
+    /// The buffer holds the modified value afterwards. Nothing is written if the read fails
+    #[inline]
+    pub fn read_modify_write(
+        &self,
+        address: usize,
+        buffer: &mut [u8],
+        address_space: AddressSpaceId,
+        behavior: ReadModifyWriteBehavior,
+        modify: impl FnOnce(&mut [u8]),
+    ) -> Result<(), ReadModifyWriteMemoryOperationError> {
+        self.read(address, buffer, address_space)?;
+
+        match behavior {
+            ReadModifyWriteBehavior::Single => {}
+            ReadModifyWriteBehavior::DoubleWrite => self.write(address, buffer, address_space)?,
+            ReadModifyWriteBehavior::DoubleRead => {
+                let mut discarded = [0; MAX_ACCESS_SIZE as usize];
+                self.read(address, &mut discarded[..buffer.len()], address_space)?;
+            }
+        }
+
+        modify(buffer);
+        self.write(address, buffer, address_space)?;
+
+        Ok(())
+    }
+
+    /// Starts recording every read and write, returning the log that was being written before if there was one
+    pub fn start_bus_log(&self, writer: BusLogWriter) -> Option<BusLogWriter> {
+        let previous = self.bus_log.lock().unwrap().replace(writer);
+        self.bus_logging.store(true, Ordering::Relaxed);
+
+        previous
+    }
+
+    /// Stops recording and hands the log back so it can be finished
+    pub fn stop_bus_log(&self) -> Option<BusLogWriter> {
+        self.bus_logging.store(false, Ordering::Relaxed);
+        self.bus_log.lock().unwrap().take()
+    }
+
+    #[inline]
+    fn log_access(
+        &self,
+        kind: BusAccessKind,
+        address: usize,
+        data: &[u8],
+        address_space: AddressSpaceId,
+    ) {
+        if !self.bus_logging.load(Ordering::Relaxed) {
+            return;
+        }
+
+        if let Some(writer) = self.bus_log.lock().unwrap().as_mut() {
+            writer.record(&BusAccess {
+                kind,
+                address_space,
+                address,
+                data: data.to_vec(),
+                source: running_component(),
+            });
+        }
+    }
+
+    /// Starts reporting accesses of the kind to the range, dropping the returned watchpoint removes it
+    pub fn watchpoint(
+        &self,
+        address_space: AddressSpaceId,
+        range: Range<usize>,
+        kind: WatchpointKind,
+    ) -> Watchpoint {
+        assert!(
+            self.busses.contains_key(&address_space),
+            "Non existant address space"
+        );
+
+        let (sender, receiver) = channel();
+        let state = Arc::new(WatchpointState {
+            address_space,
+            range,
+            kind,
+            hits: sender,
+        });
+
+        let mut watchpoints = self.watchpoints.lock().unwrap();
+        watchpoints.retain(|watchpoint| watchpoint.strong_count() != 0);
+        watchpoints.push(Arc::downgrade(&state));
+        self.watchpoint_count
+            .store(watchpoints.len(), Ordering::Relaxed);
+
+        Watchpoint {
+            state,
+            hits: receiver,
+        }
+    }
+
+    #[inline]
+    fn check_watchpoints(
+        &self,
+        kind: BusAccessKind,
+        address: usize,
+        data: &[u8],
+        address_space: AddressSpaceId,
+    ) {
+        if self.watchpoint_count.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        let accessed = address..address + data.len();
+        let mut watchpoints = self.watchpoints.lock().unwrap();
+
+        watchpoints.retain(|watchpoint| {
+            let Some(watchpoint) = watchpoint.upgrade() else {
+                return false;
+            };
+
+            if watchpoint.address_space == address_space
+                && watchpoint.kind.matches(kind)
+                && watchpoint.range.start < accessed.end
+                && accessed.start < watchpoint.range.end
+            {
+                let _ = watchpoint.hits.send(BusAccess {
+                    kind,
+                    address_space,
+                    address,
+                    data: data.to_vec(),
+                    source: running_component(),
+                });
+            }
+
+            true
+        });
+
+        self.watchpoint_count
+            .store(watchpoints.len(), Ordering::Relaxed);
+    }
+
+    /// Subscribe to writes in a range, dropping the returned watch unsubscribes
+    pub fn watch(&self, address_space: AddressSpaceId, range: Range<usize>) -> MemoryWatch {
+        assert!(
+            self.busses.contains_key(&address_space),
+            "Non existant address space"
+        );
+
+        let state = Arc::new(WatchState {
+            address_space,
+            range,
+            generation: AtomicU64::new(0),
+        });
+
+        let mut watches = self.watches.lock().unwrap();
+        watches.retain(|watch| watch.strong_count() != 0);
+        watches.push(Arc::downgrade(&state));
+        self.watch_count.store(watches.len(), Ordering::Relaxed);
+
+        MemoryWatch {
+            state,
+            last_seen: 0,
+        }
+    }
+
+    fn notify_watches(&self, address_space: AddressSpaceId, written: Range<usize>) {
+        let mut watches = self.watches.lock().unwrap();
+
+        watches.retain(|watch| {
+            let Some(watch) = watch.upgrade() else {
+                return false;
+            };
+
+            if watch.address_space == address_space
+                && watch.range.start < written.end
+                && written.start < watch.range.end
+            {
+                watch.generation.fetch_add(1, Ordering::AcqRel);
+            }
+
+            true
+        });
+
+        self.watch_count.store(watches.len(), Ordering::Relaxed);
+    }
+
     #[inline]
     pub fn preview(
         &self,
@@ -318,6 +914,16 @@ impl MemoryTranslationTable {
 
         let address = address.view_bits::<Lsb0>()[..bus_info.width as usize].load_le::<usize>();
 
+        let Some((address, rotation)) = bus_info.alignment.resolve(address, buffer.len()) else {
+            let mut errors = RangeMap::default();
+            errors.insert(
+                address..address + buffer.len(),
+                PreviewMemoryOperationErrorFailureType::Misaligned,
+            );
+
+            return Err(PreviewMemoryOperationError(errors));
+        };
+
         let mut needed_accesses =
             ArrayVec::<_, { MAX_ACCESS_SIZE as usize }>::from_iter([(address, 0..buffer.len())]);
 
@@ -382,6 +988,260 @@ impl MemoryTranslationTable {
             }
         }
 
+        if !bus_info.conflicts.is_empty() {
+            self.resolve_read_conflicts(bus_info, address, buffer, address_space, true);
+        }
+
+        buffer.rotate_left(rotation);
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        definitions::misc::memory::standard::{
+            StandardMemory, StandardMemoryConfig, StandardMemoryInitialContents,
+        },
+        machine::Machine,
+        rom::{manager::RomManager, system::GameSystem},
+    };
+
+    #[test]
+    fn and_conflicts() {
+        let memory = |value| StandardMemoryConfig {
+            max_word_size: 1,
+            readable: true,
+            writable: true,
+            assigned_range: 0..4,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value },
+        };
+
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .bus_conflict_policy(0, BusConflictPolicy::And)
+        .build_component::<StandardMemory>(memory(0b1100));
+        let machine = machine
+            .build_component::<StandardMemory>(memory(0b1010))
+            .0
+            .build();
+
+        let mut buffer = [0; 1];
+        machine
+            .memory_translation_table
+            .read(2, &mut buffer, 0)
+            .unwrap();
+        assert_eq!(buffer, [0b1000]);
+    }
+
+    #[test]
+    fn watches() {
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .build_component::<StandardMemory>(StandardMemoryConfig {
+            max_word_size: 1,
+            readable: true,
+            writable: true,
+            assigned_range: 0..16,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value: 0 },
+        });
+        let machine = machine.build();
+        let memory_translation_table = &machine.memory_translation_table;
+
+        let mut watch = memory_translation_table.watch(0, 4..8);
+        assert!(!watch.changed());
+
+        memory_translation_table.write(0, &[1], 0).unwrap();
+        assert!(!watch.changed());
+
+        memory_translation_table.write(6, &[1], 0).unwrap();
+        memory_translation_table.write(7, &[1], 0).unwrap();
+        assert!(watch.changed());
+        assert!(!watch.changed());
+        assert_eq!(watch.generation(), 2);
+
+        drop(watch);
+        memory_translation_table.write(6, &[1], 0).unwrap();
+        assert_eq!(
+            memory_translation_table.watch_count.load(Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[test]
+    fn watchpoints() {
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .build_component::<StandardMemory>(StandardMemoryConfig {
+            max_word_size: 2,
+            readable: true,
+            writable: true,
+            assigned_range: 0..16,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value: 0 },
+        });
+        let machine = machine.build();
+        let memory_translation_table = &machine.memory_translation_table;
+
+        let writes = memory_translation_table.watchpoint(0, 4..6, WatchpointKind::Write);
+        let accesses = memory_translation_table.watchpoint(0, 5..6, WatchpointKind::Access);
+
+        memory_translation_table.write(2, &[1, 2], 0).unwrap();
+        memory_translation_table.write(3, &[3, 4], 0).unwrap();
+        memory_translation_table.read(4, &mut [0, 0], 0).unwrap();
+        memory_translation_table.preview(5, &mut [0], 0).unwrap();
+
+        assert_eq!(
+            writes.hits().collect::<Vec<_>>(),
+            [BusAccess {
+                kind: BusAccessKind::Write,
+                address_space: 0,
+                address: 3,
+                data: vec![3, 4],
+                source: None,
+            }]
+        );
+        assert_eq!(
+            accesses
+                .hits()
+                .map(|hit| (hit.kind, hit.address))
+                .collect::<Vec<_>>(),
+            [(BusAccessKind::Read, 4)]
+        );
+
+        drop(writes);
+        drop(accesses);
+        memory_translation_table.write(4, &[1], 0).unwrap();
+        assert_eq!(
+            memory_translation_table
+                .watchpoint_count
+                .load(Ordering::Relaxed),
+            0
+        );
+    }
+
+    #[test]
+    fn generations() {
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .insert_bus(1, 8)
+        .build_component::<StandardMemory>(StandardMemoryConfig {
+            max_word_size: 1,
+            readable: true,
+            writable: true,
+            assigned_range: 0..16,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value: 0 },
+        });
+        let machine = machine.build();
+        let memory_translation_table = &machine.memory_translation_table;
+
+        assert_eq!(memory_translation_table.generation(0), Some(1));
+        assert_eq!(memory_translation_table.generation(1), Some(0));
+        assert_eq!(memory_translation_table.generation(2), None);
+
+        memory_translation_table.mappings_changed(1);
+        assert_eq!(memory_translation_table.generation(0), Some(1));
+        assert_eq!(memory_translation_table.generation(1), Some(1));
+    }
+
+    #[test]
+    fn read_modify_write() {
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .build_component::<StandardMemory>(StandardMemoryConfig {
+            max_word_size: 1,
+            readable: true,
+            writable: true,
+            assigned_range: 0..16,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value: 0x7f },
+        });
+        let machine = machine.build();
+        let memory_translation_table = &machine.memory_translation_table;
+
+        let mut watch = memory_translation_table.watch(0, 3..4);
+        let mut buffer = [0];
+        memory_translation_table
+            .read_modify_write(
+                3,
+                &mut buffer,
+                0,
+                ReadModifyWriteBehavior::DoubleWrite,
+                |value| value[0] += 1,
+            )
+            .unwrap();
+        assert_eq!(buffer, [0x80]);
+        // The old value went out first
+        assert_eq!(watch.generation(), 2);
+
+        memory_translation_table.read(3, &mut buffer, 0).unwrap();
+        assert_eq!(buffer, [0x80]);
+
+        memory_translation_table
+            .read_modify_write(
+                3,
+                &mut buffer,
+                0,
+                ReadModifyWriteBehavior::Single,
+                |value| value[0] -= 1,
+            )
+            .unwrap();
+        assert!(watch.changed());
+        assert_eq!(watch.generation(), 3);
+    }
+
+    #[test]
+    fn alignment() {
+        assert_eq!(AlignmentPolicy::Fault.resolve(6, 4), None);
+        assert_eq!(AlignmentPolicy::Ignore.resolve(6, 4), Some((4, 0)));
+        assert_eq!(AlignmentPolicy::Unrestricted.resolve(6, 4), Some((6, 0)));
+
+        let (machine, _) = Machine::build(
+            GameSystem::Unknown,
+            Arc::new(RomManager::new(None).unwrap()),
+        )
+        .insert_bus(0, 8)
+        .bus_alignment_policy(0, AlignmentPolicy::Rotate)
+        .build_component::<StandardMemory>(StandardMemoryConfig {
+            max_word_size: 4,
+            readable: true,
+            writable: true,
+            assigned_range: 0..16,
+            assigned_address_space: 0,
+            initial_contents: StandardMemoryInitialContents::Value { value: 0 },
+        });
+        let machine = machine.build();
+        let memory_translation_table = &machine.memory_translation_table;
+
+        memory_translation_table.write(4, &[1, 2, 3, 4], 0).unwrap();
+
+        let mut buffer = [0; 4];
+        memory_translation_table.read(5, &mut buffer, 0).unwrap();
+        assert_eq!(buffer, [2, 3, 4, 1]);
+
+        // Writes land on the aligned word
+        memory_translation_table.write(7, &[5, 6], 0).unwrap();
+        memory_translation_table.read(4, &mut buffer, 0).unwrap();
+        assert_eq!(buffer, [1, 2, 5, 6]);
+    }
+}
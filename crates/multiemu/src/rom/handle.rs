@@ -0,0 +1,106 @@
+use super::id::RomId;
+use memmap2::Mmap;
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::{self, Read},
+    ops::Deref,
+    sync::Arc,
+};
+
+enum RomContents {
+    Mapped(Mmap),
+    /// For when mapping the file didn't work, like on platforms without mmap
+    Loaded(Vec<u8>),
+}
+
+/// A ROM handed out by the [super::manager::RomManager]
+///
+/// Cloning is cheap and every clone reads the same contents, so components holding one can be snapshotted and
+/// duplicated without caring about file handles or seek positions
+#[derive(Clone)]
+pub struct RomHandle {
+    id: RomId,
+    contents: Arc<RomContents>,
+}
+
+impl Debug for RomHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RomHandle")
+            .field("id", &self.id)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl RomHandle {
+    pub(super) fn open(id: RomId, mut file: File) -> io::Result<Self> {
+        // Safety: ROMs aren't supposed to change under us, if they do we only read garbage
+        let contents = match unsafe { Mmap::map(&file) } {
+            Ok(mapping) => RomContents::Mapped(mapping),
+            Err(error) => {
+                tracing::debug!("Could not map ROM {}, reading it instead: {}", id, error);
+
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                RomContents::Loaded(contents)
+            }
+        };
+
+        Ok(Self {
+            id,
+            contents: Arc::new(contents),
+        })
+    }
+
+    /// Wraps ROM contents already in memory
+    pub fn from_bytes(id: RomId, contents: Vec<u8>) -> Self {
+        Self {
+            id,
+            contents: Arc::new(RomContents::Loaded(contents)),
+        }
+    }
+
+    pub fn id(&self) -> RomId {
+        self.id
+    }
+
+    /// Copies out as much as the ROM has starting at offset, returning how many bytes that was
+    pub fn read_at(&self, offset: usize, buffer: &mut [u8]) -> usize {
+        let Some(available) = self.get(offset..) else {
+            return 0;
+        };
+
+        let amount = available.len().min(buffer.len());
+        buffer[..amount].copy_from_slice(&available[..amount]);
+        amount
+    }
+}
+
+impl Deref for RomHandle {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self.contents.as_ref() {
+            RomContents::Mapped(mapping) => mapping,
+            RomContents::Loaded(contents) => contents,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_at_end() {
+        let handle =
+            RomHandle::from_bytes(RomId::from_read(&mut [1, 2, 3].as_slice()), vec![1, 2, 3]);
+        let clone = handle.clone();
+        let mut buffer = [0xff; 4];
+
+        assert_eq!(clone.read_at(1, &mut buffer), 2);
+        assert_eq!(buffer, [2, 3, 0xff, 0xff]);
+        assert_eq!(handle.read_at(5, &mut buffer), 0);
+    }
+}
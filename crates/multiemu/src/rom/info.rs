@@ -6,14 +6,56 @@ use native_model::Model;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
+/// Older versions of the database model, kept around so existing databases can be migrated
+pub mod v1 {
+    use super::*;
+
+    #[serde_as]
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    #[native_model(id = 1, version = 1)]
+    #[native_db]
+    pub struct RomInfo {
+        #[primary_key]
+        pub id: RomId,
+        pub name: Option<String>,
+        pub system: GameSystem,
+        pub region: Option<RomRegion>,
+    }
+}
+
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-#[native_model(id = 1, version = 1)]
+#[native_model(id = 1, version = 2, from = v1::RomInfo)]
 #[native_db]
 pub struct RomInfo {
     #[primary_key]
     pub id: RomId,
     pub name: Option<String>,
     pub system: GameSystem,
-    pub region: Option<RomRegion>,
+    pub regions: Vec<RomRegion>,
+    /// The ROM this is a clone of, in DAT terms, if it is one
+    pub parent: Option<RomId>,
+}
+
+impl From<v1::RomInfo> for RomInfo {
+    fn from(value: v1::RomInfo) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            system: value.system,
+            regions: value.region.into_iter().collect(),
+            parent: None,
+        }
+    }
+}
+
+impl From<RomInfo> for v1::RomInfo {
+    fn from(value: RomInfo) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            system: value.system,
+            region: value.regions.first().copied(),
+        }
+    }
 }
@@ -0,0 +1,124 @@
+use crate::runtime::progress::{ProgressHandle, PROGRESS};
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, Sender, TryRecvError},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+};
+
+/// Workers hashing and copying files, more than this and they just fight over the disk
+const MAX_WORKERS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Threads for the file heavy parts of managing ROMs, so hashing or extracting a big archive never stalls a frame
+///
+/// The threads only start once the first job is submitted, so the many short lived managers tests make cost nothing
+#[derive(Debug, Default)]
+pub struct IoPool {
+    sender: OnceLock<Mutex<Sender<Job>>>,
+}
+
+impl IoPool {
+    /// Queues work for the pool
+    pub fn submit<T: Send + 'static>(&self, work: impl FnOnce() -> T + Send + 'static) -> IoJob<T> {
+        let (result_sender, result_receiver) = channel();
+
+        let job: Job = Box::new(move || {
+            // Nobody waiting for the result is fine, they just lost interest
+            let _ = result_sender.send(work());
+        });
+
+        self.sender()
+            .lock()
+            .unwrap()
+            .send(job)
+            .expect("IO workers went away");
+
+        IoJob {
+            receiver: result_receiver,
+        }
+    }
+
+    /// Same as [Self::submit] but shown on [PROGRESS] under the label until it finishes
+    ///
+    /// The work is handed the progress entry so it can report how far along it is and notice being cancelled
+    pub fn submit_tracked<T: Send + 'static>(
+        &self,
+        label: impl Into<String>,
+        work: impl FnOnce(&ProgressHandle) -> T + Send + 'static,
+    ) -> IoJob<T> {
+        let progress = PROGRESS.begin(label, None);
+
+        self.submit(move || work(&progress))
+    }
+
+    fn sender(&self) -> &Mutex<Sender<Job>> {
+        self.sender.get_or_init(|| {
+            let (sender, receiver) = channel::<Job>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            let workers = thread::available_parallelism()
+                .map_or(1, |threads| threads.get())
+                .min(MAX_WORKERS);
+
+            for index in 0..workers {
+                let receiver = receiver.clone();
+
+                thread::Builder::new()
+                    .name(format!("rom-io-{}", index))
+                    .spawn(move || loop {
+                        // The lock is only held while waiting, never while working
+                        let job = receiver.lock().unwrap().recv();
+
+                        match job {
+                            Ok(job) => job(),
+                            // The pool was dropped
+                            Err(_) => return,
+                        }
+                    })
+                    .expect("Could not start IO worker");
+            }
+
+            Mutex::new(sender)
+        })
+    }
+}
+
+/// The eventual result of something submitted to an [IoPool]
+#[derive(Debug)]
+pub struct IoJob<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> IoJob<T> {
+    /// The result if the job is done, for checking once a frame without blocking
+    ///
+    /// Panics if the job itself panicked
+    pub fn poll(&self) -> Option<T> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => panic!("IO job panicked"),
+        }
+    }
+
+    /// Blocks until the job is done, never call this from the emulation or render threads
+    pub fn wait(self) -> T {
+        self.receiver.recv().expect("IO job panicked")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn jobs_complete() {
+        let pool = IoPool::default();
+        let jobs: Vec<_> = (0..8).map(|value| pool.submit(move || value * 2)).collect();
+
+        let results: Vec<_> = jobs.into_iter().map(IoJob::wait).collect();
+        assert_eq!(results, (0..8).map(|value| value * 2).collect::<Vec<_>>());
+    }
+}
@@ -1,4 +1,12 @@
-use super::{id::RomId, info::RomInfo};
+use super::{
+    handle::RomHandle,
+    id::RomId,
+    info::{v1, RomInfo},
+    io::IoPool,
+    region::RomRegion,
+    statistics::PlayStatistics,
+    system::{strip_brackets_and_parens, GameSystem},
+};
 use dashmap::DashMap;
 use std::{
     collections::HashMap,
@@ -8,16 +16,23 @@ use std::{
     path::{Path, PathBuf},
     sync::LazyLock,
 };
+use strum::Display;
 
 static DATABASE_MODELS: LazyLock<native_db::Models> = LazyLock::new(|| {
     let mut models = native_db::Models::new();
+    models.define::<v1::RomInfo>().unwrap();
     models.define::<RomInfo>().unwrap();
+    models.define::<PlayStatistics>().unwrap();
     models
 });
 
 pub struct RomManager {
     pub rom_information: native_db::Database<'static>,
     pub rom_paths: DashMap<RomId, PathBuf>,
+    /// ROMs components have opened, shared so every component and machine copy reads the same mapping
+    rom_handles: DashMap<RomId, RomHandle>,
+    /// Where hashing, scanning and extraction happen so they stay off the emulation and render threads
+    pub io: IoPool,
 }
 
 // native_db databases don't implement debug
@@ -38,9 +53,16 @@ impl RomManager {
             native_db::Builder::new().create_in_memory(&DATABASE_MODELS)?
         };
 
+        // Bring entries written by older versions up to date
+        let transaction = rom_information.rw_transaction()?;
+        transaction.migrate::<RomInfo>()?;
+        transaction.commit()?;
+
         Ok(Self {
             rom_information,
             rom_paths: DashMap::new(),
+            rom_handles: DashMap::new(),
+            io: IoPool::default(),
         })
     }
 
@@ -129,35 +151,143 @@ impl RomManager {
         Ok(incorrect_roms)
     }
 
-    /// Components should use this function to load roms for themselves
+    /// Provides a ROM straight from memory, so tests and the fuzzer never have to touch the disk
+    ///
+    /// Replaces whatever was open under that id, components that already hold a handle keep the old contents
+    pub fn insert_bytes(&self, id: RomId, contents: Vec<u8>) {
+        self.rom_handles
+            .insert(id, RomHandle::from_bytes(id, contents));
+    }
+
+    /// If a ROM can be opened right now
+    pub fn is_available(&self, id: RomId) -> bool {
+        self.rom_handles.contains_key(&id)
+            || self
+                .rom_paths
+                .get(&id)
+                .is_some_and(|path| path.value().is_file())
+    }
+
+    /// Picks the best ROM for a game title, like "Tetris", out of every release in the database
+    ///
+    /// Regions earlier in the preference list win, then ROMs the user actually has, then parents over clones
+    pub fn find_by_title(
+        &self,
+        title: &str,
+        system: Option<GameSystem>,
+        region_preference: &[RomRegion],
+    ) -> Result<Option<RomId>, Box<dyn Error>> {
+        let title = normalize_title(title);
+        let transaction = self.rom_information.r_transaction()?;
+
+        let best = transaction
+            .scan()
+            .primary::<RomInfo>()?
+            .all()?
+            .flatten()
+            .filter(|rom_info| system.is_none_or(|system| rom_info.system == system))
+            .filter(|rom_info| {
+                rom_info
+                    .name
+                    .as_deref()
+                    .is_some_and(|name| normalize_title(name) == title)
+            })
+            .min_by_key(|rom_info| {
+                let region_rank = rom_info
+                    .regions
+                    .iter()
+                    .filter_map(|region| {
+                        region_preference
+                            .iter()
+                            .position(|preferred| preferred == region)
+                    })
+                    .min()
+                    .unwrap_or(region_preference.len());
+
+                (
+                    region_rank,
+                    !self.is_available(rom_info.id),
+                    rom_info.parent.is_some(),
+                )
+            });
+
+        Ok(best.map(|rom_info| rom_info.id))
+    }
+
+    /// Every ROM in the database that is a clone of this one
+    pub fn clones(&self, parent: RomId) -> Result<Vec<RomInfo>, Box<dyn Error>> {
+        let transaction = self.rom_information.r_transaction()?;
+
+        Ok(transaction
+            .scan()
+            .primary::<RomInfo>()?
+            .all()?
+            .flatten()
+            .filter(|rom_info| rom_info.parent == Some(parent))
+            .collect())
+    }
+
+    /// Opens the ROM file directly, components should use [RomManager::handle] instead
+    ///
+    /// ROMs provided with [RomManager::insert_bytes] have no file, so this won't find them
     pub fn open(&self, id: RomId, requirement: RomRequirement) -> Option<File> {
         if let Some(path) = self.rom_paths.get(&id) {
             return File::open(path.value()).ok();
         }
 
-        match requirement {
-            RomRequirement::Sometimes => {
-                tracing::warn!(
-                    "Could not find ROM {} for machine, machine will continue in a degraded state",
-                    id
-                );
-            }
-            RomRequirement::Optional => {
-                tracing::info!(
-                    "Could not find ROM {} for machine, but it's optional for runtime",
-                    id
-                );
-            }
-            RomRequirement::Required => {
-                tracing::error!("ROM {} is required for machine, but not found", id);
-            }
-        }
+        report_missing(id, requirement);
 
         None
     }
+
+    /// Components should use this function to load roms for themselves
+    pub fn handle(&self, id: RomId, requirement: RomRequirement) -> Option<RomHandle> {
+        if let Some(handle) = self.rom_handles.get(&id) {
+            return Some(handle.clone());
+        }
+
+        let file = self.open(id, requirement)?;
+        let handle = match RomHandle::open(id, file) {
+            Ok(handle) => handle,
+            Err(error) => {
+                tracing::error!("Could not read ROM {}: {}", id, error);
+                return None;
+            }
+        };
+
+        self.rom_handles.insert(id, handle.clone());
+        Some(handle)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+fn report_missing(id: RomId, requirement: RomRequirement) {
+    match requirement {
+        RomRequirement::Sometimes => {
+            tracing::warn!(
+                "Could not find ROM {} for machine, machine will continue in a degraded state",
+                id
+            );
+        }
+        RomRequirement::Optional => {
+            tracing::info!(
+                "Could not find ROM {} for machine, but it's optional for runtime",
+                id
+            );
+        }
+        RomRequirement::Required => {
+            tracing::error!("ROM {} is required for machine, but not found", id);
+        }
+    }
+}
+
+/// A ROM a machine asked for that could not be found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MissingRom {
+    pub id: RomId,
+    pub requirement: RomRequirement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display)]
 pub enum RomRequirement {
     /// Ok to boot machine without this ROM but runtime failure can occur without it
     Sometimes,
@@ -166,3 +296,12 @@ pub enum RomRequirement {
     /// Machine can not boot without this ROM
     Required,
 }
+
+/// Reduces a No-Intro name down to just the title so releases can be compared
+fn normalize_title(name: &str) -> String {
+    strip_brackets_and_parens(name)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
@@ -1,7 +1,13 @@
 pub mod graphics;
+pub mod handle;
 pub mod id;
 pub mod info;
+pub mod io;
 pub mod manager;
 pub mod region;
 pub mod specification;
+pub mod statistics;
 pub mod system;
+pub mod test_roms;
+pub mod verification;
+pub mod watch;
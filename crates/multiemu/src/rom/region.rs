@@ -1,9 +1,83 @@
 use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    EnumIter,
+    Display,
+)]
 pub enum RomRegion {
     World,
     Japan,
     Europe,
     NorthAmerica,
+    Korea,
+    China,
+    Brazil,
+    Australia,
+}
+
+impl RomRegion {
+    /// Pulls the regions out of a No-Intro style name, like "Tetris (USA, Europe)"
+    pub fn from_nointro_name(name: &str) -> Vec<RomRegion> {
+        let mut regions = Vec::new();
+
+        for tag in name
+            .split('(')
+            .skip(1)
+            .filter_map(|tag| tag.split_once(')').map(|(tag, _)| tag))
+        {
+            for region in tag.split(',').map(str::trim) {
+                let region = match region {
+                    "World" => RomRegion::World,
+                    "Japan" => RomRegion::Japan,
+                    "Europe" => RomRegion::Europe,
+                    // Canada gets lumped in with the USA by basically everyone
+                    "USA" | "Canada" => RomRegion::NorthAmerica,
+                    "Korea" => RomRegion::Korea,
+                    "China" | "Taiwan" | "Hong Kong" => RomRegion::China,
+                    "Brazil" => RomRegion::Brazil,
+                    "Australia" => RomRegion::Australia,
+                    // European countries released with the rest of europe
+                    "France" | "Germany" | "Italy" | "Spain" | "Netherlands" | "Sweden"
+                    | "United Kingdom" => RomRegion::Europe,
+                    // Not a region tag at all, probably version or language info
+                    _ => continue,
+                };
+
+                if !regions.contains(&region) {
+                    regions.push(region);
+                }
+            }
+        }
+
+        regions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nointro_regions() {
+        assert_eq!(
+            RomRegion::from_nointro_name("Tetris (USA, Europe) (Rev 1)"),
+            vec![RomRegion::NorthAmerica, RomRegion::Europe]
+        );
+        assert_eq!(
+            RomRegion::from_nointro_name("Pulseman (Japan) (En,Ja)"),
+            vec![RomRegion::Japan]
+        );
+        assert!(RomRegion::from_nointro_name("Homebrew").is_empty());
+    }
 }
@@ -0,0 +1,171 @@
+use super::{id::RomId, manager::RomManager};
+use crate::machine::clock::MachineClock;
+use native_db::native_db;
+use native_db::ToKey;
+use native_model::native_model;
+use native_model::Model;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// How much a ROM has been played, kept in the library database next to [super::info::RomInfo]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[native_model(id = 2, version = 1)]
+#[native_db]
+pub struct PlayStatistics {
+    #[primary_key]
+    pub id: RomId,
+    pub play_time: Duration,
+    pub launches: u64,
+    pub last_played: Option<SystemTime>,
+}
+
+impl PlayStatistics {
+    fn new(id: RomId) -> Self {
+        Self {
+            id,
+            play_time: Duration::ZERO,
+            launches: 0,
+            last_played: None,
+        }
+    }
+}
+
+impl RomManager {
+    pub fn play_statistics(&self, rom_id: RomId) -> Option<PlayStatistics> {
+        self.rom_information
+            .r_transaction()
+            .ok()?
+            .get()
+            .primary::<PlayStatistics>(rom_id)
+            .ok()
+            .flatten()
+    }
+
+    /// ROMs that have been played, most recent first
+    pub fn recently_played(&self) -> Vec<PlayStatistics> {
+        let mut statistics = self.all_play_statistics();
+        statistics.sort_by(|a, b| b.last_played.cmp(&a.last_played));
+        statistics
+    }
+
+    /// ROMs that have been played, longest play time first
+    pub fn most_played(&self) -> Vec<PlayStatistics> {
+        let mut statistics = self.all_play_statistics();
+        statistics.sort_by(|a, b| b.play_time.cmp(&a.play_time));
+        statistics
+    }
+
+    pub fn record_launch(&self, rom_id: RomId) -> Result<(), Box<dyn Error>> {
+        self.update_play_statistics(rom_id, |statistics| {
+            statistics.launches += 1;
+            statistics.last_played = Some(SystemTime::now());
+        })
+    }
+
+    pub fn record_play_time(
+        &self,
+        rom_id: RomId,
+        play_time: Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        self.update_play_statistics(rom_id, |statistics| {
+            statistics.play_time += play_time;
+            statistics.last_played = Some(SystemTime::now());
+        })
+    }
+
+    fn all_play_statistics(&self) -> Vec<PlayStatistics> {
+        let Ok(transaction) = self.rom_information.r_transaction() else {
+            return Vec::new();
+        };
+
+        let Ok(scan) = transaction.scan().primary::<PlayStatistics>() else {
+            return Vec::new();
+        };
+
+        scan.all()
+            .map(|statistics| statistics.flatten().collect())
+            .unwrap_or_default()
+    }
+
+    fn update_play_statistics(
+        &self,
+        rom_id: RomId,
+        update: impl FnOnce(&mut PlayStatistics),
+    ) -> Result<(), Box<dyn Error>> {
+        let transaction = self.rom_information.rw_transaction()?;
+        let mut statistics = transaction
+            .get()
+            .primary::<PlayStatistics>(rom_id)?
+            .unwrap_or_else(|| PlayStatistics::new(rom_id));
+
+        update(&mut statistics);
+
+        transaction.upsert(statistics)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+}
+
+/// Counts a launch when a machine starts and adds its play time to the library when it goes away
+#[derive(Debug)]
+pub struct PlaySession {
+    rom_manager: Arc<RomManager>,
+    rom_id: RomId,
+    clock: Arc<MachineClock>,
+}
+
+impl PlaySession {
+    pub fn start(rom_manager: Arc<RomManager>, rom_id: RomId, clock: Arc<MachineClock>) -> Self {
+        if let Err(error) = rom_manager.record_launch(rom_id) {
+            tracing::warn!("Could not record launch of {}: {}", rom_id, error);
+        }
+
+        Self {
+            rom_manager,
+            rom_id,
+            clock,
+        }
+    }
+}
+
+impl Drop for PlaySession {
+    fn drop(&mut self) {
+        // Leaves out pauses, so leaving the menu open doesn't count as playing
+        let play_time = self.clock.session_time();
+
+        if let Err(error) = self.rom_manager.record_play_time(self.rom_id, play_time) {
+            tracing::warn!("Could not record play time of {}: {}", self.rom_id, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn most_and_recently_played() {
+        let rom_manager = RomManager::new(None).unwrap();
+        let long = RomId::new([1; 20]);
+        let recent = RomId::new([2; 20]);
+
+        rom_manager.record_launch(long).unwrap();
+        rom_manager
+            .record_play_time(long, Duration::from_secs(3600))
+            .unwrap();
+        rom_manager.record_launch(recent).unwrap();
+        rom_manager.record_launch(recent).unwrap();
+        rom_manager
+            .record_play_time(recent, Duration::from_secs(60))
+            .unwrap();
+
+        assert_eq!(rom_manager.play_statistics(recent).unwrap().launches, 2);
+        assert_eq!(rom_manager.most_played()[0].id, long);
+        assert_eq!(rom_manager.recently_played()[0].id, recent);
+    }
+}
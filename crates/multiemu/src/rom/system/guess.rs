@@ -65,6 +65,28 @@ static MAGIC_TABLE: LazyLock<HashMap<GameSystem, Vec<MagicTableEntry>>> = LazyLo
             },
         ]);
 
+    table
+        .entry(GameSystem::Other(OtherSystem::Commodore64))
+        .or_default()
+        .extend([
+            MagicTableEntry {
+                bytes: b"C64 CARTRIDGE   ",
+                offset: 0x00,
+            },
+            MagicTableEntry {
+                bytes: b"C64-TAPE-RAW",
+                offset: 0x00,
+            },
+            MagicTableEntry {
+                bytes: b"C64 tape image file",
+                offset: 0x00,
+            },
+            MagicTableEntry {
+                bytes: b"C64S tape",
+                offset: 0x00,
+            },
+        ]);
+
     table
 });
 
@@ -121,9 +143,13 @@ fn guess_by_extension(rom: &Path) -> Option<GameSystem> {
                 NintendoSystem::SuperNintendoEntertainmentSystem,
             )),
             "n64" | "z64" => Some(GameSystem::Nintendo(NintendoSystem::Nintendo64)),
-            "md" => Some(GameSystem::Sega(SegaSystem::MasterSystem)),
+            "sms" => Some(GameSystem::Sega(SegaSystem::MasterSystem)),
+            "md" => Some(GameSystem::Sega(SegaSystem::Genesis)),
             "gg" => Some(GameSystem::Sega(SegaSystem::GameGear)),
-            "ch8" | "c8" => Some(GameSystem::Other(OtherSystem::Chip8)),
+            "ch8" | "c8" | "8o" | "o8" => Some(GameSystem::Other(OtherSystem::Chip8)),
+            "xo8" => Some(GameSystem::Other(OtherSystem::XoChip)),
+            "mx1" => Some(GameSystem::Other(OtherSystem::Msx)),
+            "crt" | "t64" => Some(GameSystem::Other(OtherSystem::Commodore64)),
             "a26" => Some(GameSystem::Atari(AtariSystem::Atari2600)),
             "a52" => Some(GameSystem::Atari(AtariSystem::Atari5200)),
             "a78" => Some(GameSystem::Atari(AtariSystem::Atari7800)),
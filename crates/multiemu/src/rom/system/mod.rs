@@ -74,6 +74,9 @@ pub enum SonySystem {
 )]
 pub enum OtherSystem {
     Chip8,
+    XoChip,
+    Msx,
+    Commodore64,
 }
 
 #[derive(
@@ -133,6 +136,9 @@ impl Display for GameSystem {
             GameSystem::Sega(SegaSystem::SegaCD) => write!(f, "Sega - Sega CD"),
             GameSystem::Sega(SegaSystem::Sega32X) => write!(f, "Sega - Sega 32X"),
             GameSystem::Other(OtherSystem::Chip8) => write!(f, "Other - Chip8"),
+            GameSystem::Other(OtherSystem::XoChip) => write!(f, "Other - XO-CHIP"),
+            GameSystem::Other(OtherSystem::Msx) => write!(f, "Microsoft - MSX"),
+            GameSystem::Other(OtherSystem::Commodore64) => write!(f, "Commodore - 64"),
             GameSystem::Atari(AtariSystem::Atari2600) => write!(f, "Atari - 2600"),
             GameSystem::Atari(AtariSystem::Atari5200) => write!(f, "Atari - 5200"),
             GameSystem::Atari(AtariSystem::Atari7800) => write!(f, "Atari - 7800"),
@@ -143,7 +149,7 @@ impl Display for GameSystem {
     }
 }
 
-fn strip_brackets_and_parens(input: &str) -> String {
+pub(crate) fn strip_brackets_and_parens(input: &str) -> String {
     let mut result = String::new();
     let mut skip_level = 0;
 
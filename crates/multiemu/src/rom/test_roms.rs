@@ -0,0 +1,232 @@
+use super::{
+    id::RomId,
+    manager::RomManager,
+    system::{GameSystem, OtherSystem},
+};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestRom {
+    pub name: &'static str,
+    pub url: &'static str,
+    /// Hex SHA-1 the download has to match
+    ///
+    /// TODO: Pin the ones still missing, until then what was downloaded is logged so it can be filled in
+    pub sha1: Option<&'static str>,
+}
+
+/// A collection of test ROMs whose license lets us fetch them for the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestRomSuite {
+    pub name: &'static str,
+    pub system: GameSystem,
+    pub license: &'static str,
+    pub roms: &'static [TestRom],
+}
+
+macro_rules! timendus {
+    ($name:literal) => {
+        TestRom {
+            name: $name,
+            url: concat!(
+                "https://raw.githubusercontent.com/Timendus/chip8-test-suite/main/bin/",
+                $name
+            ),
+            sha1: None,
+        }
+    };
+}
+
+pub const TEST_ROM_SUITES: &[TestRomSuite] = &[
+    TestRomSuite {
+        name: "timendus-chip8",
+        system: GameSystem::Other(OtherSystem::Chip8),
+        license: "GPL-3.0",
+        roms: &[
+            timendus!("1-chip8-logo.ch8"),
+            timendus!("2-ibm-logo.ch8"),
+            timendus!("3-corax+.ch8"),
+            timendus!("4-flags.ch8"),
+            timendus!("5-quirks.ch8"),
+            timendus!("6-keypad.ch8"),
+        ],
+    },
+    TestRomSuite {
+        name: "klaus-6502",
+        // A bare 64k memory image, not for any machine in particular
+        system: GameSystem::Unknown,
+        license: "GPL-3.0",
+        roms: &[TestRom {
+            name: "6502_functional_test.bin",
+            url: "https://raw.githubusercontent.com/Klaus2m5/6502_65C02_functional_tests/master/bin_files/6502_functional_test.bin",
+            sha1: None,
+        }],
+    },
+];
+
+#[derive(Error, Debug)]
+pub enum TestRomError {
+    #[error("No test ROM suite is called {0}")]
+    UnknownSuite(String),
+    #[error("Could not download {url}: {message}")]
+    Download { url: &'static str, message: String },
+    #[error("{name} has hash {actual} instead of the expected {expected}")]
+    HashMismatch {
+        name: &'static str,
+        expected: &'static str,
+        actual: RomId,
+    },
+    #[error("Could not store {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Could not record test ROM in the database: {0}")]
+    Database(String),
+}
+
+pub fn find_suite(name: &str) -> Result<&'static TestRomSuite, TestRomError> {
+    TEST_ROM_SUITES
+        .iter()
+        .find(|suite| suite.name == name)
+        .ok_or_else(|| TestRomError::UnknownSuite(name.to_string()))
+}
+
+/// Checks a downloaded ROM against its pinned hash
+pub fn verify(rom: &TestRom, contents: &[u8]) -> Result<RomId, TestRomError> {
+    let actual = RomId::from_read(&mut &contents[..]);
+
+    match rom.sha1 {
+        Some(expected) if !actual.to_string().eq_ignore_ascii_case(expected) => {
+            Err(TestRomError::HashMismatch {
+                name: rom.name,
+                expected,
+                actual,
+            })
+        }
+        Some(_) => Ok(actual),
+        None => {
+            tracing::warn!("{} has no pinned hash yet, downloaded {}", rom.name, actual);
+            Ok(actual)
+        }
+    }
+}
+
+impl RomManager {
+    /// Downloads every ROM in a suite into the ROM store and adds it to the library
+    ///
+    /// ROMs already in the store are not downloaded again
+    #[cfg(feature = "test-rom-download")]
+    pub fn download_test_roms(
+        &self,
+        suite: &TestRomSuite,
+        store: &Path,
+    ) -> Result<Vec<RomId>, TestRomError> {
+        use std::io::Read;
+
+        let mut downloaded = Vec::new();
+
+        for rom in suite.roms {
+            if let Some(rom_id) = self.stored_test_rom(rom, store) {
+                tracing::debug!("{} is already in the ROM store", rom.name);
+                downloaded.push(rom_id);
+                continue;
+            }
+
+            tracing::info!("Downloading {} from {}", rom.name, rom.url);
+
+            let mut contents = Vec::new();
+            ureq::get(rom.url)
+                .call()
+                .map_err(|error| TestRomError::Download {
+                    url: rom.url,
+                    message: error.to_string(),
+                })?
+                .into_reader()
+                .read_to_end(&mut contents)
+                .map_err(|error| TestRomError::Download {
+                    url: rom.url,
+                    message: error.to_string(),
+                })?;
+
+            let rom_id = verify(rom, &contents)?;
+            self.store_test_rom(suite, rom, rom_id, &contents, store)?;
+            downloaded.push(rom_id);
+        }
+
+        Ok(downloaded)
+    }
+
+    /// Where a test ROM already is, if a verified copy has been stored before
+    pub fn stored_test_rom(&self, rom: &TestRom, store: &Path) -> Option<RomId> {
+        let rom_id: RomId = rom.sha1?.parse().ok()?;
+        let path = store.join(rom_id.to_string());
+
+        if !path.is_file() {
+            return None;
+        }
+
+        self.rom_paths.insert(rom_id, path);
+        Some(rom_id)
+    }
+
+    #[cfg(feature = "test-rom-download")]
+    fn store_test_rom(
+        &self,
+        suite: &TestRomSuite,
+        rom: &TestRom,
+        rom_id: RomId,
+        contents: &[u8],
+        store: &Path,
+    ) -> Result<(), TestRomError> {
+        let path = store.join(rom_id.to_string());
+
+        std::fs::create_dir_all(store)
+            .and_then(|_| std::fs::write(&path, contents))
+            .map_err(|error| TestRomError::Io {
+                path: path.clone(),
+                error,
+            })?;
+
+        let database_error =
+            |error: native_db::db_type::Error| TestRomError::Database(error.to_string());
+        let transaction = self
+            .rom_information
+            .rw_transaction()
+            .map_err(database_error)?;
+        transaction
+            .upsert(super::info::RomInfo {
+                id: rom_id,
+                name: Some(format!("{} ({})", rom.name, suite.name)),
+                system: suite.system,
+                regions: Vec::new(),
+                parent: None,
+            })
+            .map_err(database_error)?;
+        transaction.commit().map_err(database_error)?;
+
+        self.rom_paths.insert(rom_id, path);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rejects_wrong_hash() {
+        let rom = TestRom {
+            name: "test.ch8",
+            url: "https://example.com/test.ch8",
+            sha1: Some("0000000000000000000000000000000000000000"),
+        };
+
+        assert!(matches!(
+            verify(&rom, &[0x12, 0x00]),
+            Err(TestRomError::HashMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+use super::{id::RomId, info::RomInfo, manager::RomManager};
+use std::{error::Error, fmt::Display, fs::read, path::Path};
+
+/// Headers dumping tools commonly glue onto the front of ROMs
+///
+/// iNES for the NES and the copier header for the SNES
+const KNOWN_HEADER_SIZES: &[usize] = &[16, 512];
+
+/// What we think of a ROM compared to what the database says it should be
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomVerification {
+    /// Matches a database entry exactly
+    Verified,
+    /// Matches a database entry once a header is removed
+    Headered { id: RomId, header_size: usize },
+    /// Matches a database entry once trailing padding or repeated data is removed
+    Overdump { id: RomId },
+    /// The file name matches a known ROM but the contents do not, likely trained or hacked
+    Modified { expected: RomId },
+    /// Nothing in the database resembles this
+    Unknown,
+}
+
+impl Display for RomVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RomVerification::Verified => write!(f, "Verified good dump"),
+            RomVerification::Headered { id, header_size } => write!(
+                f,
+                "Has a {} byte header, otherwise matches known ROM {}",
+                header_size, id
+            ),
+            RomVerification::Overdump { id } => {
+                write!(f, "Overdump of known ROM {}, it has extra data", id)
+            }
+            RomVerification::Modified { expected } => write!(
+                f,
+                "Named like known ROM {} but the contents differ, it may be trained or hacked",
+                expected
+            ),
+            RomVerification::Unknown => write!(f, "Not in the database, it may be a bad dump"),
+        }
+    }
+}
+
+/// A ROM that booted but the user should know something is off with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomWarning {
+    pub id: RomId,
+    pub verification: RomVerification,
+}
+
+impl RomManager {
+    fn is_known(&self, id: RomId) -> Result<bool, Box<dyn Error>> {
+        Ok(self
+            .rom_information
+            .r_transaction()?
+            .get()
+            .primary::<RomInfo>(id)?
+            .is_some())
+    }
+
+    /// Compares a ROM against the database and tries to figure out what is wrong with it, if anything
+    pub fn verify(&self, id: RomId) -> Result<RomVerification, Box<dyn Error>> {
+        // ROMs are registered under their hash so if the database knows the id we are done
+        if self.is_known(id)? {
+            return Ok(RomVerification::Verified);
+        }
+
+        let Some(path) = self.rom_paths.get(&id).map(|path| path.value().clone()) else {
+            return Ok(RomVerification::Unknown);
+        };
+        let contents = read(&path)?;
+
+        for header_size in KNOWN_HEADER_SIZES {
+            if contents.len() > *header_size && contents.len() % 1024 == *header_size {
+                let id = RomId::from_read(&mut &contents[*header_size..]);
+
+                if self.is_known(id)? {
+                    return Ok(RomVerification::Headered {
+                        id,
+                        header_size: *header_size,
+                    });
+                }
+            }
+        }
+
+        for candidate in overdump_candidates(&contents) {
+            let id = RomId::from_read(&mut &contents[..candidate]);
+
+            if self.is_known(id)? {
+                return Ok(RomVerification::Overdump { id });
+            }
+        }
+
+        if let Some(expected) = self.find_by_file_name(&path)? {
+            return Ok(RomVerification::Modified { expected });
+        }
+
+        Ok(RomVerification::Unknown)
+    }
+
+    fn find_by_file_name(&self, path: &Path) -> Result<Option<RomId>, Box<dyn Error>> {
+        let Some(file_stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            return Ok(None);
+        };
+
+        let transaction = self.rom_information.r_transaction()?;
+
+        for rom_info in transaction.scan().primary::<RomInfo>()?.all()?.flatten() {
+            if rom_info.name.as_deref() == Some(file_stem) {
+                return Ok(Some(rom_info.id));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Lengths the real ROM could be, if this is an overdump
+fn overdump_candidates(contents: &[u8]) -> Vec<usize> {
+    let mut candidates = Vec::new();
+
+    if contents.is_empty() {
+        return candidates;
+    }
+
+    // Dumpers that read past the end of the chip mirror the data
+    let mut length = contents.len();
+    while length % 2 == 0 && length > 1 && contents[..length / 2] == contents[length / 2..length] {
+        length /= 2;
+        candidates.push(length);
+    }
+
+    // Or pad out the file to a nice size
+    let padding = contents[contents.len() - 1];
+    let trimmed_length = contents
+        .iter()
+        .rposition(|byte| *byte != padding)
+        .map(|position| position + 1)
+        .unwrap_or_default();
+
+    if [0x00, 0xff].contains(&padding) && trimmed_length != 0 && trimmed_length != contents.len() {
+        candidates.push(trimmed_length.next_power_of_two().min(contents.len()));
+        candidates.push(trimmed_length);
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mirrored_overdump() {
+        let rom = [1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4];
+
+        assert_eq!(overdump_candidates(&rom), vec![8, 4]);
+    }
+
+    #[test]
+    fn padded_overdump() {
+        let mut rom = vec![7; 12];
+        rom.extend([0xff; 20]);
+
+        assert_eq!(overdump_candidates(&rom), vec![16, 12]);
+    }
+}
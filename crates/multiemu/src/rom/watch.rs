@@ -0,0 +1,201 @@
+use super::{id::RomId, info::RomInfo, io::IoJob, manager::RomManager, system::GameSystem};
+use crate::{config::GLOBAL_CONFIG, runtime::progress::PROGRESS};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+use walkdir::WalkDir;
+
+/// How often watch folders are looked through, polling keeps this working on network shares that never send change
+/// events
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A ROM that showed up in a watch folder and was added to the library
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IngestedRom {
+    pub id: RomId,
+    pub name: Option<String>,
+    pub system: GameSystem,
+    pub path: PathBuf,
+}
+
+/// Remembers what was in the folders last time, so only new or changed files get hashed
+#[derive(Debug, Default)]
+struct FolderScanner {
+    seen: HashMap<PathBuf, SystemTime>,
+}
+
+impl FolderScanner {
+    /// Files that appeared or were modified since the last scan
+    fn scan(&mut self, folders: &[PathBuf]) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+
+        for entry in folders
+            .iter()
+            .flat_map(|folder| WalkDir::new(folder).follow_links(true))
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+        {
+            let Some(modified) = entry
+                .metadata()
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+            else {
+                continue;
+            };
+
+            if self.seen.get(entry.path()) != Some(&modified) {
+                self.seen.insert(entry.path().to_path_buf(), modified);
+                changed.push(entry.into_path());
+            }
+        }
+
+        changed
+    }
+}
+
+impl RomManager {
+    /// Hashes a file and adds it to the library if the database knows what it is
+    pub fn ingest(&self, path: &Path) -> Result<Option<IngestedRom>, Box<dyn Error>> {
+        let id = RomId::from_read(&mut File::open(path)?);
+
+        let Some(rom_info) = self
+            .rom_information
+            .r_transaction()?
+            .get()
+            .primary::<RomInfo>(id)?
+        else {
+            return Ok(None);
+        };
+
+        self.rom_paths.insert(id, path.to_path_buf());
+
+        Ok(Some(IngestedRom {
+            id,
+            name: rom_info.name,
+            system: rom_info.system,
+            path: path.to_path_buf(),
+        }))
+    }
+
+    /// Keeps an eye on the watch folders from the config in the background, sending every ROM it adds
+    ///
+    /// Whatever is already there when this starts is added without being sent, so startup isn't a flood of
+    /// notifications. The watcher stops once the receiver or the manager goes away
+    pub fn watch_folders(self: &Arc<Self>) -> Receiver<IngestedRom> {
+        let (sender, receiver) = channel();
+        let rom_manager = Arc::downgrade(self);
+
+        thread::Builder::new()
+            .name("rom-watcher".to_string())
+            .spawn(move || {
+                let mut scanner = FolderScanner::default();
+                let mut first_scan = true;
+
+                loop {
+                    let folders = GLOBAL_CONFIG.read().unwrap().watch_folders.clone();
+                    let Some(rom_manager) = rom_manager.upgrade() else {
+                        return;
+                    };
+
+                    let changed = scanner.scan(&folders);
+                    // Quiet polls aren't worth a progress bar flickering up every few seconds
+                    let progress = (!changed.is_empty())
+                        .then(|| PROGRESS.begin("Scanning library", Some(changed.len() as u64)));
+
+                    // Hashed across the IO workers, then reported here in order
+                    let jobs: Vec<_> = changed
+                        .into_iter()
+                        .map(|path| {
+                            let worker_rom_manager = rom_manager.clone();
+                            let progress = progress.clone();
+
+                            rom_manager.io.submit(move || {
+                                // Skipped files stay marked as seen, so they're only looked at again once they change
+                                if progress
+                                    .as_ref()
+                                    .is_some_and(|progress| progress.is_cancelled())
+                                {
+                                    return (path, Ok(None));
+                                }
+
+                                // Boxed errors can't cross threads
+                                let result = worker_rom_manager
+                                    .ingest(&path)
+                                    .map_err(|error| error.to_string());
+                                if let Some(progress) = &progress {
+                                    progress.advance(1);
+                                }
+
+                                (path, result)
+                            })
+                        })
+                        .collect();
+
+                    for (path, result) in jobs.into_iter().map(IoJob::wait) {
+                        match result {
+                            Ok(Some(rom)) => {
+                                tracing::info!(
+                                    "Added {} from watch folder as {}",
+                                    path.display(),
+                                    rom.id
+                                );
+
+                                if !first_scan && sender.send(rom).is_err() {
+                                    return;
+                                }
+                            }
+                            Ok(None) => {
+                                tracing::debug!(
+                                    "{} in watch folder is not a known ROM",
+                                    path.display()
+                                )
+                            }
+                            Err(error) => {
+                                tracing::warn!("Could not inspect {}: {}", path.display(), error)
+                            }
+                        }
+                    }
+
+                    drop(progress);
+                    drop(rom_manager);
+                    first_scan = false;
+                    thread::sleep(POLL_INTERVAL);
+                }
+            })
+            .expect("Could not start the ROM watcher");
+
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn scanner_reports_new_files_once() {
+        let directory =
+            std::env::temp_dir().join(format!("multiemu-watch-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let folders = [directory.clone()];
+        let mut scanner = FolderScanner::default();
+
+        assert!(scanner.scan(&folders).is_empty());
+
+        fs::write(directory.join("game.ch8"), [0x00, 0xe0]).unwrap();
+        assert_eq!(scanner.scan(&folders), vec![directory.join("game.ch8")]);
+        assert!(scanner.scan(&folders).is_empty());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{config::GLOBAL_CONFIG, runtime::av_sync::AV_SYNC};
+use num::rational::Ratio;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use strum::{Display, EnumIter};
+
+/// Which platform audio API the output goes through
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, EnumIter, Display, PartialEq, Eq)]
+pub enum AudioHost {
+    /// Whatever the platform normally mixes everything through
+    #[default]
+    Shared,
+    /// Windows only, takes the device for ourselves so nothing gets mixed in on the way
+    ///
+    /// cpal can only open WASAPI in shared mode, so this is never offered. It stays so configs that picked it load
+    #[strum(to_string = "WASAPI Exclusive")]
+    WasapiExclusive,
+    /// Linux only, goes through a running JACK server
+    #[strum(to_string = "JACK")]
+    Jack,
+}
+
+impl AudioHost {
+    /// If this host exists at all on the platform we were built for
+    pub fn supported(&self) -> bool {
+        match self {
+            AudioHost::Shared => true,
+            AudioHost::WasapiExclusive => false,
+            AudioHost::Jack => cfg!(target_os = "linux"),
+        }
+    }
+
+    /// Falls back to the shared path if the preferred host isn't supported or can't be opened right now
+    ///
+    /// `available` is asked by the output backend, since things like the JACK server not running only show up
+    /// when trying
+    pub fn resolve(self, available: impl FnOnce(AudioHost) -> bool) -> AudioHost {
+        if self == AudioHost::Shared {
+            return self;
+        }
+
+        if self.supported() && available(self) {
+            return self;
+        }
+
+        tracing::warn!("{} audio is not available, using the shared path", self);
+        AudioHost::Shared
+    }
+}
+
+/// How often the audio output ran dry or had to throw samples away, for tuning the buffer settings
+#[derive(Debug, Default)]
+pub struct AudioStats {
+    underruns: AtomicU64,
+    overruns: AtomicU64,
+    /// Bits of an f32, atomics don't do floats
+    fill_level: AtomicU32,
+    /// Nanoseconds of audio queued in whichever buffer last changed
+    latency: AtomicU64,
+}
+
+impl AudioStats {
+    pub const fn new() -> Self {
+        Self {
+            underruns: AtomicU64::new(0),
+            overruns: AtomicU64::new(0),
+            fill_level: AtomicU32::new(0),
+            latency: AtomicU64::new(0),
+        }
+    }
+
+    /// Times the device asked for samples the emulator hadn't made yet, heard as crackling
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Times the emulator got so far ahead samples had to be dropped
+    pub fn overruns(&self) -> u64 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    /// Fill level of whichever buffer last changed, see [AudioBuffer::fill_level]
+    pub fn fill_level(&self) -> f32 {
+        f32::from_bits(self.fill_level.load(Ordering::Relaxed))
+    }
+
+    /// How long a sample pushed now waits before reaching the device
+    pub fn latency(&self) -> Duration {
+        Duration::from_nanos(self.latency.load(Ordering::Relaxed))
+    }
+
+    pub fn reset(&self) {
+        self.underruns.store(0, Ordering::Relaxed);
+        self.overruns.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Shared by every audio output so the menu can show them without knowing which is running
+pub static AUDIO_STATS: AudioStats = AudioStats::new();
+
+/// Interleaved samples waiting between the machine and the output device
+#[derive(Debug)]
+pub struct AudioBuffer {
+    samples: Mutex<VecDeque<f32>>,
+    sample_rate: u32,
+    channels: u16,
+    /// Latency the buffer was made with, used until A/V sync has measured something
+    latency: Duration,
+    /// Samples the buffer tries to stay around
+    target: AtomicUsize,
+    /// Samples one emulated frame makes, the target never goes below this
+    frame_samples: AtomicUsize,
+}
+
+impl AudioBuffer {
+    pub fn new(sample_rate: u32, channels: u16, latency: Duration) -> Self {
+        let target = samples_for(sample_rate, channels, latency);
+
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(target * 2)),
+            sample_rate,
+            channels,
+            latency,
+            target: AtomicUsize::new(target),
+            frame_samples: AtomicUsize::new(0),
+        }
+    }
+
+    /// Sized from the users latency setting
+    pub fn from_config(sample_rate: u32, channels: u16) -> Self {
+        let latency = GLOBAL_CONFIG.read().unwrap().audio_latency;
+
+        Self::new(sample_rate, channels, Duration::from_millis(latency as u64))
+    }
+
+    /// Samples only arrive once a frame, so aiming for less than a frame's worth runs dry between every one
+    ///
+    /// See [crate::machine::Machine::samples_per_frame]
+    pub fn set_samples_per_frame(&self, samples_per_frame: Ratio<u64>) {
+        let frame_samples = samples_per_frame.ceil().to_integer() as usize * self.channels as usize;
+
+        self.frame_samples.store(frame_samples, Ordering::Relaxed);
+        self.target.fetch_max(frame_samples, Ordering::Relaxed);
+    }
+
+    /// Called from the emulation side with freshly made samples
+    pub fn push(&self, samples: &[f32]) {
+        let target = samples_for(
+            self.sample_rate,
+            self.channels,
+            AV_SYNC.audio_target(self.latency),
+        )
+        .max(self.frame_samples.load(Ordering::Relaxed));
+        self.target.store(target, Ordering::Relaxed);
+        // Twice the target so normal jitter doesn't count as an overrun
+        let capacity = target * 2;
+
+        let mut buffer = self.samples.lock().unwrap();
+        buffer.extend(samples);
+
+        if buffer.len() > capacity {
+            let excess = buffer.len() - capacity;
+            buffer.drain(..excess);
+            AUDIO_STATS.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.report_fill_level(buffer.len());
+    }
+
+    /// Called from the device side, anything the buffer can't provide is filled with silence
+    pub fn fill(&self, output: &mut [f32]) {
+        let mut buffer = self.samples.lock().unwrap();
+        let available = buffer.len().min(output.len());
+
+        for (destination, sample) in output.iter_mut().zip(buffer.drain(..available)) {
+            *destination = sample;
+        }
+
+        if available < output.len() {
+            output[available..].fill(0.0);
+            AUDIO_STATS.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.report_fill_level(buffer.len());
+    }
+
+    /// How full the buffer is compared to the latency target, 1.0 is right on it
+    pub fn fill_level(&self) -> f32 {
+        self.samples.lock().unwrap().len() as f32 / self.target.load(Ordering::Relaxed) as f32
+    }
+
+    fn report_fill_level(&self, length: usize) {
+        AUDIO_STATS.fill_level.store(
+            (length as f32 / self.target.load(Ordering::Relaxed) as f32).to_bits(),
+            Ordering::Relaxed,
+        );
+        AUDIO_STATS.latency.store(
+            (length as u64 * 1_000_000_000) / (self.sample_rate as u64 * self.channels as u64),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/// Interleaved samples making up this much audio
+fn samples_for(sample_rate: u32, channels: u16, latency: Duration) -> usize {
+    ((sample_rate as f64 * latency.as_secs_f64()) as usize).max(1) * channels as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unsupported_host_falls_back() {
+        assert_eq!(AudioHost::Jack.resolve(|_| false), AudioHost::Shared);
+        // Nothing can open it, even where it would exist
+        assert_eq!(
+            AudioHost::WasapiExclusive.resolve(|_| true),
+            AudioHost::Shared
+        );
+    }
+
+    #[test]
+    fn counts_underruns_and_overruns() {
+        // 10 samples target, 20 capacity
+        let buffer = AudioBuffer::new(1000, 1, Duration::from_millis(10));
+        let underruns = AUDIO_STATS.underruns();
+        let overruns = AUDIO_STATS.overruns();
+
+        buffer.push(&[0.5; 25]);
+        assert_eq!(AUDIO_STATS.overruns(), overruns + 1);
+        assert_eq!(buffer.fill_level(), 2.0);
+
+        let mut output = [1.0; 30];
+        buffer.fill(&mut output);
+        assert_eq!(AUDIO_STATS.underruns(), underruns + 1);
+        assert_eq!(output[19], 0.5);
+        assert_eq!(output[20], 0.0);
+    }
+
+    #[test]
+    fn target_covers_a_frame() {
+        // 5 samples of latency, but a 50Hz machine makes 20 a frame
+        let buffer = AudioBuffer::new(1000, 1, Duration::from_millis(5));
+        buffer.set_samples_per_frame(Ratio::new(1000, 50));
+
+        buffer.push(&[0.5; 20]);
+        assert_eq!(buffer.fill_level(), 1.0);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{component::audio::AudioComponent, machine::Machine, runtime::audio::AudioBuffer};
+use num::ToPrimitive;
+use std::{collections::VecDeque, sync::Arc};
+
+/// Furthest the output rate is bent to steer the buffer back to its target, half a percent is too little to hear
+const MAXIMUM_RATE_DEVIATION: f64 = 0.005;
+
+/// How slowly the DC blocker lets through low frequencies, closer to 1 cuts off lower
+const DC_BLOCKER_POLE: f32 = 0.995;
+
+/// How much faster than nominal to produce samples, given how full the output buffer is
+///
+/// Neither the scheduler nor the device clock is exact, so a buffer fed at exactly the device rate slowly drifts into
+/// underruns or overruns. Running a touch fast when it is low and slow when it is high keeps it near the target
+/// without dropping or repeating anything
+fn rate_adjustment(fill_level: f32) -> f64 {
+    1.0 + (1.0 - fill_level.clamp(0.0, 2.0) as f64) * MAXIMUM_RATE_DEVIATION
+}
+
+/// Linear interpolation from one sample rate to another
+#[derive(Debug, Default)]
+struct Resampler {
+    /// Where the next output sample sits between the previous input sample and the next one
+    position: f64,
+    previous: f32,
+}
+
+impl Resampler {
+    /// `step` is input samples per output sample
+    fn process(
+        &mut self,
+        input: impl IntoIterator<Item = f32>,
+        step: f64,
+        output: &mut VecDeque<f32>,
+    ) {
+        for sample in input {
+            while self.position < 1.0 {
+                output.push_back(self.previous + (sample - self.previous) * self.position as f32);
+                self.position += step;
+            }
+
+            self.position -= 1.0;
+            self.previous = sample;
+        }
+    }
+}
+
+/// Components output between 0 and 1, this takes out the resting level so silence sits at 0
+#[derive(Debug, Default)]
+struct DcBlocker {
+    previous_input: f32,
+    previous_output: f32,
+}
+
+impl DcBlocker {
+    fn process(&mut self, sample: f32) -> f32 {
+        let output = sample - self.previous_input + DC_BLOCKER_POLE * self.previous_output;
+        self.previous_input = sample;
+        self.previous_output = output;
+
+        output
+    }
+}
+
+/// One sound making component, and its samples on their way to the device rate
+#[derive(Debug)]
+struct AudioProducer {
+    component: Arc<dyn AudioComponent>,
+    channels: usize,
+    sample_rate: f64,
+    resampler: Resampler,
+    dc_blocker: DcBlocker,
+    /// Mono samples at the device rate not mixed yet
+    pending: VecDeque<f32>,
+    /// Reused between frames so draining doesn't allocate
+    scratch: Vec<f32>,
+}
+
+impl AudioProducer {
+    fn produce(&mut self, device_rate: f64) {
+        self.scratch.clear();
+        self.component.drain_samples(&mut self.scratch);
+
+        // None of the machines here have stereo sound, so every channel is mixed down to one
+        let channels = self.channels.max(1);
+        let dc_blocker = &mut self.dc_blocker;
+        let input = self
+            .scratch
+            .chunks_exact(channels)
+            .map(|frame| dc_blocker.process(frame.iter().sum::<f32>() / channels as f32));
+
+        self.resampler
+            .process(input, self.sample_rate / device_rate, &mut self.pending);
+    }
+}
+
+/// Collects what every audio component of a machine made over a frame and mixes it into the output buffer
+///
+/// Each component runs at whatever rate its hardware did, so each is resampled to the device rate on its own before
+/// being summed
+#[derive(Debug)]
+pub struct AudioMixer {
+    producers: Vec<AudioProducer>,
+    sample_rate: u32,
+    channels: u16,
+    mixed: Vec<f32>,
+}
+
+impl AudioMixer {
+    /// None if the machine makes no sound
+    pub fn new(machine: &Machine, sample_rate: u32, channels: u16) -> Option<Self> {
+        let producers: Vec<_> = machine
+            .audio_components()
+            .map(|audio| AudioProducer {
+                component: audio.component.clone(),
+                channels: audio.component.audio_channels(),
+                sample_rate: audio.component.sample_rate().to_f64().unwrap(),
+                resampler: Resampler::default(),
+                dc_blocker: DcBlocker::default(),
+                pending: VecDeque::default(),
+                scratch: Vec::default(),
+            })
+            .collect();
+
+        (!producers.is_empty()).then_some(Self {
+            producers,
+            sample_rate,
+            channels,
+            mixed: Vec::default(),
+        })
+    }
+
+    /// Called after the machine ran, moving everything its components made into the buffer
+    pub fn mix(&mut self, buffer: &AudioBuffer) {
+        let device_rate = self.sample_rate as f64 * rate_adjustment(buffer.fill_level());
+
+        for producer in &mut self.producers {
+            producer.produce(device_rate);
+        }
+
+        // Rounding leaves the producers a sample or so apart, the extra waits for the next frame
+        let length = self
+            .producers
+            .iter()
+            .map(|producer| producer.pending.len())
+            .min()
+            .unwrap_or_default();
+
+        self.mixed.clear();
+        for _ in 0..length {
+            let sample = self
+                .producers
+                .iter_mut()
+                .filter_map(|producer| producer.pending.pop_front())
+                .sum::<f32>()
+                .clamp(-1.0, 1.0);
+
+            self.mixed
+                .extend(std::iter::repeat_n(sample, self.channels as usize));
+        }
+
+        buffer.push(&self.mixed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resampling_and_rate_control() {
+        // Doubling the rate puts a sample halfway between every pair
+        let mut resampler = Resampler::default();
+        let mut output = VecDeque::new();
+        resampler.process([1.0, 0.0, 1.0], 0.5, &mut output);
+        assert_eq!(output, [0.0, 0.5, 1.0, 0.5, 0.0, 0.5]);
+
+        // Halving it keeps every other one
+        let mut resampler = Resampler::default();
+        output.clear();
+        resampler.process([1.0, 2.0, 3.0, 4.0], 2.0, &mut output);
+        assert_eq!(output, [0.0, 2.0]);
+
+        // Low buffers get samples faster, full ones slower
+        assert_eq!(rate_adjustment(1.0), 1.0);
+        assert_eq!(rate_adjustment(0.0), 1.0 + MAXIMUM_RATE_DEVIATION);
+        assert_eq!(rate_adjustment(5.0), 1.0 - MAXIMUM_RATE_DEVIATION);
+
+        // A steady level fades out
+        let mut dc_blocker = DcBlocker::default();
+        let mut settled = 0.0;
+        for _ in 0..4000 {
+            settled = dc_blocker.process(0.5);
+        }
+        assert!(settled.abs() < 0.001);
+    }
+}
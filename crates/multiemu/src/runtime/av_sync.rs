@@ -0,0 +1,116 @@
+use crate::{config::GLOBAL_CONFIG, runtime::audio::AUDIO_STATS};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Shared between the presenter measuring video and the audio buffer being retargeted
+pub static AV_SYNC: AvSync = AvSync::new();
+
+/// Weight each new measurement gets, low so a single slow present doesn't make the audio jump around
+const SMOOTHING: f64 = 0.05;
+
+/// Audio is never buffered less than this, whatever the video is doing, or it starts crackling
+const MINIMUM_AUDIO_LATENCY: Duration = Duration::from_millis(10);
+
+/// Most the audio buffer is stretched to wait for slow video, past this being out of sync is the lesser evil
+const MAXIMUM_AUDIO_LATENCY: Duration = Duration::from_millis(250);
+
+/// Lines up when a frame is seen with when its sound is heard
+///
+/// A frame and its samples are made at the same moment, but the frame shows up after the presenter's latency and the
+/// samples after sitting in the audio buffer. Instead of holding frames back this retargets the audio buffer to take
+/// as long as presenting does, plus whatever offset the user set for their speakers or TV
+#[derive(Debug)]
+pub struct AvSync {
+    /// Bits of an f64 of seconds, smoothed, 0 until measured
+    video_latency: AtomicU64,
+}
+
+impl AvSync {
+    pub const fn new() -> Self {
+        Self {
+            video_latency: AtomicU64::new(0),
+        }
+    }
+
+    /// Called by the presenter with how long it took from a frame being finished to it being on screen
+    pub fn record_video_latency(&self, latency: Duration) {
+        let previous = f64::from_bits(self.video_latency.load(Ordering::Relaxed));
+        let latency = latency.as_secs_f64();
+
+        let smoothed = if previous == 0.0 {
+            latency
+        } else {
+            previous + (latency - previous) * SMOOTHING
+        };
+
+        self.video_latency
+            .store(smoothed.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn video_latency(&self) -> Duration {
+        Duration::from_secs_f64(f64::from_bits(self.video_latency.load(Ordering::Relaxed)))
+    }
+
+    /// How far audio trails video right now, negative when the sound comes first
+    pub fn skew(&self) -> f64 {
+        AUDIO_STATS.latency().as_secs_f64() - self.video_latency().as_secs_f64()
+    }
+
+    /// How much audio a buffer should keep queued, starting from the latency it would have picked by itself
+    pub fn audio_target(&self, fallback: Duration) -> Duration {
+        let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+        if !global_config_guard.av_sync {
+            return fallback;
+        }
+        let offset = global_config_guard.av_offset;
+        drop(global_config_guard);
+
+        // Nothing presented yet, so nothing to line up with
+        if self.video_latency() == Duration::ZERO {
+            return fallback;
+        }
+
+        let target = self.video_latency().as_secs_f64() + offset as f64 / 1000.0;
+
+        Duration::from_secs_f64(target.max(0.0)).clamp(MINIMUM_AUDIO_LATENCY, MAXIMUM_AUDIO_LATENCY)
+    }
+
+    /// Forget the measurements, for when the presenter changes
+    pub fn reset(&self) {
+        self.video_latency.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Default for AvSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn audio_follows_video() {
+        let av_sync = AvSync::new();
+        let fallback = Duration::from_millis(64);
+        assert_eq!(av_sync.audio_target(fallback), fallback);
+
+        av_sync.record_video_latency(Duration::from_millis(40));
+        // One slow present barely moves it
+        av_sync.record_video_latency(Duration::from_millis(140));
+        let latency = av_sync.video_latency().as_secs_f64();
+        assert!((0.044..0.046).contains(&latency));
+
+        let offset = GLOBAL_CONFIG.read().unwrap().av_offset as f64 / 1000.0;
+        let target = av_sync.audio_target(fallback).as_secs_f64();
+        assert!((target - (latency + offset).clamp(0.01, 0.25)).abs() < 0.001);
+
+        av_sync.record_video_latency(Duration::from_secs(10));
+        av_sync.reset();
+        assert_eq!(av_sync.audio_target(fallback), fallback);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    config::{ColorBlindFilter, ColorCorrection, GLOBAL_CONFIG},
+    rom::system::GameSystem,
+};
+use nalgebra::{DMatrixViewMut, Matrix3, Vector3};
+use palette::{LinSrgb, LinSrgba, Srgba};
+use std::{fs::read, path::Path};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PaletteLoadingError {
+    #[error("Could not read palette file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Palette file size {0} is not a multiple of 3")]
+    InvalidSize(usize),
+    #[error("Palette has {found} colors but at least {required} are needed")]
+    TooSmall { found: usize, required: usize },
+}
+
+/// A list of colors indexed by whatever the emulated hardware outputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette(Vec<Srgba<u8>>);
+
+impl Palette {
+    /// Loads a headerless RGB palette, such as the .pal files used by NES emulators
+    pub fn load(path: impl AsRef<Path>, required: usize) -> Result<Self, PaletteLoadingError> {
+        Self::from_bytes(&read(path)?, required)
+    }
+
+    pub fn from_bytes(bytes: &[u8], required: usize) -> Result<Self, PaletteLoadingError> {
+        if bytes.len() % 3 != 0 {
+            return Err(PaletteLoadingError::InvalidSize(bytes.len()));
+        }
+
+        let colors: Vec<_> = bytes
+            .chunks_exact(3)
+            .map(|color| Srgba::new(color[0], color[1], color[2], 0xff))
+            .collect();
+
+        if colors.len() < required {
+            return Err(PaletteLoadingError::TooSmall {
+                found: colors.len(),
+                required,
+            });
+        }
+
+        Ok(Self(colors))
+    }
+
+    /// Loads the users palette for this system, or the given default if they have none or it is broken
+    pub fn load_for_system(system: GameSystem, default: &[u8]) -> Self {
+        let required = default.len() / 3;
+        let palette_file = GLOBAL_CONFIG.read().unwrap().palettes.get(&system).cloned();
+
+        palette_file
+            .and_then(|path| match Self::load(&path, required) {
+                Ok(palette) => {
+                    tracing::info!("Using palette {} for {}", path.display(), system);
+
+                    Some(palette)
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        "Failed to load palette {} for {}, using the default: {}",
+                        path.display(),
+                        system,
+                        error
+                    );
+
+                    None
+                }
+            })
+            .unwrap_or_else(|| Self::from_bytes(default, required).unwrap())
+    }
+
+    pub fn get(&self, index: usize) -> Srgba<u8> {
+        self.0[index % self.0.len()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl ColorCorrection {
+    pub fn is_identity(&self) -> bool {
+        *self == ColorCorrection::None
+    }
+
+    pub fn correct(&self, color: Srgba<u8>) -> Srgba<u8> {
+        // Approximations of how the LCDs of these handhelds blend and darken colors
+        // https://near.sh/articles/video/color-emulation
+        let (matrix, gamma) = match self {
+            ColorCorrection::None => return color,
+            ColorCorrection::GameBoyColor => (
+                Matrix3::new(26.0, 4.0, 2.0, 0.0, 24.0, 8.0, 6.0, 4.0, 22.0) / 32.0,
+                1.0,
+            ),
+            // The GBA screen is notoriously dark, so games brightened their colors to compensate
+            ColorCorrection::GameBoyAdvance => (
+                Matrix3::new(0.82, 0.125, 0.195, 0.24, 0.665, 0.075, -0.06, 0.21, 0.73),
+                1.2,
+            ),
+        };
+
+        let linear: LinSrgb<f32> = color.color.into_format::<f32>().into_linear();
+        let linear = Vector3::new(linear.red, linear.green, linear.blue).map(|c| c.powf(gamma));
+        let corrected = (matrix * linear).map(|c| c.clamp(0.0, 1.0));
+
+        Srgba::from_linear(LinSrgba::new(
+            corrected.x,
+            corrected.y,
+            corrected.z,
+            color.alpha as f32 / 255.0,
+        ))
+    }
+
+    pub fn correct_framebuffer(&self, mut framebuffer: DMatrixViewMut<'_, Srgba<u8>>) {
+        if self.is_identity() {
+            return;
+        }
+
+        for pixel in framebuffer.iter_mut() {
+            *pixel = self.correct(*pixel);
+        }
+    }
+}
+
+impl ColorBlindFilter {
+    pub fn is_identity(&self) -> bool {
+        *self == ColorBlindFilter::None
+    }
+
+    pub fn apply(&self, color: Srgba<u8>) -> Srgba<u8> {
+        // Simulations from Viénot, Brettel and Mollon 1999, in linear rgb
+        let protanopia = Matrix3::new(
+            0.11238, 0.88762, 0.0, 0.11238, 0.88762, 0.0, 0.00401, -0.00401, 1.0,
+        );
+        let deuteranopia = Matrix3::new(
+            0.29275, 0.70725, 0.0, 0.29275, 0.70725, 0.0, -0.02234, 0.02234, 1.0,
+        );
+        // Daltonization, the information lost is shifted onto the channels that can still see it
+        let error_shift = Matrix3::new(0.0, 0.0, 0.0, 0.7, 1.0, 0.0, 0.7, 0.0, 1.0);
+
+        let linear: LinSrgb<f32> = color.color.into_format::<f32>().into_linear();
+        let linear = Vector3::new(linear.red, linear.green, linear.blue);
+
+        let filtered = match self {
+            ColorBlindFilter::None => return color,
+            ColorBlindFilter::SimulateProtanopia => protanopia * linear,
+            ColorBlindFilter::SimulateDeuteranopia => deuteranopia * linear,
+            ColorBlindFilter::CorrectProtanopia => {
+                linear + error_shift * (linear - protanopia * linear)
+            }
+            ColorBlindFilter::CorrectDeuteranopia => {
+                linear + error_shift * (linear - deuteranopia * linear)
+            }
+        }
+        .map(|c| c.clamp(0.0, 1.0));
+
+        Srgba::from_linear(LinSrgba::new(
+            filtered.x,
+            filtered.y,
+            filtered.z,
+            color.alpha as f32 / 255.0,
+        ))
+    }
+
+    pub fn apply_framebuffer(&self, mut framebuffer: DMatrixViewMut<'_, Srgba<u8>>) {
+        if self.is_identity() {
+            return;
+        }
+
+        for pixel in framebuffer.iter_mut() {
+            *pixel = self.apply(*pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn palette_parsing() {
+        let palette = Palette::from_bytes(&[0, 0, 0, 255, 128, 0], 2).unwrap();
+
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette.get(1), Srgba::new(255, 128, 0, 255));
+        assert!(Palette::from_bytes(&[0, 0, 0, 0], 1).is_err());
+        assert!(Palette::from_bytes(&[0, 0, 0], 2).is_err());
+    }
+
+    #[test]
+    fn lcd_correction_keeps_extremes() {
+        for correction in [
+            ColorCorrection::GameBoyColor,
+            ColorCorrection::GameBoyAdvance,
+        ] {
+            assert_eq!(
+                correction.correct(Srgba::new(0, 0, 0, 255)),
+                Srgba::new(0, 0, 0, 255)
+            );
+        }
+
+        assert_eq!(
+            ColorCorrection::None.correct(Srgba::new(12, 34, 56, 255)),
+            Srgba::new(12, 34, 56, 255)
+        );
+    }
+
+    #[test]
+    fn protanopia_confuses_red_and_green() {
+        let red = ColorBlindFilter::SimulateProtanopia.apply(Srgba::new(255, 0, 0, 255));
+        let green = ColorBlindFilter::SimulateProtanopia.apply(Srgba::new(0, 255, 0, 255));
+
+        // Both end up on the same yellowish axis with red and green matching
+        assert_eq!(red.red, red.green);
+        assert_eq!(green.red, green.green);
+
+        // Grays are left alone
+        let gray = Srgba::new(128, 128, 128, 255);
+        let filtered = ColorBlindFilter::SimulateProtanopia.apply(gray);
+        assert!(filtered.red.abs_diff(128) <= 1 && filtered.blue.abs_diff(128) <= 1);
+    }
+}
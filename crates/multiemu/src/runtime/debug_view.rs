@@ -0,0 +1,151 @@
+use super::rendering_backend::DisplayComponentFramebuffer;
+use crate::{
+    definitions::nes::NES_PPU_ADDRESS_SPACE_ID,
+    machine::Machine,
+    memory::AddressSpaceId,
+    rom::system::{GameSystem, NintendoSystem},
+};
+use nalgebra::DMatrix;
+use palette::Srgba;
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+/// Bytes per row in the memory viewer
+const MEMORY_VIEW_WIDTH: usize = 256;
+/// The memory viewer only shows the start of large address spaces
+const MEMORY_VIEW_MAX_SIZE: usize = 0x10000;
+/// Two NES pattern tables worth of tiles
+const TILE_VIEW_SIZE: usize = 0x2000;
+
+/// Identifies a window opened alongside the main one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ViewId(pub u32);
+
+/// Something about a running machine that can be shown in its own window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    /// Raw contents of an address space, one pixel per byte
+    Memory { address_space: AddressSpaceId },
+    /// Planar 2 bits per pixel tiles, like the NES PPU uses
+    Tiles { address_space: AddressSpaceId },
+    /// A display component besides the first, which is always in the main window
+    Display { index: usize },
+    /// Everything [crate::machine::map_capture::MapCapture] has stitched together so far
+    StitchedMap,
+}
+
+impl Display for DebugView {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DebugView::Memory { address_space } => {
+                write!(f, "Memory Viewer (Address Space {})", address_space)
+            }
+            DebugView::Tiles { address_space } => {
+                write!(f, "Tile Viewer (Address Space {})", address_space)
+            }
+            DebugView::Display { index } => write!(f, "Display {}", index + 1),
+            DebugView::StitchedMap => write!(f, "Stitched Map"),
+        }
+    }
+}
+
+impl DebugView {
+    /// Every view that makes sense for this machine
+    pub fn available(machine: &Machine) -> Vec<DebugView> {
+        let mut views: Vec<_> = machine
+            .memory_translation_table
+            .address_space_ids()
+            .map(|address_space| DebugView::Memory { address_space })
+            .collect();
+
+        if machine.system == GameSystem::Nintendo(NintendoSystem::NintendoEntertainmentSystem) {
+            views.push(DebugView::Tiles {
+                address_space: NES_PPU_ADDRESS_SPACE_ID,
+            });
+        }
+
+        views.extend(Self::secondary_displays(machine));
+
+        if machine.map_capture.is_some() {
+            views.push(DebugView::StitchedMap);
+        }
+
+        views
+    }
+
+    /// Views that should open on their own when a machine starts, so multi screen machines show every screen
+    pub fn secondary_displays(machine: &Machine) -> impl Iterator<Item = DebugView> {
+        (1..machine.display_components().count()).map(|index| DebugView::Display { index })
+    }
+
+    pub fn render(&self, machine: &Machine) -> Option<DisplayComponentFramebuffer> {
+        let contents = match self {
+            DebugView::Memory { address_space } => {
+                let size = machine
+                    .memory_translation_table
+                    .address_space_width(*address_space)
+                    .map(|width| 1usize.checked_shl(width as u32).unwrap_or(usize::MAX))?
+                    .min(MEMORY_VIEW_MAX_SIZE);
+                let memory = preview(machine, *address_space, size);
+
+                DMatrix::from_fn(
+                    MEMORY_VIEW_WIDTH,
+                    size.div_ceil(MEMORY_VIEW_WIDTH),
+                    |x, y| {
+                        let byte = memory
+                            .get(y * MEMORY_VIEW_WIDTH + x)
+                            .copied()
+                            .unwrap_or_default();
+
+                        Srgba::new(byte, byte, byte, 0xff)
+                    },
+                )
+            }
+            DebugView::Tiles { address_space } => {
+                let memory = preview(machine, *address_space, TILE_VIEW_SIZE);
+                let tiles = TILE_VIEW_SIZE / 16;
+
+                // 16 tiles across, like most tile viewers
+                DMatrix::from_fn(16 * 8, (tiles / 16) * 8, |x, y| {
+                    let tile = (y / 8) * 16 + x / 8;
+                    let row = y % 8;
+                    let bit = 7 - (x % 8);
+
+                    let low = (memory[tile * 16 + row] >> bit) & 1;
+                    let high = (memory[tile * 16 + row + 8] >> bit) & 1;
+                    let shade = (low | (high << 1)) * 0x55;
+
+                    Srgba::new(shade, shade, shade, 0xff)
+                })
+            }
+            DebugView::Display { index } => {
+                return machine
+                    .display_components()
+                    .nth(*index)
+                    .map(|component_info| component_info.component.get_framebuffer());
+            }
+            DebugView::StitchedMap => machine.map_capture.as_ref()?.render(),
+        };
+
+        Some(DisplayComponentFramebuffer::Software(Arc::new(Mutex::new(
+            contents,
+        ))))
+    }
+}
+
+/// Reads memory without disturbing the machine, anything that can't be read shows up as zero
+fn preview(machine: &Machine, address_space: AddressSpaceId, size: usize) -> Vec<u8> {
+    let mut memory = vec![0; size];
+
+    for (address, byte) in memory.iter_mut().enumerate() {
+        let _ = machine.memory_translation_table.preview(
+            address,
+            std::slice::from_mut(byte),
+            address_space,
+        );
+    }
+
+    memory
+}
@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+/// Gaps between presentations longer than this are the menu being open or the window being dragged, not lag
+const PAUSE_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// Most machine frames run to catch up in one presentation, past this the machine just slows down
+const MAX_CATCH_UP: u32 = 4;
+
+/// Works out how many machine frames each host presentation should show
+///
+/// The host refresh rate (60hz, 144hz, variable) has nothing to do with the machines, so frames are repeated when the
+/// host is faster and skipped when it is slower, keeping emulated time in step with the wall clock
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    frame_period: Duration,
+    /// Host time that has passed but not yet been emulated
+    accumulated: Duration,
+    last_presentation: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new(frame_period: Duration) -> Self {
+        Self {
+            frame_period,
+            accumulated: Duration::ZERO,
+            last_presentation: None,
+        }
+    }
+
+    /// Called once per presentation, returns how many machine frames to run before drawing
+    ///
+    /// Zero means the last frame should be shown again
+    pub fn frames_due(&mut self, now: Instant) -> u32 {
+        match self.last_presentation {
+            Some(last_presentation) => {
+                let elapsed = now.saturating_duration_since(last_presentation);
+
+                if elapsed < PAUSE_THRESHOLD {
+                    self.accumulated += elapsed;
+                }
+            }
+            // Always have something to show on the first presentation
+            None => self.accumulated += self.frame_period,
+        }
+
+        self.last_presentation = Some(now);
+
+        let frames = (self.accumulated.as_nanos() / self.frame_period.as_nanos().max(1)) as u32;
+
+        if frames > MAX_CATCH_UP {
+            tracing::debug!(
+                "Dropping {} frames the host could not keep up with",
+                frames - MAX_CATCH_UP
+            );
+            self.accumulated = Duration::ZERO;
+
+            return MAX_CATCH_UP;
+        }
+
+        self.accumulated -= self.frame_period * frames;
+
+        frames
+    }
+
+    /// When the next machine frame is due, for presenting at the emulated rate on variable refresh displays
+    pub fn next_deadline(&self) -> Instant {
+        let until_next = self.frame_period.saturating_sub(self.accumulated);
+
+        self.last_presentation
+            .map(|last_presentation| last_presentation + until_next)
+            .unwrap_or_else(Instant::now)
+    }
+
+    /// Start over, for when the machine changes
+    pub fn reset(&mut self, frame_period: Duration) {
+        *self = Self::new(frame_period);
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1) / 60)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn high_refresh_host() {
+        let mut pacer = FramePacer::default();
+        let start = Instant::now();
+
+        // A second of a 144hz display should show a second of a 60hz machine
+        let frames: u32 = (0..144)
+            .map(|presentation| {
+                pacer.frames_due(start + Duration::from_secs(1) * presentation / 144)
+            })
+            .sum();
+
+        assert!((59..=61).contains(&frames));
+    }
+
+    #[test]
+    fn slow_host() {
+        let mut pacer = FramePacer::default();
+        let start = Instant::now();
+        pacer.frames_due(start);
+
+        // A 30hz host runs two machine frames per presentation
+        assert_eq!(pacer.frames_due(start + Duration::from_secs(1) / 30), 2);
+    }
+}
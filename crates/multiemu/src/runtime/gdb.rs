@@ -0,0 +1,469 @@
+use crate::{
+    component::processor::DebuggableProcessor, machine::Machine, memory::MemoryTranslationTable,
+};
+use std::{
+    fmt::Write as _,
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::Arc,
+};
+
+/// Signal reported when a breakpoint or step stops the processor
+const SIGTRAP: u8 = 5;
+/// Signal reported when the debugger interrupted it
+const SIGINT: u8 = 2;
+/// Byte the debugger sends outside of any packet to stop the processor
+const INTERRUPT: u8 = 0x03;
+
+/// An attached debugger
+#[derive(Debug)]
+struct GdbConnection {
+    stream: TcpStream,
+    /// Bytes read that haven't made up a whole packet yet
+    incoming: Vec<u8>,
+    /// Both sides stopped sending + and - after every packet
+    no_ack: bool,
+    /// The debugger is waiting to hear when the processor stops
+    running: bool,
+}
+
+/// Server for the GDB remote serial protocol, so gdb or lldb can attach to a processor in the running machine
+///
+/// Polled by the runtime between frames rather than given a thread, since the machine lives on the runtime's.
+/// Breakpoints are checked by the processor itself before every instruction, and memory is read with
+/// [MemoryTranslationTable::preview] so looking doesn't disturb anything
+#[derive(Debug)]
+pub struct GdbStub {
+    listener: TcpListener,
+    connection: Option<GdbConnection>,
+    processor: Arc<dyn DebuggableProcessor>,
+    memory_translation_table: Arc<MemoryTranslationTable>,
+}
+
+impl GdbStub {
+    /// Listens on `port` for a debugger to attach to the first processor in the machine that supports one
+    pub fn new(port: u16, machine: &Machine) -> std::io::Result<Option<Self>> {
+        let Some(processor) = machine.debuggable_processors().next() else {
+            tracing::warn!(
+                "{} has no processor a debugger can attach to",
+                machine.system
+            );
+            return Ok(None);
+        };
+
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        tracing::info!("Waiting for a debugger on port {}", port);
+
+        Ok(Some(Self {
+            listener,
+            connection: None,
+            processor: processor.component.clone(),
+            memory_translation_table: machine.memory_translation_table.clone(),
+        }))
+    }
+
+    /// The debugger has the processor stopped, so the machine shouldn't run
+    pub fn holding(&self) -> bool {
+        self.connection.is_some() && self.processor.debug_state().halted()
+    }
+
+    /// Picks up a new debugger, answers whatever it sent, and tells it if the processor stopped since last time
+    pub fn poll(&mut self) {
+        if self.connection.is_none() {
+            match self.listener.accept() {
+                Ok((stream, address)) => {
+                    tracing::info!("Debugger attached from {}", address);
+
+                    if let Err(error) = stream.set_nonblocking(true) {
+                        tracing::error!("Could not set up debugger connection: {}", error);
+                        return;
+                    }
+
+                    // Debuggers expect to find the target stopped when they attach
+                    self.processor.debug_state().halt();
+                    self.connection = Some(GdbConnection {
+                        stream,
+                        incoming: Vec::new(),
+                        no_ack: false,
+                        running: false,
+                    });
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => return,
+                Err(error) => {
+                    tracing::error!("Could not accept debugger: {}", error);
+                    return;
+                }
+            }
+        }
+
+        if let Err(error) = self.exchange() {
+            tracing::info!("Debugger detached: {}", error);
+            self.detach();
+        }
+    }
+
+    fn exchange(&mut self) -> std::io::Result<()> {
+        let Some(connection) = &mut self.connection else {
+            return Ok(());
+        };
+
+        let mut buffer = [0; 4096];
+        loop {
+            match connection.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(length) => connection.incoming.extend_from_slice(&buffer[..length]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        while let Some(packet) = self.take_packet() {
+            let Some(packet) = packet else {
+                self.processor.debug_state().halt();
+
+                if let Some(connection) = &mut self.connection {
+                    if connection.running {
+                        connection.running = false;
+                        send_packet(&mut connection.stream, &format!("S{:02x}", SIGINT))?;
+                    }
+                }
+                continue;
+            };
+
+            let reply = self.handle_packet(&packet);
+            let Some(connection) = &mut self.connection else {
+                return Ok(());
+            };
+            if !connection.no_ack {
+                connection.stream.write_all(b"+")?;
+            }
+            if let Some(reply) = reply {
+                send_packet(&mut connection.stream, &reply)?;
+            }
+            if packet == "QStartNoAckMode" {
+                connection.no_ack = true;
+            }
+            if packet == "D" {
+                tracing::info!("Debugger detached");
+                self.detach();
+                return Ok(());
+            }
+        }
+
+        if let Some(connection) = &mut self.connection {
+            if connection.running && self.processor.debug_state().halted() {
+                connection.running = false;
+                send_packet(&mut connection.stream, &format!("S{:02x}", SIGTRAP))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The next packet's contents, or None inside for an interrupt, skipping acks and anything that doesn't check out
+    fn take_packet(&mut self) -> Option<Option<String>> {
+        let connection = self.connection.as_mut()?;
+
+        loop {
+            let start = connection
+                .incoming
+                .iter()
+                .position(|byte| matches!(*byte, b'$' | INTERRUPT))?;
+
+            if connection.incoming[start] == INTERRUPT {
+                connection.incoming.drain(..=start);
+                return Some(None);
+            }
+
+            let end = connection.incoming[start..]
+                .iter()
+                .position(|byte| *byte == b'#')?
+                + start;
+            if connection.incoming.len() < end + 3 {
+                return None;
+            }
+
+            let body = connection.incoming[start + 1..end].to_vec();
+            let checksum = std::str::from_utf8(&connection.incoming[end + 1..end + 3])
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+            connection.incoming.drain(..end + 3);
+
+            if connection.no_ack || checksum == Some(checksum_of(&body)) {
+                return Some(Some(String::from_utf8_lossy(&body).into_owned()));
+            }
+
+            // Asks for it again
+            let _ = connection.stream.write_all(b"-");
+        }
+    }
+
+    /// Reply to a packet, None when the reply comes later, once the processor stops
+    fn handle_packet(&mut self, packet: &str) -> Option<String> {
+        if packet.starts_with('c') || packet.starts_with("vCont;c") {
+            self.resume();
+            return None;
+        }
+
+        if packet == "k" {
+            self.detach();
+            return None;
+        }
+
+        Some(self.answer(packet).unwrap_or_else(|| "E01".to_string()))
+    }
+
+    /// Reply to a packet that is answered straight away, None if it didn't make sense
+    fn answer(&mut self, packet: &str) -> Option<String> {
+        let (command, arguments) = packet.split_at(packet.len().min(1));
+
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => (0..self.processor.registers().len())
+                .map(|index| self.encode_register(index))
+                .collect(),
+            "G" => {
+                let mut bytes = decode_hex(arguments)?.into_iter();
+
+                for (index, register) in self.processor.registers().iter().enumerate() {
+                    let value = bytes
+                        .by_ref()
+                        .take(register.size)
+                        .enumerate()
+                        .fold(0, |value, (byte_index, byte)| {
+                            value | ((byte as u64) << (byte_index * 8))
+                        });
+                    self.processor.write_register(index, value);
+                }
+                "OK".to_string()
+            }
+            "p" => {
+                let index = usize::from_str_radix(arguments, 16).ok()?;
+                if index >= self.processor.registers().len() {
+                    return Some("E01".to_string());
+                }
+                self.encode_register(index)
+            }
+            "P" => {
+                let (index, value) = arguments.split_once('=')?;
+                let index = usize::from_str_radix(index, 16).ok()?;
+                let value = decode_hex(value)?
+                    .into_iter()
+                    .rev()
+                    .fold(0, |value, byte| (value << 8) | byte as u64);
+
+                self.processor.write_register(index, value);
+                "OK".to_string()
+            }
+            "m" => {
+                let (address, length) = arguments.split_once(',')?;
+                let address = usize::from_str_radix(address, 16).ok()?;
+                let length = usize::from_str_radix(length, 16).ok()?;
+                // A byte at a time, so a read running into unmapped memory still returns what came before it
+                let bytes: Vec<_> = (address..address + length)
+                    .map_while(|address| {
+                        let mut byte = [0];
+                        self.memory_translation_table
+                            .preview(address, &mut byte, self.processor.address_space())
+                            .ok()
+                            .map(|_| byte[0])
+                    })
+                    .collect();
+
+                if bytes.is_empty() && length != 0 {
+                    "E14".to_string()
+                } else {
+                    encode_hex(&bytes)
+                }
+            }
+            "M" => {
+                let (location, data) = arguments.split_once(':')?;
+                let (address, _) = location.split_once(',')?;
+                let address = usize::from_str_radix(address, 16).ok()?;
+
+                let written = (address..).zip(decode_hex(data)?).all(|(address, byte)| {
+                    self.memory_translation_table
+                        .write(address, &[byte], self.processor.address_space())
+                        .is_ok()
+                });
+
+                if written {
+                    "OK".to_string()
+                } else {
+                    "E14".to_string()
+                }
+            }
+            "s" => self.step(),
+            "Z" | "z" => {
+                let mut fields = arguments.split(',');
+                let kind = fields.next()?;
+                let address = usize::from_str_radix(fields.next()?, 16).ok()?;
+
+                // Software and hardware breakpoints are the same thing here, watchpoints aren't supported
+                if matches!(kind, "0" | "1") {
+                    self.processor
+                        .debug_state()
+                        .set_breakpoint(address, command == "Z");
+                    "OK".to_string()
+                } else {
+                    String::new()
+                }
+            }
+            "H" | "T" => "OK".to_string(),
+            // Let go of once the reply is out
+            "D" => "OK".to_string(),
+            _ => match packet {
+                _ if packet.starts_with("qSupported") => {
+                    "PacketSize=1000;QStartNoAckMode+;swbreak+;hwbreak+;vContSupported+".to_string()
+                }
+                "QStartNoAckMode" => "OK".to_string(),
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                "vCont?" => "vCont;c;s".to_string(),
+                _ if packet.starts_with("vCont;s") => self.step(),
+                _ if packet.starts_with("qRegisterInfo") => {
+                    let index = usize::from_str_radix(&packet["qRegisterInfo".len()..], 16).ok()?;
+                    self.register_info(index)
+                }
+                // Anything else isn't supported, which an empty reply says
+                _ => String::new(),
+            },
+        };
+
+        Some(reply)
+    }
+
+    fn resume(&mut self) {
+        if let Some(connection) = &mut self.connection {
+            connection.running = true;
+        }
+        self.processor.debug_state().resume();
+    }
+
+    fn step(&mut self) -> String {
+        self.processor.step();
+
+        format!("S{:02x}", SIGTRAP)
+    }
+
+    /// Lets the processor go and drops the debugger
+    fn detach(&mut self) {
+        self.connection = None;
+        self.processor.debug_state().resume();
+    }
+
+    /// A register in target byte order, which is little endian for everything here
+    fn encode_register(&self, index: usize) -> String {
+        let size = self.processor.registers()[index].size;
+        let value = self.processor.read_register(index);
+
+        encode_hex(&value.to_le_bytes()[..size])
+    }
+
+    /// lldb asks for registers one by one, since there is no architecture it knows to look them up in
+    fn register_info(&self, index: usize) -> String {
+        let Some(register) = self.processor.registers().get(index) else {
+            return "E45".to_string();
+        };
+        let offset: usize = self.processor.registers()[..index]
+            .iter()
+            .map(|register| register.size)
+            .sum();
+
+        let mut info = format!(
+            "name:{};bitsize:{};offset:{};encoding:uint;format:hex;set:General Purpose Registers;",
+            register.name,
+            register.size * 8,
+            offset
+        );
+        if let Some(generic) = register.generic {
+            let _ = write!(info, "generic:{};", generic);
+        }
+
+        info
+    }
+}
+
+fn checksum_of(body: &[u8]) -> u8 {
+    body.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn send_packet(stream: &mut TcpStream, body: &str) -> std::io::Result<()> {
+    write!(stream, "${}#{:02x}", body, checksum_of(body.as_bytes()))
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::definitions::misc::processor::m6502::{
+        M6502Config, M6502Kind, UndocumentedOpcodes, M6502,
+    };
+    use crate::machine::test_machine::TestMachineBuilder;
+    use num::rational::Ratio;
+
+    #[test]
+    fn registers_memory_and_breakpoints() {
+        let (builder, processor) = TestMachineBuilder::new()
+            .bus(0, 16)
+            .scratch_ram(0, 0x0000..0x10000, 0xea)
+            .component::<M6502>(M6502Config {
+                kind: M6502Kind::M6502 {
+                    quirk_broken_ror: false,
+                },
+                frequency: Ratio::from_integer(1_000_000),
+                assigned_address_space: 0,
+                undocumented_opcodes: UndocumentedOpcodes::Full,
+                magic_constant: 0xee,
+                cycle_accurate: false,
+                wait_states: None,
+            });
+        let machine = builder.build();
+        let mut stub = GdbStub {
+            listener: TcpListener::bind(("127.0.0.1", 0)).unwrap(),
+            connection: None,
+            processor: machine
+                .machine
+                .debuggable_processors()
+                .next()
+                .unwrap()
+                .component
+                .clone(),
+            memory_translation_table: machine.machine.memory_translation_table.clone(),
+        };
+        let mut handle = |packet: &str| stub.handle_packet(packet);
+
+        // A, X, Y, SP, P, then the program counter as two bytes
+        assert_eq!(handle("g").as_deref(), Some("000000ff000000"));
+        assert_eq!(handle("P5=0002").as_deref(), Some("OK"));
+        assert_eq!(handle("p5").as_deref(), Some("0002"));
+
+        assert_eq!(handle("M200,2:a942").as_deref(), Some("OK"));
+        assert_eq!(handle("m200,3").as_deref(), Some("a942ea"));
+
+        // Stepping runs LDA #$42
+        assert_eq!(handle("s").as_deref(), Some("S05"));
+        assert_eq!(handle("p0").as_deref(), Some("42"));
+
+        // Continuing waits for the breakpoint, which stops the processor before that instruction
+        assert_eq!(handle("Z0,205,1").as_deref(), Some("OK"));
+        assert_eq!(handle("c"), None);
+        drop(handle);
+        machine.run_component::<M6502>(processor, 100);
+        assert!(stub.processor.debug_state().halted());
+        assert_eq!(stub.handle_packet("p5").as_deref(), Some("0502"));
+    }
+}
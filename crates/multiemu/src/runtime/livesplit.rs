@@ -0,0 +1,143 @@
+use crate::machine::{clock::MachineClock, trigger::TriggerEvent};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    net::TcpStream,
+    sync::{mpsc::Receiver, Arc},
+    thread::JoinHandle,
+    time::Duration,
+};
+use strum::Display;
+
+/// Commands from the LiveSplit Server component that a trigger can send
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Display, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum LiveSplitCommand {
+    StartTimer,
+    Split,
+    SkipSplit,
+    UndoSplit,
+    Reset,
+    Pause,
+    Resume,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct LiveSplitConfig {
+    /// Where the LiveSplit Server component is listening
+    #[serde(default = "default_livesplit_address")]
+    pub address: String,
+    /// Trigger names and what to tell LiveSplit when they fire
+    pub triggers: IndexMap<String, LiveSplitCommand>,
+    /// Keeps LiveSplits game time in line with emulated time, so lag and pauses don't count
+    #[serde(default)]
+    pub game_time: bool,
+}
+
+fn default_livesplit_address() -> String {
+    "localhost:16834".to_string()
+}
+
+/// Connection to LiveSplit, which reconnects on the next command if LiveSplit was restarted
+#[derive(Debug)]
+pub struct LiveSplitClient {
+    address: String,
+    stream: Option<TcpStream>,
+}
+
+impl LiveSplitClient {
+    pub fn new(address: String) -> Self {
+        Self {
+            address,
+            stream: None,
+        }
+    }
+
+    fn send_line(&mut self, line: &str) -> std::io::Result<()> {
+        // One retry so a dropped connection is reopened instead of losing the split
+        for _ in 0..2 {
+            let stream = match &mut self.stream {
+                Some(stream) => stream,
+                None => self.stream.insert(TcpStream::connect(&self.address)?),
+            };
+
+            match stream.write_all(format!("{}\r\n", line).as_bytes()) {
+                Ok(()) => return Ok(()),
+                Err(_) => self.stream = None,
+            }
+        }
+
+        Err(std::io::ErrorKind::BrokenPipe.into())
+    }
+
+    pub fn send(&mut self, command: LiveSplitCommand) -> std::io::Result<()> {
+        self.send_line(&command.to_string())
+    }
+
+    pub fn set_game_time(&mut self, time: Duration) -> std::io::Result<()> {
+        self.send_line(&format!("setgametime {}", format_time(time)))
+    }
+}
+
+/// LiveSplit parses times as hours:minutes:seconds
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60,
+        time.subsec_millis()
+    )
+}
+
+/// Forwards trigger events to LiveSplit on its own thread, so a slow connection never stalls emulation
+pub fn spawn(
+    config: LiveSplitConfig,
+    events: Receiver<TriggerEvent>,
+    clock: Arc<MachineClock>,
+) -> JoinHandle<()> {
+    std::thread::Builder::new()
+        .name("livesplit".to_string())
+        .spawn(move || {
+            let mut client = LiveSplitClient::new(config.address);
+
+            // Ends when the machine and its trigger engine go away
+            for event in events {
+                let Some(command) = config.triggers.get(&event.name).copied() else {
+                    continue;
+                };
+
+                let result = if config.game_time {
+                    client
+                        .set_game_time(clock.timestamp_to_duration(event.timestamp))
+                        .and_then(|_| client.send(command))
+                } else {
+                    client.send(command)
+                };
+
+                if let Err(error) = result {
+                    tracing::error!(
+                        "Could not send {} to LiveSplit for trigger {}: {}",
+                        command,
+                        event.name,
+                        error
+                    );
+                }
+            }
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands_and_times() {
+        assert_eq!(LiveSplitCommand::StartTimer.to_string(), "starttimer");
+        assert_eq!(format_time(Duration::from_millis(3_723_045)), "1:02:03.045");
+    }
+}
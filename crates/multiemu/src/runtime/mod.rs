@@ -1,4 +1,17 @@
+pub mod audio;
+pub mod audio_backend;
+pub mod av_sync;
+pub mod color;
+pub mod debug_view;
+pub mod frame_pacer;
+pub mod gdb;
 pub mod launch;
+pub mod livesplit;
+pub mod ntsc;
 pub mod platform;
+pub mod progress;
 pub mod rendering_backend;
+pub mod rewind;
+pub mod scaler;
 pub mod timing_tracker;
+pub mod video_sink;
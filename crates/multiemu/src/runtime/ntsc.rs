@@ -0,0 +1,178 @@
+use crate::config::NtscFilter;
+use nalgebra::DMatrixViewMut;
+use palette::Srgba;
+
+/// Samples of the video signal taken for every pixel, with a color subcarrier cycle every two pixels
+///
+/// Same ratio as the Apple II and CGA, so single pixel wide patterns land right on the subcarrier
+const SAMPLES_PER_PIXEL: usize = 2;
+/// Samples in one cycle of the color subcarrier
+const CARRIER_PERIOD: usize = 4;
+/// Cycles of the subcarrier chroma is demodulated over, more than a pixel so it bleeds into the neighbors
+const CHROMA_CYCLES: usize = 3;
+
+impl NtscFilter {
+    pub fn is_identity(&self) -> bool {
+        *self == NtscFilter::None
+    }
+
+    /// Encodes each line into a signal and decodes it back, like a tv would
+    ///
+    /// Kept in step with the shader the vulkan renderer uses, so both look the same
+    pub fn apply_framebuffer(&self, mut framebuffer: DMatrixViewMut<'_, Srgba<u8>>) {
+        if self.is_identity() {
+            return;
+        }
+
+        let width = framebuffer.nrows();
+        let signal_length = width * SAMPLES_PER_PIXEL;
+        let mut luma = vec![0.0; signal_length];
+        let mut chroma = vec![0.0; signal_length];
+
+        for y in 0..framebuffer.ncols() {
+            // Shifting the phase every line stops artifacts from lining up into vertical stripes
+            let phase_offset = y % 2 * (CARRIER_PERIOD / 2);
+
+            for sample in 0..signal_length {
+                let [sample_luma, i, q] = to_yiq(framebuffer[(sample / SAMPLES_PER_PIXEL, y)]);
+                let (cosine, sine) = carrier(sample + phase_offset);
+
+                luma[sample] = sample_luma;
+                chroma[sample] = i * cosine + q * sine;
+            }
+
+            if *self == NtscFilter::Composite {
+                // The tv only gets the sum, and has to pull them apart again
+                for sample in 0..signal_length {
+                    luma[sample] += chroma[sample];
+                    chroma[sample] = luma[sample];
+                }
+            }
+
+            for x in 0..width {
+                // Windows are a whole number of subcarrier cycles centered on the pixel, so steady colors cancel out
+                // of the luma and come through the demodulation unchanged
+                let center = x * SAMPLES_PER_PIXEL + SAMPLES_PER_PIXEL / 2;
+
+                let decoded_luma = match self {
+                    NtscFilter::Composite => {
+                        window(center, CARRIER_PERIOD, signal_length)
+                            .map(|sample| luma[sample])
+                            .sum::<f32>()
+                            / CARRIER_PERIOD as f32
+                    }
+                    _ => luma[center - 1],
+                };
+
+                let chroma_window = CARRIER_PERIOD * CHROMA_CYCLES;
+                let (decoded_i, decoded_q) = window(center, chroma_window, signal_length).fold(
+                    (0.0, 0.0),
+                    |(i, q), sample| {
+                        let (cosine, sine) = carrier(sample + phase_offset);
+
+                        (i + chroma[sample] * cosine, q + chroma[sample] * sine)
+                    },
+                );
+                // Each component is only on the carrier half the time
+                let scale = 2.0 / chroma_window as f32;
+
+                framebuffer[(x, y)] = from_yiq(
+                    [decoded_luma, decoded_i * scale, decoded_q * scale],
+                    framebuffer[(x, y)].alpha,
+                );
+            }
+        }
+    }
+}
+
+/// Cosine and sine of the subcarrier, exact since it is sampled every quarter turn
+fn carrier(phase: usize) -> (f32, f32) {
+    match phase % CARRIER_PERIOD {
+        0 => (1.0, 0.0),
+        1 => (0.0, 1.0),
+        2 => (-1.0, 0.0),
+        _ => (0.0, -1.0),
+    }
+}
+
+/// Samples around a center, moved by whole cycles where it goes past the edges so the phase still lines up
+fn window(center: usize, length: usize, signal_length: usize) -> impl Iterator<Item = usize> {
+    let start = center as isize - length as isize / 2;
+
+    (start..start + length as isize).map(move |sample| {
+        let mut sample = sample;
+
+        while sample < 0 {
+            sample += CARRIER_PERIOD as isize;
+        }
+        while sample >= signal_length as isize {
+            sample -= CARRIER_PERIOD as isize;
+        }
+
+        sample.max(0) as usize
+    })
+}
+
+/// The signal carries gamma encoded values, so this works on them directly
+fn to_yiq(color: Srgba<u8>) -> [f32; 3] {
+    let (r, g, b) = (
+        color.red as f32 / 255.0,
+        color.green as f32 / 255.0,
+        color.blue as f32 / 255.0,
+    );
+
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        0.596 * r - 0.274 * g - 0.322 * b,
+        0.211 * r - 0.523 * g + 0.312 * b,
+    ]
+}
+
+fn from_yiq([y, i, q]: [f32; 3], alpha: u8) -> Srgba<u8> {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    Srgba::new(
+        channel(y + 0.956 * i + 0.621 * q),
+        channel(y - 0.272 * i - 0.647 * q),
+        channel(y - 1.106 * i + 1.703 * q),
+        alpha,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nalgebra::DMatrix;
+
+    #[test]
+    fn flat_colors_survive() {
+        let color = Srgba::new(200, 60, 30, 255);
+
+        for filter in [NtscFilter::Composite, NtscFilter::SVideo] {
+            let mut framebuffer = DMatrix::from_element(16, 2, color);
+            filter.apply_framebuffer(framebuffer.as_view_mut());
+
+            let decoded = framebuffer[(8, 1)];
+            assert!(decoded.red.abs_diff(color.red) <= 2);
+            assert!(decoded.green.abs_diff(color.green) <= 2);
+            assert!(decoded.blue.abs_diff(color.blue) <= 2);
+        }
+    }
+
+    #[test]
+    fn only_composite_makes_artifact_colors() {
+        let white = Srgba::new(255, 255, 255, 255);
+        let black = Srgba::new(0, 0, 0, 255);
+        // Alternating columns, the classic way to get color out of a monochrome mode
+        let stripes = DMatrix::from_fn(16, 1, |x, _| if x % 2 == 0 { white } else { black });
+
+        let mut composite = stripes.clone();
+        NtscFilter::Composite.apply_framebuffer(composite.as_view_mut());
+        let pixel = composite[(8, 0)];
+        assert!(pixel.red.abs_diff(pixel.blue) > 32 || pixel.red.abs_diff(pixel.green) > 32);
+
+        let mut s_video = stripes.clone();
+        NtscFilter::SVideo.apply_framebuffer(s_video.as_view_mut());
+        assert_eq!(s_video, stripes);
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{
+    config::GLOBAL_CONFIG,
+    machine::Machine,
+    runtime::{
+        audio::{AudioBuffer, AudioHost},
+        audio_backend::AudioMixer,
+    },
+};
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    BufferSize, Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+    SupportedBufferSize,
+};
+use std::sync::Arc;
+
+/// Plays a machine's sound through cpal
+pub struct AudioOutput {
+    /// Playback stops when this is dropped
+    _stream: Stream,
+    buffer: Arc<AudioBuffer>,
+    mixer: AudioMixer,
+}
+
+impl AudioOutput {
+    /// None if the machine makes no sound or there is nothing to play it on
+    pub fn new(machine: &Machine) -> Option<Self> {
+        let host = open_host();
+
+        let Some(device) = host.default_output_device() else {
+            tracing::warn!("No audio output device, running without sound");
+            return None;
+        };
+
+        let supported_config = device
+            .default_output_config()
+            .inspect_err(|error| tracing::error!("Could not query the audio device: {}", error))
+            .ok()?;
+        let sample_format = supported_config.sample_format();
+        let mut config = supported_config.config();
+
+        // Devices that don't say what they take get whatever they default to
+        if let SupportedBufferSize::Range { min, max } = supported_config.buffer_size() {
+            let buffer_size = GLOBAL_CONFIG.read().unwrap().audio_buffer_size;
+            config.buffer_size = BufferSize::Fixed(buffer_size.clamp(*min, *max));
+        }
+
+        let mixer = AudioMixer::new(machine, config.sample_rate.0, config.channels)?;
+        let buffer = Arc::new(AudioBuffer::from_config(
+            config.sample_rate.0,
+            config.channels,
+        ));
+        buffer.set_samples_per_frame(machine.samples_per_frame(config.sample_rate.0));
+
+        let stream = match sample_format {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, buffer.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, buffer.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, buffer.clone()),
+            SampleFormat::I32 => build_stream::<i32>(&device, &config, buffer.clone()),
+            sample_format => {
+                tracing::error!("Audio sample format {} is not supported", sample_format);
+                return None;
+            }
+        }
+        .inspect_err(|error| tracing::error!("Could not open the audio output: {}", error))
+        .ok()?;
+
+        stream
+            .play()
+            .inspect_err(|error| tracing::error!("Could not start the audio output: {}", error))
+            .ok()?;
+
+        tracing::info!(
+            "Playing audio at {}hz with {} channels",
+            config.sample_rate.0,
+            config.channels
+        );
+
+        Some(Self {
+            _stream: stream,
+            buffer,
+            mixer,
+        })
+    }
+
+    /// Hands the samples made since the last call to the device
+    pub fn mix(&mut self) {
+        self.mixer.mix(&self.buffer);
+    }
+}
+
+/// The cpal host matching the users choice, or the platform default
+fn open_host() -> cpal::Host {
+    let preferred = GLOBAL_CONFIG.read().unwrap().audio_host;
+    let host_id = |audio_host: AudioHost| match audio_host {
+        AudioHost::Shared | AudioHost::WasapiExclusive => None,
+        AudioHost::Jack => cpal::available_hosts()
+            .into_iter()
+            .find(|host_id| host_id.name() == "JACK"),
+    };
+
+    match host_id(preferred.resolve(|audio_host| host_id(audio_host).is_some())) {
+        Some(host_id) => cpal::host_from_id(host_id).unwrap_or_else(|error| {
+            tracing::warn!("Could not open {}: {}", preferred, error);
+            cpal::default_host()
+        }),
+        None => cpal::default_host(),
+    }
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &Device,
+    config: &StreamConfig,
+    buffer: Arc<AudioBuffer>,
+) -> Result<Stream, cpal::BuildStreamError> {
+    let mut samples = Vec::new();
+
+    device.build_output_stream(
+        config,
+        move |output: &mut [T], _| {
+            samples.resize(output.len(), 0.0);
+            buffer.fill(&mut samples);
+
+            for (destination, sample) in output.iter_mut().zip(samples.iter().copied()) {
+                *destination = T::from_sample(sample);
+            }
+        },
+        |error| tracing::error!("Audio output failed: {}", error),
+        None,
+    )
+}
@@ -0,0 +1,80 @@
+use crate::config::{FullscreenMode, GlobalConfig};
+use winit::{
+    monitor::MonitorHandle,
+    window::{Fullscreen, Window},
+};
+
+/// Finds the monitor the user asked for, falling back to whatever the window is on
+fn select_monitor(window: &Window, monitor_name: Option<&str>) -> Option<MonitorHandle> {
+    if let Some(monitor_name) = monitor_name {
+        if let Some(monitor) = window
+            .available_monitors()
+            .find(|monitor| monitor.name().as_deref() == Some(monitor_name))
+        {
+            return Some(monitor);
+        }
+
+        tracing::warn!(
+            "Could not find monitor \"{}\", using the current one instead",
+            monitor_name
+        );
+    }
+
+    window
+        .current_monitor()
+        .or_else(|| window.primary_monitor())
+}
+
+/// Builds the fullscreen state the user configured
+pub fn configured_fullscreen(window: &Window, config: &GlobalConfig) -> Option<Fullscreen> {
+    let monitor = select_monitor(window, config.fullscreen_monitor.as_deref());
+
+    match config.fullscreen_mode {
+        FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        FullscreenMode::Exclusive => {
+            let monitor = monitor?;
+
+            // Biggest and fastest mode that matches what the user wants
+            let video_mode = monitor
+                .video_modes()
+                .filter(|video_mode| {
+                    config.fullscreen_resolution.is_none_or(|(width, height)| {
+                        video_mode.size().width == width && video_mode.size().height == height
+                    })
+                })
+                .max_by_key(|video_mode| {
+                    (
+                        video_mode.size().width * video_mode.size().height,
+                        video_mode.refresh_rate_millihertz(),
+                        video_mode.bit_depth(),
+                    )
+                });
+
+            if let Some(video_mode) = video_mode {
+                tracing::info!(
+                    "Switching to exclusive fullscreen at {}x{} {}mHz",
+                    video_mode.size().width,
+                    video_mode.size().height,
+                    video_mode.refresh_rate_millihertz()
+                );
+
+                Some(Fullscreen::Exclusive(video_mode))
+            } else {
+                tracing::warn!(
+                    "No video mode matched the configured resolution, using borderless fullscreen"
+                );
+
+                Some(Fullscreen::Borderless(Some(monitor)))
+            }
+        }
+    }
+}
+
+pub fn toggle_fullscreen(window: &Window, config: &GlobalConfig) {
+    if window.fullscreen().is_some() {
+        tracing::info!("Leaving fullscreen");
+        window.set_fullscreen(None);
+    } else {
+        window.set_fullscreen(configured_fullscreen(window, config));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{
+    input::{controller_db::RawInput, manager::InputManager, profile::DeviceIdentity, GamepadId},
+    machine::Machine,
+};
+use gilrs_core::{EvCode, EventType, Gilrs};
+use std::collections::HashMap;
+
+/// Keyboard and mouse take the ids below this
+const FIRST_GAMEPAD_ID: GamepadId = 2;
+
+/// Physical controllers, read through gilrs-core so the raw button and axis numbers stay visible
+pub struct GamepadBackend {
+    gilrs: Option<Gilrs>,
+    /// Keyed by the backends own id
+    devices: HashMap<usize, (GamepadId, DeviceIdentity)>,
+}
+
+impl GamepadBackend {
+    pub fn new() -> Self {
+        let gilrs = Gilrs::new()
+            .inspect_err(|error| tracing::warn!("Controllers are unavailable: {}", error))
+            .ok();
+        let mut backend = Self {
+            gilrs,
+            devices: HashMap::default(),
+        };
+
+        // Controllers already plugged in don't get a connect event
+        let connected: Vec<_> = backend
+            .gilrs
+            .iter()
+            .flat_map(|gilrs| {
+                (0..gilrs.last_gamepad_hint()).filter(|id| {
+                    gilrs
+                        .gamepad(*id)
+                        .is_some_and(|gamepad| gamepad.is_connected())
+                })
+            })
+            .collect();
+        for id in connected {
+            backend.connect(id);
+        }
+
+        backend
+    }
+
+    /// Tells a machine that just started about every controller plugged in, so their profiles apply from the start
+    pub fn announce(&self, input_manager: &InputManager) {
+        for (gamepad_id, device) in self.devices.values() {
+            input_manager.device_connected(*gamepad_id, device.clone());
+        }
+    }
+
+    /// Handles everything the controllers did since last time, returns if one was plugged in or out
+    pub fn poll(&mut self, machine: Option<&Machine>) -> bool {
+        let input_manager = machine.map(|machine| &*machine.input_manager);
+        let mut devices_changed = false;
+
+        while let Some(event) = self.gilrs.as_mut().and_then(Gilrs::next_event) {
+            match event.event {
+                EventType::Connected => {
+                    if let Some((gamepad_id, device)) = self.connect(event.id) {
+                        if let Some(input_manager) = input_manager {
+                            input_manager.device_connected(gamepad_id, device);
+                        }
+                        devices_changed = true;
+                    }
+                }
+                EventType::Disconnected => {
+                    if let Some((gamepad_id, _)) = self.devices.remove(&event.id) {
+                        if let Some(input_manager) = input_manager {
+                            input_manager.device_disconnected(gamepad_id);
+                        }
+                        devices_changed = true;
+                    }
+                }
+                event_type => {
+                    let (Some(machine), Some((gamepad_id, _))) =
+                        (machine, self.devices.get(&event.id))
+                    else {
+                        continue;
+                    };
+
+                    // Only the numbers the device reports, the controller database knows what they are
+                    for (raw_input, value) in self.raw_inputs(event.id, event_type) {
+                        machine.input_manager.insert_raw_input(
+                            machine.system,
+                            *gamepad_id,
+                            raw_input,
+                            value,
+                        );
+                    }
+                }
+            }
+        }
+
+        devices_changed
+    }
+
+    /// Numbers buttons, axes and hats the way SDL does, so database mappings line up
+    fn raw_inputs(&self, id: usize, event_type: EventType) -> Vec<(RawInput, f32)> {
+        let Some(gamepad) = self.gilrs.as_ref().and_then(|gilrs| gilrs.gamepad(id)) else {
+            return Vec::new();
+        };
+
+        match event_type {
+            EventType::ButtonPressed(code) | EventType::ButtonReleased(code) => {
+                let value = if matches!(event_type, EventType::ButtonPressed(_)) {
+                    1.0
+                } else {
+                    0.0
+                };
+
+                gamepad
+                    .buttons()
+                    .iter()
+                    .position(|button| *button == code)
+                    .map(|index| vec![(RawInput::Button(index as u8), value)])
+                    .unwrap_or_default()
+            }
+            EventType::AxisValueChanged(value, code) => {
+                let Some(info) = gamepad.axis_info(code) else {
+                    return Vec::new();
+                };
+                let value = ((value - info.min) as f32 / (info.max - info.min) as f32) * 2.0 - 1.0;
+
+                if let Some((index, vertical)) = hat_axis(code) {
+                    // SDL hat bits are up, right, down, left
+                    let (negative, positive) = if vertical { (1, 4) } else { (8, 2) };
+
+                    return vec![
+                        (
+                            RawInput::Hat(index, negative),
+                            if value < -0.5 { 1.0 } else { 0.0 },
+                        ),
+                        (
+                            RawInput::Hat(index, positive),
+                            if value > 0.5 { 1.0 } else { 0.0 },
+                        ),
+                    ];
+                }
+
+                gamepad
+                    .axes()
+                    .iter()
+                    .filter(|axis| hat_axis(**axis).is_none())
+                    .position(|axis| *axis == code)
+                    .map(|index| vec![(RawInput::Axis(index as u8), value)])
+                    .unwrap_or_default()
+            }
+            EventType::Connected | EventType::Disconnected => Vec::new(),
+        }
+    }
+
+    fn connect(&mut self, id: usize) -> Option<(GamepadId, DeviceIdentity)> {
+        let gamepad = self.gilrs.as_ref()?.gamepad(id)?;
+        let Some(gamepad_id) = GamepadId::try_from(id)
+            .ok()
+            .and_then(|id| id.checked_add(FIRST_GAMEPAD_ID))
+        else {
+            tracing::warn!("Too many controllers, ignoring {}", gamepad.name());
+            return None;
+        };
+        // gilrs-core hands out the same bytes SDL builds its GUIDs from
+        let device = DeviceIdentity {
+            guid: Some(
+                gamepad
+                    .uuid()
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect(),
+            ),
+            name: gamepad.name().to_string(),
+        };
+
+        tracing::info!("{} connected", device);
+        self.devices.insert(id, (gamepad_id, device.clone()));
+
+        Some((gamepad_id, device))
+    }
+}
+
+/// Linux reports hats as a pair of axes each, which SDL counts apart from the other axes
+///
+/// Returns the hat and if this is its vertical axis
+#[cfg(target_os = "linux")]
+fn hat_axis(code: EvCode) -> Option<(u8, bool)> {
+    // ABS_HAT0X through ABS_HAT3Y, under the event type in the top half
+    let code = code.into_u32() & 0xffff;
+
+    (0x10..=0x17)
+        .contains(&code)
+        .then(|| (((code - 0x10) / 2) as u8, code % 2 == 1))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn hat_axis(_code: EvCode) -> Option<(u8, bool)> {
+    None
+}
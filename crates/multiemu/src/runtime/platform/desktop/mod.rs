@@ -1,33 +1,89 @@
 use crate::{
-    gui::menu::MenuState,
-    rom::{id::RomId, manager::RomManager, system::GameSystem},
+    definitions::chip8::assembler::OctoLoadError,
+    gui::{menu::MenuState, pause_menu::PauseMenu, stats_overlay::StatsOverlay},
+    input::{paste::TextPaste, Input},
+    machine::serialization::MachineState,
+    rom::{id::RomId, io::IoJob, manager::RomManager, system::GameSystem, watch::IngestedRom},
     runtime::{
-        launch::Runtime, rendering_backend::RenderingBackendState, timing_tracker::TimingTracker,
+        frame_pacer::FramePacer,
+        gdb::GdbStub,
+        launch::Runtime,
+        rendering_backend::RenderingBackendState,
+        rewind::RewindBuffer,
+        timing_tracker::TimingTracker,
+        video_sink::{SinkId, VideoSinks},
     },
 };
 use ::winit::{event_loop::EventLoop, window::Window};
-use std::sync::Arc;
+use audio::AudioOutput;
+use gamepad::GamepadBackend;
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::{mpsc::Receiver, Arc},
+};
 use winit::{MachineContext, WindowingContext};
 
+mod audio;
+mod fullscreen;
+mod gamepad;
 pub mod renderer;
 mod winit;
 
 pub struct PlatformRuntime<RS: RenderingBackendState> {
     menu: MenuState,
+    pause_menu: PauseMenu,
     windowing_context: Option<WindowingContext<RS>>,
     machine_context: Option<MachineContext>,
     rom_manager: Arc<RomManager>,
+    /// ROMs the watch folders turned up, shown to the user as they arrive
+    ingested_roms: Receiver<IngestedRom>,
+    /// ROM the user picked that the IO workers are still hashing, along with where it was picked from
+    pending_rom: Option<(PathBuf, IoJob<Result<(RomId, PathBuf), OctoLoadError>>)>,
     timing_tracker: TimingTracker,
+    stats_overlay: StatsOverlay,
+    frame_pacer: FramePacer,
+    /// Keys currently held, used to detect hotkey combinations
+    pressed_inputs: BTreeSet<Input>,
+    /// Everything consuming the machine's frames besides the window itself
+    video_sinks: VideoSinks,
+    /// The sink writing the recording started with the hotkey and where it is going, if one is going
+    recording: Option<(SinkId, PathBuf)>,
+    /// The running machine right after it booted, for resetting it from the pause menu
+    boot_state: Option<MachineState>,
+    /// Snapshots of the running machine to step back through, if rewinding is on and the machine allows it
+    rewind: Option<RewindBuffer>,
+    /// Where a debugger attaches to the running machine, if one was asked for
+    gdb: Option<GdbStub>,
+    /// Sound of the running machine, missing if it makes none or there is nowhere to play it
+    audio_output: Option<AudioOutput>,
+    /// Clipboard text still being typed into the running machine
+    paste: Option<TextPaste>,
+    gamepads: GamepadBackend,
 }
 
 impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> Runtime for PlatformRuntime<RS> {
     fn launch_gui(rom_manager: Arc<RomManager>) {
         let mut me = Self {
             menu: MenuState::default(),
+            pause_menu: PauseMenu::default(),
             windowing_context: None,
             machine_context: None,
+            ingested_roms: rom_manager.watch_folders(),
             rom_manager,
+            pending_rom: None,
             timing_tracker: TimingTracker::default(),
+            stats_overlay: StatsOverlay::default(),
+            frame_pacer: FramePacer::default(),
+            pressed_inputs: BTreeSet::default(),
+            video_sinks: VideoSinks::default(),
+            recording: None,
+            boot_state: None,
+            rewind: None,
+            gdb: None,
+            audio_output: None,
+            paste: None,
+            gamepads: GamepadBackend::new(),
         };
 
         let event_loop = EventLoop::new().unwrap();
@@ -41,13 +97,27 @@ impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> Runtime for Plat
     ) {
         let mut me = Self {
             menu: MenuState::default(),
+            pause_menu: PauseMenu::default(),
             windowing_context: None,
             machine_context: Some(MachineContext::Pending {
                 user_specified_roms,
                 forced_system,
             }),
+            ingested_roms: rom_manager.watch_folders(),
             rom_manager,
+            pending_rom: None,
             timing_tracker: TimingTracker::default(),
+            stats_overlay: StatsOverlay::default(),
+            frame_pacer: FramePacer::default(),
+            pressed_inputs: BTreeSet::default(),
+            video_sinks: VideoSinks::default(),
+            recording: None,
+            boot_state: None,
+            rewind: None,
+            gdb: None,
+            audio_output: None,
+            paste: None,
+            gamepads: GamepadBackend::new(),
         };
 
         let event_loop = EventLoop::new().unwrap();
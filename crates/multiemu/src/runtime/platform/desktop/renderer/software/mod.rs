@@ -1,60 +1,82 @@
 use crate::{
     component::display::DisplayComponent,
+    config::{DisplayScaling, WindowSizing, GLOBAL_CONFIG},
     gui::software_rasterizer::SoftwareEguiRenderer,
     machine::Machine,
-    runtime::rendering_backend::{
-        DisplayComponentFramebuffer, DisplayComponentInitializationData, RenderingBackendState,
+    runtime::{
+        debug_view::ViewId,
+        rendering_backend::{
+            blit_nearest, display_viewport, DisplayComponentFramebuffer,
+            DisplayComponentInitializationData, FrameBlender, RenderingBackendState,
+        },
     },
 };
 use nalgebra::{DMatrixViewMut, Vector2};
 use palette::Srgba;
 use softbuffer::{Context, Surface};
-use std::{num::NonZero, sync::Arc};
+use std::{collections::HashMap, error::Error, num::NonZero, sync::Arc};
 use winit::window::Window;
 
 pub struct SoftwareRenderingRuntime {
     surface: Surface<Arc<Window>, Arc<Window>>,
     display_api_handle: Arc<Window>,
     egui_renderer: SoftwareEguiRenderer,
+    frame_blender: FrameBlender,
+    views: HashMap<ViewId, SoftwareView>,
 }
 
-impl RenderingBackendState for SoftwareRenderingRuntime {
-    type DisplayApiHandle = Arc<Window>;
+struct SoftwareView {
+    surface: Surface<Arc<Window>, Arc<Window>>,
+    window: Arc<Window>,
+}
 
-    fn new(display_api_handle: Self::DisplayApiHandle) -> Self {
-        let window_dimensions = display_api_handle.inner_size();
-        let window_dimensions = Vector2::new(
-            NonZero::new(window_dimensions.width).unwrap(),
-            NonZero::new(window_dimensions.height).unwrap(),
-        );
+fn create_surface(window: &Arc<Window>) -> Surface<Arc<Window>, Arc<Window>> {
+    let window_dimensions = window.inner_size();
+    let window_dimensions = Vector2::new(
+        NonZero::new(window_dimensions.width).unwrap(),
+        NonZero::new(window_dimensions.height).unwrap(),
+    );
+
+    let context = Context::new(window.clone()).unwrap();
+    let mut surface = Surface::new(&context, window.clone()).unwrap();
+
+    surface
+        .resize(window_dimensions.x, window_dimensions.y)
+        .unwrap();
 
-        let context = Context::new(display_api_handle.clone()).unwrap();
-        let mut surface = Surface::new(&context, display_api_handle.clone()).unwrap();
+    surface
+}
+
+fn resize_surface(surface: &mut Surface<Arc<Window>, Arc<Window>>, window: &Window) {
+    let window_dimensions = window.inner_size();
+
+    // Minimized windows report a size of zero, which softbuffer can't handle
+    if let (Some(width), Some(height)) = (
+        NonZero::new(window_dimensions.width),
+        NonZero::new(window_dimensions.height),
+    ) {
+        surface.resize(width, height).unwrap();
+    }
+}
 
-        surface
-            .resize(window_dimensions.x, window_dimensions.y)
-            .unwrap();
+impl RenderingBackendState for SoftwareRenderingRuntime {
+    type DisplayApiHandle = Arc<Window>;
 
+    fn new(display_api_handle: Self::DisplayApiHandle) -> Self {
         Self {
-            surface,
+            surface: create_surface(&display_api_handle),
             display_api_handle,
             egui_renderer: SoftwareEguiRenderer::default(),
+            frame_blender: FrameBlender::default(),
+            views: HashMap::default(),
         }
     }
 
     fn surface_resized(&mut self) {
-        let window_dimensions = self.display_api_handle.inner_size();
-        let window_dimensions = Vector2::new(window_dimensions.width, window_dimensions.height);
-
-        self.surface
-            .resize(
-                window_dimensions.x.try_into().unwrap(),
-                window_dimensions.y.try_into().unwrap(),
-            )
-            .unwrap();
+        resize_surface(&mut self.surface, &self.display_api_handle);
     }
 
-    fn redraw(&mut self, machine: &Machine) {
+    fn redraw(&mut self, machine: &Machine, overlay: Option<(&egui::Context, egui::FullOutput)>) {
         let window_dimensions = self.display_api_handle.inner_size();
         let window_dimensions =
             Vector2::new(window_dimensions.width, window_dimensions.height).cast::<usize>();
@@ -66,7 +88,9 @@ impl RenderingBackendState for SoftwareRenderingRuntime {
         else {
             unreachable!()
         };
-        let display_component_framebuffer = display_component_framebuffer.lock().unwrap();
+        // Copied out so presentation effects don't touch the components own buffer
+        let mut display_component_framebuffer =
+            display_component_framebuffer.lock().unwrap().clone();
 
         // Skip rendering if impossible window size
         if window_dimensions.min() == 0 {
@@ -86,47 +110,58 @@ impl RenderingBackendState for SoftwareRenderingRuntime {
         let component_display_buffer_size = Vector2::new(
             display_component_framebuffer.nrows(),
             display_component_framebuffer.ncols(),
-        )
-        .cast::<u16>();
-
-        let scaling = window_dimensions
-            .cast::<f32>()
-            .component_div(&component_display_buffer_size.cast::<f32>());
-
-        // Iterate over each pixel in the display component buffer
-        for x in 0..display_component_framebuffer.nrows() {
-            for y in 0..display_component_framebuffer.ncols() {
-                let source_pixel = display_component_framebuffer[(x, y)];
-
-                let dest_start = Vector2::new(x, y)
-                    .cast::<f32>()
-                    .component_mul(&scaling)
-                    .map(f32::round)
-                    .try_cast::<usize>()
-                    .unwrap()
-                    .zip_map(&window_dimensions, |dest_dim, window_dim| {
-                        dest_dim.min(window_dim)
-                    });
-
-                let dest_end = Vector2::new(x, y)
-                    .cast::<f32>()
-                    .add_scalar(1.0)
-                    .component_mul(&scaling)
-                    .map(f32::round)
-                    .try_cast::<usize>()
-                    .unwrap()
-                    .zip_map(&window_dimensions, |dest_dim, window_dim| {
-                        dest_dim.min(window_dim)
-                    });
-
-                // Fill the destination pixels with the source pixel
-                let mut destination_pixels = surface_buffer_view.view_mut(
-                    (dest_start.x, dest_start.y),
-                    (dest_end.x - dest_start.x, dest_end.y - dest_start.y),
-                );
-
-                destination_pixels.fill(source_pixel);
-            }
+        );
+
+        let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+        let (viewport_offset, viewport_size) = display_viewport(
+            window_dimensions.cast(),
+            self.display_api_handle.scale_factor(),
+            component_display_buffer_size.cast(),
+            global_config_guard.display_scaling,
+            global_config_guard.window_sizing,
+        );
+        let color_correction = global_config_guard
+            .color_correction
+            .get(&machine.system)
+            .copied()
+            .unwrap_or_default();
+        let frame_blending = global_config_guard
+            .frame_blending
+            .get(&machine.system)
+            .copied()
+            .unwrap_or_default();
+        let scaler_filter = global_config_guard.scaler_filter;
+        let color_blind_filter = global_config_guard.color_blind_filter;
+        let ntsc_filter = global_config_guard
+            .ntsc_filter
+            .get(&machine.system)
+            .copied()
+            .unwrap_or_default();
+        drop(global_config_guard);
+
+        if frame_blending {
+            self.frame_blender
+                .blend(display_component_framebuffer.as_view_mut());
+        } else {
+            self.frame_blender.reset();
+        }
+        color_correction.correct_framebuffer(display_component_framebuffer.as_view_mut());
+        color_blind_filter.apply_framebuffer(display_component_framebuffer.as_view_mut());
+        ntsc_filter.apply_framebuffer(display_component_framebuffer.as_view_mut());
+        // The viewport was worked out with the original size so scalers don't change the aspect ratio
+        let display_component_framebuffer =
+            scaler_filter.scale(display_component_framebuffer.as_view());
+
+        blit_nearest(
+            display_component_framebuffer.as_view(),
+            surface_buffer_view.as_view_mut(),
+            viewport_offset.cast(),
+            viewport_size.cast(),
+        );
+
+        if let Some((egui_context, full_output)) = overlay {
+            self.egui_renderer
+                .render_overlay(egui_context, surface_buffer_view, full_output);
         }
 
         surface_buffer.present().unwrap();
@@ -156,4 +191,73 @@ impl RenderingBackendState for SoftwareRenderingRuntime {
                 .set_display_data(DisplayComponentInitializationData::Software);
         }
     }
+
+    fn open_view(
+        &mut self,
+        view: ViewId,
+        display_api_handle: Self::DisplayApiHandle,
+    ) -> Result<(), Box<dyn Error>> {
+        self.views.insert(
+            view,
+            SoftwareView {
+                surface: create_surface(&display_api_handle),
+                window: display_api_handle,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn close_view(&mut self, view: ViewId) {
+        self.views.remove(&view);
+    }
+
+    fn view_resized(&mut self, view: ViewId) {
+        if let Some(view) = self.views.get_mut(&view) {
+            resize_surface(&mut view.surface, &view.window);
+        }
+    }
+
+    fn redraw_view(&mut self, view: ViewId, framebuffer: DisplayComponentFramebuffer) {
+        let Some(view) = self.views.get_mut(&view) else {
+            return;
+        };
+        let DisplayComponentFramebuffer::Software(framebuffer) = framebuffer else {
+            unreachable!()
+        };
+        let framebuffer = framebuffer.lock().unwrap();
+
+        let window_dimensions = view.window.inner_size();
+        let window_dimensions = Vector2::new(window_dimensions.width, window_dimensions.height);
+
+        if window_dimensions.min() == 0 {
+            return;
+        }
+
+        let mut surface_buffer = view.surface.buffer_mut().unwrap();
+        let mut surface_buffer_view = DMatrixViewMut::from_slice(
+            bytemuck::cast_slice_mut(surface_buffer.as_mut()),
+            window_dimensions.x as usize,
+            window_dimensions.y as usize,
+        );
+        surface_buffer_view.fill(Srgba::<u8>::new(0, 0, 0, 0xff));
+
+        // Debug views keep their aspect ratio no matter what the main window does
+        let (viewport_offset, viewport_size) = display_viewport(
+            window_dimensions,
+            view.window.scale_factor(),
+            Vector2::new(framebuffer.nrows(), framebuffer.ncols()).cast(),
+            DisplayScaling::Fit,
+            WindowSizing::Physical,
+        );
+
+        blit_nearest(
+            framebuffer.as_view(),
+            surface_buffer_view,
+            viewport_offset.cast(),
+            viewport_size.cast(),
+        );
+
+        surface_buffer.present().unwrap();
+    }
 }
@@ -1,25 +1,46 @@
 use crate::{
     component::display::DisplayComponent,
-    config::GLOBAL_CONFIG,
+    config::{
+        ColorBlindFilter, ColorCorrection, DisplayScaling, NtscFilter, VulkanDevicePreference,
+        WindowSizing, GLOBAL_CONFIG,
+    },
     machine::Machine,
-    runtime::rendering_backend::{
-        DisplayComponentFramebuffer, DisplayComponentInitializationData, RenderingBackendState,
+    runtime::{
+        debug_view::ViewId,
+        rendering_backend::{
+            display_viewport, DisplayComponentFramebuffer, DisplayComponentInitializationData,
+            FrameBlender, RenderingBackendState,
+        },
     },
 };
-use nalgebra::Vector2;
-use std::sync::Arc;
+use nalgebra::{DMatrixViewMut, Vector2};
+use ntsc::NtscSettings;
+use palette::Srgba;
+use std::{collections::HashMap, error::Error, sync::Arc};
 use vulkano::{
+    buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer},
     command_buffer::{
         allocator::StandardCommandBufferAllocator, AutoCommandBufferBuilder, BlitImageInfo,
-        CommandBufferUsage,
+        ClearColorImageInfo, CommandBufferUsage, CopyBufferToImageInfo, CopyImageToBufferInfo,
+        PrimaryAutoCommandBuffer, PrimaryCommandBufferAbstract, RenderPassBeginInfo,
+        SubpassBeginInfo, SubpassContents, SubpassEndInfo,
+    },
+    descriptor_set::{
+        allocator::StandardDescriptorSetAllocator, PersistentDescriptorSet, WriteDescriptorSet,
     },
     device::{
-        physical::PhysicalDeviceType, Device, DeviceCreateInfo, DeviceExtensions, Queue,
-        QueueCreateInfo, QueueFlags,
+        physical::{PhysicalDevice, PhysicalDeviceType},
+        Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
+    },
+    format::{ClearColorValue, Format, NumericFormat},
+    image::{
+        sampler::{Filter, Sampler, SamplerCreateInfo},
+        view::ImageView,
+        Image, ImageCreateInfo, ImageLayout, ImageType, ImageUsage,
     },
-    image::{sampler::Filter, view::ImageView, Image, ImageLayout, ImageUsage},
     instance::{Instance, InstanceCreateFlags, InstanceCreateInfo},
-    memory::allocator::StandardMemoryAllocator,
+    memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator},
+    pipeline::{graphics::viewport::Viewport, GraphicsPipeline, Pipeline, PipelineBindPoint},
     render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass},
     single_pass_renderpass,
     swapchain::{
@@ -31,6 +52,24 @@ use vulkano::{
 };
 use winit::window::Window;
 
+mod ntsc;
+
+/// Staging area for effects we apply to display component output on the cpu
+struct HostProcessingState {
+    buffer: Subbuffer<[Srgba<u8>]>,
+    image: Arc<Image>,
+}
+
+/// An extra window with its own swapchain
+struct VulkanView {
+    window: Arc<Window>,
+    swapchain: Arc<Swapchain>,
+    swapchain_images: Vec<Arc<Image>>,
+    recreate_swapchain: bool,
+    /// Where framebuffers that live on the host are uploaded to before being blit
+    upload: Option<HostProcessingState>,
+}
+
 pub struct VulkanRenderingRuntime {
     instance: Arc<Instance>,
     surface: Arc<Surface>,
@@ -46,6 +85,13 @@ pub struct VulkanRenderingRuntime {
     swapchain_images: Vec<Arc<Image>>,
     recreate_swapchain: bool,
     display_api_handle: Arc<Window>,
+    host_processing: Option<HostProcessingState>,
+    frame_blender: FrameBlender,
+    views: HashMap<ViewId, VulkanView>,
+    ntsc_pipeline: Arc<GraphicsPipeline>,
+    /// Texel fetches ignore filtering, but a sampler is still needed to bind the image
+    ntsc_sampler: Arc<Sampler>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
 }
 
 impl RenderingBackendState for VulkanRenderingRuntime {
@@ -76,48 +122,25 @@ impl RenderingBackendState for VulkanRenderingRuntime {
             khr_swapchain: true,
             ..DeviceExtensions::empty()
         };
-        let (physical_device, queue_family_index) = instance
-            .enumerate_physical_devices()
-            .unwrap()
-            .filter(|p| p.supported_extensions().contains(&device_extensions))
-            .filter_map(|p| {
-                p.queue_family_properties()
-                    .iter()
-                    .enumerate()
-                    .position(|(i, q)| {
-                        q.queue_flags.intersects(QueueFlags::GRAPHICS)
-                            && p.surface_support(i as u32, &surface).unwrap_or(false)
-                    })
-                    .map(|i| (p, i as u32))
-            })
-            .min_by_key(|(p, _)| match p.properties().device_type {
-                PhysicalDeviceType::DiscreteGpu => 0,
-                PhysicalDeviceType::IntegratedGpu => 1,
-                PhysicalDeviceType::VirtualGpu => 2,
-                PhysicalDeviceType::Cpu => 3,
-                PhysicalDeviceType::Other => 4,
-                _ => 5,
-            })
-            .unwrap();
+        // The probe had no surface to check against, so the device it liked may not be usable here
+        let (device, queues) = select_physical_devices(
+            &instance,
+            &device_extensions,
+            &global_config_guard.vulkan_device,
+            Some(&surface),
+        )
+        .into_iter()
+        .find_map(|(physical_device, queue_family_index)| {
+            create_device(physical_device, queue_family_index, device_extensions)
+        })
+        .expect("No usable vulkan device found");
 
         tracing::info!(
             "Using device: {} (type: {:?})",
-            physical_device.properties().device_name,
-            physical_device.properties().device_type,
+            device.physical_device().properties().device_name,
+            device.physical_device().properties().device_type,
         );
 
-        let (device, queues) = Device::new(
-            physical_device,
-            DeviceCreateInfo {
-                enabled_extensions: device_extensions,
-                queue_create_infos: vec![QueueCreateInfo {
-                    queue_family_index,
-                    ..Default::default()
-                }],
-                ..Default::default()
-            },
-        )
-        .unwrap();
         let queues: Vec<_> = queues.collect();
 
         tracing::info!("Using {} queue(s)", queues.len());
@@ -129,40 +152,12 @@ impl RenderingBackendState for VulkanRenderingRuntime {
             (gui_queue.clone(), queues.to_vec())
         };
 
-        let (swapchain, swapchain_images) = {
-            let surface_capabilities = device
-                .physical_device()
-                .surface_capabilities(&surface, Default::default())
-                .unwrap();
-            let image_format = device
-                .physical_device()
-                .surface_formats(&surface, Default::default())
-                .unwrap()[0]
-                .0;
-
-            Swapchain::new(
-                device.clone(),
-                surface.clone(),
-                SwapchainCreateInfo {
-                    min_image_count: surface_capabilities.min_image_count.max(2),
-                    image_format,
-                    image_extent: window_dimensions.into(),
-                    image_usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
-                    composite_alpha: surface_capabilities
-                        .supported_composite_alpha
-                        .into_iter()
-                        .next()
-                        .unwrap(),
-                    present_mode: if global_config_guard.vsync {
-                        PresentMode::Fifo
-                    } else {
-                        PresentMode::Immediate
-                    },
-                    ..Default::default()
-                },
-            )
-            .unwrap()
-        };
+        let (swapchain, swapchain_images) = create_swapchain(
+            &device,
+            &surface,
+            window_dimensions,
+            global_config_guard.vsync,
+        );
         let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(device.clone()));
         let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
             device.clone(),
@@ -202,6 +197,11 @@ impl RenderingBackendState for VulkanRenderingRuntime {
             })
             .collect();
 
+        let ntsc_pipeline = ntsc::create_pipeline(&device, &render_pass);
+        let ntsc_sampler = Sampler::new(device.clone(), SamplerCreateInfo::default()).unwrap();
+        let descriptor_set_allocator =
+            StandardDescriptorSetAllocator::new(device.clone(), Default::default());
+
         drop(global_config_guard);
 
         Self {
@@ -219,6 +219,12 @@ impl RenderingBackendState for VulkanRenderingRuntime {
             swapchain_images,
             recreate_swapchain: false,
             display_api_handle,
+            host_processing: None,
+            frame_blender: FrameBlender::default(),
+            views: HashMap::default(),
+            ntsc_pipeline,
+            ntsc_sampler,
+            descriptor_set_allocator,
         }
     }
 
@@ -226,7 +232,8 @@ impl RenderingBackendState for VulkanRenderingRuntime {
         self.recreate_swapchain = true;
     }
 
-    fn redraw(&mut self, machine: &Machine) {
+    // TODO: Draw the overlay once this backend can draw egui at all, see redraw_menu
+    fn redraw(&mut self, machine: &Machine, _overlay: Option<(&egui::Context, egui::FullOutput)>) {
         let window_dimensions = self.display_api_handle.inner_size();
         let window_dimensions = Vector2::new(window_dimensions.width, window_dimensions.height);
 
@@ -301,14 +308,79 @@ impl RenderingBackendState for VulkanRenderingRuntime {
         )
         .unwrap();
 
-        command_buffer
-            .blit_image(BlitImageInfo {
+        let color_correction = global_config_guard
+            .color_correction
+            .get(&machine.system)
+            .copied()
+            .unwrap_or_default();
+        let frame_blending = global_config_guard
+            .frame_blending
+            .get(&machine.system)
+            .copied()
+            .unwrap_or_default();
+        let color_blind_filter = global_config_guard.color_blind_filter;
+        let ntsc_filter = global_config_guard
+            .ntsc_filter
+            .get(&machine.system)
+            .copied()
+            .unwrap_or_default();
+        let component_framebuffer = if color_correction.is_identity()
+            && color_blind_filter.is_identity()
+            && !frame_blending
+        {
+            self.frame_blender.reset();
+            component_framebuffer
+        } else {
+            self.process_on_host(
+                component_framebuffer,
+                color_correction,
+                color_blind_filter,
+                frame_blending,
+            )
+        };
+
+        let framebuffer_extent = component_framebuffer.extent();
+        let (viewport_offset, viewport_size) = display_viewport(
+            window_dimensions,
+            self.display_api_handle.scale_factor(),
+            Vector2::new(framebuffer_extent[0], framebuffer_extent[1]),
+            global_config_guard.display_scaling,
+            global_config_guard.window_sizing,
+        );
+        if !ntsc_filter.is_identity() {
+            // Drawn through a shader instead of blit, the render pass clears around the viewport
+            self.draw_ntsc(
+                &mut command_buffer,
+                component_framebuffer,
+                image_index,
+                viewport_offset,
+                viewport_size,
+                ntsc_filter,
+            );
+        } else {
+            let viewport_end = viewport_offset + viewport_size;
+
+            let mut blit_image_info = BlitImageInfo {
                 src_image_layout: ImageLayout::TransferSrcOptimal,
                 dst_image_layout: ImageLayout::TransferDstOptimal,
                 filter: Filter::Nearest,
                 ..BlitImageInfo::images(component_framebuffer, swapchain_image.clone())
-            })
-            .unwrap();
+            };
+            blit_image_info.regions[0].dst_offsets = [
+                [viewport_offset.x, viewport_offset.y, 0],
+                [viewport_end.x, viewport_end.y, 1],
+            ];
+
+            command_buffer
+                // Clear out whatever the viewport doesn't cover
+                .clear_color_image(ClearColorImageInfo {
+                    clear_value: ClearColorValue::Float([0.0, 0.0, 0.0, 1.0]),
+                    ..ClearColorImageInfo::image(swapchain_image.clone())
+                })
+                .unwrap()
+                .blit_image(blit_image_info)
+                .unwrap();
+        }
 
         let command_buffer = command_buffer.build().unwrap();
 
@@ -338,8 +410,166 @@ impl RenderingBackendState for VulkanRenderingRuntime {
         }
     }
 
+    fn device_name(&self) -> Option<String> {
+        Some(
+            self.device
+                .physical_device()
+                .properties()
+                .device_name
+                .clone(),
+        )
+    }
+
     fn redraw_menu(&mut self, _egui_context: &egui::Context, _full_output: egui::FullOutput) {}
 
+    fn open_view(
+        &mut self,
+        view: ViewId,
+        display_api_handle: Self::DisplayApiHandle,
+    ) -> Result<(), Box<dyn Error>> {
+        let surface = Surface::from_window(self.instance.clone(), display_api_handle.clone())?;
+
+        if !self
+            .device
+            .physical_device()
+            .surface_support(self.gui_queue.queue_family_index(), &surface)
+            .unwrap_or(false)
+        {
+            return Err("Vulkan device cannot present to the new window".into());
+        }
+
+        let window_dimensions = display_api_handle.inner_size();
+        let (swapchain, swapchain_images) = create_swapchain(
+            &self.device,
+            &surface,
+            Vector2::new(window_dimensions.width, window_dimensions.height),
+            GLOBAL_CONFIG.read().unwrap().vsync,
+        );
+
+        self.views.insert(
+            view,
+            VulkanView {
+                window: display_api_handle,
+                swapchain,
+                swapchain_images,
+                recreate_swapchain: false,
+                upload: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn close_view(&mut self, view: ViewId) {
+        self.views.remove(&view);
+    }
+
+    fn view_resized(&mut self, view: ViewId) {
+        if let Some(view) = self.views.get_mut(&view) {
+            view.recreate_swapchain = true;
+        }
+    }
+
+    fn redraw_view(&mut self, view: ViewId, framebuffer: DisplayComponentFramebuffer) {
+        let framebuffer = match framebuffer {
+            DisplayComponentFramebuffer::Vulkan(image) => image,
+            DisplayComponentFramebuffer::Software(framebuffer) => {
+                let framebuffer = framebuffer.lock().unwrap();
+                self.upload_to_view(view, framebuffer.as_slice(), framebuffer.shape())
+            }
+        };
+        let Some(view) = self.views.get_mut(&view) else {
+            return;
+        };
+
+        let window_dimensions = view.window.inner_size();
+        let window_dimensions = Vector2::new(window_dimensions.width, window_dimensions.height);
+
+        if window_dimensions.min() == 0 {
+            return;
+        }
+
+        if view.recreate_swapchain {
+            let (new_swapchain, new_images) = view
+                .swapchain
+                .recreate(SwapchainCreateInfo {
+                    image_extent: window_dimensions.into(),
+                    ..view.swapchain.create_info()
+                })
+                .expect("Failed to recreate swapchain");
+
+            view.swapchain = new_swapchain;
+            view.swapchain_images = new_images;
+            view.recreate_swapchain = false;
+        }
+
+        let (image_index, recreate_swapchain, acquire_future) =
+            match acquire_next_image(view.swapchain.clone(), None).map_err(Validated::unwrap) {
+                Ok(acquired) => acquired,
+                Err(VulkanError::OutOfDate) => {
+                    view.recreate_swapchain = true;
+                    return;
+                }
+                Err(_) => panic!("Failed to acquire next image"),
+            };
+        view.recreate_swapchain |= recreate_swapchain;
+        let swapchain_image = view.swapchain_images[image_index as usize].clone();
+
+        // Debug views keep their aspect ratio no matter what the main window does
+        let framebuffer_extent = framebuffer.extent();
+        let (viewport_offset, viewport_size) = display_viewport(
+            window_dimensions,
+            view.window.scale_factor(),
+            Vector2::new(framebuffer_extent[0], framebuffer_extent[1]),
+            DisplayScaling::Fit,
+            WindowSizing::Physical,
+        );
+        let viewport_end = viewport_offset + viewport_size;
+
+        let mut blit_image_info = BlitImageInfo {
+            src_image_layout: ImageLayout::TransferSrcOptimal,
+            dst_image_layout: ImageLayout::TransferDstOptimal,
+            filter: Filter::Nearest,
+            ..BlitImageInfo::images(framebuffer, swapchain_image.clone())
+        };
+        blit_image_info.regions[0].dst_offsets = [
+            [viewport_offset.x, viewport_offset.y, 0],
+            [viewport_end.x, viewport_end.y, 1],
+        ];
+
+        let mut command_buffer = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.gui_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        command_buffer
+            .clear_color_image(ClearColorImageInfo {
+                clear_value: ClearColorValue::Float([0.0, 0.0, 0.0, 1.0]),
+                ..ClearColorImageInfo::image(swapchain_image)
+            })
+            .unwrap()
+            .blit_image(blit_image_info)
+            .unwrap();
+        let command_buffer = command_buffer.build().unwrap();
+
+        // Views are small and rare so just wait on them instead of juggling another future
+        match acquire_future
+            .then_execute(self.gui_queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(
+                self.gui_queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(view.swapchain.clone(), image_index),
+            )
+            .then_signal_fence_and_flush()
+            .map_err(Validated::unwrap)
+        {
+            Ok(future) => future.wait(None).unwrap(),
+            Err(VulkanError::OutOfDate) => view.recreate_swapchain = true,
+            Err(_) => panic!("Failed to present swapchain image"),
+        }
+    }
+
     fn initialize_machine(&mut self, machine: &Machine) {
         for (component_info, queue) in machine
             .display_components()
@@ -359,6 +589,428 @@ impl RenderingBackendState for VulkanRenderingRuntime {
     }
 }
 
+impl VulkanRenderingRuntime {
+    /// Round trips the image through host memory to apply presentation effects
+    ///
+    /// Emulated displays are tiny so this is cheaper than it sounds
+    fn process_on_host(
+        &mut self,
+        component_framebuffer: Arc<Image>,
+        color_correction: ColorCorrection,
+        color_blind_filter: ColorBlindFilter,
+        frame_blending: bool,
+    ) -> Arc<Image> {
+        let extent = component_framebuffer.extent();
+
+        if self
+            .host_processing
+            .as_ref()
+            .is_none_or(|state| state.image.extent() != extent)
+        {
+            let buffer = Buffer::new_slice(
+                self.memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                    ..Default::default()
+                },
+                extent[0] as u64 * extent[1] as u64,
+            )
+            .unwrap();
+
+            let image = Image::new(
+                self.memory_allocator.clone(),
+                ImageCreateInfo {
+                    image_type: ImageType::Dim2d,
+                    format: component_framebuffer.format(),
+                    extent,
+                    usage: ImageUsage::TRANSFER_SRC
+                        | ImageUsage::TRANSFER_DST
+                        | ImageUsage::SAMPLED,
+                    ..Default::default()
+                },
+                AllocationCreateInfo::default(),
+            )
+            .unwrap();
+
+            self.host_processing = Some(HostProcessingState { buffer, image });
+        }
+        let state = self.host_processing.as_ref().unwrap();
+
+        let mut command_buffer = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.gui_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        command_buffer
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                component_framebuffer,
+                state.buffer.clone(),
+            ))
+            .unwrap();
+        command_buffer
+            .build()
+            .unwrap()
+            .execute(self.gui_queue.clone())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        {
+            let mut buffer = state.buffer.write().unwrap();
+            let mut framebuffer =
+                DMatrixViewMut::from_slice(&mut buffer, extent[0] as usize, extent[1] as usize);
+
+            if frame_blending {
+                self.frame_blender.blend(framebuffer.as_view_mut());
+            } else {
+                self.frame_blender.reset();
+            }
+            color_correction.correct_framebuffer(framebuffer.as_view_mut());
+            color_blind_filter.apply_framebuffer(framebuffer);
+        }
+
+        let mut command_buffer = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.gui_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        command_buffer
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+                state.buffer.clone(),
+                state.image.clone(),
+            ))
+            .unwrap();
+        command_buffer
+            .build()
+            .unwrap()
+            .execute(self.gui_queue.clone())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        state.image.clone()
+    }
+
+    /// Records drawing a framebuffer into the swapchain image through the ntsc shader
+    fn draw_ntsc(
+        &self,
+        command_buffer: &mut AutoCommandBufferBuilder<
+            PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>,
+            Arc<StandardCommandBufferAllocator>,
+        >,
+        component_framebuffer: Arc<Image>,
+        image_index: u32,
+        viewport_offset: Vector2<u32>,
+        viewport_size: Vector2<u32>,
+        ntsc_filter: NtscFilter,
+    ) {
+        let layout = self.ntsc_pipeline.layout().clone();
+        let descriptor_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            layout.set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                ImageView::new_default(component_framebuffer).unwrap(),
+                self.ntsc_sampler.clone(),
+            )],
+            [],
+        )
+        .unwrap();
+        let encode_output =
+            self.swapchain.image_format().numeric_format_color() != Some(NumericFormat::SRGB);
+
+        command_buffer
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 0.0, 1.0].into())],
+                    ..RenderPassBeginInfo::framebuffer(
+                        self.framebuffers[image_index as usize].clone(),
+                    )
+                },
+                SubpassBeginInfo {
+                    contents: SubpassContents::Inline,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .set_viewport(
+                0,
+                [Viewport {
+                    offset: viewport_offset.cast::<f32>().into(),
+                    extent: viewport_size.cast::<f32>().into(),
+                    depth_range: 0.0..=1.0,
+                }]
+                .into_iter()
+                .collect(),
+            )
+            .unwrap()
+            .bind_pipeline_graphics(self.ntsc_pipeline.clone())
+            .unwrap()
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                layout.clone(),
+                0,
+                descriptor_set,
+            )
+            .unwrap()
+            .push_constants(layout, 0, NtscSettings::new(ntsc_filter, encode_output))
+            .unwrap()
+            .draw(3, 1, 0, 0)
+            .unwrap()
+            .end_render_pass(SubpassEndInfo::default())
+            .unwrap();
+    }
+
+    /// Copies a host side framebuffer into an image a view can blit from
+    fn upload_to_view(
+        &mut self,
+        view: ViewId,
+        framebuffer: &[Srgba<u8>],
+        (width, height): (usize, usize),
+    ) -> Arc<Image> {
+        let extent = [width as u32, height as u32, 1];
+        let view = self.views.get_mut(&view).expect("View was not opened");
+
+        if view
+            .upload
+            .as_ref()
+            .is_none_or(|state| state.image.extent() != extent)
+        {
+            let buffer = Buffer::new_slice(
+                self.memory_allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::TRANSFER_SRC,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                        | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                    ..Default::default()
+                },
+                (width * height) as u64,
+            )
+            .unwrap();
+
+            let image = Image::new(
+                self.memory_allocator.clone(),
+                ImageCreateInfo {
+                    image_type: ImageType::Dim2d,
+                    format: Format::R8G8B8A8_SRGB,
+                    extent,
+                    usage: ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST,
+                    ..Default::default()
+                },
+                AllocationCreateInfo::default(),
+            )
+            .unwrap();
+
+            view.upload = Some(HostProcessingState { buffer, image });
+        }
+        let state = view.upload.as_ref().unwrap();
+
+        state.buffer.write().unwrap().copy_from_slice(framebuffer);
+
+        let mut command_buffer = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.gui_queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        command_buffer
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(
+                state.buffer.clone(),
+                state.image.clone(),
+            ))
+            .unwrap();
+        command_buffer
+            .build()
+            .unwrap()
+            .execute(self.gui_queue.clone())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        state.image.clone()
+    }
+
+    /// Attempts to create a device according to the users preference without a window
+    ///
+    /// Used to decide if we should fall back to the software renderer before committing to vulkan
+    pub fn probe(preference: &VulkanDevicePreference) -> Result<String, Box<dyn Error>> {
+        let library = VulkanLibrary::new()?;
+        let instance = Instance::new(
+            library,
+            InstanceCreateInfo {
+                flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+                ..Default::default()
+            },
+        )?;
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (device, _) = select_physical_devices(&instance, &device_extensions, preference, None)
+            .into_iter()
+            .find_map(|(physical_device, queue_family_index)| {
+                create_device(physical_device, queue_family_index, device_extensions)
+            })
+            .ok_or("No usable vulkan device found")?;
+
+        Ok(device.physical_device().properties().device_name.clone())
+    }
+}
+
+/// Creates a device with one queue family, None if the driver refuses so the next candidate can be tried
+fn create_device(
+    physical_device: Arc<PhysicalDevice>,
+    queue_family_index: u32,
+    device_extensions: DeviceExtensions,
+) -> Option<(Arc<Device>, impl ExactSizeIterator<Item = Arc<Queue>>)> {
+    let device_name = physical_device.properties().device_name.clone();
+
+    Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            enabled_extensions: device_extensions,
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    )
+    .inspect_err(|error| {
+        tracing::warn!(
+            "Could not create a device on {} ({}), trying the next one",
+            device_name,
+            error
+        );
+    })
+    .ok()
+}
+
+fn create_swapchain(
+    device: &Arc<Device>,
+    surface: &Arc<Surface>,
+    window_dimensions: Vector2<u32>,
+    vsync: bool,
+) -> (Arc<Swapchain>, Vec<Arc<Image>>) {
+    let surface_capabilities = device
+        .physical_device()
+        .surface_capabilities(surface, Default::default())
+        .unwrap();
+    let image_format = device
+        .physical_device()
+        .surface_formats(surface, Default::default())
+        .unwrap()[0]
+        .0;
+
+    Swapchain::new(
+        device.clone(),
+        surface.clone(),
+        SwapchainCreateInfo {
+            min_image_count: surface_capabilities.min_image_count.max(2),
+            image_format,
+            image_extent: window_dimensions.into(),
+            image_usage: ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_DST,
+            composite_alpha: surface_capabilities
+                .supported_composite_alpha
+                .into_iter()
+                .next()
+                .unwrap(),
+            present_mode: if vsync {
+                PresentMode::Fifo
+            } else {
+                PresentMode::Immediate
+            },
+            ..Default::default()
+        },
+    )
+    .unwrap()
+}
+
+/// Every physical device with a graphics capable queue family, best match for the preference first
+///
+/// If a surface is given the queue family must also be able to present to it
+fn select_physical_devices(
+    instance: &Arc<Instance>,
+    device_extensions: &DeviceExtensions,
+    preference: &VulkanDevicePreference,
+    surface: Option<&Surface>,
+) -> Vec<(Arc<PhysicalDevice>, u32)> {
+    let Ok(physical_devices) = instance.enumerate_physical_devices() else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<_> = physical_devices
+        .filter(|p| p.supported_extensions().contains(device_extensions))
+        .filter_map(|p| {
+            p.queue_family_properties()
+                .iter()
+                .enumerate()
+                .position(|(i, q)| {
+                    q.queue_flags.intersects(QueueFlags::GRAPHICS)
+                        && surface.is_none_or(|surface| {
+                            p.surface_support(i as u32, surface).unwrap_or(false)
+                        })
+                })
+                .map(|i| (p, i as u32))
+        })
+        .collect();
+
+    for (physical_device, _) in candidates.iter() {
+        tracing::debug!(
+            "Found vulkan device candidate: {} (type: {:?})",
+            physical_device.properties().device_name,
+            physical_device.properties().device_type,
+        );
+    }
+
+    // Stable, so devices of the same kind keep the order the driver listed them in
+    candidates.sort_by_key(|(physical_device, _)| {
+        match (preference, physical_device.properties().device_type) {
+            (VulkanDevicePreference::PreferIntegrated, PhysicalDeviceType::IntegratedGpu) => 0,
+            (VulkanDevicePreference::PreferIntegrated, PhysicalDeviceType::DiscreteGpu) => 1,
+            (_, PhysicalDeviceType::DiscreteGpu) => 0,
+            (_, PhysicalDeviceType::IntegratedGpu) => 1,
+            (_, PhysicalDeviceType::VirtualGpu) => 2,
+            (_, PhysicalDeviceType::Cpu) => 3,
+            (_, PhysicalDeviceType::Other) => 4,
+            _ => 5,
+        }
+    });
+
+    if let VulkanDevicePreference::Named(name) = preference {
+        match candidates
+            .iter()
+            .position(|(physical_device, _)| physical_device.properties().device_name == *name)
+        {
+            Some(index) => {
+                let candidate = candidates.remove(index);
+                candidates.insert(0, candidate);
+            }
+            None => tracing::warn!(
+                "Could not find vulkan device named \"{}\", falling back to automatic selection",
+                name
+            ),
+        }
+    }
+
+    candidates
+}
+
 pub struct VulkanDisplayComponentInitializationData {
     pub device: Arc<Device>,
     pub queue: Arc<Queue>,
@@ -0,0 +1,219 @@
+use crate::config::NtscFilter;
+use std::sync::Arc;
+use vulkano::{
+    device::Device,
+    pipeline::{
+        graphics::{
+            color_blend::{ColorBlendAttachmentState, ColorBlendState},
+            input_assembly::InputAssemblyState,
+            multisample::MultisampleState,
+            rasterization::RasterizationState,
+            vertex_input::VertexInputState,
+            viewport::ViewportState,
+            GraphicsPipelineCreateInfo,
+        },
+        layout::PipelineDescriptorSetLayoutCreateInfo,
+        DynamicState, GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo,
+    },
+    render_pass::{RenderPass, Subpass},
+};
+
+mod vertex_shader {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 450
+
+            layout(location = 0) out vec2 position;
+
+            void main() {
+                // One triangle covering the whole viewport
+                vec2 corner = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+
+                position = corner;
+                gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+// Mirrors the software filter in runtime/ntsc.rs, changes to one belong in the other
+mod fragment_shader {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        src: r"
+            #version 450
+
+            layout(location = 0) in vec2 position;
+            layout(location = 0) out vec4 color;
+
+            layout(set = 0, binding = 0) uniform sampler2D source;
+
+            layout(push_constant) uniform Settings {
+                // 1 for composite, 2 for s-video
+                uint mode;
+                // Set when the attachment doesn't do the srgb conversion itself
+                uint encode_output;
+            } settings;
+
+            const int SAMPLES_PER_PIXEL = 2;
+            const int CARRIER_PERIOD = 4;
+            const int CHROMA_CYCLES = 3;
+
+            // Column major, so these read transposed
+            const mat3 RGB_TO_YIQ = mat3(0.299, 0.596, 0.211, 0.587, -0.274, -0.523, 0.114, -0.322, 0.312);
+            const mat3 YIQ_TO_RGB = mat3(1.0, 1.0, 1.0, 0.956, -0.272, -1.106, 0.621, -0.647, 1.703);
+
+            int line;
+            int signal_length;
+            int phase_offset;
+
+            vec3 to_gamma(vec3 linear) {
+                return mix(linear * 12.92, 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, linear));
+            }
+
+            vec3 to_linear(vec3 gamma) {
+                return mix(gamma / 12.92, pow((gamma + 0.055) / 1.055, vec3(2.4)), step(0.04045, gamma));
+            }
+
+            vec2 carrier(int phase) {
+                switch (phase % CARRIER_PERIOD) {
+                    case 0: return vec2(1.0, 0.0);
+                    case 1: return vec2(0.0, 1.0);
+                    case 2: return vec2(-1.0, 0.0);
+                    default: return vec2(0.0, -1.0);
+                }
+            }
+
+            int wrap(int sample_index) {
+                while (sample_index < 0) {
+                    sample_index += CARRIER_PERIOD;
+                }
+                while (sample_index >= signal_length) {
+                    sample_index -= CARRIER_PERIOD;
+                }
+
+                return max(sample_index, 0);
+            }
+
+            vec3 yiq_at(int sample_index) {
+                // The sampled image is srgb, so this undoes the decoding the hardware did
+                vec3 rgb = texelFetch(source, ivec2(sample_index / SAMPLES_PER_PIXEL, line), 0).rgb;
+                return RGB_TO_YIQ * to_gamma(rgb);
+            }
+
+            float chroma_at(int sample_index) {
+                vec3 yiq = yiq_at(sample_index);
+                vec2 phase = carrier(sample_index + phase_offset);
+
+                return yiq.y * phase.x + yiq.z * phase.y;
+            }
+
+            float signal_at(int sample_index) {
+                return yiq_at(sample_index).x + chroma_at(sample_index);
+            }
+
+            void main() {
+                ivec2 size = textureSize(source, 0);
+                ivec2 pixel = min(ivec2(position * vec2(size)), size - 1);
+
+                line = pixel.y;
+                signal_length = size.x * SAMPLES_PER_PIXEL;
+                phase_offset = line % 2 * (CARRIER_PERIOD / 2);
+
+                int center = pixel.x * SAMPLES_PER_PIXEL + SAMPLES_PER_PIXEL / 2;
+                bool composite = settings.mode == 1;
+
+                float luma = 0.0;
+                if (composite) {
+                    for (int i = 0; i < CARRIER_PERIOD; i++) {
+                        luma += signal_at(wrap(center - CARRIER_PERIOD / 2 + i));
+                    }
+                    luma /= float(CARRIER_PERIOD);
+                } else {
+                    luma = yiq_at(center - 1).x;
+                }
+
+                int chroma_window = CARRIER_PERIOD * CHROMA_CYCLES;
+                vec2 iq = vec2(0.0);
+                for (int i = 0; i < chroma_window; i++) {
+                    int sample_index = wrap(center - chroma_window / 2 + i);
+                    float signal = composite ? signal_at(sample_index) : chroma_at(sample_index);
+
+                    iq += signal * carrier(sample_index + phase_offset);
+                }
+                iq *= 2.0 / float(chroma_window);
+
+                vec3 rgb = clamp(YIQ_TO_RGB * vec3(luma, iq), 0.0, 1.0);
+                float alpha = texelFetch(source, pixel, 0).a;
+
+                color = vec4(settings.encode_output != 0 ? rgb : to_linear(rgb), alpha);
+            }
+        ",
+    }
+}
+
+pub use fragment_shader::Settings as NtscSettings;
+
+impl NtscSettings {
+    pub fn new(filter: NtscFilter, encode_output: bool) -> Self {
+        Self {
+            mode: match filter {
+                NtscFilter::None => 0,
+                NtscFilter::Composite => 1,
+                NtscFilter::SVideo => 2,
+            },
+            encode_output: encode_output as u32,
+        }
+    }
+}
+
+/// Pipeline drawing the emulated display through the filter into the first subpass of the render pass
+pub fn create_pipeline(
+    device: &Arc<Device>,
+    render_pass: &Arc<RenderPass>,
+) -> Arc<GraphicsPipeline> {
+    let vertex_shader = vertex_shader::load(device.clone())
+        .unwrap()
+        .entry_point("main")
+        .unwrap();
+    let fragment_shader = fragment_shader::load(device.clone())
+        .unwrap()
+        .entry_point("main")
+        .unwrap();
+
+    let stages = [
+        PipelineShaderStageCreateInfo::new(vertex_shader),
+        PipelineShaderStageCreateInfo::new(fragment_shader),
+    ];
+    let layout = PipelineLayout::new(
+        device.clone(),
+        PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+            .into_pipeline_layout_create_info(device.clone())
+            .unwrap(),
+    )
+    .unwrap();
+    let subpass = Subpass::from(render_pass.clone(), 0).unwrap();
+
+    GraphicsPipeline::new(
+        device.clone(),
+        None,
+        GraphicsPipelineCreateInfo {
+            stages: stages.into_iter().collect(),
+            vertex_input_state: Some(VertexInputState::default()),
+            input_assembly_state: Some(InputAssemblyState::default()),
+            viewport_state: Some(ViewportState::default()),
+            rasterization_state: Some(RasterizationState::default()),
+            multisample_state: Some(MultisampleState::default()),
+            color_blend_state: Some(ColorBlendState::with_attachment_states(
+                subpass.num_color_attachments(),
+                ColorBlendAttachmentState::default(),
+            )),
+            // The viewport follows the window and display scaling
+            dynamic_state: [DynamicState::Viewport].into_iter().collect(),
+            subpass: Some(subpass.into()),
+            ..GraphicsPipelineCreateInfo::layout(layout)
+        },
+    )
+    .unwrap()
+}
@@ -1,23 +1,49 @@
-use super::PlatformRuntime;
+use super::{audio::AudioOutput, fullscreen::toggle_fullscreen, PlatformRuntime};
 use crate::{
-    config::GLOBAL_CONFIG,
-    definitions::chip8::chip8_machine,
-    gui::menu::UiOutput,
-    input::{GamepadId, InputState},
+    config::{WindowGeometry, GLOBAL_CONFIG, STORAGE_DIRECTORY},
+    definitions::chip8::assembler::{assemble_into_store, is_octo_source, OctoLoadError},
+    gui::{
+        accessibility,
+        menu::{HostDeviceAssignment, MenuState, UiOutput},
+        navigation::UiNavigation,
+        pause_menu::PauseMenuItem,
+        progress,
+    },
+    input::{
+        hotkey::Hotkey, manager::InputManager, paste::TextPaste, pointer::PointerInput, GamepadId,
+        Input, InputState,
+    },
+    logging,
     machine::Machine,
-    rom::{
-        id::RomId,
-        info::RomInfo,
-        system::{GameSystem, OtherSystem},
+    rom::{id::RomId, info::RomInfo, system::GameSystem},
+    runtime::{
+        av_sync::AV_SYNC,
+        debug_view::{DebugView, ViewId},
+        gdb::GdbStub,
+        livesplit,
+        progress::PROGRESS,
+        rendering_backend::{window_to_display, RenderingBackendState},
+        rewind::RewindBuffer,
+        video_sink::{FfmpegSink, ScreenshotSink},
     },
-    runtime::rendering_backend::RenderingBackendState,
+    save::{archive::write_machine_archive, manager::SaveManager, snapshot::SnapshotStore},
+    scheduler::watchdog::Watchdog,
 };
 use indexmap::IndexMap;
-use std::{fs::File, sync::Arc, time::{Duration, Instant}};
+use nalgebra::Vector2;
+use std::{
+    collections::HashMap,
+    fs::File,
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
-    event_loop::ActiveEventLoop,
+    dpi::{PhysicalPosition, PhysicalSize},
+    event::{MouseButton, StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow},
     keyboard::PhysicalKey,
     window::{Window, WindowId},
 };
@@ -25,6 +51,66 @@ use winit::{
 // FIXME: Duplicated hack code is present here
 
 const KEYBOARD_GAMEPAD_ID: GamepadId = 0;
+/// Not wired to anything by default, the user points it at a light gun or similar from the input menu
+const MOUSE_GAMEPAD_ID: GamepadId = 1;
+
+/// Opens the debugger port for the running machine, if the config asks for one
+fn start_gdb(machine: &Machine) -> Option<GdbStub> {
+    let port = GLOBAL_CONFIG.read().unwrap().gdb_port?;
+
+    GdbStub::new(port, machine).unwrap_or_else(|error| {
+        tracing::error!(
+            "Could not listen for a debugger on port {}: {}",
+            port,
+            error
+        );
+        None
+    })
+}
+
+/// Fills in the input menu from the running machine
+fn refresh_input_menu(menu: &mut MenuState, input_manager: &InputManager) {
+    menu.emulated_gamepads = input_manager.emulated_gamepads();
+    menu.host_devices = [
+        (KEYBOARD_GAMEPAD_ID, "Keyboard".to_string()),
+        (MOUSE_GAMEPAD_ID, "Mouse".to_string()),
+    ]
+    .into_iter()
+    .chain(input_manager.host_devices())
+    .map(|(id, name)| HostDeviceAssignment {
+        id,
+        name,
+        port: input_manager.real_to_emulated_mapping(id),
+    })
+    .collect();
+}
+
+/// Sends where the mouse is over the display, None if it isn't over it
+fn pointer_moved(machine: &Machine, position: Option<Vector2<f32>>) {
+    let input_manager = &machine.input_manager;
+
+    input_manager.insert_input(
+        machine.system,
+        MOUSE_GAMEPAD_ID,
+        Input::Pointer(PointerInput::OnScreen),
+        InputState::Digital(position.is_some()),
+    );
+
+    if let Some(position) = position {
+        input_manager.insert_input(
+            machine.system,
+            MOUSE_GAMEPAD_ID,
+            Input::Pointer(PointerInput::X),
+            InputState::Analog(position.x),
+        );
+        input_manager.insert_input(
+            machine.system,
+            MOUSE_GAMEPAD_ID,
+            Input::Pointer(PointerInput::Y),
+            InputState::Analog(position.y),
+        );
+    }
+}
 
 pub enum MachineContext {
     /// Machine is waiting for graphics context to be ready
@@ -40,6 +126,76 @@ pub struct WindowingContext<RS: RenderingBackendState> {
     window: Arc<Window>,
     egui_winit_context: egui_winit::State,
     runtime_state: RS,
+    /// Extra windows showing debug views or additional screens
+    views: HashMap<WindowId, DetachedView>,
+    next_view_id: u32,
+}
+
+struct DetachedView {
+    id: ViewId,
+    view: DebugView,
+    window: Arc<Window>,
+}
+
+impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> WindowingContext<RS> {
+    fn open_view(&mut self, event_loop: &ActiveEventLoop, view: DebugView) {
+        let window = Arc::new(
+            event_loop
+                .create_window(
+                    Window::default_attributes()
+                        .with_title(format!("MultiEMU - {}", view))
+                        .with_resizable(true),
+                )
+                .unwrap(),
+        );
+        let id = ViewId(self.next_view_id);
+        self.next_view_id += 1;
+
+        tracing::info!("Opening {} in its own window", view);
+
+        // Dropping the window closes it, so a view that can't be drawn doesn't linger empty
+        if let Err(error) = self.runtime_state.open_view(id, window.clone()) {
+            tracing::error!("Could not open {} in its own window: {}", view, error);
+            return;
+        }
+        window.request_redraw();
+        self.views
+            .insert(window.id(), DetachedView { id, view, window });
+    }
+
+    /// Views belong to a specific machine so they all go when it does
+    fn close_views(&mut self) {
+        for (_, view) in self.views.drain() {
+            self.runtime_state.close_view(view.id);
+        }
+    }
+
+    fn view_event(&mut self, window_id: WindowId, event: WindowEvent, machine: Option<&Machine>) {
+        let Some(view) = self.views.get(&window_id) else {
+            return;
+        };
+
+        match event {
+            WindowEvent::CloseRequested => {
+                self.runtime_state.close_view(view.id);
+                self.views.remove(&window_id);
+            }
+            WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                self.runtime_state.view_resized(view.id);
+                view.window.request_redraw();
+            }
+            WindowEvent::RedrawRequested => {
+                if let Some(machine) = machine {
+                    if let Some(framebuffer) = view.view.render(machine) {
+                        self.runtime_state.redraw_view(view.id, framebuffer);
+                    }
+
+                    view.window.request_redraw();
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> ApplicationHandler
@@ -81,30 +237,64 @@ impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> ApplicationHandl
                     })
                     .expect("Could not figure out system");
 
-                let machine =
-                    Machine::from_system(user_specified_roms, self.rom_manager.clone(), system);
-                runtime_state.initialize_machine(&machine);
-
-                // HACK: Wire the keyboard to port 0
-                machine
-                    .input_manager
-                    .set_real_to_emulated_mapping(KEYBOARD_GAMEPAD_ID, 0);
-
-                // Make sure the system being run has a default mapping
-                let mut global_config_guard = GLOBAL_CONFIG.write().unwrap();
-
-                for (gamepad_type, metadata) in machine.input_manager.gamepad_types.iter() {
-                    global_config_guard
-                        .gamepad_configs
-                        .entry(machine.system)
-                        .or_default()
-                        .entry(gamepad_type.clone())
-                        .or_insert_with(|| IndexMap::from_iter(metadata.default_bindings.clone()));
-                }
+                match Machine::try_from_system(
+                    user_specified_roms,
+                    self.rom_manager.clone(),
+                    system,
+                ) {
+                    Ok(mut machine) => {
+                        self.menu.missing_roms = machine.missing_roms.clone();
+                        self.menu.rom_warnings = machine.rom_warnings.clone();
+
+                        if machine.bootable() {
+                            runtime_state.initialize_machine(&machine);
+
+                            if let Some(livesplit_config) =
+                                GLOBAL_CONFIG.read().unwrap().livesplit.clone()
+                            {
+                                livesplit::spawn(
+                                    livesplit_config,
+                                    machine.triggers.subscribe(),
+                                    machine.clock.clone(),
+                                );
+                            }
+
+                            // HACK: Wire the keyboard to port 0
+                            machine
+                                .input_manager
+                                .set_real_to_emulated_mapping(KEYBOARD_GAMEPAD_ID, 0);
+                            self.gamepads.announce(&machine.input_manager);
 
-                self.menu.active = false;
+                            // Make sure the system being run has a default mapping
+                            let mut global_config_guard = GLOBAL_CONFIG.write().unwrap();
 
-                self.machine_context = Some(MachineContext::Running(machine));
+                            for (gamepad_type, metadata) in
+                                machine.input_manager.gamepad_types.iter()
+                            {
+                                global_config_guard
+                                    .gamepad_configs
+                                    .entry(machine.system)
+                                    .or_default()
+                                    .entry(gamepad_type.clone())
+                                    .or_insert_with(|| {
+                                        IndexMap::from_iter(metadata.default_bindings.clone())
+                                    });
+                            }
+
+                            // Give the user a chance to see what is missing before it starts
+                            self.menu.active = self.menu.has_boot_problems();
+
+                            self.machine_context = Some(MachineContext::Running(machine));
+                        } else {
+                            tracing::error!("Machine is missing required ROMs, not starting it");
+                        }
+                    }
+                    Err(error) => {
+                        tracing::error!("{}", error);
+                        self.menu.load_error = Some(error.to_string());
+                        self.menu.active = true;
+                    }
+                }
             }
             Some(MachineContext::Running(_)) => {
                 panic!("Window resume while machine is running");
@@ -112,17 +302,70 @@ impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> ApplicationHandl
             None => {}
         }
 
-        self.windowing_context = Some(WindowingContext {
+        let mut windowing_context = WindowingContext {
             window,
             egui_winit_context,
             runtime_state,
-        })
+            views: HashMap::default(),
+            next_view_id: 0,
+        };
+
+        if let Some(MachineContext::Running(machine)) = &self.machine_context {
+            let capabilities = machine.capabilities();
+            self.menu.debug_views = DebugView::available(machine);
+            self.menu.text_outputs = machine
+                .text_outputs()
+                .map(|info| info.component.clone())
+                .collect();
+            self.menu.capabilities = Some(capabilities);
+            self.rewind = GLOBAL_CONFIG
+                .read()
+                .unwrap()
+                .rewind
+                .clone()
+                .filter(|_| capabilities.rewind_safe)
+                .map(RewindBuffer::new);
+            self.gdb = start_gdb(machine);
+            self.frame_pacer.reset(machine.frame_period());
+            AV_SYNC.reset();
+            self.boot_state = Some(machine.state());
+            self.audio_output = AudioOutput::new(machine);
+            refresh_input_menu(&mut self.menu, &machine.input_manager);
+
+            for view in DebugView::secondary_displays(machine) {
+                windowing_context.open_view(event_loop, view);
+            }
+        }
+
+        self.windowing_context = Some(windowing_context);
+    }
+
+    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+        let machine = match &self.machine_context {
+            Some(MachineContext::Running(machine)) => Some(machine),
+            _ => None,
+        };
+
+        if self.gamepads.poll(machine) {
+            if let Some(machine) = machine {
+                refresh_input_menu(&mut self.menu, &machine.input_manager);
+            }
+        }
+    }
+
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        // Variable refresh rate presentation waits on a timer instead of the display
+        if let StartCause::ResumeTimeReached { .. } = cause {
+            if let Some(window_context) = &self.windowing_context {
+                window_context.window.request_redraw();
+            }
+        }
     }
 
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        _window_id: WindowId,
+        window_id: WindowId,
         event: WindowEvent,
     ) {
         // This helps the user not stare at a black screen
@@ -135,13 +378,47 @@ impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> ApplicationHandl
             .as_mut()
             .expect("Window was not initialized");
 
+        if window_id != window_context.window.id() {
+            let machine = match &self.machine_context {
+                Some(MachineContext::Running(machine)) => Some(machine),
+                _ => None,
+            };
+
+            window_context.view_event(window_id, event, machine);
+            return;
+        }
+
+        // egui needs to know about scale changes even if the menu isn't open
+        if let WindowEvent::ScaleFactorChanged { scale_factor, .. } = &event {
+            tracing::info!("Window scale factor changed to {}", scale_factor);
+
+            let _ = window_context
+                .egui_winit_context
+                .on_window_event(&window_context.window, &event);
+            window_context.runtime_state.surface_resized();
+            window_context.window.request_redraw();
+            return;
+        }
+
         // Ensure a resize happens before drawing occurs
-        if matches!(event, WindowEvent::Resized(_)) {
+        if let WindowEvent::Resized(size) = event {
+            if window_context.window.fullscreen().is_none() {
+                GLOBAL_CONFIG.write().unwrap().window_geometry.size =
+                    Some((size.width, size.height));
+            }
+
             window_context.runtime_state.surface_resized();
             return;
         }
 
-        if self.menu.active {
+        if let WindowEvent::Moved(position) = event {
+            if window_context.window.fullscreen().is_none() {
+                GLOBAL_CONFIG.write().unwrap().window_geometry.position =
+                    Some((position.x, position.y));
+            }
+        }
+
+        if self.menu.active || self.pause_menu.open {
             let egui_winit::EventResponse { consumed, repaint } = window_context
                 .egui_winit_context
                 .on_window_event(&window_context.window, &event);
@@ -179,91 +456,406 @@ impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> ApplicationHandl
 
                 if let PhysicalKey::Code(key_code) = event.physical_key {
                     let state = event.state.is_pressed();
+                    let input: Input = key_code.try_into().unwrap();
 
-                    if !self.menu.active {
-                        if let Some(MachineContext::Running(machine)) = &mut self.machine_context {
-                            machine.input_manager.insert_input(
-                                machine.system,
-                                KEYBOARD_GAMEPAD_ID,
-                                key_code.try_into().unwrap(),
-                                InputState::Digital(state),
-                            );
+                    if state {
+                        self.pressed_inputs.insert(input);
+
+                        let hotkey = GLOBAL_CONFIG
+                            .read()
+                            .unwrap()
+                            .hotkeys
+                            .get(&self.pressed_inputs)
+                            .copied();
+
+                        if let Some(hotkey) = hotkey {
+                            tracing::debug!("Hotkey {:?} triggered", hotkey);
+
+                            if hotkey == Hotkey::ToggleFullscreen {
+                                toggle_fullscreen(
+                                    &window_context.window,
+                                    &GLOBAL_CONFIG.read().unwrap(),
+                                );
+                            }
+
+                            if hotkey == Hotkey::ToggleMenu && !self.menu.active {
+                                if let Some(MachineContext::Running(machine)) =
+                                    &self.machine_context
+                                {
+                                    self.pause_menu.snapshots_available =
+                                        machine.user_specified_roms.is_some();
+                                    self.pause_menu.tape_available =
+                                        machine.tape_components().next().is_some();
+                                    self.pause_menu.toggle();
+                                }
+                            }
+
+                            if hotkey == Hotkey::ToggleStatsOverlay {
+                                let mut global_config_guard = GLOBAL_CONFIG.write().unwrap();
+                                global_config_guard.stats_overlay =
+                                    !global_config_guard.stats_overlay;
+                                self.stats_overlay.reset();
+                            }
+
+                            if hotkey == Hotkey::Paste {
+                                if let (Some(MachineContext::Running(machine)), Some(text)) = (
+                                    &self.machine_context,
+                                    window_context.egui_winit_context.clipboard_text(),
+                                ) {
+                                    self.paste = Some(TextPaste::new(
+                                        &text,
+                                        GLOBAL_CONFIG.read().unwrap().paste_hold_frames,
+                                        |character| {
+                                            machine.input_components().find_map(|info| {
+                                                info.component.character_keys(character)
+                                            })
+                                        },
+                                    ));
+                                }
+                            }
+
+                            if hotkey == Hotkey::Rewind {
+                                if let (Some(MachineContext::Running(machine)), Some(state)) = (
+                                    &mut self.machine_context,
+                                    self.rewind.as_mut().and_then(RewindBuffer::step_back),
+                                ) {
+                                    machine
+                                        .restore_state(state)
+                                        .expect("A machine's own state never needs converting");
+                                }
+                            }
+
+                            if let Some(MachineContext::Running(machine)) = &self.machine_context {
+                                let capture_directory =
+                                    GLOBAL_CONFIG.read().unwrap().capture_directory.clone();
+                                let timestamp = SystemTime::now()
+                                    .duration_since(UNIX_EPOCH)
+                                    .unwrap_or_default()
+                                    .as_millis();
+
+                                if hotkey == Hotkey::Screenshot {
+                                    self.video_sinks.add(ScreenshotSink::new(
+                                        capture_directory
+                                            .join(format!("screenshot-{}.png", timestamp)),
+                                    ));
+                                }
+
+                                if hotkey == Hotkey::ToggleRecording {
+                                    match self.recording.take() {
+                                        // A recording that broke by itself already detached, so start a new one
+                                        Some((recording, _))
+                                            if self.video_sinks.remove(recording) => {}
+                                        _ => {
+                                            let path = capture_directory
+                                                .join(format!("recording-{}.mp4", timestamp));
+                                            self.recording = Some((
+                                                self.video_sinks.add(FfmpegSink::new(
+                                                    path.clone(),
+                                                    machine.frame_rate(),
+                                                )),
+                                                path,
+                                            ));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    } else {
+                        self.pressed_inputs.remove(&input);
+                    }
+
+                    if self.pause_menu.open {
+                        if let Some(item) = UiNavigation::from_input(input)
+                            .filter(|_| state)
+                            .and_then(|navigation| self.pause_menu.navigate(navigation))
+                        {
+                            self.pause_menu_action(item);
                         }
+                    } else if self.menu.active {
+                        // egui already moves focus with the keyboard, so only what it has no key for is routed
+                        if let Some(navigation) =
+                            UiNavigation::from_input(input).filter(|navigation| {
+                                state
+                                    && (!matches!(input, Input::Keyboard(_))
+                                        || matches!(
+                                            navigation,
+                                            UiNavigation::Back
+                                                | UiNavigation::NextTab
+                                                | UiNavigation::PreviousTab
+                                        ))
+                            })
+                        {
+                            self.menu.navigate(navigation);
+                        }
+                    } else if let Some(MachineContext::Running(machine)) = &mut self.machine_context
+                    {
+                        machine.input_manager.insert_input(
+                            machine.system,
+                            KEYBOARD_GAMEPAD_ID,
+                            input,
+                            InputState::Digital(state),
+                        );
                     }
                 }
             }
+            WindowEvent::CursorMoved { position, .. } => {
+                if self.menu.active {
+                    return;
+                }
+
+                if let Some(MachineContext::Running(machine)) = &self.machine_context {
+                    let Some(display) = machine.display_components().next() else {
+                        return;
+                    };
+                    let window_dimensions = window_context.window.inner_size();
+                    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+                    let position = window_to_display(
+                        Vector2::new(position.x, position.y),
+                        Vector2::new(window_dimensions.width, window_dimensions.height),
+                        window_context.window.scale_factor(),
+                        display.component.get_framebuffer().dimensions(),
+                        global_config_guard.display_scaling,
+                        global_config_guard.window_sizing,
+                    );
+                    drop(global_config_guard);
+
+                    pointer_moved(machine, position);
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                if let Some(MachineContext::Running(machine)) = &self.machine_context {
+                    pointer_moved(machine, None);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let input = match button {
+                    MouseButton::Left => PointerInput::Primary,
+                    MouseButton::Right => PointerInput::Secondary,
+                    _ => return,
+                };
+
+                if self.menu.active {
+                    return;
+                }
+
+                if let Some(MachineContext::Running(machine)) = &self.machine_context {
+                    machine.input_manager.insert_input(
+                        machine.system,
+                        MOUSE_GAMEPAD_ID,
+                        Input::Pointer(input),
+                        InputState::Digital(state.is_pressed()),
+                    );
+                }
+            }
             WindowEvent::RedrawRequested => {
+                for rom in self.ingested_roms.try_iter() {
+                    // TODO: Show these on screen once there is somewhere to put them while a game runs
+                    tracing::info!(
+                        "{} was added to the library",
+                        rom.name.as_deref().unwrap_or("Unknown ROM")
+                    );
+                    self.menu.recently_added.push(rom);
+                }
+
+                let mut pause_choice = None;
+                let mut crashed = false;
+
                 if self.menu.active {
                     // We put the ui output like this so multipassing egui gui building works
                     let mut ui_output = None;
-                    let full_output = self.menu.egui_context.clone().run(
-                        window_context
-                            .egui_winit_context
-                            .take_egui_input(&window_context.window),
-                        |context| {
-                            ui_output = ui_output.take().or(self.menu.run_menu(context));
-                        },
-                    );
+                    let mut raw_input = window_context
+                        .egui_winit_context
+                        .take_egui_input(&window_context.window);
+                    self.menu.inject_navigation(&mut raw_input);
+
+                    let full_output = self.menu.egui_context.clone().run(raw_input, |context| {
+                        ui_output = ui_output.take().or(self.menu.run_menu(context));
+                    });
+
+                    accessibility::announce(&full_output.platform_output);
+
+                    let mut opened_rom = None;
+                    if let Some((path, job)) = &self.pending_rom {
+                        if let Some(opened) = job.poll() {
+                            opened_rom = Some((path.clone(), opened));
+                            self.pending_rom = None;
+                        }
+                    }
+
+                    if let Some((path, opened)) = opened_rom {
+                        let opened = match opened {
+                            Ok(opened) => Some(opened),
+                            Err(error) => {
+                                tracing::error!("{}", error);
+                                self.menu.load_error = Some(error.to_string());
+                                None
+                            }
+                        };
+
+                        // Check if we know about the game from the manager
+                        if let Some((rom_id, program_path, system)) =
+                            opened.and_then(|(rom_id, program_path)| {
+                                self.rom_manager
+                                    .rom_information
+                                    .r_transaction()
+                                    .unwrap()
+                                    .get()
+                                    .primary::<RomInfo>(rom_id)
+                                    .unwrap()
+                                    .map(|info| info.system)
+                                    .or_else(|| GameSystem::guess(&path))
+                                    .map(|system| (rom_id, program_path, system))
+                            })
+                        {
+                            self.rom_manager.rom_paths.insert(rom_id, program_path);
+
+                            match Machine::try_from_system(
+                                vec![rom_id],
+                                self.rom_manager.clone(),
+                                system,
+                            ) {
+                                Ok(machine) => {
+                                    self.menu.missing_roms = machine.missing_roms.clone();
+                                    self.menu.rom_warnings = machine.rom_warnings.clone();
+
+                                    if machine.bootable() {
+                                        // HACK: Wire the keyboard to port 0
+                                        machine
+                                            .input_manager
+                                            .set_real_to_emulated_mapping(KEYBOARD_GAMEPAD_ID, 0);
+                                        self.gamepads.announce(&machine.input_manager);
+
+                                        // Make sure the system being run has a default mapping
+                                        let mut global_config_guard =
+                                            GLOBAL_CONFIG.write().unwrap();
+
+                                        for (gamepad_type, metadata) in
+                                            machine.input_manager.gamepad_types.iter()
+                                        {
+                                            global_config_guard
+                                                .gamepad_configs
+                                                .entry(machine.system)
+                                                .or_default()
+                                                .entry(gamepad_type.clone())
+                                                .or_insert_with(|| {
+                                                    IndexMap::from_iter(
+                                                        metadata.default_bindings.clone(),
+                                                    )
+                                                });
+                                        }
+
+                                        // Initialize graphics components
+                                        window_context.runtime_state.initialize_machine(&machine);
+
+                                        if let Some(livesplit_config) =
+                                            global_config_guard.livesplit.clone()
+                                        {
+                                            livesplit::spawn(
+                                                livesplit_config,
+                                                machine.triggers.subscribe(),
+                                                machine.clock.clone(),
+                                            );
+                                        }
+                                        let capabilities = machine.capabilities();
+                                        self.rewind = global_config_guard
+                                            .rewind
+                                            .clone()
+                                            .filter(|_| capabilities.rewind_safe)
+                                            .map(RewindBuffer::new);
+                                        drop(global_config_guard);
+                                        self.gdb = start_gdb(&machine);
+
+                                        window_context.close_views();
+                                        self.menu.debug_views = DebugView::available(&machine);
+                                        self.menu.text_outputs = machine
+                                            .text_outputs()
+                                            .map(|info| info.component.clone())
+                                            .collect();
+                                        self.menu.capabilities = Some(capabilities);
+                                        self.frame_pacer.reset(machine.frame_period());
+                                        AV_SYNC.reset();
+                                        self.boot_state = Some(machine.state());
+                                        self.audio_output = AudioOutput::new(&machine);
+                                        refresh_input_menu(&mut self.menu, &machine.input_manager);
+                                        for view in DebugView::secondary_displays(&machine) {
+                                            window_context.open_view(event_loop, view);
+                                        }
+
+                                        self.machine_context =
+                                            Some(MachineContext::Running(machine));
+                                        // Close the menu, unless there is something to tell the user
+                                        self.menu.active = self.menu.has_boot_problems();
+                                    } else {
+                                        tracing::error!(
+                                            "Machine is missing required ROMs, not starting it"
+                                        );
+                                    }
+                                }
+                                Err(error) => {
+                                    tracing::error!("{}", error);
+                                    self.menu.load_error = Some(error.to_string());
+                                }
+                            }
+                        } else {
+                            tracing::error!("Could not identify rom at {}", path.display());
+                        }
+                    }
 
                     match ui_output {
                         None => {}
                         Some(UiOutput::OpenGame { path }) => {
                             tracing::info!("Opening rom at {}", path.display());
 
-                            let mut rom_file = File::open(&path).unwrap();
-                            let rom_id = RomId::from_read(&mut rom_file);
-
-                            // Check if we know about the game from the manager
-                            if let Some(system) = self
-                                .rom_manager
-                                .rom_information
-                                .r_transaction()
-                                .unwrap()
-                                .get()
-                                .primary::<RomInfo>(rom_id)
-                                .unwrap()
-                                .map(|info| info.system)
-                                .or_else(|| GameSystem::guess(&path))
-                            {
-                                self.rom_manager.rom_paths.insert(rom_id, path.clone());
-
-                                let machine = match system {
-                                    GameSystem::Other(OtherSystem::Chip8) => {
-                                        chip8_machine(vec![rom_id], self.rom_manager.clone())
-                                    }
-                                    _ => {
-                                        unimplemented!()
+                            // Hashing a big ROM would freeze the menu, so it happens on the IO workers
+                            let job_path = path.clone();
+                            let job =
+                                self.rom_manager.io.submit_tracked(
+                                    format!("Opening {}", path.display()),
+                                    move |_| open_rom_file(&job_path),
+                                );
+                            self.pending_rom = Some((path, job));
+                        }
+                        Some(UiOutput::ResumeMachine) => {
+                            if let Some(MachineContext::Running(machine)) = &self.machine_context {
+                                if let Some(watchdog) = &machine.watchdog {
+                                    watchdog.resume();
+                                }
+                                self.menu.active = false;
+                            }
+                        }
+                        Some(UiOutput::OpenDebugView(view)) => {
+                            window_context.open_view(event_loop, view);
+                        }
+                        Some(UiOutput::AssignGamepad { host, port }) => {
+                            if let Some(MachineContext::Running(machine)) = &self.machine_context {
+                                match port {
+                                    Some(port) => machine
+                                        .input_manager
+                                        .set_real_to_emulated_mapping(host, port),
+                                    None => {
+                                        machine.input_manager.clear_real_to_emulated_mapping(host)
                                     }
-                                };
-
-                                // HACK: Wire the keyboard to port 0
-                                machine
-                                    .input_manager
-                                    .set_real_to_emulated_mapping(KEYBOARD_GAMEPAD_ID, 0);
+                                }
 
-                                // Make sure the system being run has a default mapping
-                                let mut global_config_guard = GLOBAL_CONFIG.write().unwrap();
+                                refresh_input_menu(&mut self.menu, &machine.input_manager);
+                            }
+                        }
+                        Some(UiOutput::SaveBugReport {
+                            path,
+                            include_state,
+                        }) => {
+                            let machine = match &self.machine_context {
+                                Some(MachineContext::Running(machine)) => Some(machine),
+                                _ => None,
+                            };
 
-                                for (gamepad_type, metadata) in
-                                    machine.input_manager.gamepad_types.iter()
-                                {
-                                    global_config_guard
-                                        .gamepad_configs
-                                        .entry(machine.system)
-                                        .or_default()
-                                        .entry(gamepad_type.clone())
-                                        .or_insert_with(|| {
-                                            IndexMap::from_iter(metadata.default_bindings.clone())
-                                        });
+                            match logging::write_bug_report(&path, machine, None, include_state) {
+                                Ok(()) => {
+                                    tracing::info!("Saved bug report to {}", path.display())
+                                }
+                                Err(error) => {
+                                    tracing::error!("Could not save bug report: {}", error)
                                 }
-
-                                // Initialize graphics components
-                                window_context.runtime_state.initialize_machine(&machine);
-                                self.machine_context = Some(MachineContext::Running(machine));
-                                // Close the menu
-                                self.menu.active = false;
-                            } else {
-                                tracing::error!("Could not identify rom at {}", path.display());
                             }
                         }
                     }
@@ -271,44 +863,348 @@ impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> ApplicationHandl
                     window_context
                         .runtime_state
                         .redraw_menu(&self.menu.egui_context, full_output);
+
+                    // Keep drawing so progress bars move and finished background work gets picked up
+                    if self.pending_rom.is_some() || PROGRESS.is_busy() {
+                        window_context.window.request_redraw();
+                    }
                 } else if let Some(MachineContext::Running(machine)) = &mut self.machine_context {
                     let now = Instant::now();
-                    
+
+                    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+                    let stats_overlay = global_config_guard.stats_overlay;
+                    let variable_refresh_rate = global_config_guard.variable_refresh_rate;
+                    drop(global_config_guard);
+                    machine.scheduler.set_profiling(stats_overlay);
+
                     self.timing_tracker.frame_rendering_starting();
-                    machine.run();
-                    window_context.runtime_state.redraw(machine);
+                    // The host refresh rate rarely matches the machine, so this repeats or skips frames to keep up
+                    let frames_due = self.frame_pacer.frames_due(now);
+                    if let Some(gdb) = &mut self.gdb {
+                        gdb.poll();
+                    }
+                    // Still asked while paused, so the time spent in the pause menu or a debugger isn't caught up on
+                    // afterwards
+                    let frames_due = if self.pause_menu.open
+                        || self.gdb.as_ref().is_some_and(GdbStub::holding)
+                    {
+                        0
+                    } else {
+                        frames_due
+                    };
+                    for _ in 0..frames_due {
+                        if self
+                            .paste
+                            .as_mut()
+                            .is_some_and(|paste| !paste.step(&machine.input_manager))
+                        {
+                            self.paste = None;
+                        }
+
+                        // A component panicking takes the machine down, not the whole frontend
+                        if catch_unwind(AssertUnwindSafe(|| machine.run_frame())).is_err() {
+                            crashed = true;
+                            break;
+                        }
+
+                        if let Some(rewind) = &mut self.rewind {
+                            rewind.frame_finished(machine);
+                        }
+
+                        if !self.video_sinks.is_empty() {
+                            machine.read_raw_frame(|frame| {
+                                self.video_sinks.feed(frame, machine.clock.emulated_time())
+                            });
+                        }
+                    }
+
+                    // Nothing below can be trusted with locks the panic left poisoned
+                    if crashed {
+                        self.machine_crashed();
+                        return;
+                    }
+
+                    if let Some(audio_output) = &mut self.audio_output {
+                        audio_output.mix();
+                    }
+
+                    if let Some(report) = machine.watchdog.as_ref().and_then(Watchdog::take_report)
+                    {
+                        self.menu.watchdog_report = Some(report.to_string());
+                        self.menu.active = true;
+                    }
+
+                    let device_name = stats_overlay
+                        .then(|| window_context.runtime_state.device_name())
+                        .flatten();
+                    // Drawn from the history up to the last frame, so this frame isn't timing itself
+                    let overlay = (stats_overlay || PROGRESS.is_busy() || self.pause_menu.open)
+                        .then(|| {
+                            let full_output = self.menu.egui_context.run(
+                                window_context
+                                    .egui_winit_context
+                                    .take_egui_input(&window_context.window),
+                                |context| {
+                                    if stats_overlay {
+                                        self.stats_overlay.show(
+                                            context,
+                                            machine,
+                                            device_name.as_deref(),
+                                        );
+                                    }
+
+                                    progress::show(context, false);
+
+                                    if self.pause_menu.open {
+                                        pause_choice = self.pause_menu.show(context);
+                                    }
+                                },
+                            );
+
+                            (&self.menu.egui_context, full_output)
+                        });
+                    let frame_finished = Instant::now();
+                    window_context.runtime_state.redraw(machine, overlay);
+                    // Submission is as close to the screen as we can see, repeated frames don't count
+                    if frames_due != 0 {
+                        AV_SYNC.record_video_latency(frame_finished.elapsed());
+                    }
                     self.timing_tracker.frame_rendering_ending();
 
                     let total_time_taken = Instant::now() - now;
-                    let average_timings = self.timing_tracker.average_frame_timings();
-                    
-                    if total_time_taken > average_timings {
-                        machine.scheduler.too_slow();
-                    } 
 
-                    if total_time_taken < average_timings {
-                        machine.scheduler.too_fast();
+                    if stats_overlay {
+                        self.stats_overlay
+                            .record_frame(total_time_taken, machine.clock.emulated_time());
                     }
+                    let average_timings = self.timing_tracker.average_frame_timings();
 
                     tracing::debug!(
                         "Average framerate is {}",
                         Duration::from_secs(1).as_secs_f32() / average_timings.as_secs_f32()
                     );
 
-                    window_context.window.request_redraw();
+                    if variable_refresh_rate {
+                        // The display follows us, so present exactly when the next machine frame is done
+                        event_loop.set_control_flow(ControlFlow::WaitUntil(
+                            self.frame_pacer.next_deadline(),
+                        ));
+                    } else {
+                        event_loop.set_control_flow(ControlFlow::Wait);
+                        window_context.window.request_redraw();
+                    }
                 } else {
                     tracing::warn!("Machine not running when redraw requested");
                 }
+
+                if let Some(item) = pause_choice {
+                    self.pause_menu_action(item);
+                }
             }
             _ => {}
         }
     }
 }
 
+impl<RS: RenderingBackendState<DisplayApiHandle = Arc<Window>>> PlatformRuntime<RS> {
+    fn pause_menu_action(&mut self, item: PauseMenuItem) {
+        let Some(MachineContext::Running(machine)) = &mut self.machine_context else {
+            return;
+        };
+        let snapshot_store =
+            SnapshotStore::new(GLOBAL_CONFIG.read().unwrap().snapshot_directory.clone());
+        let rom_id = machine
+            .user_specified_roms
+            .as_ref()
+            .and_then(|roms| roms.first().copied());
+
+        match item {
+            PauseMenuItem::Resume => {}
+            PauseMenuItem::Reset => {
+                if let Some(boot_state) = self.boot_state.clone() {
+                    machine
+                        .restore_state(boot_state)
+                        .expect("A machine's own state never needs converting");
+                }
+            }
+            PauseMenuItem::SaveState => {
+                if let Some(rom_id) = rom_id {
+                    if let Err(error) = snapshot_store.store(machine, rom_id, 0) {
+                        tracing::error!("Could not save state: {}", error);
+                    }
+                }
+            }
+            PauseMenuItem::LoadState => {
+                if let Some(rom_id) = rom_id {
+                    match snapshot_store.load(rom_id, 0) {
+                        Ok(state) => {
+                            if let Err(error) = machine.restore_state(state) {
+                                tracing::error!("Could not load state: {}", error);
+                            }
+                        }
+                        Err(error) => tracing::error!("Could not load state: {}", error),
+                    }
+                }
+            }
+            PauseMenuItem::ExportArchive => {
+                // The recording has to be finished before it can be played, so it stops here
+                let recording = self
+                    .recording
+                    .take()
+                    .filter(|(recording, _)| self.video_sinks.remove(*recording))
+                    .map(|(_, path)| path);
+                let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+                let save_manager = SaveManager::new(
+                    global_config_guard.save_directory.clone(),
+                    global_config_guard.save_location,
+                    self.rom_manager.clone(),
+                );
+                let path = global_config_guard.capture_directory.join(format!(
+                    "session-{}.zip",
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis()
+                ));
+                drop(global_config_guard);
+
+                match write_machine_archive(
+                    &path,
+                    machine,
+                    &snapshot_store,
+                    &save_manager,
+                    recording.as_deref(),
+                ) {
+                    Ok(()) => tracing::info!("Exported session archive to {}", path.display()),
+                    Err(error) => tracing::error!("Could not export session archive: {}", error),
+                }
+            }
+            PauseMenuItem::Settings => {
+                self.menu.active = true;
+            }
+            PauseMenuItem::TapePlay | PauseMenuItem::TapeStop | PauseMenuItem::TapeRewind => {
+                for tape in machine.tape_components() {
+                    let mut deck = tape.component.deck().lock().unwrap();
+
+                    match item {
+                        PauseMenuItem::TapePlay => deck.play(),
+                        PauseMenuItem::TapeStop => deck.stop(),
+                        _ => deck.rewind(),
+                    }
+                }
+            }
+            PauseMenuItem::TapeFastLoad => {
+                for tape in machine.tape_components() {
+                    if !tape.component.fast_load() {
+                        tracing::warn!("Tape has no files that can be loaded without playing it");
+                    }
+                }
+            }
+            PauseMenuItem::QuitToLauncher => {
+                tracing::info!("Quitting to the launcher");
+
+                self.close_machine();
+            }
+        }
+
+        self.pause_menu.open = false;
+    }
+
+    /// Tears down the running machine and everything attached to it, leaving the launcher up
+    fn close_machine(&mut self) {
+        self.machine_context = None;
+        self.boot_state = None;
+        self.rewind = None;
+        self.paste = None;
+        self.gdb = None;
+        self.audio_output = None;
+        self.menu.capabilities = None;
+        self.menu.debug_views.clear();
+        self.menu.text_outputs.clear();
+        self.menu.active = true;
+
+        if let Some(window_context) = &mut self.windowing_context {
+            window_context.close_views();
+        }
+    }
+
+    /// Writes a crash report for the panic that just unwound out of the machine, then closes it
+    fn machine_crashed(&mut self) {
+        let panic = logging::take_panic();
+        let crash_directory = STORAGE_DIRECTORY.join("crash_reports");
+        let path = crash_directory.join(format!(
+            "crash-{}.zip",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        ));
+        let machine = match &self.machine_context {
+            Some(MachineContext::Running(machine)) => Some(machine),
+            _ => None,
+        };
+
+        let written = std::fs::create_dir_all(&crash_directory)
+            .map_err(Into::into)
+            .and_then(|_| logging::write_bug_report(&path, machine, panic.as_ref(), true));
+        let summary = panic
+            .as_ref()
+            .map_or("Unknown panic".to_string(), ToString::to_string);
+
+        self.menu.crash_report = Some(match written {
+            Ok(()) => {
+                tracing::error!("Machine crashed, saved crash report to {}", path.display());
+                format!("{}\n\n{}", summary, path.display())
+            }
+            Err(error) => {
+                tracing::error!("Machine crashed, could not save crash report: {}", error);
+                summary
+            }
+        });
+
+        self.close_machine();
+        self.pause_menu.open = false;
+    }
+}
+
+/// The id and file to load for a ROM the user picked, assembling it first if it is Octo source
+fn open_rom_file(path: &Path) -> Result<(RomId, PathBuf), OctoLoadError> {
+    if is_octo_source(path) {
+        let roms_directory = GLOBAL_CONFIG.read().unwrap().roms_directory.clone();
+
+        return assemble_into_store(path, &roms_directory);
+    }
+
+    let mut rom_file = File::open(path).map_err(|error| OctoLoadError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    Ok((RomId::from_read(&mut rom_file), path.to_path_buf()))
+}
+
 fn setup_window(event_loop: &ActiveEventLoop) -> Arc<Window> {
-    let window_attributes = Window::default_attributes()
+    let global_config_guard = GLOBAL_CONFIG.read().unwrap();
+    let WindowGeometry { position, size } = global_config_guard.window_geometry;
+
+    let mut window_attributes = Window::default_attributes()
         .with_title("MultiEMU")
         .with_resizable(true)
         .with_transparent(false);
-    Arc::new(event_loop.create_window(window_attributes).unwrap())
+
+    if let Some((width, height)) = size {
+        window_attributes = window_attributes.with_inner_size(PhysicalSize::new(width, height));
+    }
+
+    if let Some((x, y)) = position {
+        window_attributes = window_attributes.with_position(PhysicalPosition::new(x, y));
+    }
+
+    let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+
+    if global_config_guard.start_fullscreen {
+        toggle_fullscreen(&window, &global_config_guard);
+    }
+
+    window
 }
@@ -0,0 +1,164 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Mutex, Weak,
+};
+
+/// Every long running operation in progress, for the menu and overlay to show without knowing what started them
+pub static PROGRESS: ProgressBoard = ProgressBoard::new();
+
+#[derive(Debug)]
+struct TaskState {
+    id: u64,
+    label: String,
+    /// 0 while the amount of work isn't known yet
+    total: AtomicU64,
+    done: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+/// What a task looked like when [ProgressBoard::tasks] was called
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressEntry {
+    pub id: u64,
+    pub label: String,
+    /// None if the task can't say how far along it is
+    pub fraction: Option<f32>,
+    pub done: u64,
+    pub total: Option<u64>,
+    pub cancelled: bool,
+}
+
+#[derive(Debug)]
+pub struct ProgressBoard {
+    next_id: AtomicU64,
+    /// Weak so a task is over as soon as the last handle to it goes away, however the work ended
+    tasks: Mutex<Vec<Weak<TaskState>>>,
+}
+
+impl Default for ProgressBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressBoard {
+    pub const fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Starts tracking a task, which disappears from the board again once the handle is dropped
+    pub fn begin(&self, label: impl Into<String>, total: Option<u64>) -> ProgressHandle {
+        let state = Arc::new(TaskState {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            label: label.into(),
+            total: AtomicU64::new(total.unwrap_or(0)),
+            done: AtomicU64::new(0),
+            cancelled: AtomicBool::new(false),
+        });
+        self.tasks.lock().unwrap().push(Arc::downgrade(&state));
+
+        ProgressHandle { state }
+    }
+
+    /// Tasks still running, oldest first
+    pub fn tasks(&self) -> Vec<ProgressEntry> {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|task| task.strong_count() != 0);
+
+        tasks
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|task| {
+                let total = task.total.load(Ordering::Relaxed);
+                let done = task.done.load(Ordering::Relaxed);
+                let total = (total != 0).then_some(total);
+
+                ProgressEntry {
+                    id: task.id,
+                    label: task.label.clone(),
+                    fraction: total.map(|total| (done as f32 / total as f32).min(1.0)),
+                    done,
+                    total,
+                    cancelled: task.cancelled.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|task| task.strong_count() != 0)
+    }
+
+    /// Asks a task to stop, it is up to the task to notice and actually do so
+    pub fn cancel(&self, id: u64) {
+        if let Some(task) = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|task| task.id == id)
+        {
+            task.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Held by whatever is doing the work, reporting how far along it is
+///
+/// Cheap to clone so work split across threads can share one entry
+#[derive(Debug, Clone)]
+pub struct ProgressHandle {
+    state: Arc<TaskState>,
+}
+
+impl ProgressHandle {
+    pub fn set_total(&self, total: u64) {
+        self.state.total.store(total, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, amount: u64) {
+        self.state.done.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    /// Work should check this between steps and stop early when it is set
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tasks_report_and_cancel() {
+        let board = ProgressBoard::new();
+        let scan = board.begin("Scanning", None);
+        let import = board.begin("Importing", Some(4));
+
+        import.advance(1);
+        let tasks = board.tasks();
+        assert_eq!(tasks[0].fraction, None);
+        assert_eq!(tasks[1].fraction, Some(0.25));
+
+        board.cancel(tasks[1].id);
+        assert!(import.is_cancelled());
+        assert!(!scan.is_cancelled());
+
+        let clone = import.clone();
+        drop(import);
+        assert_eq!(board.tasks().len(), 2);
+        drop(clone);
+        drop(scan);
+        assert!(!board.is_busy());
+        assert!(board.tasks().is_empty());
+    }
+}
@@ -1,8 +1,15 @@
-use crate::machine::Machine;
+use super::debug_view::ViewId;
+use crate::{
+    config::{DisplayScaling, WindowSizing},
+    machine::Machine,
+};
 use egui::FullOutput;
-use nalgebra::DMatrix;
+use nalgebra::{DMatrix, DMatrixView, DMatrixViewMut, Vector2};
 use palette::Srgba;
-use std::sync::{Arc, Mutex};
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
 
 pub enum DisplayComponentInitializationData {
     Software,
@@ -17,12 +24,349 @@ pub enum DisplayComponentFramebuffer {
     Vulkan(Arc<vulkano::image::Image>),
 }
 
+impl DisplayComponentFramebuffer {
+    /// Width and height in pixels
+    pub fn dimensions(&self) -> Vector2<u32> {
+        match self {
+            DisplayComponentFramebuffer::Software(framebuffer) => {
+                let framebuffer = framebuffer.lock().unwrap();
+
+                Vector2::new(framebuffer.nrows(), framebuffer.ncols()).cast()
+            }
+            #[cfg(graphics_vulkan)]
+            DisplayComponentFramebuffer::Vulkan(image) => {
+                let [width, height, _] = image.extent();
+
+                Vector2::new(width, height)
+            }
+        }
+    }
+
+    /// Lends out the current frame as RGBA bytes without copying it
+    ///
+    /// The framebuffer stays locked while the reader runs, so it should be quick. Hardware framebuffers would need
+    /// a readback and return None
+    pub fn read_raw<R>(&self, reader: impl FnOnce(RawFrame<'_>) -> R) -> Option<R> {
+        match self {
+            DisplayComponentFramebuffer::Software(framebuffer) => {
+                let framebuffer = framebuffer.lock().unwrap();
+
+                Some(reader(RawFrame::new(&framebuffer)))
+            }
+            #[cfg(graphics_vulkan)]
+            DisplayComponentFramebuffer::Vulkan(_) => None,
+        }
+    }
+}
+
+/// A frame as RGBA8 bytes, borrowed from the framebuffer it came from
+#[derive(Debug, Clone, Copy)]
+pub struct RawFrame<'a> {
+    pub bytes: &'a [u8],
+    /// Width and height in pixels
+    pub extent: Vector2<u32>,
+    /// Bytes from the start of one row to the next
+    pub stride: usize,
+}
+
+impl<'a> RawFrame<'a> {
+    pub fn new(framebuffer: &'a DMatrix<Srgba<u8>>) -> Self {
+        // Column major with x as the row index, so each line is already contiguous
+        Self {
+            bytes: bytemuck::cast_slice(framebuffer.as_slice()),
+            extent: Vector2::new(framebuffer.nrows(), framebuffer.ncols()).cast(),
+            stride: framebuffer.nrows() * size_of::<Srgba<u8>>(),
+        }
+    }
+
+    pub fn row(&self, y: u32) -> &'a [u8] {
+        let start = y as usize * self.stride;
+
+        &self.bytes[start..start + self.extent.x as usize * size_of::<Srgba<u8>>()]
+    }
+}
+
 pub trait RenderingBackendState: Sized {
     type DisplayApiHandle: Clone + 'static;
 
     fn new(display_api_handle: Self::DisplayApiHandle) -> Self;
-    fn redraw(&mut self, machine: &Machine);
+    /// Draws the machine, with egui output like the stats overlay on top if there is any
+    fn redraw(&mut self, machine: &Machine, overlay: Option<(&egui::Context, FullOutput)>);
     fn redraw_menu(&mut self, egui_context: &egui::Context, full_output: FullOutput);
     fn surface_resized(&mut self) {}
+    /// Name of the device actually doing the rendering, if the backend has such a concept
+    fn device_name(&self) -> Option<String> {
+        None
+    }
     fn initialize_machine(&mut self, machine: &Machine);
+    /// Sets up a surface for an extra window, like a detached debug view
+    ///
+    /// On failure the view was never opened and the window should not be drawn to
+    fn open_view(
+        &mut self,
+        view: ViewId,
+        display_api_handle: Self::DisplayApiHandle,
+    ) -> Result<(), Box<dyn Error>>;
+    fn close_view(&mut self, view: ViewId);
+    fn view_resized(&mut self, _view: ViewId) {}
+    /// Shows a framebuffer in an extra window, fit to its size
+    fn redraw_view(&mut self, view: ViewId, framebuffer: DisplayComponentFramebuffer);
+}
+
+/// Figures out where in the window a display components framebuffer should go
+///
+/// Returns the offset and size of the area, both in physical pixels
+pub fn display_viewport(
+    window_dimensions: Vector2<u32>,
+    scale_factor: f64,
+    framebuffer_dimensions: Vector2<u32>,
+    scaling: DisplayScaling,
+    sizing: WindowSizing,
+) -> (Vector2<u32>, Vector2<u32>) {
+    if window_dimensions.min() == 0 || framebuffer_dimensions.min() == 0 {
+        return (Vector2::zeros(), window_dimensions);
+    }
+
+    let window = window_dimensions.cast::<f64>();
+    let framebuffer = framebuffer_dimensions.cast::<f64>();
+
+    let viewport = match scaling {
+        DisplayScaling::Stretch => return (Vector2::zeros(), window_dimensions),
+        DisplayScaling::Fit => framebuffer * window.component_div(&framebuffer).min(),
+        DisplayScaling::Integer => {
+            let unit = match sizing {
+                WindowSizing::Physical => 1.0,
+                WindowSizing::Logical => scale_factor,
+            };
+
+            // Always show at least one multiple even if the window is too small for it
+            let multiple = (window / unit)
+                .component_div(&framebuffer)
+                .min()
+                .floor()
+                .max(1.0);
+
+            framebuffer * multiple * unit
+        }
+    };
+
+    let viewport = viewport
+        .map(f64::round)
+        .try_cast::<u32>()
+        .unwrap()
+        .zip_map(&window_dimensions, |viewport, window| viewport.min(window));
+    let offset = (window_dimensions - viewport) / 2;
+
+    (offset, viewport)
+}
+
+/// Where a point in the window lands on the display, from 0.0 to 1.0 on each axis
+///
+/// None if the point is in the borders around the display
+pub fn window_to_display(
+    position: Vector2<f64>,
+    window_dimensions: Vector2<u32>,
+    scale_factor: f64,
+    framebuffer_dimensions: Vector2<u32>,
+    scaling: DisplayScaling,
+    sizing: WindowSizing,
+) -> Option<Vector2<f32>> {
+    let (offset, size) = display_viewport(
+        window_dimensions,
+        scale_factor,
+        framebuffer_dimensions,
+        scaling,
+        sizing,
+    );
+
+    if size.min() == 0 {
+        return None;
+    }
+
+    let position = (position - offset.cast()).component_div(&size.cast());
+
+    position
+        .iter()
+        .all(|axis| (0.0..1.0).contains(axis))
+        .then(|| position.cast())
+}
+
+/// Nearest neighbor copy of a framebuffer into part of a larger surface
+pub fn blit_nearest(
+    source: DMatrixView<'_, Srgba<u8>>,
+    mut destination: DMatrixViewMut<'_, Srgba<u8>>,
+    viewport_offset: Vector2<usize>,
+    viewport_size: Vector2<usize>,
+) {
+    let viewport_end = viewport_offset + viewport_size;
+    let source_size = Vector2::new(source.nrows(), source.ncols());
+
+    let scaling = viewport_size
+        .cast::<f32>()
+        .component_div(&source_size.cast::<f32>());
+
+    // Iterate over each pixel in the source buffer
+    for x in 0..source.nrows() {
+        for y in 0..source.ncols() {
+            let source_pixel = source[(x, y)];
+
+            let dest_start = (viewport_offset
+                + Vector2::new(x, y)
+                    .cast::<f32>()
+                    .component_mul(&scaling)
+                    .map(f32::round)
+                    .try_cast::<usize>()
+                    .unwrap())
+            .zip_map(&viewport_end, |dest_dim, viewport_dim| {
+                dest_dim.min(viewport_dim)
+            });
+
+            let dest_end = (viewport_offset
+                + Vector2::new(x, y)
+                    .cast::<f32>()
+                    .add_scalar(1.0)
+                    .component_mul(&scaling)
+                    .map(f32::round)
+                    .try_cast::<usize>()
+                    .unwrap())
+            .zip_map(&viewport_end, |dest_dim, viewport_dim| {
+                dest_dim.min(viewport_dim)
+            });
+
+            // Fill the destination pixels with the source pixel
+            let mut destination_pixels = destination.view_mut(
+                (dest_start.x, dest_start.y),
+                (dest_end.x - dest_start.x, dest_end.y - dest_start.y),
+            );
+
+            destination_pixels.fill(source_pixel);
+        }
+    }
+}
+
+/// Averages each frame with the one before it, hiding flicker based transparency
+#[derive(Debug, Default)]
+pub struct FrameBlender {
+    previous_frame: Option<DMatrix<Srgba<u8>>>,
+}
+
+impl FrameBlender {
+    pub fn blend(&mut self, mut framebuffer: DMatrixViewMut<'_, Srgba<u8>>) {
+        let current_frame = framebuffer.clone_owned();
+
+        if let Some(previous_frame) = self
+            .previous_frame
+            .as_ref()
+            .filter(|previous_frame| previous_frame.shape() == framebuffer.shape())
+        {
+            framebuffer.zip_apply(previous_frame, |current, previous| {
+                // Average the light, not the encoded values
+                let blended =
+                    (current.into_linear::<f32, f32>() + previous.into_linear::<f32, f32>()) / 2.0;
+
+                *current = Srgba::from_linear(blended);
+            });
+        }
+
+        self.previous_frame = Some(current_frame);
+    }
+
+    /// Forget the last frame, so blending does not smear across a pause or a machine swap
+    pub fn reset(&mut self) {
+        self.previous_frame = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn raw_frame_rows() {
+        let mut framebuffer = DMatrix::from_element(3, 2, Srgba::new(0, 0, 0, 255));
+        framebuffer[(2, 1)] = Srgba::new(1, 2, 3, 4);
+        let framebuffer = DisplayComponentFramebuffer::Software(Arc::new(Mutex::new(framebuffer)));
+
+        framebuffer
+            .read_raw(|frame| {
+                assert_eq!(frame.extent, Vector2::new(3, 2));
+                assert_eq!(frame.stride, 12);
+                assert_eq!(&frame.row(1)[8..], &[1, 2, 3, 4]);
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn integer_scaling() {
+        let (offset, size) = display_viewport(
+            Vector2::new(800, 600),
+            1.0,
+            Vector2::new(64, 32),
+            DisplayScaling::Integer,
+            WindowSizing::Physical,
+        );
+
+        assert_eq!(size, Vector2::new(768, 384));
+        assert_eq!(offset, Vector2::new(16, 108));
+    }
+
+    #[test]
+    fn integer_scaling_logical() {
+        // A 1.5x monitor should pick the multiple based on the logical size
+        let (_, size) = display_viewport(
+            Vector2::new(1200, 900),
+            1.5,
+            Vector2::new(64, 32),
+            DisplayScaling::Integer,
+            WindowSizing::Logical,
+        );
+
+        assert_eq!(size, Vector2::new(1152, 576));
+    }
+
+    #[test]
+    fn fit_scaling() {
+        let (offset, size) = display_viewport(
+            Vector2::new(800, 600),
+            1.0,
+            Vector2::new(64, 32),
+            DisplayScaling::Fit,
+            WindowSizing::Physical,
+        );
+
+        assert_eq!(size, Vector2::new(800, 400));
+        assert_eq!(offset, Vector2::new(0, 100));
+    }
+
+    #[test]
+    fn pointer_in_borders() {
+        let to_display = |x, y| {
+            window_to_display(
+                Vector2::new(x, y),
+                Vector2::new(800, 600),
+                1.0,
+                Vector2::new(64, 32),
+                DisplayScaling::Fit,
+                WindowSizing::Physical,
+            )
+        };
+
+        // The display is letterboxed between 100 and 500
+        assert_eq!(to_display(400.0, 50.0), None);
+        assert_eq!(to_display(400.0, 300.0), Some(Vector2::new(0.5, 0.5)));
+    }
+
+    #[test]
+    fn frame_blending() {
+        let mut blender = FrameBlender::default();
+        let mut first = DMatrix::from_element(2, 2, Srgba::new(255, 255, 255, 255));
+        let mut second = DMatrix::from_element(2, 2, Srgba::new(0, 0, 0, 255));
+
+        blender.blend(first.as_view_mut());
+        assert_eq!(first[(0, 0)], Srgba::new(255, 255, 255, 255));
+
+        blender.blend(second.as_view_mut());
+        // Half the light of white is quite a bit brighter than half the srgb value
+        assert!((186..=189).contains(&second[(0, 0)].red));
+    }
 }
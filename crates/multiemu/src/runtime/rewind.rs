@@ -0,0 +1,215 @@
+use crate::machine::{serialization::MachineState, Machine};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, time::Instant};
+
+/// Equal bytes between two changed ones before they are stored as separate runs
+const MERGE_GAP: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RewindConfig {
+    /// Frames between snapshots, one step back goes this far
+    #[serde(default = "default_rewind_interval")]
+    pub interval: u32,
+    /// Snapshots kept before the oldest is dropped
+    #[serde(default = "default_rewind_capacity")]
+    pub capacity: usize,
+}
+
+fn default_rewind_interval() -> u32 {
+    5
+}
+
+fn default_rewind_capacity() -> usize {
+    600
+}
+
+/// How to turn one encoded snapshot into another, only keeping the bytes that changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Delta {
+    length: usize,
+    /// Where each changed run starts, and what it should be
+    runs: Vec<(usize, Vec<u8>)>,
+}
+
+impl Delta {
+    fn new(from: &[u8], to: &[u8]) -> Self {
+        let mut runs = Vec::new();
+        let mut position = 0;
+
+        while position < to.len() {
+            if from.get(position) == Some(&to[position]) {
+                position += 1;
+                continue;
+            }
+
+            // Short stretches of equal bytes cost less inside a run than as the start of a new one
+            let start = position;
+            let mut end = position;
+            while position < to.len() && position - end < MERGE_GAP {
+                if from.get(position) != Some(&to[position]) {
+                    end = position + 1;
+                }
+                position += 1;
+            }
+
+            runs.push((start, to[start..end].to_vec()));
+        }
+
+        Self {
+            length: to.len(),
+            runs,
+        }
+    }
+
+    fn apply(&self, bytes: &mut Vec<u8>) {
+        bytes.resize(self.length, 0);
+
+        for (start, run) in &self.runs {
+            bytes[*start..*start + run.len()].copy_from_slice(run);
+        }
+    }
+}
+
+/// Machine snapshots taken every few frames, for stepping back through what just happened
+///
+/// Only the newest snapshot is kept whole, each older one is a [Delta] from the one after it. Frames next to each
+/// other change little, so most snapshots cost a few kilobytes, and the oldest can be dropped without touching the
+/// rest
+#[derive(Debug)]
+pub struct RewindBuffer {
+    config: RewindConfig,
+    frames_since_capture: u32,
+    /// The newest snapshot, encoded
+    current: Option<Vec<u8>>,
+    /// Deltas back from [Self::current], oldest first
+    history: VecDeque<Delta>,
+    /// Reused for encoding so capturing doesn't allocate a whole snapshot each time
+    scratch: Vec<u8>,
+}
+
+impl RewindBuffer {
+    pub fn new(config: RewindConfig) -> Self {
+        Self {
+            config,
+            frames_since_capture: 0,
+            current: None,
+            history: VecDeque::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Called after every frame the machine runs, capturing it once enough have gone by
+    pub fn frame_finished(&mut self, machine: &Machine) {
+        self.frames_since_capture += 1;
+
+        if self.frames_since_capture >= self.config.interval {
+            self.frames_since_capture = 0;
+            self.capture(machine);
+        }
+    }
+
+    pub fn capture(&mut self, machine: &Machine) {
+        let started = Instant::now();
+
+        let mut encoded = std::mem::take(&mut self.scratch);
+        encoded.clear();
+        if let Err(error) = rmp_serde::encode::write_named(&mut encoded, &machine.state()) {
+            tracing::error!("Could not capture rewind snapshot: {}", error);
+            self.scratch = encoded;
+            return;
+        }
+        self.push(encoded);
+
+        tracing::trace!("Captured rewind snapshot in {:?}", started.elapsed());
+    }
+
+    /// The snapshot before the newest one, which becomes the newest, or None if there is nothing further back
+    pub fn step_back(&mut self) -> Option<MachineState> {
+        let encoded = self.pop()?;
+
+        match rmp_serde::decode::from_slice(encoded) {
+            Ok(state) => Some(state),
+            Err(error) => {
+                tracing::error!("Could not decode rewind snapshot: {}", error);
+                None
+            }
+        }
+    }
+
+    /// Snapshots that can be stepped back to
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    fn push(&mut self, encoded: Vec<u8>) {
+        if let Some(current) = self.current.take() {
+            self.history.push_back(Delta::new(&encoded, &current));
+            self.scratch = current;
+        }
+        self.current = Some(encoded);
+
+        while self.history.len() > self.config.capacity {
+            self.history.pop_front();
+        }
+    }
+
+    fn pop(&mut self) -> Option<&[u8]> {
+        let delta = self.history.pop_back()?;
+        let current = self.current.as_mut()?;
+
+        delta.apply(current);
+        // Captures pick back up a full interval after where the machine now is
+        self.frames_since_capture = 0;
+
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn steps_back_through_deltas() {
+        let mut buffer = RewindBuffer::new(RewindConfig {
+            interval: 1,
+            capacity: 2,
+        });
+        let snapshots = [
+            vec![0; 64],
+            [vec![1; 4], vec![0; 60]].concat(),
+            [vec![1; 4], vec![0; 50], vec![2; 20]].concat(),
+            vec![3; 8],
+        ];
+
+        for snapshot in &snapshots {
+            buffer.push(snapshot.clone());
+        }
+
+        // The first one fell off the end
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.pop(), Some(snapshots[2].as_slice()));
+        assert_eq!(buffer.pop(), Some(snapshots[1].as_slice()));
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn deltas_only_hold_changes() {
+        let from = vec![0; 1024];
+        let mut to = from.clone();
+        to[10] = 1;
+        to[12] = 1;
+        to[500] = 1;
+
+        let delta = Delta::new(&from, &to);
+        assert_eq!(delta.runs, vec![(10, vec![1, 0, 1]), (500, vec![1])]);
+
+        let mut bytes = from.clone();
+        delta.apply(&mut bytes);
+        assert_eq!(bytes, to);
+    }
+}
@@ -0,0 +1,245 @@
+use crate::config::ScalerFilter;
+use nalgebra::{DMatrix, DMatrixView};
+use palette::Srgba;
+
+impl ScalerFilter {
+    /// How many times larger the output is than the input on each axis
+    pub fn factor(&self) -> usize {
+        match self {
+            ScalerFilter::Nearest => 1,
+            ScalerFilter::Scale2x | ScalerFilter::Hq2x => 2,
+            ScalerFilter::Scale3x => 3,
+        }
+    }
+
+    /// Upscales a framebuffer, nearest returns it untouched since the blit already does that
+    pub fn scale(&self, source: DMatrixView<'_, Srgba<u8>>) -> DMatrix<Srgba<u8>> {
+        match self {
+            ScalerFilter::Nearest => source.clone_owned(),
+            ScalerFilter::Scale2x => scale2x(source),
+            ScalerFilter::Scale3x => scale3x(source),
+            ScalerFilter::Hq2x => hq2x(source),
+        }
+    }
+}
+
+/// The 3x3 block around a pixel, clamped at the edges
+///
+/// Laid out row by row, so index 4 is the pixel itself
+fn neighborhood(source: &DMatrixView<'_, Srgba<u8>>, x: usize, y: usize) -> [Srgba<u8>; 9] {
+    let clamp = |position: usize, offset: isize, length: usize| {
+        position.saturating_add_signed(offset).min(length - 1)
+    };
+
+    std::array::from_fn(|index| {
+        let offset_x = (index % 3) as isize - 1;
+        let offset_y = (index / 3) as isize - 1;
+
+        source[(
+            clamp(x, offset_x, source.nrows()),
+            clamp(y, offset_y, source.ncols()),
+        )]
+    })
+}
+
+fn scale2x(source: DMatrixView<'_, Srgba<u8>>) -> DMatrix<Srgba<u8>> {
+    let mut destination = DMatrix::from_element(
+        source.nrows() * 2,
+        source.ncols() * 2,
+        Srgba::new(0, 0, 0, 0xff),
+    );
+
+    for x in 0..source.nrows() {
+        for y in 0..source.ncols() {
+            let [_, up, _, left, center, right, _, down, _] = neighborhood(&source, x, y);
+
+            let (top_left, top_right, bottom_left, bottom_right) = if up != down && left != right {
+                (
+                    if left == up { left } else { center },
+                    if up == right { right } else { center },
+                    if left == down { left } else { center },
+                    if down == right { right } else { center },
+                )
+            } else {
+                (center, center, center, center)
+            };
+
+            destination[(x * 2, y * 2)] = top_left;
+            destination[(x * 2 + 1, y * 2)] = top_right;
+            destination[(x * 2, y * 2 + 1)] = bottom_left;
+            destination[(x * 2 + 1, y * 2 + 1)] = bottom_right;
+        }
+    }
+
+    destination
+}
+
+fn scale3x(source: DMatrixView<'_, Srgba<u8>>) -> DMatrix<Srgba<u8>> {
+    let mut destination = DMatrix::from_element(
+        source.nrows() * 3,
+        source.ncols() * 3,
+        Srgba::new(0, 0, 0, 0xff),
+    );
+
+    for x in 0..source.nrows() {
+        for y in 0..source.ncols() {
+            let [a, b, c, d, e, f, g, h, i] = neighborhood(&source, x, y);
+
+            let output = if b != h && d != f {
+                [
+                    if d == b { d } else { e },
+                    if (d == b && e != c) || (b == f && e != a) {
+                        b
+                    } else {
+                        e
+                    },
+                    if b == f { f } else { e },
+                    if (d == b && e != g) || (d == h && e != a) {
+                        d
+                    } else {
+                        e
+                    },
+                    e,
+                    if (b == f && e != i) || (h == f && e != c) {
+                        f
+                    } else {
+                        e
+                    },
+                    if d == h { d } else { e },
+                    if (d == h && e != i) || (h == f && e != g) {
+                        h
+                    } else {
+                        e
+                    },
+                    if h == f { f } else { e },
+                ]
+            } else {
+                [e; 9]
+            };
+
+            for (index, pixel) in output.into_iter().enumerate() {
+                destination[(x * 3 + index % 3, y * 3 + index / 3)] = pixel;
+            }
+        }
+    }
+
+    destination
+}
+
+/// If two colors are far enough apart in YUV space to count as an edge, using the thresholds from hqx
+fn yuv_differs(first: Srgba<u8>, second: Srgba<u8>) -> bool {
+    let to_yuv = |color: Srgba<u8>| {
+        let (r, g, b) = (color.red as f32, color.green as f32, color.blue as f32);
+
+        (
+            0.299 * r + 0.587 * g + 0.114 * b,
+            -0.169 * r - 0.331 * g + 0.5 * b,
+            0.5 * r - 0.419 * g - 0.081 * b,
+        )
+    };
+
+    let first = to_yuv(first);
+    let second = to_yuv(second);
+
+    (first.0 - second.0).abs() > 48.0
+        || (first.1 - second.1).abs() > 7.0
+        || (first.2 - second.2).abs() > 6.0
+}
+
+fn mix(colors: &[(Srgba<u8>, u16)]) -> Srgba<u8> {
+    let total: u16 = colors.iter().map(|(_, weight)| weight).sum();
+    let channel = |select: fn(&Srgba<u8>) -> u8| {
+        (colors
+            .iter()
+            .map(|(color, weight)| select(color) as u16 * weight)
+            .sum::<u16>()
+            / total) as u8
+    };
+
+    Srgba::new(
+        channel(|color| color.red),
+        channel(|color| color.green),
+        channel(|color| color.blue),
+        channel(|color| color.alpha),
+    )
+}
+
+/// A simplified hq2x
+///
+/// Rather than the full 256 case lookup table this handles each output corner on its own, blending it towards
+/// its neighbors when they form an edge, which gets most of the smooth diagonals for a fraction of the code
+fn hq2x(source: DMatrixView<'_, Srgba<u8>>) -> DMatrix<Srgba<u8>> {
+    let mut destination = DMatrix::from_element(
+        source.nrows() * 2,
+        source.ncols() * 2,
+        Srgba::new(0, 0, 0, 0xff),
+    );
+
+    for x in 0..source.nrows() {
+        for y in 0..source.ncols() {
+            let neighbors = neighborhood(&source, x, y);
+            let center = neighbors[4];
+
+            // For each corner the vertical neighbor, horizontal neighbor, and diagonal neighbor
+            for (corner_x, corner_y, vertical, horizontal, diagonal) in [
+                (0, 0, 1, 3, 0),
+                (1, 0, 1, 5, 2),
+                (0, 1, 7, 3, 6),
+                (1, 1, 7, 5, 8),
+            ] {
+                let vertical = neighbors[vertical];
+                let horizontal = neighbors[horizontal];
+                let diagonal = neighbors[diagonal];
+
+                let pixel = if !yuv_differs(vertical, horizontal) && yuv_differs(center, vertical) {
+                    // Sitting on a diagonal edge, round it off
+                    mix(&[(center, 2), (vertical, 1), (horizontal, 1)])
+                } else if yuv_differs(center, diagonal) {
+                    mix(&[(center, 3), (diagonal, 1)])
+                } else {
+                    center
+                };
+
+                destination[(x * 2 + corner_x, y * 2 + corner_y)] = pixel;
+            }
+        }
+    }
+
+    destination
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scale2x_rounds_diagonals() {
+        let white = Srgba::new(255, 255, 255, 255);
+        let black = Srgba::new(0, 0, 0, 255);
+
+        // A diagonal line of white going from the bottom left to the top right
+        let source = DMatrix::from_fn(3, 3, |x, y| if x + y >= 2 { white } else { black });
+        let destination = ScalerFilter::Scale2x.scale(source.as_view());
+
+        assert_eq!(destination.shape(), (6, 6));
+        // The top left of the center pixel gets pulled towards the black side of the line
+        assert_eq!(destination[(2, 2)], black);
+        assert_eq!(destination[(3, 3)], white);
+    }
+
+    #[test]
+    fn flat_images_are_unchanged() {
+        let source = DMatrix::from_element(4, 4, Srgba::new(12, 34, 56, 255));
+
+        for filter in [
+            ScalerFilter::Scale2x,
+            ScalerFilter::Scale3x,
+            ScalerFilter::Hq2x,
+        ] {
+            let destination = filter.scale(source.as_view());
+
+            assert_eq!(destination.nrows(), 4 * filter.factor());
+            assert!(destination.iter().all(|pixel| *pixel == source[(0, 0)]));
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::runtime::rendering_backend::RawFrame;
+use image::{ImageFormat, RgbaImage};
+use nalgebra::Vector2;
+use num::rational::Ratio;
+use std::{
+    fs::create_dir_all,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    time::Duration,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VideoSinkError {
+    #[error("Could not access {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Could not write image {path}: {error}")]
+    Image {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+    #[error("Could not start ffmpeg: {0}")]
+    Spawn(std::io::Error),
+    #[error("Frame size changed from {from} to {to} while recording")]
+    ExtentChanged { from: String, to: String },
+}
+
+/// Whether a sink wants any more frames after the one it was just given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkStatus {
+    Continue,
+    Done,
+}
+
+/// Something that consumes the machine's finished frames, like a recorder or a streamer
+pub trait VideoSink: Send {
+    /// Name for logs, like "screenshot" or "recording"
+    fn name(&self) -> &str;
+
+    /// Called with every frame the machine finishes, timestamped with emulated time
+    fn frame(
+        &mut self,
+        frame: RawFrame<'_>,
+        timestamp: Duration,
+    ) -> Result<SinkStatus, VideoSinkError>;
+
+    /// Flushes whatever the sink still has buffered, called once when it is removed
+    fn finish(&mut self) -> Result<(), VideoSinkError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SinkId(u32);
+
+/// Every sink currently attached, all fed the same frames
+#[derive(Default)]
+pub struct VideoSinks {
+    next_id: u32,
+    sinks: Vec<(SinkId, Box<dyn VideoSink>)>,
+}
+
+impl VideoSinks {
+    pub fn add(&mut self, sink: impl VideoSink + 'static) -> SinkId {
+        let id = SinkId(self.next_id);
+        self.next_id += 1;
+
+        tracing::info!("Attached {} video sink", sink.name());
+        self.sinks.push((id, Box::new(sink)));
+
+        id
+    }
+
+    /// Detaches and finishes a sink, false if it already went away by itself
+    pub fn remove(&mut self, id: SinkId) -> bool {
+        let Some(index) = self.sinks.iter().position(|(sink_id, _)| *sink_id == id) else {
+            return false;
+        };
+        let (_, sink) = self.sinks.remove(index);
+        finish(sink);
+
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Hands the frame to every sink, detaching the ones that are done or broke
+    pub fn feed(&mut self, frame: RawFrame<'_>, timestamp: Duration) {
+        let mut index = 0;
+
+        while index < self.sinks.len() {
+            let (_, sink) = &mut self.sinks[index];
+
+            match sink.frame(frame, timestamp) {
+                Ok(SinkStatus::Continue) => index += 1,
+                Ok(SinkStatus::Done) => {
+                    let (_, sink) = self.sinks.remove(index);
+                    finish(sink);
+                }
+                Err(error) => {
+                    tracing::error!("{} video sink failed: {}", sink.name(), error);
+                    let (_, sink) = self.sinks.remove(index);
+                    finish(sink);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for VideoSinks {
+    fn drop(&mut self) {
+        for (_, sink) in self.sinks.drain(..) {
+            finish(sink);
+        }
+    }
+}
+
+fn finish(mut sink: Box<dyn VideoSink>) {
+    match sink.finish() {
+        Ok(()) => tracing::info!("Detached {} video sink", sink.name()),
+        Err(error) => tracing::error!("Could not finish {} video sink: {}", sink.name(), error),
+    }
+}
+
+fn to_image(frame: RawFrame<'_>) -> RgbaImage {
+    let mut bytes = Vec::with_capacity(frame.extent.x as usize * frame.extent.y as usize * 4);
+    for y in 0..frame.extent.y {
+        bytes.extend_from_slice(frame.row(y));
+    }
+
+    RgbaImage::from_raw(frame.extent.x, frame.extent.y, bytes)
+        .expect("Frame rows should fill the image exactly")
+}
+
+pub fn save_png(frame: RawFrame<'_>, path: &Path) -> Result<(), VideoSinkError> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).map_err(|error| VideoSinkError::Io {
+            path: parent.to_path_buf(),
+            error,
+        })?;
+    }
+
+    to_image(frame)
+        .save_with_format(path, ImageFormat::Png)
+        .map_err(|error| VideoSinkError::Image {
+            path: path.to_path_buf(),
+            error,
+        })
+}
+
+/// Saves the next frame as a PNG, then detaches itself
+pub struct ScreenshotSink {
+    path: PathBuf,
+}
+
+impl ScreenshotSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl VideoSink for ScreenshotSink {
+    fn name(&self) -> &str {
+        "screenshot"
+    }
+
+    fn frame(&mut self, frame: RawFrame<'_>, _: Duration) -> Result<SinkStatus, VideoSinkError> {
+        save_png(frame, &self.path)?;
+        tracing::info!("Saved screenshot to {}", self.path.display());
+
+        Ok(SinkStatus::Done)
+    }
+}
+
+/// Pipes raw frames into an ffmpeg process, which picks the container and codec from the output extension
+///
+/// ffmpeg is only started on the first frame, since that is when the frame size is known
+pub struct FfmpegSink {
+    output: PathBuf,
+    frame_rate: Ratio<u64>,
+    process: Option<(Child, ChildStdin, Vector2<u32>)>,
+}
+
+impl FfmpegSink {
+    pub fn new(output: impl Into<PathBuf>, frame_rate: Ratio<u64>) -> Self {
+        Self {
+            output: output.into(),
+            frame_rate,
+            process: None,
+        }
+    }
+
+    fn spawn(&self, extent: Vector2<u32>) -> Result<(Child, ChildStdin), VideoSinkError> {
+        if let Some(parent) = self.output.parent() {
+            create_dir_all(parent).map_err(|error| VideoSinkError::Io {
+                path: parent.to_path_buf(),
+                error,
+            })?;
+        }
+
+        let mut child = Command::new("ffmpeg")
+            .args([
+                "-loglevel",
+                "error",
+                "-y",
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "rgba",
+            ])
+            .arg("-s")
+            .arg(format!("{}x{}", extent.x, extent.y))
+            .arg("-r")
+            .arg(format!(
+                "{}/{}",
+                self.frame_rate.numer(),
+                self.frame_rate.denom()
+            ))
+            .args(["-i", "-"])
+            .arg(&self.output)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(VideoSinkError::Spawn)?;
+        let stdin = child.stdin.take().expect("stdin was piped");
+
+        Ok((child, stdin))
+    }
+}
+
+impl VideoSink for FfmpegSink {
+    fn name(&self) -> &str {
+        "ffmpeg"
+    }
+
+    fn frame(&mut self, frame: RawFrame<'_>, _: Duration) -> Result<SinkStatus, VideoSinkError> {
+        if self.process.is_none() {
+            let (child, stdin) = self.spawn(frame.extent)?;
+            self.process = Some((child, stdin, frame.extent));
+        }
+        let (_, stdin, extent) = self.process.as_mut().unwrap();
+
+        // Raw video has no way to say the size changed
+        if *extent != frame.extent {
+            return Err(VideoSinkError::ExtentChanged {
+                from: format!("{}x{}", extent.x, extent.y),
+                to: format!("{}x{}", frame.extent.x, frame.extent.y),
+            });
+        }
+
+        for y in 0..frame.extent.y {
+            stdin
+                .write_all(frame.row(y))
+                .map_err(|error| VideoSinkError::Io {
+                    path: self.output.clone(),
+                    error,
+                })?;
+        }
+
+        Ok(SinkStatus::Continue)
+    }
+
+    fn finish(&mut self) -> Result<(), VideoSinkError> {
+        let Some((mut child, stdin, _)) = self.process.take() else {
+            return Ok(());
+        };
+        // Closing stdin is what tells ffmpeg the video is over
+        drop(stdin);
+
+        child.wait().map_err(|error| VideoSinkError::Io {
+            path: self.output.clone(),
+            error,
+        })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nalgebra::DMatrix;
+    use palette::Srgba;
+
+    struct Counter(u32);
+
+    impl VideoSink for Counter {
+        fn name(&self) -> &str {
+            "counter"
+        }
+
+        fn frame(&mut self, _: RawFrame<'_>, _: Duration) -> Result<SinkStatus, VideoSinkError> {
+            self.0 += 1;
+
+            Ok(if self.0 == 2 {
+                SinkStatus::Done
+            } else {
+                SinkStatus::Continue
+            })
+        }
+    }
+
+    #[test]
+    fn sinks_detach_when_done() {
+        let framebuffer = DMatrix::from_element(2, 2, Srgba::new(1, 2, 3, 4));
+        let mut sinks = VideoSinks::default();
+        let counter = sinks.add(Counter(0));
+        let forever = sinks.add(Counter(5));
+
+        sinks.feed(RawFrame::new(&framebuffer), Duration::ZERO);
+        sinks.feed(RawFrame::new(&framebuffer), Duration::ZERO);
+        assert!(!sinks.remove(counter));
+        assert!(sinks.remove(forever));
+        assert!(sinks.is_empty());
+    }
+
+    #[test]
+    fn screenshot_matches_frame() {
+        let mut framebuffer = DMatrix::from_element(3, 2, Srgba::new(0, 0, 0, 0xff));
+        framebuffer[(2, 1)] = Srgba::new(0xff, 0, 0, 0xff);
+        let path = std::env::temp_dir().join(format!(
+            "multiemu-screenshot-test-{}.png",
+            std::process::id()
+        ));
+
+        let mut sinks = VideoSinks::default();
+        sinks.add(ScreenshotSink::new(&path));
+        sinks.feed(RawFrame::new(&framebuffer), Duration::ZERO);
+        assert!(sinks.is_empty());
+
+        let image = image::open(&path).unwrap().to_rgba8();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(image.dimensions(), (3, 2));
+        assert_eq!(image.get_pixel(2, 1).0, [0xff, 0, 0, 0xff]);
+        assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0xff]);
+    }
+}
@@ -0,0 +1,183 @@
+use super::{
+    manager::{SaveError, SaveManager},
+    snapshot::{encode_snapshot, SnapshotError, SnapshotStore},
+};
+use crate::{config::GLOBAL_CONFIG, machine::Machine};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("Machine was not started from ROMs, so there is nothing to archive against")]
+    NoRom,
+    #[error("Could not access {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Could not write archive: {0}")]
+    Write(#[from] std::io::Error),
+    #[error("Could not write archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Could not write config: {0}")]
+    Config(#[from] ron::Error),
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error(transparent)]
+    Save(#[from] SaveError),
+}
+
+/// Everything that makes up a session with a ROM, zipped up for bug reports or for carrying over to another computer
+///
+/// Laid out as
+/// - `session.snapshot`, the machine as it is right now, in the same format as a snapshot slot
+/// - `snapshots/<slot>.snapshot`, every slot the ROM has
+/// - `saves/<rom id>.sav`, battery saves for each ROM the machine was started with
+/// - `screenshot.webp`, the main display right now, if there is a software framebuffer to take it from
+/// - `config.ron`, the settings the session ran with
+/// - `recording.mp4`, the recording, if one was made
+pub fn write_machine_archive(
+    path: impl AsRef<Path>,
+    machine: &Machine,
+    snapshot_store: &SnapshotStore,
+    save_manager: &SaveManager,
+    recording: Option<&Path>,
+) -> Result<(), ArchiveError> {
+    let rom_ids = machine
+        .user_specified_roms
+        .as_deref()
+        .filter(|roms| !roms.is_empty())
+        .ok_or(ArchiveError::NoRom)?;
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|error| ArchiveError::Io {
+            path: parent.to_path_buf(),
+            error,
+        })?;
+    }
+    let file = File::create(path).map_err(|error| ArchiveError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+
+    zip.start_file("session.snapshot", options)?;
+    let metadata = encode_snapshot(machine, 0, &mut zip)?;
+
+    // Slots are only kept against the first ROM, like the pause menu does
+    for slot in snapshot_store.list(rom_ids[0]) {
+        let slot_path = snapshot_store.snapshot_path(rom_ids[0], slot.slot);
+        let contents = fs::read(&slot_path).map_err(|error| ArchiveError::Io {
+            path: slot_path,
+            error,
+        })?;
+
+        zip.start_file(format!("snapshots/{}.snapshot", slot.slot), options)?;
+        zip.write_all(&contents)?;
+    }
+
+    for rom_id in rom_ids {
+        if let Some(contents) = save_manager.load(machine.system, *rom_id)? {
+            zip.start_file(format!("saves/{}.sav", rom_id), options)?;
+            zip.write_all(&contents)?;
+        }
+    }
+
+    if let Some(screenshot) = &metadata.screenshot {
+        zip.start_file("screenshot.webp", options)?;
+        zip.write_all(screenshot)?;
+    }
+
+    zip.start_file("config.ron", options)?;
+    zip.write_all(
+        ron::ser::to_string_pretty(&*GLOBAL_CONFIG.read().unwrap(), Default::default())?.as_bytes(),
+    )?;
+
+    if let Some(recording) = recording {
+        let contents = fs::read(recording).map_err(|error| ArchiveError::Io {
+            path: recording.to_path_buf(),
+            error,
+        })?;
+
+        zip.start_file("recording.mp4", options)?;
+        zip.write_all(&contents)?;
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        config::SaveLocation,
+        rom::{
+            id::RomId,
+            manager::RomManager,
+            system::{GameSystem, OtherSystem},
+        },
+        runtime::rendering_backend::DisplayComponentInitializationData,
+    };
+    use std::sync::Arc;
+    use zip::ZipArchive;
+
+    #[test]
+    fn archive_contents() {
+        let directory =
+            std::env::temp_dir().join(format!("multiemu-archive-test-{}", std::process::id()));
+        let rom_path = directory.join("rom.ch8");
+        fs::create_dir_all(&directory).unwrap();
+        // Jumps to itself forever
+        fs::write(&rom_path, [0x12, 0x00]).unwrap();
+
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let rom_id = RomId::from_read(&mut File::open(&rom_path).unwrap());
+        rom_manager.rom_paths.insert(rom_id, rom_path);
+
+        let system = GameSystem::Other(OtherSystem::Chip8);
+        let machine = Machine::from_system(vec![rom_id], rom_manager.clone(), system);
+        for display in machine.display_components() {
+            display
+                .component
+                .set_display_data(DisplayComponentInitializationData::Software);
+        }
+
+        let snapshot_store = SnapshotStore::new(directory.join("snapshots"));
+        snapshot_store.store(&machine, rom_id, 2).unwrap();
+        let save_manager = SaveManager::new(
+            directory.join("saves"),
+            SaveLocation::Directory,
+            rom_manager,
+        );
+        save_manager.store(system, rom_id, &[1, 2, 3]).unwrap();
+
+        let path = directory.join("session.zip");
+        write_machine_archive(&path, &machine, &snapshot_store, &save_manager, None).unwrap();
+
+        let archive = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let battery_save = format!("saves/{}.sav", rom_id);
+        let mut names: Vec<_> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "config.ron",
+                battery_save.as_str(),
+                "screenshot.webp",
+                "session.snapshot",
+                "snapshots/2.snapshot",
+            ]
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
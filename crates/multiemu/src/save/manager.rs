@@ -0,0 +1,216 @@
+use crate::{
+    config::SaveLocation,
+    rom::{id::RomId, info::RomInfo, manager::RomManager, system::GameSystem},
+};
+use std::{
+    fs::{self, read_dir},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+
+const BATTERY_SAVE_FILE_NAME: &str = "battery.sav";
+
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("Could not access save file {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("No save exists for ROM {0}")]
+    Missing(RomId),
+}
+
+/// Keeps track of where battery backed saves live and makes sure writing them can't lose the old one
+///
+/// Saves are laid out as `<save directory>/<system>/<rom id>/battery.sav`, unless the user asked for them
+/// to go next to their ROMs
+#[derive(Debug)]
+pub struct SaveManager {
+    directory: PathBuf,
+    location: SaveLocation,
+    rom_manager: Arc<RomManager>,
+}
+
+impl SaveManager {
+    pub fn new(directory: PathBuf, location: SaveLocation, rom_manager: Arc<RomManager>) -> Self {
+        Self {
+            directory,
+            location,
+            rom_manager,
+        }
+    }
+
+    /// The system a ROM was registered under, saves for ROMs the database doesn't know go under unknown
+    pub fn system_of(&self, rom_id: RomId) -> GameSystem {
+        self.rom_manager
+            .rom_information
+            .r_transaction()
+            .ok()
+            .and_then(|transaction| transaction.get().primary::<RomInfo>(rom_id).ok().flatten())
+            .map(|rom_info| rom_info.system)
+            .unwrap_or_default()
+    }
+
+    /// Where the save for a ROM is, or would be once written
+    pub fn save_path(&self, system: GameSystem, rom_id: RomId) -> PathBuf {
+        if self.location == SaveLocation::NextToRom {
+            // ROMs in our own store are named after their hash, so this is only useful for ones loaded from elsewhere
+            if let Some(rom_path) = self.rom_manager.rom_paths.get(&rom_id) {
+                return rom_path.value().with_extension("sav");
+            }
+        }
+
+        self.directory
+            .join(system.to_string())
+            .join(rom_id.to_string())
+            .join(BATTERY_SAVE_FILE_NAME)
+    }
+
+    pub fn load(&self, system: GameSystem, rom_id: RomId) -> Result<Option<Vec<u8>>, SaveError> {
+        let path = self.save_path(system, rom_id);
+
+        match fs::read(&path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(SaveError::Io { path, error }),
+        }
+    }
+
+    /// Writes a save, the previous one is kept as a backup and the new one is renamed into place
+    pub fn store(
+        &self,
+        system: GameSystem,
+        rom_id: RomId,
+        contents: &[u8],
+    ) -> Result<(), SaveError> {
+        write_save(&self.save_path(system, rom_id), contents)
+    }
+
+    /// Copies a save out as a plain file, for other emulators or flash carts
+    pub fn export(
+        &self,
+        system: GameSystem,
+        rom_id: RomId,
+        destination: impl AsRef<Path>,
+    ) -> Result<(), SaveError> {
+        let contents = self
+            .load(system, rom_id)?
+            .ok_or(SaveError::Missing(rom_id))?;
+        let destination = destination.as_ref();
+
+        fs::write(destination, contents).map_err(|error| SaveError::Io {
+            path: destination.to_path_buf(),
+            error,
+        })
+    }
+
+    pub fn import(
+        &self,
+        system: GameSystem,
+        rom_id: RomId,
+        source: impl AsRef<Path>,
+    ) -> Result<(), SaveError> {
+        let source = source.as_ref();
+        let contents = fs::read(source).map_err(|error| SaveError::Io {
+            path: source.to_path_buf(),
+            error,
+        })?;
+
+        self.store(system, rom_id, &contents)
+    }
+
+    /// Every ROM with a save in the save directory, saves next to ROMs aren't tracked
+    pub fn saves(&self) -> Vec<(GameSystem, RomId)> {
+        let mut saves = Vec::new();
+
+        for system in GameSystem::iter().chain(std::iter::once(GameSystem::Unknown)) {
+            let Ok(entries) = read_dir(self.directory.join(system.to_string())) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                if let Some(rom_id) = entry
+                    .file_name()
+                    .to_str()
+                    // RomId parsing expects exactly a sha1 worth of hex
+                    .filter(|name| name.len() == 40)
+                    .and_then(|name| name.parse().ok())
+                    .filter(|_| entry.path().join(BATTERY_SAVE_FILE_NAME).is_file())
+                {
+                    saves.push((system, rom_id));
+                }
+            }
+        }
+
+        saves
+    }
+}
+
+/// Same path with another extension tacked on, so "game.sav" becomes "game.sav.bak"
+pub(super) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(suffix);
+
+    path.with_file_name(file_name)
+}
+
+/// Writes to a temporary file first and keeps the old contents around as a backup, so a crash can't eat a save
+pub(super) fn write_save(path: &Path, contents: &[u8]) -> Result<(), SaveError> {
+    let io_error = |error| SaveError::Io {
+        path: path.to_path_buf(),
+        error,
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+
+    let temporary_path = with_suffix(path, "tmp");
+    fs::write(&temporary_path, contents).map_err(io_error)?;
+
+    if path.is_file() {
+        fs::copy(path, with_suffix(path, "bak")).map_err(io_error)?;
+    }
+
+    fs::rename(&temporary_path, path).map_err(io_error)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::system::OtherSystem;
+
+    #[test]
+    fn store_keeps_backup() {
+        let directory =
+            std::env::temp_dir().join(format!("multiemu-save-test-{}", std::process::id()));
+        let save_manager = SaveManager::new(
+            directory.clone(),
+            SaveLocation::Directory,
+            Arc::new(RomManager::new(None).unwrap()),
+        );
+        let system = GameSystem::Other(OtherSystem::Chip8);
+        let rom_id = RomId::new([1; 20]);
+
+        assert!(save_manager.load(system, rom_id).unwrap().is_none());
+
+        save_manager.store(system, rom_id, &[1, 2, 3]).unwrap();
+        save_manager.store(system, rom_id, &[4, 5, 6]).unwrap();
+
+        let path = save_manager.save_path(system, rom_id);
+        assert_eq!(
+            save_manager.load(system, rom_id).unwrap(),
+            Some(vec![4, 5, 6])
+        );
+        assert_eq!(fs::read(with_suffix(&path, "bak")).unwrap(), vec![1, 2, 3]);
+        assert_eq!(save_manager.saves(), vec![(system, rom_id)]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
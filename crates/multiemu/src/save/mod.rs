@@ -0,0 +1,5 @@
+#[cfg(platform_desktop)]
+pub mod archive;
+pub mod manager;
+pub mod snapshot;
+pub mod sync;
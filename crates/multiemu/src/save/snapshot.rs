@@ -0,0 +1,303 @@
+use crate::{
+    machine::{clock::MachineTimestamp, serialization::MachineState, Machine},
+    rom::id::RomId,
+};
+use image::{ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+use std::{
+    fs::{self, read_dir, File},
+    io::{BufReader, BufWriter, Cursor, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+
+const SNAPSHOT_EXTENSION: &str = "snapshot";
+
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    #[error("Could not access snapshot {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Could not write snapshot: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error("Could not read snapshot: {0}")]
+    Decode(#[from] rmp_serde::decode::Error),
+    #[error("No snapshot in slot {slot} for ROM {rom_id}")]
+    Missing { rom_id: RomId, slot: u8 },
+}
+
+/// Written in front of the machine state so listing snapshots doesn't have to read the whole thing
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotMetadata {
+    pub slot: u8,
+    /// When the snapshot was taken on the host
+    pub created: SystemTime,
+    pub timestamp: MachineTimestamp,
+    /// Time the user had been playing when the snapshot was taken
+    pub play_time: Duration,
+    /// WebP of the main display, missing for machines without a display or with hardware framebuffers
+    #[serde_as(as = "Option<Bytes>")]
+    pub screenshot: Option<Vec<u8>>,
+}
+
+impl SnapshotMetadata {
+    /// Decodes the embedded screenshot, left to the caller so listing many snapshots stays cheap
+    pub fn screenshot(&self) -> Option<RgbaImage> {
+        let screenshot = self.screenshot.as_ref()?;
+
+        match image::load_from_memory_with_format(screenshot, ImageFormat::WebP) {
+            Ok(image) => Some(image.into_rgba8()),
+            Err(error) => {
+                tracing::warn!(
+                    "Snapshot in slot {} has a broken screenshot: {}",
+                    self.slot,
+                    error
+                );
+                None
+            }
+        }
+    }
+}
+
+/// Numbered save state slots for each ROM
+///
+/// Snapshots are laid out as `<snapshot directory>/<rom id>/<slot>.snapshot`
+#[derive(Debug)]
+pub struct SnapshotStore {
+    directory: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+
+    pub fn snapshot_path(&self, rom_id: RomId, slot: u8) -> PathBuf {
+        self.directory
+            .join(rom_id.to_string())
+            .join(slot.to_string())
+            .with_extension(SNAPSHOT_EXTENSION)
+    }
+
+    pub fn store(
+        &self,
+        machine: &Machine,
+        rom_id: RomId,
+        slot: u8,
+    ) -> Result<SnapshotMetadata, SnapshotError> {
+        write_snapshot(machine, &self.snapshot_path(rom_id, slot), slot)
+    }
+
+    pub fn load(&self, rom_id: RomId, slot: u8) -> Result<MachineState, SnapshotError> {
+        let mut reader = self.open(rom_id, slot)?;
+        let _: SnapshotMetadata = rmp_serde::decode::from_read(&mut reader)?;
+
+        Ok(rmp_serde::decode::from_read(&mut reader)?)
+    }
+
+    pub fn metadata(&self, rom_id: RomId, slot: u8) -> Result<SnapshotMetadata, SnapshotError> {
+        let mut reader = self.open(rom_id, slot)?;
+
+        Ok(rmp_serde::decode::from_read(&mut reader)?)
+    }
+
+    /// Every snapshot a ROM has, sorted by slot
+    pub fn list(&self, rom_id: RomId) -> Vec<SnapshotMetadata> {
+        let directory = self.directory.join(rom_id.to_string());
+        let Ok(entries) = read_dir(&directory) else {
+            return Vec::new();
+        };
+
+        let mut snapshots: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter_map(|path| slot_of(&path))
+            .filter_map(|slot| match self.metadata(rom_id, slot) {
+                Ok(metadata) => Some(metadata),
+                Err(error) => {
+                    tracing::warn!(
+                        "Skipping snapshot in slot {} for {}: {}",
+                        slot,
+                        rom_id,
+                        error
+                    );
+                    None
+                }
+            })
+            .collect();
+
+        snapshots.sort_by_key(|metadata| metadata.slot);
+        snapshots
+    }
+
+    pub fn delete(&self, rom_id: RomId, slot: u8) -> Result<(), SnapshotError> {
+        let path = self.snapshot_path(rom_id, slot);
+
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                Err(SnapshotError::Missing { rom_id, slot })
+            }
+            Err(error) => Err(SnapshotError::Io { path, error }),
+        }
+    }
+
+    fn open(&self, rom_id: RomId, slot: u8) -> Result<BufReader<File>, SnapshotError> {
+        let path = self.snapshot_path(rom_id, slot);
+
+        match File::open(&path) {
+            Ok(file) => Ok(BufReader::new(file)),
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                Err(SnapshotError::Missing { rom_id, slot })
+            }
+            Err(error) => Err(SnapshotError::Io { path, error }),
+        }
+    }
+}
+
+/// Writes a snapshot file for the machine as it is right now, in the same layout [SnapshotStore] keeps its slots in
+pub fn write_snapshot(
+    machine: &Machine,
+    path: &Path,
+    slot: u8,
+) -> Result<SnapshotMetadata, SnapshotError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|error| SnapshotError::Io {
+            path: parent.to_path_buf(),
+            error,
+        })?;
+    }
+
+    // Written next to the old one and moved over it, so a crash can't leave a slot half written
+    let temporary_path = path.with_extension("tmp");
+    let file = File::create(&temporary_path).map_err(|error| SnapshotError::Io {
+        path: temporary_path.clone(),
+        error,
+    })?;
+    let mut writer = BufWriter::new(file);
+
+    let metadata = encode_snapshot(machine, slot, &mut writer)?;
+    writer.flush().map_err(|error| SnapshotError::Io {
+        path: temporary_path.clone(),
+        error,
+    })?;
+
+    fs::rename(&temporary_path, path).map_err(|error| SnapshotError::Io {
+        path: path.to_path_buf(),
+        error,
+    })?;
+
+    Ok(metadata)
+}
+
+/// Writes the metadata and machine state of a snapshot file to anything, like a file inside an archive
+pub fn encode_snapshot(
+    machine: &Machine,
+    slot: u8,
+    writer: &mut impl Write,
+) -> Result<SnapshotMetadata, SnapshotError> {
+    let metadata = SnapshotMetadata {
+        slot,
+        created: SystemTime::now(),
+        timestamp: machine.clock.now(),
+        play_time: machine.clock.wall_time(),
+        screenshot: capture_screenshot(machine),
+    };
+
+    rmp_serde::encode::write_named(writer, &metadata)?;
+    rmp_serde::encode::write_named(writer, &machine.state())?;
+
+    Ok(metadata)
+}
+
+/// Encodes the main display as a WebP
+fn capture_screenshot(machine: &Machine) -> Option<Vec<u8>> {
+    // Hardware framebuffers would need a readback
+    let image = machine.read_raw_frame(|frame| {
+        RgbaImage::from_raw(frame.extent.x, frame.extent.y, frame.bytes.to_vec())
+    })??;
+
+    let mut encoded = Cursor::new(Vec::new());
+    match image.write_to(&mut encoded, ImageFormat::WebP) {
+        Ok(()) => Some(encoded.into_inner()),
+        Err(error) => {
+            tracing::warn!("Could not encode snapshot screenshot: {}", error);
+            None
+        }
+    }
+}
+
+/// Slot a snapshot file is for, if it looks like one of ours
+fn slot_of(path: &Path) -> Option<u8> {
+    if path.extension()? != SNAPSHOT_EXTENSION {
+        return None;
+    }
+
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        rom::{
+            manager::RomManager,
+            system::{GameSystem, OtherSystem},
+        },
+        runtime::rendering_backend::DisplayComponentInitializationData,
+    };
+    use std::{fs::write, sync::Arc};
+
+    #[test]
+    fn store_and_list() {
+        let directory =
+            std::env::temp_dir().join(format!("multiemu-snapshot-test-{}", std::process::id()));
+        let rom_path = directory.join("rom.ch8");
+        fs::create_dir_all(&directory).unwrap();
+        // Jumps to itself forever
+        write(&rom_path, [0x12, 0x00]).unwrap();
+
+        let rom_manager = Arc::new(RomManager::new(None).unwrap());
+        let rom_id = RomId::from_read(&mut File::open(&rom_path).unwrap());
+        rom_manager.rom_paths.insert(rom_id, rom_path);
+
+        let machine = Machine::from_system(
+            vec![rom_id],
+            rom_manager,
+            GameSystem::Other(OtherSystem::Chip8),
+        );
+        for display in machine.display_components() {
+            display
+                .component
+                .set_display_data(DisplayComponentInitializationData::Software);
+        }
+
+        let store = SnapshotStore::new(directory.join("snapshots"));
+        store.store(&machine, rom_id, 3).unwrap();
+        store.store(&machine, rom_id, 1).unwrap();
+
+        let snapshots = store.list(rom_id);
+        assert_eq!(
+            snapshots
+                .iter()
+                .map(|metadata| metadata.slot)
+                .collect::<Vec<_>>(),
+            [1, 3]
+        );
+        assert!(snapshots[0].screenshot().is_some());
+        assert!(store.load(rom_id, 3).is_ok());
+
+        store.delete(rom_id, 3).unwrap();
+        assert!(matches!(
+            store.load(rom_id, 3),
+            Err(SnapshotError::Missing { slot: 3, .. })
+        ));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,29 @@
+use super::{SyncBackend, SyncError};
+use crate::save::manager::write_save;
+use std::{fs, io::ErrorKind, path::PathBuf};
+
+/// Copies saves into a plain folder
+#[derive(Debug)]
+pub struct FolderBackend {
+    directory: PathBuf,
+}
+
+impl FolderBackend {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+}
+
+impl SyncBackend for FolderBackend {
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        match fs::read(self.directory.join(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn put(&self, name: &str, contents: &[u8]) -> Result<(), SyncError> {
+        Ok(write_save(&self.directory.join(name), contents)?)
+    }
+}
@@ -0,0 +1,304 @@
+use super::manager::{with_suffix, write_save, SaveError, SaveManager};
+use crate::rom::{id::RomId, system::GameSystem};
+use data_encoding::HEXLOWER;
+use folder::FolderBackend;
+use rsync::RsyncBackend;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::{
+    fmt::Debug,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+use webdav::WebDavBackend;
+
+pub mod folder;
+pub mod rsync;
+pub mod webdav;
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("{0}")]
+    Save(#[from] SaveError),
+    #[error("Sync backend IO failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Server answered {status} for {url}")]
+    Http { status: u16, url: String },
+    #[error("Unsupported sync url {0}, only plain http is supported")]
+    UnsupportedUrl(String),
+    #[error("Sync command failed: {0}")]
+    Command(String),
+    #[error("Remote copy of {0} does not match its recorded hash")]
+    Corrupt(String),
+}
+
+/// Somewhere saves can be copied to and from
+///
+/// Backends only have to move bytes around under a flat name, the bookkeeping is done by the [SaveManager]
+pub trait SyncBackend: Debug + Send + Sync {
+    /// Returns None if nothing is stored under the name
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, SyncError>;
+    fn put(&self, name: &str, contents: &[u8]) -> Result<(), SyncError>;
+}
+
+/// Where saves are synced to, None in the config disables syncing
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum SyncBackendConfig {
+    /// A folder, useful with network mounts or a folder another program syncs
+    Folder(PathBuf),
+    WebDav {
+        /// Collection saves go in, like "http://nas.local/dav/saves/"
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+    /// Anything rsync understands as a destination, like "user@host:saves"
+    Rsync(String),
+}
+
+impl SyncBackendConfig {
+    pub fn build(&self) -> Result<Box<dyn SyncBackend>, SyncError> {
+        Ok(match self {
+            SyncBackendConfig::Folder(path) => Box::new(FolderBackend::new(path.clone())),
+            SyncBackendConfig::WebDav {
+                url,
+                username,
+                password,
+            } => Box::new(WebDavBackend::new(
+                url,
+                username.clone().zip(password.clone()),
+            )?),
+            SyncBackendConfig::Rsync(destination) => {
+                Box::new(RsyncBackend::new(destination.clone()))
+            }
+        })
+    }
+}
+
+/// What a save looked like at some point, stored next to it on both ends to detect changes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SyncRecord {
+    /// Sha-1 of the contents in hex
+    pub hash: String,
+    /// Seconds since the unix epoch the save was last written
+    pub modified: u64,
+}
+
+impl SyncRecord {
+    fn new(contents: &[u8], modified: SystemTime) -> Self {
+        Self {
+            hash: HEXLOWER.encode(&Sha1::digest(contents)),
+            modified: modified
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncSide {
+    Local,
+    Remote,
+}
+
+/// Both copies changed since the last sync, so neither can be picked without losing something
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncConflict {
+    pub local: SyncRecord,
+    pub remote: SyncRecord,
+}
+
+impl SyncConflict {
+    /// Which side was written last, local wins ties
+    pub fn newer(&self) -> SyncSide {
+        if self.remote.modified > self.local.modified {
+            SyncSide::Remote
+        } else {
+            SyncSide::Local
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// Neither side has the save
+    Empty,
+    UpToDate,
+    Uploaded,
+    Downloaded,
+    Conflict(SyncConflict),
+}
+
+impl SaveManager {
+    /// Syncs the battery save of a ROM, it is stored remotely under the ROM id since that is unique across systems
+    pub fn sync(
+        &self,
+        system: GameSystem,
+        rom_id: RomId,
+        backend: &dyn SyncBackend,
+    ) -> Result<SyncOutcome, SyncError> {
+        sync_file(
+            &self.save_path(system, rom_id),
+            &format!("{}.sav", rom_id),
+            backend,
+            None,
+        )
+    }
+
+    /// Overwrites one side of a conflict with the other
+    pub fn resolve_conflict(
+        &self,
+        system: GameSystem,
+        rom_id: RomId,
+        backend: &dyn SyncBackend,
+        keep: SyncSide,
+    ) -> Result<SyncOutcome, SyncError> {
+        sync_file(
+            &self.save_path(system, rom_id),
+            &format!("{}.sav", rom_id),
+            backend,
+            Some(keep),
+        )
+    }
+}
+
+/// Syncs any file, like a snapshot, under the given remote name
+///
+/// The hash of what was last synced is kept beside the file, so we can tell which side changed since then.
+/// If both did it is reported as a conflict unless `force` picks a side.
+pub fn sync_file(
+    path: &Path,
+    name: &str,
+    backend: &dyn SyncBackend,
+    force: Option<SyncSide>,
+) -> Result<SyncOutcome, SyncError> {
+    let record_name = format!("{}.record", name);
+    let last_sync_path = with_suffix(path, "sync");
+
+    let local_contents = read_optional(path)?;
+    let local = local_contents
+        .as_ref()
+        .map(|contents| -> Result<_, SyncError> {
+            Ok(SyncRecord::new(contents, fs::metadata(path)?.modified()?))
+        })
+        .transpose()?;
+    let remote = backend
+        .get(&record_name)?
+        .and_then(|record| ron::de::from_bytes::<SyncRecord>(&record).ok());
+    let last_sync = read_optional(&last_sync_path)?
+        .and_then(|record| ron::de::from_bytes::<SyncRecord>(&record).ok());
+
+    let direction = match (&local, &remote, force) {
+        (None, None, _) => return Ok(SyncOutcome::Empty),
+        (Some(local), Some(remote), _) if local.hash == remote.hash => {
+            fs::write(&last_sync_path, ron::to_string(local).unwrap())?;
+            return Ok(SyncOutcome::UpToDate);
+        }
+        (Some(_), None, _) => SyncSide::Local,
+        (None, Some(_), _) => SyncSide::Remote,
+        (Some(_), Some(_), Some(keep)) => keep,
+        (Some(local), Some(remote), None) => {
+            let last_sync_hash = last_sync.map(|record| record.hash);
+
+            if last_sync_hash.as_ref() == Some(&local.hash) {
+                SyncSide::Remote
+            } else if last_sync_hash.as_ref() == Some(&remote.hash) {
+                SyncSide::Local
+            } else {
+                return Ok(SyncOutcome::Conflict(SyncConflict {
+                    local: local.clone(),
+                    remote: remote.clone(),
+                }));
+            }
+        }
+    };
+
+    match direction {
+        SyncSide::Local => {
+            let (Some(contents), Some(local)) = (local_contents, local) else {
+                unreachable!()
+            };
+            let record = ron::to_string(&local).unwrap();
+
+            // Contents go first so a half finished upload never has a record vouching for it
+            backend.put(name, &contents)?;
+            backend.put(&record_name, record.as_bytes())?;
+            fs::write(&last_sync_path, record)?;
+
+            Ok(SyncOutcome::Uploaded)
+        }
+        SyncSide::Remote => {
+            let remote = remote.unwrap();
+            let contents = backend
+                .get(name)?
+                .ok_or_else(|| SyncError::Corrupt(name.to_string()))?;
+
+            if SyncRecord::new(&contents, UNIX_EPOCH).hash != remote.hash {
+                return Err(SyncError::Corrupt(name.to_string()));
+            }
+
+            write_save(path, &contents)?;
+            fs::write(&last_sync_path, ron::to_string(&remote).unwrap())?;
+
+            Ok(SyncOutcome::Downloaded)
+        }
+    }
+}
+
+fn read_optional(path: &Path) -> Result<Option<Vec<u8>>, std::io::Error> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_conflicts() {
+        let directory =
+            std::env::temp_dir().join(format!("multiemu-sync-test-{}", std::process::id()));
+        let remote = FolderBackend::new(directory.join("remote"));
+        let first = directory.join("first").join("game.sav");
+        let second = directory.join("second").join("game.sav");
+
+        write_save(&first, &[1]).unwrap();
+        assert_eq!(
+            sync_file(&first, "game.sav", &remote, None).unwrap(),
+            SyncOutcome::Uploaded
+        );
+        assert_eq!(
+            sync_file(&second, "game.sav", &remote, None).unwrap(),
+            SyncOutcome::Downloaded
+        );
+        assert_eq!(fs::read(&second).unwrap(), vec![1]);
+
+        // Only one side changed, so it can be pushed
+        write_save(&second, &[2]).unwrap();
+        assert_eq!(
+            sync_file(&second, "game.sav", &remote, None).unwrap(),
+            SyncOutcome::Uploaded
+        );
+
+        // Now the first machine changed its copy without pulling the newer one
+        write_save(&first, &[3]).unwrap();
+        assert!(matches!(
+            sync_file(&first, "game.sav", &remote, None).unwrap(),
+            SyncOutcome::Conflict(_)
+        ));
+        assert_eq!(
+            sync_file(&first, "game.sav", &remote, Some(SyncSide::Remote)).unwrap(),
+            SyncOutcome::Downloaded
+        );
+        assert_eq!(fs::read(&first).unwrap(), vec![2]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+use super::{SyncBackend, SyncError};
+use std::{fs, process::Command};
+
+/// Exit code rsync uses when some files could not be transferred, which is what a missing file looks like
+const RSYNC_PARTIAL_TRANSFER: i32 = 23;
+
+/// Shells out to rsync, so anything it can reach over ssh works as a destination
+#[derive(Debug)]
+pub struct RsyncBackend {
+    destination: String,
+}
+
+impl RsyncBackend {
+    pub fn new(destination: String) -> Self {
+        Self {
+            destination: destination.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn run(&self, source: &str, destination: &str) -> Result<Option<()>, SyncError> {
+        let status = Command::new("rsync")
+            .args(["--quiet", "--times", source, destination])
+            .status()?;
+
+        match status.code() {
+            Some(0) => Ok(Some(())),
+            Some(RSYNC_PARTIAL_TRANSFER) => Ok(None),
+            _ => Err(SyncError::Command(format!(
+                "rsync {} {} exited with {}",
+                source, destination, status
+            ))),
+        }
+    }
+}
+
+impl SyncBackend for RsyncBackend {
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        let local = std::env::temp_dir().join(format!("multiemu-rsync-{}", name));
+        let remote = format!("{}/{}", self.destination, name);
+
+        if self.run(&remote, &local.to_string_lossy())?.is_none() {
+            return Ok(None);
+        }
+
+        let contents = fs::read(&local)?;
+        fs::remove_file(&local)?;
+
+        Ok(Some(contents))
+    }
+
+    fn put(&self, name: &str, contents: &[u8]) -> Result<(), SyncError> {
+        let local = std::env::temp_dir().join(format!("multiemu-rsync-{}", name));
+        let remote = format!("{}/{}", self.destination, name);
+
+        fs::write(&local, contents)?;
+        let result = self.run(&local.to_string_lossy(), &remote);
+        fs::remove_file(&local)?;
+
+        result?.ok_or_else(|| SyncError::Command(format!("rsync could not write {}", remote)))
+    }
+}
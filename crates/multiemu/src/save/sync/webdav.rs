@@ -0,0 +1,162 @@
+use super::{SyncBackend, SyncError};
+use data_encoding::BASE64;
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+};
+
+/// Talks just enough HTTP/1.1 to GET and PUT files in a WebDAV collection
+///
+/// There is no TLS library in our dependencies, so this is for servers on the local network
+#[derive(Debug)]
+pub struct WebDavBackend {
+    host: String,
+    port: u16,
+    path: String,
+    authorization: Option<String>,
+}
+
+impl WebDavBackend {
+    pub fn new(url: &str, credentials: Option<(String, String)>) -> Result<Self, SyncError> {
+        let Some(remainder) = url.strip_prefix("http://") else {
+            return Err(SyncError::UnsupportedUrl(url.to_string()));
+        };
+
+        let (authority, path) = remainder
+            .split_once('/')
+            .map(|(authority, path)| (authority, format!("/{}", path)))
+            .unwrap_or((remainder, "/".to_string()));
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host.to_string(),
+                port.parse()
+                    .map_err(|_| SyncError::UnsupportedUrl(url.to_string()))?,
+            ),
+            None => (authority.to_string(), 80),
+        };
+
+        Ok(Self {
+            host,
+            port,
+            path: if path.ends_with('/') {
+                path
+            } else {
+                format!("{}/", path)
+            },
+            authorization: credentials.map(|(username, password)| {
+                format!(
+                    "Basic {}",
+                    BASE64.encode(format!("{}:{}", username, password).as_bytes())
+                )
+            }),
+        })
+    }
+
+    fn request(&self, method: &str, name: &str, body: &[u8]) -> Result<(u16, Vec<u8>), SyncError> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))?;
+
+        let mut header = format!(
+            "{} {}{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            self.path,
+            name,
+            self.host,
+            body.len()
+        );
+        if let Some(authorization) = &self.authorization {
+            header.push_str(&format!("Authorization: {}\r\n", authorization));
+        }
+        header.push_str("\r\n");
+
+        stream.write_all(header.as_bytes())?;
+        stream.write_all(body)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+
+        parse_response(&response).ok_or_else(|| SyncError::Http {
+            status: 0,
+            url: self.url(name),
+        })
+    }
+
+    fn url(&self, name: &str) -> String {
+        format!("http://{}:{}{}{}", self.host, self.port, self.path, name)
+    }
+}
+
+impl SyncBackend for WebDavBackend {
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, SyncError> {
+        match self.request("GET", name, &[])? {
+            (200, body) => Ok(Some(body)),
+            (404, _) => Ok(None),
+            (status, _) => Err(SyncError::Http {
+                status,
+                url: self.url(name),
+            }),
+        }
+    }
+
+    fn put(&self, name: &str, contents: &[u8]) -> Result<(), SyncError> {
+        match self.request("PUT", name, contents)? {
+            (200..=299, _) => Ok(()),
+            (status, _) => Err(SyncError::Http {
+                status,
+                url: self.url(name),
+            }),
+        }
+    }
+}
+
+/// Splits a response into its status and body, undoing chunked encoding if the server used it
+fn parse_response(response: &[u8]) -> Option<(u16, Vec<u8>)> {
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")?;
+    let header = std::str::from_utf8(&response[..header_end]).ok()?;
+    let body = &response[header_end + 4..];
+
+    let mut lines = header.split("\r\n");
+    let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("transfer-encoding")
+                && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+
+    if !chunked {
+        return Some((status, body.to_vec()));
+    }
+
+    let mut decoded = Vec::new();
+    let mut remaining = body;
+
+    loop {
+        let line_end = remaining.windows(2).position(|window| window == b"\r\n")?;
+        let size_line = std::str::from_utf8(&remaining[..line_end]).ok()?;
+        let size = usize::from_str_radix(size_line.split(';').next()?.trim(), 16).ok()?;
+        remaining = &remaining[line_end + 2..];
+
+        if size == 0 {
+            return Some((status, decoded));
+        }
+
+        decoded.extend_from_slice(remaining.get(..size)?);
+        remaining = remaining.get(size + 2..)?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunked_response() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+
+        assert_eq!(parse_response(response), Some((200, b"abcde".to_vec())));
+    }
+}
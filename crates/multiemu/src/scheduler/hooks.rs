@@ -0,0 +1,111 @@
+use crate::machine::component_store::ComponentStore;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Where in a frame a hook runs, see [super::Scheduler::add_frame_hook]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FramePhase {
+    /// Before any component runs for the frame
+    Begin,
+    /// After every component has run for the frame
+    End,
+}
+
+/// Handed back on registration so the hook can be taken out again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FrameHookId(u64);
+
+/// What a hook gets to look at, anything else it needs it should hold onto itself
+pub struct FrameHookContext<'a> {
+    pub phase: FramePhase,
+    pub components: &'a ComponentStore,
+    /// Where the scheduler is in its schedule, the same for every hook in a phase
+    pub current_tick: u64,
+}
+
+pub type FrameHook = Arc<dyn Fn(&FrameHookContext) + Send + Sync>;
+
+/// Callbacks for things like cheats, scripting, movies and achievements that need to see the machine between
+/// component runs, never in the middle of one
+///
+/// Hooks run by their order, lowest first, and then by when they were added, so the same set of hooks always runs the
+/// same way
+#[derive(Clone, Default)]
+pub struct FrameHooks {
+    hooks: BTreeMap<(FramePhase, i32, FrameHookId), FrameHook>,
+    next_id: u64,
+}
+
+impl FrameHooks {
+    pub fn add(&mut self, phase: FramePhase, order: i32, hook: FrameHook) -> FrameHookId {
+        let id = FrameHookId(self.next_id);
+        self.next_id += 1;
+        self.hooks.insert((phase, order, id), hook);
+
+        id
+    }
+
+    /// Returns if the hook was there to remove
+    pub fn remove(&mut self, id: FrameHookId) -> bool {
+        let before = self.hooks.len();
+        self.hooks.retain(|(_, _, hook_id), _| *hook_id != id);
+
+        self.hooks.len() != before
+    }
+
+    pub(super) fn run(&self, phase: FramePhase, components: &ComponentStore, current_tick: u64) {
+        let context = FrameHookContext {
+            phase,
+            components,
+            current_tick,
+        };
+
+        for ((hook_phase, _, _), hook) in &self.hooks {
+            if *hook_phase == phase {
+                hook(&context);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+    use std::sync::Mutex;
+
+    #[test]
+    fn hooks_run_in_order() {
+        let mut machine = TestMachineBuilder::new()
+            .bus(0, 16)
+            .scratch_ram(0, 0..0x10, 0)
+            .build();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let hook = |name: &'static str| -> FrameHook {
+            let calls = calls.clone();
+            Arc::new(move |context: &FrameHookContext| {
+                calls.lock().unwrap().push((context.phase, name))
+            })
+        };
+
+        let scheduler = &mut machine.machine.scheduler;
+        scheduler.add_frame_hook(FramePhase::End, 0, hook("movie"));
+        scheduler.add_frame_hook(FramePhase::Begin, 10, hook("achievements"));
+        scheduler.add_frame_hook(FramePhase::Begin, -5, hook("cheats"));
+        let removed = scheduler.add_frame_hook(FramePhase::Begin, 0, hook("script"));
+        scheduler.add_frame_hook(FramePhase::Begin, 10, hook("late achievements"));
+        assert!(scheduler.remove_frame_hook(removed));
+        assert!(!scheduler.remove_frame_hook(removed));
+
+        machine.machine.run_frame();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                (FramePhase::Begin, "cheats"),
+                (FramePhase::Begin, "achievements"),
+                (FramePhase::Begin, "late achievements"),
+                (FramePhase::End, "movie"),
+            ]
+        );
+    }
+}
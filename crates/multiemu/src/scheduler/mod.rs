@@ -1,14 +1,30 @@
 use crate::component::ComponentId;
 use crate::machine::component_store::ComponentStore;
+use hooks::{FrameHook, FrameHookId, FrameHooks, FramePhase};
 use itertools::Itertools;
 use num::ToPrimitive;
 use num::{integer::lcm, rational::Ratio, Integer};
 use rangemap::RangeMap;
 use serde::{Deserialize, Serialize};
 use std::{
+    cell::Cell,
     collections::HashMap,
+    sync::Arc,
     time::{Duration, Instant},
 };
+use watchdog::Heartbeat;
+
+pub mod hooks;
+pub mod watchdog;
+
+thread_local! {
+    static RUNNING_COMPONENT: Cell<Option<ComponentId>> = const { Cell::new(None) };
+}
+
+/// The component the scheduler is running on this thread right now, if any
+pub fn running_component() -> Option<ComponentId> {
+    RUNNING_COMPONENT.get()
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Scheduler {
@@ -18,6 +34,20 @@ pub struct Scheduler {
     // Stores precomputed periods for each component
     schedule: RangeMap<u64, Vec<ComponentId>>,
     allotted_time: Duration,
+    /// Host time each component took during the last run, only filled while profiling
+    #[serde(skip)]
+    component_time: HashMap<ComponentId, Duration>,
+    #[serde(skip)]
+    profiling: bool,
+    /// Ticks [Self::run_for] owes or has overrun, so fractional frame lengths even out
+    #[serde(skip)]
+    tick_debt: f64,
+    /// Beaten around every component run so a watchdog can tell when one gets stuck
+    #[serde(skip)]
+    heartbeat: Option<Arc<Heartbeat>>,
+    /// Run around every frame, see [Self::add_frame_hook]
+    #[serde(skip)]
+    frame_hooks: FrameHooks,
 }
 
 impl Scheduler {
@@ -144,47 +174,175 @@ impl Scheduler {
             tick_real_time,
             schedule,
             allotted_time: Duration::from_millis(16),
+            component_time: HashMap::default(),
+            profiling: false,
+            tick_debt: 0.0,
+            heartbeat: None,
+            frame_hooks: FrameHooks::default(),
         }
     }
 
-    pub fn run(&mut self, components: &ComponentStore) {
+    /// Runs components for roughly a frame, returning how many ticks passed
+    pub fn run(&mut self, components: &ComponentStore) -> u64 {
         // TODO: This should actually be calculating how much time is between frames minus draw time
         let starting_tick = self.current_tick;
+        let mut ticks_passed: u64 = 0;
         let timestamp = Instant::now();
+        self.component_time.clear();
+        self.frame_hooks
+            .run(FramePhase::Begin, components, self.current_tick);
 
         // Ensure we don't overstep the framerate
-        while self.allotted_time > timestamp.elapsed()
+        while !self.stalled() && self.allotted_time > timestamp.elapsed()
             // ensure we don't overstate the emulated timespace
             && (self.current_tick.wrapping_sub(starting_tick) as f32
                 * self.tick_real_time.to_f32().unwrap())
                 <  self.allotted_time.as_secs_f32()
         {
-            if let Some((time_slice, component_ids)) =
-                self.schedule.get_key_value(&self.current_tick)
-            {
-                // TODO: Run this through rayon once we can stop vulkan related concurrency issues
-                for component_id in component_ids {
-                    if let Some(component_info) = components
-                        .get(*component_id)
-                        .and_then(|table| table.as_schedulable.as_ref())
-                    {
-                        component_info
-                            .component
-                            .run(time_slice.clone().count() as u64);
-                    } else {
-                        panic!("Schedule referencing non existant component");
+            ticks_passed += self.step(components);
+        }
+
+        self.frame_hooks
+            .run(FramePhase::End, components, self.current_tick);
+
+        ticks_passed
+    }
+
+    /// Runs components for exactly this much emulated time, no matter how long the host takes to do it
+    ///
+    /// Any fraction of a tick left over is carried into the next call, so frame lengths that aren't a whole number of
+    /// ticks still add up over time
+    pub fn run_for(&mut self, components: &ComponentStore, duration: Duration) -> u64 {
+        let mut ticks_passed: u64 = 0;
+        self.component_time.clear();
+        self.tick_debt += duration.as_secs_f64() / self.tick_real_time.to_f64().unwrap();
+        self.frame_hooks
+            .run(FramePhase::Begin, components, self.current_tick);
+
+        while !self.stalled() && (ticks_passed as f64) < self.tick_debt {
+            ticks_passed += self.step(components);
+        }
+
+        self.tick_debt -= ticks_passed as f64;
+        self.frame_hooks
+            .run(FramePhase::End, components, self.current_tick);
+
+        ticks_passed
+    }
+
+    /// Runs at least this many ticks, ignoring real time entirely, returning how many actually passed
+    ///
+    /// A component's time slice is never split, so this can overshoot by up to one slice
+    pub fn run_ticks(&mut self, components: &ComponentStore, ticks: u64) -> u64 {
+        let mut ticks_passed: u64 = 0;
+        self.component_time.clear();
+
+        while !self.stalled() && ticks_passed < ticks {
+            ticks_passed += self.step(components);
+        }
+
+        ticks_passed
+    }
+
+    /// Runs whatever is scheduled at the current tick, returning how many ticks that covered
+    fn step(&mut self, components: &ComponentStore) -> u64 {
+        let ticks = if let Some((time_slice, component_ids)) =
+            self.schedule.get_key_value(&self.current_tick)
+        {
+            // TODO: Run this through rayon once we can stop vulkan related concurrency issues
+            for component_id in component_ids {
+                if let Some(component_info) = components
+                    .get(*component_id)
+                    .and_then(|table| table.as_schedulable.as_ref())
+                {
+                    let component_start = self.profiling.then(Instant::now);
+
+                    RUNNING_COMPONENT.set(Some(*component_id));
+                    if let Some(heartbeat) = &self.heartbeat {
+                        heartbeat.enter(*component_id);
                     }
-                }
+                    component_info
+                        .component
+                        .run(time_slice.clone().count() as u64);
+                    if let Some(heartbeat) = &self.heartbeat {
+                        heartbeat.exit();
+                    }
+                    RUNNING_COMPONENT.set(None);
 
-                self.current_tick = self
-                    .current_tick
-                    .saturating_add(time_slice.clone().count() as u64);
-            } else {
-                self.current_tick = self.current_tick.saturating_add(1);
+                    if let Some(component_start) = component_start {
+                        *self.component_time.entry(*component_id).or_default() +=
+                            component_start.elapsed();
+                    }
+                } else {
+                    panic!("Schedule referencing non existant component");
+                }
             }
 
-            self.current_tick %= self.rollover_tick;
-        }
+            time_slice.clone().count() as u64
+        } else {
+            1
+        };
+
+        self.current_tick = self.current_tick.saturating_add(ticks) % self.rollover_tick;
+
+        ticks
+    }
+
+    /// Reports to a [watchdog::Watchdog], which stops the scheduler if a component takes too long
+    pub fn set_heartbeat(&mut self, heartbeat: Option<Arc<Heartbeat>>) {
+        self.heartbeat = heartbeat;
+    }
+
+    pub fn heartbeat(&self) -> Option<Arc<Heartbeat>> {
+        self.heartbeat.clone()
+    }
+
+    /// Registers a hook that runs at the start or end of every frame [Self::run] and [Self::run_for] make
+    ///
+    /// Hooks run on the scheduler's thread between component runs, lowest `order` first with ties going to whichever
+    /// was added first. [Self::run_ticks] isn't a frame so doesn't run them
+    pub fn add_frame_hook(
+        &mut self,
+        phase: FramePhase,
+        order: i32,
+        hook: FrameHook,
+    ) -> FrameHookId {
+        self.frame_hooks.add(phase, order, hook)
+    }
+
+    /// Returns if the hook was registered
+    pub fn remove_frame_hook(&mut self, id: FrameHookId) -> bool {
+        self.frame_hooks.remove(id)
+    }
+
+    /// Every registered hook, for carrying them over to a scheduler restored from a save state
+    pub fn frame_hooks(&self) -> FrameHooks {
+        self.frame_hooks.clone()
+    }
+
+    pub fn set_frame_hooks(&mut self, frame_hooks: FrameHooks) {
+        self.frame_hooks = frame_hooks;
+    }
+
+    fn stalled(&self) -> bool {
+        self.heartbeat
+            .as_ref()
+            .is_some_and(|heartbeat| heartbeat.tripped())
+    }
+
+    /// Time components every run, which costs a little so it is off unless something is showing it
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.profiling = profiling;
+    }
+
+    /// Host time each component spent running during the last frame, empty unless profiling
+    pub fn component_time(&self) -> &HashMap<ComponentId, Duration> {
+        &self.component_time
+    }
+
+    /// How much real time a single tick represents, in seconds
+    pub fn tick_real_time(&self) -> Ratio<u64> {
+        self.tick_real_time
     }
 
     pub fn too_slow(&mut self) {
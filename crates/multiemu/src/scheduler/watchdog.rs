@@ -0,0 +1,252 @@
+use crate::{
+    component::ComponentId, logging::trace_event, machine::component_store::ComponentStore,
+};
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        mpsc::channel,
+        Arc, Mutex, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How much of a component's state goes into the report, whole memories are not useful in a dialog
+const STATE_PREVIEW_LENGTH: usize = 512;
+
+/// What the scheduler updates every time it hands a component control, read by the watchdog thread
+#[derive(Debug, Default)]
+pub struct Heartbeat {
+    beats: AtomicU64,
+    /// Component id plus one, 0 while the scheduler is between components
+    running: AtomicU32,
+    tripped: AtomicBool,
+}
+
+impl Heartbeat {
+    pub fn enter(&self, component_id: ComponentId) {
+        self.running
+            .store(component_id.0 as u32 + 1, Ordering::Relaxed);
+        self.beats.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn exit(&self) {
+        self.running.store(0, Ordering::Relaxed);
+        self.beats.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Set once a component blew its budget, the scheduler stops running anything until it is cleared
+    pub fn tripped(&self) -> bool {
+        self.tripped.load(Ordering::Relaxed)
+    }
+}
+
+/// Which component got stuck, and what it looked like when it did
+#[derive(Debug, Clone)]
+pub struct WatchdogReport {
+    pub component_id: ComponentId,
+    pub name: &'static str,
+    pub stalled_for: Duration,
+    /// None if the component could not be asked, usually because it holds its own lock while running
+    pub last_state: Option<rmpv::Value>,
+}
+
+impl Display for WatchdogReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} ({:?}) ran for {:.1}s without returning",
+            self.name,
+            self.component_id,
+            self.stalled_for.as_secs_f64()
+        )?;
+
+        match &self.last_state {
+            Some(state) => {
+                let state = state.to_string();
+
+                if state.chars().count() > STATE_PREVIEW_LENGTH {
+                    let preview: String = state.chars().take(STATE_PREVIEW_LENGTH).collect();
+                    write!(f, "Last state: {}...", preview)
+                } else {
+                    write!(f, "Last state: {}", state)
+                }
+            }
+            None => write!(f, "Its state could not be read while it is stuck"),
+        }
+    }
+}
+
+/// Notices a component that has been running far longer than any time slice should take, like a core stuck in an
+/// infinite loop, and stops the machine instead of letting the whole program look frozen
+///
+/// Checked from its own thread. A component that eventually returns gets the machine paused and the report handed to
+/// the frontend, one that never does at least gets the report logged, since nothing can take control back from it
+#[derive(Debug)]
+pub struct Watchdog {
+    heartbeat: Arc<Heartbeat>,
+    report: Arc<Mutex<Option<WatchdogReport>>>,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Watchdog {
+    pub fn new(components: Weak<ComponentStore>, budget: Duration) -> Self {
+        let heartbeat = Arc::new(Heartbeat::default());
+        let report = Arc::new(Mutex::new(None));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let heartbeat = heartbeat.clone();
+            let report = report.clone();
+            let stop = stop.clone();
+
+            thread::Builder::new()
+                .name("watchdog".to_string())
+                .spawn(move || monitor(components, budget, heartbeat, report, stop))
+                .expect("Could not start watchdog")
+        };
+
+        Self {
+            heartbeat,
+            report,
+            stop,
+            thread,
+        }
+    }
+
+    pub fn heartbeat(&self) -> Arc<Heartbeat> {
+        self.heartbeat.clone()
+    }
+
+    pub fn tripped(&self) -> bool {
+        self.heartbeat.tripped()
+    }
+
+    /// The report of the last stall, once, for the frontend to show
+    pub fn take_report(&self) -> Option<WatchdogReport> {
+        self.report.lock().unwrap().take()
+    }
+
+    /// Lets the machine run again, for users who would rather wait it out
+    pub fn resume(&self) {
+        self.heartbeat.tripped.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.thread().unpark();
+    }
+}
+
+fn monitor(
+    components: Weak<ComponentStore>,
+    budget: Duration,
+    heartbeat: Arc<Heartbeat>,
+    report: Arc<Mutex<Option<WatchdogReport>>>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut last_beat = (0, 0);
+    let mut last_change = Instant::now();
+
+    loop {
+        thread::park_timeout(budget / 4);
+
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let beat = (
+            heartbeat.beats.load(Ordering::Relaxed),
+            heartbeat.running.load(Ordering::Relaxed),
+        );
+
+        if beat != last_beat {
+            last_beat = beat;
+            last_change = Instant::now();
+            continue;
+        }
+
+        let stalled_for = last_change.elapsed();
+        if beat.1 == 0 || stalled_for < budget || heartbeat.tripped() {
+            continue;
+        }
+
+        let Some(components) = components.upgrade() else {
+            return;
+        };
+        let component_id = ComponentId((beat.1 - 1) as u16);
+        let Some(table) = components.get(component_id) else {
+            continue;
+        };
+
+        heartbeat.tripped.store(true, Ordering::Relaxed);
+
+        // Asked from another thread, since a component stuck holding its own lock would hang this one too
+        let component = table.component.clone();
+        let (sender, receiver) = channel();
+        thread::spawn(move || {
+            let _ = sender.send(component.save_snapshot());
+        });
+
+        let stall = WatchdogReport {
+            component_id,
+            name: table.name,
+            stalled_for,
+            last_state: receiver
+                .recv_timeout(budget.min(Duration::from_secs(1)))
+                .ok(),
+        };
+
+        tracing::error!("Watchdog stopped the machine: {}", stall);
+        trace_event(format_args!(
+            "Watchdog stopped {} ({:?})",
+            stall.name, stall.component_id
+        ));
+        *report.lock().unwrap() = Some(stall);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::machine::test_machine::TestMachineBuilder;
+
+    #[test]
+    fn stalled_component_trips() {
+        let machine = TestMachineBuilder::new()
+            .bus(0, 16)
+            .scratch_ram(0, 0..0x10, 0)
+            .build();
+        let watchdog = Watchdog::new(
+            Arc::downgrade(&machine.machine.component_store),
+            Duration::from_millis(20),
+        );
+        let heartbeat = watchdog.heartbeat();
+
+        // Busy but making progress
+        for _ in 0..10 {
+            heartbeat.enter(ComponentId(0));
+            heartbeat.exit();
+            thread::sleep(Duration::from_millis(5));
+        }
+        // Idle between frames isn't a stall either
+        thread::sleep(Duration::from_millis(60));
+        assert!(!watchdog.tripped());
+
+        heartbeat.enter(ComponentId(0));
+        thread::sleep(Duration::from_millis(200));
+        assert!(watchdog.tripped());
+
+        let report = watchdog.take_report().unwrap();
+        assert_eq!(report.component_id, ComponentId(0));
+        assert!(report.stalled_for >= Duration::from_millis(20));
+        assert!(report.last_state.is_some());
+
+        watchdog.resume();
+        assert!(!watchdog.tripped());
+    }
+}
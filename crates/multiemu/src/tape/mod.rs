@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Arc, time::Duration};
+use thiserror::Error;
+
+pub mod t64;
+pub mod tap;
+pub mod tzx;
+
+#[derive(Error, Debug)]
+pub enum TapeError {
+    #[error("Could not read file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("File is not a tape image format we know of")]
+    Unrecognized,
+    #[error("File claims to be a {0} but it is truncated or corrupt")]
+    Corrupt(&'static str),
+    #[error("TZX block {0:#04x} is not supported")]
+    UnsupportedBlock(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeFormat {
+    /// Pulse lengths sampled off a real datasette
+    C64Tap,
+    /// The files of a tape, with no timing at all
+    T64,
+    /// Bare blocks the Spectrum ROM saves, with only their length in front
+    SpectrumTap,
+    Tzx,
+}
+
+impl TapeFormat {
+    /// Works out the format from its magic, falling back to the extension for formats that have none
+    pub fn detect(image: &[u8], extension: Option<&str>) -> Option<Self> {
+        if image.starts_with(tap::C64_TAP_MAGIC) {
+            return Some(TapeFormat::C64Tap);
+        }
+
+        if image.starts_with(tzx::TZX_MAGIC) {
+            return Some(TapeFormat::Tzx);
+        }
+
+        if t64::T64_MAGICS.iter().any(|magic| image.starts_with(magic)) {
+            return Some(TapeFormat::T64);
+        }
+
+        match extension {
+            Some("t64") => Some(TapeFormat::T64),
+            Some("tap") => Some(TapeFormat::SpectrumTap),
+            _ => None,
+        }
+    }
+}
+
+/// A file the image names outright, so it can be put in memory without playing the tape
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapeFile {
+    pub name: String,
+    /// Where the file asks to be loaded
+    pub start_address: u16,
+    pub data: Vec<u8>,
+}
+
+/// A tape as the read head sees it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tape {
+    /// Nanoseconds between each flip of the read line, starting low
+    pub pulses: Vec<u64>,
+    /// Empty if the image only has pulses, which would need decoding the way the machine does
+    pub files: Vec<TapeFile>,
+}
+
+impl Tape {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TapeError> {
+        let path = path.as_ref();
+        let contents = std::fs::read(path)?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        Self::from_bytes(&contents, extension.as_deref())
+    }
+
+    pub fn from_bytes(image: &[u8], extension: Option<&str>) -> Result<Self, TapeError> {
+        match TapeFormat::detect(image, extension).ok_or(TapeError::Unrecognized)? {
+            TapeFormat::C64Tap => tap::parse_c64_tap(image),
+            TapeFormat::T64 => t64::parse_t64(image),
+            TapeFormat::SpectrumTap => tap::parse_spectrum_tap(image),
+            TapeFormat::Tzx => tzx::parse_tzx(image),
+        }
+    }
+
+    pub fn length(&self) -> Duration {
+        Duration::from_nanos(self.pulses.iter().sum())
+    }
+}
+
+/// Where the tape is and what the buttons are doing, which is all a savestate needs since the tape is a ROM
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TapeDeckState {
+    /// Pulse under the read head
+    pub pulse: usize,
+    /// Nanoseconds into that pulse
+    pub into_pulse: u64,
+    /// Level of the read line
+    pub level: bool,
+    /// Play is held down
+    pub playing: bool,
+    /// The machine has the motor running, the tape only moves with both
+    pub motor: bool,
+}
+
+/// A tape transport, with the tape in it if there is one
+#[derive(Debug, Default)]
+pub struct TapeDeck {
+    tape: Option<Arc<Tape>>,
+    pub state: TapeDeckState,
+}
+
+impl TapeDeck {
+    pub fn new(tape: Option<Tape>) -> Self {
+        Self {
+            tape: tape.map(Arc::new),
+            state: TapeDeckState::default(),
+        }
+    }
+
+    pub fn tape(&self) -> Option<&Tape> {
+        self.tape.as_deref()
+    }
+
+    pub fn insert(&mut self, tape: Tape) {
+        self.tape = Some(Arc::new(tape));
+        self.state.playing = false;
+        self.rewind();
+    }
+
+    pub fn eject(&mut self) -> Option<Arc<Tape>> {
+        self.state.playing = false;
+        self.tape.take()
+    }
+
+    /// Holds down play, which does nothing without a tape in
+    pub fn play(&mut self) {
+        self.state.playing = self.tape.is_some();
+    }
+
+    pub fn stop(&mut self) {
+        self.state.playing = false;
+    }
+
+    pub fn rewind(&mut self) {
+        self.state.pulse = 0;
+        self.state.into_pulse = 0;
+        self.state.level = false;
+    }
+
+    pub fn set_motor(&mut self, motor: bool) {
+        self.state.motor = motor;
+    }
+
+    pub fn running(&self) -> bool {
+        self.state.playing && self.state.motor
+    }
+
+    /// How far into the tape the read head is
+    pub fn position(&self) -> Duration {
+        let Some(tape) = &self.tape else {
+            return Duration::ZERO;
+        };
+
+        Duration::from_nanos(
+            tape.pulses[..self.state.pulse.min(tape.pulses.len())]
+                .iter()
+                .sum::<u64>()
+                + self.state.into_pulse,
+        )
+    }
+
+    /// Moves the tape on if it is running, returning how many times the read line fell
+    pub fn advance(&mut self, mut nanoseconds: u64) -> u32 {
+        if !self.running() {
+            return 0;
+        }
+
+        let Some(tape) = &self.tape else {
+            return 0;
+        };
+
+        let mut falling_edges = 0;
+        while let Some(&length) = tape.pulses.get(self.state.pulse) {
+            let remaining = length.saturating_sub(self.state.into_pulse);
+
+            if nanoseconds < remaining {
+                self.state.into_pulse += nanoseconds;
+                return falling_edges;
+            }
+
+            nanoseconds -= remaining;
+            self.state.into_pulse = 0;
+            self.state.pulse += 1;
+            if self.state.level {
+                falling_edges += 1;
+            }
+            self.state.level = !self.state.level;
+        }
+
+        // The end of the tape pops play back up, like a real deck would
+        self.state.playing = false;
+        falling_edges
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deck_counts_falling_edges() {
+        let mut deck = TapeDeck::new(Some(Tape {
+            pulses: vec![100, 100, 100, 100, 100],
+            files: Vec::new(),
+        }));
+
+        assert_eq!(deck.advance(1000), 0, "Should not move without play");
+        deck.play();
+        assert_eq!(deck.advance(1000), 0, "Should not move without the motor");
+
+        deck.set_motor(true);
+        assert_eq!(deck.advance(150), 0);
+        assert_eq!(deck.advance(100), 1);
+        assert_eq!(deck.position(), Duration::from_nanos(250));
+
+        assert_eq!(deck.advance(1000), 1);
+        assert!(!deck.state.playing, "Should stop at the end of the tape");
+
+        deck.rewind();
+        assert_eq!(deck.position(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,54 @@
+use super::{Tape, TapeError, TapeFile};
+
+/// The signature is free text, these are what the tools that write them put there
+pub const T64_MAGICS: &[&[u8]] = &[
+    b"C64 tape image file",
+    b"C64S tape image file",
+    b"C64S tape file",
+];
+const T64_HEADER_SIZE: usize = 0x40;
+const T64_ENTRY_SIZE: usize = 0x20;
+
+/// Reads the directory of a .t64, which is only files and has nothing to play
+pub fn parse_t64(image: &[u8]) -> Result<Tape, TapeError> {
+    if image.len() < T64_HEADER_SIZE {
+        return Err(TapeError::Corrupt("T64 image"));
+    }
+
+    let read_u16 = |offset: usize| u16::from_le_bytes([image[offset], image[offset + 1]]);
+    // Some tools leave the entry count at 0 when there is only one
+    let max_entries = (read_u16(0x22) as usize).max(1);
+
+    let mut files = Vec::new();
+    for entry in image[T64_HEADER_SIZE..]
+        .chunks_exact(T64_ENTRY_SIZE)
+        .take(max_entries)
+    {
+        // Free slot
+        if entry[0] == 0 {
+            continue;
+        }
+
+        let start_address = u16::from_le_bytes([entry[2], entry[3]]);
+        let end_address = u16::from_le_bytes([entry[4], entry[5]]);
+        let offset = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize;
+        // Lots of images have a bogus end address, so the file can't go past the end of the image either
+        let length = end_address.wrapping_sub(start_address) as usize;
+        let data = image
+            .get(offset..image.len().min(offset + length))
+            .ok_or(TapeError::Corrupt("T64 image"))?;
+
+        files.push(TapeFile {
+            name: String::from_utf8_lossy(&entry[0x10..0x20])
+                .trim_end()
+                .to_string(),
+            start_address,
+            data: data.to_vec(),
+        });
+    }
+
+    Ok(Tape {
+        pulses: Vec::new(),
+        files,
+    })
+}
@@ -0,0 +1,245 @@
+use super::{Tape, TapeError, TapeFile};
+
+pub const C64_TAP_MAGIC: &[u8] = b"C64-TAPE-RAW";
+const C64_TAP_HEADER_SIZE: usize = 20;
+/// The PAL clock, which the pulse lengths in .tap files count in
+const C64_TAP_FREQUENCY: u64 = 985_248;
+
+/// Pulse lengths the Spectrum ROM saves with, in T-states of its 3.5MHz clock
+const SPECTRUM_PILOT: u16 = 2168;
+const SPECTRUM_SYNC_FIRST: u16 = 667;
+const SPECTRUM_SYNC_SECOND: u16 = 735;
+const SPECTRUM_ZERO: u16 = 855;
+const SPECTRUM_ONE: u16 = 1710;
+/// Headers get a longer pilot tone so there is time to show the file name
+const SPECTRUM_HEADER_PILOT_PULSES: u16 = 8063;
+const SPECTRUM_DATA_PILOT_PULSES: u16 = 3223;
+/// Silence after each block of a .tap, which has no way to say otherwise
+const SPECTRUM_TAP_PAUSE: u16 = 1000;
+const SPECTRUM_HEADER_SIZE: usize = 19;
+
+/// How a block is laid down on a Spectrum tape, in T-states
+#[derive(Debug, Clone, Copy)]
+pub(super) struct SpectrumTimings {
+    pub pilot: u16,
+    pub pilot_pulses: u16,
+    pub sync_first: u16,
+    pub sync_second: u16,
+    pub zero: u16,
+    pub one: u16,
+    /// Bits of the last byte that are actually there
+    pub used_bits: u8,
+}
+
+impl SpectrumTimings {
+    /// What the ROM uses, which depends on whether the flag byte says it's a header
+    pub fn standard(block: &[u8]) -> Self {
+        Self {
+            pilot: SPECTRUM_PILOT,
+            pilot_pulses: if block.first().is_some_and(|flag| *flag < 0x80) {
+                SPECTRUM_HEADER_PILOT_PULSES
+            } else {
+                SPECTRUM_DATA_PILOT_PULSES
+            },
+            sync_first: SPECTRUM_SYNC_FIRST,
+            sync_second: SPECTRUM_SYNC_SECOND,
+            zero: SPECTRUM_ZERO,
+            one: SPECTRUM_ONE,
+            used_bits: 8,
+        }
+    }
+}
+
+pub(super) fn t_states(t_states: u16) -> u64 {
+    t_states as u64 * 2000 / 7
+}
+
+/// Silence is a single long pulse, nothing reads edges that far apart
+pub(super) fn push_pause(pulses: &mut Vec<u64>, milliseconds: u16) {
+    if milliseconds != 0 {
+        pulses.push(milliseconds as u64 * 1_000_000);
+    }
+}
+
+/// Bits go out highest first, each as two pulses of the same length
+pub(super) fn push_spectrum_data(
+    pulses: &mut Vec<u64>,
+    zero: u16,
+    one: u16,
+    used_bits: u8,
+    data: &[u8],
+) {
+    for (index, byte) in data.iter().enumerate() {
+        let bits = if index + 1 == data.len() {
+            used_bits.clamp(1, 8)
+        } else {
+            8
+        };
+
+        for bit in 0..bits {
+            let length = if byte & (0x80 >> bit) != 0 { one } else { zero };
+            pulses.extend([t_states(length); 2]);
+        }
+    }
+}
+
+pub(super) fn push_spectrum_block(pulses: &mut Vec<u64>, timings: SpectrumTimings, data: &[u8]) {
+    pulses.extend(std::iter::repeat_n(
+        t_states(timings.pilot),
+        timings.pilot_pulses as usize,
+    ));
+    pulses.extend([t_states(timings.sync_first), t_states(timings.sync_second)]);
+    push_spectrum_data(pulses, timings.zero, timings.one, timings.used_bits, data);
+}
+
+/// Pairs each header block with the data block after it, the way LOAD "" would
+pub(super) fn spectrum_files<'a>(blocks: impl IntoIterator<Item = &'a [u8]>) -> Vec<TapeFile> {
+    let mut files = Vec::new();
+    let mut header: Option<&[u8]> = None;
+
+    for block in blocks {
+        if block.len() == SPECTRUM_HEADER_SIZE && block[0] == 0x00 {
+            header = Some(block);
+            continue;
+        }
+
+        if let Some(header) = header.take().filter(|_| block.len() >= 2) {
+            files.push(TapeFile {
+                name: String::from_utf8_lossy(&header[2..12])
+                    .trim_end()
+                    .to_string(),
+                start_address: u16::from_le_bytes([header[14], header[15]]),
+                // Without the flag and checksum
+                data: block[1..block.len() - 1].to_vec(),
+            });
+        }
+    }
+
+    files
+}
+
+pub fn parse_c64_tap(image: &[u8]) -> Result<Tape, TapeError> {
+    if image.len() < C64_TAP_HEADER_SIZE {
+        return Err(TapeError::Corrupt("C64 .tap image"));
+    }
+
+    let version = image[12];
+    let length = u32::from_le_bytes([image[16], image[17], image[18], image[19]]) as usize;
+    // Plenty of images get the length wrong, what is actually there wins
+    let data = &image[C64_TAP_HEADER_SIZE..image.len().min(C64_TAP_HEADER_SIZE + length)];
+    let nanoseconds = |cycles: u64| cycles * 1_000_000_000 / C64_TAP_FREQUENCY;
+
+    let mut pulses = Vec::with_capacity(data.len() * 2);
+    let mut index = 0;
+    while let Some(&value) = data.get(index) {
+        index += 1;
+
+        let cycles = match value {
+            // Version 0 can only say the pulse was too long to count
+            0 if version == 0 => 256 * 8,
+            0 => {
+                let Some(bytes) = data.get(index..index + 3) else {
+                    break;
+                };
+                index += 3;
+                u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as u64
+            }
+            value => value as u64 * 8,
+        };
+
+        // Version 2 stores each half of the wave on its own, the rest only the whole wave
+        if version == 2 {
+            pulses.push(nanoseconds(cycles));
+        } else {
+            let half = nanoseconds(cycles) / 2;
+            pulses.extend([half, nanoseconds(cycles) - half]);
+        }
+    }
+
+    Ok(Tape {
+        pulses,
+        files: Vec::new(),
+    })
+}
+
+pub fn parse_spectrum_tap(image: &[u8]) -> Result<Tape, TapeError> {
+    let mut blocks = Vec::new();
+    let mut offset = 0;
+
+    while offset < image.len() {
+        let Some(length) = image.get(offset..offset + 2) else {
+            return Err(TapeError::Corrupt("Spectrum .tap image"));
+        };
+        let length = u16::from_le_bytes([length[0], length[1]]) as usize;
+        let block = image
+            .get(offset + 2..offset + 2 + length)
+            .ok_or(TapeError::Corrupt("Spectrum .tap image"))?;
+
+        blocks.push(block);
+        offset += 2 + length;
+    }
+
+    let mut pulses = Vec::new();
+    for block in blocks.iter() {
+        push_spectrum_block(&mut pulses, SpectrumTimings::standard(block), block);
+        push_pause(&mut pulses, SPECTRUM_TAP_PAUSE);
+    }
+
+    Ok(Tape {
+        pulses,
+        files: spectrum_files(blocks),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn c64_tap_pulses() {
+        let mut image = C64_TAP_MAGIC.to_vec();
+        image.extend([1, 0, 0, 0, 5, 0, 0, 0]);
+        // A short pulse, then a long one spelled out in cycles
+        image.extend([0x30, 0x00, 0x00, 0x10, 0x00]);
+
+        let tape = Tape::from_bytes(&image, None).unwrap();
+        assert_eq!(tape.pulses.len(), 4);
+        assert_eq!(
+            tape.pulses[0] + tape.pulses[1],
+            0x30 * 8 * 1_000_000_000 / C64_TAP_FREQUENCY
+        );
+        assert_eq!(
+            tape.pulses[2] + tape.pulses[3],
+            0x1000 * 1_000_000_000 / C64_TAP_FREQUENCY
+        );
+    }
+
+    #[test]
+    fn spectrum_tap_files() {
+        let mut header = vec![0x00, 0x03];
+        header.extend(b"SCREEN    ");
+        header.extend([2, 0, 0x00, 0x40, 0, 0, 0]);
+        let data = [0xff, 0xaa, 0x55, 0x00];
+
+        let mut image = Vec::new();
+        for block in [&header[..], &data[..]] {
+            image.extend((block.len() as u16).to_le_bytes());
+            image.extend(block);
+        }
+
+        let tape = Tape::from_bytes(&image, Some("tap")).unwrap();
+        assert_eq!(
+            tape.files,
+            vec![TapeFile {
+                name: "SCREEN".to_string(),
+                start_address: 0x4000,
+                data: vec![0xaa, 0x55],
+            }]
+        );
+        // Pilot, sync, 16 pulses a byte and a pause for each block
+        assert_eq!(
+            tape.pulses.len(),
+            (8063 + 2 + 19 * 16 + 1) + (3223 + 2 + 4 * 16 + 1)
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use super::{
+    tap::{
+        push_pause, push_spectrum_block, push_spectrum_data, spectrum_files, t_states,
+        SpectrumTimings,
+    },
+    Tape, TapeError,
+};
+
+pub const TZX_MAGIC: &[u8] = b"ZXTape!\x1a";
+/// The magic, then the major and minor version
+const TZX_HEADER_SIZE: usize = 10;
+
+struct TzxReader<'a> {
+    image: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> TzxReader<'a> {
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], TapeError> {
+        let bytes = self
+            .image
+            .get(self.offset..self.offset + length)
+            .ok_or(TapeError::Corrupt("TZX image"))?;
+        self.offset += length;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, TapeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, TapeError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u24(&mut self) -> Result<usize, TapeError> {
+        let bytes = self.bytes(3)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) as usize)
+    }
+
+    fn u32(&mut self) -> Result<usize, TapeError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+}
+
+/// Turns samples of the read line into how long it stayed at each level
+fn push_direct_recording(pulses: &mut Vec<u64>, sample_length: u16, used_bits: u8, data: &[u8]) {
+    let sample = t_states(sample_length);
+    let mut level = false;
+    let mut length = 0;
+
+    for (index, byte) in data.iter().enumerate() {
+        let bits = if index + 1 == data.len() {
+            used_bits.clamp(1, 8)
+        } else {
+            8
+        };
+
+        for bit in 0..bits {
+            let sampled = byte & (0x80 >> bit) != 0;
+            if sampled != level {
+                pulses.push(length);
+                level = sampled;
+                length = 0;
+            }
+            length += sample;
+        }
+    }
+
+    if length != 0 {
+        pulses.push(length);
+    }
+}
+
+/// Plays the blocks of a .tzx into pulses
+///
+/// Blocks that only describe the tape are skipped. Ones that pick between machines or jump around aren't supported,
+/// since the tape is laid out once up front
+pub fn parse_tzx(image: &[u8]) -> Result<Tape, TapeError> {
+    if image.len() < TZX_HEADER_SIZE {
+        return Err(TapeError::Corrupt("TZX image"));
+    }
+
+    let mut reader = TzxReader {
+        image,
+        offset: TZX_HEADER_SIZE,
+    };
+    let mut pulses = Vec::new();
+    let mut blocks = Vec::new();
+    // Where the pulses of the loop being read start, and how many times to play them
+    let mut loop_start: Option<(usize, u16)> = None;
+
+    while reader.offset < image.len() {
+        let id = reader.u8()?;
+
+        match id {
+            // Standard speed data
+            0x10 => {
+                let pause = reader.u16()?;
+                let length = reader.u16()? as usize;
+                let data = reader.bytes(length)?;
+
+                push_spectrum_block(&mut pulses, SpectrumTimings::standard(data), data);
+                push_pause(&mut pulses, pause);
+                blocks.push(data);
+            }
+            // Turbo speed data
+            0x11 => {
+                let pilot = reader.u16()?;
+                let sync_first = reader.u16()?;
+                let sync_second = reader.u16()?;
+                let zero = reader.u16()?;
+                let one = reader.u16()?;
+                let pilot_pulses = reader.u16()?;
+                let used_bits = reader.u8()?;
+                let pause = reader.u16()?;
+                let length = reader.u24()?;
+                let data = reader.bytes(length)?;
+
+                push_spectrum_block(
+                    &mut pulses,
+                    SpectrumTimings {
+                        pilot,
+                        pilot_pulses,
+                        sync_first,
+                        sync_second,
+                        zero,
+                        one,
+                        used_bits,
+                    },
+                    data,
+                );
+                push_pause(&mut pulses, pause);
+                blocks.push(data);
+            }
+            // Pure tone
+            0x12 => {
+                let length = reader.u16()?;
+                let count = reader.u16()?;
+
+                pulses.extend(std::iter::repeat_n(t_states(length), count as usize));
+            }
+            // Pulse sequence
+            0x13 => {
+                let count = reader.u8()?;
+
+                for _ in 0..count {
+                    pulses.push(t_states(reader.u16()?));
+                }
+            }
+            // Pure data
+            0x14 => {
+                let zero = reader.u16()?;
+                let one = reader.u16()?;
+                let used_bits = reader.u8()?;
+                let pause = reader.u16()?;
+                let length = reader.u24()?;
+                let data = reader.bytes(length)?;
+
+                push_spectrum_data(&mut pulses, zero, one, used_bits, data);
+                push_pause(&mut pulses, pause);
+            }
+            // Direct recording
+            0x15 => {
+                let sample_length = reader.u16()?;
+                let pause = reader.u16()?;
+                let used_bits = reader.u8()?;
+                let length = reader.u24()?;
+                let data = reader.bytes(length)?;
+
+                push_direct_recording(&mut pulses, sample_length, used_bits, data);
+                push_pause(&mut pulses, pause);
+            }
+            // Pause, where 0 means stop the tape, which there is no one to press play again for
+            0x20 => {
+                let pause = reader.u16()?;
+                push_pause(&mut pulses, pause);
+            }
+            // Group start
+            0x21 => {
+                let length = reader.u8()? as usize;
+                reader.bytes(length)?;
+            }
+            // Group end
+            0x22 => {}
+            // Loop start
+            0x24 => {
+                loop_start = Some((pulses.len(), reader.u16()?));
+            }
+            // Loop end
+            0x25 => {
+                if let Some((start, repetitions)) = loop_start.take() {
+                    let repeated = pulses[start..].to_vec();
+
+                    for _ in 1..repetitions {
+                        pulses.extend_from_slice(&repeated);
+                    }
+                }
+            }
+            // Stop the tape if in 48K mode, and set signal level, neither of which change what is on the tape
+            0x2a | 0x2b => {
+                let length = reader.u32()?;
+                reader.bytes(length)?;
+            }
+            // Text description
+            0x30 => {
+                let length = reader.u8()? as usize;
+                reader.bytes(length)?;
+            }
+            // Message
+            0x31 => {
+                reader.u8()?;
+                let length = reader.u8()? as usize;
+                reader.bytes(length)?;
+            }
+            // Archive info
+            0x32 => {
+                let length = reader.u16()? as usize;
+                reader.bytes(length)?;
+            }
+            // Hardware type
+            0x33 => {
+                let count = reader.u8()? as usize;
+                reader.bytes(count * 3)?;
+            }
+            // Custom info
+            0x35 => {
+                reader.bytes(0x10)?;
+                let length = reader.u32()?;
+                reader.bytes(length)?;
+            }
+            // Glue, from joining two files together
+            0x5a => {
+                reader.bytes(9)?;
+            }
+            id => return Err(TapeError::UnsupportedBlock(id)),
+        }
+    }
+
+    Ok(Tape {
+        pulses,
+        files: spectrum_files(blocks),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tzx_blocks() {
+        let mut image = TZX_MAGIC.to_vec();
+        image.extend([1, 20]);
+        // A description, then three pulses played twice
+        image.extend([0x30, 3]);
+        image.extend(b"abc");
+        image.extend([0x24, 2, 0]);
+        image.extend([0x13, 3, 0x10, 0, 0x20, 0, 0x30, 0]);
+        image.extend([0x25]);
+        image.extend([0x20, 5, 0]);
+
+        let tape = Tape::from_bytes(&image, None).unwrap();
+        let pulses = [t_states(0x10), t_states(0x20), t_states(0x30)];
+        assert_eq!(tape.pulses[..3], pulses);
+        assert_eq!(tape.pulses[3..6], pulses);
+        assert_eq!(tape.pulses[6..], [5_000_000]);
+
+        image.push(0x19);
+        assert!(matches!(
+            Tape::from_bytes(&image, None),
+            Err(TapeError::UnsupportedBlock(0x19))
+        ));
+    }
+}